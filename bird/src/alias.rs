@@ -0,0 +1,122 @@
+//! Word-pair aliases for invocations (`brave-otter`).
+//!
+//! An alias spells the last four hex digits of an invocation id (16 random
+//! bits of its UUIDv7) as an adjective and a noun, 256 words each. That is
+//! too few to tell every invocation apart, so the store assigns aliases as
+//! invocations are written and keeps them: the first invocation with a
+//! word pair gets it as is, later ones get it with a numeric suffix
+//! (`brave-otter-2`). Resolving an alias is an exact lookup of the stored
+//! ones (see `Store::find_by_alias`).
+
+use uuid::Uuid;
+
+/// The word pair for invocation `id`: its alias unless another invocation
+/// got the pair first.
+pub fn invocation_alias(id: &Uuid) -> String {
+    let bytes = id.as_bytes();
+    format!("{}-{}", ADJECTIVES[bytes[14] as usize], NOUNS[bytes[15] as usize])
+}
+
+/// Whether `text` is shaped like an alias: a word pair, optionally followed
+/// by a numeric suffix.
+pub fn is_alias(text: &str) -> bool {
+    let text = text.trim().to_ascii_lowercase();
+    let mut parts = text.splitn(3, '-');
+    let (Some(adjective), Some(noun)) = (parts.next(), parts.next()) else {
+        return false;
+    };
+    let suffix_ok = match parts.next() {
+        Some(n) => !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()),
+        None => true,
+    };
+    suffix_ok && ADJECTIVES.contains(&adjective) && NOUNS.contains(&noun)
+}
+
+/// First word, from the higher of the two bytes.
+const ADJECTIVES: [&str; 256] = [
+    "able", "acid", "aged", "airy", "alert", "alive", "amber", "ample", "apt", "arid", "ashen",
+    "avid", "awake", "aware", "balmy", "bare", "basic", "bent", "big", "black", "bland", "blank",
+    "blond", "blue", "blunt", "bold", "bony", "brave", "brief", "bright", "brisk", "broad", "brown",
+    "bumpy", "busy", "calm", "candid", "chief", "chilly", "civil", "clean", "clear", "clever",
+    "close", "cloudy", "coarse", "cold", "cool", "cosy", "coy", "crisp", "curly", "curvy", "cute",
+    "damp", "dark", "dear", "deep", "dense", "dizzy", "dry", "dual", "dusty", "eager", "early",
+    "easy", "elder", "empty", "epic", "equal", "even", "exact", "faint", "fair", "fancy", "far",
+    "fast", "fine", "firm", "first", "fit", "flat", "fluffy", "foggy", "fond", "frank", "free",
+    "fresh", "full", "funny", "fuzzy", "giant", "glad", "gold", "good", "grand", "gray", "great",
+    "green", "gusty", "happy", "hardy", "hasty", "hazy", "heavy", "hidden", "high", "hollow",
+    "holy", "honest", "humble", "hungry", "icy", "ideal", "idle", "inner", "jolly", "juicy", "keen",
+    "kind", "large", "last", "late", "lazy", "lean", "level", "light", "little", "live", "local",
+    "lone", "long", "loose", "loud", "lucky", "lunar", "lush", "magic", "major", "mellow", "merry",
+    "mild", "minor", "misty", "modern", "moist", "mossy", "muddy", "mute", "narrow", "near", "neat",
+    "new", "nice", "nimble", "noble", "noisy", "novel", "odd", "old", "open", "oval", "pale",
+    "plain", "plump", "polar", "polite", "posh", "prime", "proud", "pure", "quick", "quiet",
+    "rapid", "rare", "raw", "ready", "real", "regal", "rich", "rigid", "ripe", "robust", "rosy",
+    "rough", "round", "royal", "rural", "rusty", "safe", "salty", "sandy", "sharp", "shiny",
+    "short", "shy", "silent", "silky", "silly", "simple", "sleek", "slim", "slow", "small", "smart",
+    "smoky", "snowy", "soft", "solar", "solid", "sour", "spare", "spicy", "stable", "steep",
+    "stiff", "still", "stony", "stormy", "strong", "sunny", "super", "sure", "sweet", "swift",
+    "tall", "tame", "tart", "tense", "thick", "thin", "tidy", "tiny", "tough", "true", "twin",
+    "urban", "vague", "valid", "vast", "vivid", "wacky", "warm", "wary", "wet", "white", "whole",
+    "wide", "wild", "windy", "wise", "witty", "woody", "young", "zany", "zesty",
+];
+
+/// Second word, from the lower byte.
+const NOUNS: [&str; 256] = [
+    "acorn", "adder", "agate", "alder", "alpaca", "anchor", "ant", "apple", "apron", "arrow",
+    "aspen", "atlas", "badger", "bagel", "banjo", "barn", "basil", "bass", "bat", "bay", "beach",
+    "beagle", "bean", "bear", "beaver", "bee", "beet", "beetle", "bell", "berry", "birch", "bison",
+    "bloom", "boar", "boat", "bobcat", "bolt", "bongo", "brook", "broom", "bubble", "bucket",
+    "buffalo", "bunny", "buzzard", "cabin", "cactus", "camel", "canoe", "canyon", "caribou", "carp",
+    "cedar", "cello", "chalk", "cheetah", "cherry", "chess", "chipmunk", "cider", "clam", "cliff",
+    "clover", "cobra", "comet", "condor", "coral", "cougar", "crab", "crane", "cricket", "crow",
+    "cub", "cypress", "daisy", "deer", "delta", "dingo", "dolphin", "donkey", "dove", "dragon",
+    "drum", "duck", "dune", "eagle", "eel", "egret", "elk", "elm", "ember", "emu", "falcon", "fern",
+    "ferret", "fig", "finch", "firefly", "fjord", "flame", "flute", "fox", "frog", "gannet",
+    "gazelle", "gecko", "geyser", "gibbon", "ginger", "goat", "goose", "gopher", "gorilla", "grape",
+    "grouse", "gull", "hamster", "hare", "harp", "hawk", "hazel", "hedgehog", "heron", "hippo",
+    "holly", "hornet", "horse", "husky", "hyena", "ibis", "iguana", "impala", "iris", "ivy",
+    "jackal", "jackdaw", "jaguar", "jay", "jelly", "kayak", "kelp", "kestrel", "kettle", "kiwi",
+    "koala", "koi", "ladybug", "lake", "lark", "lemon", "lemur", "lily", "lime", "lizard", "llama",
+    "lobster", "lotus", "lynx", "macaw", "magpie", "manatee", "mango", "maple", "marmot", "marsh",
+    "meadow", "melon", "mink", "mole", "mongoose", "moose", "moth", "mule", "narwhal", "newt",
+    "nutmeg", "oak", "oasis", "ocelot", "octopus", "olive", "onion", "orca", "osprey", "otter",
+    "owl", "oyster", "panda", "panther", "parrot", "peach", "pear", "pebble", "pelican", "pepper",
+    "perch", "pheasant", "pigeon", "piglet", "pine", "plover", "plum", "pony", "poppy", "possum",
+    "puffin", "puma", "quail", "quartz", "quokka", "rabbit", "radish", "raven", "reed", "reef",
+    "rhino", "river", "robin", "rook", "rose", "sage", "salmon", "seal", "shark", "sheep", "shrimp",
+    "skunk", "sloth", "snail", "sparrow", "spruce", "squid", "stork", "swan", "tapir", "teal",
+    "thrush", "tiger", "toad", "trout", "tulip", "tuna", "turkey", "turtle", "viper", "vole",
+    "walnut", "walrus", "wasp", "whale", "willow", "wolf", "wombat", "wren", "yak", "zebra",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_shape() {
+        let id = Uuid::parse_str("0190a1b2-0000-7000-8000-00003f2a9c1e").unwrap();
+        let alias = invocation_alias(&id);
+        assert!(is_alias(&alias));
+        assert!(is_alias(&alias.to_uppercase()));
+        assert!(is_alias(&format!("{}-12", alias)));
+        assert!(!is_alias(&format!("{}-", alias)));
+        assert!(!is_alias(&format!("{}-x2", alias)));
+
+        assert_eq!(invocation_alias(&Uuid::nil()), "able-acorn");
+        assert!(!is_alias("make-test"));
+        assert!(!is_alias("brave"));
+    }
+
+    #[test]
+    fn test_word_lists() {
+        for words in [&ADJECTIVES, &NOUNS] {
+            let mut sorted = words.to_vec();
+            sorted.sort_unstable();
+            sorted.dedup();
+            assert_eq!(sorted.len(), 256);
+            assert!(words.iter().all(|w| w.chars().all(|c| c.is_ascii_lowercase())));
+        }
+        assert!(!ADJECTIVES.iter().any(|w| NOUNS.contains(w)));
+    }
+}
@@ -0,0 +1,180 @@
+//! Consistent pseudonyms for anonymized exports.
+//!
+//! [`Anonymizer`] replaces identifying values (hosts, users, path components,
+//! command arguments) with numbered pseudonyms such as `host1` or `d3`. The
+//! same input always maps to the same pseudonym within one anonymizer, so
+//! joins, repetition and directory structure survive while the values do
+//! not. Numbers are assigned in first-seen order, which makes the output
+//! deterministic for a given input order.
+
+use std::collections::HashMap;
+
+/// Shell operators that separate commands; the next word is an executable.
+const COMMAND_SEPARATORS: &[&str] = &["|", "||", "&&", ";", "&", "|&"];
+
+/// Redirections kept verbatim (their target is anonymized like any argument).
+const REDIRECTIONS: &[&str] = &[">", ">>", "<", "2>", "2>>", "&>", "2>&1", "<<<"];
+
+/// Maps identifying values to stable pseudonyms.
+#[derive(Debug, Default)]
+pub struct Anonymizer {
+    /// Pseudonyms per kind (e.g. "host" -> {"laptop" -> "host1"}).
+    maps: HashMap<&'static str, HashMap<String, String>>,
+}
+
+impl Anonymizer {
+    /// Create an empty anonymizer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pseudonym for `value` within `kind` (`<kind><n>`).
+    pub fn token(&mut self, kind: &'static str, value: &str) -> String {
+        let map = self.maps.entry(kind).or_default();
+        if let Some(existing) = map.get(value) {
+            return existing.clone();
+        }
+        let pseudonym = format!("{}{}", kind, map.len() + 1);
+        map.insert(value.to_string(), pseudonym.clone());
+        pseudonym
+    }
+
+    /// Anonymize a hostname.
+    pub fn host(&mut self, host: &str) -> String {
+        self.token("host", host)
+    }
+
+    /// Anonymize a client ID (`user@hostname`), keeping its shape.
+    pub fn client(&mut self, client_id: &str) -> String {
+        match client_id.split_once('@') {
+            Some((user, host)) => format!("{}@{}", self.token("user", user), self.host(host)),
+            None => self.token("user", client_id),
+        }
+    }
+
+    /// Anonymize a path component by component.
+    ///
+    /// Separators, `~`, `.` and `..` are kept so the tree shape (depth,
+    /// shared prefixes, absolute vs relative) is preserved.
+    pub fn path(&mut self, path: &str) -> String {
+        path.split('/')
+            .map(|component| match component {
+                "" | "~" | "." | ".." => component.to_string(),
+                _ => self.token("d", component),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Anonymize a command line, keeping executables, flags and operators.
+    ///
+    /// Executable names are kept (they are what performance reports are
+    /// about); for a path, only the directories are anonymized. Flag names
+    /// are kept, flag values and positional arguments become pseudonyms.
+    /// Splitting is on single spaces, so spacing is preserved; quoting is
+    /// best-effort.
+    pub fn command(&mut self, cmd: &str) -> String {
+        let mut expect_executable = true;
+        let mut words = Vec::new();
+
+        for word in cmd.split(' ') {
+            if word.is_empty() || COMMAND_SEPARATORS.contains(&word) {
+                if !word.is_empty() {
+                    expect_executable = true;
+                }
+                words.push(word.to_string());
+                continue;
+            }
+            if REDIRECTIONS.contains(&word) {
+                words.push(word.to_string());
+                continue;
+            }
+
+            if expect_executable && !word.contains('=') {
+                expect_executable = false;
+                // Keep the name, which matches the `executable` column
+                words.push(match word.rsplit_once('/') {
+                    Some((dir, name)) => format!("{}/{}", self.path(dir), name),
+                    None => word.to_string(),
+                });
+                continue;
+            }
+
+            words.push(self.argument(word));
+        }
+
+        words.join(" ")
+    }
+
+    /// Anonymize one command argument (flag, `key=value`, path, or word).
+    fn argument(&mut self, word: &str) -> String {
+        // Keep surrounding quotes so the command still reads as quoted
+        let quote = |c: char| c == '"' || c == '\'';
+        let inner = word.trim_matches(quote);
+        if inner.is_empty() {
+            return word.to_string();
+        }
+        let start = word.find(inner).unwrap_or(0);
+        let (prefix, suffix) = (&word[..start], &word[start + inner.len()..]);
+
+        let anonymized = if let Some((name, value)) = inner.split_once('=') {
+            if value.is_empty() {
+                inner.to_string()
+            } else {
+                format!("{}={}", name, self.argument(value))
+            }
+        } else if inner.starts_with('-') {
+            inner.to_string()
+        } else if inner.contains('/') {
+            self.path(inner)
+        } else {
+            self.token("arg", inner)
+        };
+
+        format!("{}{}{}", prefix, anonymized, suffix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_are_consistent() {
+        let mut anon = Anonymizer::new();
+        assert_eq!(anon.host("laptop"), "host1");
+        assert_eq!(anon.host("server"), "host2");
+        assert_eq!(anon.host("laptop"), "host1");
+    }
+
+    #[test]
+    fn test_client_keeps_shape() {
+        let mut anon = Anonymizer::new();
+        assert_eq!(anon.client("alice@laptop"), "user1@host1");
+        assert_eq!(anon.host("laptop"), "host1");
+        assert_eq!(anon.client("bob@laptop"), "user2@host1");
+    }
+
+    #[test]
+    fn test_path_preserves_structure() {
+        let mut anon = Anonymizer::new();
+        assert_eq!(anon.path("/home/alice/src"), "/d1/d2/d3");
+        assert_eq!(anon.path("/home/alice"), "/d1/d2");
+        assert_eq!(anon.path("~/src/../bin"), "~/d3/../d4");
+    }
+
+    #[test]
+    fn test_command_keeps_executables_and_flags() {
+        let mut anon = Anonymizer::new();
+        assert_eq!(
+            anon.command("git commit -m \"secret plan\" --author=alice"),
+            "git arg1 -m \"arg2 arg3\" --author=arg4"
+        );
+        assert_eq!(
+            anon.command("cat /etc/hosts | grep acme > out.txt"),
+            "cat /d1/d2 | grep arg5 > arg6"
+        );
+        assert_eq!(anon.command("/opt/acme/bin/deploy prod"), "/d3/d4/d5/deploy arg7");
+        assert_eq!(anon.command("FOO=bar make"), "FOO=arg8 make");
+    }
+}
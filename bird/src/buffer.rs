@@ -282,7 +282,7 @@ impl Buffer {
         }
 
         // Sort by start time, most recent first
-        entries.sort_by(|a, b| b.meta.started_at.cmp(&a.meta.started_at));
+        entries.sort_by_key(|e| std::cmp::Reverse(e.meta.started_at));
 
         Ok(entries)
     }
@@ -0,0 +1,52 @@
+//! Cancellation of long-running operations.
+//!
+//! Compaction, archiving, push/pull and event backfill check the
+//! [`CancelToken`] set on the store with
+//! [`Store::with_cancel`](crate::Store::with_cancel) between units of work
+//! (a partition, a table, a blob, a batch). Once it is cancelled they stop
+//! at the next such boundary, leaving the store consistent, and return
+//! [`Error::Cancelled`](crate::Error::Cancelled).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A shared flag asking long-running operations to stop.
+///
+/// Clones share the flag, so one clone can be handed to a signal handler or
+/// another thread while the store holds another.
+#[derive(Debug, Default, Clone)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// A token that hasn't been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask operations using this token to stop.
+    ///
+    /// Only stores to an atomic, so it is safe to call from a signal handler.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether [`cancel`](Self::cancel) has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_cancellation() {
+        let token = CancelToken::new();
+        let handle = token.clone();
+        assert!(!token.is_cancelled());
+
+        std::thread::spawn(move || handle.cancel()).join().unwrap();
+        assert!(token.is_cancelled());
+    }
+}
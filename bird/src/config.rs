@@ -5,12 +5,14 @@
 //! 2. BIRD_ROOT environment variable
 //! 3. Default: ~/.local/share/bird
 
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 
+use crate::histfile::HistFormat;
 use crate::{Error, Result};
 
 /// Storage mode for BIRD data.
@@ -65,6 +67,17 @@ pub enum RemoteType {
     Postgres,
     /// Local or network file path
     File,
+    /// Atuin sync server (https://...), push-only
+    #[serde(rename = "atuin")]
+    AtuinServer,
+}
+
+impl RemoteType {
+    /// Whether remotes of this type are DuckDB databases that can be
+    /// ATTACHed and queried.
+    pub fn is_attachable(&self) -> bool {
+        *self != RemoteType::AtuinServer
+    }
 }
 
 impl std::fmt::Display for RemoteType {
@@ -74,6 +87,7 @@ impl std::fmt::Display for RemoteType {
             RemoteType::MotherDuck => write!(f, "motherduck"),
             RemoteType::Postgres => write!(f, "postgres"),
             RemoteType::File => write!(f, "file"),
+            RemoteType::AtuinServer => write!(f, "atuin"),
         }
     }
 }
@@ -87,8 +101,9 @@ impl FromStr for RemoteType {
             "motherduck" | "md" => Ok(RemoteType::MotherDuck),
             "postgres" | "postgresql" | "pg" => Ok(RemoteType::Postgres),
             "file" | "local" => Ok(RemoteType::File),
+            "atuin" | "atuin-server" => Ok(RemoteType::AtuinServer),
             _ => Err(Error::Config(format!(
-                "Invalid remote type '{}': expected 's3', 'motherduck', 'postgres', or 'file'",
+                "Invalid remote type '{}': expected 's3', 'motherduck', 'postgres', 'file', or 'atuin'",
                 s
             ))),
         }
@@ -132,13 +147,106 @@ pub struct RemoteConfig {
     #[serde(default)]
     pub mode: RemoteMode,
 
-    /// Credential provider for S3 (e.g., "credential_chain", "config")
+    /// Credential provider for S3 (e.g., "credential_chain", "config").
+    /// For Atuin remotes, the Atuin data directory holding the `key` and
+    /// `session` files, if not the default.
     #[serde(default)]
     pub credential_provider: Option<String>,
 
     /// Whether to auto-attach on connection open
     #[serde(default = "default_true")]
     pub auto_attach: bool,
+
+    /// Seconds to wait for the ATTACH before skipping this remote
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attach_timeout: Option<u64>,
+
+    /// Sanitization applied to records pushed to this remote
+    #[serde(default, skip_serializing_if = "SanitizePolicy::is_default")]
+    pub sanitize: SanitizePolicy,
+
+    /// Signing of pushed records and verification of pulled ones
+    #[serde(default, skip_serializing_if = "SigningPolicy::is_off")]
+    pub signing: SigningPolicy,
+}
+
+/// Push-time sanitization for a shared remote.
+///
+/// Applied only to the copy that lands on the remote; local storage keeps
+/// the original values. Configured per remote:
+///
+/// ```toml
+/// [remotes.sanitize]
+/// relative_home = true
+/// redact_commands = true
+/// hash_fields = ["hostname"]
+/// push_env = false
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SanitizePolicy {
+    /// Rewrite working directories under $HOME as `~/...`.
+    #[serde(default)]
+    pub relative_home: bool,
+
+    /// Redact secret-looking command arguments (see `privacy::redact_command`),
+    /// even if they were stored verbatim locally.
+    #[serde(default)]
+    pub redact_commands: bool,
+
+    /// Fields replaced by a hash keyed with a secret kept by this client
+    /// (`keys/sanitize-<remote>.key`): `cwd`, `hostname`, `client`.
+    #[serde(default)]
+    pub hash_fields: Vec<String>,
+
+    /// Push environment snapshots (the `env` metadata of invocations, see
+    /// `privacy.record_env`). Off by default: they stay local.
+    #[serde(default)]
+    pub push_env: bool,
+}
+
+impl SanitizePolicy {
+    /// Fields that can be listed in `hash_fields`.
+    pub const HASHABLE_FIELDS: [&'static str; 3] = ["cwd", "hostname", "client"];
+
+    /// Whether the policy leaves records unchanged.
+    pub fn is_noop(&self) -> bool {
+        !self.relative_home && !self.redact_commands && self.hash_fields.is_empty() && self.push_env
+    }
+
+    /// Whether nothing is configured (environment snapshots are dropped).
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether `field` should be hashed.
+    pub fn hashes(&self, field: &str) -> bool {
+        self.hash_fields.iter().any(|f| f == field)
+    }
+}
+
+/// Client identity signing for a shared remote (see `signing`).
+///
+/// ```toml
+/// [remotes.signing]
+/// sign = true    # sign what this client pushes
+/// verify = true  # drop pulled records without a trusted signature
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningPolicy {
+    /// Sign pushed invocations with this client's key.
+    #[serde(default)]
+    pub sign: bool,
+
+    /// Only keep pulled invocations signed by their client's trusted key.
+    #[serde(default)]
+    pub verify: bool,
+}
+
+impl SigningPolicy {
+    /// Whether neither signing nor verification is enabled.
+    pub fn is_off(&self) -> bool {
+        !self.sign && !self.verify
+    }
 }
 
 fn default_true() -> bool {
@@ -155,6 +263,9 @@ impl RemoteConfig {
             mode: RemoteMode::default(),
             credential_provider: None,
             auto_attach: true,
+            attach_timeout: None,
+            sanitize: SanitizePolicy::default(),
+            signing: SigningPolicy::default(),
         }
     }
 
@@ -164,6 +275,24 @@ impl RemoteConfig {
         self
     }
 
+    /// A mount of the BIRD store at `bird_root` (`shq mount`): a read-only
+    /// file remote on its database.
+    pub fn mount(name: impl Into<String>, bird_root: &Path) -> Self {
+        let db_path = Config::with_root(bird_root).db_path();
+        Self::new(name, RemoteType::File, format!("file://{}", db_path.display())).read_only()
+    }
+
+    /// The BIRD root a read-only file remote mounts, if this is one and its
+    /// database sits in a BIRD root's `db/` directory.
+    pub fn mounted_root(&self) -> Option<PathBuf> {
+        if self.remote_type != RemoteType::File || self.mode != RemoteMode::ReadOnly {
+            return None;
+        }
+        let db_path = Path::new(self.uri.strip_prefix("file://").unwrap_or(&self.uri));
+        let db_dir = db_path.parent()?;
+        (db_dir.file_name()? == "db").then(|| db_dir.parent().map(Path::to_path_buf))?
+    }
+
     /// Get the DuckDB schema name for this remote.
     pub fn schema_name(&self) -> String {
         format!("remote_{}", self.name)
@@ -319,6 +448,13 @@ pub struct PrivacyConfig {
     /// stored command lines. Best-effort, on by default.
     #[serde(default = "default_true")]
     pub redact_commands: bool,
+
+    /// Record a snapshot of environment variables with each invocation
+    /// (for `shq env-diff`). Off by default: values are only redacted by
+    /// name and by well-known formats, and anything else (a database URL
+    /// with a password in it) would be stored as is.
+    #[serde(default)]
+    pub record_env: bool,
 }
 
 impl Default for PrivacyConfig {
@@ -326,6 +462,79 @@ impl Default for PrivacyConfig {
         Self {
             exclude_patterns: default_sensitive_exclude_patterns(),
             redact_commands: true,
+            record_env: false,
+        }
+    }
+}
+
+/// DuckDB extension installation settings.
+///
+/// ```toml
+/// [extensions]
+/// repository = "/opt/bird-extensions"  # from `shq extensions bundle`
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExtensionsConfig {
+    /// Repository (directory or URL) to install missing extensions from
+    /// before trying the network. For air-gapped machines.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+}
+
+/// Task runner shims generated by `shq wrap <tool>`.
+///
+/// Every wrapped tool is captured with extraction enabled unless configured
+/// otherwise here:
+///
+/// ```toml
+/// [wrap.tools.make]
+/// extract_format = "gcc"
+/// tag = "build"
+///
+/// [wrap.tools.just]
+/// extract = false
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WrapConfig {
+    /// Per-tool settings, keyed by tool name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tools: BTreeMap<String, WrapToolConfig>,
+}
+
+impl WrapConfig {
+    /// Settings for `tool`, falling back to the defaults.
+    pub fn tool(&self, tool: &str) -> WrapToolConfig {
+        self.tools.get(tool).cloned().unwrap_or_default()
+    }
+}
+
+/// Capture settings for one wrapped tool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrapToolConfig {
+    /// Extract events from the tool's output.
+    #[serde(default = "default_true")]
+    pub extract: bool,
+
+    /// Format for event extraction (default: auto-detect).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extract_format: Option<String>,
+
+    /// Tag applied to each captured run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+
+    /// Capture without a PTY (separate stdout/stderr, no colors).
+    #[serde(default)]
+    pub no_pty: bool,
+}
+
+impl Default for WrapToolConfig {
+    fn default() -> Self {
+        Self {
+            extract: true,
+            extract_format: None,
+            tag: None,
+            no_pty: false,
         }
     }
 }
@@ -420,6 +629,457 @@ fn default_sensitive_exclude_patterns() -> Vec<String> {
     ]
 }
 
+/// Trash for data removed by destructive operations (e.g. archive pruning).
+///
+/// Removed files are kept under `$BIRD_ROOT/trash` for the grace period so
+/// `shq undo` can restore them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashConfig {
+    /// Days to keep trashed data before deleting it for good (0 = no trash).
+    #[serde(default = "default_trash_grace_days")]
+    pub grace_days: u32,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            grace_days: default_trash_grace_days(),
+        }
+    }
+}
+
+fn default_trash_grace_days() -> u32 {
+    7
+}
+
+/// Parquet settings for data moved to the archive tier (`shq archive`).
+///
+/// ```toml
+/// [archive]
+/// compression_level = 9     # zstd level, 1-22
+/// row_group_size = 1000000  # rows per row group
+/// dictionary = true         # dictionary-encode repetitive columns
+/// ```
+///
+/// Archive files are written once and mostly scanned, so they trade write
+/// time for size; recent files keep DuckDB's defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveConfig {
+    /// Zstd compression level.
+    #[serde(default = "default_archive_compression_level")]
+    pub compression_level: i32,
+
+    /// Rows per parquet row group.
+    #[serde(default = "default_archive_row_group_size")]
+    pub row_group_size: usize,
+
+    /// Dictionary-encode columns whose distinct values fit in a row group
+    /// (commands, directories, hosts), rather than only small dictionaries.
+    #[serde(default = "default_true")]
+    pub dictionary: bool,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            compression_level: default_archive_compression_level(),
+            row_group_size: default_archive_row_group_size(),
+            dictionary: true,
+        }
+    }
+}
+
+fn default_archive_compression_level() -> i32 {
+    9
+}
+
+fn default_archive_row_group_size() -> usize {
+    1_000_000
+}
+
+impl ArchiveConfig {
+    /// Options for `COPY ... TO` writing an archive parquet file.
+    pub fn copy_options(&self) -> String {
+        let mut options = format!(
+            "FORMAT PARQUET, COMPRESSION ZSTD, COMPRESSION_LEVEL {}, ROW_GROUP_SIZE {}",
+            self.compression_level.clamp(1, 22),
+            self.row_group_size.max(1)
+        );
+        // A dictionary larger than the limit falls back to plain encoding
+        let dictionary_limit = if self.dictionary { self.row_group_size.max(1) } else { 0 };
+        options.push_str(&format!(", DICTIONARY_SIZE_LIMIT {}", dictionary_limit));
+        options
+    }
+}
+
+/// Cold storage tier for old blobs (`shq tier`).
+///
+/// ```toml
+/// [blobs]
+/// cold_tier = "s3://bucket/bird-blobs"
+/// tier_after_days = 30
+/// ```
+///
+/// The cold tier is also a blob root, so tiered blobs are read through from it.
+/// An `s3://` tier is written and read with the credentials in the AWS
+/// environment variables (`AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`,
+/// `AWS_REGION`, and `AWS_ENDPOINT_URL` for S3-compatible stores).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobsConfig {
+    /// Where old blobs go: a directory, `file://` URL or object store URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cold_tier: Option<String>,
+
+    /// Move blobs not accessed for this many days.
+    #[serde(default = "default_tier_after_days")]
+    pub tier_after_days: u32,
+
+    /// Copy cold blobs back to local storage when they're read.
+    #[serde(default)]
+    pub cache_reads: bool,
+}
+
+impl Default for BlobsConfig {
+    fn default() -> Self {
+        Self {
+            cold_tier: None,
+            tier_after_days: default_tier_after_days(),
+            cache_reads: false,
+        }
+    }
+}
+
+fn default_tier_after_days() -> u32 {
+    30
+}
+
+impl BlobsConfig {
+    /// The cold tier as a URL usable in blob roots and `read_blob`.
+    pub fn cold_tier_url(&self) -> Option<String> {
+        let tier = self.cold_tier.as_deref()?.trim_end_matches('/');
+        Some(if tier.contains("://") {
+            tier.to_string()
+        } else {
+            format!("file://{}", tier)
+        })
+    }
+
+    /// The cold tier's directory, if it is on a (possibly mounted) filesystem.
+    pub fn cold_tier_dir(&self) -> Option<PathBuf> {
+        let url = self.cold_tier_url()?;
+        url.strip_prefix("file://").map(PathBuf::from)
+    }
+}
+
+/// Files snapshotted after `shq run` commands, besides those given with `--attach`.
+///
+/// ```toml
+/// [artifacts]
+/// capture = ["target/*.html", "coverage/lcov.info"]
+/// ```
+///
+/// Patterns are relative to the command's working directory, and only files
+/// written while the command ran are captured.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArtifactsConfig {
+    /// Glob patterns (`*` wildcards) of files to capture.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capture: Vec<String>,
+}
+
+/// Daily store snapshots for `--as-of` queries.
+///
+/// Snapshots hard-link the (immutable) parquet and blob files and copy the
+/// database file, so in parquet mode they cost little beyond the catalog.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotConfig {
+    /// Take a snapshot once a day (from `shq compact`, which the shell hooks run).
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Days to keep snapshots.
+    #[serde(default = "default_snapshot_keep_days")]
+    pub keep_days: u32,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keep_days: default_snapshot_keep_days(),
+        }
+    }
+}
+
+fn default_snapshot_keep_days() -> u32 {
+    30
+}
+
+/// Coalescing of bursts of writes into grouped writes (see `WriteCoalescer`).
+///
+/// ```toml
+/// [ingest]
+/// max_batch = 64      # write once this many invocations are queued
+/// max_delay_ms = 250  # or once the oldest has waited this long
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestConfig {
+    /// Most invocations queued before they're written together.
+    #[serde(default = "default_ingest_max_batch")]
+    pub max_batch: usize,
+
+    /// Longest an invocation waits in the queue, in milliseconds.
+    #[serde(default = "default_ingest_max_delay_ms")]
+    pub max_delay_ms: u64,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            max_batch: default_ingest_max_batch(),
+            max_delay_ms: default_ingest_max_delay_ms(),
+        }
+    }
+}
+
+fn default_ingest_max_batch() -> usize {
+    64
+}
+
+fn default_ingest_max_delay_ms() -> u64 {
+    250
+}
+
+/// Bridging with the shell's history file (see [`crate::histfile`]).
+///
+/// ```toml
+/// [histfile]
+/// write = "~/.zsh_history"       # append every captured command here
+/// ingest = ["~/.bash_history"]   # read by `shq histfile import`
+/// format = "zsh"                 # zsh, bash or plain (default: from the file name)
+/// ```
+///
+/// Commands imported from a history file are never written back to one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistfileConfig {
+    /// History file captured commands are appended to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write: Option<String>,
+
+    /// History files `shq histfile import` reads new entries from.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ingest: Vec<String>,
+
+    /// Format of these files, if not the one their names suggest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<HistFormat>,
+}
+
+impl HistfileConfig {
+    /// The file to append captured commands to, with its format.
+    pub fn write_target(&self) -> Option<(PathBuf, HistFormat)> {
+        let path = crate::histfile::expand_home(self.write.as_deref()?);
+        let format = self.format_for(&path);
+        Some((path, format))
+    }
+
+    /// The format of the history file at `path`.
+    pub fn format_for(&self, path: &Path) -> HistFormat {
+        self.format.unwrap_or_else(|| HistFormat::for_path(path))
+    }
+}
+
+/// Where `shq i` looks when the query doesn't name a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryScope {
+    /// Commands run in the current directory or below it (default)
+    #[default]
+    Cwd,
+    /// Commands run anywhere in the current project
+    Project,
+    /// All history
+    Global,
+}
+
+impl std::fmt::Display for QueryScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryScope::Cwd => write!(f, "cwd"),
+            QueryScope::Project => write!(f, "project"),
+            QueryScope::Global => write!(f, "global"),
+        }
+    }
+}
+
+impl FromStr for QueryScope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "cwd" => Ok(QueryScope::Cwd),
+            "project" => Ok(QueryScope::Project),
+            "global" => Ok(QueryScope::Global),
+            _ => Err(Error::Config(format!(
+                "Invalid query scope '{}': expected 'cwd', 'project' or 'global'",
+                s
+            ))),
+        }
+    }
+}
+
+/// Defaults for history queries.
+///
+/// ```toml
+/// [query]
+/// default_scope = "cwd"  # cwd, project or global
+/// ```
+///
+/// The scope only applies inside a project (a directory with `.bird/`);
+/// elsewhere, and with `shq i -g`, history is global.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryConfig {
+    /// Scope of `shq i` when the query has no path.
+    #[serde(default)]
+    pub default_scope: QueryScope,
+}
+
+/// Whether terminal output is colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayTheme {
+    /// Color on a terminal, unless `NO_COLOR` is set or `TERM=dumb` (default)
+    #[default]
+    Auto,
+    /// Always color
+    Color,
+    /// Never color
+    Plain,
+}
+
+impl std::fmt::Display for DisplayTheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisplayTheme::Auto => write!(f, "auto"),
+            DisplayTheme::Color => write!(f, "color"),
+            DisplayTheme::Plain => write!(f, "plain"),
+        }
+    }
+}
+
+impl FromStr for DisplayTheme {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(DisplayTheme::Auto),
+            "color" => Ok(DisplayTheme::Color),
+            "plain" => Ok(DisplayTheme::Plain),
+            _ => Err(Error::Config(format!(
+                "Invalid display theme '{}': expected 'auto', 'color' or 'plain'",
+                s
+            ))),
+        }
+    }
+}
+
+/// Characters the status and output glyphs are drawn from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Charset {
+    /// Unicode when the locale is UTF-8, otherwise ASCII (default)
+    #[default]
+    Auto,
+    /// `✓ ✗ ● ◐ ○`
+    Unicode,
+    /// `+ x B O -`
+    Ascii,
+}
+
+impl std::fmt::Display for Charset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Charset::Auto => write!(f, "auto"),
+            Charset::Unicode => write!(f, "unicode"),
+            Charset::Ascii => write!(f, "ascii"),
+        }
+    }
+}
+
+impl FromStr for Charset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Charset::Auto),
+            "unicode" => Ok(Charset::Unicode),
+            "ascii" => Ok(Charset::Ascii),
+            _ => Err(Error::Config(format!(
+                "Invalid charset '{}': expected 'auto', 'unicode' or 'ascii'",
+                s
+            ))),
+        }
+    }
+}
+
+/// How times, colors and glyphs are shown.
+///
+/// ```toml
+/// [display]
+/// timezone = "America/New_York"  # default: the system's local time zone
+/// theme = "auto"                 # auto, color or plain
+/// charset = "auto"               # auto (from the locale), unicode or ascii
+///
+/// [display.glyphs]               # override single glyphs
+/// ok = "+"
+/// fail = "!"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DisplayConfig {
+    /// IANA time zone to render timestamps in (they are stored in UTC).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+
+    /// Whether output is colored.
+    #[serde(default)]
+    pub theme: DisplayTheme,
+
+    /// Unicode or ASCII glyphs.
+    #[serde(default)]
+    pub charset: Charset,
+
+    /// Glyphs replacing those of the charset, by name (`ok`, `fail`,
+    /// `mixed`, `pending`, `marker`, `out_combined`, `out_both`,
+    /// `out_stdout`, `out_stderr`, `out_empty`, `out_none`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub glyphs: BTreeMap<String, String>,
+}
+
+/// A command `shq cron daemon` runs on a schedule.
+///
+/// ```toml
+/// [[cron]]
+/// name = "backup-db"
+/// schedule = "0 3 * * *"
+/// command = "pg_dump mydb > /backups/mydb.sql"
+/// cwd = "/srv"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CronJob {
+    /// Unique name; runs are recorded with the `cron=<name>` dimension.
+    pub name: String,
+
+    /// Cron expression, in local time (see [`crate::cron::Schedule`]).
+    pub schedule: String,
+
+    /// Shell command line.
+    pub command: String,
+
+    /// Directory to run in (default: the home directory).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
+
 /// BIRD configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -438,10 +1098,25 @@ pub struct Config {
     #[serde(default = "default_inline_threshold")]
     pub inline_threshold: usize,
 
+    /// Check output content against its BLAKE3 hash on every read.
+    #[serde(default)]
+    pub verify_blob_hashes: bool,
+
     /// Automatically extract events after `shq run` commands.
     #[serde(default)]
     pub auto_extract: bool,
 
+    /// Record the phase timings of every shq command (parse, connect, query,
+    /// render) in `self-profile.jsonl`, for `shq self-profile`. Local only.
+    #[serde(default)]
+    pub self_profile: bool,
+
+    /// Record a `git stash create` snapshot of the working tree when `shq run`
+    /// starts a command, for `shq rerun --at-snapshot`. The snapshot is an
+    /// unreferenced commit, so `git gc` may eventually prune it.
+    #[serde(default)]
+    pub snapshot_worktree: bool,
+
     /// Storage mode for writing data.
     /// - parquet: Multi-writer safe, requires compaction (default)
     /// - duckdb: Single-writer, no compaction needed
@@ -467,6 +1142,58 @@ pub struct Config {
     /// Privacy configuration (applies to all capture paths).
     #[serde(default)]
     pub privacy: PrivacyConfig,
+
+    /// DuckDB extension installation settings.
+    #[serde(default)]
+    pub extensions: ExtensionsConfig,
+
+    /// Task runner shims (`shq wrap`).
+    #[serde(default)]
+    pub wrap: WrapConfig,
+
+    /// Trash for removed data (`shq undo`).
+    #[serde(default)]
+    pub trash: TrashConfig,
+
+    /// Daily snapshots for time-travel queries.
+    #[serde(default)]
+    pub snapshots: SnapshotConfig,
+
+    /// Blob tiering to cold storage.
+    #[serde(default)]
+    pub blobs: BlobsConfig,
+
+    /// Parquet settings for archived data.
+    #[serde(default)]
+    pub archive: ArchiveConfig,
+
+    /// Files captured from `shq run` commands.
+    #[serde(default)]
+    pub artifacts: ArtifactsConfig,
+
+    /// Grouping of write bursts.
+    #[serde(default)]
+    pub ingest: IngestConfig,
+
+    /// Shell history file bridge.
+    #[serde(default)]
+    pub histfile: HistfileConfig,
+
+    /// History query defaults.
+    #[serde(default)]
+    pub query: QueryConfig,
+
+    /// Rendering of times.
+    #[serde(default)]
+    pub display: DisplayConfig,
+
+    /// Scheduled commands (`shq cron`).
+    #[serde(default, rename = "cron", skip_serializing_if = "Vec::is_empty")]
+    pub cron_jobs: Vec<CronJob>,
+
+    /// Keep the database in memory (see [`Config::in_memory`]). Never saved.
+    #[serde(skip)]
+    pub in_memory: bool,
 }
 
 fn default_client_id() -> String {
@@ -496,13 +1223,29 @@ impl Config {
             client_id: default_client_id(),
             hot_days: default_hot_days(),
             inline_threshold: default_inline_threshold(),
+            verify_blob_hashes: false,
             auto_extract: true,
+            self_profile: false,
+            snapshot_worktree: false,
             storage_mode: StorageMode::default(),
             remotes: Vec::new(),
             sync: SyncConfig::default(),
             hooks: HooksConfig::default(),
             buffer: BufferConfig::default(),
             privacy: PrivacyConfig::default(),
+            extensions: ExtensionsConfig::default(),
+            wrap: WrapConfig::default(),
+            trash: TrashConfig::default(),
+            snapshots: SnapshotConfig::default(),
+            blobs: BlobsConfig::default(),
+            archive: ArchiveConfig::default(),
+            artifacts: ArtifactsConfig::default(),
+            ingest: IngestConfig::default(),
+            histfile: HistfileConfig::default(),
+            query: QueryConfig::default(),
+            display: DisplayConfig::default(),
+            cron_jobs: Vec::new(),
+            in_memory: false,
         }
     }
 
@@ -513,13 +1256,56 @@ impl Config {
             client_id: default_client_id(),
             hot_days: default_hot_days(),
             inline_threshold: default_inline_threshold(),
+            verify_blob_hashes: false,
             auto_extract: true,
+            self_profile: false,
+            snapshot_worktree: false,
             storage_mode: StorageMode::DuckDB,
             remotes: Vec::new(),
             sync: SyncConfig::default(),
             hooks: HooksConfig::default(),
             buffer: BufferConfig::default(),
             privacy: PrivacyConfig::default(),
+            extensions: ExtensionsConfig::default(),
+            wrap: WrapConfig::default(),
+            trash: TrashConfig::default(),
+            snapshots: SnapshotConfig::default(),
+            blobs: BlobsConfig::default(),
+            archive: ArchiveConfig::default(),
+            artifacts: ArtifactsConfig::default(),
+            ingest: IngestConfig::default(),
+            histfile: HistfileConfig::default(),
+            query: QueryConfig::default(),
+            display: DisplayConfig::default(),
+            cron_jobs: Vec::new(),
+            in_memory: false,
+        }
+    }
+
+    /// Create a config for an in-memory store, for tests and ephemeral use.
+    ///
+    /// The database lives in memory for as long as the [`Store`] opened on
+    /// it, in DuckDB storage mode with every output stored inline, so
+    /// recording and querying touch no files and need no `initialize`:
+    ///
+    /// ```no_run
+    /// let store = bird::Store::open(bird::Config::in_memory())?;
+    /// # Ok::<(), bird::Error>(())
+    /// ```
+    ///
+    /// Operations that work on files (compaction, archiving, snapshots,
+    /// push/pull) have nothing to work on. `bird_root` is a path under the
+    /// temp directory that only exists if something writes there. The
+    /// database belongs to the one store: opening the config (or a clone of
+    /// it) again while that store is open is an error.
+    ///
+    /// [`Store`]: crate::Store
+    pub fn in_memory() -> Self {
+        let bird_root = std::env::temp_dir().join(format!("bird-memory-{}", uuid::Uuid::now_v7()));
+        Self {
+            inline_threshold: usize::MAX,
+            in_memory: true,
+            ..Self::with_duckdb_mode(bird_root)
         }
     }
 
@@ -540,23 +1326,30 @@ impl Config {
         let config_path = bird_root.join("config.toml");
 
         if config_path.exists() {
-            let contents = std::fs::read_to_string(&config_path)?;
-            let mut config: Config = toml::from_str(&contents)
-                .map_err(|e| Error::Config(format!("Failed to parse config: {}", e)))?;
-            // Ensure bird_root matches the actual location
-            config.bird_root = bird_root.to_path_buf();
-            Ok(config)
+            Self::from_toml(&std::fs::read_to_string(&config_path)?, bird_root)
         } else {
             Ok(Self::with_root(bird_root))
         }
     }
 
+    /// Parse config.toml contents for the BIRD_ROOT `bird_root`.
+    pub fn from_toml(contents: &str, bird_root: &Path) -> Result<Self> {
+        let mut config: Config = toml::from_str(contents)
+            .map_err(|e| Error::Config(format!("Failed to parse config: {}", e)))?;
+        // Ensure bird_root matches the actual location
+        config.bird_root = bird_root.to_path_buf();
+        Ok(config)
+    }
+
+    /// The config as config.toml contents.
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|e| Error::Config(format!("Failed to serialize config: {}", e)))
+    }
+
     /// Save config to BIRD_ROOT/config.toml.
     pub fn save(&self) -> Result<()> {
         let config_path = self.bird_root.join("config.toml");
-        let contents = toml::to_string_pretty(self)
-            .map_err(|e| Error::Config(format!("Failed to serialize config: {}", e)))?;
-        std::fs::write(&config_path, contents)?;
+        std::fs::write(&config_path, self.to_toml()?)?;
         // Config may name remotes/credentials providers; keep it owner-only.
         crate::perms::harden_file(&config_path);
         Ok(())
@@ -672,10 +1465,20 @@ impl Config {
         self.recent_dir().join("blobs/content")
     }
 
+    /// Path to output being captured by running commands.
+    ///
+    /// Kept next to the blobs content directory, on the same filesystem, so a
+    /// finished capture becomes its blob with a rename:
+    /// `capture/<invocation_id>.<stream>.out`
+    pub fn capture_dir(&self) -> PathBuf {
+        self.recent_dir().join("blobs/capture")
+    }
+
     /// Path to the running directory for in-progress command output.
     ///
-    /// Files here are temp files streamed during command execution:
-    /// `running/<invocation_id>.out`
+    /// Files here are streamed during command execution:
+    /// `running/<invocation_id>.out`, a link to the capture file when output
+    /// is captured as one stream (see [`Self::capture_dir`]).
     ///
     /// On completion, content is moved to blob storage. On crash, files survive
     /// for recovery. Use `shq show --follow <id>` to tail while running.
@@ -697,6 +1500,23 @@ impl Config {
         self.bird_root.join("buffer")
     }
 
+    /// Path to the task runner shim directory (`shq wrap`).
+    ///
+    /// Must come before the real tools in `$PATH` for shims to take effect.
+    pub fn shims_dir(&self) -> PathBuf {
+        self.bird_root.join("shims")
+    }
+
+    /// Path to the trash directory (data kept for `shq undo`).
+    pub fn trash_dir(&self) -> PathBuf {
+        self.bird_root.join("trash")
+    }
+
+    /// Path to the snapshots directory (one BIRD root per day).
+    pub fn snapshots_dir(&self) -> PathBuf {
+        self.bird_root.join("snapshots")
+    }
+
     /// Path to a buffer entry's output file.
     pub fn buffer_output_path(&self, id: &uuid::Uuid) -> PathBuf {
         self.buffer_dir().join(format!("{}.out", id))
@@ -726,6 +1546,49 @@ impl Config {
         self.bird_root.join("format-hints.toml")
     }
 
+    /// Path to the client key directory (`client.key`, `known_clients`).
+    pub fn keys_dir(&self) -> PathBuf {
+        self.bird_root.join("keys")
+    }
+
+    /// Path to the lock files of maintenance operations (see [`crate::locks`]).
+    pub fn locks_dir(&self) -> PathBuf {
+        self.bird_root.join("locks")
+    }
+
+    /// Path to the spool of async saves waiting to be written.
+    pub fn spool_dir(&self) -> PathBuf {
+        self.bird_root.join("spool")
+    }
+
+    /// Path to the record of how far each history file has been imported.
+    pub fn histfile_offsets_path(&self) -> PathBuf {
+        self.bird_root.join("histfile-offsets.json")
+    }
+
+    /// Path to the sync token, rewritten after maintenance and spool flushes
+    /// (parquet mode).
+    pub fn sync_token_path(&self) -> PathBuf {
+        self.bird_root.join("sync-token")
+    }
+
+    /// Path to the log of shq's own phase timings (`self_profile`).
+    pub fn self_profile_path(&self) -> PathBuf {
+        self.bird_root.join("self-profile.jsonl")
+    }
+
+    /// Path to the record of what has been pushed to an Atuin remote.
+    pub fn atuin_state_path(&self, remote: &str) -> PathBuf {
+        self.bird_root.join(format!("atuin-{}.json", remote))
+    }
+
+    /// Path to the capture state file (written by `shq off` / `shq on`).
+    ///
+    /// Shell hooks read this on every prompt, so it stays a one-line file.
+    pub fn state_path(&self) -> PathBuf {
+        self.bird_root.join("state")
+    }
+
     /// Path to events parquet files for a given date.
     pub fn events_dir(&self, date: &chrono::NaiveDate) -> PathBuf {
         self.recent_dir()
@@ -754,14 +1617,33 @@ impl Config {
         self.remotes.len() < len_before
     }
 
+    /// Add a cron job, replacing any with the same name.
+    pub fn add_cron_job(&mut self, job: CronJob) {
+        self.cron_jobs.retain(|j| j.name != job.name);
+        self.cron_jobs.push(job);
+    }
+
+    /// Remove a cron job by name. Returns true if removed.
+    pub fn remove_cron_job(&mut self, name: &str) -> bool {
+        let len_before = self.cron_jobs.len();
+        self.cron_jobs.retain(|j| j.name != name);
+        self.cron_jobs.len() < len_before
+    }
+
     /// Get all blob roots for multi-location resolution.
-    /// Returns local blobs dir first, then remote blob URLs.
+    /// Returns local blobs dir first, then the cold tier, then remote blob URLs.
     pub fn blob_roots(&self) -> Vec<String> {
         let mut roots = vec![self.blobs_dir().to_string_lossy().to_string()];
 
+        if let Some(cold_tier) = self.blobs.cold_tier_url() {
+            roots.push(cold_tier);
+        }
+
         for remote in &self.remotes {
             if let Some(blob_url) = remote.blob_base_url() {
                 roots.push(blob_url);
+            } else if let Some(root) = remote.mounted_root() {
+                roots.push(Config::with_root(root).blobs_dir().to_string_lossy().to_string());
             }
         }
 
@@ -770,7 +1652,7 @@ impl Config {
 
     /// Get remotes that should be auto-attached.
     pub fn auto_attach_remotes(&self) -> Vec<&RemoteConfig> {
-        self.remotes.iter().filter(|r| r.auto_attach).collect()
+        self.remotes.iter().filter(|r| r.auto_attach && r.remote_type.is_attachable()).collect()
     }
 }
 
@@ -851,6 +1733,27 @@ mod tests {
         assert_eq!(loaded.inline_threshold, config.inline_threshold);
     }
 
+    #[test]
+    fn test_wrap_tool_config() {
+        let config: WrapConfig = toml::from_str(
+            r#"
+            [tools.make]
+            extract_format = "gcc"
+            tag = "build"
+            "#,
+        )
+        .unwrap();
+        let make = config.tool("make");
+        assert!(make.extract);
+        assert_eq!(make.extract_format.as_deref(), Some("gcc"));
+        assert_eq!(make.tag.as_deref(), Some("build"));
+
+        // Unconfigured tools get the defaults
+        let just = config.tool("just");
+        assert!(just.extract);
+        assert!(just.tag.is_none());
+    }
+
     // V5 schema path tests
 
     #[test]
@@ -882,6 +1785,12 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_capture_dir_beside_blobs() {
+        let config = Config::with_root("/tmp/test-bird");
+        assert_eq!(config.capture_dir().parent(), config.blobs_dir().parent());
+    }
+
     #[test]
     fn test_running_path() {
         let config = Config::with_root("/tmp/test-bird");
@@ -891,4 +1800,71 @@ mod tests {
             PathBuf::from("/tmp/test-bird/running/01234567-89ab-cdef-0123-456789abcdef.out")
         );
     }
+
+    #[test]
+    fn test_cold_tier_blob_root() {
+        let mut config = Config::with_root("/tmp/test-bird");
+        assert_eq!(config.blob_roots().len(), 1);
+
+        config.blobs.cold_tier = Some("/mnt/cold/".to_string());
+        assert_eq!(config.blobs.cold_tier_dir(), Some(PathBuf::from("/mnt/cold")));
+        assert_eq!(config.blob_roots()[1], "file:///mnt/cold");
+
+        config.blobs.cold_tier = Some("s3://bucket/bird-blobs".to_string());
+        assert_eq!(config.blobs.cold_tier_dir(), None);
+        assert_eq!(config.blob_roots()[1], "s3://bucket/bird-blobs");
+    }
+
+    #[test]
+    fn test_archive_copy_options() {
+        let toml = "bird_root = \"/tmp/test-bird\"\n[archive]\ncompression_level = 30\ndictionary = false\n";
+        let config = Config::from_toml(toml, Path::new("/tmp/test-bird")).unwrap();
+        assert_eq!(
+            config.archive.copy_options(),
+            "FORMAT PARQUET, COMPRESSION ZSTD, COMPRESSION_LEVEL 22, ROW_GROUP_SIZE 1000000, DICTIONARY_SIZE_LIMIT 0"
+        );
+        assert!(Config::with_root("/tmp/test-bird").archive.copy_options().ends_with("DICTIONARY_SIZE_LIMIT 1000000"));
+    }
+
+    #[cfg(feature = "store")]
+    #[test]
+    fn test_archive_copy_options_accepted_by_duckdb() {
+        let tmp = TempDir::new().unwrap();
+        let conn = duckdb::Connection::open_in_memory().unwrap();
+        for dictionary in [true, false] {
+            let archive = ArchiveConfig { dictionary, ..ArchiveConfig::default() };
+            let path = tmp.path().join(format!("dictionary-{}.parquet", dictionary));
+            conn.execute(
+                &format!(
+                    "COPY (SELECT range AS n, 'cmd-' || (range % 3) AS cmd FROM range(100)) TO '{}' ({})",
+                    path.display(),
+                    archive.copy_options()
+                ),
+                [],
+            )
+            .unwrap();
+            let compression: String = conn
+                .query_row(
+                    &format!("SELECT DISTINCT compression FROM parquet_metadata('{}')", path.display()),
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(compression, "ZSTD");
+        }
+    }
+
+    #[test]
+    fn test_mount_remote() {
+        let mount = RemoteConfig::mount("old", Path::new("/mnt/backup/old-bird"));
+        assert_eq!(mount.uri, "file:///mnt/backup/old-bird/db/bird.duckdb");
+        assert_eq!(mount.mode, RemoteMode::ReadOnly);
+        assert_eq!(mount.data_dir(), Some(PathBuf::from("/mnt/backup/old-bird/db/data")));
+        assert_eq!(mount.mounted_root(), Some(PathBuf::from("/mnt/backup/old-bird")));
+
+        let shared = RemoteConfig::new("team", RemoteType::File, "/srv/team.duckdb").read_only();
+        assert_eq!(shared.mounted_root(), None);
+        let writable = RemoteConfig::new("copy", RemoteType::File, "file:///x/db/bird.duckdb");
+        assert_eq!(writable.mounted_root(), None);
+    }
 }
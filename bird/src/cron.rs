@@ -0,0 +1,181 @@
+//! Cron schedules for `shq cron`.
+//!
+//! The usual five fields - minute, hour, day of month, month, day of week
+//! (0 or 7 is Sunday) - each `*`, a number, a range `a-b`, a step `*/n` or
+//! `a-b/n`, or a comma-separated list of those. `@hourly`, `@daily`,
+//! `@weekly`, `@monthly` and `@yearly` are shorthands. As in cron, when both
+//! day of month and day of week are restricted, a day matching either runs.
+
+use chrono::{Datelike, NaiveDateTime, TimeDelta, Timelike};
+
+use crate::{Error, Result};
+
+/// How far ahead [`Schedule::next_after`] looks (covers Feb 29).
+const LOOKAHEAD_DAYS: i64 = 366 * 8;
+
+/// A parsed cron schedule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days: Vec<bool>,
+    months: Vec<bool>,
+    weekdays: Vec<bool>,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    /// Parse a five-field expression or an `@` shorthand.
+    pub fn parse(expr: &str) -> Result<Self> {
+        let expanded = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(Error::Config(format!(
+                "Invalid schedule '{}': expected 5 fields (minute hour day month weekday)",
+                expr
+            )));
+        };
+
+        let parse = |field: &str, min: u32, max: u32| {
+            parse_field(field, min, max)
+                .ok_or_else(|| Error::Config(format!("Invalid schedule '{}': bad field '{}'", expr, field)))
+        };
+        let mut weekdays = parse(weekday, 0, 7)?;
+        if weekdays[7] {
+            weekdays[0] = true;
+        }
+        Ok(Schedule {
+            minutes: parse(minute, 0, 59)?,
+            hours: parse(hour, 0, 23)?,
+            days: parse(day, 1, 31)?,
+            months: parse(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    /// Whether the schedule fires in the minute of `time`.
+    pub fn matches(&self, time: NaiveDateTime) -> bool {
+        self.minutes[time.minute() as usize] && self.hours[time.hour() as usize] && self.matches_day(time)
+    }
+
+    fn matches_day(&self, time: NaiveDateTime) -> bool {
+        if !self.months[time.month() as usize] {
+            return false;
+        }
+        let day = self.days[time.day() as usize];
+        let weekday = self.weekdays[time.weekday().num_days_from_sunday() as usize];
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// The first minute strictly after `time` that the schedule fires in.
+    pub fn next_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = time.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let first_day = start.date();
+        for offset in 0..LOOKAHEAD_DAYS {
+            let date = first_day + TimeDelta::days(offset);
+            if !self.matches_day(date.and_hms_opt(0, 0, 0)?) {
+                continue;
+            }
+            for hour in (0..24).filter(|h| self.hours[*h as usize]) {
+                for minute in (0..60).filter(|m| self.minutes[*m as usize]) {
+                    let candidate = date.and_hms_opt(hour, minute, 0)?;
+                    if candidate >= start {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Values `min..=max` a field selects, indexed by value.
+fn parse_field(field: &str, min: u32, max: u32) -> Option<Vec<bool>> {
+    let mut selected = vec![false; max as usize + 1];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (low, high) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((low, high)) => (low.parse().ok()?, high.parse().ok()?),
+                // `5/15` means from 5 to the end, every 15
+                None if step > 1 => (range.parse().ok()?, max),
+                None => {
+                    let value = range.parse().ok()?;
+                    (value, value)
+                }
+            },
+        };
+        if low < min || high > max || low > high {
+            return None;
+        }
+        for value in (low..=high).step_by(step as usize) {
+            selected[value as usize] = true;
+        }
+    }
+    Some(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        assert!(Schedule::parse("0 3 * * *").is_ok());
+        assert!(Schedule::parse("*/15 9-17 * * 1-5").is_ok());
+        assert!(Schedule::parse("@daily").is_ok());
+        assert!(Schedule::parse("0 3 * *").is_err());
+        assert!(Schedule::parse("60 3 * * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+        assert!(Schedule::parse("0 3 * * mon").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let nightly = Schedule::parse("0 3 * * *").unwrap();
+        assert!(nightly.matches(at("2024-06-10 03:00")));
+        assert!(!nightly.matches(at("2024-06-10 03:01")));
+
+        // 2024-06-10 is a Monday
+        let weekdays = Schedule::parse("*/15 9-17 * * 1-5").unwrap();
+        assert!(weekdays.matches(at("2024-06-10 09:45")));
+        assert!(!weekdays.matches(at("2024-06-09 09:45")));
+        assert!(Schedule::parse("0 0 * * 7").unwrap().matches(at("2024-06-09 00:00")));
+
+        // Day of month or day of week, when both are given
+        let either = Schedule::parse("0 0 1 * 1").unwrap();
+        assert!(either.matches(at("2024-06-10 00:00")));
+        assert!(either.matches(at("2024-06-01 00:00")));
+        assert!(!either.matches(at("2024-06-02 00:00")));
+    }
+
+    #[test]
+    fn test_next_after() {
+        let nightly = Schedule::parse("0 3 * * *").unwrap();
+        assert_eq!(nightly.next_after(at("2024-06-10 02:59")), Some(at("2024-06-10 03:00")));
+        assert_eq!(nightly.next_after(at("2024-06-10 03:00")), Some(at("2024-06-11 03:00")));
+        let leap = Schedule::parse("0 0 29 2 *").unwrap();
+        assert_eq!(leap.next_after(at("2024-03-01 00:00")), Some(at("2028-02-29 00:00")));
+    }
+}
@@ -7,6 +7,7 @@ pub enum Error {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
+    #[cfg(feature = "store")]
     #[error("DuckDB error: {0}")]
     DuckDb(#[from] duckdb::Error),
 
@@ -33,6 +34,130 @@ pub enum Error {
 
     #[error("Extension error: {0}")]
     Extension(String),
+
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    #[error("Database {} is locked by another process", path.display())]
+    LockConflict { path: PathBuf },
+
+    #[error("{intent} in progress by {holder}")]
+    Busy { intent: String, holder: String },
+
+    #[error("Extension '{name}' is not available")]
+    ExtensionMissing { name: String },
+
+    #[error("Authentication failed for remote '{remote}': {detail}")]
+    RemoteAuth { remote: String, detail: String },
+
+    #[error("Database schema version {found} does not match this version of BIRD (expected {expected})")]
+    SchemaVersionMismatch { found: String, expected: String },
+
+    #[error("{what} was written by a newer shq (version {found}; this shq reads up to {supported})")]
+    NewerFormat { what: String, found: u32, supported: u32 },
+
+    #[error("Output content at {storage_ref} is corrupted (hash {actual}, expected {expected})")]
+    ContentCorrupted { storage_ref: String, expected: String, actual: String },
+
+    #[error("Invalid query: {}", diagnostics.first().map(ToString::to_string).unwrap_or_default())]
+    InvalidQuery { query: String, diagnostics: Vec<crate::query::QueryDiagnostic> },
+
+    #[error("ID '{id}' is ambiguous: {count} invocations match")]
+    AmbiguousId { id: String, count: usize, candidates: Vec<String> },
+}
+
+impl Error {
+    /// What the user can do about this error, if there's a known fix.
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Error::NotInitialized(_) => Some("run `shq init` to create it".to_string()),
+            Error::LockConflict { .. } => Some(
+                "wait for the other shq or DuckDB process to finish; stores used by \
+                 concurrent writers should use parquet mode (`shq init --mode parquet`)"
+                    .to_string(),
+            ),
+            Error::Busy { .. } => Some(
+                "wait for it to finish and try again; `shq locks` shows what's running".to_string(),
+            ),
+            Error::ExtensionMissing { .. } => Some(
+                "run `shq update-extensions`, or offline `shq extensions install --from <dir>`"
+                    .to_string(),
+            ),
+            Error::RemoteAuth { remote, .. } => Some(format!(
+                "check `credential_provider` for remote '{}' in config.toml and that your \
+                 credentials are current (e.g. `aws sso login`)",
+                remote
+            )),
+            Error::SchemaVersionMismatch { found, expected } => {
+                let newer = matches!(
+                    (found.parse::<u32>(), expected.parse::<u32>()),
+                    (Ok(found), Ok(expected)) if found > expected
+                );
+                Some(if newer {
+                    "the database was written by a newer shq; upgrade shq to use it".to_string()
+                } else {
+                    "back up BIRD_ROOT and re-create the store with `shq init --force`".to_string()
+                })
+            }
+            Error::NewerFormat { .. } => Some(
+                "upgrade shq; `shq doctor` shows which parts of the setup are older".to_string(),
+            ),
+            Error::ContentCorrupted { .. } => Some(
+                "run `shq fsck --verify-content` to find other corrupted outputs; a copy may \
+                 still exist on a remote (`shq pull`)"
+                    .to_string(),
+            ),
+            Error::AmbiguousId { count, candidates, .. } => {
+                let mut hint = String::from("use more characters of the ID; it could be:");
+                for candidate in candidates {
+                    hint.push_str("\n  ");
+                    hint.push_str(candidate);
+                }
+                if *count > candidates.len() {
+                    hint.push_str(&format!("\n  ... and {} more", count - candidates.len()));
+                }
+                Some(hint)
+            }
+            _ => None,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hints() {
+        let err = Error::ExtensionMissing { name: "parquet".to_string() };
+        assert_eq!(err.to_string(), "Extension 'parquet' is not available");
+        assert!(err.hint().unwrap().contains("shq update-extensions"));
+
+        let newer = Error::SchemaVersionMismatch { found: "6".to_string(), expected: "5".to_string() };
+        assert!(newer.hint().unwrap().contains("upgrade shq"));
+        let older = Error::SchemaVersionMismatch { found: "4".to_string(), expected: "5".to_string() };
+        assert!(older.hint().unwrap().contains("shq init --force"));
+
+        let corrupted = Error::ContentCorrupted {
+            storage_ref: "file://recent/blobs/content/ab/abcd--make.bin".to_string(),
+            expected: "abcd".to_string(),
+            actual: "ef01".to_string(),
+        };
+        assert!(corrupted.hint().unwrap().contains("shq fsck --verify-content"));
+
+        let ambiguous = Error::AmbiguousId {
+            id: "9c1e".to_string(),
+            count: 3,
+            candidates: vec!["a  make".to_string(), "b  ls".to_string()],
+        };
+        assert_eq!(ambiguous.to_string(), "ID '9c1e' is ambiguous: 3 invocations match");
+        assert_eq!(
+            ambiguous.hint().unwrap(),
+            "use more characters of the ID; it could be:\n  a  make\n  b  ls\n  ... and 1 more"
+        );
+
+        assert!(Error::Storage("disk full".to_string()).hint().is_none());
+    }
+}
@@ -0,0 +1,141 @@
+//! Event parsing - turning command output into structured events.
+//!
+//! The preferred implementation is the duck_hunt DuckDB extension, which
+//! reads output blobs directly in SQL and knows dozens of formats. When it
+//! is unavailable, extraction falls back to the pure-Rust [`EventParser`]s
+//! returned by [`fallback_parsers`] (gcc, cargo, pytest, eslint). Library
+//! consumers can supply their own parsers through
+//! `Store::extract_events_with_parsers`.
+
+mod parsers;
+
+pub use parsers::{CargoParser, EslintParser, GccParser, PytestParser};
+
+/// An event parsed from command output, before it is tied to an invocation.
+///
+/// Mirrors the columns duck_hunt produces.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedEvent {
+    /// Event type, e.g. "diagnostic" or "test_result".
+    pub event_type: Option<String>,
+    /// Severity level: error, warning, info, note.
+    pub severity: Option<String>,
+    pub ref_file: Option<String>,
+    pub ref_line: Option<i32>,
+    pub ref_column: Option<i32>,
+    pub message: Option<String>,
+    /// Error/warning code (e.g., "E0308", "no-unused-vars").
+    pub error_code: Option<String>,
+    pub test_name: Option<String>,
+    /// Test status: passed, failed, skipped.
+    pub status: Option<String>,
+}
+
+/// A parser for one family of output formats.
+pub trait EventParser: Send + Sync {
+    /// Format name, recorded as `format_used` on extracted events.
+    fn name(&self) -> &'static str;
+
+    /// Whether this parser handles `format` (a name from event-formats.toml).
+    fn handles(&self, format: &str) -> bool {
+        format == self.name()
+    }
+
+    /// Parse command output into events.
+    fn parse(&self, output: &str) -> Vec<ParsedEvent>;
+}
+
+/// The built-in pure-Rust parsers, in auto-detection order.
+pub fn fallback_parsers() -> Vec<Box<dyn EventParser>> {
+    vec![
+        Box::new(CargoParser),
+        Box::new(PytestParser),
+        Box::new(EslintParser),
+        Box::new(GccParser),
+    ]
+}
+
+/// Bytes of output inspected by [`detect_format`].
+pub const DETECT_SAMPLE_BYTES: usize = 1024;
+
+/// Guess the format of command output from its first [`DETECT_SAMPLE_BYTES`].
+///
+/// Returns the name of the first parser that finds events in the sample.
+pub fn detect_format(parsers: &[Box<dyn EventParser>], output: &[u8]) -> Option<&'static str> {
+    let sample = match output.get(..DETECT_SAMPLE_BYTES) {
+        // Drop the partial last line so it can't produce a bogus match
+        Some(head) => match head.iter().rposition(|&b| b == b'\n') {
+            Some(end) => &head[..end],
+            None => head,
+        },
+        None => output,
+    };
+
+    let text = String::from_utf8_lossy(sample);
+    parse_output(parsers, "auto", &text).map(|(name, _)| name)
+}
+
+/// Parse `output` as `format` with the first parser that handles it.
+///
+/// For `auto` (or a format no parser handles), every parser is tried in
+/// order and the first one that finds events wins. Returns the name of the
+/// parser used along with its events.
+pub fn parse_output(
+    parsers: &[Box<dyn EventParser>],
+    format: &str,
+    output: &str,
+) -> Option<(&'static str, Vec<ParsedEvent>)> {
+    if let Some(parser) = parsers.iter().find(|p| p.handles(format)) {
+        return Some((parser.name(), parser.parse(output)));
+    }
+
+    parsers.iter().find_map(|parser| {
+        let events = parser.parse(output);
+        (!events.is_empty()).then(|| (parser.name(), events))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_output_explicit_format() {
+        let parsers = fallback_parsers();
+        let (name, events) =
+            parse_output(&parsers, "gcc", "main.c:3:5: error: expected ';'\n").unwrap();
+        assert_eq!(name, "gcc");
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_output_auto_detects() {
+        let parsers = fallback_parsers();
+        let output = "FAILED tests/test_a.py::test_x - AssertionError: boom\n";
+        let (name, events) = parse_output(&parsers, "auto", output).unwrap();
+        assert_eq!(name, "pytest_text");
+        assert_eq!(events[0].status.as_deref(), Some("failed"));
+    }
+
+    #[test]
+    fn test_detect_format_from_content() {
+        let parsers = fallback_parsers();
+        let output = b"error[E0425]: cannot find value `x`\n --> src/main.rs:2:5\n";
+        assert_eq!(detect_format(&parsers, output), Some("cargo_build"));
+        assert_eq!(detect_format(&parsers, b"total 0\n"), None);
+    }
+
+    #[test]
+    fn test_detect_format_only_reads_sample() {
+        let parsers = fallback_parsers();
+        let mut output = "x\n".repeat(DETECT_SAMPLE_BYTES);
+        output.push_str("main.c:3:5: error: expected ';'\n");
+        assert_eq!(detect_format(&parsers, output.as_bytes()), None);
+    }
+
+    #[test]
+    fn test_parse_output_nothing_found() {
+        let parsers = fallback_parsers();
+        assert!(parse_output(&parsers, "auto", "hello world\n").is_none());
+    }
+}
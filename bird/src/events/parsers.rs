@@ -0,0 +1,398 @@
+//! Pure-Rust fallback parsers for common compiler, test and lint output.
+//!
+//! These cover the everyday cases only; duck_hunt remains the preferred
+//! parser when the extension is available.
+
+use super::{EventParser, ParsedEvent};
+
+/// Build a diagnostic event.
+fn diagnostic(
+    severity: &str,
+    message: &str,
+    file: Option<&str>,
+    line: Option<i32>,
+    column: Option<i32>,
+) -> ParsedEvent {
+    ParsedEvent {
+        event_type: Some("diagnostic".to_string()),
+        severity: Some(severity.to_string()),
+        ref_file: file.map(str::to_string),
+        ref_line: line,
+        ref_column: column,
+        message: Some(message.trim().to_string()),
+        ..Default::default()
+    }
+}
+
+/// Build a test result event.
+fn test_result(test_name: &str, status: &str, message: Option<&str>) -> ParsedEvent {
+    let severity = match status {
+        "failed" => "error",
+        "skipped" => "warning",
+        _ => "info",
+    };
+    ParsedEvent {
+        event_type: Some("test_result".to_string()),
+        severity: Some(severity.to_string()),
+        test_name: Some(test_name.to_string()),
+        status: Some(status.to_string()),
+        message: message.map(|m| m.trim().to_string()),
+        ..Default::default()
+    }
+}
+
+/// Split `file:line[:col]` into its parts.
+fn parse_location(location: &str) -> Option<(&str, i32, Option<i32>)> {
+    let mut parts = location.rsplitn(3, ':');
+    let last = parts.next()?.trim();
+    let middle = parts.next()?.trim();
+    match parts.next() {
+        Some(file) => match (middle.parse().ok(), last.parse().ok()) {
+            (Some(line), Some(column)) => Some((file, line, Some(column))),
+            // `file:line` where the file itself contains a colon
+            (None, Some(line)) => Some((location.rsplit_once(':')?.0, line, None)),
+            _ => None,
+        },
+        None => Some((middle, last.parse().ok()?, None)),
+    }
+}
+
+/// GCC/Clang diagnostics: `file:line:col: error: message [-Wflag]`.
+pub struct GccParser;
+
+impl EventParser for GccParser {
+    fn name(&self) -> &'static str {
+        "gcc"
+    }
+
+    fn parse(&self, output: &str) -> Vec<ParsedEvent> {
+        let mut events = Vec::new();
+
+        for line in output.lines() {
+            let Some((severity, location, message)) =
+                ["fatal error", "error", "warning", "note"].iter().find_map(|severity| {
+                    let marker = format!(": {}: ", severity);
+                    line.split_once(&marker).map(|(loc, msg)| (*severity, loc, msg))
+                })
+            else {
+                continue;
+            };
+            let Some((file, line_no, column)) = parse_location(location) else {
+                continue;
+            };
+
+            let severity = if severity == "fatal error" { "error" } else { severity };
+            let mut event = diagnostic(severity, message, Some(file), Some(line_no), column);
+
+            // Trailing [-Wflag] is the warning code
+            if let Some((text, flag)) = message.trim_end().rsplit_once(" [") {
+                if let Some(code) = flag.strip_suffix(']') {
+                    event.message = Some(text.trim().to_string());
+                    event.error_code = Some(code.to_string());
+                }
+            }
+            events.push(event);
+        }
+
+        events
+    }
+}
+
+/// Cargo/rustc diagnostics (`error[E0308]: ...` + `--> file:line:col`)
+/// and `cargo test` results (`test name ... FAILED`).
+pub struct CargoParser;
+
+impl EventParser for CargoParser {
+    fn name(&self) -> &'static str {
+        "cargo_build"
+    }
+
+    fn handles(&self, format: &str) -> bool {
+        matches!(format, "cargo_build" | "rustc" | "cargo_test")
+    }
+
+    fn parse(&self, output: &str) -> Vec<ParsedEvent> {
+        let mut events: Vec<ParsedEvent> = Vec::new();
+        // Index of the diagnostic still waiting for its `-->` location
+        let mut pending: Option<usize> = None;
+
+        for line in output.lines() {
+            let trimmed = line.trim_start();
+
+            if let Some(location) = trimmed.strip_prefix("--> ") {
+                if let Some(index) = pending.take() {
+                    if let Some((file, line_no, column)) = parse_location(location) {
+                        let event = &mut events[index];
+                        event.ref_file = Some(file.to_string());
+                        event.ref_line = Some(line_no);
+                        event.ref_column = column;
+                    }
+                }
+                continue;
+            }
+
+            if let Some(rest) = trimmed.strip_prefix("test ") {
+                if let Some((name, outcome)) = rest.rsplit_once(" ... ") {
+                    let status = match outcome.trim() {
+                        "ok" => "passed",
+                        "FAILED" => "failed",
+                        "ignored" => "skipped",
+                        _ => continue,
+                    };
+                    events.push(test_result(name.trim(), status, None));
+                    pending = None;
+                }
+                continue;
+            }
+
+            // Headers start at column 0: `error[E0308]: msg` / `warning: msg`
+            let Some((severity, rest)) = ["error", "warning"]
+                .iter()
+                .find_map(|s| line.strip_prefix(s).map(|rest| (*s, rest)))
+            else {
+                continue;
+            };
+            let (code, message) = match rest.strip_prefix('[') {
+                Some(rest) => match rest.split_once("]: ") {
+                    Some((code, message)) => (Some(code), message),
+                    None => continue,
+                },
+                None => match rest.strip_prefix(": ") {
+                    Some(message) => (None, message),
+                    None => continue,
+                },
+            };
+
+            // Cargo's closing summaries repeat what was already reported
+            if message.starts_with("aborting due to")
+                || message.starts_with("could not compile")
+                || message.contains("generated ")
+                || message.starts_with("test failed")
+            {
+                pending = None;
+                continue;
+            }
+
+            let mut event = diagnostic(severity, message, None, None, None);
+            event.error_code = code.map(str::to_string);
+            events.push(event);
+            pending = Some(events.len() - 1);
+        }
+
+        events
+    }
+}
+
+/// Pytest text output: `FAILED path::test - msg` summaries and verbose
+/// `path::test PASSED` lines.
+pub struct PytestParser;
+
+impl EventParser for PytestParser {
+    fn name(&self) -> &'static str {
+        "pytest_text"
+    }
+
+    fn handles(&self, format: &str) -> bool {
+        matches!(format, "pytest_text" | "pytest")
+    }
+
+    fn parse(&self, output: &str) -> Vec<ParsedEvent> {
+        let mut events = Vec::new();
+
+        for line in output.lines() {
+            let line = line.trim();
+
+            // Short test summary: `FAILED tests/test_a.py::test_x - AssertionError`
+            if let Some((status, rest)) = [("FAILED ", "failed"), ("ERROR ", "failed")]
+                .iter()
+                .find_map(|(prefix, status)| line.strip_prefix(prefix).map(|rest| (*status, rest)))
+            {
+                let (test, message) = match rest.split_once(" - ") {
+                    Some((test, message)) => (test, Some(message)),
+                    None => (rest, None),
+                };
+                if test.contains("::") {
+                    let mut event = test_result(test.trim(), status, message);
+                    event.ref_file = test.split("::").next().map(str::to_string);
+                    events.push(event);
+                }
+                continue;
+            }
+
+            // Verbose mode: `tests/test_a.py::test_x PASSED   [ 50%]`
+            let Some((test, rest)) = line.split_once(' ') else {
+                continue;
+            };
+            if !test.contains("::") {
+                continue;
+            }
+            let status = match rest.split_whitespace().next() {
+                Some("PASSED") => "passed",
+                Some("FAILED") | Some("ERROR") => "failed",
+                Some("SKIPPED") | Some("XFAIL") => "skipped",
+                _ => continue,
+            };
+            let mut event = test_result(test, status, None);
+            event.ref_file = test.split("::").next().map(str::to_string);
+            events.push(event);
+        }
+
+        events
+    }
+}
+
+/// ESLint's default "stylish" output: a file header line followed by
+/// indented `line:col  severity  message  rule` problem lines.
+pub struct EslintParser;
+
+impl EventParser for EslintParser {
+    fn name(&self) -> &'static str {
+        "eslint"
+    }
+
+    fn parse(&self, output: &str) -> Vec<ParsedEvent> {
+        let mut events = Vec::new();
+        let mut file: Option<&str> = None;
+
+        for line in output.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if !line.starts_with(' ') {
+                // A header names a file; the closing `✖ N problems` does not
+                file = (!line.starts_with('\u{2716}')).then(|| line.trim());
+                continue;
+            }
+
+            let columns: Vec<&str> = line
+                .split("  ")
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .collect();
+            let [location, severity, message, rest @ ..] = columns.as_slice() else {
+                continue;
+            };
+            if !matches!(*severity, "error" | "warning") {
+                continue;
+            }
+            let Some((line_no, column)) = location.split_once(':') else {
+                continue;
+            };
+            let (Ok(line_no), Ok(column)) = (line_no.parse(), column.parse()) else {
+                continue;
+            };
+
+            let mut event = diagnostic(severity, message, file, Some(line_no), Some(column));
+            event.error_code = rest.last().map(|rule| rule.to_string());
+            events.push(event);
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_location() {
+        assert_eq!(parse_location("src/main.rs:4:5"), Some(("src/main.rs", 4, Some(5))));
+        assert_eq!(parse_location("main.c:10"), Some(("main.c", 10, None)));
+        assert_eq!(parse_location("C:/x/main.c:10"), Some(("C:/x/main.c", 10, None)));
+        assert_eq!(parse_location("no location"), None);
+    }
+
+    #[test]
+    fn test_gcc() {
+        let output = "\
+main.c: In function 'main':
+main.c:3:5: error: expected ';' before 'return'
+main.c:2:9: warning: unused variable 'x' [-Wunused-variable]
+cc1: fatal error: missing.c: No such file or directory
+compilation terminated.
+";
+        let events = GccParser.parse(output);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].severity.as_deref(), Some("error"));
+        assert_eq!(events[0].ref_file.as_deref(), Some("main.c"));
+        assert_eq!(events[0].ref_line, Some(3));
+        assert_eq!(events[0].ref_column, Some(5));
+        assert_eq!(events[1].message.as_deref(), Some("unused variable 'x'"));
+        assert_eq!(events[1].error_code.as_deref(), Some("-Wunused-variable"));
+    }
+
+    #[test]
+    fn test_cargo_build() {
+        let output = "\
+   Compiling demo v0.1.0
+error[E0308]: mismatched types
+  --> src/main.rs:4:5
+   |
+4  |     1
+   |     ^ expected `()`, found integer
+warning: unused variable: `x`
+ --> src/lib.rs:2:9
+warning: `demo` (lib) generated 1 warning
+error: could not compile `demo` due to previous error
+";
+        let events = CargoParser.parse(output);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].error_code.as_deref(), Some("E0308"));
+        assert_eq!(events[0].message.as_deref(), Some("mismatched types"));
+        assert_eq!(events[0].ref_file.as_deref(), Some("src/main.rs"));
+        assert_eq!(events[0].ref_line, Some(4));
+        assert_eq!(events[1].severity.as_deref(), Some("warning"));
+        assert_eq!(events[1].ref_file.as_deref(), Some("src/lib.rs"));
+    }
+
+    #[test]
+    fn test_cargo_test() {
+        let output = "\
+running 3 tests
+test tests::adds ... ok
+test tests::fails ... FAILED
+test tests::slow ... ignored
+";
+        let events = CargoParser.parse(output);
+        let statuses: Vec<_> = events.iter().map(|e| e.status.as_deref().unwrap()).collect();
+        assert_eq!(statuses, vec!["passed", "failed", "skipped"]);
+        assert_eq!(events[1].test_name.as_deref(), Some("tests::fails"));
+        assert_eq!(events[1].severity.as_deref(), Some("error"));
+    }
+
+    #[test]
+    fn test_pytest() {
+        let output = "\
+tests/test_a.py::test_ok PASSED                                     [ 50%]
+tests/test_a.py::test_bad FAILED                                    [100%]
+=========================== short test summary info ============================
+FAILED tests/test_a.py::test_bad - AssertionError: assert 1 == 2
+";
+        let events = PytestParser.parse(output);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].status.as_deref(), Some("passed"));
+        assert_eq!(events[2].test_name.as_deref(), Some("tests/test_a.py::test_bad"));
+        assert_eq!(events[2].ref_file.as_deref(), Some("tests/test_a.py"));
+        assert_eq!(events[2].message.as_deref(), Some("AssertionError: assert 1 == 2"));
+    }
+
+    #[test]
+    fn test_eslint() {
+        let output = "
+/home/user/app/src/index.js
+   1:10  error    'x' is defined but never used  no-unused-vars
+  12:1   warning  Unexpected console statement   no-console
+
+\u{2716} 2 problems (1 error, 1 warning)
+";
+        let events = EslintParser.parse(output);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].ref_file.as_deref(), Some("/home/user/app/src/index.js"));
+        assert_eq!(events[0].ref_line, Some(1));
+        assert_eq!(events[0].ref_column, Some(10));
+        assert_eq!(events[0].error_code.as_deref(), Some("no-unused-vars"));
+        assert_eq!(events[1].severity.as_deref(), Some("warning"));
+        assert_eq!(events[1].message.as_deref(), Some("Unexpected console statement"));
+    }
+}
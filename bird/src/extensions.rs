@@ -0,0 +1,316 @@
+//! DuckDB extension management for offline and air-gapped machines.
+//!
+//! BIRD normally installs its extensions from the DuckDB core and community
+//! repositories on first use. Machines without network access can instead
+//! install from a local repository directory produced by [`bundle`] on a
+//! connected machine (same DuckDB version and platform):
+//!
+//! ```text
+//! shq extensions bundle ./bird-extensions          # connected machine
+//! shq extensions install --from ./bird-extensions  # air-gapped machine
+//! ```
+//!
+//! Setting `extensions.repository` in `config.toml` makes every connection
+//! install missing extensions from that repository instead of the network.
+//! The bundle uses DuckDB's repository layout
+//! (`<dir>/<version>/<platform>/<name>.duckdb_extension`), so it can also be
+//! served over HTTP.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use duckdb::Connection;
+
+use crate::{Error, Result};
+
+/// Extensions BIRD loads, with what each one provides.
+pub const EXTENSIONS: &[(&str, &str)] = &[
+    ("parquet", "parquet storage"),
+    ("icu", "time zone handling"),
+    ("httpfs", "S3/HTTP remotes and blobs"),
+    ("json", "JSON metadata"),
+    ("scalarfs", "data: URL support for inline blobs"),
+    ("duck_hunt", "log/output parsing for event extraction"),
+];
+
+/// An optional feature backed by a DuckDB extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// `data:` URL reads for inline blobs.
+    Scalarfs,
+    /// Event extraction from command output.
+    DuckHunt,
+    /// S3/HTTP remotes and blob storage.
+    Httpfs,
+    /// Full-text search over output and events.
+    Fts,
+}
+
+impl Capability {
+    /// Every optional capability, in display order.
+    pub const ALL: [Capability; 4] = [
+        Capability::Scalarfs,
+        Capability::DuckHunt,
+        Capability::Httpfs,
+        Capability::Fts,
+    ];
+
+    /// Name of the DuckDB extension providing this capability.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Capability::Scalarfs => "scalarfs",
+            Capability::DuckHunt => "duck_hunt",
+            Capability::Httpfs => "httpfs",
+            Capability::Fts => "fts",
+        }
+    }
+
+    /// What the capability provides.
+    pub fn description(self) -> &'static str {
+        match self {
+            Capability::Scalarfs => "data: URL support for inline blobs",
+            Capability::DuckHunt => "log/output parsing for event extraction",
+            Capability::Httpfs => "S3/HTTP remotes and blobs",
+            Capability::Fts => "full-text search",
+        }
+    }
+
+    /// Core extensions DuckDB autoloads on first use, so installed is enough.
+    fn autoloads(self) -> bool {
+        matches!(self, Capability::Httpfs | Capability::Fts)
+    }
+}
+
+/// Optional features available on a connection.
+///
+/// BIRD degrades gracefully when an optional extension is missing; these
+/// flags say which features are currently active. Commands that depend on
+/// one should call [`Capabilities::require`] up front rather than failing
+/// (or silently doing nothing) halfway through.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// `data:` URL reads for inline blobs (scalarfs).
+    pub scalarfs: bool,
+    /// Event extraction from command output (duck_hunt).
+    pub duck_hunt: bool,
+    /// S3/HTTP remotes and blobs (httpfs).
+    pub httpfs: bool,
+    /// Full-text search (fts).
+    pub fts: bool,
+}
+
+impl Capabilities {
+    /// Detect which optional extensions are usable on `conn`.
+    pub fn detect(conn: &Connection) -> Result<Self> {
+        let mut stmt =
+            conn.prepare("SELECT extension_name, loaded, installed FROM duckdb_extensions()")?;
+        let extensions: Vec<(String, bool, bool)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<std::result::Result<_, _>>()?;
+        let usable = |cap: Capability| {
+            extensions.iter().any(|(name, loaded, installed)| {
+                name == cap.extension() && (*loaded || (cap.autoloads() && *installed))
+            })
+        };
+
+        Ok(Self {
+            scalarfs: usable(Capability::Scalarfs),
+            duck_hunt: usable(Capability::DuckHunt),
+            httpfs: usable(Capability::Httpfs),
+            fts: usable(Capability::Fts),
+        })
+    }
+
+    /// Whether `capability` is available.
+    pub fn has(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Scalarfs => self.scalarfs,
+            Capability::DuckHunt => self.duck_hunt,
+            Capability::Httpfs => self.httpfs,
+            Capability::Fts => self.fts,
+        }
+    }
+
+    /// Fail with [`Error::ExtensionMissing`] if `capability` is unavailable.
+    pub fn require(&self, capability: Capability) -> Result<()> {
+        if self.has(capability) {
+            return Ok(());
+        }
+        tracing::debug!(extension = capability.extension(), "needed for {}", capability.description());
+        Err(Error::ExtensionMissing { name: capability.extension().to_string() })
+    }
+}
+
+/// Outcome of installing one extension from a repository.
+#[derive(Debug, Clone)]
+pub struct InstallResult {
+    pub name: String,
+    /// Error message if the install or load failed.
+    pub error: Option<String>,
+}
+
+/// Copy the installed BIRD extensions into a repository directory at `dir`.
+///
+/// Returns the paths written. Extensions that are not installed locally (or
+/// are built into DuckDB) are skipped.
+pub fn bundle(dir: &Path) -> Result<Vec<PathBuf>> {
+    let conn = Connection::open_in_memory()?;
+    let target = dir.join(repository_subdir(&conn)?);
+    fs::create_dir_all(&target)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT extension_name, install_path FROM duckdb_extensions() WHERE installed",
+    )?;
+    let installed: Vec<(String, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<_, _>>()?;
+
+    let mut written = Vec::new();
+    for (name, install_path) in installed {
+        let source = Path::new(&install_path);
+        if !EXTENSIONS.iter().any(|(ext, _)| *ext == name) || !source.is_file() {
+            continue;
+        }
+        let dest = target.join(format!("{}.duckdb_extension", name));
+        fs::copy(source, &dest)?;
+        written.push(dest);
+    }
+
+    Ok(written)
+}
+
+/// Install (and load) every BIRD extension from `repository`.
+///
+/// `repository` is a directory produced by [`bundle`] or a URL serving the
+/// same layout. Extensions missing from it are reported, not fatal.
+pub fn install_from(repository: &str) -> Result<Vec<InstallResult>> {
+    let conn = Connection::open_in_memory()?;
+    conn.execute("SET allow_community_extensions = true", [])?;
+
+    let results = EXTENSIONS
+        .iter()
+        .map(|(name, _)| {
+            let error = conn
+                .execute(&format!("FORCE INSTALL {} FROM {}", name, quote(repository)), [])
+                .and_then(|_| conn.execute(&format!("LOAD {}", name), []))
+                .err()
+                .map(|e| e.to_string());
+            InstallResult {
+                name: name.to_string(),
+                error,
+            }
+        })
+        .collect();
+
+    Ok(results)
+}
+
+/// Ensure a DuckDB extension is loaded, installing it if necessary.
+///
+/// Attempts in order:
+/// 1. LOAD (extension might already be available)
+/// 2. INSTALL from `repository`, if configured, then LOAD
+/// 3. INSTALL from default repository, then LOAD
+/// 4. INSTALL FROM community, then LOAD
+///
+/// Returns Ok(true) if loaded successfully, Ok(false) if extension unavailable.
+pub(crate) fn ensure_extension(
+    conn: &Connection,
+    name: &str,
+    repository: Option<&str>,
+) -> Result<bool> {
+    // Try loading directly first (already installed/cached)
+    match conn.execute(&format!("LOAD {}", name), []) {
+        Ok(_) => return Ok(true),
+        Err(e) => tracing::debug!(extension = name, "LOAD failed, installing: {}", e),
+    }
+
+    let mut installs = Vec::new();
+    if let Some(repository) = repository {
+        installs.push(format!("INSTALL {} FROM {}", name, quote(repository)));
+    }
+    installs.push(format!("INSTALL {}", name));
+    installs.push(format!("INSTALL {} FROM community", name));
+
+    for install in installs {
+        let result = conn
+            .execute(&install, [])
+            .and_then(|_| conn.execute(&format!("LOAD {}", name), []));
+        match result {
+            Ok(_) => {
+                tracing::debug!(extension = name, "loaded after `{}`", install);
+                return Ok(true);
+            }
+            Err(e) => tracing::debug!(extension = name, "`{}` failed: {}", install, e),
+        }
+    }
+
+    Ok(false)
+}
+
+/// `<version>/<platform>` of the running DuckDB, e.g. `v1.4.4/linux_amd64`.
+fn repository_subdir(conn: &Connection) -> Result<PathBuf> {
+    let version: String =
+        conn.query_row("SELECT library_version FROM pragma_version()", [], |row| row.get(0))?;
+    let platform: String =
+        conn.query_row("SELECT platform FROM pragma_platform()", [], |row| row.get(0))?;
+    Ok(Path::new(&version).join(platform))
+}
+
+/// Quote a repository path or URL as a SQL string literal.
+fn quote(repository: &str) -> String {
+    format!("'{}'", repository.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_escapes() {
+        assert_eq!(quote("/opt/ext"), "'/opt/ext'");
+        assert_eq!(quote("/tmp/o'brien"), "'/tmp/o''brien'");
+    }
+
+    #[test]
+    fn test_repository_subdir_layout() {
+        let conn = Connection::open_in_memory().unwrap();
+        let subdir = repository_subdir(&conn).unwrap();
+        let parts: Vec<_> = subdir.iter().map(|p| p.to_string_lossy().to_string()).collect();
+        assert_eq!(parts.len(), 2);
+        assert!(parts[0].starts_with('v'), "version looks like v1.x: {:?}", parts);
+    }
+
+    #[test]
+    fn test_capabilities_detect_bare_connection() {
+        let conn = Connection::open_in_memory().unwrap();
+        // Community extensions only count once loaded (httpfs/fts may be
+        // installed on this machine and would autoload)
+        let caps = Capabilities::detect(&conn).unwrap();
+        assert!(!caps.scalarfs);
+        assert!(!caps.duck_hunt);
+    }
+
+    #[test]
+    fn test_require_names_missing_extension() {
+        let caps = Capabilities {
+            duck_hunt: true,
+            ..Default::default()
+        };
+        assert!(caps.require(Capability::DuckHunt).is_ok());
+
+        let err = caps.require(Capability::Httpfs).unwrap_err();
+        assert!(matches!(&err, Error::ExtensionMissing { name } if name == "httpfs"), "{}", err);
+        assert!(err.hint().unwrap().contains("shq extensions install"));
+    }
+
+    #[test]
+    fn test_bundle_writes_into_target_dir() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        // Whatever is installed locally ends up as files under the target
+        for path in bundle(tmp.path()).unwrap() {
+            assert!(path.is_file());
+            assert!(path.starts_with(tmp.path()));
+        }
+    }
+}
@@ -18,10 +18,14 @@
 //! [format-hints.100]
 //! "legacy-*" = "text"
 //! ```
+//!
+//! Hints can be shared as TOML (see [`FormatHints::merge`]) and installed
+//! from the built-in [`PACKS`].
 
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 
@@ -30,6 +34,123 @@ use crate::{Error, Result};
 /// Default priority for simple pattern = "format" entries.
 pub const DEFAULT_PRIORITY: i32 = 500;
 
+/// How imported hints combine with the existing ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    /// Imported hints win when a pattern already exists.
+    #[default]
+    Overwrite,
+    /// Existing hints win; only new patterns are added.
+    KeepExisting,
+    /// Discard all existing hints first.
+    Replace,
+}
+
+impl FromStr for MergeStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "overwrite" | "theirs" => Ok(MergeStrategy::Overwrite),
+            "keep" | "keep-existing" | "ours" => Ok(MergeStrategy::KeepExisting),
+            "replace" => Ok(MergeStrategy::Replace),
+            _ => Err(Error::Config(format!(
+                "Invalid merge strategy '{}': expected 'overwrite', 'keep', or 'replace'",
+                s
+            ))),
+        }
+    }
+}
+
+/// Counts of what a merge changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergeSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
+/// A named set of hints shipped with BIRD (e.g. "python", "js").
+#[derive(Debug, Clone, Copy)]
+pub struct HintPack {
+    pub name: &'static str,
+    pub description: &'static str,
+    toml: &'static str,
+}
+
+impl HintPack {
+    /// Parse the pack's hints.
+    pub fn hints(&self) -> Result<FormatHints> {
+        FormatHints::parse(self.toml)
+    }
+}
+
+/// Built-in hint packs, installable with `shq format-hints install <pack>`.
+pub const PACKS: &[HintPack] = &[
+    HintPack {
+        name: "c",
+        description: "GCC, Clang, Make, CMake, Ninja",
+        toml: r#"
+[format-hints]
+"gcc*" = "gcc"
+"g++*" = "gcc"
+"clang*" = "gcc"
+"cc *" = "gcc"
+"make*" = "make_error"
+"cmake*" = "cmake"
+"ninja*" = "ninja"
+"#,
+    },
+    HintPack {
+        name: "go",
+        description: "go build, go test, go vet",
+        toml: r#"
+[format-hints]
+"go build*" = "go_build"
+"go vet*" = "go_build"
+"go test*" = "go_test"
+"#,
+    },
+    HintPack {
+        name: "js",
+        description: "ESLint, TypeScript, Jest",
+        toml: r#"
+[format-hints]
+"*eslint*" = "eslint"
+"tsc*" = "typescript"
+"*jest*" = "jest"
+"#,
+    },
+    HintPack {
+        name: "python",
+        description: "pytest, mypy, flake8, pylint",
+        toml: r#"
+[format-hints]
+"*pytest*" = "pytest_text"
+"*mypy*" = "mypy"
+"*flake8*" = "flake8"
+"*pylint*" = "pylint"
+"#,
+    },
+    HintPack {
+        name: "rust",
+        description: "cargo build/check/clippy/test, rustc",
+        toml: r#"
+[format-hints]
+"cargo build*" = "cargo_build"
+"cargo check*" = "cargo_build"
+"cargo clippy*" = "cargo_build"
+"cargo test*" = "cargo_build"
+"rustc*" = "rustc"
+"#,
+    },
+];
+
+/// Look up a built-in hint pack by name.
+pub fn pack(name: &str) -> Option<&'static HintPack> {
+    PACKS.iter().find(|p| p.name == name)
+}
+
 /// A single format hint rule.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct FormatHint {
@@ -252,6 +373,42 @@ impl FormatHints {
         self.hints.iter().find(|h| h.pattern == pattern)
     }
 
+    /// Merge `other` into these hints (used by import and pack install).
+    ///
+    /// A non-`auto` default format in `other` is taken unless the strategy
+    /// is [`MergeStrategy::KeepExisting`].
+    pub fn merge(&mut self, other: &FormatHints, strategy: MergeStrategy) -> MergeSummary {
+        let mut summary = MergeSummary::default();
+
+        if strategy == MergeStrategy::Replace {
+            self.hints.clear();
+        }
+
+        for hint in &other.hints {
+            match self.get(&hint.pattern) {
+                None => summary.added += 1,
+                Some(existing) if existing == hint => {
+                    summary.skipped += 1;
+                    continue;
+                }
+                Some(_) if strategy == MergeStrategy::KeepExisting => {
+                    summary.skipped += 1;
+                    continue;
+                }
+                Some(_) => summary.updated += 1,
+            }
+            self.add(hint.clone());
+        }
+
+        if other.default_format != "auto"
+            && (strategy != MergeStrategy::KeepExisting || self.default_format == "auto")
+        {
+            self.default_format = other.default_format.clone();
+        }
+
+        summary
+    }
+
     /// Detect format for a command string.
     /// Returns the format from the highest-priority matching hint, or default.
     pub fn detect(&self, cmd: &str) -> &str {
@@ -510,6 +667,59 @@ format = "text"
         assert_eq!(parsed.get("legacy-*").unwrap().priority, 100);
     }
 
+    #[test]
+    fn test_merge_strategies() {
+        let mut base = FormatHints::new();
+        base.add(FormatHint::new("*pytest*", "pytest_json"));
+        base.add(FormatHint::new("mybuild*", "gcc"));
+
+        let incoming = pack("python").unwrap().hints().unwrap();
+
+        let mut kept = base.clone();
+        let summary = kept.merge(&incoming, MergeStrategy::KeepExisting);
+        assert_eq!(summary, MergeSummary { added: 3, updated: 0, skipped: 1 });
+        assert_eq!(kept.detect("pytest tests/"), "pytest_json");
+
+        let mut overwritten = base.clone();
+        let summary = overwritten.merge(&incoming, MergeStrategy::Overwrite);
+        assert_eq!(summary, MergeSummary { added: 3, updated: 1, skipped: 0 });
+        assert_eq!(overwritten.detect("pytest tests/"), "pytest_text");
+        assert_eq!(overwritten.detect("mybuild all"), "gcc");
+
+        let mut replaced = base.clone();
+        replaced.merge(&incoming, MergeStrategy::Replace);
+        assert_eq!(replaced.hints().len(), 4);
+        assert_eq!(replaced.detect("mybuild all"), "auto");
+    }
+
+    #[test]
+    fn test_merge_strategy_from_str() {
+        assert_eq!("keep".parse::<MergeStrategy>().unwrap(), MergeStrategy::KeepExisting);
+        assert_eq!("Replace".parse::<MergeStrategy>().unwrap(), MergeStrategy::Replace);
+        assert!("union".parse::<MergeStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_packs_parse() {
+        for p in PACKS {
+            let hints = p.hints().unwrap();
+            assert!(!hints.hints().is_empty(), "pack {} is empty", p.name);
+        }
+        assert!(pack("cobol").is_none());
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let mut hints = FormatHints::new();
+        hints.add(FormatHint::with_priority("ci-*", "gcc", 900));
+        hints.set_default_format("text");
+
+        let mut imported = FormatHints::new();
+        imported.merge(&FormatHints::parse(&hints.to_toml()).unwrap(), MergeStrategy::Overwrite);
+        assert_eq!(imported.hints(), hints.hints());
+        assert_eq!(imported.default_format(), "text");
+    }
+
     #[test]
     fn test_pattern_matches() {
         assert!(pattern_matches("*gcc*", "gcc -o foo foo.c"));
@@ -0,0 +1,283 @@
+//! The shell's own history file (`HISTFILE`), read and written.
+//!
+//! BIRD can be the source of truth for history while tools that read
+//! `~/.zsh_history` or `~/.bash_history` (fzf, `history`, prompt plugins)
+//! keep working: with `[histfile] write` set, every captured command is
+//! appended to a history file, and `shq histfile import` ingests entries
+//! from history files as commands without output.
+//!
+//! Three formats are understood:
+//! - `zsh`: extended history, `: START:ELAPSED;COMMAND`, with multi-line
+//!   commands continued by a trailing backslash and non-ASCII bytes
+//!   "metafied" the way zsh writes them. Plain zsh lines are read too.
+//! - `bash`: commands with `#EPOCH` timestamp lines (`HISTTIMEFORMAT`).
+//! - `plain`: one command per line.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// zsh's escape byte for metafied characters.
+const META: u8 = 0x83;
+
+/// A history file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistFormat {
+    Zsh,
+    Bash,
+    Plain,
+}
+
+impl HistFormat {
+    /// The format a history file at `path` is likely in: zsh for a name
+    /// containing "zsh" (`.zsh_history`, `.zhistory`), bash otherwise.
+    pub fn for_path(path: &Path) -> Self {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+        if name.contains("zsh") || name.contains("zhistory") {
+            HistFormat::Zsh
+        } else {
+            HistFormat::Bash
+        }
+    }
+}
+
+impl std::str::FromStr for HistFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "zsh" => Ok(HistFormat::Zsh),
+            "bash" => Ok(HistFormat::Bash),
+            "plain" => Ok(HistFormat::Plain),
+            _ => Err(Error::Config(format!(
+                "Invalid history format '{}': expected 'zsh', 'bash' or 'plain'",
+                s
+            ))),
+        }
+    }
+}
+
+/// One history entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistEntry {
+    pub command: String,
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Seconds it ran (zsh extended history).
+    pub elapsed_secs: Option<i64>,
+    /// Byte offset of the entry in the data it was parsed from.
+    pub offset: usize,
+}
+
+/// Parse history entries from `data`, which starts at a line boundary.
+/// A trailing line without its newline (a write in progress) is left out.
+pub fn parse(data: &[u8], format: HistFormat) -> Vec<HistEntry> {
+    let complete = data.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    let mut entries: Vec<HistEntry> = Vec::new();
+    // bash: the next command's timestamp, and whether the file has any (then
+    // lines up to the next timestamp belong to one multi-line command)
+    let mut timestamp = None;
+    let mut timestamped = false;
+    let mut pos = 0;
+
+    while pos < complete {
+        let start = pos;
+        let mut line = next_line(data, &mut pos).to_vec();
+        match format {
+            HistFormat::Zsh => {
+                // A trailing backslash continues the command on the next line
+                while line.ends_with(b"\\") && pos < complete {
+                    line.pop();
+                    line.push(b'\n');
+                    line.extend_from_slice(next_line(data, &mut pos));
+                }
+                let text = String::from_utf8_lossy(&unmetafy(&line)).to_string();
+                entries.push(parse_zsh_line(&text, start));
+            }
+            HistFormat::Bash => {
+                let text = String::from_utf8_lossy(&line).to_string();
+                if let Some(epoch) = text.strip_prefix('#').and_then(|t| t.trim().parse::<i64>().ok()) {
+                    timestamp = Utc.timestamp_opt(epoch, 0).single();
+                    timestamped = true;
+                    continue;
+                }
+                match entries.last_mut() {
+                    Some(last) if timestamped && timestamp.is_none() => {
+                        last.command.push('\n');
+                        last.command.push_str(&text);
+                    }
+                    _ => entries.push(HistEntry { command: text, timestamp: timestamp.take(), elapsed_secs: None, offset: start }),
+                }
+            }
+            HistFormat::Plain => {
+                let command = String::from_utf8_lossy(&line).to_string();
+                entries.push(HistEntry { command, timestamp: None, elapsed_secs: None, offset: start });
+            }
+        }
+    }
+    entries.retain(|e| !e.command.trim().is_empty());
+    entries
+}
+
+/// Format `entry` as it would appear in a history file.
+pub fn format(entry: &HistEntry, format: HistFormat) -> Vec<u8> {
+    let mut out = Vec::new();
+    match format {
+        HistFormat::Zsh => {
+            let start = entry.timestamp.map_or(0, |t| t.timestamp());
+            out.extend_from_slice(format!(": {}:{};", start, entry.elapsed_secs.unwrap_or(0)).as_bytes());
+            out.extend_from_slice(&metafy(entry.command.replace('\n', "\\\n").as_bytes()));
+        }
+        HistFormat::Bash => {
+            if let Some(t) = entry.timestamp {
+                out.extend_from_slice(format!("#{}\n", t.timestamp()).as_bytes());
+            }
+            out.extend_from_slice(entry.command.as_bytes());
+        }
+        HistFormat::Plain => out.extend_from_slice(entry.command.as_bytes()),
+    }
+    out.push(b'\n');
+    out
+}
+
+/// Append `entries` to the history file at `path` in one write, creating it
+/// owner-only if needed.
+pub fn append(path: &Path, fmt: HistFormat, entries: &[HistEntry]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let data: Vec<u8> = entries.iter().flat_map(|e| format(e, fmt)).collect();
+    let mut options = std::fs::OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(crate::perms::FILE_MODE);
+    }
+    // One write with O_APPEND, so it doesn't interleave with the shell's own
+    options.open(path)?.write_all(&data)?;
+    Ok(())
+}
+
+/// `path` with a leading `~/` expanded to the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn parse_zsh_line(text: &str, offset: usize) -> HistEntry {
+    // `: 1700000000:12;make test`
+    let extended = text.strip_prefix(": ").and_then(|rest| {
+        let (meta, command) = rest.split_once(';')?;
+        let (start, elapsed) = meta.split_once(':')?;
+        Some((start.parse::<i64>().ok()?, elapsed.parse::<i64>().ok()?, command))
+    });
+    match extended {
+        Some((start, elapsed, command)) => HistEntry {
+            command: command.to_string(),
+            timestamp: Utc.timestamp_opt(start, 0).single(),
+            elapsed_secs: Some(elapsed),
+            offset,
+        },
+        None => HistEntry { command: text.to_string(), timestamp: None, elapsed_secs: None, offset },
+    }
+}
+
+fn next_line<'a>(data: &'a [u8], pos: &mut usize) -> &'a [u8] {
+    let rest = &data[*pos..];
+    let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+    *pos += (end + 1).min(rest.len());
+    &rest[..end]
+}
+
+/// Bytes zsh stores escaped: NUL and its internal tokens, META..=0xa2.
+fn is_meta(b: u8) -> bool {
+    b == 0 || (META..=0xa2).contains(&b)
+}
+
+fn metafy(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    for &b in bytes {
+        if is_meta(b) {
+            out.extend_from_slice(&[META, b ^ 32]);
+        } else {
+            out.push(b);
+        }
+    }
+    out
+}
+
+fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&b) = iter.next() {
+        match (b, iter.clone().next()) {
+            (META, Some(&next)) => {
+                out.push(next ^ 32);
+                iter.next();
+            }
+            _ => out.push(b),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, epoch: Option<i64>, elapsed: Option<i64>) -> HistEntry {
+        HistEntry {
+            command: command.to_string(),
+            timestamp: epoch.and_then(|e| Utc.timestamp_opt(e, 0).single()),
+            elapsed_secs: elapsed,
+            offset: 0,
+        }
+    }
+
+    #[test]
+    fn test_zsh_round_trip() {
+        let entries = [entry("make test", Some(1_700_000_000), Some(12)), entry("echo 'a\nb' # café", Some(1_700_000_020), Some(0))];
+        let data: Vec<u8> = entries.iter().flat_map(|e| format(e, HistFormat::Zsh)).collect();
+        assert!(data.starts_with(b": 1700000000:12;make test\n: 1700000020:0;echo 'a\\\nb'"));
+        // zsh escapes its own token bytes (é's UTF-8 bytes aren't among them)
+        assert_eq!(metafy(&[0x83, b'x']), [META, 0xa3, b'x']);
+
+        let parsed = parse(&data, HistFormat::Zsh);
+        let summary: Vec<_> = parsed.iter().map(|e| (e.command.as_str(), e.timestamp, e.elapsed_secs)).collect();
+        let expected: Vec<_> = entries.iter().map(|e| (e.command.as_str(), e.timestamp, e.elapsed_secs)).collect();
+        assert_eq!(summary, expected);
+        assert_eq!(parsed[1].offset, ": 1700000000:12;make test\n".len());
+    }
+
+    #[test]
+    fn test_bash_timestamps() {
+        let data = b"ls\n#1700000000\nfor f in *; do\n  echo $f\ndone\n#1700000005\nmake\n";
+        let parsed = parse(data, HistFormat::Bash);
+        let summary: Vec<_> = parsed.iter().map(|e| (e.command.as_str(), e.timestamp.map(|t| t.timestamp()))).collect();
+        assert_eq!(
+            summary,
+            [("ls", None), ("for f in *; do\n  echo $f\ndone", Some(1_700_000_000)), ("make", Some(1_700_000_005))]
+        );
+        assert_eq!(format(&parsed[2], HistFormat::Bash), b"#1700000005\nmake\n");
+    }
+
+    #[test]
+    fn test_partial_line_is_left_out() {
+        let parsed = parse(b"ls\nmak", HistFormat::Plain);
+        assert_eq!(parsed.len(), 1);
+    }
+
+    #[test]
+    fn test_format_for_path() {
+        assert_eq!(HistFormat::for_path(Path::new("/home/me/.zsh_history")), HistFormat::Zsh);
+        assert_eq!(HistFormat::for_path(Path::new("/home/me/.bash_history")), HistFormat::Bash);
+        assert_eq!("plain".parse::<HistFormat>().unwrap(), HistFormat::Plain);
+    }
+}
@@ -102,16 +102,28 @@ fn create_directories(config: &Config) -> Result<()> {
     Ok(())
 }
 
+/// Create the schema of an in-memory store (see [`Config::in_memory`]) in
+/// a fresh in-memory database, returning its connection.
+pub fn initialize_in_memory(config: &Config) -> Result<duckdb::Connection> {
+    let conn = duckdb::Connection::open_in_memory()?;
+    create_schema(&conn, config)?;
+    Ok(conn)
+}
+
 /// Initialize the DuckDB database with schema architecture.
 fn init_database(config: &Config) -> Result<()> {
     let conn = duckdb::Connection::open(config.db_path())?;
+    create_schema(&conn, config)
+}
 
+/// Create the schema architecture in `conn`'s database.
+fn create_schema(conn: &duckdb::Connection, config: &Config) -> Result<()> {
     // Enable community extensions
     conn.execute("SET allow_community_extensions = true", [])?;
 
     // Install and load required extensions
     // This pre-installs to the default location so connect() is fast
-    install_extensions(&conn)?;
+    install_extensions(conn, config.extensions.repository.as_deref())?;
 
     // Set file search path so views use relative paths
     let data_dir = config.data_dir();
@@ -121,39 +133,39 @@ fn init_database(config: &Config) -> Result<()> {
     )?;
 
     // Create core schemas
-    create_core_schemas(&conn)?;
+    create_core_schemas(conn)?;
 
     // Create bird_meta table for schema versioning (v5)
-    create_bird_meta(&conn)?;
+    create_bird_meta(conn)?;
 
     // Create blob_registry table in main schema (used by both modes)
-    create_blob_registry(&conn)?;
+    create_blob_registry(conn)?;
 
     // Mode-specific initialization for local schema
     match config.storage_mode {
         StorageMode::Parquet => {
             // Create seed parquet files with correct schema but no rows (v5: attempts, outcomes)
-            create_seed_files(&conn, config)?;
+            create_seed_files(conn, config)?;
             // Create local schema with views over parquet files (v5: attempts, outcomes tables)
-            create_local_parquet_views(&conn)?;
+            create_local_parquet_views(conn)?;
         }
         StorageMode::DuckDB => {
             // Create local schema with tables for direct storage (v5: attempts, outcomes tables)
-            create_local_tables(&conn)?;
+            create_local_tables(conn)?;
         }
     }
 
     // Create placeholder schemas (for empty unions)
-    create_placeholder_schemas(&conn)?;
+    create_placeholder_schemas(conn)?;
 
     // Create union schemas (caches, remotes, main, bird)
-    create_union_schemas(&conn)?;
+    create_union_schemas(conn)?;
 
     // Create helper views in main schema
-    create_helper_views(&conn)?;
+    create_helper_views(conn)?;
 
     // Create cwd schema views (placeholders, rebuilt at connection time)
-    create_cwd_views(&conn)?;
+    create_cwd_views(conn)?;
 
     Ok(())
 }
@@ -693,7 +705,8 @@ fn create_local_tables(conn: &duckdb::Connection) -> Result<()> {
             storage_type VARCHAR,
             storage_ref VARCHAR,
             content_type VARCHAR,
-            date DATE
+            date DATE,
+            sequence INTEGER
         );
 
         -- Events table
@@ -712,7 +725,8 @@ fn create_local_tables(conn: &duckdb::Connection) -> Result<()> {
             test_name VARCHAR,
             status VARCHAR,
             format_used VARCHAR,
-            date DATE
+            date DATE,
+            origin VARCHAR
         );
         "#,
     )?;
@@ -797,6 +811,14 @@ fn create_bird_meta(conn: &duckdb::Connection) -> Result<()> {
         -- Insert schema version
         INSERT INTO bird_meta (key, value, updated_at) VALUES ('schema_version', '5', now())
         ON CONFLICT (key) DO UPDATE SET value = '5', updated_at = now();
+
+        -- Created with outputs.sequence (see ensure_output_sequence)
+        INSERT INTO bird_meta (key, value, updated_at) VALUES ('outputs_sequence', '1', now())
+        ON CONFLICT (key) DO NOTHING;
+
+        -- Created with events.origin (see ensure_event_origin)
+        INSERT INTO bird_meta (key, value, updated_at) VALUES ('events_origin', '1', now())
+        ON CONFLICT (key) DO NOTHING;
         "#,
     )?;
     Ok(())
@@ -823,32 +845,12 @@ fn create_cwd_views(conn: &duckdb::Connection) -> Result<()> {
 
 /// Ensure a DuckDB extension is loaded, installing if necessary.
 ///
-/// Attempts in order:
-/// 1. LOAD (extension might already be available)
-/// 2. INSTALL from default repository, then LOAD
-/// 3. INSTALL FROM community, then LOAD
-///
 /// Includes retry logic to handle race conditions when multiple processes
 /// try to install extensions concurrently.
-fn ensure_extension(conn: &duckdb::Connection, name: &str) -> Result<bool> {
+fn ensure_extension(conn: &duckdb::Connection, name: &str, repository: Option<&str>) -> Result<bool> {
     // Retry up to 3 times to handle concurrent installation races
     for attempt in 0..3 {
-        // Try loading directly first (already installed/cached)
-        if conn.execute(&format!("LOAD {}", name), []).is_ok() {
-            return Ok(true);
-        }
-
-        // Try installing from default repository
-        if conn.execute(&format!("INSTALL {}", name), []).is_ok()
-            && conn.execute(&format!("LOAD {}", name), []).is_ok()
-        {
-            return Ok(true);
-        }
-
-        // Try installing from community repository
-        if conn.execute(&format!("INSTALL {} FROM community", name), []).is_ok()
-            && conn.execute(&format!("LOAD {}", name), []).is_ok()
-        {
+        if crate::extensions::ensure_extension(conn, name, repository)? {
             return Ok(true);
         }
 
@@ -863,14 +865,11 @@ fn ensure_extension(conn: &duckdb::Connection, name: &str) -> Result<bool> {
 
 /// Install and load all required extensions during initialization.
 /// This pre-populates the extension cache so connect() is fast.
-fn install_extensions(conn: &duckdb::Connection) -> Result<()> {
+fn install_extensions(conn: &duckdb::Connection, repository: Option<&str>) -> Result<()> {
     // Required extensions - fail if not available
     for name in ["parquet", "icu", "httpfs", "json"] {
-        if !ensure_extension(conn, name)? {
-            return Err(Error::Config(format!(
-                "Required extension '{}' could not be installed",
-                name
-            )));
+        if !ensure_extension(conn, name, repository)? {
+            return Err(Error::ExtensionMissing { name: name.to_string() });
         }
     }
 
@@ -879,8 +878,8 @@ fn install_extensions(conn: &duckdb::Connection) -> Result<()> {
         ("scalarfs", "data: URL support for inline blobs"),
         ("duck_hunt", "log/output parsing for event extraction"),
     ] {
-        if !ensure_extension(conn, name)? {
-            eprintln!("Warning: {} extension not available ({})", name, desc);
+        if !ensure_extension(conn, name, repository)? {
+            tracing::warn!(extension = name, "{} extension not available ({})", name, desc);
         }
     }
 
@@ -962,28 +961,7 @@ fn create_seed_files(conn: &duckdb::Connection, config: &Config) -> Result<()> {
     ))?;
 
     // Create outputs seed
-    let outputs_seed_dir = config.recent_dir().join("outputs").join("date=1970-01-01");
-    fs::create_dir_all(&outputs_seed_dir)?;
-
-    let outputs_seed_path = outputs_seed_dir.join("_seed.parquet");
-    conn.execute_batch(&format!(
-        r#"
-        COPY (
-            SELECT
-                NULL::UUID as id,
-                NULL::UUID as invocation_id,
-                NULL::VARCHAR as stream,
-                NULL::VARCHAR as content_hash,
-                NULL::BIGINT as byte_length,
-                NULL::VARCHAR as storage_type,
-                NULL::VARCHAR as storage_ref,
-                NULL::VARCHAR as content_type,
-                NULL::DATE as date
-            WHERE false
-        ) TO '{}' (FORMAT PARQUET);
-        "#,
-        outputs_seed_path.display()
-    ))?;
+    write_outputs_seed(conn, config)?;
 
     // Create sessions seed
     let sessions_seed_dir = config.recent_dir().join("sessions").join("date=1970-01-01");
@@ -1008,11 +986,50 @@ fn create_seed_files(conn: &duckdb::Connection, config: &Config) -> Result<()> {
         sessions_seed_path.display()
     ))?;
 
-    // Create events seed
+    write_events_seed(conn, config)?;
+
+    Ok(())
+}
+
+/// Write the empty outputs seed file that fixes the outputs schema.
+fn write_outputs_seed(conn: &duckdb::Connection, config: &Config) -> Result<()> {
+    let outputs_seed_dir = config.recent_dir().join("outputs").join("date=1970-01-01");
+    fs::create_dir_all(&outputs_seed_dir)?;
+
+    // Written next to the seed and renamed over it: snapshots hard-link it
+    let outputs_seed_path = outputs_seed_dir.join("_seed.parquet");
+    let temp_path = outputs_seed_dir.join("._seed.parquet.tmp");
+    conn.execute_batch(&format!(
+        r#"
+        COPY (
+            SELECT
+                NULL::UUID as id,
+                NULL::UUID as invocation_id,
+                NULL::VARCHAR as stream,
+                NULL::VARCHAR as content_hash,
+                NULL::BIGINT as byte_length,
+                NULL::VARCHAR as storage_type,
+                NULL::VARCHAR as storage_ref,
+                NULL::VARCHAR as content_type,
+                NULL::DATE as date,
+                NULL::INTEGER as sequence
+            WHERE false
+        ) TO '{}' (FORMAT PARQUET);
+        "#,
+        temp_path.display()
+    ))?;
+    fs::rename(&temp_path, &outputs_seed_path)?;
+    Ok(())
+}
+
+/// Write the empty events seed file that fixes the events schema.
+fn write_events_seed(conn: &duckdb::Connection, config: &Config) -> Result<()> {
     let events_seed_dir = config.recent_dir().join("events").join("date=1970-01-01");
     fs::create_dir_all(&events_seed_dir)?;
 
+    // Written next to the seed and renamed over it: snapshots hard-link it
     let events_seed_path = events_seed_dir.join("_seed.parquet");
+    let temp_path = events_seed_dir.join("._seed.parquet.tmp");
     conn.execute_batch(&format!(
         r#"
         COPY (
@@ -1031,13 +1048,119 @@ fn create_seed_files(conn: &duckdb::Connection, config: &Config) -> Result<()> {
                 NULL::VARCHAR as test_name,
                 NULL::VARCHAR as status,
                 NULL::VARCHAR as format_used,
-                NULL::DATE as date
+                NULL::DATE as date,
+                NULL::VARCHAR as origin
             WHERE false
         ) TO '{}' (FORMAT PARQUET);
         "#,
-        events_seed_path.display()
+        temp_path.display()
     ))?;
+    fs::rename(&temp_path, &events_seed_path)?;
+    Ok(())
+}
 
+/// Add `outputs.sequence` to stores initialized before it existed.
+///
+/// Rows written before then have no sequence; readers number them by id.
+/// The views over outputs are recreated, since DuckDB rejects views whose
+/// underlying columns changed.
+pub(crate) fn ensure_output_sequence(conn: &duckdb::Connection, config: &Config) -> Result<()> {
+    let done: bool = conn
+        .query_row(
+            "SELECT COUNT(*) > 0 FROM bird_meta WHERE key = 'outputs_sequence'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(true);
+    if done {
+        return Ok(());
+    }
+
+    match config.storage_mode {
+        StorageMode::Parquet => {
+            write_outputs_seed(conn, config)?;
+            create_local_parquet_views(conn)?;
+        }
+        StorageMode::DuckDB => {
+            conn.execute("ALTER TABLE local.outputs ADD COLUMN IF NOT EXISTS sequence INTEGER", [])?;
+        }
+    }
+    conn.execute_batch(
+        r#"
+        CREATE OR REPLACE VIEW main.outputs AS
+            SELECT *, 'local' as _source FROM local.outputs
+            UNION ALL BY NAME SELECT * FROM caches.outputs;
+        CREATE OR REPLACE VIEW unified.outputs AS
+            SELECT * FROM main.outputs
+            UNION ALL BY NAME SELECT * FROM remotes.outputs;
+        CREATE OR REPLACE VIEW unified.qualified_outputs AS
+            SELECT * EXCLUDE (_source), list(DISTINCT _source) as _sources
+            FROM unified.outputs
+            GROUP BY ALL;
+        CREATE OR REPLACE VIEW cwd.outputs AS
+        SELECT * FROM main.outputs WHERE false;
+
+        INSERT INTO bird_meta (key, value, updated_at) VALUES ('outputs_sequence', '1', now())
+        ON CONFLICT (key) DO NOTHING;
+        "#,
+    )?;
+    Ok(())
+}
+
+/// Add `events.origin` to stores initialized before it existed.
+///
+/// Rows written before then have no origin; readers treat NULL as
+/// `parser`. The views over events are recreated, as for
+/// [`ensure_output_sequence`].
+pub(crate) fn ensure_event_origin(conn: &duckdb::Connection, config: &Config) -> Result<()> {
+    let done: bool = conn
+        .query_row(
+            "SELECT COUNT(*) > 0 FROM bird_meta WHERE key = 'events_origin'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(true);
+    if done {
+        return Ok(());
+    }
+
+    match config.storage_mode {
+        StorageMode::Parquet => {
+            write_events_seed(conn, config)?;
+            create_local_parquet_views(conn)?;
+        }
+        StorageMode::DuckDB => {
+            conn.execute("ALTER TABLE local.events ADD COLUMN IF NOT EXISTS origin VARCHAR", [])?;
+        }
+    }
+    conn.execute_batch(
+        r#"
+        CREATE OR REPLACE VIEW main.events AS
+            SELECT *, 'local' as _source FROM local.events
+            UNION ALL BY NAME SELECT * FROM caches.events;
+        CREATE OR REPLACE VIEW unified.events AS
+            SELECT * FROM main.events
+            UNION ALL BY NAME SELECT * FROM remotes.events;
+        CREATE OR REPLACE VIEW unified.qualified_events AS
+            SELECT * EXCLUDE (_source), list(DISTINCT _source) as _sources
+            FROM unified.events
+            GROUP BY ALL;
+        CREATE OR REPLACE VIEW main.events_with_context AS
+        SELECT
+            e.*,
+            i.cmd,
+            i.timestamp,
+            i.cwd,
+            i.exit_code
+        FROM main.events e
+        JOIN main.invocations i ON e.invocation_id = i.id;
+        CREATE OR REPLACE VIEW cwd.events AS
+        SELECT * FROM main.events WHERE false;
+
+        INSERT INTO bird_meta (key, value, updated_at) VALUES ('events_origin', '1', now())
+        ON CONFLICT (key) DO NOTHING;
+        "#,
+    )?;
     Ok(())
 }
 
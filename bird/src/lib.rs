@@ -1,26 +1,60 @@
 //! BIRD: Buffer and Invocation Record Database
 //!
 //! Storage layer for shell command history using DuckDB and Parquet.
+//!
+//! The store itself is behind the default `store` feature. Without it, bird
+//! builds without DuckDB and provides what a capture-only client needs to
+//! spool saves (see [`spool`]).
 
+pub mod alias;
+pub mod anonymize;
 pub mod buffer;
+pub mod cancel;
 pub mod config;
 pub mod context;
+pub mod cron;
 pub mod error;
+pub mod events;
+#[cfg(feature = "store")]
+pub mod extensions;
 pub mod format_hints;
+pub mod histfile;
+#[cfg(feature = "store")]
 pub mod init;
+pub mod locks;
+pub mod osc133;
 pub mod perms;
 pub mod privacy;
+pub mod profile;
+pub mod progress;
 pub mod project;
 pub mod query;
+pub mod reltime;
 pub mod schema;
+pub mod setup;
+pub mod signing;
+pub mod spool;
+#[cfg(feature = "store")]
 pub mod store;
+mod suggest;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod typescript;
+pub mod version;
+pub mod wrap;
 
+pub use alias::{invocation_alias, is_alias};
 pub use buffer::{Buffer, BufferEntry, BufferMeta};
-pub use config::{BufferConfig, Config, PrivacyConfig, RemoteConfig, RemoteMode, RemoteType, StorageMode, SyncConfig};
+pub use cancel::CancelToken;
+pub use config::{ArchiveConfig, ArtifactsConfig, BlobsConfig, BufferConfig, Charset, Config, CronJob, DisplayConfig, DisplayTheme, ExtensionsConfig, HistfileConfig, IngestConfig, PrivacyConfig, QueryConfig, QueryScope, RemoteConfig, RemoteMode, RemoteType, SanitizePolicy, SigningPolicy, SnapshotConfig, StorageMode, SyncConfig, TrashConfig, WrapConfig, WrapToolConfig};
 pub use error::{Error, Result};
-pub use format_hints::{FormatHint, FormatHints};
-pub use project::{find_current_project, find_project, is_in_project, ProjectInfo};
-pub use query::{parse_query, CompareOp, FieldFilter, PathFilter, Query, QueryComponent, RangeSelector, SourceSelector};
+#[cfg(feature = "store")]
+pub use extensions::{Capabilities, Capability};
+pub use format_hints::{FormatHint, FormatHints, HintPack, MergeStrategy, MergeSummary};
+pub use progress::{NoProgress, ProgressPhase, ProgressSink};
+pub use project::{find_current_project, find_project, is_in_project, ProjectConfig, ProjectInfo};
+pub use reltime::{format_relative, parse_since, parse_time};
+pub use query::{parse_query, parse_query_strict, CompareOp, FieldFilter, PathFilter, Query, QueryComponent, QueryDiagnostic, RangeSelector, SourceSelector};
 pub use schema::{
     // V5 schema types
     AttemptRecord, OutcomeRecord,
@@ -30,10 +64,19 @@ pub use schema::{
     InvocationRecord, OutputRecord, SessionRecord,
     // Environment variables
     BIRD_INVOCATION_UUID_VAR, BIRD_PARENT_CLIENT_VAR,
+    // User-defined dimensions
+    parse_dim, DIMS_METADATA_KEY,
+    // Event origins
+    EVENT_ORIGIN_MANUAL, EVENT_ORIGIN_PARSER,
 };
-pub use context::ContextMetadata;
+pub use context::{diff_env, worktree_snapshot, ContextMetadata, EnvChange};
+#[cfg(feature = "store")]
 pub use store::{
-    parse_since, ArchiveStats, AutoCompactOptions, BuiltinFormat, CompactOptions, CompactStats,
-    ConnectionOptions, EventFilters, EventSummary, FormatMatch, FormatSource, InvocationBatch,
-    InvocationSummary, OutputInfo, PullOptions, PullStats, PushOptions, PushStats, Store,
+    artifact_name, find_artifacts, is_artifact_stream, AggregateRow, Artifact, ArchiveStats, attach_sql, AutoCompactOptions, BlobDedup, BlobUsage, BuiltinFormat, BundleManifest, BundleStats, CatalogEntry, CatalogKind, ClassHealth, CommandClass, DedupedCommand,
+    CompactOptions, CompactStats, ConnectionOptions, ContextEntry, ContextSummary, DailyCount, DedupStats, DirHealth,
+    event_key, EventCorrection, EventEdit, EventFilters, EventSummary, ExecutableStats, ExportOptions, ExportStats, ExtractSummary, ExtractionReport, FeedEntry, FeedOptions, FormatMatch, FormatReason, FormatSource,
+    FsckIssue, FsckReport, GroupField, HistImportStats, InvocationBatch, InvocationProfile, InvocationSummary, Link, Marker, MirrorReport, OutputInfo, OutputWriter, Plan, PullOptions,
+    PullStats, PushOptions, PushStats, RebalanceStats, SessionEntry, SessionSummary, SnapshotInfo, SpoolFlushStats, SqlValue,
+    StorageBreakdown, Store, StoredOutput, SyncToken, TierStats, TimeEntry, TimePeriod, TrashEntry, Typo, UsageEntry, WriteCoalescer,
+    read_bundle_manifest, unpack_bundle, verify_mirror, ARTIFACT_STREAM_PREFIX, BUNDLE_EXTENSION, CONTRACT_VERSION, CONTRACT_VIEWS, DEFAULT_CONTEXT_GAP_MINUTES, DEFAULT_DEDUP_MIN_BYTES, DEFAULT_HEALTH_DAYS, EVENT_TYPE_COMMAND_NOT_FOUND, EVENT_TYPE_MARKER, HISTFILE_SESSION_PREFIX,
 };
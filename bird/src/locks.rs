@@ -0,0 +1,207 @@
+//! Advisory locks coordinating maintenance across processes.
+//!
+//! Shell hooks, `shq cron daemon`, a manual `shq compact` and sync can all
+//! decide to rewrite the store at the same moment. Maintenance operations
+//! take a [`Lease`] first: an exclusive `flock` on `$BIRD_ROOT/locks/NAME.lock`
+//! (released by the kernel if the holder dies, so there are no stale locks
+//! to clean up) whose contents record the holder's pid and intent. A second
+//! process gets [`Error::Busy`] naming who it's waiting for, instead of
+//! colliding or retrying.
+//!
+//! Locks are advisory: capture and queries never take them.
+
+use std::fs;
+use std::io::{Read, Seek, Write};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{perms, Config, Error, Result};
+
+/// Lock held while the store's files are rewritten (compaction, archiving,
+/// tiering, dedup, bundles) or synced with a remote.
+pub const MAINTENANCE_LOCK: &str = "maintenance";
+
+/// Lock held while the sync token is scanned and rewritten, so a slower
+/// scan can't replace a newer token (see `Store::write_sync_token`).
+pub const SYNC_TOKEN_LOCK: &str = "sync-token";
+
+/// Who holds a lock, as written in its lock file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaseInfo {
+    /// Lock name.
+    #[serde(skip)]
+    pub lock: String,
+    pub pid: u32,
+    /// What the holder is doing ("compaction", "push to 'team'").
+    pub intent: String,
+    pub started_at: DateTime<Utc>,
+}
+
+/// A held lock; released on drop.
+pub struct Lease {
+    file: fs::File,
+}
+
+impl Lease {
+    /// Take `lock` for `intent`, or fail with [`Error::Busy`] if another
+    /// process holds it.
+    pub fn acquire(config: &Config, lock: &str, intent: &str) -> Result<Self> {
+        let mut file = open_lock(config, lock)?;
+        if !try_flock(&file, true) {
+            // The holder may not have written its info yet
+            return Err(match read_info(&mut file, lock) {
+                Some(info) => Error::Busy { intent: info.intent, holder: format!("pid {}", info.pid) },
+                None => Error::Busy { intent: lock.to_string(), holder: "another process".to_string() },
+            });
+        }
+        Self::hold(file, lock, intent)
+    }
+
+    /// Take `lock` for `intent`, waiting for another process to release it
+    /// if it holds it.
+    pub fn wait(config: &Config, lock: &str, intent: &str) -> Result<Self> {
+        let file = open_lock(config, lock)?;
+        wait_flock(&file)?;
+        Self::hold(file, lock, intent)
+    }
+
+    /// Record this process as the holder of the locked `file`.
+    fn hold(mut file: fs::File, lock: &str, intent: &str) -> Result<Self> {
+        let info = LeaseInfo {
+            lock: lock.to_string(),
+            pid: std::process::id(),
+            intent: intent.to_string(),
+            started_at: Utc::now(),
+        };
+        file.set_len(0)?;
+        file.rewind()?;
+        file.write_all(&serde_json::to_vec(&info)?)?;
+        file.flush()?;
+        Ok(Self { file })
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        // Clear the holder so a reader racing the unlock doesn't report us
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// Who holds `lock`, if anyone.
+pub fn holder(config: &Config, lock: &str) -> Result<Option<LeaseInfo>> {
+    let path = lock_path(config, lock);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut file = fs::File::open(&path)?;
+    if try_flock(&file, false) {
+        return Ok(None);
+    }
+    Ok(read_info(&mut file, lock))
+}
+
+/// Every lock currently held.
+pub fn holders(config: &Config) -> Result<Vec<LeaseInfo>> {
+    let dir = config.locks_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut held = Vec::new();
+    for entry in fs::read_dir(&dir)?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(lock) = name.strip_suffix(".lock") {
+            held.extend(holder(config, lock)?);
+        }
+    }
+    held.sort_by(|a, b| a.lock.cmp(&b.lock));
+    Ok(held)
+}
+
+fn open_lock(config: &Config, lock: &str) -> Result<fs::File> {
+    let dir = config.locks_dir();
+    fs::create_dir_all(&dir)?;
+    perms::harden_dir(&dir);
+    Ok(fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(lock_path(config, lock))?)
+}
+
+fn lock_path(config: &Config, lock: &str) -> PathBuf {
+    config.locks_dir().join(format!("{}.lock", lock))
+}
+
+fn read_info(file: &mut fs::File, lock: &str) -> Option<LeaseInfo> {
+    let mut json = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut json).ok()?;
+    let mut info: LeaseInfo = serde_json::from_str(&json).ok()?;
+    info.lock = lock.to_string();
+    Some(info)
+}
+
+/// Non-blocking `flock`; true if taken. Always true where `flock` isn't
+/// available.
+fn try_flock(file: &fs::File, exclusive: bool) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+        let op = if exclusive { libc::LOCK_EX } else { libc::LOCK_SH };
+        // SAFETY: flock on a file descriptor we own; released when it's closed
+        unsafe { libc::flock(file.as_raw_fd(), op | libc::LOCK_NB) == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (file, exclusive);
+        true
+    }
+}
+
+/// Blocking exclusive `flock`. A no-op where `flock` isn't available.
+fn wait_flock(file: &fs::File) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+        // SAFETY: flock on a file descriptor we own; released when it's closed
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = file;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lease_is_exclusive() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        assert!(holder(&config, MAINTENANCE_LOCK).unwrap().is_none());
+
+        let lease = Lease::acquire(&config, MAINTENANCE_LOCK, "compaction").unwrap();
+        let info = holder(&config, MAINTENANCE_LOCK).unwrap().unwrap();
+        assert_eq!((info.pid, info.intent.as_str()), (std::process::id(), "compaction"));
+        assert_eq!(holders(&config).unwrap().len(), 1);
+
+        match Lease::acquire(&config, MAINTENANCE_LOCK, "archiving") {
+            Err(err @ Error::Busy { .. }) => {
+                assert_eq!(err.to_string(), format!("compaction in progress by pid {}", std::process::id()));
+            }
+            other => panic!("expected Busy, got {:?}", other.err()),
+        }
+
+        drop(lease);
+        assert!(holders(&config).unwrap().is_empty());
+        assert!(Lease::acquire(&config, MAINTENANCE_LOCK, "archiving").is_ok());
+    }
+}
@@ -0,0 +1,319 @@
+//! OSC 133 semantic prompt markers (FinalTerm shell integration).
+//!
+//! Shells with shell integration mark their prompt and commands in the
+//! terminal stream: `ESC ] 133 ; A` where the prompt starts, `B` where the
+//! typed command starts, `C` where its output starts and `D ; <exit>` where
+//! it ends, each optionally tagged `aid=<id>` to tell nested shells apart.
+//! The shq hook emits them (`SHQ_OSC133=1`); [`split`] reads them back out
+//! of combined PTY output, e.g. `shq run bash` or an ssh session, to find
+//! the commands run inside it.
+
+use std::ops::Range;
+
+const PREFIX: &[u8] = b"\x1b]133;";
+
+/// Kind of a marker, by its letter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkKind {
+    /// `A`: a prompt starts.
+    PromptStart,
+    /// `B`: the prompt ends and the typed command starts.
+    CommandStart,
+    /// `C`: the command was entered and its output starts.
+    OutputStart,
+    /// `D`: the command finished.
+    CommandEnd,
+}
+
+/// One marker found in output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mark {
+    pub kind: MarkKind,
+    /// Exit code given with a `D` marker.
+    pub exit_code: Option<i32>,
+    /// Application id (`aid=`), set by the shell that emitted the marker.
+    pub aid: Option<String>,
+    /// Byte range of the escape sequence.
+    pub range: Range<usize>,
+}
+
+/// A command found in output, delimited by its markers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// The command line, if the shell marked or echoed it.
+    pub command: Option<String>,
+    pub exit_code: Option<i32>,
+    /// Shell nesting level: 0 for the outermost shell seen.
+    pub depth: usize,
+    /// Byte range of the command's output, markers of nested shells included.
+    pub output: Range<usize>,
+    /// Whether a `D` marker ended it, rather than the output or its shell.
+    pub finished: bool,
+}
+
+/// The OSC 133 markers in `data`, in order. Unknown kinds are skipped.
+pub fn marks(data: &[u8]) -> Vec<Mark> {
+    let mut marks = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = find(&data[pos..], PREFIX) {
+        let start = pos + offset;
+        let body_start = start + PREFIX.len();
+        // Terminated by BEL or ST (ESC \); an unterminated marker ends the scan
+        let Some((body_end, end)) = data[body_start..].iter().enumerate().find_map(|(i, &b)| match b {
+            0x07 => Some((body_start + i, body_start + i + 1)),
+            0x1b if data.get(body_start + i + 1) == Some(&b'\\') => Some((body_start + i, body_start + i + 2)),
+            _ => None,
+        }) else {
+            break;
+        };
+        if let Some(mark) = parse(&String::from_utf8_lossy(&data[body_start..body_end]), start..end) {
+            marks.push(mark);
+        }
+        pos = end;
+    }
+    marks
+}
+
+/// Split `data` into the commands its markers delimit, in the order they
+/// started.
+///
+/// Markers tagged with `aid` are matched to their shell, so a nested shell
+/// starts a deeper level and a marker from an outer shell ends it. Without
+/// `aid`, a prompt inside a running command is taken to be a nested shell,
+/// and an end marker at such a shell's prompt to end the command that
+/// started it.
+pub fn split(data: &[u8]) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut stack: Vec<Frame> = Vec::new();
+
+    for mark in marks(data) {
+        let depth = frame_for(&mut stack, &mut segments, &mark);
+        let frame = &mut stack[depth];
+        match mark.kind {
+            MarkKind::PromptStart => {
+                close(&mut segments, frame.running.take(), mark.range.start, None);
+                frame.prompt = Some(mark.range.end);
+                frame.input = None;
+            }
+            MarkKind::CommandStart => frame.input = Some(mark.range.end),
+            MarkKind::OutputStart => {
+                close(&mut segments, frame.running.take(), mark.range.start, None);
+                let command = match (frame.input.take(), frame.prompt.take()) {
+                    (Some(input), _) => command_text(&data[input..mark.range.start], false),
+                    (None, Some(prompt)) => command_text(&data[prompt..mark.range.start], true),
+                    (None, None) => None,
+                };
+                frame.running = Some(segments.len());
+                segments.push(Segment {
+                    command,
+                    exit_code: None,
+                    depth,
+                    output: mark.range.end..data.len(),
+                    finished: false,
+                });
+            }
+            MarkKind::CommandEnd => {
+                // Without a running command (an empty line), there's nothing to end
+                if let Some(index) = frame.running.take() {
+                    close(&mut segments, Some(index), mark.range.start, Some(mark.exit_code));
+                }
+            }
+        }
+    }
+    segments
+}
+
+/// A shell seen in the output.
+struct Frame {
+    aid: Option<String>,
+    /// Where the current prompt's text starts.
+    prompt: Option<usize>,
+    /// Where the typed command starts.
+    input: Option<usize>,
+    /// Index of the running command's segment.
+    running: Option<usize>,
+}
+
+impl Frame {
+    fn new(aid: Option<String>) -> Self {
+        Frame { aid, prompt: None, input: None, running: None }
+    }
+}
+
+/// Index of the frame `mark` belongs to, pushing or popping nested shells.
+fn frame_for(stack: &mut Vec<Frame>, segments: &mut [Segment], mark: &Mark) -> usize {
+    if let Some(aid) = &mark.aid {
+        if let Some(index) = stack.iter().position(|f| f.aid.as_ref() == Some(aid)) {
+            // Shells nested in this one have exited
+            for frame in stack.drain(index + 1..) {
+                close(segments, frame.running, mark.range.start, None);
+            }
+            return index;
+        }
+        match stack.last_mut() {
+            // A shell at its prompt: the same level, under another name
+            Some(top) if top.running.is_none() => top.aid = Some(aid.clone()),
+            _ => stack.push(Frame::new(Some(aid.clone()))),
+        }
+        return stack.len() - 1;
+    }
+
+    match stack.last() {
+        None => stack.push(Frame::new(None)),
+        Some(top) if top.running.is_some() && mark.kind == MarkKind::PromptStart => stack.push(Frame::new(None)),
+        Some(top) if top.running.is_none() && mark.kind == MarkKind::CommandEnd && stack.len() > 1 => {
+            stack.pop();
+        }
+        Some(_) => {}
+    }
+    stack.len() - 1
+}
+
+/// End segment `index` (if any) at `end`; `exit` is `Some` for a `D` marker.
+fn close(segments: &mut [Segment], index: Option<usize>, end: usize, exit: Option<Option<i32>>) {
+    let Some(segment) = index.and_then(|i| segments.get_mut(i)) else {
+        return;
+    };
+    segment.output.end = end;
+    if let Some(exit_code) = exit {
+        segment.exit_code = exit_code;
+        segment.finished = true;
+    }
+}
+
+/// The command typed between `B` and `C`, or with only an `A` marker, the
+/// last line of the prompt and its echoed input.
+fn command_text(bytes: &[u8], prompt_included: bool) -> Option<String> {
+    let text = String::from_utf8_lossy(&strip_escapes(bytes)).replace('\r', "");
+    let text = if prompt_included {
+        text.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("").to_string()
+    } else {
+        text
+    };
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn parse(body: &str, range: Range<usize>) -> Option<Mark> {
+    let mut fields = body.split(';');
+    let kind = match fields.next()? {
+        "A" => MarkKind::PromptStart,
+        "B" => MarkKind::CommandStart,
+        "C" => MarkKind::OutputStart,
+        "D" => MarkKind::CommandEnd,
+        _ => return None,
+    };
+    let mut mark = Mark { kind, exit_code: None, aid: None, range };
+    for field in fields {
+        match field.split_once('=') {
+            Some(("aid", aid)) => mark.aid = Some(aid.to_string()),
+            Some(_) => {}
+            None if kind == MarkKind::CommandEnd && mark.exit_code.is_none() => mark.exit_code = field.parse().ok(),
+            None => {}
+        }
+    }
+    Some(mark)
+}
+
+/// `bytes` without CSI and OSC escape sequences.
+fn strip_escapes(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (0x1b, Some(b'[')) => {
+                i += 2;
+                while i < bytes.len() && !(0x40..=0x7e).contains(&bytes[i]) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            (0x1b, Some(b']')) => {
+                i += 2;
+                while i < bytes.len() && bytes[i] != 0x07 && !(bytes[i] == 0x1b && bytes.get(i + 1) == Some(&b'\\')) {
+                    i += 1;
+                }
+                i += if bytes.get(i) == Some(&0x1b) { 2 } else { 1 };
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn osc(body: &str) -> String {
+        format!("\x1b]133;{}\x07", body)
+    }
+
+    #[test]
+    fn test_marks() {
+        let data = format!("{}$ {}ls\n{}a b\n\x1b]133;D;2;aid=7\x1b\\", osc("A"), osc("B"), osc("C"));
+        let marks = marks(data.as_bytes());
+        let kinds: Vec<_> = marks.iter().map(|m| m.kind).collect();
+        assert_eq!(kinds, [MarkKind::PromptStart, MarkKind::CommandStart, MarkKind::OutputStart, MarkKind::CommandEnd]);
+        assert_eq!(marks[3].exit_code, Some(2));
+        assert_eq!(marks[3].aid.as_deref(), Some("7"));
+        assert_eq!(marks[3].range.end, data.len());
+    }
+
+    #[test]
+    fn test_split_commands() {
+        let data = [
+            osc("A"), "$ ".into(), osc("B"), "make\r\n".into(), osc("C"), "built\n".into(), osc("D;0"),
+            // Empty line: no command to end
+            osc("A"), "$ ".into(), osc("B"), osc("D"),
+            // No B marker: the echoed prompt line
+            osc("A"), "\x1b[32mme@host\x1b[0m $ ls\r\n".into(), osc("C"), "a\n".into(), osc("D;1"),
+        ]
+        .concat();
+        let segments = split(data.as_bytes());
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].command.as_deref(), Some("make"));
+        assert_eq!(&data[segments[0].output.clone()], "built\n");
+        assert_eq!((segments[0].exit_code, segments[0].finished), (Some(0), true));
+        assert_eq!(segments[1].command.as_deref(), Some("me@host $ ls"));
+        assert_eq!(segments[1].exit_code, Some(1));
+    }
+
+    #[test]
+    fn test_split_nested_shells() {
+        // An outer shell (aid=1) runs bash (aid=2), which runs make and exits
+        let data = [
+            osc("A;aid=1"), osc("B;aid=1"), "bash\n".into(), osc("C;aid=1"),
+            osc("A;aid=2"), osc("B;aid=2"), "make\n".into(), osc("C;aid=2"), "built\n".into(), osc("D;0;aid=2"),
+            osc("A;aid=2"), osc("B;aid=2"), "exit\n".into(), osc("C;aid=2"),
+            osc("D;0;aid=1"), osc("A;aid=1"),
+        ]
+        .concat();
+        let segments = split(data.as_bytes());
+        let summary: Vec<_> = segments.iter().map(|s| (s.command.as_deref().unwrap(), s.depth, s.finished)).collect();
+        assert_eq!(summary, [("bash", 0, true), ("make", 1, true), ("exit", 1, false)]);
+        assert_eq!(&data[segments[1].output.clone()], "built\n");
+
+        // The same without aid: the end marker at the inner prompt ends bash
+        let data = data.replace(";aid=1", "").replace(";aid=2", "").replace(&[osc("B"), "exit\n".into(), osc("C")].concat(), "");
+        let segments = split(data.as_bytes());
+        let summary: Vec<_> = segments.iter().map(|s| (s.command.as_deref().unwrap(), s.depth, s.finished)).collect();
+        assert_eq!(summary, [("bash", 0, true), ("make", 1, true)]);
+    }
+
+    #[test]
+    fn test_unfinished_command() {
+        let data = format!("{}{}sleep 9\n{}zzz", osc("A"), osc("B"), osc("C"));
+        let segments = split(data.as_bytes());
+        assert_eq!(segments.len(), 1);
+        assert!(!segments[0].finished);
+        assert_eq!(&data[segments[0].output.clone()], "zzz");
+    }
+}
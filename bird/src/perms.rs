@@ -102,7 +102,7 @@ pub fn repair_permissions(config: &Config) {
             harden_file(&f);
         }
     }
-    for d in [root.join("db"), config.running_dir(), config.buffer_dir()] {
+    for d in [root.join("db"), config.running_dir(), config.capture_dir(), config.buffer_dir()] {
         if d.exists() {
             harden_dir(&d);
         }
@@ -15,8 +15,16 @@
 //! patterns are the stronger tool; users handling unusual secret shapes
 //! should extend `privacy.exclude_patterns` or use the leading-space /
 //! `SHQ_DISABLED` escapes.
+//!
+//! Capture can also be **paused** wholesale (`shq off`, optionally
+//! `--for 1h`). The pause lives in the state file so every shell sees it;
+//! hooks check it before saving and `shq save` re-checks it.
+
+use std::fs;
+
+use chrono::{DateTime, TimeDelta, Utc};
 
-use crate::Config;
+use crate::{Config, Error, Result};
 
 /// Replacement text for redacted secret values.
 pub const REDACTED: &str = "[REDACTED]";
@@ -30,6 +38,76 @@ pub fn should_exclude(config: &Config, cmd: &str) -> bool {
         .any(|p| matches_glob_pattern(p, cmd))
 }
 
+/// State file prefix for the pause expiry (epoch seconds, 0 = no expiry).
+const PAUSED_UNTIL_PREFIX: &str = "paused_until=";
+
+/// An active capture pause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pause {
+    /// Paused until `shq on`.
+    Indefinite,
+    /// Paused until the given time, then capture resumes on its own.
+    Until(DateTime<Utc>),
+}
+
+/// Pause capture, indefinitely or until `until`.
+pub fn pause(config: &Config, until: Option<DateTime<Utc>>) -> Result<()> {
+    let epoch = until.map(|t| t.timestamp()).unwrap_or(0);
+    fs::create_dir_all(&config.bird_root)?;
+    fs::write(config.state_path(), format!("{}{}\n", PAUSED_UNTIL_PREFIX, epoch))?;
+    Ok(())
+}
+
+/// Resume capture. Returns whether capture was paused.
+pub fn resume(config: &Config) -> Result<bool> {
+    let was_paused = pause_state(config).is_some();
+    match fs::remove_file(config.state_path()) {
+        Ok(()) => Ok(was_paused),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Current pause, if capture is paused. Expired pauses read as `None`.
+pub fn pause_state(config: &Config) -> Option<Pause> {
+    let content = fs::read_to_string(config.state_path()).ok()?;
+    let epoch: i64 = content
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(PAUSED_UNTIL_PREFIX))?
+        .parse()
+        .ok()?;
+
+    if epoch == 0 {
+        return Some(Pause::Indefinite);
+    }
+    let until = DateTime::from_timestamp(epoch, 0)?;
+    (until > Utc::now()).then_some(Pause::Until(until))
+}
+
+/// Check whether capture is currently paused.
+pub fn is_paused(config: &Config) -> bool {
+    pause_state(config).is_some()
+}
+
+/// Parse a pause length such as "30s", "15m", "1h" or "2d" (see
+/// [`crate::reltime::parse_duration`]).
+pub fn parse_pause_duration(s: &str) -> Result<TimeDelta> {
+    let invalid = || Error::Config(format!("Invalid duration '{}' (expected e.g. 30m, 1h, 2d)", s.trim()));
+    crate::reltime::parse_duration(s).ok_or_else(invalid)
+}
+
+/// OSC escape sequence that commands can emit to opt out of recording.
+/// Format: ESC ] shq;nosave BEL  (or ESC ] shq;nosave ESC \)
+pub const NOSAVE_OSC: &[u8] = b"\x1b]shq;nosave\x07";
+pub const NOSAVE_OSC_ST: &[u8] = b"\x1b]shq;nosave\x1b\\";
+
+/// Check if output contains the nosave marker.
+/// Commands can emit this OSC escape sequence to opt out of being recorded.
+pub fn contains_nosave_marker(data: &[u8]) -> bool {
+    data.windows(NOSAVE_OSC.len()).any(|w| w == NOSAVE_OSC)
+        || data.windows(NOSAVE_OSC_ST.len()).any(|w| w == NOSAVE_OSC_ST)
+}
+
 /// Substrings that mark a `name=value` or `--name value` pair as secret.
 const SECRET_NAME_MARKERS: &[&str] = &[
     "password",
@@ -88,6 +166,21 @@ const SECRET_TOKEN_PREFIXES: &[&str] = &[
 /// Deliberately narrow: `-p` means "port" or "parents" for many other tools.
 const ATTACHED_P_COMMANDS: &[&str] = &["mysql", "mysqldump", "mysqladmin", "mariadb", "mariadb-dump"];
 
+/// Whether a variable or option name looks like it holds a secret.
+pub fn is_secret_name(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    SECRET_NAME_MARKERS.iter().any(|m| lower.contains(m))
+}
+
+/// Redact an environment variable's value if its name or value looks secret.
+pub fn redact_env_value(name: &str, value: &str) -> String {
+    if is_secret_name(name) || SECRET_TOKEN_PREFIXES.iter().any(|p| value.starts_with(p)) {
+        REDACTED.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
 /// Redact recognizable secret values in a command line.
 ///
 /// The command structure (program, flags, whitespace) is preserved; only the
@@ -326,6 +419,45 @@ pub fn matches_glob_pattern(pattern: &str, text: &str) -> bool {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_pause_and_resume() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+
+        assert_eq!(pause_state(&config), None);
+        assert!(!resume(&config).unwrap());
+
+        pause(&config, None).unwrap();
+        assert_eq!(pause_state(&config), Some(Pause::Indefinite));
+        assert!(resume(&config).unwrap());
+        assert!(!is_paused(&config));
+    }
+
+    #[test]
+    fn test_pause_expires() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+
+        let until = Utc::now() + TimeDelta::hours(1);
+        pause(&config, Some(until)).unwrap();
+        assert!(matches!(pause_state(&config), Some(Pause::Until(_))));
+
+        pause(&config, Some(Utc::now() - TimeDelta::seconds(1))).unwrap();
+        assert!(!is_paused(&config));
+    }
+
+    #[test]
+    fn test_parse_pause_duration() {
+        assert_eq!(parse_pause_duration("30s").unwrap(), TimeDelta::seconds(30));
+        assert_eq!(parse_pause_duration("15m").unwrap(), TimeDelta::minutes(15));
+        assert_eq!(parse_pause_duration("1h").unwrap(), TimeDelta::hours(1));
+        assert_eq!(parse_pause_duration("2D").unwrap(), TimeDelta::days(2));
+        assert!(parse_pause_duration("h").is_err());
+        assert!(parse_pause_duration("10").is_err());
+        assert_eq!(parse_pause_duration("1w").unwrap(), TimeDelta::weeks(1));
+        assert!(parse_pause_duration("1x").is_err());
+    }
+
     #[test]
     fn test_redacts_secret_env_assignment() {
         let out = redact_command("export AWS_SECRET_ACCESS_KEY=CANARY123");
@@ -417,4 +549,12 @@ mod tests {
         assert!(!should_exclude(&config, "cargo build"));
         assert!(!should_exclude(&config, "git status"));
     }
+
+    #[test]
+    fn test_redact_env_value() {
+        assert_eq!(redact_env_value("GITHUB_TOKEN", "abc"), REDACTED);
+        assert_eq!(redact_env_value("AWS_SECRET_ACCESS_KEY", "abc"), REDACTED);
+        assert_eq!(redact_env_value("SOME_VAR", "ghp_0123456789"), REDACTED);
+        assert_eq!(redact_env_value("RUSTFLAGS", "-D warnings"), "-D warnings");
+    }
 }
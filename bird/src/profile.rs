@@ -0,0 +1,301 @@
+//! Timing shq's own phases, for `shq self-profile`.
+//!
+//! A command's wall time is split between phases: parsing its arguments,
+//! connecting to the database (opening it, loading extensions, attaching
+//! remotes), rendering results, and querying, which is everything else.
+//! One clock runs per process, on the thread that started it: code marks
+//! the phase it's in with [`during`], and time accrues to the innermost
+//! phase marked. Until [`start`] is called, and on other threads, the
+//! marks do nothing.
+//!
+//! Nothing leaves the machine: shq appends each command's timings to
+//! `$BIRD_ROOT/self-profile.jsonl` when `self_profile` is set in
+//! config.toml.
+
+use std::fs;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{perms, Config, Result};
+
+/// Size past which the log is trimmed to its newer half.
+const MAX_LOG_BYTES: u64 = 4 * 1024 * 1024;
+
+/// A phase of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Parse,
+    Connect,
+    Query,
+    Render,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 4] = [Phase::Parse, Phase::Connect, Phase::Query, Phase::Render];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Parse => "parse",
+            Phase::Connect => "connect",
+            Phase::Query => "query",
+            Phase::Render => "render",
+        }
+    }
+}
+
+/// Time spent in each phase, and how often it was entered.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhaseTimes {
+    times: [Duration; 4],
+    entered: [u32; 4],
+}
+
+impl PhaseTimes {
+    pub fn get(&self, phase: Phase) -> Duration {
+        self.times[phase as usize]
+    }
+
+    /// Times `phase` was entered (for connect: connections opened).
+    pub fn entered(&self, phase: Phase) -> u32 {
+        self.entered[phase as usize]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.times.iter().sum()
+    }
+}
+
+struct Clock {
+    thread: ThreadId,
+    phase: Phase,
+    since: Instant,
+    times: PhaseTimes,
+}
+
+impl Clock {
+    /// Charge the time since the last switch to the current phase, and
+    /// switch to `phase`.
+    fn switch(&mut self, phase: Phase) -> Phase {
+        let now = Instant::now();
+        self.times.times[self.phase as usize] += now - self.since;
+        self.since = now;
+        std::mem::replace(&mut self.phase, phase)
+    }
+}
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static CLOCK: Mutex<Option<Clock>> = Mutex::new(None);
+
+/// Start the clock in `phase`, from `since`, discarding earlier timings.
+pub fn start(phase: Phase, since: Instant) {
+    let mut times = PhaseTimes::default();
+    times.entered[phase as usize] = 1;
+    *CLOCK.lock().unwrap_or_else(|e| e.into_inner()) = Some(Clock { thread: thread::current().id(), phase, since, times });
+    RUNNING.store(true, Ordering::Relaxed);
+}
+
+/// Stop the clock and return the timings, if it was running.
+pub fn finish() -> Option<PhaseTimes> {
+    RUNNING.store(false, Ordering::Relaxed);
+    let mut clock = CLOCK.lock().unwrap_or_else(|e| e.into_inner()).take()?;
+    let phase = clock.phase;
+    clock.switch(phase);
+    Some(clock.times)
+}
+
+/// Account the time until the returned guard is dropped to `phase`; the
+/// phase before it resumes then.
+pub fn during(phase: Phase) -> PhaseGuard {
+    PhaseGuard { previous: switch(phase) }
+}
+
+/// Switch the running clock to `phase`, returning the phase it was in.
+fn switch(phase: Phase) -> Option<Phase> {
+    if !RUNNING.load(Ordering::Relaxed) {
+        return None;
+    }
+    let mut clock = CLOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let clock = clock.as_mut().filter(|clock| clock.thread == thread::current().id())?;
+    clock.times.entered[phase as usize] += 1;
+    Some(clock.switch(phase))
+}
+
+/// See [`during`].
+#[must_use = "the phase ends when the guard is dropped"]
+pub struct PhaseGuard {
+    previous: Option<Phase>,
+}
+
+impl Drop for PhaseGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous {
+            if RUNNING.load(Ordering::Relaxed) {
+                let mut clock = CLOCK.lock().unwrap_or_else(|e| e.into_inner());
+                if let Some(clock) = clock.as_mut().filter(|clock| clock.thread == thread::current().id()) {
+                    clock.switch(previous);
+                }
+            }
+        }
+    }
+}
+
+/// One command's timings, as recorded in the log. Only the command's name
+/// is kept, never its arguments.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfileRecord {
+    pub at: DateTime<Utc>,
+    /// Subcommand, e.g. `invocations` or `hook status`.
+    pub command: String,
+    /// shq version that ran it.
+    pub version: String,
+    pub ok: bool,
+    pub parse_ms: f64,
+    pub connect_ms: f64,
+    pub query_ms: f64,
+    pub render_ms: f64,
+    pub total_ms: f64,
+    /// Database connections opened.
+    pub connections: u32,
+}
+
+impl ProfileRecord {
+    pub fn new(command: &str, times: &PhaseTimes, ok: bool) -> Self {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        Self {
+            at: Utc::now(),
+            command: command.to_string(),
+            version: crate::version::SHQ_VERSION.to_string(),
+            ok,
+            parse_ms: ms(times.get(Phase::Parse)),
+            connect_ms: ms(times.get(Phase::Connect)),
+            query_ms: ms(times.get(Phase::Query)),
+            render_ms: ms(times.get(Phase::Render)),
+            total_ms: ms(times.total()),
+            connections: times.entered(Phase::Connect),
+        }
+    }
+
+    /// Milliseconds spent in `phase`.
+    pub fn phase_ms(&self, phase: Phase) -> f64 {
+        match phase {
+            Phase::Parse => self.parse_ms,
+            Phase::Connect => self.connect_ms,
+            Phase::Query => self.query_ms,
+            Phase::Render => self.render_ms,
+        }
+    }
+}
+
+/// Append `record` to the log (one JSON object per line).
+pub fn record(config: &Config, record: &ProfileRecord) -> Result<()> {
+    let path = config.self_profile_path();
+    if fs::metadata(&path).is_ok_and(|meta| meta.len() > MAX_LOG_BYTES) {
+        trim(config)?;
+    }
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    let mut options = fs::OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(perms::FILE_MODE);
+    }
+    // One write with O_APPEND, so concurrent shq processes don't interleave
+    options.open(&path)?.write_all(&line)?;
+    Ok(())
+}
+
+/// The recorded timings, oldest first. Lines that don't parse (a write cut
+/// short) are skipped.
+pub fn read(config: &Config) -> Result<Vec<ProfileRecord>> {
+    match fs::read_to_string(config.self_profile_path()) {
+        Ok(log) => Ok(log.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Delete the recorded timings.
+pub fn clear(config: &Config) -> Result<()> {
+    match fs::remove_file(config.self_profile_path()) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Keep the newer half of the log.
+fn trim(config: &Config) -> Result<()> {
+    let records = read(config)?;
+    let path = config.self_profile_path();
+    let temp = path.with_file_name(format!(".tmp.{}.self-profile.jsonl", std::process::id()));
+    let mut out = Vec::new();
+    for record in &records[records.len() / 2..] {
+        out.extend(serde_json::to_vec(record)?);
+        out.push(b'\n');
+    }
+    fs::write(&temp, out)?;
+    perms::harden_file(&temp);
+    fs::rename(&temp, &path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phases_nest() {
+        start(Phase::Parse, Instant::now());
+        std::thread::sleep(Duration::from_millis(2));
+        let query = during(Phase::Query);
+        {
+            let _connect = during(Phase::Connect);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let render = during(Phase::Render);
+        std::thread::sleep(Duration::from_millis(2));
+        drop(render);
+        drop(query);
+
+        let times = finish().unwrap();
+        assert!(times.get(Phase::Connect) >= Duration::from_millis(5));
+        assert!(times.get(Phase::Parse) >= Duration::from_millis(2));
+        assert!(times.get(Phase::Render) >= Duration::from_millis(2));
+        assert_eq!(times.entered(Phase::Connect), 1);
+        assert_eq!(times.total(), Phase::ALL.iter().map(|p| times.get(*p)).sum());
+
+        // Stopped: marks do nothing
+        assert!(finish().is_none());
+        let _ = during(Phase::Render);
+    }
+
+    #[test]
+    fn test_record_and_read() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        assert!(read(&config).unwrap().is_empty());
+
+        let mut times = PhaseTimes::default();
+        times.times[Phase::Connect as usize] = Duration::from_millis(40);
+        times.times[Phase::Query as usize] = Duration::from_millis(10);
+        times.entered[Phase::Connect as usize] = 2;
+        record(&config, &ProfileRecord::new("invocations", &times, true)).unwrap();
+        // A line cut short by a crash is skipped
+        fs::OpenOptions::new().append(true).open(config.self_profile_path()).unwrap().write_all(b"{\"at\":").unwrap();
+
+        let records = read(&config).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].command.as_str(), records[0].connections), ("invocations", 2));
+        assert_eq!(records[0].total_ms, 50.0);
+
+        clear(&config).unwrap();
+        assert!(read(&config).unwrap().is_empty());
+    }
+}
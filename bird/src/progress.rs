@@ -0,0 +1,153 @@
+//! Progress reporting for long-running operations.
+//!
+//! Compaction, archiving, push/pull, event backfill and schema migration
+//! report progress through the [`ProgressSink`] set on the store with
+//! [`Store::with_progress`](crate::Store::with_progress). The CLI renders
+//! it as progress bars; other frontends (e.g. a daemon) can turn it into
+//! status reports. Stores report to [`NoProgress`] unless told otherwise.
+
+/// Receives progress from long-running store operations.
+///
+/// Operations are split into phases (e.g. `compact recent/outputs`); each
+/// phase is started, advanced step by step, and finished, also when the
+/// operation fails or is cancelled partway (see [`ProgressPhase`]). Phases
+/// don't nest. Methods take `&self` so one sink can be shared across threads.
+pub trait ProgressSink: Send + Sync {
+    /// A phase started. `total` is the number of steps, if known.
+    fn start(&self, phase: &str, total: Option<u64>);
+
+    /// `steps` more steps of the current phase are done.
+    fn advance(&self, steps: u64);
+
+    /// Status detail for the current phase (e.g. running totals).
+    fn message(&self, _message: &str) {}
+
+    /// The current phase finished.
+    fn finish(&self);
+}
+
+/// A sink that discards all progress.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn start(&self, _phase: &str, _total: Option<u64>) {}
+    fn advance(&self, _steps: u64) {}
+    fn finish(&self) {}
+}
+
+/// A started phase, finished when dropped, so operations that fail or are
+/// cancelled partway still finish it.
+#[must_use = "the phase finishes when dropped"]
+pub struct ProgressPhase<'a> {
+    sink: &'a dyn ProgressSink,
+}
+
+impl<'a> ProgressPhase<'a> {
+    /// Start `phase` on `sink`.
+    pub fn start(sink: &'a dyn ProgressSink, phase: &str, total: Option<u64>) -> Self {
+        sink.start(phase, total);
+        Self { sink }
+    }
+
+    /// See [`ProgressSink::advance`].
+    pub fn advance(&self, steps: u64) {
+        self.sink.advance(steps);
+    }
+
+    /// See [`ProgressSink::message`].
+    pub fn message(&self, message: &str) {
+        self.sink.message(message);
+    }
+}
+
+impl Drop for ProgressPhase<'_> {
+    fn drop(&mut self) {
+        self.sink.finish();
+    }
+}
+
+/// Records every call, for checking what store operations report.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct Recorder(std::sync::Mutex<Vec<String>>);
+
+#[cfg(test)]
+impl Recorder {
+    pub(crate) fn calls(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Whether every phase started was finished before the next started.
+    pub(crate) fn balanced(&self) -> bool {
+        let mut open = false;
+        for call in self.calls() {
+            if call.starts_with("start ") {
+                if open {
+                    return false;
+                }
+                open = true;
+            } else if call == "finish" {
+                if !open {
+                    return false;
+                }
+                open = false;
+            }
+        }
+        !open
+    }
+}
+
+#[cfg(test)]
+impl ProgressSink for Recorder {
+    fn start(&self, phase: &str, total: Option<u64>) {
+        self.0.lock().unwrap().push(format!("start {} {:?}", phase, total));
+    }
+    fn advance(&self, steps: u64) {
+        self.0.lock().unwrap().push(format!("advance {}", steps));
+    }
+    fn finish(&self) {
+        self.0.lock().unwrap().push("finish".to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sink_is_object_safe_and_shareable() {
+        let recorder = std::sync::Arc::new(Recorder::default());
+        let sink: std::sync::Arc<dyn ProgressSink> = recorder.clone();
+
+        sink.start("compact recent/outputs", Some(2));
+        std::thread::scope(|scope| {
+            scope.spawn(|| sink.advance(1));
+            scope.spawn(|| sink.advance(1));
+        });
+        sink.message("ignored by default");
+        sink.finish();
+
+        let calls = recorder.calls();
+        assert_eq!(calls.len(), 4);
+        assert_eq!(calls[0], "start compact recent/outputs Some(2)");
+        assert_eq!(calls[3], "finish");
+    }
+
+    #[test]
+    fn test_phase_finishes_when_dropped() {
+        let recorder = Recorder::default();
+        let run = |fail: bool| -> Result<(), ()> {
+            let phase = ProgressPhase::start(&recorder, "check outputs", Some(3));
+            phase.advance(1);
+            if fail {
+                return Err(());
+            }
+            phase.advance(2);
+            Ok(())
+        };
+        assert!(run(true).is_err());
+        assert_eq!(recorder.calls(), vec!["start check outputs Some(3)", "advance 1", "finish"]);
+        assert!(recorder.balanced());
+    }
+}
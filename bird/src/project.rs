@@ -1,15 +1,48 @@
 //! Project detection for BIRD.
 //!
 //! Finds project-level `.bird/` directories by walking up from the current directory.
+//!
+//! A project may also carry settings in `.bird/config.toml` (see
+//! [`ProjectConfig`]). The shell hook applies them on entering the project by
+//! evaluating `shq project env`, which exports them as `SHQ_PROJECT*`
+//! variables, and drops them again on leaving.
 
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
 /// The name of the BIRD project directory.
 pub const BIRD_DIR_NAME: &str = ".bird";
 
 /// The name of the project database file.
 pub const BIRD_DB_NAME: &str = "bird.duckdb";
 
+/// The name of the project settings file inside `.bird/`.
+pub const PROJECT_CONFIG_NAME: &str = "config.toml";
+
+/// Root of the project whose settings are active.
+pub const PROJECT_VAR: &str = "SHQ_PROJECT";
+/// `0` when capture is disabled for the active project.
+pub const PROJECT_CAPTURE_VAR: &str = "SHQ_PROJECT_CAPTURE";
+/// `0`/`1` overriding `auto_extract` for the active project.
+pub const PROJECT_EXTRACT_VAR: &str = "SHQ_PROJECT_EXTRACT";
+/// Default event extraction format for the active project.
+pub const PROJECT_EXTRACT_FORMAT_VAR: &str = "SHQ_PROJECT_EXTRACT_FORMAT";
+/// Default dimensions for the active project, one `KEY=VALUE` per line.
+pub const PROJECT_DIMS_VAR: &str = "SHQ_PROJECT_DIMS";
+
+/// All variables exported by `shq project env`.
+pub const PROJECT_ENV_VARS: &[&str] = &[
+    PROJECT_VAR,
+    PROJECT_CAPTURE_VAR,
+    PROJECT_EXTRACT_VAR,
+    PROJECT_EXTRACT_FORMAT_VAR,
+    PROJECT_DIMS_VAR,
+];
+
 /// Result of project detection.
 #[derive(Debug, Clone)]
 pub struct ProjectInfo {
@@ -33,6 +66,103 @@ impl ProjectInfo {
     pub fn is_initialized(&self) -> bool {
         self.db_path.exists()
     }
+
+    /// Path to the project settings file.
+    pub fn config_path(&self) -> PathBuf {
+        self.bird_dir.join(PROJECT_CONFIG_NAME)
+    }
+
+    /// Load the project settings, or defaults if there is no settings file.
+    pub fn load_config(&self) -> Result<ProjectConfig> {
+        let path = self.config_path();
+        if !path.exists() {
+            return Ok(ProjectConfig::default());
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        toml::from_str(&contents)
+            .map_err(|e| Error::Config(format!("Failed to parse {}: {}", path.display(), e)))
+    }
+}
+
+/// Per-project settings from `.bird/config.toml`.
+///
+/// ```toml
+/// capture = true            # false: shell hooks don't record commands here
+/// extract = true            # override auto_extract for `shq run`
+/// extract_format = "cargo"  # default format for event extraction
+///
+/// [dims]                    # recorded on every command (query with %dim:KEY)
+/// project = "payments"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectConfig {
+    /// Record commands run in this project (default: true).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture: Option<bool>,
+
+    /// Extract events from captured output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extract: Option<bool>,
+
+    /// Default format for event extraction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extract_format: Option<String>,
+
+    /// Dimensions recorded on every command run in the project.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dims: BTreeMap<String, String>,
+}
+
+impl ProjectConfig {
+    /// Environment variables carrying these settings for a project at `root`.
+    pub fn env_vars(&self, root: &Path) -> Vec<(&'static str, String)> {
+        let flag = |b: bool| if b { "1" } else { "0" }.to_string();
+
+        let mut vars = vec![(PROJECT_VAR, root.display().to_string())];
+        if let Some(capture) = self.capture {
+            vars.push((PROJECT_CAPTURE_VAR, flag(capture)));
+        }
+        if let Some(extract) = self.extract {
+            vars.push((PROJECT_EXTRACT_VAR, flag(extract)));
+        }
+        if let Some(format) = &self.extract_format {
+            vars.push((PROJECT_EXTRACT_FORMAT_VAR, format.clone()));
+        }
+        if !self.dims.is_empty() {
+            let dims: Vec<String> = self.dims.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            vars.push((PROJECT_DIMS_VAR, dims.join("\n")));
+        }
+        vars
+    }
+
+    /// Settings exported for the active project, if any.
+    ///
+    /// Reads the variables written by [`ProjectConfig::env_vars`]; malformed
+    /// values are ignored.
+    pub fn from_env() -> Self {
+        let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+        let flag = |name| var(name).map(|v| v != "0");
+
+        let dims = var(PROJECT_DIMS_VAR)
+            .map(|dims| {
+                dims.lines()
+                    .filter_map(|line| crate::schema::parse_dim(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            capture: flag(PROJECT_CAPTURE_VAR),
+            extract: flag(PROJECT_EXTRACT_VAR),
+            extract_format: var(PROJECT_EXTRACT_FORMAT_VAR),
+            dims,
+        }
+    }
+
+    /// Whether commands run in this project should be recorded.
+    pub fn capture_enabled(&self) -> bool {
+        self.capture.unwrap_or(true)
+    }
 }
 
 /// Find the project root by walking up from the given directory.
@@ -145,6 +275,35 @@ mod tests {
         assert!(project_relative_path(&project, outside).is_none());
     }
 
+    #[test]
+    fn test_load_project_config() {
+        let tmp = TempDir::new().unwrap();
+        let bird_dir = tmp.path().join(".bird");
+        std::fs::create_dir_all(&bird_dir).unwrap();
+        let project = find_project(tmp.path()).unwrap();
+
+        // No settings file: defaults
+        assert_eq!(project.load_config().unwrap(), ProjectConfig::default());
+
+        std::fs::write(
+            project.config_path(),
+            "capture = false\nextract_format = \"cargo\"\n\n[dims]\nproject = \"payments\"\nteam = \"core\"\n",
+        )
+        .unwrap();
+        let config = project.load_config().unwrap();
+        assert!(!config.capture_enabled());
+        assert_eq!(config.extract_format.as_deref(), Some("cargo"));
+
+        let vars = config.env_vars(&project.root);
+        assert_eq!(vars[0], (PROJECT_VAR, project.root.display().to_string()));
+        assert!(vars.contains(&(PROJECT_CAPTURE_VAR, "0".to_string())));
+        assert!(vars.contains(&(PROJECT_DIMS_VAR, "project=payments\nteam=core".to_string())));
+        assert!(!vars.iter().any(|(name, _)| *name == PROJECT_EXTRACT_VAR));
+
+        std::fs::write(project.config_path(), "capture = maybe\n").unwrap();
+        assert!(project.load_config().is_err());
+    }
+
     #[test]
     fn test_is_initialized() {
         let tmp = TempDir::new().unwrap();
@@ -7,15 +7,20 @@
 //! - **Source selectors**: `host:type:client:session:`
 //! - **Path filters**: `.`, `~/`, `/path/`
 //! - **Command regex**: `%/pattern/`
-//! - **Field filters**: `%field<op>value`
+//! - **Field filters**: `%field<op>value` (`%timestamp>2h`: durations count
+//!   back from now)
+//! - **Time bound**: `@30m`, `@2d`, `@2024-01-15` (since then)
 //! - **Tags**: `%tag-name` or bare word
 //! - **Range**: `~N` or `~N:~M`
+//!
+//! [`parse_query`] never fails; [`parse_query_strict`] reports what it had
+//! to guess at as [`QueryDiagnostic`]s located in the input.
 
 mod parser;
 
 pub use parser::{
-    parse_query, CompareOp, FieldFilter, PathFilter, Query, QueryComponent, RangeSelector,
-    SourceSelector,
+    parse_query, parse_query_strict, CompareOp, FieldFilter, PathFilter, Query, QueryComponent,
+    QueryDiagnostic, RangeSelector, SourceSelector,
 };
 
 #[cfg(test)]
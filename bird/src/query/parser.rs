@@ -1,7 +1,14 @@
 //! Query parser for the cross-client micro-language.
 
+use std::ops::Range;
 use std::path::PathBuf;
 
+use chrono::Utc;
+
+use crate::reltime::{parse_duration, parse_time};
+use crate::suggest::suggest;
+use crate::{Error, Result};
+
 /// A parsed query containing all components.
 #[derive(Debug, Clone, Default, PartialEq)]
 pub struct Query {
@@ -67,7 +74,9 @@ pub enum QueryComponent {
 /// Field filter with comparison operator.
 #[derive(Debug, Clone, PartialEq)]
 pub struct FieldFilter {
-    /// Field name (cmd, exit, cwd, duration, host, type, client, session)
+    /// Field name (cmd, exit, cwd, duration, host, type, client, session, date,
+    /// timestamp, linked, terminal, title, mux, window), or `dim:<key>` for a
+    /// user-defined dimension
     pub field: String,
     /// Comparison operator
     pub op: CompareOp,
@@ -130,14 +139,93 @@ impl RangeSelector {
 }
 
 /// Parse a query string into structured components.
+///
+/// Never fails: anything that doesn't parse is read as a tag or skipped.
+/// Use [`parse_query_strict`] for input typed by a user.
 pub fn parse_query(input: &str) -> Query {
+    parse(input, &mut Vec::new())
+}
+
+/// Parse a query string, failing with [`Error::InvalidQuery`] on input
+/// [`parse_query`] would only make a guess at: a filter on an unknown field
+/// (`%exot<>0` would be a tag), an unterminated `%/regex`, a bad `@` time
+/// or stray characters.
+pub fn parse_query_strict(input: &str) -> Result<Query> {
+    let mut diagnostics = Vec::new();
+    let query = parse(input, &mut diagnostics);
+    if diagnostics.is_empty() {
+        Ok(query)
+    } else {
+        Err(Error::InvalidQuery { query: input.to_string(), diagnostics })
+    }
+}
+
+/// A problem [`parse_query_strict`] found, located in the query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryDiagnostic {
+    /// Byte range of the query the problem is in.
+    pub span: Range<usize>,
+    pub message: String,
+    /// What the span probably should have been (`%exit` for `%exot`).
+    pub suggestion: Option<String>,
+}
+
+impl QueryDiagnostic {
+    fn new(span: Range<usize>, message: impl Into<String>) -> Self {
+        Self { span, message: message.into(), suggestion: None }
+    }
+
+    /// The query with the span underlined, then the message and suggestion:
+    ///
+    /// ```text
+    ///   %exot<>0
+    ///   ^^^^^ unknown field `exot`
+    ///   help: did you mean `%exit`?
+    /// ```
+    pub fn render(&self, query: &str) -> String {
+        let start = self.span.start.min(query.len());
+        let end = self.span.end.clamp(start, query.len());
+        let indent = query.get(..start).map_or(0, |s| s.chars().count());
+        let width = query.get(start..end).map_or(1, |s| s.chars().count().max(1));
+        let mut out = format!("  {}\n  {}{} {}\n", query, " ".repeat(indent), "^".repeat(width), self.message);
+        if let Some(suggestion) = &self.suggestion {
+            out.push_str(&format!("  help: did you mean `{}`?\n", suggestion));
+        }
+        out
+    }
+}
+
+impl std::fmt::Display for QueryDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean `{}`?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Field names of `%field<op>value` filters.
+const FIELDS: &[&str] = &[
+    "cmd", "exit", "cwd", "duration", "host", "type", "client", "session", "date", "timestamp", "linked",
+    "terminal", "title", "mux", "window",
+];
+
+/// Parse `input`, adding a diagnostic for each part that only parses as a guess.
+fn parse(input: &str, diagnostics: &mut Vec<QueryDiagnostic>) -> Query {
     let mut query = Query::default();
+    let lead = input.len() - input.trim_start().len();
     let input = input.trim();
 
     if input.is_empty() {
         return query;
     }
 
+    // Byte offset of a suffix of the (trimmed) input in the original input
+    let offset = |rest: &str| lead + input.len() - rest.len();
+    // After a problem, the rest of its word isn't reported again
+    let mut quiet_until = 0;
+
     // Track remaining input as we parse components
     let mut remaining = input;
 
@@ -156,6 +244,14 @@ pub fn parse_query(input: &str) -> Query {
     // Parse remaining components (filters, tags, range)
     while !remaining.is_empty() {
         remaining = remaining.trim_start();
+        let start = offset(remaining);
+        let word_end = start + remaining.find(char::is_whitespace).unwrap_or(remaining.len());
+        let mut report = |diagnostic: QueryDiagnostic| {
+            if diagnostic.span.start >= quiet_until {
+                quiet_until = word_end;
+                diagnostics.push(diagnostic);
+            }
+        };
 
         // Check for range at end
         if let Some((range, rest)) = try_parse_range(remaining) {
@@ -164,8 +260,18 @@ pub fn parse_query(input: &str) -> Query {
             continue;
         }
 
+        // Check for time bound (@2h, @2024-01-15)
+        if let Some((filter, rest)) = try_parse_since(remaining) {
+            query.filters.push(QueryComponent::FieldFilter(filter));
+            remaining = rest;
+            continue;
+        }
+
         // Check for filter/tag (starts with %)
         if let Some((component, rest)) = try_parse_filter(remaining) {
+            if let Some(diagnostic) = check_filter(&component, rest, start) {
+                report(diagnostic);
+            }
             query.filters.push(component);
             remaining = rest;
             continue;
@@ -173,6 +279,9 @@ pub fn parse_query(input: &str) -> Query {
 
         // Bare word = tag fallback (consume until range or end)
         if let Some((tag, rest)) = try_parse_bare_tag(remaining) {
+            if let Some(diagnostic) = check_bare_tag(&tag, start) {
+                report(diagnostic);
+            }
             query.filters.push(QueryComponent::Tag(tag));
             remaining = rest;
             continue;
@@ -182,15 +291,80 @@ pub fn parse_query(input: &str) -> Query {
         // Advance on a char boundary: `&remaining[1..]` byte-slices and
         // panics on multibyte UTF-8 input.
         let mut chars = remaining.chars();
-        chars.next();
+        let skipped = chars.next().unwrap_or_default();
+        if skipped == '@' {
+            let end = start + 1 + find_filter_end(&remaining[1..]);
+            report(QueryDiagnostic::new(
+                start..end,
+                "invalid time bound (use a duration like @30m or @2d, or a date like @2024-01-15)",
+            ));
+        } else {
+            report(QueryDiagnostic::new(start..start + skipped.len_utf8(), format!("unexpected `{}`", skipped)));
+        }
         remaining = chars.as_str();
     }
 
     query
 }
 
+/// Problems with a `%` component starting at `start`, followed by `rest`.
+fn check_filter(component: &QueryComponent, rest: &str, start: usize) -> Option<QueryDiagnostic> {
+    match component {
+        QueryComponent::Tag(tag) if tag.starts_with('/') => Some(QueryDiagnostic::new(
+            start..start + 1 + tag.len(),
+            "unterminated command regex (close it with `/`)",
+        )),
+        QueryComponent::Tag(tag) => {
+            // `%exot<>0` and `%exot~=x` read as tags; they're filters on a field we don't know
+            let op_at = tag.find(['<', '>', '=', '!']).or_else(|| rest.starts_with("~=").then_some(tag.len()))?;
+            Some(unknown_field(&tag[..op_at], start, true))
+        }
+        QueryComponent::FieldFilter(filter) if filter.field == "exit" && filter.value.parse::<i32>().is_err() => {
+            let value_start = start + 1 + "exit".len() + filter.op.to_string().len();
+            let span = value_start..value_start + filter.value.len().max(1);
+            Some(QueryDiagnostic::new(span, "`%exit` takes an exit code, e.g. %exit=1"))
+        }
+        QueryComponent::FieldFilter(filter) if filter.value.is_empty() && filter.field != "linked" => {
+            Some(QueryDiagnostic::new(start..start + 1 + filter.field.len(), format!("missing value for `%{}`", filter.field)))
+        }
+        _ => None,
+    }
+}
+
+/// Problems with a bare word starting at `start`: `exit=0` for `%exit=0`.
+fn check_bare_tag(tag: &str, start: usize) -> Option<QueryDiagnostic> {
+    let op_at = tag.find(['<', '>', '=', '!'])?;
+    let field = &tag[..op_at];
+    if FIELDS.contains(&field) {
+        let mut diagnostic = QueryDiagnostic::new(start..start + field.len(), "field filters start with `%`");
+        diagnostic.suggestion = Some(format!("%{}", field));
+        return Some(diagnostic);
+    }
+    Some(unknown_field(field, start, false))
+}
+
+/// A filter on `field`, which isn't a known field, at `start` (the `%` if `percent`).
+fn unknown_field(field: &str, start: usize, percent: bool) -> QueryDiagnostic {
+    let span = start..start + usize::from(percent) + field.len().max(1);
+    if field.is_empty() {
+        return QueryDiagnostic::new(span, "missing field name");
+    }
+    let mut diagnostic = QueryDiagnostic::new(span, format!("unknown field `{}`", field));
+    diagnostic.suggestion = match suggest(field, FIELDS.iter().copied()) {
+        Some(known) => Some(format!("%{}", known)),
+        // Not a typo of a field: probably a user-defined dimension
+        None => Some(format!("%dim:{}", field)),
+    };
+    diagnostic
+}
+
 /// Try to parse a source selector (ends with ':').
 fn try_parse_source(input: &str) -> Option<(SourceSelector, &str)> {
+    // A time bound may contain ':' (@2024-01-15T14:02)
+    if input.starts_with('@') {
+        return None;
+    }
+
     // Source selector must contain ':' and the relevant part ends with ':'
     // But we need to find where the source selector ends
 
@@ -447,6 +621,32 @@ fn try_parse_range(input: &str) -> Option<(RangeSelector, &str)> {
     None
 }
 
+/// Try to parse a time bound: `@<time>` selects invocations since that time,
+/// a duration back from now (`@30m`, `@2d`) or a date (`@2024-01-15`).
+/// See [`crate::reltime`].
+fn try_parse_since(input: &str) -> Option<(FieldFilter, &str)> {
+    let after_at = input.strip_prefix('@')?;
+    let end = find_filter_end(after_at);
+    let since = parse_time(&after_at[..end], Utc::now()).ok()?;
+    let filter = FieldFilter {
+        field: "timestamp".to_string(),
+        op: CompareOp::Gte,
+        value: since.format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    Some((filter, &after_at[end..]))
+}
+
+/// Resolve a duration given for a `date` or `timestamp` filter
+/// (`%timestamp>2h`, `%date>=7d`) to the time that long ago. Other values
+/// are kept as written.
+fn resolve_time_value(field: &str, value: &str) -> String {
+    match (field, parse_duration(value)) {
+        ("date", Some(duration)) => (Utc::now() - duration).format("%Y-%m-%d").to_string(),
+        ("timestamp", Some(duration)) => (Utc::now() - duration).format("%Y-%m-%d %H:%M:%S").to_string(),
+        _ => value.to_string(),
+    }
+}
+
 /// Try to parse a filter component (%, %/, %field<op>value).
 fn try_parse_filter(input: &str) -> Option<(QueryComponent, &str)> {
     if !input.starts_with('%') {
@@ -496,6 +696,18 @@ fn try_parse_filter(input: &str) -> Option<(QueryComponent, &str)> {
         return Some((QueryComponent::FieldFilter(filter), rest));
     }
 
+    // %linked: has any link (checked after %linked<op>value)
+    if let Some(rest) = after_percent.strip_prefix("linked") {
+        if rest.is_empty() || rest.starts_with(['~', '%']) || rest.starts_with(char::is_whitespace) {
+            let filter = FieldFilter {
+                field: "linked".to_string(),
+                op: CompareOp::NotEq,
+                value: String::new(),
+            };
+            return Some((QueryComponent::FieldFilter(filter), rest));
+        }
+    }
+
     // Tag: %bare-word (no operator)
     let end = find_filter_end(after_percent);
     if end > 0 {
@@ -509,10 +721,15 @@ fn try_parse_filter(input: &str) -> Option<(QueryComponent, &str)> {
 
 /// Try to parse a field filter (field<op>value).
 fn try_parse_field_filter(input: &str) -> Option<(FieldFilter, &str)> {
-    // Known field names
-    let fields = ["cmd", "exit", "cwd", "duration", "host", "type", "client", "session"];
-
-    for field in &fields {
+    // User-defined dimension: dim:<key>
+    let dim_field = input.strip_prefix("dim:").map(|after| {
+        let key_len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')))
+            .unwrap_or(after.len());
+        &input[..4 + key_len]
+    });
+
+    for field in dim_field.iter().chain(FIELDS.iter()) {
         if let Some(after_field) = input.strip_prefix(field) {
 
             // Try each operator (order matters: check 2-char ops before 1-char)
@@ -544,7 +761,7 @@ fn try_parse_field_filter(input: &str) -> Option<(FieldFilter, &str)> {
                 FieldFilter {
                     field: field.to_string(),
                     op,
-                    value: value.to_string(),
+                    value: resolve_time_value(field, value),
                 },
                 rest,
             ));
@@ -595,7 +812,12 @@ impl Query {
             && self.range.is_none()
     }
 
-    /// Check if source selector is for all sources.
+    /// Check if the source selector names a host, which may only exist on a remote.
+    pub fn needs_remotes(&self) -> bool {
+        matches!(&self.source, Some(s) if s.host.is_some())
+    }
+
+        /// Check if source selector is for all sources.
     pub fn is_all_sources(&self) -> bool {
         match &self.source {
             None => false,
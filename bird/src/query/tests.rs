@@ -139,6 +139,14 @@ fn test_source_wildcards() {
     assert_eq!(source.session, Some("*".to_string()));
 }
 
+#[test]
+fn test_needs_remotes() {
+    assert!(parse_query("*:*:*:*:").needs_remotes());
+    assert!(parse_query("laptop:shell:zsh:").needs_remotes());
+    assert!(!parse_query("shell:zsh:").needs_remotes());
+    assert!(!parse_query("%exit<>0 ~5").needs_remotes());
+}
+
 #[test]
 fn test_source_partial_wildcards() {
     let q = parse_query("*:shell:*:*:");
@@ -264,6 +272,28 @@ fn test_field_filter_exit_neq_bang() {
     }
 }
 
+#[test]
+fn test_field_filter_date() {
+    let q = parse_query("%date>=2026-01-01%timestamp<2026-02-01T12:00:00~5");
+    assert_eq!(q.filters.len(), 2);
+    assert_eq!(
+        q.filters[0],
+        QueryComponent::FieldFilter(FieldFilter {
+            field: "date".to_string(),
+            op: CompareOp::Gte,
+            value: "2026-01-01".to_string(),
+        })
+    );
+    if let QueryComponent::FieldFilter(f) = &q.filters[1] {
+        assert_eq!(f.field, "timestamp");
+        assert_eq!(f.op, CompareOp::Lt);
+        assert_eq!(f.value, "2026-02-01T12:00:00");
+    } else {
+        panic!("Expected FieldFilter");
+    }
+    assert_eq!(q.range, Some(RangeSelector { start: 5, end: None }));
+}
+
 #[test]
 fn test_field_filter_duration() {
     let q = parse_query("%duration>5000");
@@ -290,6 +320,98 @@ fn test_field_filter_regex() {
     }
 }
 
+#[test]
+fn test_field_filter_terminal() {
+    let q = parse_query("%mux=tmux%window=work:2%title~=vim~3");
+    let fields: Vec<_> = q
+        .filters
+        .iter()
+        .map(|f| match f {
+            QueryComponent::FieldFilter(f) => (f.field.as_str(), f.op, f.value.as_str()),
+            other => panic!("Expected FieldFilter, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(fields, [("mux", CompareOp::Eq, "tmux"), ("window", CompareOp::Eq, "work:2"), ("title", CompareOp::Regex, "vim")]);
+    assert_eq!(q.range.unwrap().start, 3);
+}
+
+#[test]
+fn test_relative_times() {
+    let timestamp = |q: &Query| match &q.filters[0] {
+        QueryComponent::FieldFilter(f) => (f.field.clone(), f.op, f.value.clone()),
+        other => panic!("Expected FieldFilter, got {:?}", other),
+    };
+    let ago = |d: chrono::TimeDelta, format: &str| (chrono::Utc::now() - d).format(format).to_string();
+
+    // @<time> = since then; second-resolution values may tick over
+    let q = parse_query("@30m%failed~5");
+    let (field, op, value) = timestamp(&q);
+    assert_eq!((field.as_str(), op), ("timestamp", CompareOp::Gte));
+    assert!(value <= ago(chrono::TimeDelta::minutes(30), "%Y-%m-%d %H:%M:%S"), "{}", value);
+    assert!(value >= ago(chrono::TimeDelta::minutes(31), "%Y-%m-%d %H:%M:%S"), "{}", value);
+    assert_eq!(q.filters.len(), 2);
+    assert_eq!(q.range.unwrap().start, 5);
+    assert!(q.source.is_none());
+
+    let q = parse_query("%date>=7d");
+    assert_eq!(timestamp(&q).2, ago(chrono::TimeDelta::days(7), "%Y-%m-%d"));
+    let q = parse_query("%date=2024-01-15");
+    assert_eq!(timestamp(&q).2, "2024-01-15");
+
+    // Not a time: left as a tag
+    let q = parse_query("@soon");
+    assert!(q.filters.is_empty() || matches!(q.filters[0], QueryComponent::Tag(_)), "{:?}", q);
+}
+
+#[test]
+fn test_field_filter_dim() {
+    let q = parse_query("%dim:ticket=JIRA-123%dim:team<>payments~5");
+    assert_eq!(q.filters.len(), 2);
+    assert_eq!(
+        q.filters[0],
+        QueryComponent::FieldFilter(FieldFilter {
+            field: "dim:ticket".to_string(),
+            op: CompareOp::Eq,
+            value: "JIRA-123".to_string(),
+        })
+    );
+    if let QueryComponent::FieldFilter(f) = &q.filters[1] {
+        assert_eq!(f.field, "dim:team");
+        assert_eq!(f.op, CompareOp::NotEq);
+        assert_eq!(f.value, "payments");
+    } else {
+        panic!("Expected FieldFilter");
+    }
+    assert_eq!(q.range.unwrap().start, 5);
+    assert!(q.source.is_none());
+}
+
+#[test]
+fn test_field_filter_linked() {
+    let q = parse_query("%linked~5");
+    assert_eq!(
+        q.filters,
+        vec![QueryComponent::FieldFilter(FieldFilter {
+            field: "linked".to_string(),
+            op: CompareOp::NotEq,
+            value: String::new(),
+        })]
+    );
+    assert_eq!(q.range.unwrap().start, 5);
+
+    let q = parse_query("%linked=issue");
+    if let QueryComponent::FieldFilter(f) = &q.filters[0] {
+        assert_eq!(f.field, "linked");
+        assert_eq!(f.op, CompareOp::Eq);
+        assert_eq!(f.value, "issue");
+    } else {
+        panic!("Expected FieldFilter");
+    }
+
+    // Only the exact word is the alias; anything longer is a tag
+    assert_eq!(parse_query("%linkedin").filters, vec![QueryComponent::Tag("linkedin".to_string())]);
+}
+
 #[test]
 fn test_tag_explicit() {
     let q = parse_query("%my-project");
@@ -411,3 +533,56 @@ fn test_multibyte_mixed_query_does_not_panic() {
     let _ = parse_query("¡hola");
     let _ = parse_query("★%failed");
 }
+
+// Strict parsing
+
+#[test]
+fn test_strict_accepts_valid_queries() {
+    for input in ["", "%failed~5", "laptop:shell:zsh:%cwd~=/magic/%/make/~3", "%exit<>0 %host=*", "deploy %dim:ticket=A-1"] {
+        assert_eq!(parse_query_strict(input).unwrap(), parse_query(input), "{}", input);
+    }
+}
+
+fn strict_diagnostics(input: &str) -> Vec<QueryDiagnostic> {
+    match parse_query_strict(input) {
+        Err(crate::Error::InvalidQuery { diagnostics, .. }) => diagnostics,
+        other => panic!("expected InvalidQuery for {:?}, got {:?}", input, other),
+    }
+}
+
+#[test]
+fn test_strict_unknown_field_suggests_known_one() {
+    let diagnostics = strict_diagnostics("%exot<>0");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].span, 0..5);
+    assert_eq!(diagnostics[0].message, "unknown field `exot`");
+    assert_eq!(diagnostics[0].suggestion.as_deref(), Some("%exit"));
+    assert_eq!(
+        diagnostics[0].render("%exot<>0"),
+        "  %exot<>0\n  ^^^^^ unknown field `exot`\n  help: did you mean `%exit`?\n"
+    );
+
+    // One report per word, however the lenient parse splits it up
+    let diagnostics = strict_diagnostics("make %cmnd~=build");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].span, 5..10);
+    assert_eq!(diagnostics[0].suggestion.as_deref(), Some("%cmd"));
+
+    let diagnostics = strict_diagnostics("%ticket=A-1");
+    assert_eq!(diagnostics[0].suggestion.as_deref(), Some("%dim:ticket"));
+}
+
+#[test]
+fn test_strict_other_mistakes() {
+    let diagnostics = strict_diagnostics("exit=0");
+    assert_eq!((diagnostics[0].span.clone(), diagnostics[0].suggestion.as_deref()), (0..4, Some("%exit")));
+
+    assert!(strict_diagnostics("%/cargo")[0].message.contains("unterminated"));
+    assert!(strict_diagnostics("%exit=bad")[0].message.contains("exit code"));
+    assert!(strict_diagnostics("@soon")[0].message.contains("time bound"));
+
+    // Spans count from the untrimmed input, in bytes
+    let diagnostics = strict_diagnostics("  ★ ~2");
+    assert_eq!(diagnostics[0].span, 2..5);
+    assert_eq!(diagnostics[0].message, "unexpected `★`");
+}
@@ -0,0 +1,248 @@
+//! Relative times - "3m ago" for display, "2h" or "7d" for input.
+//!
+//! Everywhere a time or date is accepted (`--since`, `--as-of`, `@30m` and
+//! `%timestamp>2h` in queries) a duration back from now works too:
+//! `30s`, `30m`/`30min`, `2h`, `7d`, `2w`, `3mo`, `1y`. Displayed times
+//! count back from now while recent ("3m ago", "yesterday 14:02") and turn
+//! into dates as they age, on the clock of the zone the store rendered them
+//! in (the system's, or `display.timezone`).
+
+use chrono::{
+    DateTime, Datelike, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Utc,
+};
+
+use crate::{Error, Result};
+
+/// Parse a duration such as `30m`, `2h` or `7d`.
+///
+/// Units: `s`, `m`/`min`, `h`, `d`, `w`, `mo` (30 days), `y` (365 days).
+pub fn parse_duration(s: &str) -> Option<TimeDelta> {
+    let s = s.trim().to_lowercase();
+    let split = s.find(|c: char| !c.is_ascii_digit())?;
+    let (num, unit) = s.split_at(split);
+    let n: i64 = num.parse().ok()?;
+    match unit {
+        "s" | "sec" => TimeDelta::try_seconds(n),
+        "m" | "min" => TimeDelta::try_minutes(n),
+        "h" | "hr" => TimeDelta::try_hours(n),
+        "d" => TimeDelta::try_days(n),
+        "w" => TimeDelta::try_weeks(n),
+        "mo" => TimeDelta::try_days(n.checked_mul(30)?),
+        "y" => TimeDelta::try_days(n.checked_mul(365)?),
+        _ => None,
+    }
+}
+
+/// Parse a point in time: a duration back from `now` (`2h`), `today`,
+/// `yesterday`, a date (`2024-01-15`, local midnight) or a local date and
+/// time (`2024-01-15 14:02`).
+pub fn parse_time(s: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let s = s.trim();
+    if let Some(duration) = parse_duration(s) {
+        return Ok(now - duration);
+    }
+
+    let today = now.with_timezone(&Local).date_naive();
+    let date = match s.to_lowercase().as_str() {
+        "today" => Some(today),
+        "yesterday" => today.pred_opt(),
+        _ => NaiveDate::parse_from_str(s, "%Y-%m-%d").ok(),
+    };
+    let local = match date {
+        Some(date) => date.and_hms_opt(0, 0, 0),
+        None => ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok()),
+    };
+    local
+        .and_then(|local| Local.from_local_datetime(&local).earliest())
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| {
+            Error::Config(format!("Invalid time '{}' (expected e.g. 30m, 2h, 7d or 2024-01-15)", s))
+        })
+}
+
+/// Parse a "since" string into a date.
+///
+/// Supports:
+/// - Duration: "2h", "7d", "2w", "3mo" (see [`parse_duration`])
+/// - Date: "2024-01-15"
+///
+/// `--since` filters are day-precise: a duration keeps only the (UTC) day it
+/// reaches back to, so "2h" just after midnight includes all of yesterday.
+pub fn parse_since(s: &str) -> Result<NaiveDate> {
+    let s = s.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date);
+    }
+    let now = Utc::now();
+    if let Some(duration) = parse_duration(s) {
+        return Ok((now - duration).date_naive());
+    }
+    parse_time(s, now).map(|time| time.date_naive())
+}
+
+/// Parse a timestamp as the store renders it, keeping its offset:
+/// `2024-01-15 15:02:03.123456+01` in the display zone, or without an offset
+/// (`2024-01-15 14:02:03`) meaning UTC.
+pub fn parse_timestamp(s: &str) -> Option<DateTime<FixedOffset>> {
+    let s = s.trim();
+    DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f%#z")
+        .or_else(|_| DateTime::parse_from_rfc3339(s))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f")
+                .ok()
+                .map(|time| time.and_utc().fixed_offset())
+        })
+}
+
+/// `time` relative to `now`, on the wall clock of `time`'s own zone:
+/// "12s ago", "3m ago", "2h ago", "today 08:15", "yesterday 14:02",
+/// "Mon 14:02", "Oct 03 14:02", and just the date after a year.
+pub fn format_relative<Tz: TimeZone>(time: DateTime<Tz>, now: DateTime<Utc>) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let age = now - time.with_timezone(&Utc);
+    let tz = time.timezone();
+    let local = time;
+    // A little clock skew still reads as now
+    if age < TimeDelta::seconds(-5) {
+        return local.format("%Y-%m-%d %H:%M").to_string();
+    }
+    if age < TimeDelta::minutes(1) {
+        return format!("{}s ago", age.num_seconds().max(0));
+    }
+    if age < TimeDelta::hours(1) {
+        return format!("{}m ago", age.num_minutes());
+    }
+    if age < TimeDelta::hours(12) {
+        return format!("{}h ago", age.num_hours());
+    }
+
+    let today = now.with_timezone(&tz).date_naive();
+    let date = local.date_naive();
+    if date == today {
+        local.format("today %H:%M").to_string()
+    } else if Some(date) == today.pred_opt() {
+        local.format("yesterday %H:%M").to_string()
+    } else if today - date < TimeDelta::days(7) {
+        local.format("%a %H:%M").to_string()
+    } else if date.year() == today.year() || today - date < TimeDelta::days(300) {
+        local.format("%b %d %H:%M").to_string()
+    } else {
+        local.format("%Y-%m-%d").to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap().and_utc()
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s"), Some(TimeDelta::seconds(30)));
+        assert_eq!(parse_duration("30m"), Some(TimeDelta::minutes(30)));
+        assert_eq!(parse_duration("30min"), Some(TimeDelta::minutes(30)));
+        assert_eq!(parse_duration("2H"), Some(TimeDelta::hours(2)));
+        assert_eq!(parse_duration("7d"), Some(TimeDelta::days(7)));
+        assert_eq!(parse_duration("2w"), Some(TimeDelta::days(14)));
+        assert_eq!(parse_duration("3mo"), Some(TimeDelta::days(90)));
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("2024-01-15"), None);
+        assert_eq!(parse_duration("5x"), None);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let now = at("2024-06-10 12:00:00");
+        assert_eq!(parse_time("30m", now).unwrap(), at("2024-06-10 11:30:00"));
+        assert_eq!(parse_time("2d", now).unwrap(), at("2024-06-08 12:00:00"));
+
+        let local = |s: &str| {
+            let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+            Local.from_local_datetime(&naive).earliest().unwrap().with_timezone(&Utc)
+        };
+        assert_eq!(parse_time("2024-01-15", now).unwrap(), local("2024-01-15 00:00"));
+        assert_eq!(parse_time("2024-01-15 14:02", now).unwrap(), local("2024-01-15 14:02"));
+        assert!(parse_time("soon", now).is_err());
+    }
+
+    #[test]
+    fn test_parse_since_days() {
+        let today = Utc::now().date_naive();
+        let result = parse_since("7d").unwrap();
+        assert_eq!(result, today - TimeDelta::days(7));
+    }
+
+    #[test]
+    fn test_parse_since_weeks() {
+        let today = Utc::now().date_naive();
+        let result = parse_since("2w").unwrap();
+        assert_eq!(result, today - TimeDelta::days(14));
+    }
+
+    #[test]
+    fn test_parse_since_months() {
+        let today = Utc::now().date_naive();
+        let result = parse_since("1mo").unwrap();
+        assert_eq!(result, today - TimeDelta::days(30));
+    }
+
+    #[test]
+    fn test_parse_since_hours() {
+        let result = parse_since("2h").unwrap();
+        assert_eq!(result, (Utc::now() - TimeDelta::hours(2)).date_naive());
+    }
+
+    #[test]
+    fn test_parse_since_date() {
+        let result = parse_since("2024-01-15").unwrap();
+        assert_eq!(result, NaiveDate::from_ymd_opt(2024, 1, 15).unwrap());
+    }
+
+    #[test]
+    fn test_parse_since_invalid() {
+        assert!(parse_since("invalid").is_err());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("2024-01-15 14:02:03.123456").unwrap().timestamp_subsec_micros(), 123456);
+        assert_eq!(parse_timestamp("2024-01-15 14:02:03"), Some(at("2024-01-15 14:02:03").fixed_offset()));
+        let zoned = parse_timestamp("2024-01-15 16:02:03+02").unwrap();
+        assert_eq!(zoned, at("2024-01-15 14:02:03"));
+        assert_eq!(zoned.offset().local_minus_utc(), 2 * 3600);
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn test_format_relative() {
+        let now = at("2024-06-10 20:00:00");
+        let fmt = |s: &str| format_relative(at(s), now);
+        assert_eq!(fmt("2024-06-10 19:59:48"), "12s ago");
+        assert_eq!(fmt("2024-06-10 19:57:00"), "3m ago");
+        assert_eq!(fmt("2024-06-10 17:30:00"), "2h ago");
+        assert_eq!(fmt("2024-06-10 07:15:00"), "today 07:15");
+        assert_eq!(fmt("2024-06-09 14:02:00"), "yesterday 14:02");
+        assert_eq!(fmt("2024-06-06 09:00:00"), "Thu 09:00");
+        assert_eq!(fmt("2024-03-02 09:00:00"), "Mar 02 09:00");
+        assert_eq!(fmt("2023-03-02 09:00:00"), "2023-03-02");
+        // From the future (clock skew): absolute
+        assert_eq!(fmt("2024-06-11 09:00:00"), "2024-06-11 09:00");
+    }
+
+    #[test]
+    fn test_format_relative_uses_time_zone_of_time() {
+        // 23:30 UTC on the 9th is 01:30 on the 10th in Berlin (+02)
+        let now = at("2024-06-10 20:00:00");
+        let berlin = parse_timestamp("2024-06-10 01:30:00+02").unwrap();
+        assert_eq!(format_relative(berlin, now), "today 01:30");
+        assert_eq!(format_relative(berlin.with_timezone(&Utc), now), "yesterday 23:30");
+    }
+}
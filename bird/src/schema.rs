@@ -336,6 +336,36 @@ pub const BIRD_INVOCATION_UUID_VAR: &str = "BIRD_INVOCATION_UUID";
 /// Used to avoid duplicate recording in nested scenarios.
 pub const BIRD_PARENT_CLIENT_VAR: &str = "BIRD_PARENT_CLIENT";
 
+/// Metadata key holding user-defined dimensions (`shq run --dim team=payments`).
+///
+/// Stored as a JSON object of string values and queried with `%dim:team=payments`.
+pub const DIMS_METADATA_KEY: &str = "dims";
+
+/// Parse a `key=value` dimension.
+///
+/// Keys are limited to letters, digits, `_`, `-` and `.` so they can be
+/// used unquoted in queries.
+pub fn parse_dim(s: &str) -> crate::Result<(String, String)> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| crate::Error::Config(format!("Invalid dimension '{}': expected key=value", s)))?;
+    if !is_dim_key(key) {
+        return Err(crate::Error::Config(format!(
+            "Invalid dimension key '{}': use letters, digits, '_', '-' or '.'",
+            key
+        )));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Whether `key` is a valid dimension name.
+pub fn is_dim_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
 impl InvocationRecord {
     /// Create a new invocation record.
     ///
@@ -536,6 +566,23 @@ impl InvocationRecord {
         self
     }
 
+    /// Add user-defined dimensions (merged into any already set).
+    pub fn with_dims(mut self, dims: &[(String, String)]) -> Self {
+        if dims.is_empty() {
+            return self;
+        }
+        let entry = self
+            .metadata
+            .entry(DIMS_METADATA_KEY.to_string())
+            .or_insert_with(|| serde_json::json!({}));
+        if let Some(object) = entry.as_object_mut() {
+            for (key, value) in dims {
+                object.insert(key.clone(), serde_json::Value::String(value.clone()));
+            }
+        }
+        self
+    }
+
     /// Get the date portion of the timestamp (for partitioning).
     pub fn date(&self) -> NaiveDate {
         self.timestamp.date_naive()
@@ -721,6 +768,10 @@ pub struct OutputRecord {
 
     /// Date for partitioning.
     pub date: NaiveDate,
+
+    /// Position among the invocation's outputs on this stream, from 0
+    /// (commands that restart or run in phases can have several).
+    pub sequence: i32,
 }
 
 impl OutputRecord {
@@ -752,6 +803,7 @@ impl OutputRecord {
             storage_ref,
             content_type: Some("text/plain".to_string()),
             date,
+            sequence: 0,
         }
     }
 
@@ -820,6 +872,21 @@ pub struct EventRecord {
 
     /// Date for partitioning.
     pub date: NaiveDate,
+
+    /// How the event was produced: [`EVENT_ORIGIN_PARSER`] or
+    /// [`EVENT_ORIGIN_MANUAL`] (added or corrected by hand).
+    #[serde(default = "default_event_origin")]
+    pub origin: String,
+}
+
+/// Origin of events extracted from output by a parser.
+pub const EVENT_ORIGIN_PARSER: &str = "parser";
+
+/// Origin of events added or corrected by hand.
+pub const EVENT_ORIGIN_MANUAL: &str = "manual";
+
+fn default_event_origin() -> String {
+    EVENT_ORIGIN_PARSER.to_string()
 }
 
 impl EventRecord {
@@ -846,6 +913,7 @@ impl EventRecord {
             status: None,
             format_used: format_used.into(),
             date,
+            origin: default_event_origin(),
         }
     }
 }
@@ -867,7 +935,8 @@ CREATE TABLE events (
     test_name         VARCHAR,
     status            VARCHAR,
     format_used       VARCHAR NOT NULL,
-    date              DATE NOT NULL
+    date              DATE NOT NULL,
+    origin            VARCHAR           -- parser (NULL in older rows) or manual
 );
 "#;
 
@@ -996,6 +1065,29 @@ pub const BIRD_SCHEMA_VERSION: &str = "5";
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_dim() {
+        assert_eq!(
+            parse_dim("ticket=JIRA-123").unwrap(),
+            ("ticket".to_string(), "JIRA-123".to_string())
+        );
+        assert_eq!(parse_dim("note=a=b").unwrap().1, "a=b");
+        assert!(parse_dim("ticket").is_err());
+        assert!(parse_dim("my key=x").is_err());
+        assert!(parse_dim("=x").is_err());
+    }
+
+    #[test]
+    fn test_with_dims() {
+        let dims = vec![
+            ("team".to_string(), "payments".to_string()),
+            ("ticket".to_string(), "JIRA-123".to_string()),
+        ];
+        let record = InvocationRecord::new("s", "make", "/p", 0, "test@client").with_dims(&dims);
+        assert_eq!(record.metadata[DIMS_METADATA_KEY]["team"], "payments");
+        assert_eq!(record.metadata[DIMS_METADATA_KEY]["ticket"], "JIRA-123");
+    }
+
     #[test]
     fn test_extract_executable() {
         assert_eq!(extract_executable("make test"), Some("make".to_string()));
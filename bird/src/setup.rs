@@ -0,0 +1,176 @@
+//! Setup bundles (`shq setup export` / `shq setup apply`).
+//!
+//! A bundle is a tar of the files that make up a BIRD setup, but none of
+//! its data: `config.toml` (capture, query and display settings, themes,
+//! remotes, cron jobs), `format-hints.toml` and `event-formats.toml`.
+//! Applying one keeps the machine's own client id, and never touches keys.
+
+use std::io::Read;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::{Error, Result};
+
+/// Files a bundle may hold, relative to BIRD_ROOT.
+pub const BUNDLE_FILES: &[&str] = &["config.toml", "format-hints.toml", "event-formats.toml"];
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// What applying a bundle did (or would do) to one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Updated,
+    Unchanged,
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added => write!(f, "added"),
+            Change::Updated => write!(f, "updated"),
+            Change::Unchanged => write!(f, "unchanged"),
+        }
+    }
+}
+
+/// Write the setup of `config`'s BIRD_ROOT to a bundle at `path`.
+///
+/// Returns the names of the files bundled.
+pub fn export(config: &Config, path: &Path) -> Result<Vec<String>> {
+    let mut builder = tar::Builder::new(std::fs::File::create(path)?);
+    let mut written = Vec::new();
+    for name in BUNDLE_FILES {
+        let file = config.bird_root.join(name);
+        let contents = if file.exists() {
+            std::fs::read(&file)?
+        } else if *name == CONFIG_FILE {
+            // Defaults that were never saved are still the setup
+            config.to_toml()?.into_bytes()
+        } else {
+            continue;
+        };
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o600);
+        header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, name, contents.as_slice())?;
+        written.push(name.to_string());
+    }
+    builder.into_inner()?;
+    Ok(written)
+}
+
+/// Apply the bundle at `path` to `config`'s BIRD_ROOT, keeping a `.bak`
+/// of each file it replaces. With `dry_run`, only report what would change.
+///
+/// The whole bundle is checked before anything is written: an unexpected
+/// file or one that doesn't parse fails it.
+pub fn apply(config: &Config, path: &Path, dry_run: bool) -> Result<Vec<(String, Change)>> {
+    let mut archive = tar::Archive::new(std::fs::File::open(path)?);
+    let mut files = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        if !BUNDLE_FILES.contains(&name.as_str()) {
+            return Err(Error::Config(format!(
+                "Unexpected file '{}' in setup bundle (expected {})",
+                name,
+                BUNDLE_FILES.join(", ")
+            )));
+        }
+        let mut contents = String::new();
+        entry
+            .read_to_string(&mut contents)
+            .map_err(|e| Error::Config(format!("Failed to read '{}' from setup bundle: {}", name, e)))?;
+        files.push((name, contents));
+    }
+
+    // Check and adapt everything first
+    for (name, contents) in &mut files {
+        if name == CONFIG_FILE {
+            let mut bundled = Config::from_toml(contents, &config.bird_root)?;
+            bundled.client_id = config.client_id.clone();
+            *contents = bundled.to_toml()?;
+        } else {
+            toml::from_str::<toml::Table>(contents)
+                .map_err(|e| Error::Config(format!("Failed to parse '{}' from setup bundle: {}", name, e)))?;
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (name, contents) in files {
+        let target = config.bird_root.join(&name);
+        let change = match std::fs::read_to_string(&target) {
+            Ok(existing) if existing == contents => Change::Unchanged,
+            Ok(_) => Change::Updated,
+            Err(_) => Change::Added,
+        };
+        if !dry_run && change != Change::Unchanged {
+            if change == Change::Updated {
+                std::fs::copy(&target, config.bird_root.join(format!("{}.bak", name)))?;
+            }
+            std::fs::write(&target, contents)?;
+            crate::perms::harden_file(&target);
+        }
+        changes.push((name, change));
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_export_apply_roundtrip() {
+        let from = TempDir::new().unwrap();
+        let mut config = Config::with_root(from.path());
+        config.client_id = "alice@laptop".to_string();
+        config.display.timezone = Some("Europe/Berlin".to_string());
+        config.save().unwrap();
+        std::fs::write(from.path().join("format-hints.toml"), "[[hints]]\npattern = \"make*\"\nformat = \"gcc\"\n").unwrap();
+
+        let bundle = from.path().join("setup.tar");
+        let written = export(&config, &bundle).unwrap();
+        assert_eq!(written, vec!["config.toml", "format-hints.toml"]);
+
+        let to = TempDir::new().unwrap();
+        let mut local = Config::with_root(to.path());
+        local.client_id = "alice@desktop".to_string();
+        local.save().unwrap();
+
+        let dry = apply(&local, &bundle, true).unwrap();
+        assert_eq!(dry, vec![("config.toml".to_string(), Change::Updated), ("format-hints.toml".to_string(), Change::Added)]);
+        assert!(!to.path().join("format-hints.toml").exists());
+
+        apply(&local, &bundle, false).unwrap();
+        let applied = Config::load_from(to.path()).unwrap();
+        assert_eq!(applied.display.timezone.as_deref(), Some("Europe/Berlin"));
+        assert_eq!(applied.client_id, "alice@desktop");
+        assert!(to.path().join("config.toml.bak").exists());
+        assert!(to.path().join("format-hints.toml").exists());
+
+        let again = apply(&applied, &bundle, false).unwrap();
+        assert!(again.iter().all(|(_, change)| *change == Change::Unchanged));
+    }
+
+    #[test]
+    fn test_apply_rejects_unexpected_files() {
+        let tmp = TempDir::new().unwrap();
+        let bundle = tmp.path().join("bad.tar");
+        let mut builder = tar::Builder::new(std::fs::File::create(&bundle).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(1);
+        header.set_cksum();
+        builder.append_data(&mut header, "keys/client.key", &b"x"[..]).unwrap();
+        builder.into_inner().unwrap();
+
+        let config = Config::with_root(tmp.path());
+        assert!(apply(&config, &bundle, false).is_err());
+        assert!(!tmp.path().join("keys").exists());
+    }
+}
@@ -0,0 +1,211 @@
+//! Client identity signing for shared remotes.
+//!
+//! Anyone who can write to a shared remote can insert records claiming any
+//! `source_client`. With `[remotes.signing] sign = true`, each client signs
+//! the invocations it pushes with its own ed25519 key (generated on first
+//! use under `keys/client.key`); with `verify = true`, a pull drops every
+//! invocation that isn't signed by its client's trusted key.
+//!
+//! Trust is pinned on first use, like SSH's `known_hosts`: the first valid
+//! key seen for a client is recorded in `keys/known_clients` (one
+//! `<client_id> <public key>` per line) and any other key for that client
+//! is rejected afterwards. Add lines by hand to pin keys up front; remove a
+//! line to accept a client's new key.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::rand::SystemRandom;
+use ring::signature::{self, Ed25519KeyPair, KeyPair, UnparsedPublicKey};
+
+use crate::{perms, Config, Error, Result};
+
+/// This client's signing key.
+pub struct ClientKey {
+    pair: Ed25519KeyPair,
+}
+
+impl ClientKey {
+    /// Path of the client key file.
+    pub fn path(config: &Config) -> PathBuf {
+        config.keys_dir().join("client.key")
+    }
+
+    /// Load this client's key, generating it on first use.
+    pub fn load_or_create(config: &Config) -> Result<Self> {
+        let path = Self::path(config);
+        let pkcs8 = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let doc = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+                    .map_err(|_| Error::Storage("Failed to generate client key".to_string()))?;
+                let dir = config.keys_dir();
+                fs::create_dir_all(&dir)?;
+                perms::harden_dir(&dir);
+                fs::write(&path, doc.as_ref())?;
+                perms::harden_file(&path);
+                doc.as_ref().to_vec()
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let pair = Ed25519KeyPair::from_pkcs8(&pkcs8)
+            .map_err(|_| Error::Storage(format!("Invalid client key at {}", path.display())))?;
+        Ok(Self { pair })
+    }
+
+    /// The public key, base64-encoded.
+    pub fn public_key(&self) -> String {
+        STANDARD.encode(self.pair.public_key().as_ref())
+    }
+
+    /// Sign a message, returning the base64-encoded signature.
+    pub fn sign(&self, message: &[u8]) -> String {
+        STANDARD.encode(self.pair.sign(message).as_ref())
+    }
+}
+
+/// Check a base64 signature of `message` against a base64 public key.
+pub fn verify(public_key: &str, message: &[u8], signature: &str) -> bool {
+    let (Ok(key), Ok(sig)) = (STANDARD.decode(public_key), STANDARD.decode(signature)) else {
+        return false;
+    };
+    UnparsedPublicKey::new(&signature::ED25519, key)
+        .verify(message, &sig)
+        .is_ok()
+}
+
+/// The bytes signed for an invocation: its identifying columns as stored
+/// on the remote (after sanitization), its outcome and digests of its
+/// outputs and events, each length-prefixed so field boundaries can't be
+/// shifted.
+pub fn record_message(fields: &[Option<&str>]) -> Vec<u8> {
+    let mut message = b"bird-attempt-v2".to_vec();
+    for field in fields {
+        match field {
+            Some(value) => message.extend_from_slice(format!("\n{}:{}", value.len(), value).as_bytes()),
+            None => message.extend_from_slice(b"\n-"),
+        }
+    }
+    message
+}
+
+/// How a client's key compares with the pinned one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyTrust {
+    /// The key pinned for the client.
+    Trusted,
+    /// No key pinned for the client yet.
+    Unknown,
+    /// A different key is pinned for the client.
+    Mismatch,
+}
+
+/// Pinned client keys (`keys/known_clients`).
+#[derive(Debug, Default)]
+pub struct KnownClients {
+    path: PathBuf,
+    keys: BTreeMap<String, String>,
+    changed: bool,
+}
+
+impl KnownClients {
+    /// Path of the known clients file.
+    pub fn path(config: &Config) -> PathBuf {
+        config.keys_dir().join("known_clients")
+    }
+
+    /// Load pinned keys (none if the file doesn't exist yet).
+    pub fn load(config: &Config) -> Result<Self> {
+        let path = Self::path(config);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        // Client IDs may contain spaces; keys never do
+        let keys = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.rsplit_once(char::is_whitespace))
+            .map(|(client, key)| (client.trim().to_string(), key.to_string()))
+            .collect();
+        Ok(Self { path, keys, changed: false })
+    }
+
+    /// Compare `key` with the key pinned for `client_id`.
+    pub fn check(&self, client_id: &str, key: &str) -> KeyTrust {
+        match self.keys.get(client_id) {
+            Some(pinned) if pinned == key => KeyTrust::Trusted,
+            Some(_) => KeyTrust::Mismatch,
+            None => KeyTrust::Unknown,
+        }
+    }
+
+    /// Pin `key` for `client_id` unless a key is already pinned.
+    pub fn pin(&mut self, client_id: &str, key: &str) {
+        if !self.keys.contains_key(client_id) {
+            self.keys.insert(client_id.to_string(), key.to_string());
+            self.changed = true;
+        }
+    }
+
+    /// Write the file if keys were pinned since loading.
+    pub fn save(&mut self) -> Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+            perms::harden_dir(dir);
+        }
+        let content: String = self
+            .keys
+            .iter()
+            .map(|(client, key)| format!("{} {}\n", client, key))
+            .collect();
+        fs::write(&self.path, content)?;
+        perms::harden_file(&self.path);
+        self.changed = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        let key = ClientKey::load_or_create(&config).unwrap();
+        assert_eq!(ClientKey::load_or_create(&config).unwrap().public_key(), key.public_key());
+
+        let message = record_message(&[Some("id"), Some("make test"), None]);
+        let sig = key.sign(&message);
+        assert!(verify(&key.public_key(), &message, &sig));
+        assert!(!verify(&key.public_key(), &record_message(&[Some("id"), Some("make tes"), Some("t")]), &sig));
+        assert!(!verify(&key.public_key(), &message, "not a signature"));
+    }
+
+    #[test]
+    fn test_known_clients_pin_first_key() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+
+        let mut known = KnownClients::load(&config).unwrap();
+        assert_eq!(known.check("alice@laptop", "KEY1"), KeyTrust::Unknown);
+        known.pin("alice@laptop", "KEY1");
+        known.pin("alice@laptop", "KEY2");
+        known.save().unwrap();
+
+        let known = KnownClients::load(&config).unwrap();
+        assert_eq!(known.check("alice@laptop", "KEY1"), KeyTrust::Trusted);
+        assert_eq!(known.check("alice@laptop", "KEY2"), KeyTrust::Mismatch);
+    }
+}
@@ -0,0 +1,293 @@
+//! Spool of saves waiting to be written (`shq save --async`).
+//!
+//! An async save doesn't touch the database: it writes the finished record
+//! as one JSON file under `$BIRD_ROOT/spool` (temp file + rename, so a
+//! flusher never sees half a record) and returns. A background flusher
+//! (`shq spool flush`, see [`Store::flush_spool`]) writes spooled saves to
+//! the store in groups and removes them. Only one flusher runs at a time;
+//! files it can't read are moved to `spool/failed`.
+//!
+//! [`Store::flush_spool`]: crate::Store::flush_spool
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{InvocationRecord, SessionRecord, BIRD_INVOCATION_UUID_VAR};
+#[cfg(feature = "store")]
+use crate::store::InvocationBatch;
+use crate::version::SPOOL_VERSION;
+use crate::{perms, Config, Error, Result};
+
+/// A finished save waiting in the spool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpooledSave {
+    /// Format version (see [`SPOOL_VERSION`]).
+    #[serde(default = "legacy_version")]
+    pub version: u32,
+
+    /// The invocation, as it will be stored (already redacted).
+    pub invocation: InvocationRecord,
+
+    /// Session to register if it isn't already.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionRecord>,
+
+    /// Captured output streams.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<SpooledOutput>,
+
+    /// Extract events once written.
+    #[serde(default)]
+    pub extract: bool,
+
+    /// Run a compaction check for the session once written.
+    #[serde(default)]
+    pub compact: bool,
+}
+
+/// Spool files from before versioning.
+fn legacy_version() -> u32 {
+    1
+}
+
+/// One output stream of a spooled save.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpooledOutput {
+    pub stream: String,
+    /// Content, base64-encoded.
+    pub content: String,
+}
+
+impl SpooledSave {
+    /// A spooled save of `invocation` with no outputs.
+    pub fn new(invocation: InvocationRecord) -> Self {
+        Self {
+            version: SPOOL_VERSION,
+            invocation,
+            session: None,
+            outputs: Vec::new(),
+            extract: false,
+            compact: false,
+        }
+    }
+
+    /// Add an output stream.
+    pub fn with_output(mut self, stream: impl Into<String>, content: &[u8]) -> Self {
+        self.outputs.push(SpooledOutput {
+            stream: stream.into(),
+            content: STANDARD.encode(content),
+        });
+        self
+    }
+
+    /// Add the session record.
+    pub fn with_session(mut self, session: SessionRecord) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// The batch to write for this save.
+    #[cfg(feature = "store")]
+    pub fn to_batch(&self) -> Result<InvocationBatch> {
+        let mut batch = InvocationBatch::new(self.invocation.clone());
+        if let Some(ref session) = self.session {
+            batch = batch.with_session(session.clone());
+        }
+        for output in &self.outputs {
+            let content = STANDARD
+                .decode(&output.content)
+                .map_err(|e| crate::Error::Storage(format!("Invalid spooled output: {}", e)))?;
+            batch = batch.with_output(output.stream.clone(), content);
+        }
+        Ok(batch)
+    }
+}
+
+/// Write a save to the spool. Returns the spool file's path.
+pub fn write(config: &Config, save: &SpooledSave) -> Result<PathBuf> {
+    let dir = config.spool_dir();
+    if !dir.exists() {
+        fs::create_dir_all(&dir)?;
+        perms::harden_dir(&dir);
+    }
+
+    let path = dir.join(format!("{}.json", save.invocation.id));
+    let temp = dir.join(format!(".tmp.{}.json", save.invocation.id));
+    fs::write(&temp, serde_json::to_vec(save)?)?;
+    perms::harden_file(&temp);
+    if let Err(e) = fs::rename(&temp, &path) {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(path)
+}
+
+/// Spooled saves waiting to be written, oldest first.
+///
+/// File names are UUIDv7 invocation IDs, so name order is save order.
+pub fn pending(config: &Config) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(config.spool_dir()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "json")
+                && !path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with('.'))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Read a spooled save. A save spooled by a newer shq is an
+/// [`Error::NewerFormat`], before fields this one doesn't know are lost.
+pub fn read(path: &Path) -> Result<SpooledSave> {
+    #[derive(Deserialize)]
+    struct Header {
+        #[serde(default = "legacy_version")]
+        version: u32,
+    }
+
+    let data = fs::read(path)?;
+    let header: Header = serde_json::from_slice(&data)?;
+    if header.version > SPOOL_VERSION {
+        return Err(Error::NewerFormat {
+            what: format!("Spooled save {}", path.display()),
+            found: header.version,
+            supported: SPOOL_VERSION,
+        });
+    }
+    Ok(serde_json::from_slice(&data)?)
+}
+
+/// Move a spool file that can't be written to `spool/failed`.
+pub fn quarantine(config: &Config, path: &Path) -> Result<()> {
+    let dir = config.spool_dir().join("failed");
+    fs::create_dir_all(&dir)?;
+    if let Some(name) = path.file_name() {
+        fs::rename(path, dir.join(name))?;
+    }
+    Ok(())
+}
+
+/// Number of spool files moved to `spool/failed`.
+pub fn failed_count(config: &Config) -> usize {
+    fs::read_dir(config.spool_dir().join("failed"))
+        .map(|entries| entries.count())
+        .unwrap_or(0)
+}
+
+/// Start `<shq> spool flush` in the background, unless a flusher is
+/// running (it picks up saves spooled before it exits).
+///
+/// Nobody waits on the flusher, so its errors go to the hook error log.
+pub fn start_flusher(config: &Config, shq: &Path) -> Result<()> {
+    use std::process::{Command, Stdio};
+
+    if FlushLock::try_acquire(config)?.is_none() {
+        return Ok(());
+    }
+    let stderr = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(perms::errors_log_path(config))
+        .map(Stdio::from)
+        .unwrap_or_else(|_| Stdio::null());
+    let _ = Command::new(shq)
+        .args(["spool", "flush", "-q"])
+        // The flusher isn't part of the invocation the hook preset an id for
+        .env_remove(BIRD_INVOCATION_UUID_VAR)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(stderr)
+        .spawn();
+    Ok(())
+}
+
+/// Held while a flusher drains the spool; released on drop.
+pub struct FlushLock {
+    _file: fs::File,
+}
+
+impl FlushLock {
+    /// Take the flusher lock, or `None` if another flusher holds it.
+    pub fn try_acquire(config: &Config) -> Result<Option<Self>> {
+        let dir = config.spool_dir();
+        fs::create_dir_all(&dir)?;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(".flush.lock"))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            // SAFETY: flock on a file descriptor we own; released when it's closed
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(Self { _file: file }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_spool_roundtrip() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+
+        let first = InvocationRecord::new("s1", "make test", "/src", 2, "me@host");
+        let second = InvocationRecord::new("s1", "make", "/src", 0, "me@host");
+        write(&config, &SpooledSave::new(second.clone())).unwrap();
+        write(&config, &SpooledSave::new(first.clone()).with_output("stdout", b"FAILED\n")).unwrap();
+
+        let paths = pending(&config).unwrap();
+        assert_eq!(paths.len(), 2);
+        // Oldest (lowest UUIDv7) first
+        let save = read(&paths[0]).unwrap();
+        assert_eq!(save.invocation.id, first.id);
+        #[cfg(feature = "store")]
+        {
+            let batch = save.to_batch().unwrap();
+            assert_eq!(batch.outputs, vec![("stdout".to_string(), b"FAILED\n".to_vec())]);
+        }
+
+        // Spooled by a newer shq: refused rather than read without its new fields
+        let newer = config.spool_dir().join("newer.json");
+        let mut value = serde_json::to_value(SpooledSave::new(second)).unwrap();
+        value["version"] = (SPOOL_VERSION + 1).into();
+        fs::write(&newer, value.to_string()).unwrap();
+        assert!(matches!(read(&newer), Err(Error::NewerFormat { .. })));
+        fs::remove_file(&newer).unwrap();
+
+        quarantine(&config, &paths[1]).unwrap();
+        assert_eq!(pending(&config).unwrap().len(), 1);
+        assert_eq!(failed_count(&config), 1);
+    }
+
+    #[test]
+    fn test_flush_lock_is_exclusive() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+
+        let lock = FlushLock::try_acquire(&config).unwrap();
+        assert!(lock.is_some());
+        assert!(FlushLock::try_acquire(&config).unwrap().is_none());
+        drop(lock);
+        assert!(FlushLock::try_acquire(&config).unwrap().is_some());
+    }
+}
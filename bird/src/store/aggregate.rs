@@ -0,0 +1,500 @@
+//! Aggregate queries - per-group counts over invocations.
+//!
+//! Backs `shq i <query> --by <field>`: the query micro-language selects the
+//! invocations, and a [`GroupField`] decides how they are bucketed. Also
+//! backs `shq i --dedup`, which collapses repeats of the same command.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+
+use super::invocations::{range_limit_offset, read_summaries, where_sql, InvocationSummary};
+use super::{running_stats, Store};
+use crate::query::Query;
+use crate::{Error, Result};
+
+/// Field to group invocations by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupField {
+    /// Executable name (first word of the command).
+    Executable,
+    /// Full command string.
+    Cmd,
+    /// Working directory.
+    Cwd,
+    /// Exit code (`pending` for commands without an outcome).
+    Exit,
+    /// Calendar day (`YYYY-MM-DD`).
+    Day,
+    /// Hour of day (`00`-`23`).
+    Hour,
+    /// Shell session.
+    Session,
+    /// Machine hostname.
+    Host,
+    /// Client ID (`user@hostname`).
+    Client,
+    /// Invocation status (completed, pending, orphaned).
+    Status,
+}
+
+impl GroupField {
+    /// All group fields, in the order they are documented.
+    pub const ALL: [GroupField; 10] = [
+        GroupField::Executable,
+        GroupField::Cmd,
+        GroupField::Cwd,
+        GroupField::Exit,
+        GroupField::Day,
+        GroupField::Hour,
+        GroupField::Session,
+        GroupField::Host,
+        GroupField::Client,
+        GroupField::Status,
+    ];
+
+    /// Canonical name of the field.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupField::Executable => "executable",
+            GroupField::Cmd => "cmd",
+            GroupField::Cwd => "cwd",
+            GroupField::Exit => "exit",
+            GroupField::Day => "day",
+            GroupField::Hour => "hour",
+            GroupField::Session => "session",
+            GroupField::Host => "host",
+            GroupField::Client => "client",
+            GroupField::Status => "status",
+        }
+    }
+
+    /// SQL expression producing the group key (always VARCHAR).
+    fn key_sql(&self) -> &'static str {
+        match self {
+            GroupField::Executable => "COALESCE(executable, '(unknown)')",
+            GroupField::Cmd => "cmd",
+            GroupField::Cwd => "cwd",
+            GroupField::Exit => "COALESCE(exit_code::VARCHAR, 'pending')",
+            GroupField::Day => "strftime(timezone('UTC', timestamp), '%Y-%m-%d')",
+            GroupField::Hour => "strftime(timezone('UTC', timestamp), '%H')",
+            GroupField::Session => "COALESCE(session_id, '(none)')",
+            GroupField::Host => "COALESCE(hostname, '(unknown)')",
+            GroupField::Client => "COALESCE(client_id, '(unknown)')",
+            GroupField::Status => "status",
+        }
+    }
+
+    /// Whether groups have a natural order (time buckets) rather than
+    /// being ranked by count.
+    pub fn is_ordinal(&self) -> bool {
+        matches!(self, GroupField::Day | GroupField::Hour)
+    }
+}
+
+impl fmt::Display for GroupField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for GroupField {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "executable" | "exe" | "bin" => Ok(GroupField::Executable),
+            "cmd" | "command" => Ok(GroupField::Cmd),
+            "cwd" | "dir" => Ok(GroupField::Cwd),
+            "exit" | "exit_code" => Ok(GroupField::Exit),
+            "day" | "date" => Ok(GroupField::Day),
+            "hour" => Ok(GroupField::Hour),
+            "session" | "session_id" => Ok(GroupField::Session),
+            "host" | "hostname" => Ok(GroupField::Host),
+            "client" | "client_id" => Ok(GroupField::Client),
+            "status" => Ok(GroupField::Status),
+            other => Err(Error::Config(format!(
+                "Unknown group field '{}' (expected one of: {})",
+                other,
+                GroupField::ALL
+                    .iter()
+                    .map(|f| f.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        }
+    }
+}
+
+/// One group in an aggregate result.
+#[derive(Debug, Clone)]
+pub struct AggregateRow {
+    /// Group key (rendered as text).
+    pub key: String,
+    /// Number of invocations in the group.
+    pub count: i64,
+    /// Number of invocations with a non-zero exit code.
+    pub failed: i64,
+    /// Total duration in milliseconds (completed invocations only).
+    pub total_duration_ms: i64,
+    /// Average duration in milliseconds, if any invocation has one.
+    pub avg_duration_ms: Option<f64>,
+}
+
+/// SQL expression normalizing a command for deduplication: surrounding
+/// whitespace trimmed, runs of whitespace collapsed to one space.
+const NORMALIZED_CMD_SQL: &str = r"trim(regexp_replace(cmd, '\s+', ' ', 'g'))";
+
+/// One distinct command in a deduplicated history.
+#[derive(Debug, Clone)]
+pub struct DedupedCommand {
+    /// The command, normalized.
+    pub cmd: String,
+    /// Number of times it was run.
+    pub count: i64,
+    /// Runs that exited 0.
+    pub succeeded: i64,
+    /// Runs with an exit code (pending ones have none yet).
+    pub completed: i64,
+    /// Timestamp of the first run.
+    pub first_run: String,
+    /// Timestamp of the latest run.
+    pub last_run: String,
+    /// ID of the latest run.
+    pub last_id: String,
+}
+
+impl DedupedCommand {
+    /// Fraction of completed runs that succeeded, if any completed.
+    pub fn success_ratio(&self) -> Option<f64> {
+        (self.completed > 0).then(|| self.succeeded as f64 / self.completed as f64)
+    }
+}
+
+/// Invocation counts for one calendar day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DailyCount {
+    /// The day.
+    pub date: NaiveDate,
+    /// Number of invocations that day.
+    pub count: i64,
+    /// Number of invocations with a non-zero exit code.
+    pub failed: i64,
+}
+
+impl Store {
+    /// Count invocations matching `query`, grouped by `group_field`.
+    ///
+    /// Filters from the query apply as usual. Without a range selector every
+    /// matching invocation is counted; with one (e.g. `~100:`) only that slice
+    /// of the most recent invocations is aggregated.
+    ///
+    /// Groups are ordered by count (descending), except time buckets
+    /// (`day`, `hour`) which are returned in chronological order.
+    pub fn aggregate(&self, query: &Query, group_field: GroupField) -> Result<Vec<AggregateRow>> {
+        let conn = self.connection()?;
+
+        let where_sql = where_sql(query);
+        let slice_sql = if query.range.is_some() {
+            let (limit, offset) = range_limit_offset(query, 0);
+            format!("LIMIT {} OFFSET {}", limit, offset)
+        } else {
+            String::new()
+        };
+
+        let order_sql = if group_field.is_ordinal() {
+            "key"
+        } else {
+            "count DESC, key"
+        };
+
+        let sql = format!(
+            r#"
+            SELECT
+                {key} AS key,
+                COUNT(*) AS count,
+                COUNT(*) FILTER (WHERE exit_code IS NOT NULL AND exit_code <> 0) AS failed,
+                COALESCE(SUM(duration_ms), 0)::BIGINT AS total_duration_ms,
+                AVG(duration_ms)::DOUBLE AS avg_duration_ms
+            FROM (
+                SELECT * FROM invocations
+                {where_sql}
+                ORDER BY timestamp DESC
+                {slice_sql}
+            )
+            GROUP BY key
+            ORDER BY {order_sql}
+            "#,
+            key = group_field.key_sql(),
+        );
+
+        let mut stmt = match conn.prepare(&sql) {
+            Ok(stmt) => stmt,
+            Err(e) => {
+                if e.to_string().contains("No files found") {
+                    return Ok(Vec::new());
+                }
+                return Err(e.into());
+            }
+        };
+
+        let rows = stmt.query_map([], |row| {
+            Ok(AggregateRow {
+                key: row.get(0)?,
+                count: row.get(1)?,
+                failed: row.get(2)?,
+                total_duration_ms: row.get(3)?,
+                avg_duration_ms: row.get(4)?,
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+
+    /// Invocations matching `query` with identical commands collapsed into
+    /// one row, most recently run first.
+    ///
+    /// Commands are compared after normalizing whitespace. The range
+    /// selector counts distinct commands, so `~20:` is the 20 most recently
+    /// run ones; without a range every distinct command is returned.
+    pub fn deduped_history(&self, query: &Query) -> Result<Vec<DedupedCommand>> {
+        let conn = self.connection()?;
+
+        let where_sql = where_sql(query);
+        let slice_sql = if query.range.is_some() {
+            let (limit, offset) = range_limit_offset(query, 0);
+            format!("LIMIT {} OFFSET {}", limit, offset)
+        } else {
+            String::new()
+        };
+
+        let sql = format!(
+            r#"
+            SELECT
+                {normalized} AS normalized,
+                COUNT(*) AS count,
+                COUNT(*) FILTER (WHERE exit_code = 0) AS succeeded,
+                COUNT(exit_code) AS completed,
+                timezone('UTC', min(timestamp))::VARCHAR AS first_run,
+                timezone('UTC', max(timestamp))::VARCHAR AS last_run,
+                arg_max(id, timestamp)::VARCHAR AS last_id
+            FROM invocations
+            {where_sql}
+            GROUP BY normalized
+            ORDER BY max(timestamp) DESC, normalized
+            {slice_sql}
+            "#,
+            normalized = NORMALIZED_CMD_SQL,
+        );
+
+        let mut stmt = match conn.prepare(&sql) {
+            Ok(stmt) => stmt,
+            Err(e) if e.to_string().contains("No files found") => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let rows = stmt.query_map([], |row| {
+            Ok(DedupedCommand {
+                cmd: row.get(0)?,
+                count: row.get(1)?,
+                succeeded: row.get(2)?,
+                completed: row.get(3)?,
+                first_run: row.get(4)?,
+                last_run: row.get(5)?,
+                last_id: row.get(6)?,
+            })
+        })?;
+        Ok(rows.collect::<std::result::Result<_, _>>()?)
+    }
+
+    /// Count invocations per day between `start` and `end` (inclusive).
+    ///
+    /// Only days with activity are returned, in chronological order. Read
+    /// from the running stats, so the cost doesn't grow with history.
+    pub fn daily_counts(&self, start: NaiveDate, end: NaiveDate) -> Result<Vec<DailyCount>> {
+        let conn = self.connection()?;
+        Ok(running_stats::daily_counts(&conn, start, end)?
+            .into_iter()
+            .map(|(date, count, failed)| DailyCount { date, count, failed })
+            .collect())
+    }
+
+    /// Invocations between `start` and `end` (inclusive), most recent first,
+    /// at most `limit` of them.
+    ///
+    /// Filters on the `date` partition column so parquet stores only scan
+    /// the partitions in range.
+    pub fn invocations_between(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+        limit: usize,
+    ) -> Result<Vec<InvocationSummary>> {
+        let conn = self.connection()?;
+        let sql = format!(
+            r#"
+            SELECT id::VARCHAR, cmd, exit_code, timezone('UTC', timestamp)::VARCHAR, duration_ms, cwd
+            FROM invocations
+            WHERE date >= '{}' AND date <= '{}'
+            ORDER BY timestamp DESC
+            LIMIT {}
+            "#,
+            start, end, limit
+        );
+        read_summaries(&conn, &sql)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::query::parse_query;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    fn setup_store() -> (TempDir, Store) {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+        (tmp, store)
+    }
+
+    fn write(store: &Store, cmd: &str, exit_code: i32, duration_ms: i64) {
+        let record = InvocationRecord::new("test-session", cmd, "/home/user", exit_code, "test@client")
+            .with_duration(duration_ms);
+        store.write_invocation(&record).unwrap();
+    }
+
+    #[test]
+    fn test_group_field_parse() {
+        assert_eq!("executable".parse::<GroupField>().unwrap(), GroupField::Executable);
+        assert_eq!("exe".parse::<GroupField>().unwrap(), GroupField::Executable);
+        assert_eq!("DAY".parse::<GroupField>().unwrap(), GroupField::Day);
+        assert_eq!("date".parse::<GroupField>().unwrap(), GroupField::Day);
+        assert!("bogus".parse::<GroupField>().is_err());
+    }
+
+    #[test]
+    fn test_aggregate_by_executable() {
+        let (_tmp, store) = setup_store();
+        write(&store, "make test", 0, 100);
+        write(&store, "make build", 2, 300);
+        write(&store, "cargo test", 0, 50);
+
+        let rows = store
+            .aggregate(&parse_query(""), GroupField::Executable)
+            .unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].key, "make");
+        assert_eq!(rows[0].count, 2);
+        assert_eq!(rows[0].failed, 1);
+        assert_eq!(rows[0].total_duration_ms, 400);
+        assert_eq!(rows[1].key, "cargo");
+        assert_eq!(rows[1].count, 1);
+    }
+
+    #[test]
+    fn test_aggregate_respects_filters() {
+        let (_tmp, store) = setup_store();
+        write(&store, "make test", 0, 100);
+        write(&store, "make build", 2, 300);
+        write(&store, "cargo test", 1, 50);
+
+        let rows = store
+            .aggregate(&parse_query("%failed"), GroupField::Executable)
+            .unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|r| r.count == 1 && r.failed == 1));
+    }
+
+    #[test]
+    fn test_aggregate_by_day() {
+        let (_tmp, store) = setup_store();
+        write(&store, "ls", 0, 1);
+        write(&store, "pwd", 0, 1);
+
+        let rows = store.aggregate(&parse_query(""), GroupField::Day).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].count, 2);
+        assert_eq!(rows[0].key, chrono::Utc::now().format("%Y-%m-%d").to_string());
+    }
+
+    #[test]
+    fn test_daily_counts() {
+        let (_tmp, store) = setup_store();
+        write(&store, "ls", 0, 1);
+        write(&store, "false", 1, 1);
+
+        let today = chrono::Utc::now().date_naive();
+        let days = store
+            .daily_counts(today - chrono::TimeDelta::days(7), today)
+            .unwrap();
+
+        assert_eq!(days, vec![DailyCount { date: today, count: 2, failed: 1 }]);
+
+        // Out-of-range window sees nothing
+        let days = store
+            .daily_counts(today - chrono::TimeDelta::days(30), today - chrono::TimeDelta::days(10))
+            .unwrap();
+        assert!(days.is_empty());
+    }
+
+    #[test]
+    fn test_invocations_between() {
+        let (_tmp, store) = setup_store();
+        write(&store, "ls", 0, 1);
+        write(&store, "make", 2, 900);
+        write(&store, "pwd", 0, 1);
+
+        let today = chrono::Utc::now().date_naive();
+        let rows = store.invocations_between(today, today, 2).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].cmd, "pwd");
+
+        let rows = store
+            .invocations_between(today - chrono::TimeDelta::days(30), today - chrono::TimeDelta::days(1), 10)
+            .unwrap();
+        assert!(rows.is_empty());
+    }
+
+    #[test]
+    fn test_deduped_history() {
+        let (_tmp, store) = setup_store();
+        write(&store, "make  test", 2, 100);
+        write(&store, "ls", 0, 1);
+        write(&store, " make test", 0, 100);
+        write(&store, "make test", 0, 100);
+
+        let rows = store.deduped_history(&parse_query("")).unwrap();
+        let summary: Vec<_> = rows.iter().map(|r| (r.cmd.as_str(), r.count, r.succeeded)).collect();
+        assert_eq!(summary, [("make test", 3, 2), ("ls", 1, 1)]);
+        assert!((rows[0].success_ratio().unwrap() - 2.0 / 3.0).abs() < 1e-9);
+        assert!(rows[0].first_run < rows[0].last_run);
+
+        // The range counts distinct commands
+        let rows = store.deduped_history(&parse_query("~1:")).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].cmd, "make test");
+        let rows = store.deduped_history(&parse_query("%/ls/")).unwrap();
+        assert_eq!(rows.len(), 1);
+    }
+
+    #[test]
+    fn test_aggregate_empty() {
+        let (_tmp, store) = setup_store();
+
+        let rows = store.aggregate(&parse_query(""), GroupField::Exit).unwrap();
+        assert!(rows.is_empty());
+    }
+}
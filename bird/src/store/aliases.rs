@@ -0,0 +1,200 @@
+//! Stored aliases - resolve `shq o brave-otter` to exactly one invocation.
+//!
+//! `main.aliases` maps each alias to its invocation. An attempt is given
+//! its alias as it's written: the word pair from [`invocation_alias`], or
+//! that pair with a numeric suffix (`brave-otter-2`) if another invocation
+//! already holds it. Invocations that arrived some other way (pulls, stores
+//! from before aliases were stored) are given theirs, oldest first, the
+//! first time an alias misses. Unlike the short-id lookup the table is
+//! never rebuilt, so an alias keeps naming the invocation it was shown for.
+
+use duckdb::{params, Connection};
+use uuid::Uuid;
+
+use super::Store;
+use crate::alias::{invocation_alias, is_alias};
+use crate::Result;
+
+/// Table backing alias lookup (in the main DuckDB database, in both storage
+/// modes).
+pub(crate) const ALIASES_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS main.aliases (
+    alias VARCHAR PRIMARY KEY,
+    id    UUID NOT NULL
+);
+CREATE INDEX IF NOT EXISTS aliases_id ON main.aliases (id);
+"#;
+
+impl Store {
+    /// Resolve an alias (see [`crate::alias`]) to the full id of the
+    /// invocation it names.
+    ///
+    /// Returns `None` if `alias` isn't an alias or names no invocation.
+    pub fn find_by_alias(&self, alias: &str) -> Result<Option<String>> {
+        if !is_alias(alias) {
+            return Ok(None);
+        }
+        let alias = alias.trim().to_ascii_lowercase();
+        let conn = self.connection()?;
+        if let Some(id) = lookup(&conn, &alias)? {
+            return Ok(Some(id));
+        }
+        // It may belong to an invocation that hasn't been given one yet
+        if backfill(&conn)? == 0 {
+            return Ok(None);
+        }
+        lookup(&conn, &alias)
+    }
+
+    /// The alias of invocation `id`, giving it one if it has none yet.
+    ///
+    /// Also reserves an alias for an id that hasn't been written, which the
+    /// shell hook uses to show a command's alias before saving it.
+    pub fn alias_of(&self, id: &Uuid) -> Result<String> {
+        let conn = self.connection()?;
+        assign(&conn, id)
+    }
+}
+
+/// Give invocation `id` an alias unless it already has one. Returns its
+/// alias.
+pub(crate) fn assign(conn: &Connection, id: &Uuid) -> Result<String> {
+    let id_text = id.to_string();
+    if let Some(alias) = alias_for(conn, &id_text)? {
+        return Ok(alias);
+    }
+
+    let base = invocation_alias(id);
+    let mut n = 1;
+    loop {
+        let alias = if n == 1 { base.clone() } else { format!("{}-{}", base, n) };
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO main.aliases VALUES (?, ?::UUID)",
+            params![alias, id_text],
+        )?;
+        if inserted == 1 {
+            return Ok(alias);
+        }
+        n += 1;
+    }
+}
+
+/// The stored alias of `id`, if it has one.
+fn alias_for(conn: &Connection, id: &str) -> Result<Option<String>> {
+    match conn.query_row(
+        "SELECT alias FROM main.aliases WHERE id = ?::UUID ORDER BY alias LIMIT 1",
+        params![id],
+        |row| row.get(0),
+    ) {
+        Ok(alias) => Ok(Some(alias)),
+        Err(duckdb::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The invocation `alias` names, by exact match.
+fn lookup(conn: &Connection, alias: &str) -> Result<Option<String>> {
+    match conn.query_row("SELECT id::VARCHAR FROM main.aliases WHERE alias = ?", params![alias], |row| row.get(0)) {
+        Ok(id) => Ok(Some(id)),
+        Err(duckdb::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Give every attempt without an alias one, oldest first. Returns how many
+/// were given one.
+fn backfill(conn: &Connection) -> Result<usize> {
+    let sql = r#"
+        SELECT DISTINCT a.id::VARCHAR
+        FROM main.attempts a
+        WHERE NOT EXISTS (SELECT 1 FROM main.aliases x WHERE x.id = a.id)
+        ORDER BY 1
+    "#;
+    let mut stmt = match conn.prepare(sql) {
+        Ok(stmt) => stmt,
+        Err(e) if e.to_string().contains("No files found") => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let ids: Vec<String> = stmt.query_map([], |row| row.get(0))?.collect::<std::result::Result<_, _>>()?;
+
+    for id in &ids {
+        if let Ok(id) = Uuid::parse_str(id) {
+            assign(conn, &id)?;
+        }
+    }
+    Ok(ids.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    fn connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(ALIASES_DDL).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_colliding_aliases_get_a_suffix() {
+        let conn = connection();
+        let a = Uuid::parse_str("0190a1b2-0000-7000-8000-00003f2a9c1e").unwrap();
+        let b = Uuid::parse_str("0190a1b2-0000-7000-8000-00117a2a9c1e").unwrap();
+        let c = Uuid::parse_str("0190a1b2-0000-7000-8000-00227a2a9c1e").unwrap();
+        let base = invocation_alias(&a);
+        assert_eq!(invocation_alias(&b), base);
+
+        assert_eq!(assign(&conn, &a).unwrap(), base);
+        assert_eq!(assign(&conn, &b).unwrap(), format!("{}-2", base));
+        assert_eq!(assign(&conn, &c).unwrap(), format!("{}-3", base));
+        // Assigning again keeps the alias
+        assert_eq!(assign(&conn, &b).unwrap(), format!("{}-2", base));
+
+        assert_eq!(lookup(&conn, &base).unwrap(), Some(a.to_string()));
+        assert_eq!(lookup(&conn, &format!("{}-2", base)).unwrap(), Some(b.to_string()));
+        assert_eq!(lookup(&conn, &format!("{}-4", base)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_find_by_alias_resolves_exactly() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        let mut record = InvocationRecord::new("s1", "make", "/p", 0, "test@client");
+        record.id = Uuid::parse_str("0190a1b2-0000-7000-8000-00003f2a9c1e").unwrap();
+        store.write_invocation(&record).unwrap();
+        let mut twin = InvocationRecord::new("s1", "ls", "/p", 0, "test@client");
+        twin.id = Uuid::parse_str("0190a1b2-0000-7000-8000-00117a2a9c1e").unwrap();
+        store.write_invocation(&twin).unwrap();
+
+        let alias = invocation_alias(&record.id);
+        assert_eq!(store.alias_of(&record.id).unwrap(), alias);
+        assert_eq!(store.alias_of(&twin.id).unwrap(), format!("{}-2", alias));
+        assert_eq!(store.find_by_alias(&alias.to_uppercase()).unwrap(), Some(record.id.to_string()));
+        assert_eq!(store.find_by_alias(&format!("{}-2", alias)).unwrap(), Some(twin.id.to_string()));
+        assert_eq!(store.find_by_alias(&format!("{}-3", alias)).unwrap(), None);
+        assert_eq!(store.find_by_alias("make-test").unwrap(), None);
+    }
+
+    #[test]
+    fn test_find_by_alias_backfills_unaliased_attempts() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        let record = InvocationRecord::new("s1", "make", "/p", 0, "test@client");
+        store.write_invocation(&record).unwrap();
+        // As if written before aliases were stored
+        store.connection().unwrap().execute("DELETE FROM main.aliases", []).unwrap();
+
+        let alias = invocation_alias(&record.id);
+        assert_eq!(store.find_by_alias(&alias).unwrap(), Some(record.id.to_string()));
+    }
+}
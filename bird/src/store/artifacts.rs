@@ -0,0 +1,218 @@
+//! Files a command produced, attached to its invocation.
+//!
+//! `shq run --attach PATH` and the `[artifacts] capture` globs snapshot files
+//! after the command exits. Each is stored as an output on the stream
+//! `artifact:<path>`, so artifacts share blob storage, dedup, sync and fsck
+//! with command output; plain output queries leave them out.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use super::{OutputInfo, Store, StoredOutput};
+use crate::format_hints::pattern_matches;
+use crate::Result;
+
+/// Stream name prefix of attached artifacts.
+pub const ARTIFACT_STREAM_PREFIX: &str = "artifact:";
+
+/// Whether an output stream holds an attached artifact.
+pub fn is_artifact_stream(stream: &str) -> bool {
+    stream.starts_with(ARTIFACT_STREAM_PREFIX)
+}
+
+/// An artifact attached to an invocation.
+#[derive(Debug)]
+pub struct Artifact {
+    /// Path as attached: relative to the command's cwd when under it.
+    pub path: String,
+    pub output: OutputInfo,
+}
+
+impl Artifact {
+    /// Where to put the artifact under an extraction directory: its path
+    /// with root, `.` and `..` components dropped.
+    pub fn relative_path(&self) -> PathBuf {
+        Path::new(&self.path)
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(part) => Some(part),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Name an artifact by its path relative to `cwd`, or its full path if
+/// outside it.
+pub fn artifact_name(cwd: &Path, path: &Path) -> String {
+    let path = if path.is_absolute() { path.to_path_buf() } else { cwd.join(path) };
+    match path.strip_prefix(cwd) {
+        Ok(rel) => rel.display().to_string(),
+        Err(_) => path.display().to_string(),
+    }
+}
+
+/// Files matching `patterns` (relative to `cwd`, `*` wildcards within a
+/// path component, `**` for any number of directories), optionally only
+/// those modified since `modified_since`. Sorted, without duplicates.
+pub fn find_artifacts(cwd: &Path, patterns: &[String], modified_since: Option<SystemTime>) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for pattern in patterns {
+        let base = if Path::new(pattern).is_absolute() { Path::new("/") } else { cwd };
+        let parts: Vec<&str> = pattern.split('/').filter(|p| !p.is_empty() && *p != ".").collect();
+        walk(base.to_path_buf(), &parts, &mut found);
+    }
+    if let Some(since) = modified_since {
+        found.retain(|path| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .map(|modified| modified >= since)
+                .unwrap_or(false)
+        });
+    }
+    found.sort();
+    found.dedup();
+    found
+}
+
+fn walk(dir: PathBuf, parts: &[&str], found: &mut Vec<PathBuf>) {
+    let Some((first, rest)) = parts.split_first() else {
+        if dir.is_file() {
+            found.push(dir);
+        }
+        return;
+    };
+
+    if *first == "**" {
+        walk(dir.clone(), rest, found);
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            // Don't follow directory symlinks, which could loop
+            if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                walk(entry.path(), parts, found);
+            }
+        }
+    } else if !first.contains('*') {
+        walk(dir.join(first), rest, found);
+    } else {
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            if pattern_matches(first, &entry.file_name().to_string_lossy()) {
+                walk(entry.path(), rest, found);
+            }
+        }
+    }
+}
+
+impl Store {
+    /// Snapshot a file as an artifact of an invocation. The result goes
+    /// into its batch with `with_stored_output`.
+    pub fn attach_artifact(
+        &self,
+        invocation_id: uuid::Uuid,
+        name: &str,
+        path: &Path,
+        cmd_hint: Option<&str>,
+    ) -> Result<StoredOutput> {
+        let mut file = File::open(path)?;
+        let stream = format!("{}{}", ARTIFACT_STREAM_PREFIX, name);
+        let mut writer = self.begin_output(invocation_id, &stream)?;
+        if let Err(e) = io::copy(&mut file, &mut writer) {
+            writer.discard();
+            return Err(e.into());
+        }
+        self.finish_output(writer, cmd_hint)
+    }
+
+    /// Artifacts attached to an invocation, by path.
+    pub fn get_artifacts(&self, invocation_id: &str) -> Result<Vec<Artifact>> {
+        let clause = format!("AND starts_with(stream, '{}')", ARTIFACT_STREAM_PREFIX);
+        let outputs = self.query_outputs(invocation_id, &clause, "stream, sequence, id")?;
+        Ok(outputs
+            .into_iter()
+            .map(|output| Artifact {
+                path: output.stream[ARTIFACT_STREAM_PREFIX.len()..].to_string(),
+                output,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::store::InvocationBatch;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_artifacts_matches_globs() {
+        let tmp = TempDir::new().unwrap();
+        let cwd = tmp.path();
+        fs::create_dir_all(cwd.join("target/doc/api")).unwrap();
+        fs::write(cwd.join("target/report.html"), "<html/>").unwrap();
+        fs::write(cwd.join("target/report.txt"), "text").unwrap();
+        fs::write(cwd.join("target/doc/api/index.html"), "<html/>").unwrap();
+
+        let patterns = |p: &[&str]| p.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let rel = |paths: Vec<PathBuf>| {
+            paths.iter().map(|p| artifact_name(cwd, p)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(rel(find_artifacts(cwd, &patterns(&["target/*.html"]), None)), vec!["target/report.html"]);
+        assert_eq!(
+            rel(find_artifacts(cwd, &patterns(&["target/**/*.html", "target/report.html"]), None)),
+            vec!["target/doc/api/index.html", "target/report.html"]
+        );
+        assert!(find_artifacts(cwd, &patterns(&["missing/*"]), None).is_empty());
+
+        let later = SystemTime::now() + std::time::Duration::from_secs(60);
+        assert!(find_artifacts(cwd, &patterns(&["target/*"]), Some(later)).is_empty());
+    }
+
+    #[test]
+    fn test_relative_path_stays_inside() {
+        let artifact = |path: &str| Artifact {
+            path: path.to_string(),
+            output: OutputInfo {
+                storage_type: "inline".to_string(),
+                storage_ref: String::new(),
+                stream: format!("{}{}", ARTIFACT_STREAM_PREFIX, path),
+                byte_length: 0,
+                content_hash: String::new(),
+                sequence: 0,
+            },
+        };
+        assert_eq!(artifact("target/report.html").relative_path(), PathBuf::from("target/report.html"));
+        assert_eq!(artifact("/tmp/../etc/passwd").relative_path(), PathBuf::from("tmp/etc/passwd"));
+    }
+
+    #[test]
+    fn test_attached_artifacts_kept_apart_from_output() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path().join("bird"));
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        let report = tmp.path().join("report.html");
+        fs::write(&report, "<html>ok</html>").unwrap();
+
+        let inv = InvocationRecord::new("test-session", "make report", "/home/user", 0, "user@laptop");
+        let id = inv.id.to_string();
+        let artifact = store.attach_artifact(inv.id, "report.html", &report, Some("make")).unwrap();
+        store
+            .write_batch(&InvocationBatch::new(inv).with_output("stdout", b"done\n".to_vec()).with_stored_output(artifact))
+            .unwrap();
+
+        let outputs = store.get_outputs(&id, None).unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].stream, "stdout");
+
+        let artifacts = store.get_artifacts(&id).unwrap();
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].path, "report.html");
+        assert_eq!(store.read_output_content(&artifacts[0].output).unwrap(), b"<html>ok</html>");
+    }
+}
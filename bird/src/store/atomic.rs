@@ -2,6 +2,13 @@
 //!
 //! Provides crash-safe and race-safe file writing by using
 //! temp files and atomic renames.
+//!
+//! Data files (parquet, blobs) are immutable once published: they are
+//! written under a `.tmp.*` name and appear at their final name complete,
+//! and a published name is never written again. Changing stored rows means
+//! writing a new file and removing the old one. A plain `rsync` of the data
+//! directory therefore never copies a half-written or rewritten file (see
+//! `mirror`).
 
 use std::fs;
 use std::io;
@@ -19,31 +26,46 @@ pub fn temp_path(final_path: &Path) -> std::path::PathBuf {
     final_path.with_file_name(temp_name)
 }
 
-/// Atomically rename temp file to final path.
-/// Returns Ok(true) if renamed, Ok(false) if file already existed (dedup hit).
+/// Atomically move temp file to final path, unless a file is already there.
+/// Returns Ok(true) if moved, Ok(false) if file already existed (dedup hit);
+/// either way the temp file is gone.
 ///
 /// All persisted data files (parquet, blobs) funnel through here, so this is
 /// also where storage permissions are hardened to owner-only (0600) before
 /// the file becomes visible at its final path.
 pub fn rename_into_place(temp_path: &Path, final_path: &Path) -> io::Result<bool> {
     crate::perms::set_mode(temp_path, crate::perms::FILE_MODE)?;
-    match fs::rename(temp_path, final_path) {
-        Ok(()) => Ok(true),
-        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+    // A rename would replace an existing file; a link fails instead
+    let result = match fs::hard_link(temp_path, final_path) {
+        Ok(()) => fs::remove_file(temp_path).map(|()| true),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists || final_path.exists() => {
             // Another process wrote the same file - that's fine for content-addressed storage
-            fs::remove_file(temp_path)?;
-            Ok(false)
-        }
-        Err(e) => {
-            // Clean up temp file on other errors
-            let _ = fs::remove_file(temp_path);
-            Err(e)
+            fs::remove_file(temp_path).map(|()| false)
         }
+        // Filesystems without hard links
+        Err(_) => fs::rename(temp_path, final_path).map(|()| true),
+    };
+    if result.is_err() {
+        // Clean up temp file on errors
+        let _ = fs::remove_file(temp_path);
+    }
+    result
+}
+
+/// Atomically rename temp file over final path, replacing what's there.
+/// Only for files outside the data directory (e.g. bundles).
+pub fn replace_file(temp_path: &Path, final_path: &Path) -> io::Result<()> {
+    crate::perms::set_mode(temp_path, crate::perms::FILE_MODE)?;
+    let result = fs::rename(temp_path, final_path);
+    if result.is_err() {
+        let _ = fs::remove_file(temp_path);
     }
+    result
 }
 
 /// Write content to file atomically.
-/// Returns Ok(true) if written, Ok(false) if file already existed.
+/// Returns Ok(true) if written, Ok(false) if file already existed (and was
+/// left alone).
 pub fn write_file(final_path: &Path, content: &[u8]) -> io::Result<bool> {
     let temp = temp_path(final_path);
     fs::write(&temp, content)?;
@@ -100,11 +122,18 @@ mod tests {
         // Write initial file
         fs::write(&final_path, b"original").unwrap();
 
-        // Atomic write to existing path should still succeed (overwrites)
+        // Published files are immutable: the write succeeds but leaves it alone
         let result = write_file(&final_path, b"new content");
-        assert!(result.is_ok());
+        assert!(!result.unwrap());
+        assert_eq!(fs::read(&final_path).unwrap(), b"original");
+
+        let temps = fs::read_dir(tmp.path()).unwrap().count();
+        assert_eq!(temps, 1, "No temp files should remain");
 
-        // Content should be updated
+        // Files outside the data directory can be replaced
+        let temp = temp_path(&final_path);
+        fs::write(&temp, b"new content").unwrap();
+        replace_file(&temp, &final_path).unwrap();
         assert_eq!(fs::read(&final_path).unwrap(), b"new content");
     }
 }
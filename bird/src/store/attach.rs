@@ -0,0 +1,101 @@
+//! SQL for reading a store from your own DuckDB session (`shq attach-snippet`).
+//!
+//! The database file alone isn't enough: parquet-mode views read files
+//! relative to the data directory, and output refs resolve against the
+//! blob roots. The snippet attaches the file read-only and sets up both, so
+//! a DuckDB CLI or Python session sees the same views shq does.
+
+use super::{blob_roots_sql, Store, BLOB_RESOLUTION_MACROS};
+use crate::{Config, Error, Result};
+
+impl Store {
+    /// SQL that attaches this store read-only as `alias` (see [`attach_sql`]).
+    pub fn attach_snippet(&self, alias: &str) -> Result<String> {
+        attach_sql(&self.config, alias)
+    }
+}
+
+/// SQL that attaches the store in `config` read-only as `alias`, makes it
+/// the default database and sets up blob resolution.
+pub fn attach_sql(config: &Config, alias: &str) -> Result<String> {
+    let valid = alias.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && alias.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(Error::Config(format!(
+            "Invalid database alias '{}': use letters, digits and underscores",
+            alias
+        )));
+    }
+
+    let quote = |s: String| s.replace('\'', "''");
+    let macros = BLOB_RESOLUTION_MACROS.replace("CREATE OR REPLACE MACRO", "CREATE OR REPLACE TEMPORARY MACRO");
+
+    Ok(format!(
+        r#"-- Attach the BIRD store at {root} read-only.
+-- Generated by `shq attach-snippet`. Use with `duckdb -init <file>`, `.read <file>`
+-- in the DuckDB CLI, or `con.execute(open(<file>).read())` from Python.
+-- shq can't write to the store while another process has it attached:
+-- run `DETACH {alias};` (or close the session) when you're done.
+
+-- Needed to read outputs stored inline (data: refs)
+INSTALL scalarfs FROM community;
+LOAD scalarfs;
+
+ATTACH '{db}' AS {alias} (READ_ONLY);
+USE {alias};
+
+-- Parquet-mode views read files relative to the data directory
+SET file_search_path = '{data_dir}';
+
+-- Blob resolution: where resolve_storage_ref() looks for file: refs
+{roots};
+{macros}
+-- output_text(id) is registered by shq itself; use `shq sql` to read output content.
+-- Stable views to start from (see `shq schema`):
+--   SELECT cmd, exit_code, duration_ms FROM v1.invocations ORDER BY timestamp DESC LIMIT 10;
+"#,
+        root = config.bird_root.display(),
+        alias = alias,
+        db = quote(config.db_path().display().to_string()),
+        data_dir = quote(config.data_dir().display().to_string()),
+        roots = blob_roots_sql(&config.blob_roots()),
+        macros = macros,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use duckdb::Connection;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_attach_sql_attaches_read_only() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_duckdb_mode(tmp.path());
+        std::fs::create_dir_all(config.db_path().parent().unwrap()).unwrap();
+        {
+            let conn = Connection::open(config.db_path()).unwrap();
+            conn.execute_batch("CREATE SCHEMA v1; CREATE TABLE v1.invocations AS SELECT 'make' AS cmd;")
+                .unwrap();
+        }
+
+        let sql = attach_sql(&config, "history").unwrap();
+        // Skip the extension install: no network in tests
+        let sql = sql.replace("INSTALL scalarfs FROM community;\nLOAD scalarfs;\n", "");
+
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&sql).unwrap();
+        let cmd: String = conn.query_row("SELECT cmd FROM v1.invocations", [], |row| row.get(0)).unwrap();
+        assert_eq!(cmd, "make");
+
+        let paths: String = conn
+            .query_row("SELECT resolve_storage_ref('file:ab/abc.bin')::VARCHAR", [], |row| row.get(0))
+            .unwrap();
+        assert!(paths.contains("abc.bin*"), "{}", paths);
+        assert!(conn.execute("CREATE TABLE history.main.scratch (x INTEGER)", []).is_err());
+
+        assert!(attach_sql(&config, "bird; DROP").is_err());
+        assert!(attach_sql(&config, "").is_err());
+    }
+}
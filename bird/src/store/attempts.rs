@@ -8,7 +8,7 @@ use std::fs;
 use duckdb::params;
 
 use super::atomic;
-use super::{sanitize_filename, Store};
+use super::{aliases, recent_index, running_stats, sanitize_filename, short_ids, Store};
 use crate::config::StorageMode;
 use crate::schema::AttemptRecord;
 use crate::Result;
@@ -31,38 +31,39 @@ impl Store {
 
     /// Write attempt to a Parquet file (multi-writer safe).
     fn write_attempt_parquet(&self, record: &AttemptRecord) -> Result<()> {
+        self.write_attempts_parquet(std::slice::from_ref(record))
+    }
+
+    /// Write attempts from one date partition to a single Parquet file
+    /// (multi-writer safe).
+    ///
+    /// A lone attempt's file is `{session}--{executable}--{id}.parquet`; a
+    /// group's is `{session}--batch--{id}.parquet`, after its first attempt,
+    /// so compaction still finds it under that session.
+    pub(super) fn write_attempts_parquet(&self, records: &[AttemptRecord]) -> Result<()> {
+        let Some(first) = records.first() else {
+            return Ok(());
+        };
         let conn = self.connection_with_options(false)?;
-        let date = record.date();
+        let date = first.date();
 
         // Ensure the partition directory exists
         let partition_dir = self.config.attempts_dir(&date);
         fs::create_dir_all(&partition_dir)?;
 
         // Generate filename: {session}--{executable}--{id}.parquet
-        let executable = record.executable.as_deref().unwrap_or("unknown");
+        let executable = match records {
+            [record] => record.executable.as_deref().unwrap_or("unknown"),
+            _ => "batch",
+        };
         let filename = format!(
             "{}--{}--{}.parquet",
-            sanitize_filename(&record.session_id),
+            sanitize_filename(&first.session_id),
             sanitize_filename(executable),
-            record.id
+            first.id
         );
         let file_path = partition_dir.join(&filename);
 
-        // Convert metadata HashMap to DuckDB MAP format
-        // Format: map_from_entries([struct_pack(k := 'key1', v := 'value1'), ...])
-        let metadata_map = if record.metadata.is_empty() {
-            "map([],[]::JSON[])".to_string()
-        } else {
-            let entries: Vec<String> = record.metadata.iter()
-                .map(|(k, v)| {
-                    let key = k.replace('\'', "''");
-                    let value = v.to_string().replace('\'', "''");
-                    format!("struct_pack(k := '{}', v := '{}'::JSON)", key, value)
-                })
-                .collect();
-            format!("map_from_entries([{}])", entries.join(", "))
-        };
-
         // Write via DuckDB using COPY
         conn.execute_batch(
             r#"
@@ -84,31 +85,48 @@ impl Store {
             "#,
         )?;
 
-        // Insert with dynamic SQL for the MAP
-        conn.execute(
-            &format!(
-                r#"
-                INSERT INTO temp_attempt VALUES (
-                    ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, {}, ?
-                )
-                "#,
-                metadata_map
-            ),
-            params![
-                record.id.to_string(),
-                record.timestamp.to_rfc3339(),
-                record.cmd,
-                record.cwd,
-                record.session_id,
-                record.tag,
-                record.source_client,
-                record.machine_id,
-                record.hostname,
-                record.executable,
-                record.format_hint,
-                date.to_string(),
-            ],
-        )?;
+        for record in records {
+            // Convert metadata HashMap to DuckDB MAP format
+            // Format: map_from_entries([struct_pack(k := 'key1', v := 'value1'), ...])
+            let metadata_map = if record.metadata.is_empty() {
+                "map([],[]::JSON[])".to_string()
+            } else {
+                let entries: Vec<String> = record.metadata.iter()
+                    .map(|(k, v)| {
+                        let key = k.replace('\'', "''");
+                        let value = v.to_string().replace('\'', "''");
+                        format!("struct_pack(k := '{}', v := '{}'::JSON)", key, value)
+                    })
+                    .collect();
+                format!("map_from_entries([{}])", entries.join(", "))
+            };
+
+            // Insert with dynamic SQL for the MAP
+            conn.execute(
+                &format!(
+                    r#"
+                    INSERT INTO temp_attempt VALUES (
+                        ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, {}, ?
+                    )
+                    "#,
+                    metadata_map
+                ),
+                params![
+                    record.id.to_string(),
+                    record.timestamp.to_rfc3339(),
+                    record.cmd,
+                    record.cwd,
+                    record.session_id,
+                    record.tag,
+                    record.source_client,
+                    record.machine_id,
+                    record.hostname,
+                    record.executable,
+                    record.format_hint,
+                    record.date().to_string(),
+                ],
+            )?;
+        }
 
         // Atomic write: COPY to temp file, then rename
         let temp_path = atomic::temp_path(&file_path);
@@ -124,6 +142,12 @@ impl Store {
         // Rename temp to final (atomic on POSIX)
         atomic::rename_into_place(&temp_path, &file_path)?;
 
+        for record in records {
+            recent_index::index_attempt(&conn, record)?;
+            running_stats::record_attempt(&conn, record)?;
+            short_ids::index_attempt(&conn, record)?;
+            aliases::assign(&conn, &record.id)?;
+        }
         Ok(())
     }
 
@@ -171,6 +195,10 @@ impl Store {
             ],
         )?;
 
+        recent_index::index_attempt(&conn, record)?;
+        running_stats::record_attempt(&conn, record)?;
+        short_ids::index_attempt(&conn, record)?;
+        aliases::assign(&conn, &record.id)?;
         Ok(())
     }
 
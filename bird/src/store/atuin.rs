@@ -0,0 +1,506 @@
+//! Pushing invocations to an Atuin sync server ([`RemoteType::AtuinServer`]).
+//!
+//! For teams where some members use Atuin, a BIRD client can publish its
+//! command history to their Atuin server, where it shows up in `atuin
+//! search` like any other host's history. Only invocation metadata is sent
+//! (command, directory, exit code, duration); outputs and events have no
+//! Atuin equivalent. The remote is push-only: Atuin records carry nothing
+//! BIRD doesn't already have locally.
+//!
+//! Records go through Atuin's history sync API, the way `atuin sync` does:
+//! each one is encoded as Atuin's msgpack history record, encrypted with
+//! the account's key (XSalsa20-Poly1305) and posted with the login session
+//! token. Both are read from the Atuin data directory of an `atuin login`,
+//! `$ATUIN_DATA_DIR` or `~/.local/share/atuin` unless the remote's
+//! `credential_provider` names another.
+//!
+//! ```toml
+//! [[remotes]]
+//! name = "atuin"
+//! type = "atuin"
+//! uri = "https://api.atuin.sh"
+//! ```
+//!
+//! [`RemoteType::AtuinServer`]: crate::config::RemoteType::AtuinServer
+
+use std::fs;
+use std::path::PathBuf;
+
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, TimeDelta, TimeZone, Utc};
+use crypto_secretbox::aead::{Aead, KeyInit};
+use crypto_secretbox::{Key, Nonce, XSalsa20Poly1305};
+use serde::{Deserialize, Serialize};
+
+use super::remote::{push_plan, sanitize_home, sanitize_key, sanitize_replace, stage_sanitize_hashes, PushOptions, PushStats};
+use super::Store;
+use crate::{Error, RemoteConfig, Result};
+
+/// Sent as `Atuin-Version`; servers reject clients older than they support.
+const ATUIN_VERSION: &str = "18.0.0";
+
+/// Records per request.
+const PUSH_CHUNK: usize = 100;
+
+/// How far before the last push to look again. Invocations are ordered by
+/// completion, but one can be written after a later one was pushed (an
+/// async save, a spooled capture); the server ignores ids it already has,
+/// so sending them again is harmless.
+const RESEND_WINDOW_HOURS: i64 = 24;
+
+/// What has been pushed to an Atuin remote.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AtuinState {
+    /// Completion time of the latest invocation pushed.
+    pushed_through: Option<DateTime<Utc>>,
+}
+
+/// An invocation as an Atuin history record.
+#[derive(Debug, Clone, PartialEq)]
+struct AtuinHistory {
+    /// Atuin ids are uuids without dashes.
+    id: String,
+    timestamp: DateTime<Utc>,
+    /// Nanoseconds, or -1 if unknown.
+    duration: i64,
+    /// -1 if unknown.
+    exit: i64,
+    command: String,
+    cwd: String,
+    session: String,
+    /// `host:user`.
+    hostname: String,
+}
+
+impl AtuinHistory {
+    /// Atuin's msgpack encoding of a history record: an array of its
+    /// fields, `deleted_at` last.
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        msgpack::write_array_len(&mut out, 9);
+        msgpack::write_str(&mut out, &self.id);
+        msgpack::write_str(&mut out, &self.timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+        msgpack::write_sint(&mut out, self.duration);
+        msgpack::write_sint(&mut out, self.exit);
+        msgpack::write_str(&mut out, &self.command);
+        msgpack::write_str(&mut out, &self.cwd);
+        msgpack::write_str(&mut out, &self.session);
+        msgpack::write_str(&mut out, &self.hostname);
+        msgpack::write_nil(&mut out);
+        out
+    }
+}
+
+/// One record of a `POST /history`.
+#[derive(Debug, Serialize)]
+struct AddHistoryRequest {
+    id: String,
+    timestamp: String,
+    /// The encrypted record, as `{"ciphertext": [...], "nonce": [...]}`.
+    data: String,
+    /// SHA-256 of the record's hostname, in hex.
+    hostname: String,
+}
+
+/// Login credentials from an Atuin data directory.
+struct AtuinCredentials {
+    key: [u8; 32],
+    session: String,
+}
+
+impl AtuinCredentials {
+    fn load(remote: &RemoteConfig) -> Result<Self> {
+        let dir = atuin_data_dir(remote)?;
+        let read = |name: &str| {
+            fs::read_to_string(dir.join(name)).map_err(|e| {
+                Error::Config(format!(
+                    "Remote '{}': can't read Atuin {} file in {} ({}); log in with `atuin login` first",
+                    remote.name,
+                    name,
+                    dir.display(),
+                    e
+                ))
+            })
+        };
+        let key = decode_key(read("key")?.trim()).ok_or_else(|| {
+            Error::Config(format!("Remote '{}': {} is not an Atuin key", remote.name, dir.join("key").display()))
+        })?;
+        Ok(Self { key, session: read("session")?.trim().to_string() })
+    }
+}
+
+impl Store {
+    /// Push invocations completed since the last push to an Atuin server.
+    pub(super) fn push_atuin(&self, remote: &RemoteConfig, opts: PushOptions) -> Result<PushStats> {
+        let state_path = self.config.atuin_state_path(&remote.name);
+        let mut state: AtuinState = fs::read_to_string(&state_path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        let after = state.pushed_through.map(|t| t - TimeDelta::hours(RESEND_WINDOW_HOURS));
+        let rows = self.atuin_records(remote, opts.since, after)?;
+        let mut stats = PushStats {
+            invocations: rows.iter().filter(|(_, done)| Some(*done) > state.pushed_through).count(),
+            ..Default::default()
+        };
+        if opts.dry_run || rows.is_empty() {
+            if opts.dry_run {
+                stats.plan = push_plan(remote, &stats);
+            }
+            return Ok(stats);
+        }
+
+        let credentials = AtuinCredentials::load(remote)?;
+        let cipher = XSalsa20Poly1305::new(Key::from_slice(&credentials.key));
+        let url = format!("{}/history", remote.uri.trim_end_matches('/'));
+
+        let progress = self.progress_phase(&format!("push {}", remote.name), Some(rows.len() as u64));
+        // Each chunk moves the watermark, so a cancelled push resumes after
+        // the last chunk the server accepted
+        for chunk in rows.chunks(PUSH_CHUNK) {
+            self.checkpoint()?;
+            let requests = chunk
+                .iter()
+                .map(|(history, _)| add_history_request(&cipher, history))
+                .collect::<Result<Vec<_>>>()?;
+            post_history(remote, &url, &credentials.session, &requests)?;
+
+            state.pushed_through = state.pushed_through.max(chunk.iter().map(|(_, done)| *done).max());
+            fs::write(&state_path, serde_json::to_vec_pretty(&state)?)?;
+            crate::perms::harden_file(&state_path);
+            progress.advance(chunk.len() as u64);
+        }
+        drop(progress);
+        Ok(stats)
+    }
+
+    /// Finished invocations completed after `after`, as Atuin records with
+    /// their completion times, oldest first, sanitized by the remote's
+    /// policy.
+    fn atuin_records(
+        &self,
+        remote: &RemoteConfig,
+        since: Option<chrono::NaiveDate>,
+        after: Option<DateTime<Utc>>,
+    ) -> Result<Vec<(AtuinHistory, DateTime<Utc>)>> {
+        let policy = &remote.sanitize;
+        let replace = sanitize_replace(
+            policy,
+            sanitize_home(policy).as_deref(),
+            &[("cwd", "l.cwd"), ("hostname", "l.hostname")],
+            None,
+        );
+        let mut filters = String::new();
+        if let Some(since) = since {
+            filters.push_str(&format!(" AND timestamp >= '{}'", since));
+        }
+        if let Some(after) = after {
+            filters.push_str(&format!(" AND epoch_ms(COALESCE(completed_at, timestamp)) > {}", after.timestamp_millis()));
+        }
+
+        let conn = self.connection_with_options(false)?;
+        if !policy.hash_fields.is_empty() {
+            stage_sanitize_hashes(&conn, policy, &sanitize_key(&self.config, &remote.name)?)?;
+        }
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id::VARCHAR, session_id, epoch_ms(timestamp), duration_ms, cwd, cmd, exit_code, hostname, \
+                    epoch_ms(COALESCE(completed_at, timestamp)) AS done \
+             FROM (SELECT l.*{replace} FROM local.invocations l) \
+             WHERE status <> 'pending'{filters} \
+             ORDER BY done",
+            replace = replace,
+            filters = filters,
+        ))?;
+
+        let user = std::env::var("USER").unwrap_or_else(|_| "bird".to_string());
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, String>(5)?,
+                row.get::<_, Option<i32>>(6)?,
+                row.get::<_, Option<String>>(7)?,
+                row.get::<_, i64>(8)?,
+            ))
+        })?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            let (id, session_id, started, duration_ms, cwd, cmd, exit_code, hostname, done) = row?;
+            let command = if policy.redact_commands { crate::privacy::redact_command(&cmd) } else { cmd };
+            let history = AtuinHistory {
+                id: uuid::Uuid::parse_str(&id).map(|u| u.simple().to_string()).unwrap_or(id),
+                timestamp: millis(started),
+                duration: duration_ms.map_or(-1, |ms| ms.saturating_mul(1_000_000)),
+                exit: exit_code.map_or(-1, i64::from),
+                command,
+                cwd: cwd.unwrap_or_default(),
+                session: atuin_session(&session_id),
+                hostname: format!("{}:{}", hostname.unwrap_or_default(), user),
+            };
+            records.push((history, millis(done)));
+        }
+        Ok(records)
+    }
+}
+
+fn millis(ms: i64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(ms).single().unwrap_or_default()
+}
+
+/// Atuin sessions are uuids; BIRD session ids are whatever the invoker
+/// chose, so they're mapped to one derived from the id.
+fn atuin_session(session_id: &str) -> String {
+    let hash = blake3::hash(session_id.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash.as_bytes()[..16]);
+    uuid::Builder::from_random_bytes(bytes).into_uuid().simple().to_string()
+}
+
+fn add_history_request(cipher: &XSalsa20Poly1305, history: &AtuinHistory) -> Result<AddHistoryRequest> {
+    let nonce: [u8; 24] = rand::random();
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), history.encode().as_slice())
+        .map_err(|_| Error::Storage(format!("couldn't encrypt invocation {} for Atuin", history.id)))?;
+    let data = serde_json::json!({ "ciphertext": ciphertext, "nonce": nonce.to_vec() });
+    Ok(AddHistoryRequest {
+        id: history.id.clone(),
+        timestamp: history.timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        data: data.to_string(),
+        hostname: sha256_hex(&history.hostname),
+    })
+}
+
+fn post_history(remote: &RemoteConfig, url: &str, session: &str, requests: &[AddHistoryRequest]) -> Result<()> {
+    let body = serde_json::to_string(requests)?;
+    let response = ureq::post(url)
+        .set("Authorization", &format!("Token {}", session))
+        .set("Atuin-Version", ATUIN_VERSION)
+        .set("Content-Type", "application/json")
+        .send_string(&body);
+    match response {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(code @ (401 | 403), _)) => Err(Error::RemoteAuth {
+            remote: remote.name.clone(),
+            detail: format!("{} rejected the Atuin session (HTTP {}); run `atuin login` again", url, code),
+        }),
+        Err(ureq::Error::Status(code, response)) => Err(Error::Storage(format!(
+            "push to {} failed with HTTP {}: {}",
+            url,
+            code,
+            response.into_string().unwrap_or_default().trim()
+        ))),
+        Err(e) => Err(Error::Storage(format!("push to {} failed: {}", url, e))),
+    }
+}
+
+fn sha256_hex(s: &str) -> String {
+    ring::digest::digest(&ring::digest::SHA256, s.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The Atuin data directory: the remote's `credential_provider`, else
+/// where Atuin keeps it.
+fn atuin_data_dir(remote: &RemoteConfig) -> Result<PathBuf> {
+    if let Some(dir) = &remote.credential_provider {
+        return Ok(crate::histfile::expand_home(dir));
+    }
+    if let Some(dir) = std::env::var_os("ATUIN_DATA_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    if let Some(dir) = std::env::var_os("XDG_DATA_HOME") {
+        return Ok(PathBuf::from(dir).join("atuin"));
+    }
+    std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".local/share/atuin"))
+        .ok_or_else(|| Error::Config(format!("Remote '{}': can't locate the Atuin data directory", remote.name)))
+}
+
+/// Decode an Atuin `key` file: base64 of the msgpack-encoded key, as
+/// binary or (older Atuin versions) an array of bytes.
+fn decode_key(encoded: &str) -> Option<[u8; 32]> {
+    let buf = base64::engine::general_purpose::STANDARD.decode(encoded).ok()?;
+    let mut key = [0u8; 32];
+    match buf.as_slice() {
+        [0xc4, 32, bytes @ ..] if bytes.len() == 32 => key.copy_from_slice(bytes),
+        [0xdc, 0, 32, rest @ ..] => {
+            let mut items = rest;
+            for byte in key.iter_mut() {
+                (*byte, items) = match items {
+                    [b @ 0..=0x7f, rest @ ..] => (*b, rest),
+                    [0xcc, b, rest @ ..] => (*b, rest),
+                    _ => return None,
+                };
+            }
+            if !items.is_empty() {
+                return None;
+            }
+        }
+        _ => return None,
+    }
+    Some(key)
+}
+
+/// The few msgpack encodings Atuin's history record needs, compact as
+/// `rmp` writes them.
+mod msgpack {
+    pub fn write_array_len(out: &mut Vec<u8>, len: u32) {
+        match len {
+            0..=15 => out.push(0x90 | len as u8),
+            16..=0xffff => {
+                out.push(0xdc);
+                out.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            _ => {
+                out.push(0xdd);
+                out.extend_from_slice(&len.to_be_bytes());
+            }
+        }
+    }
+
+    pub fn write_str(out: &mut Vec<u8>, s: &str) {
+        let len = s.len();
+        match len {
+            0..=31 => out.push(0xa0 | len as u8),
+            32..=0xff => out.extend_from_slice(&[0xd9, len as u8]),
+            0x100..=0xffff => {
+                out.push(0xda);
+                out.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            _ => {
+                out.push(0xdb);
+                out.extend_from_slice(&(len as u32).to_be_bytes());
+            }
+        }
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    pub fn write_sint(out: &mut Vec<u8>, v: i64) {
+        match v {
+            -32..=-1 => out.push(v as i8 as u8),
+            -128..=-33 => out.extend_from_slice(&[0xd0, v as i8 as u8]),
+            -32768..=-129 => {
+                out.push(0xd1);
+                out.extend_from_slice(&(v as i16).to_be_bytes());
+            }
+            -2147483648..=-32769 => {
+                out.push(0xd2);
+                out.extend_from_slice(&(v as i32).to_be_bytes());
+            }
+            i64::MIN..=-2147483649 => {
+                out.push(0xd3);
+                out.extend_from_slice(&v.to_be_bytes());
+            }
+            0..=0x7f => out.push(v as u8),
+            0x80..=0xff => out.extend_from_slice(&[0xcc, v as u8]),
+            0x100..=0xffff => {
+                out.push(0xcd);
+                out.extend_from_slice(&(v as u16).to_be_bytes());
+            }
+            0x1_0000..=0xffff_ffff => {
+                out.push(0xce);
+                out.extend_from_slice(&(v as u32).to_be_bytes());
+            }
+            _ => {
+                out.push(0xcf);
+                out.extend_from_slice(&(v as u64).to_be_bytes());
+            }
+        }
+    }
+
+    pub fn write_nil(out: &mut Vec<u8>) {
+        out.push(0xc0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::store::InvocationBatch;
+    use crate::{Config, RemoteType};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_encode_history() {
+        let history = AtuinHistory {
+            id: "0190".to_string(),
+            timestamp: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+            duration: 1_500_000_000,
+            exit: -1,
+            command: "ls".to_string(),
+            cwd: "/".to_string(),
+            session: "s".to_string(),
+            hostname: "h:u".to_string(),
+        };
+        let mut expected = vec![0x99, 0xa4];
+        expected.extend_from_slice(b"0190");
+        expected.extend_from_slice(&[0xb4]);
+        expected.extend_from_slice(b"2023-11-14T22:13:20Z");
+        expected.extend_from_slice(&[0xce, 0x59, 0x68, 0x2f, 0x00, 0xff, 0xa2]);
+        expected.extend_from_slice(b"ls");
+        expected.extend_from_slice(&[0xa1, b'/', 0xa1, b's', 0xa3]);
+        expected.extend_from_slice(b"h:u");
+        expected.push(0xc0);
+        assert_eq!(history.encode(), expected);
+
+        let mut long = Vec::new();
+        msgpack::write_str(&mut long, &"x".repeat(40));
+        assert_eq!(&long[..2], &[0xd9, 40]);
+        let mut negative = Vec::new();
+        msgpack::write_sint(&mut negative, -200);
+        assert_eq!(negative, [0xd1, 0xff, 0x38]);
+    }
+
+    #[test]
+    fn test_decode_key() {
+        let engine = base64::engine::general_purpose::STANDARD;
+        let key: Vec<u8> = (0..32u8).map(|i| i * 7).collect();
+
+        let mut bin = vec![0xc4, 32];
+        bin.extend_from_slice(&key);
+        assert_eq!(decode_key(&engine.encode(&bin)).unwrap().to_vec(), key);
+
+        // Older keys are arrays of integers
+        let mut array = vec![0xdc, 0, 32];
+        for &b in &key {
+            if b > 0x7f {
+                array.push(0xcc);
+            }
+            array.push(b);
+        }
+        assert_eq!(decode_key(&engine.encode(&array)).unwrap().to_vec(), key);
+        assert!(decode_key(&engine.encode([0xc4, 2, 1, 2])).is_none());
+        assert!(decode_key("not base64!").is_none());
+    }
+
+    #[test]
+    fn test_dry_run_counts_finished_invocations() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config.clone()).unwrap();
+        let record = InvocationRecord::new("s1", "cargo build", "/tmp", 0, &config.client_id).with_duration(2000);
+        store.write_batch(&InvocationBatch::new(record)).unwrap();
+
+        let remote = RemoteConfig::new("atuin", RemoteType::AtuinServer, "https://atuin.invalid");
+        let records = store.atuin_records(&remote, None, None).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].0.duration, 2_000_000_000);
+        assert_eq!(records[0].0.session, atuin_session("s1"));
+        assert_eq!(records[0].0.id.len(), 32);
+
+        let opts = PushOptions { dry_run: true, ..Default::default() };
+        assert_eq!(store.push(&remote, opts).unwrap().invocations, 1);
+        // Pulling has nothing to read
+        assert!(store.pull(&remote, Default::default()).is_err());
+    }
+}
@@ -0,0 +1,413 @@
+//! Year bundles (`shq archive --bundle`).
+//!
+//! A bundle (`2023.birdpack`) is a gzipped tar holding one year of archived
+//! data as a small BIRD root of its own: `manifest.json` describing it, a
+//! fresh `config.toml` and `db/bird.duckdb`, the year's parquet partitions
+//! (under `recent/`, where that root's views read) and the blob files its
+//! outputs reference. Once the bundle is written the year is detached from
+//! the live store, to the trash so `shq undo` can restore it.
+//!
+//! [`unpack_bundle`] turns a bundle back into a directory that `shq mount`
+//! attaches read-only like any other BIRD root.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use duckdb::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use super::compact::is_seed_file;
+use super::tiering::{content_names, is_tiered};
+use super::{atomic, Store};
+use crate::config::{Config, StorageMode};
+use crate::{Error, Result};
+
+/// File extension of year bundles.
+pub const BUNDLE_EXTENSION: &str = "birdpack";
+
+const BUNDLE_FORMAT: &str = "birdpack";
+const BUNDLE_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Data types bundled, as directories of the archive tier.
+const BUNDLE_DATA_TYPES: &[&str] = &["attempts", "outcomes", "outputs", "sessions", "events"];
+
+/// What a bundle holds, stored in it as `manifest.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    /// Always "birdpack".
+    pub format: String,
+    pub version: u32,
+    pub year: i32,
+    /// Client the data was captured by.
+    pub client_id: String,
+    pub created_at: DateTime<Utc>,
+    /// First and last dates with data.
+    pub first_date: NaiveDate,
+    pub last_date: NaiveDate,
+    /// Rows per data type.
+    pub rows: BTreeMap<String, usize>,
+    pub blobs: usize,
+}
+
+/// Results of writing a bundle.
+#[derive(Debug, Default)]
+pub struct BundleStats {
+    /// Date partitions bundled (across data types).
+    pub partitions: usize,
+    /// Parquet files bundled.
+    pub files: usize,
+    /// Blob files bundled.
+    pub blobs: usize,
+    /// Blobs referenced but not stored locally (tiered or missing).
+    pub blobs_skipped: usize,
+    /// Size of the data bundled, before compression.
+    pub bytes: u64,
+    /// Size of the bundle file.
+    pub bundle_bytes: u64,
+    /// Files moved to the trash when detaching.
+    pub files_detached: usize,
+}
+
+/// A date partition of one data type in the archive tier.
+struct Partition {
+    data_type: &'static str,
+    date: NaiveDate,
+    dir: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+/// A blob file referenced by bundled outputs.
+struct BundledBlob {
+    hash: String,
+    /// Path relative to the data directory.
+    storage_path: String,
+}
+
+impl Store {
+    /// Write the archived data for `year` to a bundle at `path`, then move
+    /// it (and blobs nothing else references) to the trash unless `keep`.
+    ///
+    /// Only the archive tier is bundled; run `shq archive` first.
+    pub fn bundle_year(&self, year: i32, path: &Path, keep: bool, dry_run: bool) -> Result<BundleStats> {
+        if self.config.storage_mode != StorageMode::Parquet {
+            return Err(Error::Config("Bundles need parquet storage mode".to_string()));
+        }
+        let partitions = self.archived_partitions(year)?;
+        if partitions.is_empty() {
+            return Err(Error::NotFound(format!(
+                "archived data for {} (shq archive moves data older than --days)",
+                year
+            )));
+        }
+
+        let conn = self.connection_with_options(false)?;
+        let output_files: Vec<&PathBuf> = partitions
+            .iter()
+            .filter(|p| p.data_type == "outputs")
+            .flat_map(|p| &p.files)
+            .collect();
+        let referenced = blob_refs(&conn, &output_files)?;
+        let data_dir = self.config.data_dir();
+
+        let mut stats = BundleStats {
+            partitions: partitions.len(),
+            files: partitions.iter().map(|p| p.files.len()).sum(),
+            ..Default::default()
+        };
+        stats.bytes = partitions.iter().flat_map(|p| &p.files).filter_map(|f| fs::metadata(f).ok()).map(|m| m.len()).sum();
+        let mut blobs = Vec::new();
+        for blob in referenced {
+            match fs::metadata(data_dir.join(&blob.storage_path)) {
+                Ok(meta) if !is_tiered(&blob.storage_path) => {
+                    stats.bytes += meta.len();
+                    blobs.push(blob);
+                }
+                _ => stats.blobs_skipped += 1,
+            }
+        }
+        stats.blobs = blobs.len();
+        if dry_run {
+            return Ok(stats);
+        }
+
+        let manifest = BundleManifest {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            year,
+            client_id: self.config.client_id.clone(),
+            created_at: Utc::now(),
+            first_date: partitions.iter().map(|p| p.date).min().unwrap_or_default(),
+            last_date: partitions.iter().map(|p| p.date).max().unwrap_or_default(),
+            rows: row_counts(&conn, &partitions)?,
+            blobs: blobs.len(),
+        };
+        self.write_bundle(path, &manifest, &partitions, &blobs)?;
+        stats.bundle_bytes = fs::metadata(path)?.len();
+
+        if !keep {
+            stats.files_detached = self.detach_bundled(&conn, year, &partitions, &blobs)?;
+        }
+        Ok(stats)
+    }
+
+    /// The archive tier's date partitions in `year`, excluding seed files.
+    fn archived_partitions(&self, year: i32) -> Result<Vec<Partition>> {
+        let mut partitions = Vec::new();
+        for data_type in BUNDLE_DATA_TYPES {
+            let type_dir = self.config.archive_dir().join(data_type);
+            if !type_dir.exists() {
+                continue;
+            }
+            for entry in fs::read_dir(&type_dir)?.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let Some(date) = name
+                    .strip_prefix("date=")
+                    .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+                else {
+                    continue;
+                };
+                if date.year() != year || !entry.path().is_dir() {
+                    continue;
+                }
+                let mut files: Vec<PathBuf> = fs::read_dir(entry.path())?
+                    .flatten()
+                    .filter(|f| {
+                        let name = f.file_name().to_string_lossy().to_string();
+                        name.ends_with(".parquet") && !name.starts_with('.') && !is_seed_file(&name)
+                    })
+                    .map(|f| f.path())
+                    .collect();
+                if files.is_empty() {
+                    continue;
+                }
+                files.sort();
+                partitions.push(Partition {
+                    data_type,
+                    date,
+                    dir: entry.path(),
+                    files,
+                });
+            }
+        }
+        partitions.sort_by(|a, b| (a.data_type, a.date).cmp(&(b.data_type, b.date)));
+        Ok(partitions)
+    }
+
+    /// Build the bundle's root in a staging directory next to `path`, then
+    /// tar it up with the partitions and blobs.
+    fn write_bundle(
+        &self,
+        path: &Path,
+        manifest: &BundleManifest,
+        partitions: &[Partition],
+        blobs: &[BundledBlob],
+    ) -> Result<()> {
+        let staging = atomic::temp_path(&path.with_extension("root"));
+        let result = (|| {
+            let mut root = Config::with_root(&staging);
+            root.client_id = manifest.client_id.clone();
+            crate::init::initialize(&root)?;
+
+            let temp = atomic::temp_path(path);
+            let encoder = flate2::write::GzEncoder::new(fs::File::create(&temp)?, flate2::Compression::default());
+            let mut builder = tar::Builder::new(encoder);
+
+            let manifest_json = serde_json::to_vec_pretty(manifest)?;
+            let mut header = tar::Header::new_gnu();
+            header.set_size(manifest_json.len() as u64);
+            header.set_mode(0o600);
+            header.set_mtime(manifest.created_at.timestamp().max(0) as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, MANIFEST_FILE, manifest_json.as_slice())?;
+
+            builder.append_dir_all(".", &staging)?;
+            for partition in partitions {
+                let dir_name = partition.dir.file_name().unwrap_or_default().to_string_lossy();
+                for file in &partition.files {
+                    let name = file.file_name().unwrap_or_default().to_string_lossy();
+                    let entry = format!("db/data/recent/{}/{}/{}", partition.data_type, dir_name, name);
+                    builder.append_path_with_name(file, entry)?;
+                }
+            }
+            let data_dir = self.config.data_dir();
+            for blob in blobs {
+                builder.append_path_with_name(data_dir.join(&blob.storage_path), format!("db/data/{}", blob.storage_path))?;
+            }
+            builder.into_inner()?.finish()?;
+
+            if atomic::replace_file(&temp, path).is_err() {
+                return Err(Error::Storage(format!("could not write bundle {}", path.display())));
+            }
+            Ok(())
+        })();
+        let _ = fs::remove_dir_all(&staging);
+        result
+    }
+
+    /// Trash the bundled partitions and the blobs no remaining output
+    /// references. Returns how many files were trashed.
+    fn detach_bundled(
+        &self,
+        conn: &Connection,
+        year: i32,
+        partitions: &[Partition],
+        blobs: &[BundledBlob],
+    ) -> Result<usize> {
+        let mut files: Vec<PathBuf> = partitions.iter().flat_map(|p| p.files.clone()).collect();
+
+        // Archived outputs aren't in the views, so check the files themselves
+        let bundled: BTreeSet<&PathBuf> = partitions.iter().flat_map(|p| &p.files).collect();
+        let remaining: Vec<PathBuf> = self.output_files()?.into_iter().filter(|f| !bundled.contains(f)).collect();
+        let still_referenced: BTreeSet<String> =
+            blob_refs(conn, &remaining.iter().collect::<Vec<_>>())?.into_iter().map(|b| b.hash).collect();
+        let data_dir = self.config.data_dir();
+        for blob in blobs.iter().filter(|b| !still_referenced.contains(&b.hash)) {
+            conn.execute("DELETE FROM blob_registry WHERE content_hash = ?", params![blob.hash])?;
+            files.extend(content_names(&data_dir.join(&blob.storage_path), &blob.hash)?);
+        }
+
+        let detached = files.len();
+        self.trash_files(&format!("bundle {}", year), &files)?;
+        for partition in partitions {
+            let _ = fs::remove_dir(&partition.dir);
+        }
+        Ok(detached)
+    }
+}
+
+/// Distinct blobs referenced by outputs in `files`.
+fn blob_refs(conn: &Connection, files: &[&PathBuf]) -> Result<Vec<BundledBlob>> {
+    if files.is_empty() {
+        return Ok(Vec::new());
+    }
+    let sql = format!(
+        "SELECT DISTINCT content_hash, storage_ref FROM read_parquet([{}], union_by_name = true) \
+         WHERE storage_type = 'blob'",
+        file_list_sql(files)
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+    let mut blobs = Vec::new();
+    let mut seen = BTreeSet::new();
+    for row in rows {
+        let (hash, storage_ref) = row?;
+        let storage_path = storage_ref.strip_prefix("file://").unwrap_or(&storage_ref).to_string();
+        if seen.insert(storage_path.clone()) {
+            blobs.push(BundledBlob { hash, storage_path });
+        }
+    }
+    Ok(blobs)
+}
+
+/// Rows per data type in `partitions`.
+fn row_counts(conn: &Connection, partitions: &[Partition]) -> Result<BTreeMap<String, usize>> {
+    let mut rows = BTreeMap::new();
+    for data_type in BUNDLE_DATA_TYPES {
+        let files: Vec<&PathBuf> = partitions
+            .iter()
+            .filter(|p| p.data_type == *data_type)
+            .flat_map(|p| &p.files)
+            .collect();
+        if files.is_empty() {
+            continue;
+        }
+        let count: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM read_parquet([{}], union_by_name = true)", file_list_sql(&files)),
+            [],
+            |row| row.get(0),
+        )?;
+        rows.insert(data_type.to_string(), count as usize);
+    }
+    Ok(rows)
+}
+
+fn file_list_sql(files: &[&PathBuf]) -> String {
+    files
+        .iter()
+        .map(|f| format!("'{}'", f.display().to_string().replace('\'', "''")))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn open_bundle(path: &Path) -> Result<tar::Archive<flate2::read::GzDecoder<fs::File>>> {
+    Ok(tar::Archive::new(flate2::read::GzDecoder::new(fs::File::open(path)?)))
+}
+
+/// Read a bundle's manifest.
+pub fn read_bundle_manifest(path: &Path) -> Result<BundleManifest> {
+    let mut archive = open_bundle(path)?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.as_ref() != Path::new(MANIFEST_FILE) {
+            continue;
+        }
+        let mut json = String::new();
+        entry.read_to_string(&mut json)?;
+        let manifest: BundleManifest = serde_json::from_str(&json)
+            .map_err(|e| Error::Config(format!("Invalid bundle manifest in {}: {}", path.display(), e)))?;
+        if manifest.format != BUNDLE_FORMAT || manifest.version > BUNDLE_VERSION {
+            return Err(Error::Config(format!(
+                "{} is a {} v{} bundle; this version reads {} v{}",
+                path.display(),
+                manifest.format,
+                manifest.version,
+                BUNDLE_FORMAT,
+                BUNDLE_VERSION
+            )));
+        }
+        return Ok(manifest);
+    }
+    Err(Error::Config(format!("{} is not a BIRD bundle (no {})", path.display(), MANIFEST_FILE)))
+}
+
+/// Unpack the bundle at `path` into `dest` as a BIRD root.
+pub fn unpack_bundle(path: &Path, dest: &Path) -> Result<BundleManifest> {
+    let manifest = read_bundle_manifest(path)?;
+    fs::create_dir_all(dest)?;
+    crate::perms::set_mode(dest, crate::perms::DIR_MODE)?;
+    // Entries can't land outside `dest`
+    open_bundle(path)?.unpack(dest)?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bundle_year_and_unpack() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path().join("bird"));
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        let mut inv = InvocationRecord::new("test-session", "make old", "/home/user", 0, "user@laptop");
+        inv.timestamp = "2023-03-04T12:00:00Z".parse().unwrap();
+        store.write_invocation(&inv).unwrap();
+        store.archive_old_data(0, false).unwrap();
+
+        let bundle = tmp.path().join("2023.birdpack");
+        assert!(store.bundle_year(2022, &bundle, false, false).is_err());
+        let plan = store.bundle_year(2023, &bundle, false, true).unwrap();
+        assert!(plan.files > 0);
+        assert!(!bundle.exists());
+
+        let stats = store.bundle_year(2023, &bundle, false, false).unwrap();
+        assert_eq!(stats.files_detached, plan.files);
+        assert!(store.archived_partitions(2023).unwrap().is_empty());
+
+        let dest = tmp.path().join("mounted");
+        let manifest = unpack_bundle(&bundle, &dest).unwrap();
+        assert_eq!((manifest.year, manifest.rows.get("attempts")), (2023, Some(&1)));
+        let mounted = Store::open(Config::load_from(&dest).unwrap()).unwrap();
+        assert_eq!(mounted.invocation_count().unwrap(), 1);
+    }
+}
@@ -0,0 +1,343 @@
+//! The store's SQL catalog - schemas, tables, views and macros.
+//!
+//! Backs `shq schema`: the names come from DuckDB's catalog on a fully set
+//! up connection (so TEMPORARY macros like `cwd_invocations()` and the
+//! per-remote `remote_<name>_*()` are included), and known objects get a
+//! one-line description and an example query.
+
+use std::fmt;
+
+use duckdb::Connection;
+
+use super::Store;
+use crate::Result;
+
+/// Kind of catalog object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CatalogKind {
+    Schema,
+    Table,
+    View,
+    /// Scalar or table macro.
+    Macro,
+}
+
+impl fmt::Display for CatalogKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CatalogKind::Schema => "schema",
+            CatalogKind::Table => "table",
+            CatalogKind::View => "view",
+            CatalogKind::Macro => "macro",
+        })
+    }
+}
+
+/// One schema, table, view or macro.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogEntry {
+    pub kind: CatalogKind,
+    /// Schema the object lives in (the schema's own name for schemas).
+    pub schema: String,
+    pub name: String,
+    /// Created per connection (TEMPORARY), so only visible through shq.
+    pub temporary: bool,
+    /// Macro parameters, in order.
+    pub parameters: Vec<String>,
+    pub description: Option<String>,
+    pub example: Option<String>,
+}
+
+impl CatalogEntry {
+    /// How to refer to the object in SQL, e.g. `unified.invocations` or
+    /// `cwd_invocations()`.
+    pub fn sql_name(&self) -> String {
+        match self.kind {
+            CatalogKind::Schema => self.name.clone(),
+            CatalogKind::Macro => format!("{}({})", self.name, self.parameters.join(", ")),
+            _ if self.schema == "main" => self.name.clone(),
+            _ => format!("{}.{}", self.schema, self.name),
+        }
+    }
+}
+
+/// The per-table views each schema layer provides.
+const LAYER_TABLES: [&str; 6] = ["sessions", "attempts", "outcomes", "invocations", "outputs", "events"];
+
+/// What each schema holds.
+fn schema_description(schema: &str) -> Option<&'static str> {
+    Some(match schema {
+        "v1" => "Stable public views (contract version 1): use these from scripts and other tools",
+        "main" => "Your history: local data plus pulled caches (the default schema)",
+        "local" => "Data recorded on this client",
+        "caches" => "Data pulled from remotes with `shq pull`",
+        "remotes" => "Attached remotes, read live (union of all auto-attach remotes)",
+        "unified" => "Everything: main plus remotes, with a _source column",
+        "cwd" => "Placeholders; use the cwd_*() macros for the current directory",
+        "cached_placeholder" | "remote_placeholder" => "Empty tables that give unions their columns when there is no data",
+        _ => return None,
+    })
+}
+
+/// What a per-table view holds, and an example query against `sql_name`.
+fn table_description(table: &str, sql_name: &str) -> Option<(&'static str, String)> {
+    let (description, example) = match table {
+        "sessions" => (
+            "Shells and other invokers, one row per session",
+            format!("SELECT session_id, invoker, registered_at FROM {} ORDER BY registered_at DESC LIMIT 10", sql_name),
+        ),
+        "attempts" => (
+            "Command starts, written when a command begins",
+            format!("SELECT cmd, cwd, timestamp FROM {} ORDER BY timestamp DESC LIMIT 10", sql_name),
+        ),
+        "outcomes" => (
+            "Command completions: exit code, duration, signal",
+            format!("SELECT attempt_id, exit_code, duration_ms FROM {} WHERE exit_code <> 0 LIMIT 10", sql_name),
+        ),
+        "invocations" => (
+            "Commands run: attempts joined with their outcomes, with a status",
+            format!("SELECT cmd, exit_code, duration_ms FROM {} ORDER BY timestamp DESC LIMIT 10", sql_name),
+        ),
+        "outputs" => (
+            "Captured stdout/stderr per invocation (content via storage_ref)",
+            format!("SELECT invocation_id, stream, byte_length FROM {} ORDER BY byte_length DESC LIMIT 10", sql_name),
+        ),
+        "events" => (
+            "Errors, warnings and test results parsed from output",
+            format!("SELECT ref_file, ref_line, message FROM {} WHERE severity = 'error' LIMIT 10", sql_name),
+        ),
+        _ => return None,
+    };
+    Some((description, example))
+}
+
+/// Description and example for a table or view.
+fn relation_description(schema: &str, name: &str, sql_name: &str) -> (Option<String>, Option<String>) {
+    let scope = match schema {
+        "main" => "",
+        "v1" => " (stable contract)",
+        "local" => " (this client)",
+        "caches" => " (pulled from remotes)",
+        "remotes" => " (attached remotes)",
+        "unified" => " (local and remotes)",
+        "cwd" => " (placeholder; use cwd_*() macros)",
+        "cached_placeholder" | "remote_placeholder" => " (always empty)",
+        _ => "",
+    };
+
+    if let Some((description, example)) = table_description(name, sql_name) {
+        return (Some(format!("{}{}", description, scope)), Some(example));
+    }
+    if let Some(table) = name.strip_prefix("qualified_").filter(|t| LAYER_TABLES.contains(t)) {
+        return (
+            Some(format!("unified.{} deduplicated, with the list of _sources per row", table)),
+            Some(format!("SELECT * FROM {} WHERE len(_sources) > 1 LIMIT 10", sql_name)),
+        );
+    }
+
+    let (description, example) = match name {
+        "recent_invocations" => ("Invocations from the last 7 days, newest first", "SELECT cmd, exit_code FROM recent_invocations LIMIT 20"),
+        "invocations_today" => ("Today's invocations, newest first", "SELECT cmd, exit_code FROM invocations_today"),
+        "failed_invocations" => ("Invocations with a non-zero exit code, newest first", "SELECT cmd, exit_code, cwd FROM failed_invocations LIMIT 20"),
+        "invocations_with_outputs" => ("Invocations joined with their outputs (one row per stream)", "SELECT cmd, stream, byte_length FROM invocations_with_outputs LIMIT 20"),
+        "clients" => ("Clients seen in sessions, with first/last seen", "SELECT * FROM clients ORDER BY last_seen DESC"),
+        "events_with_context" => ("Events with their invocation's cmd, cwd and exit code", "SELECT cmd, severity, message FROM events_with_context LIMIT 20"),
+        "session_names" => ("Current name of each named session (`shq session name`)", "SELECT * FROM session_names"),
+        "session_events" => ("Session lifecycle events (named, closed)", "SELECT * FROM session_events ORDER BY timestamp DESC LIMIT 20"),
+        "links" => ("Issues and PRs linked to invocations (`shq link`)", "SELECT kind, target, invocation_id FROM links"),
+        "event_corrections" => ("Events marked wrong or edited by hand, with what they said", "SELECT format_used, message, note FROM event_corrections WHERE action = 'wrong'"),
+        "bird_meta" => ("Store metadata, e.g. the schema version", "SELECT * FROM bird_meta"),
+        "blob_registry" => ("Content-addressed output blobs and their reference counts", "SELECT storage_path, byte_length, ref_count FROM blob_registry ORDER BY byte_length DESC LIMIT 10"),
+        "recent_index" | "recent_index_state" => ("Index of recent invocations for fast ~N lookups", "SELECT * FROM recent_index LIMIT 10"),
+        "daily_stats" => ("Invocations and failures per day, kept up to date on write", "SELECT * FROM daily_stats ORDER BY date DESC LIMIT 14"),
+        "executable_stats" => ("Runs, failures and total duration per executable, kept up to date on write", "SELECT executable, runs, failed FROM executable_stats ORDER BY runs DESC LIMIT 10"),
+        "running_stats_pending" | "running_stats_state" => ("Bookkeeping for daily_stats and executable_stats", "SELECT * FROM running_stats_state"),
+        "short_ids" | "short_ids_state" => ("Short invocation IDs (last 8 characters) and their full IDs", "SELECT id FROM short_ids WHERE short = '3f2a9c1e'"),
+        "aliases" => ("Word-pair invocation aliases (brave-otter) and their full IDs", "SELECT id FROM aliases WHERE alias = 'brave-otter'"),
+        _ => return (None, None),
+    };
+    (Some(description.to_string()), Some(example.to_string()))
+}
+
+/// Description and example for a macro.
+fn macro_description(name: &str) -> (Option<String>, Option<String>) {
+    if let Some(table) = name.strip_prefix("cwd_").filter(|t| LAYER_TABLES.contains(t)) {
+        return (
+            Some(format!("main.{} under the current directory", table)),
+            table_description(table, &format!("{}()", name)).map(|(_, example)| example),
+        );
+    }
+    if let Some(table) = name.strip_prefix("remotes_").filter(|t| LAYER_TABLES.contains(t)) {
+        return (
+            Some(format!("{} from every attached remote, with _source", table)),
+            table_description(table, &format!("{}()", name)).map(|(_, example)| example),
+        );
+    }
+    if let Some(rest) = name.strip_prefix("remote_") {
+        if let Some((remote, table)) = rest.rsplit_once('_').filter(|(_, t)| LAYER_TABLES.contains(t)) {
+            return (
+                Some(format!("{} from the {} remote", table, remote)),
+                table_description(table, &format!("{}()", name)).map(|(_, example)| example),
+            );
+        }
+    }
+
+    let (description, example) = match name {
+        "contexts" => ("Work contexts (repo + branch, split by idle gaps)", "SELECT * FROM contexts() ORDER BY last_active DESC LIMIT 10"),
+        "invocation_contexts" => ("Invocations with the work context each belongs to", "SELECT context_id, cmd FROM invocation_contexts(gap_minutes := 60) LIMIT 20"),
+        "resolve_storage_ref" => ("Paths to read an output's storage_ref from", "SELECT resolve_storage_ref(storage_ref) FROM outputs LIMIT 5"),
+        "is_inline_data" => ("Whether a storage_ref holds its content inline", "SELECT count(*) FROM outputs WHERE is_inline_data(storage_ref)"),
+        "output_text" => ("An invocation's captured output as text (stream_name := to pick one)", "SELECT cmd, output_text(id) FROM invocations WHERE exit_code <> 0 LIMIT 5"),
+        "is_file_ref" => ("Whether a storage_ref points at a blob file", "SELECT count(*) FROM outputs WHERE is_file_ref(storage_ref)"),
+        _ => return (None, None),
+    };
+    (Some(description.to_string()), Some(example.to_string()))
+}
+
+impl Store {
+    /// List the schemas, tables, views and macros available to `shq sql`.
+    ///
+    /// With `remotes`, auto-attach remotes are attached first so their
+    /// `remote_<name>_*()` macros are listed too.
+    pub fn catalog(&self, remotes: bool) -> Result<Vec<CatalogEntry>> {
+        let conn = self.connection()?;
+        if remotes {
+            self.attach_all_remotes(&conn)?;
+        }
+        read_catalog(&conn)
+    }
+}
+
+/// Read the catalog of the connection's own database (and its TEMPORARY
+/// objects), leaving out DuckDB's internal and extension objects.
+fn read_catalog(conn: &Connection) -> Result<Vec<CatalogEntry>> {
+    let sql = r#"
+        SELECT 'schema' AS kind, schema_name, schema_name AS name, false AS temporary, '' AS parameters, comment
+        FROM duckdb_schemas()
+        WHERE database_name = current_database()
+          AND schema_name NOT IN ('information_schema', 'pg_catalog')
+        UNION ALL
+        SELECT 'table', schema_name, table_name, temporary, '', comment
+        FROM duckdb_tables()
+        WHERE database_name IN (current_database(), 'temp') AND NOT internal
+        UNION ALL
+        SELECT 'view', schema_name, view_name, temporary, '', comment
+        FROM duckdb_views()
+        WHERE database_name IN (current_database(), 'temp') AND NOT internal
+        UNION ALL
+        SELECT DISTINCT 'macro', schema_name, function_name, database_name = 'temp',
+            array_to_string(parameters, ','), comment
+        FROM duckdb_functions()
+        WHERE function_type IN ('macro', 'table_macro')
+          AND database_name IN (current_database(), 'temp') AND NOT internal
+    "#;
+
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, bool>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+        ))
+    })?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        let (kind, schema, name, temporary, parameters, comment) = row?;
+        let kind = match kind.as_str() {
+            "schema" => CatalogKind::Schema,
+            "table" => CatalogKind::Table,
+            "view" => CatalogKind::View,
+            _ => CatalogKind::Macro,
+        };
+        let mut entry = CatalogEntry {
+            kind,
+            schema,
+            name,
+            temporary,
+            parameters: parameters
+                .filter(|p| !p.is_empty())
+                .map(|p| p.split(',').map(str::to_string).collect())
+                .unwrap_or_default(),
+            description: None,
+            example: None,
+        };
+
+        let (description, example) = match kind {
+            CatalogKind::Schema => (schema_description(&entry.name).map(str::to_string), None),
+            CatalogKind::Macro => macro_description(&entry.name),
+            _ => relation_description(&entry.schema, &entry.name, &entry.sql_name()),
+        };
+        // A COMMENT ON in the database wins over the built-in description
+        entry.description = comment.filter(|c| !c.is_empty()).or(description);
+        entry.example = example;
+        entries.push(entry);
+    }
+
+    entries.sort_by(|a, b| {
+        (a.kind == CatalogKind::Schema)
+            .cmp(&(b.kind == CatalogKind::Schema))
+            .reverse()
+            .then_with(|| a.schema.cmp(&b.schema))
+            .then_with(|| a.kind.cmp(&b.kind))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_catalog() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE SCHEMA unified;
+            CREATE TABLE main.attempts (id INTEGER, cmd VARCHAR);
+            CREATE VIEW unified.invocations AS SELECT * FROM main.attempts;
+            CREATE VIEW unified.qualified_invocations AS SELECT * FROM main.attempts;
+            CREATE VIEW main.mystery AS SELECT 1;
+            COMMENT ON VIEW main.mystery IS 'Explained in the database';
+            CREATE TEMPORARY MACRO cwd_invocations() AS TABLE (SELECT * FROM main.attempts);
+            CREATE TEMPORARY MACRO remote_team_a_events() AS TABLE (SELECT 1);
+            CREATE MACRO is_file_ref(ref) AS (ref[:5] = 'file:');
+            "#,
+        )
+        .unwrap();
+
+        let entries = read_catalog(&conn).unwrap();
+        let find = |name: &str| entries.iter().find(|e| e.name == name).unwrap_or_else(|| panic!("{} missing", name));
+
+        let unified = find("unified");
+        assert_eq!(unified.kind, CatalogKind::Schema);
+        assert!(unified.description.as_deref().unwrap().contains("remotes"));
+        // Schemas come first
+        assert_eq!(entries[0].kind, CatalogKind::Schema);
+
+        let view = find("invocations");
+        assert_eq!((view.kind, view.sql_name().as_str()), (CatalogKind::View, "unified.invocations"));
+        assert!(view.description.as_deref().unwrap().ends_with("(local and remotes)"));
+        assert!(view.example.as_deref().unwrap().contains("FROM unified.invocations"));
+        assert!(find("qualified_invocations").description.as_deref().unwrap().contains("_sources"));
+        assert_eq!(find("attempts").sql_name(), "attempts");
+        assert_eq!(find("mystery").description.as_deref(), Some("Explained in the database"));
+
+        let cwd = find("cwd_invocations");
+        assert!(cwd.temporary && cwd.kind == CatalogKind::Macro);
+        assert_eq!(cwd.sql_name(), "cwd_invocations()");
+        assert!(cwd.example.as_deref().unwrap().contains("FROM cwd_invocations()"));
+        assert_eq!(find("remote_team_a_events").description.as_deref(), Some("events from the team_a remote"));
+
+        let macro_ = find("is_file_ref");
+        assert!(!macro_.temporary);
+        assert_eq!(macro_.sql_name(), "is_file_ref(ref)");
+
+        // DuckDB's own catalog stays out
+        assert!(!entries.iter().any(|e| e.schema == "information_schema" || e.name == "pg_catalog"));
+    }
+}
@@ -0,0 +1,201 @@
+//! Coalescing bursts of writes into grouped writes.
+//!
+//! Each `write_batch` opens the database (taking its lock in DuckDB mode)
+//! and, in parquet mode, writes a handful of files. A burst of invocations
+//! written one at a time turns into a lock storm or hundreds of tiny files;
+//! [`WriteCoalescer`] queues them and writes each group with
+//! [`Store::write_batches`] once the group is big enough or its oldest
+//! invocation has waited long enough (`[ingest]` in the config).
+
+use std::time::{Duration, Instant};
+
+use super::{InvocationBatch, Store};
+use crate::{Error, Result};
+
+/// A small bounded queue of batches written together.
+///
+/// The queue never holds more than `max_batch` batches: a push that fills
+/// it writes the group before returning. A batch that can't be written
+/// doesn't hold back the rest of its group; it's kept, with its error, for
+/// [`take_failed`]. Only a group that can't be written at all (e.g. the
+/// store can't be opened) fails the push or flush. Queued batches are
+/// written when the coalescer is dropped, but then errors are only logged.
+///
+/// [`take_failed`]: WriteCoalescer::take_failed
+pub struct WriteCoalescer<'a> {
+    store: &'a Store,
+    queue: Vec<InvocationBatch>,
+    oldest: Option<Instant>,
+    max_batch: usize,
+    max_delay: Duration,
+    pushed: usize,
+    written: usize,
+    failed: Vec<(usize, Error)>,
+}
+
+impl<'a> WriteCoalescer<'a> {
+    /// A coalescer with the store's `[ingest]` limits.
+    pub fn new(store: &'a Store) -> Self {
+        let ingest = &store.config.ingest;
+        Self::with_limits(store, ingest.max_batch, Duration::from_millis(ingest.max_delay_ms))
+    }
+
+    /// A coalescer that writes once `max_batch` batches are queued or the
+    /// oldest has waited `max_delay`.
+    pub fn with_limits(store: &'a Store, max_batch: usize, max_delay: Duration) -> Self {
+        Self {
+            store,
+            queue: Vec::new(),
+            oldest: None,
+            max_batch: max_batch.max(1),
+            max_delay,
+            pushed: 0,
+            written: 0,
+            failed: Vec::new(),
+        }
+    }
+
+    /// Queue a batch, writing the group if it's full or overdue.
+    pub fn push(&mut self, batch: InvocationBatch) -> Result<()> {
+        self.oldest.get_or_insert_with(Instant::now);
+        self.queue.push(batch);
+        self.pushed += 1;
+        if self.queue.len() >= self.max_batch {
+            self.flush()?;
+        } else {
+            self.flush_due()?;
+        }
+        Ok(())
+    }
+
+    /// Write the queue if its oldest batch has waited `max_delay`.
+    ///
+    /// Returns how many batches were written. Callers waiting for more
+    /// input call this between polls.
+    pub fn flush_due(&mut self) -> Result<usize> {
+        match self.oldest {
+            Some(oldest) if oldest.elapsed() >= self.max_delay => self.flush(),
+            _ => Ok(0),
+        }
+    }
+
+    /// Write every queued batch. Returns how many were written.
+    pub fn flush(&mut self) -> Result<usize> {
+        if self.queue.is_empty() {
+            return Ok(0);
+        }
+        let batches = std::mem::take(&mut self.queue);
+        let first = self.pushed - batches.len();
+        self.oldest = None;
+        tracing::debug!(batches = batches.len(), "writing coalesced batches");
+        let failed = self.store.write_batches(&batches)?;
+        let written = batches.len() - failed.len();
+        self.written += written;
+        self.failed.extend(failed.into_iter().map(|(i, e)| (first + i, e)));
+        Ok(written)
+    }
+
+    /// Batches that couldn't be written since the last call, by the order
+    /// they were pushed in (from 0), with their errors.
+    pub fn take_failed(&mut self) -> Vec<(usize, Error)> {
+        std::mem::take(&mut self.failed)
+    }
+
+    /// Batches pushed so far.
+    pub fn pushed(&self) -> usize {
+        self.pushed
+    }
+
+    /// Batches queued but not written yet.
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Batches written so far.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+}
+
+impl Drop for WriteCoalescer<'_> {
+    fn drop(&mut self) {
+        let pending = self.queue.len();
+        if let Err(e) = self.flush() {
+            tracing::warn!(pending, "could not write queued invocations: {}", e);
+        }
+        for (_, e) in self.take_failed() {
+            tracing::warn!("could not write a queued invocation: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    fn batch(cmd: &str) -> InvocationBatch {
+        InvocationBatch::new(InvocationRecord::new("test-session", cmd, "/tmp", 0, "test@client"))
+            .with_output("stdout", format!("{}\n", cmd).into_bytes())
+    }
+
+    #[test]
+    fn test_coalescer_flushes_when_full() {
+        let store = Store::open(Config::in_memory()).unwrap();
+
+        let mut coalescer = WriteCoalescer::with_limits(&store, 3, Duration::from_secs(3600));
+        coalescer.push(batch("echo 1")).unwrap();
+        coalescer.push(batch("echo 2")).unwrap();
+        assert_eq!(coalescer.pending(), 2);
+        assert_eq!(store.invocation_count().unwrap(), 0);
+
+        coalescer.push(batch("echo 3")).unwrap();
+        assert_eq!(coalescer.pending(), 0);
+        assert_eq!(coalescer.written(), 3);
+        assert_eq!(store.invocation_count().unwrap(), 3);
+
+        coalescer.push(batch("echo 4")).unwrap();
+        drop(coalescer);
+        assert_eq!(store.invocation_count().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_coalesced_burst_writes_one_file_per_table() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config.clone()).unwrap();
+
+        let batches: Vec<_> = (0..10).map(|i| batch(&format!("echo {}", i))).collect();
+        let date = batches[0].invocation.as_ref().unwrap().date();
+        let mut coalescer = WriteCoalescer::with_limits(&store, 10, Duration::from_secs(3600));
+        for batch in batches {
+            coalescer.push(batch).unwrap();
+        }
+        assert_eq!(coalescer.written(), 10);
+        assert!(coalescer.take_failed().is_empty());
+
+        let files = |dir: std::path::PathBuf| std::fs::read_dir(dir).unwrap().count();
+        assert_eq!(files(config.attempts_dir(&date)), 1);
+        assert_eq!(files(config.outcomes_dir(&date)), 1);
+        assert_eq!(files(config.outputs_dir(&date)), 1);
+        assert_eq!(store.invocation_count().unwrap(), 10);
+    }
+
+    #[test]
+    fn test_coalescer_flushes_when_overdue() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        let mut coalescer = WriteCoalescer::with_limits(&store, 100, Duration::ZERO);
+        coalescer.push(batch("echo 1")).unwrap();
+        assert_eq!(coalescer.pending(), 0);
+        assert_eq!(coalescer.flush_due().unwrap(), 0);
+        assert_eq!(store.invocation_count().unwrap(), 1);
+    }
+}
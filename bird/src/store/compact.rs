@@ -12,8 +12,9 @@ use chrono::{NaiveDate, Utc};
 use uuid::Uuid;
 
 use super::atomic;
+use super::plan::{size, Plan};
 use super::Store;
-use crate::Result;
+use crate::{Error, Result};
 
 /// Check if a filename is a compacted file (contains `__compacted-N__`).
 fn is_compacted_file(name: &str) -> bool {
@@ -21,7 +22,7 @@ fn is_compacted_file(name: &str) -> bool {
 }
 
 /// Check if a filename is a seed file.
-fn is_seed_file(name: &str) -> bool {
+pub(super) fn is_seed_file(name: &str) -> bool {
     name.starts_with("_seed")
 }
 
@@ -96,6 +97,8 @@ pub struct CompactStats {
     pub files_after: usize,
     pub bytes_before: u64,
     pub bytes_after: u64,
+    /// Dry runs: the merges that would be done.
+    pub plan: Plan,
 }
 
 impl CompactStats {
@@ -106,6 +109,7 @@ impl CompactStats {
         self.files_after += other.files_after;
         self.bytes_before += other.bytes_before;
         self.bytes_after += other.bytes_after;
+        self.plan.append(&other.plan);
     }
 }
 
@@ -115,6 +119,8 @@ pub struct ArchiveStats {
     pub partitions_archived: usize,
     pub files_moved: usize,
     pub bytes_moved: u64,
+    /// Dry runs: the partitions that would be moved.
+    pub plan: Plan,
 }
 
 /// Options for compaction operations.
@@ -203,6 +209,16 @@ pub struct CleanStats {
 }
 
 impl Store {
+    /// `COPY` options for parquet files written to `partition_dir`: the
+    /// `[archive]` settings in the archive tier, plain zstd elsewhere.
+    fn parquet_options(&self, partition_dir: &Path) -> String {
+        if partition_dir.starts_with(self.config().archive_dir()) {
+            self.config().archive.copy_options()
+        } else {
+            "FORMAT PARQUET, COMPRESSION ZSTD".to_string()
+        }
+    }
+
     /// Compact files for a specific session in a partition.
     ///
     /// Keeps the most recent `keep_count` files, compacts the rest.
@@ -219,7 +235,7 @@ impl Store {
         files.sort_by_key(|p| file_mtime(p));
 
         // Keep the most recent `keep_count` files
-        let to_keep = files.len().saturating_sub(keep_count).max(0);
+        let to_keep = files.len().saturating_sub(keep_count);
         if to_keep < 2 {
             // Need at least 2 files to compact
             return Ok(CompactStats::default());
@@ -234,6 +250,14 @@ impl Store {
             .sum();
 
         if dry_run {
+            let mut plan = Plan::new();
+            plan.add(format!(
+                "compact {} files of session {} in {} ({})",
+                files_before,
+                session,
+                self.data_relative(partition_dir).display(),
+                size(bytes_before)
+            ));
             return Ok(CompactStats {
                 partitions_compacted: 0, // Will be set by caller
                 sessions_compacted: 1,
@@ -241,6 +265,7 @@ impl Store {
                 files_after: 1,
                 bytes_before,
                 bytes_after: bytes_before, // Estimate
+                plan,
             });
         }
 
@@ -277,8 +302,9 @@ impl Store {
 
         conn.execute(
             &format!(
-                "COPY compact_temp TO '{}' (FORMAT PARQUET, COMPRESSION ZSTD)",
-                temp_path.display()
+                "COPY compact_temp TO '{}' ({})",
+                temp_path.display(),
+                self.parquet_options(partition_dir)
             ),
             [],
         )?;
@@ -303,6 +329,7 @@ impl Store {
             files_after: 1,
             bytes_before,
             bytes_after,
+            ..Default::default()
         })
     }
 
@@ -313,7 +340,7 @@ impl Store {
     fn consolidate_session_files(
         &self,
         partition_dir: &Path,
-        _session: &str,
+        session: &str,
         files: Vec<PathBuf>,
         dry_run: bool,
     ) -> Result<CompactStats> {
@@ -329,6 +356,14 @@ impl Store {
             .sum();
 
         if dry_run {
+            let mut plan = Plan::new();
+            plan.add(format!(
+                "consolidate {} files of session {} in {} ({})",
+                files_before,
+                session,
+                self.data_relative(partition_dir).display(),
+                size(bytes_before)
+            ));
             return Ok(CompactStats {
                 partitions_compacted: 0,
                 sessions_compacted: 1,
@@ -336,6 +371,7 @@ impl Store {
                 files_after: 1,
                 bytes_before,
                 bytes_after: bytes_before,
+                plan,
             });
         }
 
@@ -367,8 +403,9 @@ impl Store {
 
         conn.execute(
             &format!(
-                "COPY consolidate_temp TO '{}' (FORMAT PARQUET, COMPRESSION ZSTD)",
-                temp_path.display()
+                "COPY consolidate_temp TO '{}' ({})",
+                temp_path.display(),
+                self.parquet_options(partition_dir)
             ),
             [],
         )?;
@@ -392,6 +429,7 @@ impl Store {
             files_after: 1,
             bytes_before,
             bytes_after,
+            ..Default::default()
         })
     }
 
@@ -406,6 +444,7 @@ impl Store {
         partition_dir: &Path,
         opts: &CompactOptions,
     ) -> Result<CompactStats> {
+        let _sync = self.sync_guard();
         let mut total_stats = CompactStats::default();
 
         // Group files by session, separating compacted from non-compacted
@@ -498,6 +537,31 @@ impl Store {
         Ok(total_stats)
     }
 
+    /// The `date=` partition directories in a data type directory, sorted.
+    fn date_partitions(&self, data_dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut partitions = Vec::new();
+        for entry in fs::read_dir(data_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            // Skip non-date directories
+            if path.is_dir() && entry.file_name().to_string_lossy().starts_with("date=") {
+                partitions.push(path);
+            }
+        }
+        partitions.sort();
+        Ok(partitions)
+    }
+
+    /// Progress phase name for compacting `data_dir`, e.g. `compact recent/outputs`.
+    fn compact_phase(&self, data_dir: &Path) -> String {
+        format!("compact {}", self.data_relative(data_dir).display())
+    }
+
+    /// `path` relative to the data directory, for messages.
+    fn data_relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(self.config().data_dir()).unwrap_or(path)
+    }
+
     /// Compact files in a partition directory (legacy API).
     pub fn compact_partition(
         &self,
@@ -531,24 +595,15 @@ impl Store {
         }
 
         // v5 schema: iterate over date= partitions directly (no status= partitions)
-        for entry in fs::read_dir(data_dir)? {
-            let entry = entry?;
-            let path = entry.path();
-
-            if !path.is_dir() {
-                continue;
-            }
-
-            let dir_name = entry.file_name().to_string_lossy().to_string();
-
-            // v5 schema: only date= partitions
-            if dir_name.starts_with("date=") {
-                let stats =
-                    self.compact_partition(&path, file_threshold, session_filter, dry_run)?;
-                total_stats.add(&stats);
-            }
-            // Skip non-date directories
+        let partitions = self.date_partitions(data_dir)?;
+        let progress = self.progress_phase(&self.compact_phase(data_dir), Some(partitions.len() as u64));
+        for path in partitions {
+            self.checkpoint()?;
+            let stats = self.compact_partition(&path, file_threshold, session_filter, dry_run)?;
+            total_stats.add(&stats);
+            progress.advance(1);
         }
+        drop(progress);
 
         Ok(total_stats)
     }
@@ -585,6 +640,7 @@ impl Store {
         file_threshold: usize,
         dry_run: bool,
     ) -> Result<CompactStats> {
+        let _sync = self.sync_guard();
         let mut total_stats = CompactStats::default();
         let recent_dir = self.config().recent_dir();
         let today = Utc::now().date_naive();
@@ -640,8 +696,10 @@ impl Store {
     /// Migrate old data from recent to archive with consolidation.
     ///
     /// Consolidates all files in each date partition into a single parquet file
-    /// in the archive, then removes the source files.
+    /// in the archive, written with the `[archive]` parquet settings, then
+    /// removes the source files.
     pub fn archive_old_data(&self, older_than_days: u32, dry_run: bool) -> Result<ArchiveStats> {
+        let _sync = self.sync_guard();
         let mut stats = ArchiveStats::default();
         let cutoff_date = Utc::now().date_naive() - chrono::Duration::days(older_than_days as i64);
 
@@ -649,7 +707,7 @@ impl Store {
         let archive_dir = self.config().archive_dir();
 
         // v5 schema: attempts/outcomes instead of invocations with status partitions
-        for data_type in &["attempts", "outcomes", "outputs", "sessions", "events"] {
+        'data_types: for data_type in &["attempts", "outcomes", "outputs", "sessions", "events"] {
             let recent_data_dir = recent_dir.join(data_type);
             let archive_data_dir = archive_dir.join(data_type);
 
@@ -691,7 +749,13 @@ impl Store {
                 })
                 .collect();
 
+            let progress = self.progress_phase(&format!("archive {}", data_type), Some(partitions_to_archive.len() as u64));
             for (_date, partition_path, dir_name) in partitions_to_archive {
+                // Stop between partitions, but still invalidate the index below
+                if self.cancel_token().is_cancelled() {
+                    break 'data_types;
+                }
+                progress.advance(1);
                 let dest_dir = archive_data_dir.join(&dir_name);
 
                 // Count source stats
@@ -716,6 +780,12 @@ impl Store {
                     stats.partitions_archived += 1;
                     stats.files_moved += file_count;
                     stats.bytes_moved += bytes_before;
+                    stats.plan.add(format!(
+                        "archive {} ({} files, {})",
+                        self.data_relative(&partition_path).display(),
+                        file_count,
+                        size(bytes_before)
+                    ));
                     continue;
                 }
 
@@ -738,21 +808,20 @@ impl Store {
                 let conn = self.connection_with_options(false)?;
                 let src_glob = format!("{}/*.parquet", partition_path.display());
                 let dest_file = dest_dir.join("data_0.parquet");
-                let temp_file = dest_dir.join(".data_0.parquet.tmp");
+                let temp_file = atomic::temp_path(&dest_file);
 
                 conn.execute(
                     &format!(
                         "COPY (SELECT * FROM read_parquet('{}', union_by_name = true)) \
-                         TO '{}' (FORMAT PARQUET, COMPRESSION ZSTD)",
+                         TO '{}' ({})",
                         src_glob,
-                        temp_file.display()
+                        temp_file.display(),
+                        self.config().archive.copy_options()
                     ),
                     [],
                 )?;
 
-                // Atomic rename (hardened to owner-only first)
-                crate::perms::set_mode(&temp_file, crate::perms::FILE_MODE)?;
-                fs::rename(&temp_file, &dest_file)?;
+                atomic::rename_into_place(&temp_file, &dest_file)?;
 
                 // Get consolidated size
                 let bytes_after = fs::metadata(&dest_file)?.len();
@@ -769,6 +838,16 @@ impl Store {
             }
         }
 
+        // Archived rows leave the recent views the index mirrors
+        if stats.partitions_archived > 0 && !dry_run {
+            self.invalidate_recent_index()?;
+            self.invalidate_running_stats()?;
+            self.invalidate_short_ids()?;
+        }
+        if self.cancel_token().is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
         Ok(stats)
     }
 
@@ -798,6 +877,7 @@ impl Store {
 
     /// Compact recent data with full options.
     pub fn compact_recent_with_opts(&self, opts: &CompactOptions) -> Result<CompactStats> {
+        let _sync = self.sync_guard();
         let mut total_stats = CompactStats::default();
         let recent_dir = self.config().recent_dir();
 
@@ -813,6 +893,7 @@ impl Store {
 
     /// Compact archive data with full options.
     pub fn compact_archive_with_opts(&self, opts: &CompactOptions) -> Result<CompactStats> {
+        let _sync = self.sync_guard();
         let mut total_stats = CompactStats::default();
         let archive_dir = self.config().archive_dir();
 
@@ -832,30 +913,23 @@ impl Store {
         data_dir: &Path,
         opts: &CompactOptions,
     ) -> Result<CompactStats> {
+        let _sync = self.sync_guard();
         let mut total_stats = CompactStats::default();
 
         if !data_dir.exists() {
             return Ok(total_stats);
         }
 
-        for entry in fs::read_dir(data_dir)? {
-            let entry = entry?;
-            let path = entry.path();
-
-            if !path.is_dir() {
-                continue;
-            }
-
-            let dir_name = entry.file_name().to_string_lossy().to_string();
-
-            // v5 schema: no status= partitions, only date= partitions
-            if dir_name.starts_with("date=") {
-                // This is a date= partition
-                let stats = self.compact_partition_with_opts(&path, opts)?;
-                total_stats.add(&stats);
-            }
-            // Skip non-date directories
+        // v5 schema: no status= partitions, only date= partitions
+        let partitions = self.date_partitions(data_dir)?;
+        let progress = self.progress_phase(&self.compact_phase(data_dir), Some(partitions.len() as u64));
+        for path in partitions {
+            self.checkpoint()?;
+            let stats = self.compact_partition_with_opts(&path, opts)?;
+            total_stats.add(&stats);
+            progress.advance(1);
         }
+        drop(progress);
 
         Ok(total_stats)
     }
@@ -866,6 +940,7 @@ impl Store {
         session_id: &str,
         opts: &CompactOptions,
     ) -> Result<CompactStats> {
+        let _sync = self.sync_guard();
         let mut total_stats = CompactStats::default();
         let recent_dir = self.config().recent_dir();
 
@@ -891,6 +966,7 @@ impl Store {
     /// 2. Creates outcome records for orphaned invocations
     /// 3. Optionally prunes old archive data
     pub fn clean(&self, opts: &CleanOptions) -> Result<CleanStats> {
+        let _sync = self.sync_guard();
         let mut stats = CleanStats::default();
 
         // Recover orphaned invocations
@@ -913,11 +989,16 @@ impl Store {
 
     /// Prune old archive data.
     ///
-    /// Deletes data from the archive tier older than the specified number of days.
+    /// Removes data from the archive tier older than the specified number of
+    /// days. Removed files go to the trash, so the prune can be undone with
+    /// [`undo_last`](Store::undo_last) during the grace period.
     pub fn prune_archive(&self, older_than_days: u32, dry_run: bool) -> Result<PruneStats> {
+        let _sync = self.sync_guard();
         let mut stats = PruneStats::default();
         let cutoff_date = Utc::now().date_naive() - chrono::Duration::days(older_than_days as i64);
         let archive_dir = self.config().archive_dir();
+        let mut files = Vec::new();
+        let mut partitions = Vec::new();
 
         // v5 schema: attempts/outcomes instead of invocations with status partitions
         for data_type in &["attempts", "outcomes", "outputs", "sessions", "events"] {
@@ -928,19 +1009,28 @@ impl Store {
 
             // Archive has date= partitions directly
             let partition_stats =
-                self.prune_date_partitions(&data_dir, cutoff_date, dry_run)?;
+                self.prune_date_partitions(&data_dir, cutoff_date, &mut files, &mut partitions)?;
             stats.add(&partition_stats);
         }
 
+        if !dry_run {
+            self.trash_files(&format!("prune archive older than {} days", older_than_days), &files)?;
+            // Remove the partition directories if empty
+            for partition in partitions {
+                let _ = fs::remove_dir(&partition);
+            }
+        }
+
         Ok(stats)
     }
 
-    /// Prune date partitions older than cutoff.
+    /// Find parquet files in date partitions older than cutoff.
     fn prune_date_partitions(
         &self,
         parent_dir: &Path,
         cutoff_date: NaiveDate,
-        dry_run: bool,
+        files: &mut Vec<PathBuf>,
+        partitions: &mut Vec<PathBuf>,
     ) -> Result<PruneStats> {
         let mut stats = PruneStats::default();
         let seed_date = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
@@ -980,17 +1070,10 @@ impl Store {
                         stats.files_pruned += 1;
                         stats.bytes_freed += metadata.len();
                     }
-
-                    if !dry_run {
-                        let _ = fs::remove_file(&file_path);
-                    }
+                    files.push(file_path);
                 }
             }
-
-            // Remove the partition directory if empty (and not dry run)
-            if !dry_run {
-                let _ = fs::remove_dir(&path);
-            }
+            partitions.push(path);
         }
 
         Ok(stats)
@@ -1170,9 +1253,12 @@ mod tests {
         let stats = store.compact_recent(2, true).unwrap();
         assert_eq!(stats.sessions_compacted, 1);
         assert_eq!(stats.files_before, 3);
+        let date = chrono::Utc::now().date_naive();
+        assert_eq!(stats.plan.actions().len(), 1);
+        assert!(stats.plan.actions()[0]
+            .starts_with(&format!("compact 3 files of session test-session in recent/attempts/date={} (", date)));
 
         // Files should still be there
-        let date = chrono::Utc::now().date_naive();
         let inv_dir = store.config().attempts_dir(&date);
         let file_count = std::fs::read_dir(&inv_dir)
             .unwrap()
@@ -1419,6 +1505,122 @@ mod tests {
         assert_eq!(remaining, 0, "Recent partition should have no data files");
     }
 
+    #[test]
+    fn test_parquet_options_by_tier() {
+        let (_tmp, store) = setup_store();
+        let date = chrono::Utc::now().date_naive();
+
+        let archive_partition = store.config().archive_dir().join("attempts").join(format!("date={}", date));
+        assert_eq!(store.parquet_options(&archive_partition), store.config().archive.copy_options());
+        assert_eq!(store.parquet_options(&store.config().attempts_dir(&date)), "FORMAT PARQUET, COMPRESSION ZSTD");
+    }
+
+    #[test]
+    fn test_archive_applies_archive_parquet_settings() {
+        let tmp = TempDir::new().unwrap();
+        let mut config = Config::with_root(tmp.path());
+        config.archive.row_group_size = 1000;
+        config.archive.dictionary = false;
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        let record = InvocationRecord::new("test-session", "make", "/home/user", 0, "test@client");
+        store.write_invocation(&record).unwrap();
+
+        // Bulk the partition up past a few row groups
+        let date = chrono::Utc::now().date_naive();
+        let recent_dir = store.config().attempts_dir(&date);
+        let conn = store.connection_with_options(false).unwrap();
+        conn.execute(
+            &format!(
+                "COPY (SELECT a.* FROM read_parquet('{dir}/*.parquet', union_by_name = true) a, range(4999)) \
+                 TO '{dir}/test-session--bulk--0.parquet' (FORMAT PARQUET)",
+                dir = recent_dir.display()
+            ),
+            [],
+        )
+        .unwrap();
+        drop(conn);
+
+        store.archive_old_data(0, false).unwrap();
+
+        let archive_dir = store.config().archive_dir().join("attempts").join(format!("date={}", date));
+        let archived: Vec<_> = std::fs::read_dir(&archive_dir)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().map(|ext| ext == "parquet").unwrap_or(false))
+            .collect();
+        assert_eq!(archived.len(), 1);
+
+        let conn = store.connection_with_options(false).unwrap();
+        let (row_groups, rows, compression): (i64, i64, String) = conn
+            .query_row(
+                &format!(
+                    "SELECT count(DISTINCT row_group_id), \
+                            (SELECT count(*) FROM read_parquet('{path}')), \
+                            string_agg(DISTINCT compression, ',') \
+                     FROM parquet_metadata('{path}')",
+                    path = archived[0].display()
+                ),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(rows, 5000);
+        assert!(row_groups > 1, "expected several row groups, got {}", row_groups);
+        assert_eq!(compression, "ZSTD");
+    }
+
+    #[test]
+    fn test_cancelled_archive_leaves_data_in_place() {
+        let (_tmp, store) = setup_store();
+        let token = crate::CancelToken::new();
+        let store = store.with_cancel(token.clone());
+
+        let record = InvocationRecord::new("test-session", "make", "/home/user", 0, "test@client");
+        store.write_invocation(&record).unwrap();
+
+        token.cancel();
+        assert!(matches!(store.archive_old_data(0, false), Err(Error::Cancelled)));
+        assert!(matches!(store.compact_recent(1, false), Err(Error::Cancelled)));
+
+        // Nothing was moved: the invocation is still in recent data
+        let date = chrono::Utc::now().date_naive();
+        assert!(store.config().attempts_dir(&date).exists());
+        assert_eq!(store.invocation_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_compact_reports_progress() {
+        let (_tmp, store) = setup_store();
+        let recorder = std::sync::Arc::new(crate::progress::Recorder::default());
+        let store = store.with_progress(recorder.clone());
+
+        for i in 0..3 {
+            let record = InvocationRecord::new("test-session", format!("command-{}", i), "/home/user", 0, "test@client");
+            store.write_invocation(&record).unwrap();
+        }
+        store.compact_recent(1, false).unwrap();
+        let calls = recorder.calls();
+        assert!(calls.contains(&"start compact recent/attempts Some(1)".to_string()), "{:?}", calls);
+        assert!(calls.contains(&"advance 1".to_string()));
+        assert!(recorder.balanced(), "{:?}", calls);
+
+        // A partition that fails to compact still finishes the phase
+        let date = chrono::Utc::now().date_naive();
+        let partition = store.config().attempts_dir(&date);
+        // Named like the session's files, and oldest, so it's among those merged
+        let corrupt = partition.join("test-session--corrupt--0.parquet");
+        fs::write(&corrupt, b"not parquet").unwrap();
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(86400);
+        fs::File::options().write(true).open(&corrupt).unwrap().set_modified(old).unwrap();
+        let record = InvocationRecord::new("test-session", "command-3", "/home/user", 0, "test@client");
+        store.write_invocation(&record).unwrap();
+        assert!(store.compact_recent(1, false).is_err());
+        assert!(recorder.balanced(), "{:?}", recorder.calls());
+    }
+
     #[test]
     fn test_archive_dry_run() {
         let (_tmp, store) = setup_store();
@@ -1440,6 +1642,8 @@ mod tests {
         // Archives 4 data types, but counts partitions with files
         assert!(stats.partitions_archived >= 1, "Should report at least 1 partition");
         assert!(stats.files_moved > 0, "Should report files to move");
+        assert_eq!(stats.plan.actions().len(), stats.partitions_archived);
+        assert!(stats.plan.actions()[0].starts_with("archive recent/"));
 
         // Files should still be in recent
         let date = chrono::Utc::now().date_naive();
@@ -1768,6 +1972,29 @@ mod tests {
         assert!(prune_stats.bytes_freed > 0, "Should free bytes");
     }
 
+    #[test]
+    fn test_undo_prune_restores_archive() {
+        let (tmp, store) = setup_store();
+        let record = InvocationRecord::new("test-session", "make", "/home/user", 0, "test@client");
+        store.write_invocation(&record).unwrap();
+        store.archive_old_data(0, false).unwrap();
+
+        let archive_attempts = tmp.path().join("db/data/archive/attempts");
+        let prune_stats = store.prune_archive(0, false).unwrap();
+        assert!(prune_stats.files_pruned > 0);
+        assert!(std::fs::read_dir(&archive_attempts).unwrap().next().is_none());
+
+        let entries = store.trash_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].files.len(), prune_stats.files_pruned);
+
+        let undone = store.undo_last().unwrap().unwrap();
+        assert_eq!(undone.id, entries[0].id);
+        assert!(std::fs::read_dir(&archive_attempts).unwrap().next().is_some());
+        assert!(store.trash_entries().unwrap().is_empty());
+        assert!(store.undo_last().unwrap().is_none());
+    }
+
     #[test]
     fn test_clean_with_prune() {
         let (_tmp, store) = setup_store();
@@ -0,0 +1,231 @@
+//! Invocation profiles for `shq compare`: what to line up when the same
+//! command behaves differently in two places, typically here and on a remote.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use duckdb::params;
+
+use super::Store;
+use crate::{Error, Result};
+
+/// Everything `shq compare` looks at for one invocation.
+#[derive(Debug, Clone)]
+pub struct InvocationProfile {
+    pub id: String,
+    pub cmd: String,
+    pub cwd: String,
+    pub hostname: Option<String>,
+    /// `local`, or the remote the invocation came from.
+    pub source: String,
+    pub timestamp: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<i64>,
+    /// Environment snapshot, if one was recorded.
+    pub env: Option<BTreeMap<String, String>>,
+    /// VCS context (branch, commit, ...), if it ran in a repository.
+    pub vcs: Option<serde_json::Value>,
+    /// Distinct events, keyed by [`event_key`] so they line up across machines.
+    pub events: BTreeSet<String>,
+}
+
+/// Key an event by what it says rather than where: test results by test
+/// name and status, diagnostics by severity, message and file name (paths
+/// differ between checkouts).
+pub fn event_key(
+    severity: Option<&str>,
+    ref_file: Option<&str>,
+    message: Option<&str>,
+    test_name: Option<&str>,
+    status: Option<&str>,
+) -> String {
+    if let Some(test) = test_name {
+        return format!("{}: {}", test, status.unwrap_or("?"));
+    }
+    let mut key = format!("{}: {}", severity.unwrap_or("event"), message.unwrap_or("").trim());
+    if let Some(file) = ref_file.and_then(|f| f.rsplit('/').next()).filter(|f| !f.is_empty()) {
+        key.push_str(&format!(" ({})", file));
+    }
+    key
+}
+
+impl Store {
+    /// Find an invocation in a remote's history (attached or pulled).
+    ///
+    /// `selector` is a full or short ID, or `~N` (or empty, for `~1`) for the
+    /// Nth most recent run there of `cmd` (of anything, without one).
+    pub fn find_remote_invocation(&self, remote: &str, selector: &str, cmd: Option<&str>) -> Result<String> {
+        let config = self
+            .config
+            .get_remote(remote)
+            .ok_or_else(|| Error::Config(format!("Remote '{}' not found", remote)))?;
+        let conn = self.connection()?;
+        self.ensure_remotes_attached(&conn, &[config])?;
+
+        let selector = selector.trim();
+        let result = if selector.is_empty() || selector.starts_with('~') {
+            let n: usize = selector.trim_start_matches('~').parse().unwrap_or(1).max(1);
+            conn.query_row(
+                "SELECT id::VARCHAR FROM unified.invocations
+                 WHERE _source = ? AND (? IS NULL OR cmd = ?)
+                 ORDER BY timestamp DESC LIMIT 1 OFFSET ?",
+                params![remote, cmd, cmd, (n - 1) as i64],
+                |row| row.get(0),
+            )
+        } else {
+            conn.query_row(
+                "SELECT id::VARCHAR FROM unified.invocations
+                 WHERE _source = ? AND (id::VARCHAR = ? OR suffix(id::VARCHAR, ?))
+                 ORDER BY timestamp DESC LIMIT 1",
+                params![remote, selector, selector],
+                |row| row.get(0),
+            )
+        };
+
+        match result {
+            Ok(id) => Ok(id),
+            Err(duckdb::Error::QueryReturnedNoRows) => Err(Error::NotFound(match cmd {
+                Some(cmd) => format!("No run of `{}` matching '{}' on remote '{}'", cmd, selector, remote),
+                None => format!("No invocation matching '{}' on remote '{}'", selector, remote),
+            })),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Load an invocation's profile: from local (and pulled) history, or
+    /// with `remote`, from that remote's history.
+    pub fn invocation_profile(&self, invocation_id: &str, remote: Option<&str>) -> Result<InvocationProfile> {
+        let conn = self.connection()?;
+        let (schema, source_clause) = match remote {
+            Some(name) => {
+                let config = self
+                    .config
+                    .get_remote(name)
+                    .ok_or_else(|| Error::Config(format!("Remote '{}' not found", name)))?;
+                self.ensure_remotes_attached(&conn, &[config])?;
+                ("unified", format!("AND _source = '{}'", name.replace('\'', "''")))
+            }
+            None => ("main", String::new()),
+        };
+
+        let row = conn.query_row(
+            &format!(
+                "SELECT id::VARCHAR, cmd, cwd, hostname, _source, timezone('UTC', timestamp)::VARCHAR, exit_code,
+                        duration_ms, metadata['env']::VARCHAR, metadata['vcs']::VARCHAR
+                 FROM {}.invocations WHERE id::VARCHAR = ? {} LIMIT 1",
+                schema, source_clause
+            ),
+            params![invocation_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, Option<i32>>(6)?,
+                    row.get::<_, Option<i64>>(7)?,
+                    row.get::<_, Option<String>>(8)?,
+                    row.get::<_, Option<String>>(9)?,
+                ))
+            },
+        );
+        let (id, cmd, cwd, hostname, source, timestamp, exit_code, duration_ms, env, vcs) = match row {
+            Ok(row) => row,
+            Err(duckdb::Error::QueryReturnedNoRows) => {
+                return Err(Error::NotFound(format!("Invocation {}", invocation_id)))
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let env = env
+            .map(|json| {
+                serde_json::from_str(&json)
+                    .map_err(|e| Error::Storage(format!("Invalid env snapshot: {}", e)))
+            })
+            .transpose()?;
+        let vcs = vcs
+            .map(|json| {
+                serde_json::from_str(&json)
+                    .map_err(|e| Error::Storage(format!("Invalid VCS context: {}", e)))
+            })
+            .transpose()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT DISTINCT severity, ref_file, message, test_name, status
+             FROM {}.events WHERE invocation_id::VARCHAR = ? {}",
+            schema, source_clause
+        ))?;
+        let events = stmt
+            .query_map(params![invocation_id], |row| {
+                Ok(event_key(
+                    row.get::<_, Option<String>>(0)?.as_deref(),
+                    row.get::<_, Option<String>>(1)?.as_deref(),
+                    row.get::<_, Option<String>>(2)?.as_deref(),
+                    row.get::<_, Option<String>>(3)?.as_deref(),
+                    row.get::<_, Option<String>>(4)?.as_deref(),
+                ))
+            })?
+            .collect::<std::result::Result<BTreeSet<_>, _>>()?;
+
+        Ok(InvocationProfile {
+            id,
+            cmd,
+            cwd,
+            hostname,
+            source: source.unwrap_or_else(|| "local".to_string()),
+            timestamp,
+            exit_code,
+            duration_ms,
+            env,
+            vcs,
+            events,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_event_key_ignores_location() {
+        assert_eq!(
+            event_key(Some("error"), Some("/home/a/src/main.rs"), Some("mismatched types "), None, None),
+            event_key(Some("error"), Some("/builds/x/src/main.rs"), Some("mismatched types"), None, None),
+        );
+        assert_eq!(event_key(None, None, None, Some("tests::it_works"), Some("failed")), "tests::it_works: failed");
+        assert_eq!(event_key(Some("warning"), None, Some("unused"), None, None), "warning: unused");
+    }
+
+    #[test]
+    fn test_local_invocation_profile() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        let env = crate::context::snapshot_env([("CC".to_string(), "clang".to_string())]);
+        let inv = InvocationRecord::new("s", "make", "/p", 2, "test@client")
+            .with_duration(1500)
+            .with_metadata_entry("env", env);
+        store.write_invocation(&inv).unwrap();
+
+        let profile = store.invocation_profile(&inv.id.to_string(), None).unwrap();
+        assert_eq!(profile.cmd, "make");
+        assert_eq!(profile.exit_code, Some(2));
+        assert_eq!(profile.duration_ms, Some(1500));
+        assert_eq!(profile.source, "local");
+        assert_eq!(profile.env.unwrap().get("CC").map(String::as_str), Some("clang"));
+        assert!(profile.events.is_empty());
+
+        assert!(matches!(
+            store.find_remote_invocation("nowhere", "~1", Some("make")),
+            Err(Error::Config(_))
+        ));
+    }
+}
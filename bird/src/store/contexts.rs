@@ -0,0 +1,511 @@
+//! Work contexts - logical sessions inferred from where and when work happened.
+//!
+//! A shell session is tied to one terminal. A work context instead groups
+//! invocations by working tree root and git branch, splitting whenever the
+//! gap between commands exceeds a threshold. Commands run in several
+//! terminals on the same branch therefore land in the same context.
+//!
+//! The grouping is computed at query time by two TEMPORARY table macros,
+//! created on every full connection:
+//!
+//! - `invocation_contexts(gap_minutes := 30)`: invocations plus
+//!   `context_id`, `context_root` and `context_branch` columns
+//! - `contexts(gap_minutes := 30)`: one summary row per context
+//!
+//! Summing context durations gives an estimate of active working time per
+//! project and branch ([`Store::time_spent`], `shq time`).
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeDelta, Utc};
+use duckdb::params;
+
+use super::sessions::{from_epoch_ms, SessionEntry};
+use super::Store;
+use crate::{Error, Result};
+
+/// Default idle gap (in minutes) that starts a new context.
+pub const DEFAULT_CONTEXT_GAP_MINUTES: i64 = 30;
+
+/// Table macros that assign invocations to work contexts.
+///
+/// The root comes from the VCS metadata recorded with each invocation
+/// (falling back to the cwd outside a git repo). Context IDs look like
+/// `magic:feature-x@20261001-0900`: root basename, branch, and start time.
+pub(crate) const CONTEXT_MACROS: &str = r#"
+CREATE OR REPLACE TEMPORARY MACRO invocation_contexts(gap_minutes := 30) AS TABLE (
+    SELECT
+        *,
+        CASE
+            WHEN context_branch IS NULL THEN context_label
+            ELSE context_label || ':' || context_branch
+        END || '@' || strftime(context_started, '%Y%m%d-%H%M') AS context_id
+    FROM (
+        SELECT
+            *,
+            MIN(timestamp) OVER (
+                PARTITION BY context_root, context_branch, context_seq
+            ) AS context_started
+        FROM (
+            SELECT
+                *,
+                SUM(context_break) OVER (
+                    PARTITION BY context_root, context_branch
+                    ORDER BY timestamp
+                    ROWS UNBOUNDED PRECEDING
+                ) AS context_seq
+            FROM (
+                SELECT
+                    *,
+                    CASE
+                        WHEN timestamp - MAX(COALESCE(completed_at, timestamp)) OVER (
+                            PARTITION BY context_root, context_branch
+                            ORDER BY timestamp
+                            ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING
+                        ) <= to_minutes(gap_minutes::BIGINT) THEN 0
+                        ELSE 1
+                    END AS context_break
+                FROM (
+                    SELECT
+                        *,
+                        COALESCE(json_extract_string(metadata['vcs'], '$.root'), cwd) AS context_root,
+                        json_extract_string(metadata['vcs'], '$.branch') AS context_branch,
+                        COALESCE(
+                            NULLIF(regexp_extract(
+                                COALESCE(json_extract_string(metadata['vcs'], '$.root'), cwd),
+                                '[^/]+$'
+                            ), ''),
+                            '/'
+                        ) AS context_label
+                    FROM invocations
+                )
+            )
+        )
+    )
+);
+CREATE OR REPLACE TEMPORARY MACRO contexts(gap_minutes := 30) AS TABLE (
+    SELECT
+        context_id,
+        any_value(context_root) AS root,
+        any_value(context_branch) AS branch,
+        MIN(timestamp) AS started_at,
+        MAX(COALESCE(completed_at, timestamp)) AS last_active,
+        COUNT(*) AS invocations,
+        COUNT(*) FILTER (WHERE exit_code IS NOT NULL AND exit_code <> 0) AS failed,
+        COUNT(DISTINCT session_id) AS sessions
+    FROM invocation_contexts(gap_minutes := gap_minutes)
+    GROUP BY context_id
+);
+"#;
+
+/// Summary of a work context (for listing).
+#[derive(Debug, Clone)]
+pub struct ContextSummary {
+    pub context_id: String,
+    /// Working tree root (or cwd outside a git repo).
+    pub root: String,
+    /// Git branch, if the work happened in a repo.
+    pub branch: Option<String>,
+    /// Timestamp of the first invocation in the context.
+    pub started_at: DateTime<Utc>,
+    /// Completion (or start) time of the last invocation in the context.
+    pub last_active: DateTime<Utc>,
+    pub invocation_count: i64,
+    pub failed_count: i64,
+    /// Number of distinct shell sessions that contributed.
+    pub session_count: i64,
+}
+
+impl ContextSummary {
+    /// Time between the first and last recorded activity.
+    pub fn duration(&self) -> TimeDelta {
+        self.last_active - self.started_at
+    }
+
+    /// Whether `selector` names this context: its ID, branch, or root
+    /// (full path or basename).
+    pub fn matches(&self, selector: &str) -> bool {
+        self.context_id == selector
+            || self.branch.as_deref() == Some(selector)
+            || self.root == selector
+            || self.root.rsplit('/').next() == Some(selector)
+    }
+}
+
+/// One invocation in a context timeline.
+#[derive(Debug, Clone)]
+pub struct ContextEntry {
+    pub context_id: String,
+    pub entry: SessionEntry,
+}
+
+/// Calendar period for [`Store::time_spent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimePeriod {
+    Day,
+    /// Monday to Sunday.
+    Week,
+    Month,
+}
+
+impl TimePeriod {
+    /// Canonical name of the period.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimePeriod::Day => "day",
+            TimePeriod::Week => "week",
+            TimePeriod::Month => "month",
+        }
+    }
+
+    /// First day of the period containing `day`, and of the period after it.
+    pub fn range(&self, day: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            TimePeriod::Day => (day, day + Duration::days(1)),
+            TimePeriod::Week => {
+                let start = day - Duration::days(day.weekday().num_days_from_monday() as i64);
+                (start, start + Duration::days(7))
+            }
+            TimePeriod::Month => {
+                let start = day.with_day(1).unwrap_or(day);
+                let end = if start.month() == 12 {
+                    NaiveDate::from_ymd_opt(start.year() + 1, 1, 1)
+                } else {
+                    NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1)
+                };
+                (start, end.unwrap_or(start))
+            }
+        }
+    }
+}
+
+impl fmt::Display for TimePeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TimePeriod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "day" | "today" => Ok(TimePeriod::Day),
+            "week" => Ok(TimePeriod::Week),
+            "month" => Ok(TimePeriod::Month),
+            other => Err(Error::Config(format!(
+                "Unknown period '{}' (expected one of: day, week, month)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Estimated active time on one root and branch on one day.
+#[derive(Debug, Clone)]
+pub struct TimeEntry {
+    /// Day the contexts started (UTC).
+    pub day: NaiveDate,
+    /// Working tree root (or cwd outside a git repo).
+    pub root: String,
+    pub branch: Option<String>,
+    /// Summed duration of the day's contexts.
+    pub active: TimeDelta,
+    pub contexts: i64,
+    pub invocations: i64,
+}
+
+impl Store {
+    /// Estimate time spent per day, root and branch between `start` and
+    /// `end` (exclusive), optionally only under `root`.
+    ///
+    /// Each work context counts from its first command to the end of its
+    /// last, so idle gaps longer than `gap_minutes` aren't counted. A
+    /// context belongs to the day it started on.
+    pub fn time_spent(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+        gap_minutes: i64,
+        root: Option<&str>,
+    ) -> Result<Vec<TimeEntry>> {
+        let conn = self.connection()?;
+
+        let sql = format!(
+            r#"
+            SELECT
+                started_at::DATE::VARCHAR, root, branch,
+                SUM(epoch_ms(last_active) - epoch_ms(started_at))::BIGINT,
+                COUNT(*), SUM(invocations)::BIGINT
+            FROM contexts(gap_minutes := {})
+            WHERE started_at >= ?::DATE AND started_at < ?::DATE
+              AND (?::VARCHAR IS NULL OR root = ?::VARCHAR)
+            GROUP BY ALL
+            ORDER BY 1, 2, 3
+            "#,
+            gap_minutes
+        );
+
+        let mut stmt = match conn.prepare(&sql) {
+            Ok(stmt) => stmt,
+            Err(e) => {
+                if e.to_string().contains("No files found") {
+                    return Ok(Vec::new());
+                }
+                return Err(e.into());
+            }
+        };
+
+        let rows = stmt.query_map(params![start.to_string(), end.to_string(), root, root], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                TimeEntry {
+                    day: start,
+                    root: row.get(1)?,
+                    branch: row.get(2)?,
+                    active: TimeDelta::milliseconds(row.get(3)?),
+                    contexts: row.get(4)?,
+                    invocations: row.get(5)?,
+                },
+            ))
+        })?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            let (day, mut entry) = row?;
+            entry.day = NaiveDate::parse_from_str(&day, "%Y-%m-%d")
+                .map_err(|e| Error::Storage(format!("Invalid date '{}': {}", day, e)))?;
+            results.push(entry);
+        }
+        Ok(results)
+    }
+
+    /// List work contexts, most recently active first.
+    ///
+    /// `gap_minutes` is the idle time after which activity on the same
+    /// root and branch starts a new context.
+    pub fn list_contexts(&self, gap_minutes: i64, limit: usize) -> Result<Vec<ContextSummary>> {
+        self.query_contexts(gap_minutes, &format!("LIMIT {}", limit))
+    }
+
+    /// Find contexts named by `selector` (see [`ContextSummary::matches`]),
+    /// most recently active first.
+    ///
+    /// An exact context ID selects just that context; a branch or root name
+    /// selects every context that worked on it.
+    pub fn find_contexts(&self, selector: &str, gap_minutes: i64) -> Result<Vec<ContextSummary>> {
+        let contexts = self.query_contexts(gap_minutes, "")?;
+
+        if let Some(exact) = contexts.iter().find(|c| c.context_id == selector) {
+            return Ok(vec![exact.clone()]);
+        }
+        Ok(contexts.into_iter().filter(|c| c.matches(selector)).collect())
+    }
+
+    fn query_contexts(&self, gap_minutes: i64, limit_sql: &str) -> Result<Vec<ContextSummary>> {
+        let conn = self.connection()?;
+
+        let sql = format!(
+            r#"
+            SELECT
+                context_id, root, branch,
+                epoch_ms(started_at), epoch_ms(last_active),
+                invocations, failed, sessions
+            FROM contexts(gap_minutes := {})
+            ORDER BY last_active DESC
+            {}
+            "#,
+            gap_minutes, limit_sql
+        );
+
+        let mut stmt = match conn.prepare(&sql) {
+            Ok(stmt) => stmt,
+            Err(e) => {
+                if e.to_string().contains("No files found") {
+                    return Ok(Vec::new());
+                }
+                return Err(e.into());
+            }
+        };
+
+        let rows = stmt.query_map([], |row| {
+            Ok(ContextSummary {
+                context_id: row.get(0)?,
+                root: row.get(1)?,
+                branch: row.get(2)?,
+                started_at: from_epoch_ms(row.get(3)?),
+                last_active: from_epoch_ms(row.get(4)?),
+                invocation_count: row.get(5)?,
+                failed_count: row.get(6)?,
+                session_count: row.get(7)?,
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+
+    /// Get all invocations in the given contexts, oldest first.
+    pub fn context_invocations(
+        &self,
+        context_ids: &[String],
+        gap_minutes: i64,
+    ) -> Result<Vec<ContextEntry>> {
+        if context_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let conn = self.connection()?;
+
+        let sql = format!(
+            r#"
+            SELECT
+                context_id, id::VARCHAR, session_id, epoch_ms(timestamp),
+                cmd, cwd, exit_code, duration_ms, status
+            FROM invocation_contexts(gap_minutes := {})
+            WHERE context_id IN ({})
+            ORDER BY timestamp
+            "#,
+            gap_minutes,
+            context_ids
+                .iter()
+                .map(|id| format!("'{}'", id.replace('\'', "''")))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        let mut stmt = match conn.prepare(&sql) {
+            Ok(stmt) => stmt,
+            Err(e) => {
+                if e.to_string().contains("No files found") {
+                    return Ok(Vec::new());
+                }
+                return Err(e.into());
+            }
+        };
+
+        let rows = stmt.query_map([], |row| {
+            Ok(ContextEntry {
+                context_id: row.get(0)?,
+                entry: SessionEntry {
+                    id: row.get(1)?,
+                    session_id: row.get(2)?,
+                    timestamp: from_epoch_ms(row.get(3)?),
+                    cmd: row.get(4)?,
+                    cwd: row.get(5)?,
+                    exit_code: row.get(6)?,
+                    duration_ms: row.get(7)?,
+                    status: row.get(8)?,
+                },
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    fn setup_store() -> (TempDir, Store) {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+        (tmp, store)
+    }
+
+    fn write(store: &Store, session_id: &str, cmd: &str, branch: &str) {
+        let record = InvocationRecord::new(session_id, cmd, "/src/magic/bird", 0, "user@laptop")
+            .with_metadata_entry(
+                "vcs",
+                serde_json::json!({ "root": "/src/magic", "branch": branch }),
+            );
+        store.write_invocation(&record).unwrap();
+    }
+
+    #[test]
+    fn test_contexts_span_sessions() {
+        let (_tmp, store) = setup_store();
+        write(&store, "zsh-1", "make", "feature-x");
+        write(&store, "zsh-2", "make test", "feature-x");
+        write(&store, "zsh-1", "git log", "main");
+
+        let contexts = store.list_contexts(DEFAULT_CONTEXT_GAP_MINUTES, 10).unwrap();
+        assert_eq!(contexts.len(), 2);
+
+        let feature = contexts.iter().find(|c| c.matches("feature-x")).unwrap();
+        assert_eq!(feature.root, "/src/magic");
+        assert_eq!(feature.invocation_count, 2);
+        assert_eq!(feature.session_count, 2);
+        assert!(feature.context_id.starts_with("magic:feature-x@"));
+        assert!(feature.matches("magic"));
+
+        let found = store.find_contexts("feature-x", DEFAULT_CONTEXT_GAP_MINUTES).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].context_id, feature.context_id);
+        assert_eq!(store.find_contexts("magic", DEFAULT_CONTEXT_GAP_MINUTES).unwrap().len(), 2);
+
+        let entries = store
+            .context_invocations(std::slice::from_ref(&feature.context_id), DEFAULT_CONTEXT_GAP_MINUTES)
+            .unwrap();
+        let cmds: Vec<_> = entries.iter().map(|e| e.entry.cmd.as_str()).collect();
+        assert_eq!(cmds, vec!["make", "make test"]);
+    }
+
+    #[test]
+    fn test_time_period_range() {
+        let day = NaiveDate::from_ymd_opt(2026, 12, 17).unwrap(); // a Thursday
+        let ymd = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(TimePeriod::Day.range(day), (day, ymd(2026, 12, 18)));
+        assert_eq!(TimePeriod::Week.range(day), (ymd(2026, 12, 14), ymd(2026, 12, 21)));
+        assert_eq!(TimePeriod::Month.range(day), (ymd(2026, 12, 1), ymd(2027, 1, 1)));
+        assert_eq!("Week".parse::<TimePeriod>().unwrap(), TimePeriod::Week);
+        assert!("fortnight".parse::<TimePeriod>().is_err());
+    }
+
+    #[test]
+    fn test_time_spent() {
+        let (_tmp, store) = setup_store();
+        let start = Utc::now() - TimeDelta::hours(2);
+        // 20 minutes of work, an hour's break, then one 5 minute build
+        for (minutes, duration_ms) in [(0, 1_000), (20, 0), (80, 300_000)] {
+            let mut record = InvocationRecord::new("zsh-1", "make", "/src/magic", 0, "user@laptop")
+                .with_duration(duration_ms)
+                .with_metadata_entry("vcs", serde_json::json!({ "root": "/src/magic", "branch": "main" }));
+            record.timestamp = start + TimeDelta::minutes(minutes);
+            store.write_invocation(&record).unwrap();
+        }
+        let (from, to) = TimePeriod::Month.range(start.date_naive());
+        let to = to.max(Utc::now().date_naive() + Duration::days(1));
+
+        let entries = store.time_spent(from, to, DEFAULT_CONTEXT_GAP_MINUTES, None).unwrap();
+        let total: TimeDelta = entries.iter().map(|e| e.active).sum();
+        assert_eq!(total, TimeDelta::minutes(25));
+        assert_eq!(entries.iter().map(|e| e.contexts).sum::<i64>(), 2);
+        assert_eq!(entries[0].branch.as_deref(), Some("main"));
+
+        assert!(store.time_spent(from, to, DEFAULT_CONTEXT_GAP_MINUTES, Some("/elsewhere")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_contexts_empty() {
+        let (_tmp, store) = setup_store();
+
+        assert!(store.list_contexts(DEFAULT_CONTEXT_GAP_MINUTES, 10).unwrap().is_empty());
+        assert!(store.context_invocations(&[], DEFAULT_CONTEXT_GAP_MINUTES).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+//! Public, versioned views (`v1.*`) for tools outside shq.
+//!
+//! The internal schemas change shape as BIRD evolves (V5 split invocations
+//! into attempts and outcomes, for instance). Dashboards, notebooks and
+//! anything that `ATTACH`es the database should read the `v1` schema
+//! instead: `bird.v1.invocations` keeps its columns, names and types for as
+//! long as contract version 1 exists. Columns may be appended; anything
+//! else is a new version (`v2.*`), created alongside `v1` rather than in
+//! place of it.
+//!
+//! The views are persisted in the database file (so a plain `ATTACH` sees
+//! them) and refreshed on connect whenever [`CONTRACT_REVISION`] changes.
+
+use duckdb::Connection;
+
+use crate::Result;
+
+/// Version of the public view contract: the `v<N>` schema name.
+pub const CONTRACT_VERSION: u32 = 1;
+
+/// Revision of the `v1.*` definitions. Bump when the SQL behind the views
+/// has to change (e.g. an internal rename) without the contract changing.
+const CONTRACT_REVISION: u32 = 2;
+
+/// The contract's views, with what each holds.
+pub const CONTRACT_VIEWS: [(&str, &str); 4] = [
+    ("sessions", "Shells and other invokers, one row per session"),
+    ("invocations", "Commands run, with exit code, duration and status"),
+    ("outputs", "Captured output streams per invocation"),
+    ("events", "Errors, warnings and test results parsed from output (or added by hand)"),
+];
+
+/// DDL for contract version 1. Every column is listed explicitly, so
+/// columns added to the internal views don't leak into the contract.
+/// `v1.events` leaves out events corrected by hand (`{uncorrected}`).
+const V1_DDL: &str = r#"
+CREATE SCHEMA IF NOT EXISTS v1;
+CREATE OR REPLACE VIEW v1.sessions AS
+SELECT
+    session_id::VARCHAR AS session_id,
+    client_id::VARCHAR AS client_id,
+    invoker::VARCHAR AS invoker,
+    invoker_pid::INTEGER AS invoker_pid,
+    invoker_type::VARCHAR AS invoker_type,
+    registered_at::TIMESTAMP AS registered_at,
+    cwd::VARCHAR AS cwd,
+    date::DATE AS date,
+    _source::VARCHAR AS source
+FROM main.sessions;
+CREATE OR REPLACE VIEW v1.invocations AS
+SELECT
+    id::UUID AS id,
+    session_id::VARCHAR AS session_id,
+    timestamp::TIMESTAMP AS timestamp,
+    completed_at::TIMESTAMP AS completed_at,
+    duration_ms::BIGINT AS duration_ms,
+    cwd::VARCHAR AS cwd,
+    cmd::VARCHAR AS cmd,
+    executable::VARCHAR AS executable,
+    exit_code::INTEGER AS exit_code,
+    status::VARCHAR AS status,
+    client_id::VARCHAR AS client_id,
+    hostname::VARCHAR AS hostname,
+    tag::VARCHAR AS tag,
+    date::DATE AS date,
+    _source::VARCHAR AS source
+FROM main.invocations;
+CREATE OR REPLACE VIEW v1.outputs AS
+SELECT
+    id::UUID AS id,
+    invocation_id::UUID AS invocation_id,
+    stream::VARCHAR AS stream,
+    content_hash::VARCHAR AS content_hash,
+    byte_length::BIGINT AS byte_length,
+    storage_type::VARCHAR AS storage_type,
+    storage_ref::VARCHAR AS storage_ref,
+    content_type::VARCHAR AS content_type,
+    date::DATE AS date,
+    _source::VARCHAR AS source
+FROM main.outputs;
+CREATE OR REPLACE VIEW v1.events AS
+SELECT
+    id::UUID AS id,
+    invocation_id::UUID AS invocation_id,
+    event_type::VARCHAR AS event_type,
+    severity::VARCHAR AS severity,
+    ref_file::VARCHAR AS ref_file,
+    ref_line::INTEGER AS ref_line,
+    ref_column::INTEGER AS ref_column,
+    message::VARCHAR AS message,
+    error_code::VARCHAR AS error_code,
+    test_name::VARCHAR AS test_name,
+    status::VARCHAR AS status,
+    date::DATE AS date,
+    _source::VARCHAR AS source,
+    coalesce(origin, 'parser')::VARCHAR AS origin
+FROM main.events e
+WHERE {uncorrected};
+"#;
+
+/// The `bird_meta` value recording which contract views are installed.
+fn contract_stamp() -> String {
+    format!("{}.{}", CONTRACT_VERSION, CONTRACT_REVISION)
+}
+
+/// Create or refresh the `v1.*` views if they are missing or out of date.
+pub(crate) fn ensure_contract_views(conn: &Connection) -> Result<()> {
+    let installed: Option<String> = conn
+        .query_row("SELECT value FROM bird_meta WHERE key = 'views_contract'", [], |row| row.get(0))
+        .ok();
+    if installed.as_deref() == Some(contract_stamp().as_str()) {
+        return Ok(());
+    }
+
+    conn.execute_batch(&V1_DDL.replace("{uncorrected}", super::corrections::UNCORRECTED_EVENT_SQL))?;
+    conn.execute(
+        "INSERT INTO bird_meta (key, value, updated_at) VALUES ('views_contract', ?, now())
+         ON CONFLICT (key) DO UPDATE SET value = excluded.value, updated_at = now()",
+        [contract_stamp()],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(conn: &Connection, view: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT column_name FROM duckdb_columns() WHERE schema_name = 'v1' AND table_name = '{}' ORDER BY column_index",
+                view
+            ))
+            .unwrap();
+        stmt.query_map([], |row| row.get(0)).unwrap().map(|r| r.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_contract_views_are_stable() {
+        let conn = Connection::open_in_memory().unwrap();
+        // Internal views with extra columns and a different column order
+        conn.execute_batch(
+            r#"
+            CREATE TABLE bird_meta (key VARCHAR PRIMARY KEY, value VARCHAR NOT NULL, updated_at TIMESTAMPTZ);
+            CREATE TABLE main.sessions AS SELECT 's1' AS session_id, 'me@host' AS client_id, 'zsh' AS invoker,
+                1 AS invoker_pid, 'shell' AS invoker_type, TIMESTAMP '2026-03-02 10:00:00' AS registered_at, '/' AS cwd,
+                DATE '2026-03-02' AS date, 'local' AS _source;
+            CREATE TABLE main.invocations AS SELECT uuid() AS id, 's1' AS session_id, TIMESTAMP '2026-03-02 10:00:00' AS timestamp,
+                5 AS duration_ms, '/' AS cwd, 'make' AS cmd, 'make' AS executable, 2 AS exit_code,
+                'completed' AS status, NULL AS format_hint, 'me@host' AS client_id, 'host' AS hostname,
+                NULL AS tag, NULL AS signal, false AS timeout, TIMESTAMP '2026-03-02 10:00:00' AS completed_at,
+                NULL AS metadata, DATE '2026-03-02' AS date, 'local' AS _source, 'internal' AS new_internal_column;
+            CREATE TABLE main.outputs (id UUID, invocation_id UUID, stream VARCHAR, content_hash VARCHAR,
+                byte_length BIGINT, storage_type VARCHAR, storage_ref VARCHAR, content_type VARCHAR, date DATE,
+                sequence INTEGER, _source VARCHAR);
+            CREATE TABLE main.events (id UUID, invocation_id UUID, client_id VARCHAR, hostname VARCHAR,
+                event_type VARCHAR, severity VARCHAR, ref_file VARCHAR, ref_line INTEGER, ref_column INTEGER,
+                message VARCHAR, error_code VARCHAR, test_name VARCHAR, status VARCHAR, format_used VARCHAR,
+                date DATE, origin VARCHAR, _source VARCHAR);
+            "#,
+        )
+        .unwrap();
+        conn.execute_batch(crate::store::corrections::EVENT_CORRECTIONS_DDL).unwrap();
+
+        ensure_contract_views(&conn).unwrap();
+        // Second call is a no-op
+        ensure_contract_views(&conn).unwrap();
+
+        assert_eq!(
+            columns(&conn, "invocations"),
+            [
+                "id", "session_id", "timestamp", "completed_at", "duration_ms", "cwd", "cmd", "executable",
+                "exit_code", "status", "client_id", "hostname", "tag", "date", "source",
+            ]
+        );
+        for (view, _) in CONTRACT_VIEWS {
+            assert!(!columns(&conn, view).is_empty(), "v1.{} missing", view);
+        }
+
+        let (cmd, exit_code): (String, i32) = conn
+            .query_row("SELECT cmd, exit_code FROM v1.invocations", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((cmd.as_str(), exit_code), ("make", 2));
+
+        let stamp: String = conn
+            .query_row("SELECT value FROM bird_meta WHERE key = 'views_contract'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stamp, contract_stamp());
+    }
+}
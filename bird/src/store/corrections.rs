@@ -0,0 +1,360 @@
+//! Manual corrections to extracted events.
+//!
+//! Parsers get things wrong. An event can be marked wrong (hidden) or
+//! edited (hidden, with a manual replacement event), and events can be
+//! added by hand. Every correction keeps a copy of the event it corrects,
+//! so the list doubles as feedback on the parsers even after a reparse has
+//! replaced the original.
+
+use chrono::{DateTime, Utc};
+use duckdb::params;
+use uuid::Uuid;
+
+use super::sessions::from_epoch_ms;
+use super::Store;
+use crate::schema::{EventRecord, EVENT_ORIGIN_MANUAL};
+use crate::{Error, Result};
+
+/// DDL for event corrections.
+///
+/// Lives in the main DuckDB database in both storage modes, like `links`.
+/// Created lazily on connect so existing installations pick it up.
+pub(crate) const EVENT_CORRECTIONS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS main.event_corrections (
+    event_id       UUID NOT NULL,
+    invocation_id  UUID NOT NULL,
+    action         VARCHAR NOT NULL,
+    replacement_id UUID,
+    note           VARCHAR,
+    origin         VARCHAR,
+    format_used    VARCHAR,
+    severity       VARCHAR,
+    ref_file       VARCHAR,
+    ref_line       INTEGER,
+    message        VARCHAR,
+    created_at     TIMESTAMP NOT NULL
+);
+"#;
+
+/// Condition on an events row aliased `e` that excludes corrected events.
+///
+/// Parser events also match a correction by content, so an event marked
+/// wrong stays hidden when a reparse extracts it again under a new id.
+pub(crate) const UNCORRECTED_EVENT_SQL: &str = r#"NOT EXISTS (
+    SELECT 1 FROM main.event_corrections c
+    WHERE c.event_id = e.id
+       OR (c.origin = 'parser' AND e.origin IS DISTINCT FROM 'manual'
+           AND c.invocation_id = e.invocation_id
+           AND c.format_used IS NOT DISTINCT FROM e.format_used
+           AND c.message IS NOT DISTINCT FROM e.message
+           AND c.ref_file IS NOT DISTINCT FROM e.ref_file
+           AND c.ref_line IS NOT DISTINCT FROM e.ref_line)
+)"#;
+
+/// Correction actions.
+const ACTION_WRONG: &str = "wrong";
+const ACTION_EDITED: &str = "edited";
+
+/// Fields of a manual event, or the fields to change when editing one.
+#[derive(Debug, Clone, Default)]
+pub struct EventEdit {
+    pub event_type: Option<String>,
+    pub severity: Option<String>,
+    pub ref_file: Option<String>,
+    pub ref_line: Option<i32>,
+    pub ref_column: Option<i32>,
+    pub message: Option<String>,
+    pub error_code: Option<String>,
+    pub test_name: Option<String>,
+    pub status: Option<String>,
+}
+
+impl EventEdit {
+    /// Overwrite the fields of `record` that this edit sets.
+    fn apply(&self, record: &mut EventRecord) {
+        fn set<T: Clone>(field: &mut Option<T>, value: &Option<T>) {
+            if value.is_some() {
+                field.clone_from(value);
+            }
+        }
+        set(&mut record.event_type, &self.event_type);
+        set(&mut record.severity, &self.severity);
+        set(&mut record.ref_file, &self.ref_file);
+        set(&mut record.ref_line, &self.ref_line);
+        set(&mut record.ref_column, &self.ref_column);
+        set(&mut record.message, &self.message);
+        set(&mut record.error_code, &self.error_code);
+        set(&mut record.test_name, &self.test_name);
+        set(&mut record.status, &self.status);
+    }
+}
+
+/// A recorded correction, with the event as it was when corrected.
+#[derive(Debug, Clone)]
+pub struct EventCorrection {
+    pub event_id: String,
+    pub invocation_id: String,
+    /// `wrong` or `edited`.
+    pub action: String,
+    /// The manual event that replaced this one (edits only).
+    pub replacement_id: Option<String>,
+    pub note: Option<String>,
+    /// Origin of the corrected event (`parser` or `manual`).
+    pub origin: Option<String>,
+    pub format_used: Option<String>,
+    pub severity: Option<String>,
+    pub ref_file: Option<String>,
+    pub ref_line: Option<i32>,
+    pub message: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Store {
+    /// Hide an event that was extracted wrongly.
+    ///
+    /// `event_id` may be a unique prefix. Returns false if the event was
+    /// already corrected.
+    pub fn mark_event_wrong(&self, event_id: &str, note: Option<&str>) -> Result<bool> {
+        let conn = self.connection()?;
+        let event = find_event(&conn, event_id)?;
+        if is_corrected(&conn, &event.id)? {
+            return Ok(false);
+        }
+        record_correction(&conn, &event, ACTION_WRONG, None, note)?;
+        Ok(true)
+    }
+
+    /// Add an event to an invocation by hand. Returns the new event's id.
+    pub fn add_manual_event(&self, invocation_id: &str, event: &EventEdit) -> Result<String> {
+        if event.message.is_none() {
+            return Err(Error::Config("A manual event needs a message".to_string()));
+        }
+        let conn = self.connection()?;
+        let (client_id, hostname, date): (String, Option<String>, String) = conn
+            .query_row(
+                "SELECT client_id, hostname, date::VARCHAR FROM invocations WHERE id = ?",
+                params![invocation_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(|_| Error::NotFound(format!("Invocation {}", invocation_id)))?;
+        let invocation_uuid = Uuid::parse_str(invocation_id)
+            .map_err(|e| Error::Storage(format!("Invalid invocation id: {}", e)))?;
+        let date = date
+            .parse()
+            .map_err(|e| Error::Storage(format!("Invalid date: {}", e)))?;
+
+        let mut record = EventRecord::new(invocation_uuid, client_id, EVENT_ORIGIN_MANUAL, date);
+        record.hostname = hostname;
+        record.origin = EVENT_ORIGIN_MANUAL.to_string();
+        event.apply(&mut record);
+        self.write_events(std::slice::from_ref(&record))?;
+        Ok(record.id.to_string())
+    }
+
+    /// Correct an event: hide it and add a manual copy with `edit` applied.
+    ///
+    /// `event_id` may be a unique prefix. Returns the replacement's id.
+    pub fn edit_event(&self, event_id: &str, edit: &EventEdit, note: Option<&str>) -> Result<String> {
+        let conn = self.connection()?;
+        let original = find_event(&conn, event_id)?;
+        if is_corrected(&conn, &original.id)? {
+            return Err(Error::Config(format!("Event {} was already corrected", original.id)));
+        }
+
+        let mut replacement = original.clone();
+        replacement.id = Uuid::now_v7();
+        replacement.origin = EVENT_ORIGIN_MANUAL.to_string();
+        edit.apply(&mut replacement);
+        self.write_events(std::slice::from_ref(&replacement))?;
+
+        record_correction(&conn, &original, ACTION_EDITED, Some(&replacement.id), note)?;
+        Ok(replacement.id.to_string())
+    }
+
+    /// Corrections made so far, newest first.
+    pub fn event_corrections(&self, limit: Option<usize>) -> Result<Vec<EventCorrection>> {
+        let conn = self.connection()?;
+        let limit_clause = limit.map(|l| format!("LIMIT {}", l)).unwrap_or_default();
+        let mut stmt = conn.prepare(&format!(
+            r#"
+            SELECT event_id::VARCHAR, invocation_id::VARCHAR, action, replacement_id::VARCHAR, note,
+                   origin, format_used, severity, ref_file, ref_line, message, epoch_ms(created_at)
+            FROM main.event_corrections
+            ORDER BY created_at DESC
+            {}
+            "#,
+            limit_clause
+        ))?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(EventCorrection {
+                event_id: row.get(0)?,
+                invocation_id: row.get(1)?,
+                action: row.get(2)?,
+                replacement_id: row.get(3)?,
+                note: row.get(4)?,
+                origin: row.get(5)?,
+                format_used: row.get(6)?,
+                severity: row.get(7)?,
+                ref_file: row.get(8)?,
+                ref_line: row.get(9)?,
+                message: row.get(10)?,
+                created_at: from_epoch_ms(row.get(11)?),
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+}
+
+/// Look up an event by id or unique id prefix.
+fn find_event(conn: &duckdb::Connection, event_id: &str) -> Result<EventRecord> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT id::VARCHAR, invocation_id::VARCHAR, client_id, hostname, event_type, severity,
+               ref_file, ref_line, ref_column, message, error_code, test_name, status,
+               format_used, date::VARCHAR, coalesce(origin, 'parser')
+        FROM main.events
+        WHERE id::VARCHAR LIKE ? || '%'
+        LIMIT 2
+        "#,
+    )?;
+    let rows = stmt.query_map(params![event_id.replace(['%', '_'], "")], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(14)?,
+            EventRecord {
+                id: Uuid::nil(),
+                invocation_id: Uuid::nil(),
+                client_id: row.get(2)?,
+                hostname: row.get(3)?,
+                event_type: row.get(4)?,
+                severity: row.get(5)?,
+                ref_file: row.get(6)?,
+                ref_line: row.get(7)?,
+                ref_column: row.get(8)?,
+                message: row.get(9)?,
+                error_code: row.get(10)?,
+                test_name: row.get(11)?,
+                status: row.get(12)?,
+                format_used: row.get::<_, Option<String>>(13)?.unwrap_or_default(),
+                date: chrono::NaiveDate::default(),
+                origin: row.get(15)?,
+            },
+        ))
+    })?;
+    let mut found = rows.collect::<std::result::Result<Vec<_>, _>>()?;
+
+    match found.len() {
+        0 => Err(Error::NotFound(format!("Event {}", event_id))),
+        1 => {
+            let (id, invocation_id, date, mut record) = found.remove(0);
+            let parse_err = |e: uuid::Error| Error::Storage(format!("Invalid event id: {}", e));
+            record.id = Uuid::parse_str(&id).map_err(parse_err)?;
+            record.invocation_id = Uuid::parse_str(&invocation_id).map_err(parse_err)?;
+            record.date = date
+                .parse()
+                .map_err(|e| Error::Storage(format!("Invalid date: {}", e)))?;
+            Ok(record)
+        }
+        _ => Err(Error::Config(format!("Event id '{}' is ambiguous; use more characters", event_id))),
+    }
+}
+
+/// Whether `event_id` already has a correction.
+fn is_corrected(conn: &duckdb::Connection, event_id: &Uuid) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT COUNT(*) > 0 FROM main.event_corrections WHERE event_id = ?",
+        params![event_id.to_string()],
+        |row| row.get(0),
+    )?)
+}
+
+/// Record a correction of `event`, keeping a copy of what it said.
+fn record_correction(
+    conn: &duckdb::Connection,
+    event: &EventRecord,
+    action: &str,
+    replacement_id: Option<&Uuid>,
+    note: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO main.event_corrections VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, now())",
+        params![
+            event.id.to_string(),
+            event.invocation_id.to_string(),
+            action,
+            replacement_id.map(|id| id.to_string()),
+            note,
+            event.origin,
+            event.format_used,
+            event.severity,
+            event.ref_file,
+            event.ref_line,
+            event.message,
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::store::{EventFilters, InvocationBatch};
+    use crate::Config;
+    use tempfile::TempDir;
+
+    fn setup_store() -> (TempDir, Store) {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+        (tmp, store)
+    }
+
+    #[test]
+    fn test_mark_wrong_edit_and_add() {
+        let (_tmp, store) = setup_store();
+        let inv = InvocationRecord::new("s", "gcc -c main.c", "/p", 1, "test@client");
+        let id = inv.id.to_string();
+        let output = b"main.c:3:5: error: expected ';'\nmain.c:9:1: warning: unused variable 'x'\n";
+        store
+            .write_batch(&InvocationBatch::new(inv).with_output("stdout", output.to_vec()))
+            .unwrap();
+        store.extract_events(&id, Some("gcc")).unwrap();
+        let filters = EventFilters { invocation_id: Some(id.clone()), ..Default::default() };
+        let events = store.query_events(&filters).unwrap();
+        assert_eq!(events.len(), 2);
+        let warning = events.iter().find(|e| e.severity.as_deref() == Some("warning")).unwrap();
+        let error = events.iter().find(|e| e.severity.as_deref() == Some("error")).unwrap();
+
+        assert!(store.mark_event_wrong(&warning.id, Some("not a warning")).unwrap());
+        assert!(!store.mark_event_wrong(&warning.id, None).unwrap());
+        assert_eq!(store.event_count(&filters).unwrap(), 1);
+
+        let edit = EventEdit { ref_line: Some(4), ..Default::default() };
+        let replacement = store.edit_event(&error.id, &edit, None).unwrap();
+        let manual = EventEdit { severity: Some("error".into()), message: Some("link failed".into()), ..Default::default() };
+        store.add_manual_event(&id, &manual).unwrap();
+
+        // Reparsing keeps manual events and the corrections
+        store.reparse_events(std::slice::from_ref(&id), Some("gcc")).unwrap();
+        let events = store.query_events(&filters).unwrap();
+        assert_eq!(events.len(), 2, "{:?}", events);
+        assert!(events.iter().any(|e| e.id == replacement && e.ref_line == Some(4)));
+        assert!(events.iter().any(|e| e.message.as_deref() == Some("link failed")));
+
+        let corrections = store.event_corrections(None).unwrap();
+        assert_eq!(corrections.len(), 2);
+        assert_eq!(corrections[1].action, "wrong");
+        assert_eq!(corrections[1].note.as_deref(), Some("not a warning"));
+        assert_eq!(corrections[1].format_used.as_deref(), Some("gcc"));
+    }
+}
@@ -0,0 +1,217 @@
+//! Deduplicating repeated inline output.
+//!
+//! Outputs below `inline_threshold` are stored inline in their output row,
+//! so a command that prints the same thing every time (`ls` in the same
+//! directory, `git status` on a clean tree) stores it again per invocation.
+//! New small outputs reference a registered blob with the same content hash
+//! instead of inlining it, and [`Store::dedup_outputs`] catches up on
+//! history: inline content stored more than once, or already stored as a
+//! blob, moves to one shared blob file. In parquet mode the affected output
+//! files are replaced by rewritten copies under new names.
+
+use std::collections::HashMap;
+
+use duckdb::{params, Connection};
+
+use super::rebalance::rewrite_output_file;
+use super::Store;
+use crate::config::StorageMode;
+use crate::{Error, Result};
+
+/// Smallest inline output `shq dedup-outputs` moves to a shared blob; below
+/// this a blob file costs more than the repeats it saves.
+pub const DEFAULT_DEDUP_MIN_BYTES: usize = 256;
+
+/// Results of deduplicating inline outputs.
+#[derive(Debug, Default)]
+pub struct DedupStats {
+    /// Inline outputs now referencing a shared blob.
+    pub outputs: usize,
+    /// Distinct contents among them.
+    pub contents: usize,
+    /// Inline bytes no longer stored, less the new blob files.
+    pub bytes_saved: u64,
+    /// Output parquet files rewritten.
+    pub files_rewritten: usize,
+}
+
+/// A content hash stored inline more than once (or also as a blob).
+struct Duplicate {
+    byte_length: u64,
+    count: usize,
+    has_blob: bool,
+}
+
+impl Store {
+    /// Move inline outputs of at least `min_bytes` (default:
+    /// [`DEFAULT_DEDUP_MIN_BYTES`]) whose content is stored more than once
+    /// to shared blob files.
+    pub fn dedup_outputs(&self, min_bytes: Option<usize>, dry_run: bool) -> Result<DedupStats> {
+        let _sync = self.sync_guard();
+        let min_bytes = min_bytes.unwrap_or(DEFAULT_DEDUP_MIN_BYTES);
+        self.with_connection(|conn| {
+            let duplicates = duplicated_contents(conn, min_bytes)?;
+            let mut stats = DedupStats {
+                contents: duplicates.len(),
+                ..Default::default()
+            };
+            for dup in duplicates.values() {
+                stats.outputs += dup.count;
+                let blob_bytes = if dup.has_blob { 0 } else { dup.byte_length };
+                stats.bytes_saved += dup.byte_length * dup.count as u64 - blob_bytes;
+            }
+            if dry_run || duplicates.is_empty() {
+                return Ok(stats);
+            }
+
+            match self.config.storage_mode {
+                StorageMode::Parquet => {
+                    let files = self.output_files()?;
+                    let progress = self.progress_phase("dedup outputs", Some(files.len() as u64));
+                    for file in files {
+                        self.checkpoint()?;
+                        progress.advance(1);
+                        let source = format!("read_parquet('{}')", file.display().to_string().replace('\'', "''"));
+                        let rows = duplicate_rows(conn, &source, min_bytes, &duplicates)?;
+                        if rows.is_empty() {
+                            continue;
+                        }
+                        let changes = self.shared_refs(conn, &rows)?;
+                        rewrite_output_file(conn, &file, &changes)?;
+                        stats.files_rewritten += 1;
+                    }
+                }
+                StorageMode::DuckDB => {
+                    let rows = duplicate_rows(conn, "local.outputs", min_bytes, &duplicates)?;
+                    for (id, storage_type, storage_ref) in self.shared_refs(conn, &rows)? {
+                        conn.execute(
+                            "UPDATE local.outputs SET storage_type = ?, storage_ref = ? WHERE id::VARCHAR = ?",
+                            params![storage_type, storage_ref, id],
+                        )?;
+                    }
+                }
+            }
+            Ok(stats)
+        })
+    }
+
+    /// New `(id, storage_type, storage_ref)` for each `(id, content_hash,
+    /// storage_ref)` inline row, storing its content as a blob (once per hash).
+    fn shared_refs(&self, conn: &Connection, rows: &[(String, String, String)]) -> Result<Vec<(String, String, String)>> {
+        use base64::Engine;
+
+        let mut changes = Vec::new();
+        for (id, content_hash, storage_ref) in rows {
+            let b64 = storage_ref.split(',').nth(1).unwrap_or("");
+            let content = base64::engine::general_purpose::STANDARD
+                .decode(b64)
+                .map_err(|e| Error::Storage(format!("invalid inline output {}: {}", id, e)))?;
+            let storage_path = self.store_blob(conn, content_hash, &content, "output")?;
+            changes.push((id.clone(), "blob".to_string(), format!("file://{}", storage_path)));
+        }
+        Ok(changes)
+    }
+}
+
+/// Inline contents of at least `min_bytes` stored more than once, or also
+/// registered as a blob, by content hash.
+fn duplicated_contents(conn: &Connection, min_bytes: usize) -> Result<HashMap<String, Duplicate>> {
+    let mut stmt = conn.prepare(
+        "SELECT content_hash, MAX(byte_length), COUNT(*), \
+                content_hash IN (SELECT content_hash FROM blob_registry) \
+         FROM local.outputs \
+         WHERE storage_type = 'inline' AND byte_length >= ? \
+         GROUP BY content_hash \
+         HAVING COUNT(*) > 1 OR content_hash IN (SELECT content_hash FROM blob_registry)",
+    )?;
+    let rows = stmt.query_map(params![min_bytes as i64], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            Duplicate {
+                byte_length: row.get::<_, i64>(1)? as u64,
+                count: row.get::<_, i64>(2)? as usize,
+                has_blob: row.get(3)?,
+            },
+        ))
+    })?;
+
+    let mut duplicates = HashMap::new();
+    for row in rows {
+        let (hash, dup) = row?;
+        duplicates.insert(hash, dup);
+    }
+    Ok(duplicates)
+}
+
+/// `(id, content_hash, storage_ref)` of the inline rows of `source` whose
+/// content is in `duplicates`.
+fn duplicate_rows(
+    conn: &Connection,
+    source: &str,
+    min_bytes: usize,
+    duplicates: &HashMap<String, Duplicate>,
+) -> Result<Vec<(String, String, String)>> {
+    let sql = format!(
+        "SELECT id::VARCHAR, content_hash, storage_ref FROM {source} \
+         WHERE storage_type = 'inline' AND byte_length >= {min_bytes}",
+        source = source,
+        min_bytes = min_bytes
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+
+    let mut matching = Vec::new();
+    for row in rows {
+        let row: (String, String, String) = row?;
+        if duplicates.contains_key(&row.1) {
+            matching.push(row);
+        }
+    }
+    Ok(matching)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_dedup_repeated_inline_outputs() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        let content = b"Cargo.toml\nREADME.md\nsrc\n".to_vec();
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let inv = InvocationRecord::new("test-session", "ls", "/home/user", 0, "user@laptop");
+            store.write_invocation(&inv).unwrap();
+            store.store_output(inv.id, "stdout", &content, inv.date(), Some("ls")).unwrap();
+            ids.push(inv.id.to_string());
+        }
+
+        let plan = store.dedup_outputs(Some(1), true).unwrap();
+        assert_eq!((plan.outputs, plan.contents, plan.files_rewritten), (2, 1, 0));
+        assert_eq!(plan.bytes_saved, content.len() as u64);
+
+        let stats = store.dedup_outputs(Some(1), false).unwrap();
+        assert_eq!((stats.outputs, stats.files_rewritten), (2, 2));
+        for id in &ids {
+            let output = store.get_output(id).unwrap().unwrap();
+            assert_eq!(output.storage_type, "blob");
+            assert_eq!(store.read_output_content(&output).unwrap(), content);
+        }
+
+        // New copies reference the shared blob rather than being inlined
+        let inv = InvocationRecord::new("test-session", "ls", "/home/user", 0, "user@laptop");
+        store.write_invocation(&inv).unwrap();
+        store.store_output(inv.id, "stdout", &content, inv.date(), Some("ls")).unwrap();
+        let output = store.get_output(&inv.id.to_string()).unwrap().unwrap();
+        assert_eq!(output.storage_type, "blob");
+        assert_eq!(store.dedup_outputs(Some(1), true).unwrap().outputs, 0);
+    }
+}
@@ -10,7 +10,9 @@ use uuid::Uuid;
 use super::atomic;
 use super::Store;
 use crate::config::StorageMode;
-use crate::schema::EventRecord;
+use crate::events::{self, fallback_parsers, parse_output, EventParser, ParsedEvent};
+use crate::extensions::Capabilities;
+use crate::schema::{EventRecord, EVENT_ORIGIN_MANUAL};
 use crate::{Error, Result};
 
 /// A format detection rule from event-formats.toml.
@@ -148,6 +150,8 @@ pub struct EventSummary {
     pub error_code: Option<String>,
     pub test_name: Option<String>,
     pub status: Option<String>,
+    /// `parser` or `manual`.
+    pub origin: String,
 }
 
 /// Filters for querying events.
@@ -171,6 +175,147 @@ pub struct EventFilters {
     pub date_to: Option<NaiveDate>,
     /// Maximum number of events to return.
     pub limit: Option<usize>,
+    /// Include events that were marked wrong or edited (hidden by default).
+    pub include_corrected: bool,
+}
+
+/// Unmatched error-looking lines kept as samples by [`Store::explain_extraction`].
+const EXPLAIN_SAMPLE_LINES: usize = 5;
+
+/// Words that make an output line look like it should have produced an event.
+const ERROR_WORDS: &[&str] = &["error", "fail", "fatal", "panic", "exception", "warning"];
+
+/// Whether `line` mentions an error, failure or warning.
+fn looks_like_error(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    ERROR_WORDS.iter().any(|word| lower.contains(word))
+}
+
+/// Whether `line` is one `event` came from: it contains the event's
+/// message or its `file:line` location.
+fn line_matches_event(line: &str, event: &ParsedEvent) -> bool {
+    let message = event.message.as_deref().map(str::trim).unwrap_or("");
+    if !message.is_empty() && line.contains(message) {
+        return true;
+    }
+    match (&event.ref_file, event.ref_line) {
+        (Some(file), Some(line_no)) => line.contains(&format!("{}:{}", file, line_no)),
+        _ => false,
+    }
+}
+
+/// Count the lines of `text` and those matched by `events`, and collect
+/// error-looking lines no event accounts for into `samples`.
+fn match_lines(text: &str, events: &[ParsedEvent], samples: &mut Vec<String>) -> (usize, usize) {
+    let mut lines = 0;
+    let mut matched = 0;
+    for line in text.lines() {
+        lines += 1;
+        if events.iter().any(|event| line_matches_event(line, event)) {
+            matched += 1;
+        } else if samples.len() < EXPLAIN_SAMPLE_LINES && looks_like_error(line) {
+            let line = line.trim();
+            samples.push(match line.char_indices().nth(200) {
+                Some((end, _)) => format!("{}...", &line[..end]),
+                None => line.to_string(),
+            });
+        }
+    }
+    (lines, matched)
+}
+
+/// Why extraction uses the format it does (see [`ExtractionReport`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatReason {
+    /// Given explicitly (`--format`).
+    Override,
+    /// Recorded when the command was captured.
+    Captured,
+    /// A format hint pattern matched the command.
+    Hint { pattern: String, priority: i32 },
+    /// No hint matched; the default format.
+    Default,
+}
+
+/// What extracting events from one invocation would do, from
+/// [`Store::explain_extraction`]. Nothing is written.
+#[derive(Debug, Clone)]
+pub struct ExtractionReport {
+    pub invocation_id: String,
+    pub cmd: String,
+    /// Format extraction asks for (`auto` means content detection).
+    pub format: String,
+    pub reason: FormatReason,
+    /// `duck_hunt`, or `built-in` for the pure-Rust parsers.
+    pub engine: &'static str,
+    /// Built-in parser that handled the output; for `auto`, the one
+    /// content detection picked. `None` with duck_hunt or when nothing matched.
+    pub parser: Option<String>,
+    /// Outputs read.
+    pub outputs: usize,
+    /// Output lines.
+    pub lines: usize,
+    /// Lines that contain an event's message or location.
+    pub matched_lines: usize,
+    /// Events the parser produced.
+    pub events: usize,
+    /// Error-looking lines no event accounts for (first few).
+    pub unmatched_samples: Vec<String>,
+}
+
+/// Invocations looked up and parsed together by [`Store::extract_events_batch`].
+const EXTRACT_BATCH_SIZE: usize = 50;
+
+/// Totals from [`Store::extract_events_batch`].
+#[derive(Debug, Clone, Default)]
+pub struct ExtractSummary {
+    /// Invocations requested.
+    pub total: usize,
+    /// Invocations processed.
+    pub processed: usize,
+    /// Invocations that produced at least one event.
+    pub with_events: usize,
+    /// Events extracted.
+    pub events: usize,
+}
+
+/// An invocation queued for extraction.
+struct ExtractTarget {
+    id: String,
+    uuid: Uuid,
+    client_id: String,
+    hostname: Option<String>,
+    date: NaiveDate,
+    format: String,
+    outputs: Vec<super::OutputInfo>,
+}
+
+impl ExtractTarget {
+    /// Parse one output's content into event records for this invocation.
+    fn parse(&self, parsers: &[Box<dyn EventParser>], content: &[u8]) -> Vec<EventRecord> {
+        let text = String::from_utf8_lossy(content);
+        let Some((format_used, events)) = parse_output(parsers, &self.format, &text) else {
+            return Vec::new();
+        };
+
+        events
+            .into_iter()
+            .map(|event| {
+                let mut record = EventRecord::new(self.uuid, &self.client_id, format_used, self.date);
+                record.hostname = self.hostname.clone();
+                record.event_type = event.event_type;
+                record.severity = event.severity;
+                record.ref_file = event.ref_file;
+                record.ref_line = event.ref_line;
+                record.ref_column = event.ref_column;
+                record.message = event.message;
+                record.error_code = event.error_code;
+                record.test_name = event.test_name;
+                record.status = event.status;
+                record
+            })
+            .collect()
+    }
 }
 
 impl Store {
@@ -229,11 +374,34 @@ impl Store {
         }
     }
 
+    /// Detect the output format of a command being captured.
+    ///
+    /// Format hints for the command win. When they only give `auto`, the
+    /// first KB of output is checked with the built-in parsers, so later
+    /// reparses use the same format. Returns `None` if nothing matched.
+    pub fn detect_output_format(
+        &self,
+        cmd: &str,
+        outputs: &[(String, Vec<u8>)],
+    ) -> Result<Option<String>> {
+        let format = self.detect_format_for_command(cmd)?;
+        if format != "auto" {
+            return Ok(Some(format));
+        }
+
+        let parsers = fallback_parsers();
+        Ok(outputs
+            .iter()
+            .find_map(|(_, content)| events::detect_format(&parsers, content))
+            .map(str::to_string))
+    }
+
     /// Extract events from an invocation's output using duck_hunt.
     ///
     /// Parses the stdout/stderr of an invocation and stores the extracted events.
     /// Uses read_duck_hunt_log() directly on storage refs for efficiency - no content
-    /// is loaded into Rust memory.
+    /// is loaded into Rust memory. Without duck_hunt, falls back to the built-in
+    /// pure-Rust parsers (see [`crate::events`]).
     ///
     /// Returns the number of events extracted.
     pub fn extract_events(
@@ -243,45 +411,424 @@ impl Store {
     ) -> Result<usize> {
         let conn = self.connection()?;
 
-        // Get invocation info for format detection and metadata
-        let (cmd, client_id, hostname, date): (String, String, Option<String>, String) = conn
+        if !Capabilities::detect(&conn)?.duck_hunt {
+            return self.extract_events_with_parsers(
+                invocation_id,
+                format_override,
+                &fallback_parsers(),
+            );
+        }
+
+        let targets = self.extract_target(&conn, invocation_id, format_override)?;
+        let counts = self.extract_with_duck_hunt(&conn, &targets)?;
+        Ok(counts.iter().sum())
+    }
+
+    /// Extract events from an invocation's output using Rust-side parsers.
+    ///
+    /// Output content is read into memory and handed to the first parser that
+    /// handles the detected format (or, for `auto`, the first that finds
+    /// anything). Works without the duck_hunt extension.
+    ///
+    /// Returns the number of events extracted.
+    pub fn extract_events_with_parsers(
+        &self,
+        invocation_id: &str,
+        format_override: Option<&str>,
+        parsers: &[Box<dyn EventParser>],
+    ) -> Result<usize> {
+        let conn = self.connection()?;
+        let targets = self.extract_target(&conn, invocation_id, format_override)?;
+
+        let mut records = Vec::new();
+        for output in &targets[0].outputs {
+            // read_blob needs scalarfs for data: refs; decode those directly
+            #[allow(deprecated)]
+            let content = match self
+                .read_output_content(output)
+                .or_else(|_| output.read_content(&self.config))
+            {
+                Ok(content) => content,
+                Err(e) => {
+                    tracing::warn!(output = %output.storage_ref, "could not read output: {}", e);
+                    continue;
+                }
+            };
+            records.extend(targets[0].parse(parsers, &content));
+        }
+
+        self.write_events(&records)?;
+        Ok(records.len())
+    }
+
+    /// Explain how events would be extracted from an invocation without
+    /// extracting them: the format and why it was chosen, how much of the
+    /// output the parser matched, and error-looking lines it missed.
+    pub fn explain_extraction(
+        &self,
+        invocation_id: &str,
+        format_override: Option<&str>,
+    ) -> Result<ExtractionReport> {
+        let conn = self.connection()?;
+        let (cmd, captured): (String, Option<String>) = conn
             .query_row(
-                "SELECT cmd, client_id, hostname, date::VARCHAR FROM invocations WHERE id = ?",
+                "SELECT cmd, format_hint FROM invocations WHERE id = ?::UUID",
                 params![invocation_id],
-                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+                |row| Ok((row.get(0)?, row.get(1)?)),
             )
-            .map_err(|e| Error::NotFound(format!("Invocation {}: {}", invocation_id, e)))?;
+            .map_err(|_| Error::NotFound(format!("Invocation {}", invocation_id)))?;
+
+        let (format, reason) = match (format_override, captured) {
+            (Some(format), _) => (format.to_string(), FormatReason::Override),
+            (None, Some(format)) => (format, FormatReason::Captured),
+            (None, None) => {
+                let matched = self.check_format(&cmd)?;
+                let reason = match matched.source {
+                    super::FormatSource::UserDefined { pattern, priority }
+                    | super::FormatSource::Builtin { pattern, priority } => FormatReason::Hint { pattern, priority },
+                    super::FormatSource::Default => FormatReason::Default,
+                };
+                (matched.format, reason)
+            }
+        };
+
+        let duck_hunt = Capabilities::detect(&conn)?.duck_hunt;
+        let parsers = fallback_parsers();
+        let targets = self.extract_target(&conn, invocation_id, Some(&format))?;
+        let mut report = ExtractionReport {
+            invocation_id: invocation_id.to_string(),
+            cmd,
+            format,
+            reason,
+            engine: if duck_hunt { "duck_hunt" } else { "built-in" },
+            parser: None,
+            outputs: 0,
+            lines: 0,
+            matched_lines: 0,
+            events: 0,
+            unmatched_samples: Vec::new(),
+        };
+
+        for output in &targets[0].outputs {
+            #[allow(deprecated)]
+            let content = match self
+                .read_output_content(output)
+                .or_else(|_| output.read_content(&self.config))
+            {
+                Ok(content) => content,
+                Err(e) => {
+                    tracing::warn!(output = %output.storage_ref, "could not read output: {}", e);
+                    continue;
+                }
+            };
+            let text = String::from_utf8_lossy(&content);
+            let parsed = if duck_hunt {
+                self.duck_hunt_events(&conn, output, &report.format)?
+            } else {
+                match parse_output(&parsers, &report.format, &text) {
+                    Some((name, parsed)) if !parsed.is_empty() || report.parser.is_none() => {
+                        report.parser = Some(name.to_string());
+                        parsed
+                    }
+                    Some((_, parsed)) => parsed,
+                    None => Vec::new(),
+                }
+            };
+
+            let (lines, matched) = match_lines(&text, &parsed, &mut report.unmatched_samples);
+            report.outputs += 1;
+            report.lines += lines;
+            report.matched_lines += matched;
+            report.events += parsed.len();
+        }
+
+        Ok(report)
+    }
+
+    /// Parse one output with duck_hunt without storing the events.
+    fn duck_hunt_events(
+        &self,
+        conn: &duckdb::Connection,
+        output: &super::OutputInfo,
+        format: &str,
+    ) -> Result<Vec<ParsedEvent>> {
+        let resolved_ref = match output.storage_ref.strip_prefix("file://") {
+            Some(suffix) => self.config.data_dir().join(suffix).display().to_string(),
+            None => output.storage_ref.clone(),
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT event_type, severity, ref_file, ref_line::INTEGER, ref_column::INTEGER,
+                    message, error_code, test_name, status
+             FROM read_duck_hunt_log('{}', '{}')
+             WHERE event_type IS NOT NULL OR message IS NOT NULL",
+            resolved_ref.replace('\'', "''"),
+            format.replace('\'', "''"),
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok(ParsedEvent {
+                event_type: row.get(0)?,
+                severity: row.get(1)?,
+                ref_file: row.get(2)?,
+                ref_line: row.get(3)?,
+                ref_column: row.get(4)?,
+                message: row.get(5)?,
+                error_code: row.get(6)?,
+                test_name: row.get(7)?,
+                status: row.get(8)?,
+            })
+        })?;
+        Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+    }
+
+    /// Extract events from many invocations, in batches.
+    ///
+    /// Used for backfills. Everything runs on one connection; each batch of
+    /// invocations is looked up with a single query, and with duck_hunt all
+    /// of a batch's outputs are parsed by one statement. Without duck_hunt,
+    /// outputs are read and parsed on a pool of threads. Progress is
+    /// reported per batch to the store's [`ProgressSink`](crate::ProgressSink).
+    ///
+    /// Invocations that no longer exist are skipped.
+    pub fn extract_events_batch(
+        &self,
+        invocation_ids: &[String],
+        format_override: Option<&str>,
+    ) -> Result<ExtractSummary> {
+        let _sync = self.sync_guard();
+        self.with_connection(|conn| self.extract_batches(conn, invocation_ids, format_override, false))
+    }
+
+    /// Throw away the events of `invocation_ids` and extract them again.
+    ///
+    /// For use after format hints change: unlike [`Store::extract_events_batch`],
+    /// the format recorded at capture time is ignored and each command's
+    /// format comes from the current format hints (or `format_override`).
+    /// Events are replaced a batch at a time, so a cancelled reparse leaves
+    /// the remaining invocations' events untouched.
+    pub fn reparse_events(
+        &self,
+        invocation_ids: &[String],
+        format_override: Option<&str>,
+    ) -> Result<ExtractSummary> {
+        let _sync = self.sync_guard();
+        self.with_connection(|conn| self.extract_batches(conn, invocation_ids, format_override, true))
+    }
 
-        // Determine format to use
-        let format = match format_override {
-            Some(f) => f.to_string(),
-            None => self.detect_format(&cmd)?,
+    /// Extract events for `invocation_ids`, [`EXTRACT_BATCH_SIZE`] at a time.
+    /// With `reparse`, existing events are deleted first.
+    fn extract_batches(
+        &self,
+        conn: &duckdb::Connection,
+        invocation_ids: &[String],
+        format_override: Option<&str>,
+        reparse: bool,
+    ) -> Result<ExtractSummary> {
+        let duck_hunt = Capabilities::detect(conn)?.duck_hunt;
+        let parsers = fallback_parsers();
+        let mut summary = ExtractSummary {
+            total: invocation_ids.len(),
+            ..Default::default()
         };
+        let progress = self.progress_phase("extract events", Some(summary.total as u64));
 
-        // Parse the date
-        let date = date
-            .parse::<NaiveDate>()
-            .map_err(|e| Error::Storage(format!("Invalid date: {}", e)))?;
+        for batch in invocation_ids.chunks(EXTRACT_BATCH_SIZE) {
+            self.checkpoint()?;
+            if reparse {
+                for id in batch {
+                    self.delete_events_for_invocation(id)?;
+                }
+            }
+            let targets = self.extract_targets(conn, batch, format_override, reparse)?;
+            let counts = if duck_hunt {
+                self.extract_with_duck_hunt(conn, &targets)?
+            } else {
+                self.extract_with_parsers_parallel(&targets, &parsers)?
+            };
+
+            summary.processed += batch.len();
+            summary.events += counts.iter().sum::<usize>();
+            summary.with_events += counts.iter().filter(|&&n| n > 0).count();
+            progress.advance(batch.len() as u64);
+            progress.message(&format!("{} events", summary.events));
+        }
 
-        // Ensure the events partition directory exists
-        let partition_dir = self.config.events_dir(&date);
-        fs::create_dir_all(&partition_dir)?;
+        Ok(summary)
+    }
+
+    /// Look up a single invocation for extraction.
+    fn extract_target(
+        &self,
+        conn: &duckdb::Connection,
+        invocation_id: &str,
+        format_override: Option<&str>,
+    ) -> Result<Vec<ExtractTarget>> {
+        let targets = self.extract_targets(conn, &[invocation_id.to_string()], format_override, false)?;
+        if targets.is_empty() {
+            return Err(Error::NotFound(format!("Invocation {}", invocation_id)));
+        }
+        Ok(targets)
+    }
+
+    /// Look up invocations and their parseable outputs with one query each.
+    ///
+    /// The format for each is the override, then the format recorded at
+    /// capture time, then the command-name rules. With `redetect`, the
+    /// recorded format is skipped in favour of the current format hints.
+    fn extract_targets(
+        &self,
+        conn: &duckdb::Connection,
+        invocation_ids: &[String],
+        format_override: Option<&str>,
+        redetect: bool,
+    ) -> Result<Vec<ExtractTarget>> {
+        if invocation_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let id_list = invocation_ids
+            .iter()
+            .map(|id| format!("'{}'", id.replace('\'', "''")))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id::VARCHAR, cmd, client_id, hostname, date::VARCHAR, format_hint
+             FROM invocations WHERE id IN ({})",
+            id_list
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, Option<String>>(5)?,
+            ))
+        })?;
 
-        // Get output storage refs for this invocation
+        let mut targets = Vec::new();
+        for row in rows {
+            let (id, cmd, client_id, hostname, date, format_hint) = row?;
+            let format = match format_override.map(str::to_string) {
+                Some(f) => f,
+                None if redetect => self.detect_format_for_command(&cmd)?,
+                None => match format_hint {
+                    Some(f) => f,
+                    None => self.detect_format(&cmd)?,
+                },
+            };
+            let date = date
+                .parse::<NaiveDate>()
+                .map_err(|e| Error::Storage(format!("Invalid date: {}", e)))?;
+            let uuid = Uuid::parse_str(&id)
+                .map_err(|e| Error::Storage(format!("Invalid invocation id: {}", e)))?;
+            targets.push(ExtractTarget {
+                id,
+                uuid,
+                client_id,
+                hostname,
+                date,
+                format,
+                outputs: Vec::new(),
+            });
+        }
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT invocation_id::VARCHAR, storage_type, storage_ref, stream, byte_length, content_hash,
+                    {} AS sequence
+             FROM outputs
+             WHERE invocation_id IN ({}) AND stream IN ('stdout', 'stderr', 'combined')
+             ORDER BY stream, sequence, id",
+            super::outputs::SEQUENCE_SQL,
+            id_list
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                super::OutputInfo {
+                    storage_type: row.get(1)?,
+                    storage_ref: row.get(2)?,
+                    stream: row.get(3)?,
+                    byte_length: row.get(4)?,
+                    content_hash: row.get(5)?,
+                    sequence: row.get(6)?,
+                },
+            ))
+        })?;
+        for row in rows {
+            let (id, output) = row?;
+            if let Some(target) = targets.iter_mut().find(|t| t.id == id) {
+                target.outputs.push(output);
+            }
+        }
+
+        Ok(targets)
+    }
+
+    /// Extract events for `targets` with duck_hunt in one statement.
+    ///
+    /// Returns the number of events extracted per target.
+    fn extract_with_duck_hunt(
+        &self,
+        conn: &duckdb::Connection,
+        targets: &[ExtractTarget],
+    ) -> Result<Vec<usize>> {
         // Note: DuckDB table functions don't support lateral joins with column refs,
-        // so we iterate in Rust and call read_duck_hunt_log with literal paths.
+        // so each output gets its own read_duck_hunt_log call with literal args.
         let data_dir = self.config.data_dir();
-        let mut stmt = conn.prepare(
-            "SELECT storage_ref FROM outputs
-             WHERE invocation_id = ? AND stream IN ('stdout', 'stderr', 'combined')",
-        )?;
-        let storage_refs: Vec<String> = stmt
-            .query_map(params![invocation_id], |row| row.get(0))?
-            .filter_map(|r| r.ok())
-            .collect();
+        let mut selects = Vec::new();
+        for target in targets {
+            let escaped_format = target.format.replace('\'', "''");
+            let escaped_client_id = target.client_id.replace('\'', "''");
+            let hostname_sql = target
+                .hostname
+                .as_ref()
+                .map(|h| format!("'{}'", h.replace('\'', "''")))
+                .unwrap_or_else(|| "NULL".to_string());
+
+            for output in &target.outputs {
+                // Resolve file:// refs to absolute paths, pass data: refs through
+                let resolved_ref = if let Some(suffix) = output.storage_ref.strip_prefix("file://") {
+                    data_dir.join(suffix).display().to_string()
+                } else {
+                    output.storage_ref.clone()
+                };
+
+                let sql = format!(
+                    r#"
+                    SELECT
+                        uuid() as id,
+                        '{invocation_id}'::UUID as invocation_id,
+                        '{client_id}' as client_id,
+                        {hostname} as hostname,
+                        dh.event_type,
+                        dh.severity,
+                        dh.ref_file,
+                        dh.ref_line::INTEGER,
+                        dh.ref_column::INTEGER,
+                        dh.message,
+                        dh.error_code,
+                        dh.test_name,
+                        dh.status,
+                        '{format}' as format_used,
+                        '{date}'::DATE as date,
+                        'parser' as origin
+                    FROM read_duck_hunt_log('{ref}', '{format}') dh
+                    WHERE dh.event_type IS NOT NULL OR dh.message IS NOT NULL
+                    "#,
+                    invocation_id = target.id,
+                    client_id = escaped_client_id,
+                    hostname = hostname_sql,
+                    format = escaped_format,
+                    date = target.date,
+                    ref = resolved_ref.replace('\'', "''"),
+                );
+                selects.push((output.storage_ref.as_str(), sql));
+            }
+        }
 
-        if storage_refs.is_empty() {
-            return Ok(0);
+        if selects.is_empty() {
+            return Ok(vec![0; targets.len()]);
         }
 
         // Create temp table for events
@@ -302,102 +849,127 @@ impl Store {
                 test_name VARCHAR,
                 status VARCHAR,
                 format_used VARCHAR,
-                date DATE
+                date DATE,
+                origin VARCHAR
             );
             "#,
         )?;
 
-        // Escape values for SQL
-        let escaped_format = format.replace("'", "''");
-        let escaped_client_id = client_id.replace("'", "''");
-        let hostname_sql = hostname
-            .as_ref()
-            .map(|h| format!("'{}'", h.replace("'", "''")))
-            .unwrap_or_else(|| "NULL".to_string());
-
-        // Process each output stream separately (DuckDB table functions need literal args)
-        for storage_ref in &storage_refs {
-            // Resolve file:// refs to absolute paths, pass data: refs through
-            let resolved_ref = if let Some(suffix) = storage_ref.strip_prefix("file://") {
-                data_dir.join(suffix).display().to_string()
-            } else {
-                storage_ref.clone()
-            };
-
-            let escaped_ref = resolved_ref.replace("'", "''");
-
-            let sql = format!(
-                r#"
-                INSERT INTO temp_events
-                SELECT
-                    uuid() as id,
-                    '{invocation_id}'::UUID as invocation_id,
-                    '{client_id}' as client_id,
-                    {hostname} as hostname,
-                    dh.event_type,
-                    dh.severity,
-                    dh.ref_file,
-                    dh.ref_line::INTEGER,
-                    dh.ref_column::INTEGER,
-                    dh.message,
-                    dh.error_code,
-                    dh.test_name,
-                    dh.status,
-                    '{format}' as format_used,
-                    '{date}'::DATE as date
-                FROM read_duck_hunt_log('{ref}', '{format}') dh
-                WHERE dh.event_type IS NOT NULL OR dh.message IS NOT NULL;
-                "#,
-                invocation_id = invocation_id,
-                client_id = escaped_client_id,
-                hostname = hostname_sql,
-                format = escaped_format,
-                date = date,
-                ref = escaped_ref,
-            );
-
-            if let Err(e) = conn.execute_batch(&sql) {
-                // duck_hunt might fail on some formats - log and continue to next stream
-                eprintln!("Warning: duck_hunt parsing failed for {}: {}", storage_ref, e);
+        let all = selects.iter().map(|(_, sql)| sql.as_str()).collect::<Vec<_>>().join(" UNION ALL ");
+        if conn.execute_batch(&format!("INSERT INTO temp_events {}", all)).is_err() {
+            // One bad output fails the whole statement; retry them one at a
+            // time so the rest still get parsed
+            for (storage_ref, sql) in &selects {
+                if let Err(e) = conn.execute_batch(&format!("INSERT INTO temp_events {}", sql)) {
+                    // duck_hunt might fail on some formats - log and continue to next stream
+                    tracing::warn!(output = %storage_ref, "duck_hunt parsing failed: {}", e);
+                }
             }
         }
 
-        // Count how many events were extracted
-        let count: i64 = conn.query_row("SELECT COUNT(*) FROM temp_events", [], |row| row.get(0))?;
-
-        if count == 0 {
-            conn.execute("DROP TABLE temp_events", [])?;
-            return Ok(0);
+        let mut counts = Vec::with_capacity(targets.len());
+        for target in targets {
+            let count: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM temp_events WHERE invocation_id = ?",
+                params![target.id],
+                |row| row.get(0),
+            )?;
+            counts.push(count as usize);
         }
 
         // Write to storage based on mode
         match self.config.storage_mode {
             StorageMode::Parquet => {
-                // Write to parquet file
-                let filename = format!("{}--{}.parquet", invocation_id, Uuid::now_v7());
-                let file_path = partition_dir.join(&filename);
-
-                let temp_path = atomic::temp_path(&file_path);
-                conn.execute(
-                    &format!(
-                        "COPY temp_events TO '{}' (FORMAT PARQUET, COMPRESSION ZSTD)",
-                        temp_path.display()
-                    ),
-                    [],
-                )?;
-                conn.execute("DROP TABLE temp_events", [])?;
-
-                // Rename temp to final (atomic on POSIX)
-                atomic::rename_into_place(&temp_path, &file_path)?;
+                // One file per invocation: deletes find them by filename prefix
+                for (target, &count) in targets.iter().zip(&counts) {
+                    if count == 0 {
+                        continue;
+                    }
+                    let partition_dir = self.config.events_dir(&target.date);
+                    fs::create_dir_all(&partition_dir)?;
+                    let filename = format!("{}--{}.parquet", target.id, Uuid::now_v7());
+                    let file_path = partition_dir.join(&filename);
+
+                    let temp_path = atomic::temp_path(&file_path);
+                    conn.execute(
+                        &format!(
+                            "COPY (SELECT * FROM temp_events WHERE invocation_id = '{}') TO '{}' (FORMAT PARQUET, COMPRESSION ZSTD)",
+                            target.id,
+                            temp_path.display()
+                        ),
+                        [],
+                    )?;
+
+                    // Rename temp to final (atomic on POSIX)
+                    atomic::rename_into_place(&temp_path, &file_path)?;
+                }
             }
             StorageMode::DuckDB => {
                 // Insert directly into local.events
                 conn.execute_batch("INSERT INTO local.events SELECT * FROM temp_events")?;
-                conn.execute("DROP TABLE temp_events", [])?;
             }
         }
+        conn.execute("DROP TABLE temp_events", [])?;
 
-        Ok(count as usize)
+        Ok(counts)
+    }
+
+    /// Extract events for `targets` with the Rust parsers, reading and
+    /// parsing outputs on a pool of threads.
+    ///
+    /// Outputs are read straight from disk (no DuckDB connection is shared
+    /// between threads). Returns the number of events extracted per target.
+    fn extract_with_parsers_parallel(
+        &self,
+        targets: &[ExtractTarget],
+        parsers: &[Box<dyn EventParser>],
+    ) -> Result<Vec<usize>> {
+        let threads = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(targets.len())
+            .max(1);
+        let per_thread = targets.len().div_ceil(threads).max(1);
+
+        let parsed: Vec<Vec<EventRecord>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = targets
+                .chunks(per_thread)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|target| {
+                                let mut records = Vec::new();
+                                for output in &target.outputs {
+                                    #[allow(deprecated)]
+                                    match output.read_content(&self.config) {
+                                        Ok(content) => records.extend(target.parse(parsers, &content)),
+                                        Err(e) => tracing::warn!(
+                                            output = %output.storage_ref,
+                                            "could not read output: {}",
+                                            e
+                                        ),
+                                    }
+                                }
+                                records
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("extraction thread panicked"))
+                .collect()
+        });
+
+        // Written per invocation: parquet deletes find files by invocation id
+        let mut counts = Vec::with_capacity(parsed.len());
+        for records in &parsed {
+            self.write_events(records)?;
+            counts.push(records.len());
+        }
+        Ok(counts)
     }
 
     /// Write event records to the store.
@@ -449,7 +1021,8 @@ impl Store {
                     test_name VARCHAR,
                     status VARCHAR,
                     format_used VARCHAR,
-                    date DATE
+                    date DATE,
+                    origin VARCHAR
                 );
                 "#,
             )?;
@@ -459,7 +1032,7 @@ impl Store {
                 conn.execute(
                     r#"
                     INSERT INTO temp_events VALUES (
-                        ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
+                        ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
                     )
                     "#,
                     params![
@@ -478,13 +1051,17 @@ impl Store {
                         record.status,
                         record.format_used,
                         date.to_string(),
+                        record.origin,
                     ],
                 )?;
             }
 
-            // Write to parquet
+            // Write to parquet. Manual events get their own prefix, so
+            // deleting an invocation's (parser) events by prefix keeps them.
+            let prefix = if date_records[0].origin == EVENT_ORIGIN_MANUAL { "manual--" } else { "" };
             let filename = format!(
-                "{}--{}.parquet",
+                "{}{}--{}.parquet",
+                prefix,
                 date_records[0].invocation_id,
                 Uuid::now_v7()
             );
@@ -514,7 +1091,7 @@ impl Store {
             conn.execute(
                 r#"
                 INSERT INTO local.events VALUES (
-                    ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
+                    ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
                 )
                 "#,
                 params![
@@ -533,6 +1110,7 @@ impl Store {
                     record.status,
                     record.format_used,
                     record.date.to_string(),
+                    record.origin,
                 ],
             )?;
         }
@@ -584,6 +1162,10 @@ impl Store {
             conditions.push(format!("e.date <= '{}'", date_to));
         }
 
+        if !filters.include_corrected {
+            conditions.push(super::corrections::UNCORRECTED_EVENT_SQL.to_string());
+        }
+
         let where_clause = if conditions.is_empty() {
             String::new()
         } else {
@@ -596,8 +1178,8 @@ impl Store {
             .unwrap_or_default();
 
         // If filtering by command pattern, we need to join with invocations
-        let sql = if filters.cmd_pattern.is_some() {
-            let cmd_pattern = filters.cmd_pattern.as_ref().unwrap().replace("'", "''");
+        let sql = if let Some(cmd_pattern) = &filters.cmd_pattern {
+            let cmd_pattern = cmd_pattern.replace("'", "''");
             let where_prefix = if conditions.is_empty() {
                 "WHERE".to_string()
             } else {
@@ -614,7 +1196,8 @@ impl Store {
                     e.ref_line,
                     e.error_code,
                     e.test_name,
-                    e.status
+                    e.status,
+                    coalesce(e.origin, 'parser')
                 FROM events e
                 JOIN invocations i ON e.invocation_id = i.id
                 {}
@@ -638,7 +1221,8 @@ impl Store {
                     e.ref_line,
                     e.error_code,
                     e.test_name,
-                    e.status
+                    e.status,
+                    coalesce(e.origin, 'parser')
                 FROM events e
                 {}
                 ORDER BY e.date DESC
@@ -669,6 +1253,7 @@ impl Store {
                 error_code: row.get(6)?,
                 test_name: row.get(7)?,
                 status: row.get(8)?,
+                origin: row.get(9)?,
             })
         });
 
@@ -721,13 +1306,17 @@ impl Store {
             conditions.push(format!("client_id = '{}'", client.replace("'", "''")));
         }
 
+        if !filters.include_corrected {
+            conditions.push(super::corrections::UNCORRECTED_EVENT_SQL.to_string());
+        }
+
         let where_clause = if conditions.is_empty() {
             String::new()
         } else {
             format!("WHERE {}", conditions.join(" AND "))
         };
 
-        let sql = format!("SELECT COUNT(*) FROM events {}", where_clause);
+        let sql = format!("SELECT COUNT(*) FROM events e {}", where_clause);
 
         let result: std::result::Result<i64, _> = conn.query_row(&sql, [], |row| row.get(0));
 
@@ -745,10 +1334,12 @@ impl Store {
 
     /// Delete events for an invocation (for re-extraction).
     ///
+    /// Manual events (see [`Store::add_manual_event`]) are kept.
     /// Behavior depends on storage mode:
     /// - Parquet: Deletes parquet files containing the events
     /// - DuckDB: Deletes rows from local.events
     pub fn delete_events_for_invocation(&self, invocation_id: &str) -> Result<usize> {
+        let _sync = self.sync_guard();
         match self.config.storage_mode {
             StorageMode::Parquet => self.delete_events_parquet(invocation_id),
             StorageMode::DuckDB => self.delete_events_duckdb(invocation_id),
@@ -811,10 +1402,10 @@ impl Store {
     fn delete_events_duckdb(&self, invocation_id: &str) -> Result<usize> {
         let conn = self.connection()?;
 
-        // Count events before deletion
+        // Count events before deletion (manual events are kept)
         let count: i64 = conn
             .query_row(
-                "SELECT COUNT(*) FROM local.events WHERE invocation_id = ?",
+                "SELECT COUNT(*) FROM local.events WHERE invocation_id = ? AND origin IS DISTINCT FROM 'manual'",
                 params![invocation_id],
                 |row| row.get(0),
             )
@@ -822,7 +1413,7 @@ impl Store {
 
         if count > 0 {
             conn.execute(
-                "DELETE FROM local.events WHERE invocation_id = ?",
+                "DELETE FROM local.events WHERE invocation_id = ? AND origin IS DISTINCT FROM 'manual'",
                 params![invocation_id],
             )?;
         }
@@ -851,7 +1442,7 @@ impl Store {
 
         let sql = format!(
             r#"
-            SELECT i.id::VARCHAR, i.cmd, i.exit_code, i.timestamp::VARCHAR, i.duration_ms
+            SELECT i.id::VARCHAR, i.cmd, i.exit_code, timezone('UTC', i.timestamp)::VARCHAR, i.duration_ms, i.cwd
             FROM invocations i
             WHERE EXISTS (SELECT 1 FROM outputs o WHERE o.invocation_id = i.id)
               AND NOT EXISTS (SELECT 1 FROM events e WHERE e.invocation_id = i.id)
@@ -880,12 +1471,61 @@ impl Store {
                 exit_code: row.get(2)?,
                 timestamp: row.get(3)?,
                 duration_ms: row.get(4)?,
+                cwd: row.get(5)?,
             })
         })?;
 
         let results: Vec<_> = rows.filter_map(|r| r.ok()).collect();
         Ok(results)
     }
+
+    /// Get invocations with outputs whose command matches `cmd_like`, a SQL
+    /// LIKE pattern (e.g. `%/mytool/%`), whether or not they have events.
+    ///
+    /// Finds what [`Store::reparse_events`] should redo after a format hint
+    /// is added. Without `since` all of history is searched; without `limit`
+    /// every match is returned. Newest first.
+    pub fn invocations_matching(
+        &self,
+        cmd_like: &str,
+        since: Option<NaiveDate>,
+        limit: Option<usize>,
+    ) -> Result<Vec<super::InvocationSummary>> {
+        let conn = self.connection()?;
+
+        let mut sql = String::from(
+            r#"
+            SELECT i.id::VARCHAR, i.cmd, i.exit_code, timezone('UTC', i.timestamp)::VARCHAR, i.duration_ms, i.cwd
+            FROM invocations i
+            WHERE EXISTS (SELECT 1 FROM outputs o WHERE o.invocation_id = i.id)
+              AND i.cmd LIKE ?
+            "#,
+        );
+        if let Some(since) = since {
+            sql.push_str(&format!(" AND i.date >= '{}'", since));
+        }
+        sql.push_str(" ORDER BY i.timestamp DESC");
+        if let Some(limit) = limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        let mut stmt = match conn.prepare(&sql) {
+            Ok(stmt) => stmt,
+            Err(e) if e.to_string().contains("No files found") => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let rows = stmt.query_map(params![cmd_like], |row| {
+            Ok(super::InvocationSummary {
+                id: row.get(0)?,
+                cmd: row.get(1)?,
+                exit_code: row.get(2)?,
+                timestamp: row.get(3)?,
+                duration_ms: row.get(4)?,
+                cwd: row.get(5)?,
+            })
+        })?;
+        Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+    }
 }
 
 #[cfg(test)]
@@ -993,6 +1633,149 @@ format = "auto"
         assert!(events_dir.to_string_lossy().contains("events"));
     }
 
+    #[test]
+    fn test_detect_output_format() {
+        let (_tmp, store) = setup_store();
+        let outputs = vec![(
+            "stdout".to_string(),
+            b"main.c:3:5: error: expected ';' before 'return'\n".to_vec(),
+        )];
+        // No hint for `make`, so the output decides
+        assert_eq!(
+            store.detect_output_format("make", &outputs).unwrap().as_deref(),
+            Some("gcc")
+        );
+        let plain = vec![("stdout".to_string(), b"hello\n".to_vec())];
+        assert_eq!(store.detect_output_format("echo hello", &plain).unwrap(), None);
+    }
+
+    #[test]
+    fn test_extract_events_batch() {
+        use crate::schema::InvocationRecord;
+        use crate::store::InvocationBatch;
+
+        let (_tmp, store) = setup_store();
+        let mut ids = Vec::new();
+        for (cmd, output) in [
+            ("gcc -c main.c", &b"main.c:3:5: error: expected ';' before 'return'\n"[..]),
+            ("echo hello", &b"hello\n"[..]),
+        ] {
+            let inv = InvocationRecord::new("s", cmd, "/p", 1, "test@client");
+            ids.push(inv.id.to_string());
+            store
+                .write_batch(&InvocationBatch::new(inv).with_output("stdout", output.to_vec()))
+                .unwrap();
+        }
+        ids.push(Uuid::now_v7().to_string()); // missing invocations are skipped
+
+        let recorder = std::sync::Arc::new(crate::progress::Recorder::default());
+        let token = crate::CancelToken::new();
+        let store = store.with_progress(recorder.clone()).with_cancel(token.clone());
+
+        let summary = store.extract_events_batch(&ids, Some("gcc")).unwrap();
+        assert_eq!(summary.total, 3);
+        assert_eq!(summary.processed, 3);
+        assert_eq!(summary.with_events, 1);
+        assert_eq!(summary.events, 1);
+        assert_eq!(recorder.calls(), vec!["start extract events Some(3)", "advance 3", "finish"]);
+
+        // A cancelled extraction still finishes its phase
+        token.cancel();
+        assert!(matches!(store.extract_events_batch(&ids, Some("gcc")), Err(crate::Error::Cancelled)));
+        assert_eq!(recorder.calls()[3..], ["start extract events Some(3)", "finish"]);
+        assert!(recorder.balanced());
+    }
+
+    #[test]
+    fn test_reparse_events_after_new_hint() {
+        use crate::schema::InvocationRecord;
+        use crate::store::InvocationBatch;
+
+        let (_tmp, store) = setup_store();
+        let mut inv = InvocationRecord::new("s", "/opt/mytool/build main.c", "/p", 1, "test@client");
+        inv.format_hint = Some("text".to_string());
+        let id = inv.id.to_string();
+        store
+            .write_batch(
+                &InvocationBatch::new(inv)
+                    .with_output("stdout", b"main.c:3:5: error: expected ';' before 'return'\n".to_vec()),
+            )
+            .unwrap();
+        let other = InvocationRecord::new("s", "make", "/p", 0, "test@client");
+        store
+            .write_batch(&InvocationBatch::new(other).with_output("stdout", b"ok\n".to_vec()))
+            .unwrap();
+
+        let matching = store.invocations_matching("%/mytool/%", None, None).unwrap();
+        assert_eq!(matching.iter().map(|i| i.id.as_str()).collect::<Vec<_>>(), [id.as_str()]);
+
+        let mut hints = store.load_format_hints().unwrap();
+        hints.add(crate::FormatHint::new("*/mytool/*", "gcc"));
+        store.save_format_hints(&hints).unwrap();
+
+        let ids = vec![id.clone()];
+        let summary = store.reparse_events(&ids, None).unwrap();
+        assert_eq!(summary.events, 1);
+        // Reparsing again replaces rather than duplicates
+        store.reparse_events(&ids, None).unwrap();
+        let filters = EventFilters { invocation_id: Some(id), ..Default::default() };
+        assert_eq!(store.event_count(&filters).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_match_lines_samples_unmatched_errors() {
+        let output = "compiling\nmain.c:3:5: error: expected ';'\nld: fatal error: cannot open crt1.o\nok\n";
+        let events = vec![ParsedEvent {
+            ref_file: Some("main.c".to_string()),
+            ref_line: Some(3),
+            message: Some("expected ';'".to_string()),
+            ..Default::default()
+        }];
+        let mut samples = Vec::new();
+        assert_eq!(match_lines(output, &events, &mut samples), (4, 1));
+        assert_eq!(samples, ["ld: fatal error: cannot open crt1.o"]);
+
+        let mut samples = Vec::new();
+        assert_eq!(match_lines(output, &[], &mut samples), (4, 0));
+        assert_eq!(samples.len(), 2);
+    }
+
+    #[test]
+    fn test_explain_extraction() {
+        use crate::schema::InvocationRecord;
+        use crate::store::InvocationBatch;
+
+        let (_tmp, store) = setup_store();
+        let inv = InvocationRecord::new("s", "/opt/mytool/build main.c", "/p", 1, "test@client");
+        let id = inv.id.to_string();
+        store
+            .write_batch(&InvocationBatch::new(inv).with_output(
+                "stdout",
+                b"main.c:3:5: error: expected ';' before 'return'\nmytool: FAILED to link\n".to_vec(),
+            ))
+            .unwrap();
+
+        let mut hints = store.load_format_hints().unwrap();
+        hints.add(crate::FormatHint::new("*/mytool/*", "gcc"));
+        store.save_format_hints(&hints).unwrap();
+
+        let report = store.explain_extraction(&id, None).unwrap();
+        assert_eq!(report.format, "gcc");
+        assert_eq!(
+            report.reason,
+            FormatReason::Hint { pattern: "*/mytool/*".to_string(), priority: crate::format_hints::DEFAULT_PRIORITY }
+        );
+        assert_eq!((report.lines, report.matched_lines, report.events), (2, 1, 1));
+        assert_eq!(report.unmatched_samples, ["mytool: FAILED to link"]);
+        // Nothing is stored
+        let filters = EventFilters { invocation_id: Some(id.clone()), ..Default::default() };
+        assert_eq!(store.event_count(&filters).unwrap(), 0);
+
+        let report = store.explain_extraction(&id, Some("pytest")).unwrap();
+        assert_eq!(report.reason, FormatReason::Override);
+        assert!(store.explain_extraction(&Uuid::now_v7().to_string(), None).is_err());
+    }
+
     #[test]
     fn test_query_events_empty() {
         let (_tmp, store) = setup_store();
@@ -0,0 +1,266 @@
+//! Export - write history to standalone parquet files, optionally anonymized.
+//!
+//! Anonymized exports are meant for sharing reproduction datasets (e.g. for
+//! BIRD performance bugs). Identifying values are replaced with consistent
+//! pseudonyms from [`Anonymizer`], while row counts, IDs, timings, exit
+//! codes and sizes are kept so the data still behaves like the original.
+
+use std::fs;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use duckdb::{params, Connection};
+
+use super::Store;
+use crate::anonymize::Anonymizer;
+use crate::Result;
+
+/// Tables written by an export, in processing order.
+const EXPORT_TABLES: &[&str] = &["sessions", "invocations", "outputs", "events"];
+
+/// How a column's values are anonymized.
+#[derive(Clone, Copy)]
+enum Pseudonym {
+    Client,
+    Host,
+    Path,
+    Command,
+    Tag,
+    Blob,
+    Test,
+}
+
+/// Columns rewritten with pseudonyms when anonymizing.
+const ANONYMIZED_COLUMNS: &[(&str, &str, Pseudonym)] = &[
+    ("sessions", "client_id", Pseudonym::Client),
+    ("sessions", "cwd", Pseudonym::Path),
+    ("invocations", "client_id", Pseudonym::Client),
+    ("invocations", "hostname", Pseudonym::Host),
+    ("invocations", "cwd", Pseudonym::Path),
+    ("invocations", "cmd", Pseudonym::Command),
+    ("invocations", "tag", Pseudonym::Tag),
+    ("outputs", "content_hash", Pseudonym::Blob),
+    ("events", "client_id", Pseudonym::Client),
+    ("events", "hostname", Pseudonym::Host),
+    ("events", "ref_file", Pseudonym::Path),
+    ("events", "test_name", Pseudonym::Test),
+];
+
+/// Free-form columns cleared when anonymizing (too varied to pseudonymize).
+const CLEARED_COLUMNS: &[(&str, &str)] = &[
+    ("invocations", "metadata"),
+    ("outputs", "storage_ref"),
+    ("events", "message"),
+];
+
+/// Options for export operation.
+#[derive(Debug, Default)]
+pub struct ExportOptions {
+    /// Replace identifying values with consistent pseudonyms.
+    pub anonymize: bool,
+    /// Only export data on or after this date.
+    pub since: Option<NaiveDate>,
+}
+
+/// Statistics from export operation.
+#[derive(Debug, Default)]
+pub struct ExportStats {
+    pub sessions: usize,
+    pub invocations: usize,
+    pub outputs: usize,
+    pub events: usize,
+}
+
+impl std::fmt::Display for ExportStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} sessions, {} invocations, {} outputs, {} events",
+            self.sessions, self.invocations, self.outputs, self.events
+        )
+    }
+}
+
+impl Store {
+    /// Export sessions, invocations, outputs and events to `<dir>/<table>.parquet`.
+    ///
+    /// Output content is never exported, only output metadata. Tables with
+    /// no data are skipped.
+    pub fn export(&self, dir: &Path, opts: &ExportOptions) -> Result<ExportStats> {
+        fs::create_dir_all(dir)?;
+        let conn = self.connection()?;
+        let mut stats = ExportStats::default();
+
+        let mut staged = Vec::new();
+        for table in EXPORT_TABLES {
+            let Some(count) = stage_table(&conn, table, opts.since)? else {
+                continue;
+            };
+            match *table {
+                "sessions" => stats.sessions = count,
+                "invocations" => stats.invocations = count,
+                "outputs" => stats.outputs = count,
+                _ => stats.events = count,
+            }
+            staged.push(*table);
+        }
+
+        if opts.anonymize {
+            let mut anon = Anonymizer::new();
+            for (table, column, kind) in ANONYMIZED_COLUMNS {
+                if staged.contains(table) {
+                    anonymize_column(&conn, table, column, *kind, &mut anon)?;
+                }
+            }
+            for (table, column) in CLEARED_COLUMNS {
+                if staged.contains(table) {
+                    conn.execute(
+                        &format!("UPDATE export_{} SET {} = NULL", table, column),
+                        [],
+                    )?;
+                }
+            }
+        }
+
+        for table in staged {
+            let path = dir.join(format!("{}.parquet", table));
+            conn.execute(
+                &format!(
+                    "COPY export_{} TO '{}' (FORMAT PARQUET, COMPRESSION ZSTD)",
+                    table,
+                    path.display().to_string().replace('\'', "''")
+                ),
+                [],
+            )?;
+            conn.execute(&format!("DROP TABLE export_{}", table), [])?;
+        }
+
+        Ok(stats)
+    }
+}
+
+/// Copy a `main` view into temp table `export_<table>`.
+///
+/// Returns the row count, or `None` if the table has no data files.
+fn stage_table(conn: &Connection, table: &str, since: Option<NaiveDate>) -> Result<Option<usize>> {
+    let since_sql = since
+        .map(|d| format!("WHERE date >= '{}'", d))
+        .unwrap_or_default();
+    let order_sql = if table == "invocations" { "ORDER BY timestamp" } else { "" };
+
+    let sql = format!(
+        "CREATE OR REPLACE TEMP TABLE export_{table} AS \
+         SELECT * EXCLUDE (_source) FROM main.{table} {since} {order}",
+        table = table,
+        since = since_sql,
+        order = order_sql,
+    );
+    match conn.execute(&sql, []) {
+        Ok(_) => {}
+        Err(e) if e.to_string().contains("No files found") => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let count: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM export_{}", table), [], |row| {
+        row.get(0)
+    })?;
+    Ok(Some(count as usize))
+}
+
+/// Replace every distinct value of `column` with its pseudonym.
+fn anonymize_column(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    kind: Pseudonym,
+    anon: &mut Anonymizer,
+) -> Result<()> {
+    // Sorted so pseudonym numbering is stable for a given dataset
+    let mut stmt = conn.prepare(&format!(
+        "SELECT DISTINCT {col} FROM export_{table} WHERE {col} IS NOT NULL ORDER BY {col}",
+        col = column,
+        table = table,
+    ))?;
+    let values: Vec<String> = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<std::result::Result<_, _>>()?;
+
+    conn.execute_batch("CREATE OR REPLACE TEMP TABLE export_map (value VARCHAR, pseudonym VARCHAR)")?;
+    let mut insert = conn.prepare("INSERT INTO export_map VALUES (?, ?)")?;
+    for value in values {
+        let pseudonym = match kind {
+            Pseudonym::Client => anon.client(&value),
+            Pseudonym::Host => anon.host(&value),
+            Pseudonym::Path => anon.path(&value),
+            Pseudonym::Command => anon.command(&value),
+            Pseudonym::Tag => anon.token("tag", &value),
+            Pseudonym::Blob => anon.token("blob", &value),
+            Pseudonym::Test => anon.token("test", &value),
+        };
+        insert.execute(params![value, pseudonym])?;
+    }
+
+    conn.execute(
+        &format!(
+            "UPDATE export_{table} SET {col} = m.pseudonym \
+             FROM export_map m WHERE export_{table}.{col} = m.value",
+            table = table,
+            col = column,
+        ),
+        [],
+    )?;
+    conn.execute("DROP TABLE export_map", [])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_export_anonymizes_invocations() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        for cmd in ["make deploy-acme", "make deploy-acme", "cat /home/alice/notes"] {
+            let inv = InvocationRecord::new("test-session", cmd, "/home/alice/acme", 0, "alice@laptop");
+            store.write_invocation(&inv).unwrap();
+        }
+
+        let out = tmp.path().join("export");
+        let stats = store
+            .export(&out, &ExportOptions { anonymize: true, since: None })
+            .unwrap();
+        assert_eq!(stats.invocations, 3);
+
+        let conn = Connection::open_in_memory().unwrap();
+        let path = out.join("invocations.parquet");
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT cmd, cwd, client_id FROM '{}' ORDER BY cmd",
+                path.display()
+            ))
+            .unwrap();
+        let rows: Vec<(String, String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+
+        assert_eq!(rows.len(), 3);
+        for (cmd, cwd, client) in &rows {
+            assert!(!cmd.contains("acme") && !cmd.contains("alice"), "{}", cmd);
+            assert!(!cwd.contains("alice"), "{}", cwd);
+            assert!(!client.contains("alice") && !client.contains("laptop"), "{}", client);
+        }
+        // Repeated commands keep the same pseudonym
+        assert_eq!(rows[1].0, rows[2].0);
+        assert!(rows[1].0.starts_with("make "));
+    }
+}
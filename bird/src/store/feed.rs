@@ -0,0 +1,207 @@
+//! Teammates' recent commands from shared remotes (`shq feed`).
+//!
+//! Remote records went through the pusher's sanitize policy, so filters on
+//! this machine's project path and client are put through the same policy
+//! before they're compared. Hashed fields only match records hashed with
+//! this client's key for the remote (its own, or a teammate's sharing it).
+
+use chrono::NaiveDate;
+
+use super::remote::{sanitize_hash, sanitize_key};
+use super::Store;
+use crate::config::SanitizePolicy;
+use crate::{Error, RemoteConfig, Result};
+
+/// What `shq feed` shows.
+#[derive(Debug, Clone, Default)]
+pub struct FeedOptions {
+    /// Only this remote (default: every auto-attach remote).
+    pub remote: Option<String>,
+    /// Only commands run under this directory (as this machine knows it).
+    pub project_path: Option<String>,
+    /// Also match commands whose git `origin` is this URL, wherever the
+    /// repository was checked out.
+    pub vcs_remote: Option<String>,
+    /// Only commands on or after this date.
+    pub since: Option<NaiveDate>,
+    /// Include this client's own pushed commands.
+    pub include_self: bool,
+    /// Most entries to return (0 = no limit).
+    pub limit: usize,
+}
+
+/// A command from the feed.
+#[derive(Debug, Clone)]
+pub struct FeedEntry {
+    pub id: String,
+    /// Remote the command came from.
+    pub remote: String,
+    pub client_id: String,
+    pub hostname: Option<String>,
+    pub timestamp: String,
+    pub cmd: String,
+    pub cwd: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<i64>,
+}
+
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// SQL for `value` as a record pushed under `policy` with hash key `key`
+/// would hold it in the column for `field` (`cwd` or `client`).
+fn sanitized_value(policy: &SanitizePolicy, key: Option<&[u8; 32]>, field: &str, value: &str) -> String {
+    let mut value = value.to_string();
+    if field == "cwd" && policy.relative_home {
+        let home = std::env::var("HOME").unwrap_or_default();
+        let home = home.trim_end_matches('/');
+        if !home.is_empty() {
+            if value == home {
+                value = "~".to_string();
+            } else if let Some(rest) = value.strip_prefix(&format!("{}/", home)) {
+                value = format!("~/{}", rest);
+            }
+        }
+    }
+    match key {
+        Some(key) if policy.hashes(field) => quote(&sanitize_hash(key, &value)),
+        _ => quote(&value),
+    }
+}
+
+/// WHERE conditions selecting `opts`' commands from one remote.
+///
+/// `key` is this client's hash key for the remote, needed if its policy
+/// hashes fields.
+fn remote_conditions(remote: &RemoteConfig, client_id: &str, key: Option<&[u8; 32]>, opts: &FeedOptions) -> String {
+    let policy = &remote.sanitize;
+    let mut conds = vec![format!("_source = {}", quote(&remote.name))];
+
+    if !opts.include_self {
+        conds.push(format!("client_id IS DISTINCT FROM {}", sanitized_value(policy, key, "client", client_id)));
+    }
+
+    let mut project = Vec::new();
+    if let Some(path) = &opts.project_path {
+        let path = sanitized_value(policy, key, "cwd", path.trim_end_matches('/'));
+        if policy.hashes("cwd") {
+            // Hashes only match exactly
+            project.push(format!("cwd = {}", path));
+        } else {
+            project.push(format!("(cwd = {p} OR starts_with(cwd, {p} || '/'))", p = path));
+        }
+    }
+    if let Some(url) = &opts.vcs_remote {
+        project.push(format!("json_extract_string(metadata['vcs'], '$.remote') = {}", quote(url)));
+    }
+    if !project.is_empty() {
+        conds.push(format!("({})", project.join(" OR ")));
+    }
+
+    format!("({})", conds.join(" AND "))
+}
+
+impl Store {
+    /// Recent commands from teammates on shared remotes, newest first.
+    pub fn feed(&self, opts: &FeedOptions) -> Result<Vec<FeedEntry>> {
+        let remotes: Vec<&RemoteConfig> = match &opts.remote {
+            Some(name) => vec![self
+                .config
+                .get_remote(name)
+                .ok_or_else(|| Error::Config(format!("Remote '{}' not found", name)))?],
+            None => self.config.auto_attach_remotes(),
+        };
+        if remotes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let conn = self.connection()?;
+        self.ensure_remotes_attached(&conn, &remotes)?;
+
+        let mut by_remote = Vec::new();
+        for remote in &remotes {
+            let key = if remote.sanitize.hash_fields.is_empty() {
+                None
+            } else {
+                Some(sanitize_key(&self.config, &remote.name)?)
+            };
+            by_remote.push(remote_conditions(remote, &self.config.client_id, key.as_ref(), opts));
+        }
+        let since = opts
+            .since
+            .map(|d| format!("AND timestamp >= '{}'", d))
+            .unwrap_or_default();
+        let limit = if opts.limit > 0 {
+            format!("LIMIT {}", opts.limit)
+        } else {
+            String::new()
+        };
+
+        // Pulled and attached copies of the same command are one entry
+        let sql = format!(
+            "SELECT id::VARCHAR, _source, client_id, hostname, timezone('UTC', timestamp)::VARCHAR, cmd, cwd,
+                    exit_code, duration_ms
+             FROM (
+                 SELECT DISTINCT ON (id) * FROM unified.invocations
+                 WHERE ({}) {}
+             )
+             ORDER BY timestamp DESC
+             {}",
+            by_remote.join(" OR "),
+            since,
+            limit
+        );
+
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map([], |row| {
+            Ok(FeedEntry {
+                id: row.get(0)?,
+                remote: row.get(1)?,
+                client_id: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                hostname: row.get(3)?,
+                timestamp: row.get(4)?,
+                cmd: row.get(5)?,
+                cwd: row.get(6)?,
+                exit_code: row.get(7)?,
+                duration_ms: row.get(8)?,
+            })
+        })?;
+        Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters_follow_sanitize_policy() {
+        let Ok(home) = std::env::var("HOME") else { return };
+        let project = format!("{}/src/app", home.trim_end_matches('/'));
+
+        let mut remote = RemoteConfig::new("team", crate::RemoteType::File, "/tmp/team.duckdb");
+        let opts = FeedOptions {
+            project_path: Some(project.clone()),
+            ..Default::default()
+        };
+
+        let key = [7u8; 32];
+        let plain = remote_conditions(&remote, "me@laptop", None, &opts);
+        assert!(plain.contains(&format!("starts_with(cwd, '{}' || '/')", project)));
+        assert!(plain.contains("client_id IS DISTINCT FROM 'me@laptop'"));
+
+        remote.sanitize = SanitizePolicy {
+            relative_home: true,
+            hash_fields: vec!["client".to_string()],
+            ..Default::default()
+        };
+        let sanitized = remote_conditions(&remote, "me@laptop", Some(&key), &opts);
+        assert!(sanitized.contains("starts_with(cwd, '~/src/app' || '/')"));
+        assert!(sanitized.contains(&format!("client_id IS DISTINCT FROM '{}'", sanitize_hash(&key, "me@laptop"))));
+
+        remote.sanitize.hash_fields = vec!["cwd".to_string()];
+        let hashed = remote_conditions(&remote, "me@laptop", Some(&key), &opts);
+        assert!(hashed.contains(&format!("cwd = '{}'", sanitize_hash(&key, "~/src/app"))));
+    }
+}
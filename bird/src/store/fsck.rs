@@ -0,0 +1,151 @@
+//! Consistency checks over stored output content (`shq fsck`).
+
+use std::collections::HashSet;
+
+use super::outputs::SEQUENCE_SQL;
+use super::{OutputInfo, Store};
+use crate::{Error, Result};
+
+/// One output whose content is missing, unreadable or corrupted.
+#[derive(Debug, Clone)]
+pub struct FsckIssue {
+    pub invocation_id: String,
+    pub stream: String,
+    pub storage_ref: String,
+    pub problem: String,
+}
+
+/// Results of a consistency check.
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    /// Distinct stored contents checked.
+    pub checked: usize,
+    pub issues: Vec<FsckIssue>,
+}
+
+impl Store {
+    /// Check that every output's content can be found. With
+    /// `verify_content`, also read it and check it against its hash.
+    pub fn fsck(&self, verify_content: bool) -> Result<FsckReport> {
+        self.with_connection(|conn| {
+            let sql = format!(
+                "SELECT invocation_id::VARCHAR, storage_type, storage_ref, stream, byte_length, content_hash, {} \
+                 FROM main.outputs ORDER BY storage_ref",
+                SEQUENCE_SQL
+            );
+            let mut stmt = match conn.prepare(&sql) {
+                Ok(stmt) => stmt,
+                Err(e) if e.to_string().contains("No files found") => return Ok(FsckReport::default()),
+                Err(e) => return Err(e.into()),
+            };
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    OutputInfo {
+                        storage_type: row.get(1)?,
+                        storage_ref: row.get(2)?,
+                        stream: row.get(3)?,
+                        byte_length: row.get(4)?,
+                        content_hash: row.get(5)?,
+                        sequence: row.get(6)?,
+                    },
+                ))
+            })?;
+            let mut outputs = Vec::new();
+            for row in rows {
+                outputs.push(row?);
+            }
+
+            let mut report = FsckReport::default();
+            // Deduplicated blobs are shared; check each stored content once
+            let mut seen = HashSet::new();
+            let progress = self.progress_phase("check outputs", Some(outputs.len() as u64));
+            for (invocation_id, output) in outputs {
+                self.checkpoint()?;
+                progress.advance(1);
+                if !seen.insert((output.storage_ref.clone(), output.content_hash.clone())) {
+                    continue;
+                }
+                report.checked += 1;
+
+                let problem = if verify_content {
+                    match self.read_output_content(&output).and_then(|content| output.verify(&content)) {
+                        Ok(()) => None,
+                        Err(Error::ContentCorrupted { actual, .. }) => Some(format!("corrupted (hash {})", actual)),
+                        Err(e) => Some(format!("unreadable: {}", e)),
+                    }
+                } else {
+                    self.missing_blob(&output).then(|| "missing blob file".to_string())
+                };
+
+                if let Some(problem) = problem {
+                    report.issues.push(FsckIssue {
+                        invocation_id,
+                        stream: output.stream.clone(),
+                        storage_ref: output.storage_ref.clone(),
+                        problem,
+                    });
+                }
+            }
+            drop(progress);
+            Ok(report)
+        })
+    }
+
+    /// Whether a `file://` output's blob is neither local nor in a
+    /// filesystem cold tier. Other refs can't be checked without reading them.
+    fn missing_blob(&self, output: &OutputInfo) -> bool {
+        let Some(rel_path) = output.storage_ref.strip_prefix("file://") else {
+            return false;
+        };
+        let local = self.config.data_dir().join(rel_path);
+        if local.exists() {
+            return false;
+        }
+        match (self.config.blobs.cold_tier_dir(), local.strip_prefix(self.config.blobs_dir())) {
+            (Some(cold_dir), Ok(relative)) => !cold_dir.join(relative).exists(),
+            // Object store tiers are assumed to hold it
+            _ => self.config.blobs.cold_tier.is_none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fsck_finds_corrupted_blob() {
+        let tmp = TempDir::new().unwrap();
+        let mut config = Config::with_root(tmp.path());
+        config.verify_blob_hashes = true;
+        initialize(&config).unwrap();
+        let store = Store::open(config.clone()).unwrap();
+
+        let inv = InvocationRecord::new("test-session", "cat big.log", "/home/user", 0, "user@laptop");
+        store.write_invocation(&inv).unwrap();
+        let content: Vec<u8> = (0..5000).map(|i| (i % 256) as u8).collect();
+        store.store_output(inv.id, "stdout", &content, inv.date(), Some("cat")).unwrap();
+        assert!(store.fsck(true).unwrap().issues.is_empty());
+
+        let output = store.get_output(&inv.id.to_string()).unwrap().unwrap();
+        let blob = config.data_dir().join(output.storage_ref.strip_prefix("file://").unwrap());
+        fs::write(&blob, b"bit rot").unwrap();
+
+        assert!(matches!(
+            store.read_output_content(&output),
+            Err(Error::ContentCorrupted { .. })
+        ));
+        let report = store.fsck(true).unwrap();
+        assert_eq!(report.issues.len(), 1);
+        assert!(report.issues[0].problem.starts_with("corrupted"));
+
+        fs::remove_file(&blob).unwrap();
+        assert_eq!(store.fsck(false).unwrap().issues[0].problem, "missing blob file");
+    }
+}
@@ -0,0 +1,314 @@
+//! Project health from history alone (`shq health`).
+//!
+//! Commands run in a directory tree are sorted into build, test and lint
+//! classes using their event format (recorded at capture, or from the format
+//! hints) and the words in the command. Each class gets a recent failure
+//! rate, its last green run and the mean time from breaking to fixed.
+
+use std::fmt;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use duckdb::params;
+
+use super::sessions::from_epoch_ms;
+use super::Store;
+use crate::Result;
+
+/// Default window for [`Store::dir_health`], in days.
+pub const DEFAULT_HEALTH_DAYS: u32 = 14;
+
+/// Kind of command, inferred from its format and name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CommandClass {
+    Build,
+    Test,
+    Lint,
+}
+
+/// Formats whose commands are test runs.
+const TEST_FORMATS: &[&str] = &["pytest_text", "pytest_json", "jest", "go_test", "junit", "mocha", "rspec"];
+/// Formats whose commands are linters or type checkers.
+const LINT_FORMATS: &[&str] = &["eslint", "flake8", "pylint", "mypy", "ruff", "shellcheck", "rubocop"];
+/// Formats whose commands are compilers and build tools.
+const BUILD_FORMATS: &[&str] =
+    &["gcc", "cargo_build", "rustc", "make_error", "cmake", "ninja", "go_build", "typescript", "msbuild", "maven", "gradle"];
+
+impl CommandClass {
+    /// Canonical name of the class.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommandClass::Build => "build",
+            CommandClass::Test => "test",
+            CommandClass::Lint => "lint",
+        }
+    }
+
+    /// Class of `cmd`, parsed with event format `format`.
+    ///
+    /// Words in the command win over the format, since one tool's format
+    /// often covers all of its subcommands (`cargo test` and `cargo clippy`
+    /// both parse as `cargo_build`). Returns `None` for anything else.
+    pub fn classify(cmd: &str, format: &str) -> Option<CommandClass> {
+        let words: Vec<&str> = cmd
+            .split(|c: char| c.is_whitespace() || c == '/' || c == ':')
+            .filter(|w| !w.is_empty())
+            .take(4)
+            .collect();
+        let has = |names: &[&str]| words.iter().any(|w| names.contains(w));
+
+        if has(&["test", "tests", "pytest", "jest", "ctest", "nextest"]) || TEST_FORMATS.contains(&format) {
+            Some(CommandClass::Test)
+        } else if has(&["lint", "clippy", "eslint", "flake8", "pylint", "mypy", "ruff", "shellcheck"])
+            || LINT_FORMATS.contains(&format)
+        {
+            Some(CommandClass::Lint)
+        } else if has(&["build", "make", "check", "compile", "cmake", "ninja", "tsc"])
+            || BUILD_FORMATS.contains(&format)
+        {
+            Some(CommandClass::Build)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for CommandClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Health of one class of commands (see [`DirHealth`]).
+#[derive(Debug, Clone)]
+pub struct ClassHealth {
+    pub class: CommandClass,
+    /// Completed runs in the window.
+    pub runs: usize,
+    /// Runs with a non-zero exit code.
+    pub failures: usize,
+    /// Whether the most recent run failed.
+    pub failing: bool,
+    /// Most recent successful run, and its command.
+    pub last_green: Option<(DateTime<Utc>, String)>,
+    /// Times a failing class went green again.
+    pub fixes: usize,
+    /// Mean time from the first failure to the next success, over `fixes`.
+    pub mean_time_to_fix: Option<Duration>,
+}
+
+impl ClassHealth {
+    /// Share of runs that failed, from 0 to 1.
+    pub fn failure_rate(&self) -> f64 {
+        if self.runs == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.runs as f64
+        }
+    }
+}
+
+/// Recent health of the commands run under a directory, from
+/// [`Store::dir_health`].
+#[derive(Debug, Clone)]
+pub struct DirHealth {
+    pub dir: String,
+    /// First day of the window.
+    pub since: NaiveDate,
+    /// One entry per class that ran, in build, test, lint order.
+    pub classes: Vec<ClassHealth>,
+    /// Completed runs that are not builds, tests or lints.
+    pub other_runs: usize,
+}
+
+/// A completed run: class, start time and whether it succeeded.
+type Run = (CommandClass, DateTime<Utc>, bool, String);
+
+/// Fold runs, oldest first, into per-class health.
+fn summarize(runs: &[Run]) -> Vec<ClassHealth> {
+    let mut classes: Vec<ClassHealth> = Vec::new();
+    // Per class: when the current failing streak started
+    let mut broken_since: Vec<(CommandClass, DateTime<Utc>)> = Vec::new();
+    let mut fix_totals: Vec<(CommandClass, Duration)> = Vec::new();
+
+    for (class, at, ok, cmd) in runs {
+        let idx = match classes.iter().position(|c| c.class == *class) {
+            Some(idx) => idx,
+            None => {
+                classes.push(ClassHealth {
+                    class: *class,
+                    runs: 0,
+                    failures: 0,
+                    failing: false,
+                    last_green: None,
+                    fixes: 0,
+                    mean_time_to_fix: None,
+                });
+                classes.len() - 1
+            }
+        };
+        let health = &mut classes[idx];
+        health.runs += 1;
+        health.failing = !ok;
+
+        let streak = broken_since.iter().position(|(c, _)| c == class);
+        if *ok {
+            health.last_green = Some((*at, cmd.clone()));
+            if let Some(pos) = streak {
+                let (_, started) = broken_since.swap_remove(pos);
+                health.fixes += 1;
+                match fix_totals.iter_mut().find(|(c, _)| c == class) {
+                    Some((_, total)) => *total += *at - started,
+                    None => fix_totals.push((*class, *at - started)),
+                }
+            }
+        } else {
+            health.failures += 1;
+            if streak.is_none() {
+                broken_since.push((*class, *at));
+            }
+        }
+    }
+
+    for health in &mut classes {
+        if let Some((_, total)) = fix_totals.iter().find(|(c, _)| *c == health.class) {
+            health.mean_time_to_fix = Some(*total / health.fixes as i32);
+        }
+    }
+    classes.sort_by_key(|c| c.class);
+    classes
+}
+
+impl Store {
+    /// Summarize the last `days` days of builds, tests and lints run in
+    /// `dir` or below it.
+    pub fn dir_health(&self, dir: &str, days: u32) -> Result<DirHealth> {
+        let since = Utc::now().date_naive() - Duration::days(days as i64);
+        let dir = dir.trim_end_matches('/');
+        let dir = if dir.is_empty() { "/" } else { dir };
+        let hints = self.load_format_hints()?;
+        let conn = self.connection()?;
+
+        let mut stmt = match conn.prepare(
+            r#"
+            SELECT cmd, format_hint, exit_code, epoch_ms(timestamp)
+            FROM invocations
+            WHERE date >= ?::DATE
+              AND (cwd = ? OR starts_with(cwd, ? || '/') OR ? = '/')
+              AND exit_code IS NOT NULL
+            ORDER BY timestamp
+            "#,
+        ) {
+            Ok(stmt) => stmt,
+            Err(e) if e.to_string().contains("No files found") => {
+                return Ok(DirHealth { dir: dir.to_string(), since, classes: Vec::new(), other_runs: 0 });
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let rows = stmt.query_map(params![since.to_string(), dir, dir, dir], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, i32>(2)?,
+                row.get::<_, i64>(3)?,
+            ))
+        })?;
+
+        let mut runs = Vec::new();
+        let mut other_runs = 0;
+        for row in rows {
+            let (cmd, format_hint, exit_code, at_ms) = row?;
+            let format = format_hint.unwrap_or_else(|| hints.detect(&cmd).to_string());
+            let Some(class) = CommandClass::classify(&cmd, &format) else {
+                other_runs += 1;
+                continue;
+            };
+            runs.push((class, from_epoch_ms(at_ms), exit_code == 0, cmd));
+        }
+
+        Ok(DirHealth {
+            dir: dir.to_string(),
+            since,
+            classes: summarize(&runs),
+            other_runs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(CommandClass::classify("cargo test --lib", "cargo_build"), Some(CommandClass::Test));
+        assert_eq!(CommandClass::classify("cargo clippy", "cargo_build"), Some(CommandClass::Lint));
+        assert_eq!(CommandClass::classify("cargo build", "cargo_build"), Some(CommandClass::Build));
+        assert_eq!(CommandClass::classify("npx eslint src", "auto"), Some(CommandClass::Lint));
+        assert_eq!(CommandClass::classify("./run-ci.sh", "pytest_text"), Some(CommandClass::Test));
+        assert_eq!(CommandClass::classify("gcc -c main.c", "gcc"), Some(CommandClass::Build));
+        assert_eq!(CommandClass::classify("git status", "auto"), None);
+        // Arguments deep in the command don't count
+        assert_eq!(CommandClass::classify("git commit -m fix the build", "auto"), None);
+    }
+
+    #[test]
+    fn test_summarize_failure_rate_and_time_to_fix() {
+        let at = |mins: i64| DateTime::from_timestamp(1_700_000_000 + mins * 60, 0).unwrap();
+        let run = |class, mins, ok| (class, at(mins), ok, format!("{}", mins));
+        let runs = vec![
+            run(CommandClass::Build, 0, true),
+            run(CommandClass::Build, 10, false),
+            run(CommandClass::Test, 12, false),
+            run(CommandClass::Build, 15, false),
+            run(CommandClass::Build, 30, true), // fixed after 20 minutes
+            run(CommandClass::Build, 40, false),
+            run(CommandClass::Build, 50, true), // fixed after 10 minutes
+            run(CommandClass::Test, 60, false),
+        ];
+
+        let health = summarize(&runs);
+        assert_eq!(health.len(), 2);
+        let build = &health[0];
+        assert_eq!(build.class, CommandClass::Build);
+        assert_eq!((build.runs, build.failures, build.fixes), (6, 3, 2));
+        assert!(!build.failing);
+        assert_eq!(build.failure_rate(), 0.5);
+        assert_eq!(build.last_green.as_ref().map(|(t, _)| *t), Some(at(50)));
+        assert_eq!(build.mean_time_to_fix, Some(Duration::minutes(15)));
+
+        let test = &health[1];
+        assert!(test.failing);
+        assert_eq!((test.runs, test.failures, test.fixes), (2, 2, 0));
+        assert_eq!(test.last_green, None);
+        assert_eq!(test.mean_time_to_fix, None);
+    }
+
+    #[test]
+    fn test_dir_health() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        for (cmd, cwd, exit_code) in [
+            ("cargo build", "/work/app", 1),
+            ("cargo build", "/work/app/src", 0),
+            ("cargo test", "/work/app", 0),
+            ("ls", "/work/app", 0),
+            ("cargo build", "/work/application", 1),
+        ] {
+            let record = InvocationRecord::new("s", cmd, cwd, exit_code, "test@client");
+            store.write_invocation(&record).unwrap();
+        }
+
+        let health = store.dir_health("/work/app/", DEFAULT_HEALTH_DAYS).unwrap();
+        assert_eq!(health.dir, "/work/app");
+        assert_eq!(health.other_runs, 1);
+        let classes: Vec<_> = health.classes.iter().map(|c| (c.class, c.runs, c.failures)).collect();
+        assert_eq!(classes, [(CommandClass::Build, 2, 1), (CommandClass::Test, 1, 0)]);
+    }
+}
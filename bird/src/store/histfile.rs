@@ -0,0 +1,220 @@
+//! The `[histfile]` bridge: appending written invocations to the shell's
+//! history file, and importing history files (see [`crate::histfile`]).
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use duckdb::params;
+
+use super::{InvocationBatch, Store};
+use crate::histfile::{self, HistEntry, HistFormat};
+use crate::schema::{InvocationRecord, SessionRecord};
+use crate::{Error, Result};
+
+/// Prefix of the session ids of imported history; these invocations are
+/// never appended back to a history file.
+pub const HISTFILE_SESSION_PREFIX: &str = "histfile-";
+
+/// What a history file import did.
+#[derive(Debug, Default)]
+pub struct HistImportStats {
+    /// Entries recorded as invocations.
+    pub imported: usize,
+    /// Entries already imported before.
+    pub skipped: usize,
+}
+
+impl Store {
+    /// Append `invocations` to the `[histfile] write` file, if one is set.
+    /// Best-effort: a history file that can't be written never fails the
+    /// capture.
+    pub(crate) fn append_to_histfile<'a>(&self, invocations: impl IntoIterator<Item = &'a InvocationRecord>) {
+        if self.config.in_memory {
+            return;
+        }
+        let Some((path, format)) = self.config.histfile.write_target() else {
+            return;
+        };
+        let entries: Vec<HistEntry> = invocations
+            .into_iter()
+            .filter(|inv| !inv.session_id.starts_with(HISTFILE_SESSION_PREFIX))
+            .map(|inv| HistEntry {
+                command: inv.cmd.clone(),
+                timestamp: Some(inv.timestamp),
+                elapsed_secs: inv.duration_ms.map(|ms| ms / 1000),
+                offset: 0,
+            })
+            .collect();
+        if let Err(e) = histfile::append(&path, format, &entries) {
+            tracing::warn!(path = %path.display(), "couldn't append to history file: {}", e);
+        }
+    }
+
+    /// Record the entries of the history file at `path` that weren't
+    /// imported yet, as invocations without output. History files don't
+    /// keep exit codes, so they're recorded as 0.
+    ///
+    /// Reading resumes where the last import stopped; a file that shrank
+    /// (the shell trimmed it to `HISTSIZE`) is read again from the start,
+    /// skipping timestamped entries already in the store by their ids, which
+    /// are derived from the entry.
+    pub fn import_histfile(&self, path: &Path, format: HistFormat) -> Result<HistImportStats> {
+        let _sync = self.sync_guard();
+        // Its lines are our own captures (or the shell's, which the hook
+        // captured too)
+        if self.config.histfile.write_target().is_some_and(|(target, _)| same_file(&target, path)) {
+            return Err(Error::Config(format!(
+                "{} is where captured commands are written ([histfile] write); importing it would record them twice",
+                path.display()
+            )));
+        }
+
+        let data = fs::read(path)?;
+        let key = path.display().to_string();
+        let mut offsets = self.histfile_offsets();
+        let mut from = offsets.get(&key).copied().unwrap_or(0);
+        if from > data.len() {
+            from = 0;
+        }
+        let data = &data[from..];
+        // Up to the last complete line, as `histfile::parse` reads
+        let consumed = data.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+
+        let name = path.file_name().map(|n| n.to_string_lossy().trim_start_matches('.').to_string()).unwrap_or_default();
+        let session_id = format!("{}{}", HISTFILE_SESSION_PREFIX, name);
+        let invoker = match format {
+            HistFormat::Zsh => "zsh",
+            HistFormat::Bash => "bash",
+            HistFormat::Plain => "sh",
+        };
+        // Entries without a timestamp are dated by the file
+        let modified: DateTime<Utc> = fs::metadata(path)?.modified()?.into();
+        let cwd = std::env::var("HOME").unwrap_or_else(|_| "/".to_string());
+        let existing = self.histfile_invocation_ids(&session_id)?;
+
+        let mut stats = HistImportStats::default();
+        let mut batches = Vec::new();
+        for entry in histfile::parse(data, format) {
+            let timestamp = entry.timestamp.unwrap_or(modified);
+            let id = entry_id(&entry, from + entry.offset, timestamp);
+            if existing.contains(&id.to_string()) {
+                stats.skipped += 1;
+                continue;
+            }
+            let mut record = InvocationRecord::with_id(id, &session_id, &entry.command, &cwd, 0, &self.config.client_id);
+            record.timestamp = timestamp;
+            if let Some(secs) = entry.elapsed_secs {
+                record = record.with_duration(secs * 1000);
+            }
+            let session = SessionRecord::new(&session_id, &self.config.client_id, invoker, 0, "histfile");
+            batches.push(InvocationBatch::new(record).with_session(session));
+        }
+
+        for chunk in batches.chunks(self.config.ingest.max_batch.max(1)) {
+            let failed = self.write_batches(chunk)?;
+            if let Some((_, e)) = failed.into_iter().next() {
+                return Err(e);
+            }
+            stats.imported += chunk.len();
+        }
+
+        offsets.insert(key, from + consumed);
+        self.save_histfile_offsets(&offsets)?;
+        Ok(stats)
+    }
+
+    /// Ids of the invocations imported into `session_id`.
+    fn histfile_invocation_ids(&self, session_id: &str) -> Result<HashSet<String>> {
+        let conn = self.connection()?;
+        let mut stmt = match conn.prepare("SELECT id::VARCHAR FROM invocations WHERE session_id = ?") {
+            Ok(stmt) => stmt,
+            Err(e) if e.to_string().contains("No files found") => return Ok(HashSet::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let rows = stmt.query_map(params![session_id], |row| row.get::<_, String>(0));
+        match rows {
+            Ok(rows) => Ok(rows.collect::<std::result::Result<_, _>>()?),
+            Err(e) if e.to_string().contains("No files found") => Ok(HashSet::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// How far each history file has been imported, by path.
+    fn histfile_offsets(&self) -> BTreeMap<String, usize> {
+        fs::read_to_string(self.config.histfile_offsets_path())
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_histfile_offsets(&self, offsets: &BTreeMap<String, usize>) -> Result<()> {
+        let path = self.config.histfile_offsets_path();
+        fs::write(&path, serde_json::to_vec_pretty(offsets)?)?;
+        crate::perms::harden_file(&path);
+        Ok(())
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// A stable id for the entry at `offset`: its time and a hash of the entry,
+/// with the offset standing in for a timestamp the entry doesn't have.
+fn entry_id(entry: &HistEntry, offset: usize, timestamp: DateTime<Utc>) -> uuid::Uuid {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(entry.command.as_bytes());
+    if entry.timestamp.is_none() {
+        hasher.update(&(offset as u64).to_le_bytes());
+    }
+    let mut random = [0u8; 10];
+    random.copy_from_slice(&hasher.finalize().as_bytes()[..10]);
+    uuid::Builder::from_unix_timestamp_millis(timestamp.timestamp_millis().max(0) as u64, &random).into_uuid()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_import_and_write_back() {
+        let tmp = TempDir::new().unwrap();
+        let mut config = Config::with_root(tmp.path());
+        let zsh = tmp.path().join(".zsh_history");
+        let bash = tmp.path().join(".bash_history");
+        config.histfile.write = Some(zsh.display().to_string());
+        initialize(&config).unwrap();
+        let store = Store::open(config.clone()).unwrap();
+
+        fs::write(&bash, "#1700000000\nmake test\n#1700000010\ngit status\n").unwrap();
+        let stats = store.import_histfile(&bash, HistFormat::Bash).unwrap();
+        assert_eq!((stats.imported, stats.skipped), (2, 0));
+        // Imported commands aren't written back
+        assert!(!zsh.exists());
+
+        // Captured ones are
+        let record = InvocationRecord::new("s1", "cargo build", "/tmp", 0, &config.client_id);
+        store.write_batch(&InvocationBatch::new(record)).unwrap();
+        assert!(fs::read_to_string(&zsh).unwrap().ends_with(";cargo build\n"));
+        assert!(store.import_histfile(&zsh, HistFormat::Zsh).is_err());
+
+        // The next import reads only what's new
+        let mut file = fs::OpenOptions::new().append(true).open(&bash).unwrap();
+        std::io::Write::write_all(&mut file, b"#1700000020\nls\n").unwrap();
+        assert_eq!(store.import_histfile(&bash, HistFormat::Bash).unwrap().imported, 1);
+
+        // Trimmed by the shell: read again, but nothing is imported twice
+        fs::write(&bash, "#1700000010\ngit status\n").unwrap();
+        let stats = store.import_histfile(&bash, HistFormat::Bash).unwrap();
+        assert_eq!((stats.imported, stats.skipped), (0, 1));
+        assert_eq!(store.invocation_count().unwrap(), 4);
+    }
+}
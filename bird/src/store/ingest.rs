@@ -0,0 +1,161 @@
+//! Draining the async-save spool into the store (see [`crate::spool`]).
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use super::{Store, WriteCoalescer};
+use crate::spool::{self, FlushLock, SpooledSave};
+use crate::{Error, Result};
+
+/// What a spool flush did.
+#[derive(Debug, Default)]
+pub struct SpoolFlushStats {
+    /// Saves written to the store (and removed from the spool).
+    pub written: usize,
+    /// Saves that couldn't be read or written, moved to `spool/failed`.
+    pub failed: usize,
+    /// Saves spooled by a newer shq, left in the spool for it.
+    pub deferred: usize,
+    /// Events extracted from the written saves.
+    pub events: usize,
+    /// Sessions whose saves asked for a compaction check.
+    pub compact_sessions: Vec<String>,
+}
+
+impl Store {
+    /// Write spooled saves to the store through a [`WriteCoalescer`], in
+    /// groups of `[ingest] max_batch`, removing each from the spool once
+    /// it's written.
+    ///
+    /// Does nothing if another flusher holds the spool; it picks up whatever
+    /// is spooled before it lets go. A store that can't be opened (e.g. a
+    /// lock conflict) stops the flush with the remaining saves left in the
+    /// spool for the next one, as are saves spooled by a newer shq.
+    pub fn flush_spool(&self) -> Result<SpoolFlushStats> {
+        let _sync = self.sync_guard();
+        let mut stats = SpoolFlushStats::default();
+        let mut sessions = BTreeSet::new();
+        let mut deferred = HashSet::new();
+        let pending = |deferred: &HashSet<PathBuf>| -> Result<Vec<PathBuf>> {
+            Ok(spool::pending(&self.config)?.into_iter().filter(|path| !deferred.contains(path)).collect())
+        };
+
+        let mut coalescer = WriteCoalescer::new(self);
+        while let Some(lock) = FlushLock::try_acquire(&self.config)? {
+            loop {
+                let paths = pending(&deferred)?;
+                if paths.is_empty() {
+                    break;
+                }
+                for chunk in paths.chunks(self.config.ingest.max_batch.max(1)) {
+                    self.flush_spool_chunk(&mut coalescer, chunk, &mut stats, &mut sessions, &mut deferred)?;
+                }
+            }
+            drop(lock);
+
+            // A save spooled after the last scan may have found the lock
+            // taken and left its record to us
+            if pending(&deferred)?.is_empty() {
+                break;
+            }
+        }
+        stats.deferred = deferred.len();
+
+        stats.compact_sessions = sessions.into_iter().collect();
+        Ok(stats)
+    }
+
+    /// Write one group of spool files, then remove or quarantine each.
+    fn flush_spool_chunk(
+        &self,
+        coalescer: &mut WriteCoalescer<'_>,
+        paths: &[PathBuf],
+        stats: &mut SpoolFlushStats,
+        sessions: &mut BTreeSet<String>,
+        deferred: &mut HashSet<PathBuf>,
+    ) -> Result<()> {
+        let mut saves: Vec<(&PathBuf, SpooledSave)> = Vec::new();
+        let first = coalescer.pushed();
+        for path in paths {
+            match spool::read(path).and_then(|save| Ok((save.to_batch()?, save))) {
+                Ok((batch, save)) => {
+                    coalescer.push(batch)?;
+                    saves.push((path, save));
+                }
+                Err(e @ Error::NewerFormat { .. }) => {
+                    tracing::warn!("{}; left in the spool", e);
+                    deferred.insert(path.clone());
+                }
+                Err(e) => {
+                    tracing::warn!(path = %path.display(), "unreadable spooled save: {}", e);
+                    spool::quarantine(&self.config, path)?;
+                    stats.failed += 1;
+                }
+            }
+        }
+
+        coalescer.flush()?;
+        let failed: HashMap<usize, crate::Error> = coalescer
+            .take_failed()
+            .into_iter()
+            .map(|(i, e)| (i - first, e))
+            .collect();
+        for (i, (path, save)) in saves.into_iter().enumerate() {
+            if let Some(e) = failed.get(&i) {
+                tracing::warn!(path = %path.display(), "could not write spooled save: {}", e);
+                spool::quarantine(&self.config, path)?;
+                stats.failed += 1;
+                continue;
+            }
+            fs::remove_file(path)?;
+            stats.written += 1;
+
+            if save.extract || self.config.auto_extract {
+                match self.extract_events(&save.invocation.id.to_string(), None) {
+                    Ok(count) => stats.events += count,
+                    Err(e) => tracing::warn!(invocation = %save.invocation.id, "event extraction failed: {}", e),
+                }
+            }
+            if save.compact {
+                sessions.insert(save.invocation.session_id);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_flush_spool_writes_and_removes() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_duckdb_mode(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config.clone()).unwrap();
+
+        for i in 0..3 {
+            let inv = InvocationRecord::new("s1", format!("echo {}", i), "/tmp", 0, "me@host");
+            let mut save = SpooledSave::new(inv).with_output("stdout", format!("{}\n", i).as_bytes());
+            save.compact = i == 0;
+            spool::write(&config, &save).unwrap();
+        }
+        fs::write(config.spool_dir().join("garbage.json"), "{").unwrap();
+        let newer = config.spool_dir().join("newer.json");
+        fs::write(&newer, format!(r#"{{"version": {}}}"#, crate::version::SPOOL_VERSION + 1)).unwrap();
+
+        let stats = store.flush_spool().unwrap();
+        assert_eq!(stats.written, 3);
+        assert_eq!(stats.failed, 1);
+        assert_eq!(stats.deferred, 1);
+        assert_eq!(stats.compact_sessions, vec!["s1".to_string()]);
+        assert_eq!(spool::pending(&config).unwrap(), vec![newer]);
+        assert_eq!(store.invocation_count().unwrap(), 3);
+    }
+}
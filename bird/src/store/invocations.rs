@@ -4,12 +4,16 @@
 //! - write_invocation() writes both attempt and outcome (for completed commands)
 //! - For long-running commands, use start_invocation() and complete_invocation()
 
-use duckdb::params;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
 
-use super::Store;
-use crate::query::{CompareOp, Query, QueryComponent};
+use chrono::{NaiveDate, TimeDelta, Utc};
+use duckdb::{params, Connection};
+
+use super::{recent_index, Store};
+use crate::query::{CompareOp, FieldFilter, PathFilter, Query, QueryComponent};
 use crate::schema::InvocationRecord;
-use crate::Result;
+use crate::{Error, Result};
 
 /// Summary of an invocation (for listing).
 #[derive(Debug)]
@@ -19,6 +23,7 @@ pub struct InvocationSummary {
     pub exit_code: i32,
     pub timestamp: String,
     pub duration_ms: Option<i64>,
+    pub cwd: String,
 }
 
 impl Store {
@@ -45,53 +50,7 @@ impl Store {
 
     /// Get recent invocations (last 7 days).
     pub fn recent_invocations(&self, limit: usize) -> Result<Vec<InvocationSummary>> {
-        let conn = self.connection()?;
-
-        let sql = format!(
-            r#"
-            SELECT id::VARCHAR, cmd, exit_code, timestamp::VARCHAR, duration_ms
-            FROM recent_invocations
-            LIMIT {}
-            "#,
-            limit
-        );
-
-        let mut stmt = match conn.prepare(&sql) {
-            Ok(stmt) => stmt,
-            Err(e) => {
-                if e.to_string().contains("No files found") {
-                    return Ok(Vec::new());
-                }
-                return Err(e.into());
-            }
-        };
-
-        let rows = stmt.query_map([], |row| {
-            Ok(InvocationSummary {
-                id: row.get(0)?,
-                cmd: row.get(1)?,
-                exit_code: row.get(2)?,
-                timestamp: row.get(3)?,
-                duration_ms: row.get(4)?,
-            })
-        });
-
-        match rows {
-            Ok(rows) => {
-                let mut results = Vec::new();
-                for row in rows {
-                    results.push(row?);
-                }
-                Ok(results)
-            }
-            Err(e) => {
-                if e.to_string().contains("No files found") {
-                    Ok(Vec::new())
-                } else {
-                    Err(e.into())
-                }
-            }
-        }
+        self.query_invocations_with_limit(&Query::default(), limit)
     }
 
     /// Get the last invocation (most recent).
@@ -116,125 +75,20 @@ impl Store {
         default_limit: usize,
     ) -> Result<Vec<InvocationSummary>> {
         let conn = self.connection()?;
+        let today = Utc::now().date_naive();
 
-        // Build WHERE clauses from query filters
-        let mut where_clauses: Vec<String> = Vec::new();
-
-        for component in &query.filters {
-            match component {
-                QueryComponent::CommandRegex(pattern) => {
-                    // Use regexp_matches for regex filtering
-                    let escaped = pattern.replace('\'', "''");
-                    where_clauses.push(format!("regexp_matches(cmd, '{}')", escaped));
-                }
-                QueryComponent::FieldFilter(filter) => {
-                    // Map field names to SQL column names
-                    let column = match filter.field.as_str() {
-                        "exit" | "exit_code" => "exit_code",
-                        "duration" | "duration_ms" => "duration_ms",
-                        "cmd" | "command" => "cmd",
-                        "cwd" => "cwd",
-                        other => other, // Pass through unknown fields
-                    };
-
-                    let escaped_value = filter.value.replace('\'', "''");
-
-                    let clause = match filter.op {
-                        CompareOp::Eq => format!("{} = '{}'", column, escaped_value),
-                        CompareOp::NotEq => format!("{} <> '{}'", column, escaped_value),
-                        CompareOp::Gt => format!("{} > '{}'", column, escaped_value),
-                        CompareOp::Lt => format!("{} < '{}'", column, escaped_value),
-                        CompareOp::Gte => format!("{} >= '{}'", column, escaped_value),
-                        CompareOp::Lte => format!("{} <= '{}'", column, escaped_value),
-                        CompareOp::Regex => {
-                            format!("regexp_matches({}::VARCHAR, '{}')", column, escaped_value)
-                        }
-                    };
-                    where_clauses.push(clause);
-                }
-                QueryComponent::Tag(_) => {
-                    // Tags not implemented in MVP
-                }
-            }
+        // A host selector can match remote history, so read the unified view
+        if query.needs_remotes() {
+            self.attach_all_remotes(&conn)?;
+            let sql = invocations_sql("unified.invocations", query, default_limit, today);
+            return read_summaries(&conn, &sql);
         }
 
-        // Build the SQL query
-        let where_sql = if where_clauses.is_empty() {
-            String::new()
-        } else {
-            format!("WHERE {}", where_clauses.join(" AND "))
-        };
-
-        // Determine limit and offset based on range selector semantics:
-        // - Single item (end: None): position N means OFFSET N-1, LIMIT 1
-        // - Last N (end: Some(0)): LIMIT N
-        // - Range (end: Some(M)): positions from N to M
-        let (limit, offset) = if let Some(range) = query.range {
-            if range.is_single() {
-                // ~N = single item at position N (1-indexed)
-                (1, range.start.saturating_sub(1))
-            } else if range.is_last_n() {
-                // ~N: = last N items
-                (range.start, 0)
-            } else {
-                // ~N:~M = range from position N to M
-                // Position N is offset N-1, position M is offset M-1
-                // So we need M items starting at offset (start - 1)
-                let end_pos = range.end.unwrap_or(1);
-                let count = range.start.saturating_sub(end_pos) + 1;
-                (count, end_pos.saturating_sub(1))
-            }
-        } else {
-            (default_limit, 0)
-        };
-
-        let sql = format!(
-            r#"
-            SELECT id::VARCHAR, cmd, exit_code, timestamp::VARCHAR, duration_ms
-            FROM recent_invocations
-            {}
-            LIMIT {}
-            OFFSET {}
-            "#,
-            where_sql, limit, offset
-        );
-
-        let mut stmt = match conn.prepare(&sql) {
-            Ok(stmt) => stmt,
-            Err(e) => {
-                if e.to_string().contains("No files found") {
-                    return Ok(Vec::new());
-                }
-                return Err(e.into());
-            }
-        };
-
-        let rows = stmt.query_map([], |row| {
-            Ok(InvocationSummary {
-                id: row.get(0)?,
-                cmd: row.get(1)?,
-                exit_code: row.get(2)?,
-                timestamp: row.get(3)?,
-                duration_ms: row.get(4)?,
-            })
-        });
-
-        match rows {
-            Ok(rows) => {
-                let mut results = Vec::new();
-                for row in rows {
-                    results.push(row?);
-                }
-                Ok(results)
-            }
-            Err(e) => {
-                if e.to_string().contains("No files found") {
-                    Ok(Vec::new())
-                } else {
-                    Err(e.into())
-                }
-            }
+        if let Some(results) = recent_index::query(&conn, query, default_limit, today)? {
+            return Ok(results);
         }
+
+        read_summaries(&conn, &invocations_sql("main.invocations", query, default_limit, today))
     }
 
     /// Query invocations with default limit of 20 (for listing).
@@ -282,6 +136,71 @@ impl Store {
         }
     }
 
+    /// Environment snapshot recorded with an invocation.
+    ///
+    /// Returns `None` if the invocation has no snapshot (recorded before
+    /// snapshots existed, or with `privacy.record_env = false`).
+    pub fn env_snapshot(&self, invocation_id: &str) -> Result<Option<BTreeMap<String, String>>> {
+        let conn = self.connection()?;
+
+        let result: std::result::Result<Option<String>, _> = conn.query_row(
+            "SELECT metadata['env']::VARCHAR FROM invocations WHERE id = ?",
+            params![invocation_id],
+            |row| row.get(0),
+        );
+
+        let json = match result {
+            Ok(json) => json,
+            Err(duckdb::Error::QueryReturnedNoRows) => {
+                return Err(Error::NotFound(format!("Invocation {}", invocation_id)))
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        json.map(|json| {
+            serde_json::from_str(&json)
+                .map_err(|e| Error::Storage(format!("Invalid env snapshot: {}", e)))
+        })
+        .transpose()
+    }
+
+    /// VCS context recorded with an invocation (branch, commit, and the
+    /// working-tree `snapshot` with `snapshot_worktree`), or `None` if it
+    /// ran outside a repository.
+    pub fn vcs_context(&self, invocation_id: &str) -> Result<Option<serde_json::Value>> {
+        self.metadata_entry(invocation_id, "vcs")
+    }
+
+    /// Terminal context recorded with an invocation (emulator, multiplexer,
+    /// window and title), or `None` if it didn't run in a terminal.
+    pub fn terminal_context(&self, invocation_id: &str) -> Result<Option<serde_json::Value>> {
+        self.metadata_entry(invocation_id, "terminal")
+    }
+
+    fn metadata_entry(&self, invocation_id: &str, key: &str) -> Result<Option<serde_json::Value>> {
+        let conn = self.connection()?;
+
+        let result: std::result::Result<Option<String>, _> = conn.query_row(
+            &format!("SELECT metadata['{}']::VARCHAR FROM invocations WHERE id = ?", key),
+            params![invocation_id],
+            |row| row.get(0),
+        );
+
+        let json = match result {
+            Ok(json) => json,
+            Err(duckdb::Error::QueryReturnedNoRows) => {
+                return Err(Error::NotFound(format!("Invocation {}", invocation_id)))
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        json.map(|json| {
+            serde_json::from_str(&json)
+                .map_err(|e| Error::Storage(format!("Invalid {} context: {}", key, e)))
+        })
+        .transpose()
+    }
+
     /// Set or update the tag on an invocation.
     ///
     /// V5 schema: Updates the tag on the attempts table.
@@ -356,10 +275,374 @@ impl Store {
     }
 }
 
+/// Days searched when a query has no time filter of its own.
+pub(crate) const DEFAULT_QUERY_WINDOW_DAYS: i64 = 7;
+
+/// Inclusive `date` bounds for a query, used to prune `date=` partitions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct DateRange {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+impl DateRange {
+    /// Predicates on the `date` column.
+    ///
+    /// Bounds are emitted as literals: DuckDB only prunes hive partitions
+    /// for constant filters, not for `CURRENT_DATE - INTERVAL ...`.
+    pub(crate) fn predicates(&self) -> Vec<String> {
+        let mut predicates = Vec::new();
+        if let Some(start) = self.start {
+            predicates.push(format!("date >= '{}'", start));
+        }
+        if let Some(end) = self.end {
+            predicates.push(format!("date <= '{}'", end));
+        }
+        predicates
+    }
+
+    fn narrow_start(&mut self, date: NaiveDate) {
+        self.start = Some(self.start.map_or(date, |s| s.max(date)));
+    }
+
+    fn narrow_end(&mut self, date: NaiveDate) {
+        self.end = Some(self.end.map_or(date, |e| e.min(date)));
+    }
+}
+
+/// Derive the partition date range a query needs to read.
+///
+/// `%date` and `%timestamp` filters bound the range directly (a timestamp
+/// only bounds by its date, so comparisons stay inclusive). Without any time
+/// filter the query covers the last [`DEFAULT_QUERY_WINDOW_DAYS`] days.
+/// Range selectors (`~N`, `~N:~M`) count back from the newest invocation,
+/// so they never need older partitions than that window and add no bound of
+/// their own.
+pub(crate) fn date_range(query: &Query, today: NaiveDate) -> DateRange {
+    let mut range = DateRange::default();
+    let mut has_time_filter = false;
+
+    for component in &query.filters {
+        let QueryComponent::FieldFilter(filter) = component else {
+            continue;
+        };
+        let is_date = match filter.field.as_str() {
+            "date" => true,
+            "timestamp" => false,
+            _ => continue,
+        };
+        let Some(date) = filter
+            .value
+            .get(..10)
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        else {
+            continue;
+        };
+        has_time_filter = true;
+
+        match filter.op {
+            CompareOp::Eq => {
+                range.narrow_start(date);
+                range.narrow_end(date);
+            }
+            CompareOp::Gt if is_date => range.narrow_start(date + TimeDelta::days(1)),
+            CompareOp::Lt if is_date => range.narrow_end(date - TimeDelta::days(1)),
+            CompareOp::Gt | CompareOp::Gte => range.narrow_start(date),
+            CompareOp::Lt | CompareOp::Lte => range.narrow_end(date),
+            CompareOp::NotEq | CompareOp::Regex => {}
+        }
+    }
+
+    if !has_time_filter {
+        range.start = Some(today - TimeDelta::days(DEFAULT_QUERY_WINDOW_DAYS));
+    }
+    range
+}
+
+/// Build the invocation listing SQL for a parsed query over `source`.
+///
+/// `source` is `main.invocations`, `unified.invocations` or
+/// `main.recent_index`. The date range is applied before the other filters
+/// so that, in parquet mode, only the matching `date=` partitions are scanned.
+pub(crate) fn invocations_sql(
+    source: &str,
+    query: &Query,
+    default_limit: usize,
+    today: NaiveDate,
+) -> String {
+    let mut clauses = date_range(query, today).predicates();
+    clauses.extend(where_clauses(query));
+    let (limit, offset) = range_limit_offset(query, default_limit);
+
+    format!(
+        r#"
+        SELECT id::VARCHAR, cmd, exit_code, timezone('UTC', timestamp)::VARCHAR, duration_ms, cwd
+        FROM {}
+        {}
+        ORDER BY timestamp DESC
+        LIMIT {}
+        OFFSET {}
+        "#,
+        source,
+        if clauses.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", clauses.join(" AND "))
+        },
+        limit,
+        offset
+    )
+}
+
+/// Run an invocation listing query (no data files yet means no rows).
+pub(crate) fn read_summaries(conn: &Connection, sql: &str) -> Result<Vec<InvocationSummary>> {
+    let mut stmt = match conn.prepare(sql) {
+        Ok(stmt) => stmt,
+        Err(e) => {
+            if e.to_string().contains("No files found") {
+                return Ok(Vec::new());
+            }
+            return Err(e.into());
+        }
+    };
+
+    let rows = stmt.query_map([], |row| {
+        Ok(InvocationSummary {
+            id: row.get(0)?,
+            cmd: row.get(1)?,
+            exit_code: row.get(2)?,
+            timestamp: row.get(3)?,
+            duration_ms: row.get(4)?,
+            cwd: row.get(5)?,
+        })
+    });
+
+    match rows {
+        Ok(rows) => {
+            let mut results = Vec::new();
+            for row in rows {
+                results.push(row?);
+            }
+            Ok(results)
+        }
+        Err(e) => {
+            if e.to_string().contains("No files found") {
+                Ok(Vec::new())
+            } else {
+                Err(e.into())
+            }
+        }
+    }
+}
+
+/// Build a `WHERE ...` clause from the filters of a parsed query.
+///
+/// Returns an empty string when the query has no filters.
+pub(crate) fn where_sql(query: &Query) -> String {
+    let where_clauses = where_clauses(query);
+    if where_clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", where_clauses.join(" AND "))
+    }
+}
+
+/// Translate query filters into SQL predicates over the invocations view.
+pub(crate) fn where_clauses(query: &Query) -> Vec<String> {
+    let mut where_clauses: Vec<String> = Vec::new();
+
+    if let Some(path) = &query.path {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+        where_clauses.push(path_clause(&resolve_path(path, &cwd, &home)));
+    }
+
+    for component in &query.filters {
+        match component {
+            QueryComponent::CommandRegex(pattern) => {
+                // Use regexp_matches for regex filtering
+                let escaped = pattern.replace('\'', "''");
+                where_clauses.push(format!("regexp_matches(cmd, '{}')", escaped));
+            }
+            QueryComponent::FieldFilter(filter)
+                if matches!(filter.field.as_str(), "session" | "session_id") =>
+            {
+                where_clauses.push(session_clause(filter));
+            }
+            QueryComponent::FieldFilter(filter) if filter.field == "linked" => {
+                where_clauses.push(linked_clause(filter));
+            }
+            QueryComponent::FieldFilter(filter) => {
+                // Map field names to SQL column names
+                let dim_column;
+                let column = match filter.field.as_str() {
+                    "exit" | "exit_code" => "exit_code",
+                    "duration" | "duration_ms" => "duration_ms",
+                    "cmd" | "command" => "cmd",
+                    "cwd" => "cwd",
+                    "terminal" => "json_extract_string(metadata['terminal'], '$.program')",
+                    "title" => "json_extract_string(metadata['terminal'], '$.title')",
+                    "mux" => "json_extract_string(metadata['terminal'], '$.mux')",
+                    "window" => "json_extract_string(metadata['terminal'], '$.window')",
+                    field if field.starts_with("dim:") => {
+                        dim_column = dim_sql(&field[4..]);
+                        &dim_column
+                    }
+                    other => other, // Pass through unknown fields
+                };
+
+                let escaped_value = filter.value.replace('\'', "''");
+
+                let clause = match filter.op {
+                    CompareOp::Eq => format!("{} = '{}'", column, escaped_value),
+                    CompareOp::NotEq => format!("{} <> '{}'", column, escaped_value),
+                    CompareOp::Gt => format!("{} > '{}'", column, escaped_value),
+                    CompareOp::Lt => format!("{} < '{}'", column, escaped_value),
+                    CompareOp::Gte => format!("{} >= '{}'", column, escaped_value),
+                    CompareOp::Lte => format!("{} <= '{}'", column, escaped_value),
+                    CompareOp::Regex => {
+                        format!("regexp_matches({}::VARCHAR, '{}')", column, escaped_value)
+                    }
+                };
+                where_clauses.push(clause);
+            }
+            QueryComponent::Tag(_) => {
+                // Tags not implemented in MVP
+            }
+        }
+    }
+
+    where_clauses
+}
+
+/// SQL expression for a user-defined dimension (`%dim:<key>`).
+fn dim_sql(key: &str) -> String {
+    format!(
+        "json_extract_string(metadata['{}'], '$.\"{}\"')",
+        crate::schema::DIMS_METADATA_KEY,
+        key.replace('\'', "''").replace('"', "")
+    )
+}
+
+/// The directory a path filter names, made absolute against `cwd` (for `.`
+/// and `./x`) or `home` (for `~/x`) and with `..` resolved lexically, the
+/// way the shell's `$PWD` reports it.
+fn resolve_path(path: &PathFilter, cwd: &Path, home: &Path) -> PathBuf {
+    let joined = match path {
+        PathFilter::Current => cwd.to_path_buf(),
+        PathFilter::Relative(p) => cwd.join(p),
+        PathFilter::Home(p) => home.join(p),
+        PathFilter::Absolute(p) => p.clone(),
+    };
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    resolved
+}
+
+/// Build the predicate for a path filter: commands run in `dir` or below it
+/// (what the `cwd_*()` macros select, but stopping at path boundaries).
+fn path_clause(dir: &Path) -> String {
+    let dir = dir.to_string_lossy().trim_end_matches('/').replace('\'', "''");
+    format!("(cwd = '{}' OR starts_with(cwd, '{}/'))", dir, dir)
+}
+
+/// Build the predicate for a `%session...` filter.
+///
+/// Matches either the raw session ID or a name given with
+/// `shq session name`, so `%session=refactor-auth` selects every session
+/// carrying that name.
+fn session_clause(filter: &FieldFilter) -> String {
+    let value = filter.value.replace('\'', "''");
+    let by_name = |predicate: String| {
+        format!("session_id IN (SELECT session_id FROM main.session_names WHERE {})", predicate)
+    };
+
+    match filter.op {
+        CompareOp::Eq => format!(
+            "(session_id = '{}' OR {})",
+            value,
+            by_name(format!("name = '{}'", value))
+        ),
+        CompareOp::NotEq => format!(
+            "NOT (session_id = '{}' OR {})",
+            value,
+            by_name(format!("name = '{}'", value))
+        ),
+        CompareOp::Regex => format!(
+            "(regexp_matches(session_id, '{}') OR {})",
+            value,
+            by_name(format!("regexp_matches(name, '{}')", value))
+        ),
+        CompareOp::Gt => format!("session_id > '{}'", value),
+        CompareOp::Lt => format!("session_id < '{}'", value),
+        CompareOp::Gte => format!("session_id >= '{}'", value),
+        CompareOp::Lte => format!("session_id <= '{}'", value),
+    }
+}
+
+/// Build the predicate for a `%linked...` filter.
+///
+/// Bare `%linked` matches invocations with any link. `%linked=issue` matches
+/// a link kind or exact target, `%linked<>issue` excludes those, and
+/// `%linked~=issues/42` matches targets by regex.
+fn linked_clause(filter: &FieldFilter) -> String {
+    let value = filter.value.replace('\'', "''");
+    let linked = |predicate: String| {
+        format!("id IN (SELECT invocation_id FROM main.links WHERE {})", predicate)
+    };
+    let kind_or_target = format!("(kind = '{}' OR target = '{}')", value, value);
+
+    match filter.op {
+        CompareOp::NotEq if value.is_empty() => linked("true".to_string()),
+        CompareOp::Eq => linked(kind_or_target),
+        CompareOp::NotEq => format!("NOT {}", linked(kind_or_target)),
+        CompareOp::Regex => linked(format!("regexp_matches(target, '{}')", value)),
+        CompareOp::Gt => linked(format!("target > '{}'", value)),
+        CompareOp::Lt => linked(format!("target < '{}'", value)),
+        CompareOp::Gte => linked(format!("target >= '{}'", value)),
+        CompareOp::Lte => linked(format!("target <= '{}'", value)),
+    }
+}
+
+/// Compute `(LIMIT, OFFSET)` for a query's range selector.
+pub(crate) fn range_limit_offset(query: &Query, default_limit: usize) -> (usize, usize) {
+    // Determine limit and offset based on range selector semantics:
+    // - Single item (end: None): position N means OFFSET N-1, LIMIT 1
+    // - Last N (end: Some(0)): LIMIT N
+    // - Range (end: Some(M)): positions from N to M
+    if let Some(range) = query.range {
+        if range.is_single() {
+            // ~N = single item at position N (1-indexed)
+            (1, range.start.saturating_sub(1))
+        } else if range.is_last_n() {
+            // ~N: = last N items
+            (range.start, 0)
+        } else {
+            // ~N:~M = range from position N to M
+            // Position N is offset N-1, position M is offset M-1
+            // So we need M items starting at offset (start - 1)
+            let end_pos = range.end.unwrap_or(1);
+            let count = range.start.saturating_sub(end_pos) + 1;
+            (count, end_pos.saturating_sub(1))
+        }
+    } else {
+        (default_limit, 0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::init::initialize;
+    use crate::query::parse_query;
     use crate::Config;
     use tempfile::TempDir;
 
@@ -371,6 +654,23 @@ mod tests {
         (tmp, store)
     }
 
+    #[test]
+    fn test_path_filter_clause() {
+        let (cwd, home) = (Path::new("/work/app"), Path::new("/home/u"));
+        let resolve = |q: &str| resolve_path(parse_query(q).path.as_ref().unwrap(), cwd, home);
+        assert_eq!(resolve("."), Path::new("/work/app"));
+        assert_eq!(resolve("./src/"), Path::new("/work/app/src"));
+        assert_eq!(resolve("../"), Path::new("/work"));
+        assert_eq!(resolve("~/Projects/"), Path::new("/home/u/Projects"));
+        assert_eq!(resolve("/tmp/"), Path::new("/tmp"));
+
+        // Below the directory, not just sharing its prefix
+        assert_eq!(
+            path_clause(Path::new("/work/app/")),
+            "(cwd = '/work/app' OR starts_with(cwd, '/work/app/'))"
+        );
+    }
+
     #[test]
     fn test_write_and_count_invocation() {
         let (_tmp, store) = setup_store();
@@ -389,6 +689,56 @@ mod tests {
         assert_eq!(count, 1);
     }
 
+    #[test]
+    fn test_env_snapshot_roundtrip() {
+        let (_tmp, store) = setup_store();
+
+        let env = crate::context::snapshot_env([("RUSTFLAGS".to_string(), "-D warnings".to_string())]);
+        let with_env = InvocationRecord::new("s", "cargo build", "/p", 0, "test@client")
+            .with_metadata_entry("env", env);
+        let without_env = InvocationRecord::new("s", "ls", "/p", 0, "test@client");
+        store.write_invocation(&with_env).unwrap();
+        store.write_invocation(&without_env).unwrap();
+
+        let snapshot = store.env_snapshot(&with_env.id.to_string()).unwrap().unwrap();
+        assert_eq!(snapshot.get("RUSTFLAGS").map(String::as_str), Some("-D warnings"));
+        assert!(store.env_snapshot(&without_env.id.to_string()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_query_by_dimension() {
+        let (_tmp, store) = setup_store();
+
+        let dims = vec![("ticket".to_string(), "JIRA-123".to_string())];
+        let tagged = InvocationRecord::new("s", "make deploy", "/p", 0, "test@client").with_dims(&dims);
+        let other = InvocationRecord::new("s", "make test", "/p", 0, "test@client");
+        store.write_invocation(&tagged).unwrap();
+        store.write_invocation(&other).unwrap();
+
+        let results = store.query_invocations(&parse_query("%dim:ticket=JIRA-123")).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].cmd, "make deploy");
+    }
+
+    #[test]
+    fn test_query_by_terminal() {
+        let (_tmp, store) = setup_store();
+
+        let terminal = serde_json::json!({"program": "WezTerm", "mux": "tmux", "window": "work:2", "title": "vim"});
+        let in_tmux = InvocationRecord::new("s", "make", "/p", 0, "test@client").with_metadata_entry("terminal", terminal.clone());
+        let other = InvocationRecord::new("s", "ls", "/p", 0, "test@client");
+        store.write_invocation(&in_tmux).unwrap();
+        store.write_invocation(&other).unwrap();
+
+        for selector in ["%terminal=WezTerm", "%mux=tmux", "%window=work:2", "%title~=vi"] {
+            let results = store.query_invocations(&parse_query(selector)).unwrap();
+            assert_eq!(results.len(), 1, "{}", selector);
+            assert_eq!(results[0].cmd, "make");
+        }
+        assert_eq!(store.terminal_context(&in_tmux.id.to_string()).unwrap(), Some(terminal));
+        assert_eq!(store.terminal_context(&other.id.to_string()).unwrap(), None);
+    }
+
     #[test]
     fn test_write_and_query_invocation() {
         let (_tmp, store) = setup_store();
@@ -484,7 +834,7 @@ mod tests {
 
     #[test]
     fn test_duckdb_mode_write_and_count_invocation() {
-        let (_tmp, store) = setup_store_duckdb();
+        let store = Store::open(Config::in_memory()).unwrap();
 
         let record = InvocationRecord::new(
             "test-session",
@@ -502,7 +852,7 @@ mod tests {
 
     #[test]
     fn test_duckdb_mode_write_and_query_invocation() {
-        let (_tmp, store) = setup_store_duckdb();
+        let store = Store::open(Config::in_memory()).unwrap();
 
         let record = InvocationRecord::new(
             "test-session",
@@ -529,7 +879,7 @@ mod tests {
 
     #[test]
     fn test_duckdb_mode_recent_invocations() {
-        let (_tmp, store) = setup_store_duckdb();
+        let store = Store::open(Config::in_memory()).unwrap();
 
         // Write a few invocations
         for i in 0..3 {
@@ -697,4 +1047,74 @@ mod tests {
         let pending_after = store.get_pending_attempts().unwrap();
         assert_eq!(pending_after.len(), 1, "Attempt should still be pending");
     }
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_date_range_defaults_to_recent_window() {
+        let today = day("2026-10-15");
+        let range = date_range(&parse_query("~5"), today);
+        assert_eq!(range.start, Some(day("2026-10-08")));
+        assert_eq!(range.end, None);
+        assert_eq!(range.predicates(), vec!["date >= '2026-10-08'"]);
+    }
+
+    #[test]
+    fn test_date_range_from_time_filters() {
+        let today = day("2026-10-15");
+
+        let range = date_range(&parse_query("%date>=2026-01-01 %date<2026-02-01"), today);
+        assert_eq!(range.start, Some(day("2026-01-01")));
+        assert_eq!(range.end, Some(day("2026-01-31")));
+
+        let range = date_range(&parse_query("%date=2026-03-04"), today);
+        assert_eq!((range.start, range.end), (Some(day("2026-03-04")), Some(day("2026-03-04"))));
+
+        // Timestamps bound inclusively by their date
+        let range = date_range(&parse_query("%timestamp<2026-05-06T12:00:00"), today);
+        assert_eq!((range.start, range.end), (None, Some(day("2026-05-06"))));
+
+        // Non-time filters leave the default window in place
+        let range = date_range(&parse_query("%exit<>0"), today);
+        assert_eq!(range.start, Some(day("2026-10-08")));
+    }
+
+    #[test]
+    fn test_query_invocations_prunes_partitions() {
+        let (_tmp, store) = setup_store();
+
+        let mut old = InvocationRecord::new("test-session", "make old", "/tmp", 0, "test@client");
+        old.timestamp -= TimeDelta::days(30);
+        store.write_invocation(&old).unwrap();
+        let new = InvocationRecord::new("test-session", "make new", "/tmp", 0, "test@client");
+        store.write_invocation(&new).unwrap();
+
+        let results = store.query_invocations(&parse_query("")).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].cmd, "make new");
+
+        let sql = invocations_sql("main.invocations", &parse_query(""), 20, Utc::now().date_naive());
+        let conn = store.connection().unwrap();
+        let mut stmt = conn.prepare(&format!("EXPLAIN {}", sql)).unwrap();
+        let plan: Vec<String> = stmt
+            .query_map([], |row| row.get(1))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        let plan = plan.join("\n");
+
+        // The attempts scan must skip the old partition (and the seed one)
+        let scanned = plan
+            .lines()
+            .filter_map(|line| line.split("Scanning Files:").nth(1))
+            .map(|counts| counts.trim_matches(|c: char| c == ' ' || c == '│'))
+            .find_map(|counts| {
+                let (read, total) = counts.split_once('/')?;
+                Some((read.trim().parse::<usize>().ok()?, total.trim().parse::<usize>().ok()?))
+            });
+        let (read, total) = scanned.unwrap_or_else(|| panic!("no pruned scan in plan:\n{}", plan));
+        assert!(read < total, "expected partition pruning, got {}/{}", read, total);
+    }
 }
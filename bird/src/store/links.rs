@@ -0,0 +1,159 @@
+//! Links from invocations to issues, tickets and other external references.
+
+use chrono::{DateTime, Utc};
+use duckdb::params;
+
+use super::sessions::from_epoch_ms;
+use super::Store;
+use crate::{Error, Result};
+
+/// An external reference attached to an invocation.
+#[derive(Debug, Clone)]
+pub struct Link {
+    pub invocation_id: String,
+    /// What the target is: issue, pr, doc, ...
+    pub kind: String,
+    /// URL or identifier (e.g. `https://github.com/org/repo/issues/42`, `JIRA-123`).
+    pub target: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// DDL for invocation links.
+///
+/// Lives in the main DuckDB database in both storage modes, like
+/// `session_events`: links are small local annotations added after the
+/// fact. Created lazily on connect so existing installations pick it up.
+pub(crate) const LINKS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS main.links (
+    invocation_id UUID NOT NULL,
+    kind          VARCHAR NOT NULL,
+    target        VARCHAR NOT NULL,
+    created_at    TIMESTAMP NOT NULL
+);
+"#;
+
+impl Store {
+    /// Link an invocation to an external reference.
+    ///
+    /// Returns false if the invocation already has this link.
+    pub fn add_link(&self, invocation_id: &str, kind: &str, target: &str) -> Result<bool> {
+        if kind.is_empty() || target.is_empty() {
+            return Err(Error::Config("Link kind and target must not be empty".to_string()));
+        }
+        let conn = self.connection()?;
+
+        let exists: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM main.links WHERE invocation_id = ? AND kind = ? AND target = ?",
+            params![invocation_id, kind, target],
+            |row| row.get(0),
+        )?;
+        if exists {
+            return Ok(false);
+        }
+
+        conn.execute(
+            "INSERT INTO main.links VALUES (?, ?, ?, now())",
+            params![invocation_id, kind, target],
+        )?;
+        Ok(true)
+    }
+
+    /// Remove links from an invocation to `target`. Returns the number removed.
+    pub fn remove_link(&self, invocation_id: &str, target: &str) -> Result<usize> {
+        let conn = self.connection()?;
+        let removed = conn.execute(
+            "DELETE FROM main.links WHERE invocation_id = ? AND target = ?",
+            params![invocation_id, target],
+        )?;
+        Ok(removed)
+    }
+
+    /// Links attached to an invocation, oldest first.
+    pub fn links_for(&self, invocation_id: &str) -> Result<Vec<Link>> {
+        let conn = self.connection()?;
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT invocation_id::VARCHAR, kind, target, epoch_ms(created_at)
+            FROM main.links
+            WHERE invocation_id = ?
+            ORDER BY created_at
+            "#,
+        )?;
+
+        let rows = stmt.query_map(params![invocation_id], |row| {
+            Ok(Link {
+                invocation_id: row.get(0)?,
+                kind: row.get(1)?,
+                target: row.get(2)?,
+                created_at: from_epoch_ms(row.get(3)?),
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::init::initialize;
+    use crate::query::parse_query;
+    use crate::schema::InvocationRecord;
+    use crate::{Config, Store};
+    use tempfile::TempDir;
+
+    fn setup_store() -> (TempDir, Store) {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+        (tmp, store)
+    }
+
+    #[test]
+    fn test_add_and_remove_links() {
+        let (_tmp, store) = setup_store();
+        let inv = InvocationRecord::new("s", "make deploy", "/p", 0, "test@client");
+        store.write_invocation(&inv).unwrap();
+        let id = inv.id.to_string();
+        let url = "https://github.com/org/repo/issues/42";
+
+        assert!(store.add_link(&id, "issue", url).unwrap());
+        assert!(!store.add_link(&id, "issue", url).unwrap());
+        assert!(store.add_link(&id, "pr", "https://github.com/org/repo/pull/7").unwrap());
+
+        let links = store.links_for(&id).unwrap();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].kind, "issue");
+        assert_eq!(links[0].target, url);
+
+        assert_eq!(store.remove_link(&id, url).unwrap(), 1);
+        assert_eq!(store.links_for(&id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_linked_filter() {
+        let (_tmp, store) = setup_store();
+        let linked = InvocationRecord::new("s", "make deploy", "/p", 0, "test@client");
+        let unlinked = InvocationRecord::new("s", "make test", "/p", 0, "test@client");
+        store.write_invocation(&linked).unwrap();
+        store.write_invocation(&unlinked).unwrap();
+        store
+            .add_link(&linked.id.to_string(), "issue", "https://github.com/org/repo/issues/42")
+            .unwrap();
+
+        let results = store.query_invocations(&parse_query("%linked")).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].cmd, "make deploy");
+
+        let results = store.query_invocations(&parse_query("%linked~=issues/42")).unwrap();
+        assert_eq!(results.len(), 1);
+
+        let results = store.query_invocations(&parse_query("%linked=pr")).unwrap();
+        assert!(results.is_empty());
+    }
+}
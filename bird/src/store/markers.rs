@@ -0,0 +1,164 @@
+//! Markers - human context written into the events table (`shq mark`).
+//!
+//! A marker ("meeting start", "pomodoro 3") is a manual event with
+//! `event_type = 'marker'` and the text as its message. It hangs off the
+//! latest command of the session it was made in, so it shows up in that
+//! session's timeline; its time comes from the UUIDv7 event id.
+
+use chrono::{DateTime, Utc};
+use duckdb::params;
+use uuid::Uuid;
+
+use super::Store;
+use crate::schema::{EventRecord, EVENT_ORIGIN_MANUAL};
+use crate::{Error, Result};
+
+/// `event_type` of marker events.
+pub const EVENT_TYPE_MARKER: &str = "marker";
+
+/// A marker in a session timeline.
+#[derive(Debug, Clone)]
+pub struct Marker {
+    pub id: String,
+    /// Command the marker follows; `None` if the session had none yet.
+    pub invocation_id: Option<String>,
+    pub timestamp: DateTime<Utc>,
+    pub text: String,
+}
+
+/// When a UUIDv7 was generated.
+pub(crate) fn uuid_time(id: &Uuid) -> DateTime<Utc> {
+    id.get_timestamp()
+        .and_then(|ts| {
+            let (secs, nanos) = ts.to_unix();
+            DateTime::from_timestamp(secs as i64, nanos)
+        })
+        .unwrap_or_default()
+}
+
+impl Store {
+    /// Write a marker event for `session_id` with `text` as its message.
+    ///
+    /// The marker is attached to the session's latest command. Like other
+    /// manual events, markers survive reparsing.
+    pub fn add_marker(&self, session_id: &str, text: &str) -> Result<Marker> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(Error::Config("A marker needs some text".to_string()));
+        }
+
+        let conn = self.connection()?;
+        let latest: Option<String> = match conn.query_row(
+            "SELECT id::VARCHAR FROM invocations WHERE session_id = ? ORDER BY timestamp DESC LIMIT 1",
+            params![session_id],
+            |row| row.get(0),
+        ) {
+            Ok(id) => Some(id),
+            Err(duckdb::Error::QueryReturnedNoRows) => None,
+            Err(e) if e.to_string().contains("No files found") => None,
+            Err(e) => return Err(e.into()),
+        };
+        let invocation_uuid = match &latest {
+            Some(id) => Uuid::parse_str(id).map_err(|e| Error::Storage(format!("Invalid invocation id: {}", e)))?,
+            None => Uuid::nil(),
+        };
+
+        let mut record = EventRecord::new(
+            invocation_uuid,
+            self.config.client_id.clone(),
+            EVENT_ORIGIN_MANUAL,
+            Utc::now().date_naive(),
+        );
+        record.event_type = Some(EVENT_TYPE_MARKER.to_string());
+        record.message = Some(text.to_string());
+        record.origin = EVENT_ORIGIN_MANUAL.to_string();
+        self.write_events(std::slice::from_ref(&record))?;
+
+        Ok(Marker {
+            id: record.id.to_string(),
+            invocation_id: latest,
+            timestamp: uuid_time(&record.id),
+            text: text.to_string(),
+        })
+    }
+
+    /// Markers made in `session_id`, oldest first.
+    pub fn session_markers(&self, session_id: &str) -> Result<Vec<Marker>> {
+        let conn = self.connection()?;
+        let mut stmt = match conn.prepare(
+            r#"
+            SELECT e.id::VARCHAR, e.invocation_id::VARCHAR, e.message
+            FROM events e
+            JOIN invocations i ON i.id = e.invocation_id
+            WHERE i.session_id = ? AND e.event_type = ?
+            "#,
+        ) {
+            Ok(stmt) => stmt,
+            Err(e) if e.to_string().contains("No files found") => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let rows = stmt.query_map(params![session_id, EVENT_TYPE_MARKER], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?))
+        })?;
+
+        let mut markers = Vec::new();
+        for row in rows {
+            let (id, invocation_id, text) = row?;
+            let uuid = Uuid::parse_str(&id).map_err(|e| Error::Storage(format!("Invalid event id: {}", e)))?;
+            markers.push(Marker {
+                id,
+                invocation_id: Some(invocation_id),
+                timestamp: uuid_time(&uuid),
+                text: text.unwrap_or_default(),
+            });
+        }
+        markers.sort_by_key(|m| m.timestamp);
+        Ok(markers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::store::EventFilters;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_uuid_time() {
+        let before = Utc::now() - chrono::TimeDelta::milliseconds(1);
+        let at = uuid_time(&Uuid::now_v7());
+        assert!(at >= before && at <= Utc::now(), "{}", at);
+        assert_eq!(uuid_time(&Uuid::nil()), DateTime::<Utc>::default());
+    }
+
+    #[test]
+    fn test_markers_in_session() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        let record = InvocationRecord::new("zsh-1", "make", "/p", 0, "test@client");
+        let inv_id = record.id.to_string();
+        store.write_invocation(&record).unwrap();
+
+        let marker = store.add_marker("zsh-1", "  meeting start ").unwrap();
+        assert_eq!(marker.invocation_id.as_deref(), Some(inv_id.as_str()));
+        assert_eq!(marker.text, "meeting start");
+        store.add_marker("zsh-1", "meeting end").unwrap();
+        assert!(store.add_marker("zsh-1", " ").is_err());
+
+        let markers = store.session_markers("zsh-1").unwrap();
+        let texts: Vec<_> = markers.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(texts, ["meeting start", "meeting end"]);
+        assert!(store.session_markers("zsh-2").unwrap().is_empty());
+
+        // Markers are manual events: a reparse keeps them
+        store.delete_events_for_invocation(&inv_id).unwrap();
+        let filters = EventFilters { invocation_id: Some(inv_id), ..Default::default() };
+        assert_eq!(store.event_count(&filters).unwrap(), 2);
+    }
+}
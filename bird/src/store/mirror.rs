@@ -0,0 +1,301 @@
+//! Replicating a parquet-mode store with plain file copies (`rsync`).
+//!
+//! Data files never change once published (see `atomic`), so copying the
+//! data directory file by file can't tear a file. What a copy can catch is
+//! a store between states: a batch half written, or compaction's new file
+//! without the old ones removed yet. To tell, `$BIRD_ROOT/sync-token` holds
+//! a digest of the data files of a state the store was in. A mirror whose
+//! data files match the token it carries holds that state;
+//! [`verify_mirror`] checks that.
+//!
+//! Maintenance (compaction, archiving, tiering, rebalancing, dedup, pulls)
+//! and spool flushes rewrite the token when they finish. Shell hook writes
+//! don't: they only add files, and scanning the data directory on every
+//! command would cost more than the write. Refresh the token before
+//! copying a store that's being recorded to:
+//!
+//! ```bash
+//! shq sync-token --refresh
+//! rsync -a --delete --exclude '.tmp.*' --exclude 'blobs/capture/' \
+//!     ~/.local/share/bird/ backup:bird/
+//! ssh backup shq verify-mirror bird   # refresh and copy again until it passes
+//! ```
+//!
+//! Temp files (`.tmp.*`) and captures still being written (`blobs/capture`)
+//! aren't part of any state; the database file (`db/bird.duckdb`) is not
+//! covered by the token.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{atomic, Store};
+use crate::config::StorageMode;
+use crate::locks::{Lease, SYNC_TOKEN_LOCK};
+use crate::{Config, Error, Result};
+
+/// The data files of a consistent state of a store.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncToken {
+    pub written_at: DateTime<Utc>,
+    /// Data files, and their total size.
+    pub files: usize,
+    pub bytes: u64,
+    /// BLAKE3 of the sorted `path size` lines of the data files.
+    pub digest: String,
+}
+
+impl SyncToken {
+    /// The token for the data files under `data_dir`, as of now.
+    pub fn scan(data_dir: &Path) -> Result<Self> {
+        Ok(Self::of(&data_files(data_dir)?))
+    }
+
+    fn of(files: &BTreeMap<String, u64>) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        for (path, size) in files {
+            hasher.update(format!("{} {}\n", path, size).as_bytes());
+        }
+        Self {
+            written_at: Utc::now(),
+            files: files.len(),
+            bytes: files.values().sum(),
+            digest: hasher.finalize().to_hex().to_string(),
+        }
+    }
+
+    /// The token of the store at `bird_root`, if it has written one.
+    pub fn read(bird_root: &Path) -> Option<Self> {
+        let json = fs::read_to_string(Config::with_root(bird_root).sync_token_path()).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// Whether both describe the same data files.
+    pub fn same_files(&self, other: &SyncToken) -> bool {
+        self.digest == other.digest
+    }
+}
+
+/// What [`verify_mirror`] found.
+#[derive(Debug)]
+pub struct MirrorReport {
+    /// The token copied with the mirror, if any.
+    pub token: Option<SyncToken>,
+    /// The mirror's data files.
+    pub actual: SyncToken,
+    /// Temp files copied mid-write; readers could take them for data.
+    pub temp_files: Vec<String>,
+    /// Why the mirror's database couldn't be read, if it couldn't.
+    pub database_error: Option<String>,
+}
+
+impl MirrorReport {
+    /// Whether the mirror holds a state its source was in.
+    pub fn is_consistent(&self) -> bool {
+        self.token.as_ref().is_some_and(|token| token.same_files(&self.actual))
+            && self.temp_files.is_empty()
+            && self.database_error.is_none()
+    }
+}
+
+impl Store {
+    /// Rewrite the sync token from the data files as they are now.
+    ///
+    /// Scanning and replacing happen under [`SYNC_TOKEN_LOCK`], so when two
+    /// processes rewrite it the token left is from the later scan.
+    pub fn write_sync_token(&self) -> Result<SyncToken> {
+        let _lease = Lease::wait(&self.config, SYNC_TOKEN_LOCK, "sync token")?;
+        let token = SyncToken::scan(&self.config.data_dir())?;
+        let path = self.config.sync_token_path();
+        let temp = atomic::temp_path(&path);
+        fs::write(&temp, serde_json::to_vec_pretty(&token)?)?;
+        atomic::replace_file(&temp, &path)?;
+        Ok(token)
+    }
+
+    /// Mark the start of a maintenance operation that changes data files.
+    /// When the outermost one ends, the sync token is rewritten. Hook writes
+    /// don't take one (see the module docs).
+    pub(crate) fn sync_guard(&self) -> SyncGuard<'_> {
+        self.sync_depth.fetch_add(1, Ordering::SeqCst);
+        SyncGuard { store: self }
+    }
+}
+
+/// See [`Store::sync_guard`].
+pub(crate) struct SyncGuard<'a> {
+    store: &'a Store,
+}
+
+impl Drop for SyncGuard<'_> {
+    fn drop(&mut self) {
+        let store = self.store;
+        if store.sync_depth.fetch_sub(1, Ordering::SeqCst) != 1
+            || store.config.in_memory
+            || store.config.storage_mode != StorageMode::Parquet
+        {
+            return;
+        }
+        // Also after a failed operation: the token describes the files, not
+        // the operation
+        if let Err(e) = store.write_sync_token() {
+            tracing::warn!("couldn't update the sync token: {}", e);
+        }
+    }
+}
+
+/// Check that the parquet-mode store copied to `bird_root` holds a state
+/// its source was in.
+pub fn verify_mirror(bird_root: &Path) -> Result<MirrorReport> {
+    let config = Config::with_root(bird_root);
+    if !config.db_path().exists() {
+        return Err(Error::NotInitialized(bird_root.to_path_buf()));
+    }
+    let data_dir = config.data_dir();
+    let files = data_files(&data_dir)?;
+    let temp_files = walk(&data_dir)?
+        .into_iter()
+        .filter(|(path, _)| is_temp(path))
+        .map(|(path, _)| path)
+        .collect();
+
+    let attach = format!(
+        "ATTACH '{}' AS mirror (READ_ONLY)",
+        config.db_path().display().to_string().replace('\'', "''")
+    );
+    let database_error = duckdb::Connection::open_in_memory()
+        .and_then(|conn| conn.execute_batch(&attach))
+        .err()
+        .map(|e| e.to_string());
+
+    Ok(MirrorReport {
+        token: SyncToken::read(bird_root),
+        actual: SyncToken::of(&files),
+        temp_files,
+        database_error,
+    })
+}
+
+/// The data files under `data_dir` with their sizes, by relative path.
+fn data_files(data_dir: &Path) -> Result<BTreeMap<String, u64>> {
+    Ok(walk(data_dir)?.into_iter().filter(|(path, _)| !is_temp(path)).collect())
+}
+
+/// Temp files: not yet published, or abandoned mid-write.
+fn is_temp(path: &str) -> bool {
+    path.rsplit('/').next().is_some_and(|name| name.starts_with(".tmp."))
+}
+
+/// Every file under `root` but in-progress captures, by relative path.
+fn walk(root: &Path) -> Result<Vec<(String, u64)>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                if relative != "recent/blobs/capture" {
+                    dirs.push(path);
+                }
+            } else if file_type.is_file() {
+                // Removed since it was listed (compaction, a published temp)
+                match entry.metadata() {
+                    Ok(meta) => files.push((relative, meta.len())),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::store::InvocationBatch;
+    use tempfile::TempDir;
+
+    fn copy_dir(src: &Path, dst: &Path) {
+        fs::create_dir_all(dst).unwrap();
+        for entry in fs::read_dir(src).unwrap() {
+            let entry = entry.unwrap();
+            let target = dst.join(entry.file_name());
+            if entry.file_type().unwrap().is_dir() {
+                copy_dir(&entry.path(), &target);
+            } else {
+                fs::copy(entry.path(), target).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_mirror_of_a_consistent_state() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path().join("bird"));
+        initialize(&config).unwrap();
+        let store = Store::open(config.clone()).unwrap();
+        let record = InvocationRecord::new("s1", "make", "/tmp", 0, &config.client_id);
+        store.write_batch(&InvocationBatch::new(record).with_output("stdout", b"ok\n".to_vec())).unwrap();
+
+        // Hook writes leave the token alone; refreshing describes their files
+        assert!(SyncToken::read(&config.bird_root).is_none());
+        store.write_sync_token().unwrap();
+        let token = SyncToken::read(&config.bird_root).unwrap();
+        assert!(token.files > 0);
+        assert!(token.same_files(&SyncToken::scan(&config.data_dir()).unwrap()));
+
+        drop(store);
+        let mirror = tmp.path().join("mirror");
+        copy_dir(&config.bird_root, &mirror);
+        let report = verify_mirror(&mirror).unwrap();
+        assert!(report.is_consistent(), "{:?}", report);
+
+        // A file the token doesn't know about: copied mid-write
+        let extra = Config::with_root(&mirror).recent_dir().join("invocations/stray.parquet");
+        fs::create_dir_all(extra.parent().unwrap()).unwrap();
+        fs::write(&extra, b"PAR1").unwrap();
+        assert!(!verify_mirror(&mirror).unwrap().is_consistent());
+        fs::remove_file(&extra).unwrap();
+
+        let temp = Config::with_root(&mirror).recent_dir().join(".tmp.0123.x.parquet");
+        fs::write(&temp, b"PAR").unwrap();
+        let report = verify_mirror(&mirror).unwrap();
+        assert_eq!(report.temp_files.len(), 1);
+        assert!(!report.is_consistent());
+    }
+
+    #[test]
+    fn test_token_rewrites_are_serialized() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config.clone()).unwrap();
+
+        // A rewrite waits for the one in progress instead of racing it
+        let lease = Lease::acquire(&config, SYNC_TOKEN_LOCK, "sync token").unwrap();
+        std::thread::scope(|scope| {
+            let writer = scope.spawn(|| store.write_sync_token().unwrap());
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            assert!(SyncToken::read(&config.bird_root).is_none());
+            drop(lease);
+            let token = writer.join().unwrap();
+            assert_eq!(SyncToken::read(&config.bird_root), Some(token));
+        });
+    }
+}
@@ -12,18 +12,58 @@
 //! Use `start_invocation()` / `complete_invocation()` for the v5 API.
 //! The legacy `write_invocation()` still works for v4 compatibility.
 
+mod aggregate;
+mod aliases;
+mod artifacts;
 mod atomic;
+mod attach;
 mod attempts;
+mod atuin;
+mod bundle;
+mod catalog;
+mod coalesce;
 mod compact;
+mod compare;
+mod contexts;
+mod contract;
+mod corrections;
+mod dedup;
 mod events;
+mod export;
+mod feed;
+mod fsck;
+mod health;
+mod histfile;
+mod ingest;
 mod invocations;
+mod links;
+mod markers;
+mod mirror;
 mod outcomes;
+mod output_text;
+mod output_writer;
 mod outputs;
 mod pending;
+mod plan;
+mod rebalance;
+mod recent_index;
+mod running_stats;
+mod short_ids;
 mod remote;
+mod s3;
 mod sessions;
-
+mod snapshots;
+mod tiering;
+mod trash;
+mod typos;
+mod usage;
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
@@ -35,7 +75,10 @@ use duckdb::{
 };
 
 use crate::config::StorageMode;
-use crate::schema::{EventRecord, InvocationRecord, SessionRecord};
+use crate::extensions::ensure_extension;
+use crate::schema::{EventRecord, InvocationRecord, OutputRecord, SessionRecord};
+use crate::cancel::CancelToken;
+use crate::progress::{NoProgress, ProgressPhase, ProgressSink};
 use crate::{Config, Error, Result};
 
 /// Format a DuckDB Value to a human-readable string.
@@ -108,15 +151,47 @@ fn format_value(value: &Value) -> String {
 }
 
 // Re-export types from submodules
+pub use aggregate::{AggregateRow, DailyCount, DedupedCommand, GroupField};
+pub use artifacts::{artifact_name, find_artifacts, is_artifact_stream, Artifact, ARTIFACT_STREAM_PREFIX};
+pub use attach::attach_sql;
+pub use bundle::{read_bundle_manifest, unpack_bundle, BundleManifest, BundleStats, BUNDLE_EXTENSION};
+pub use catalog::{CatalogEntry, CatalogKind};
 pub use compact::{
     ArchiveStats, AutoCompactOptions, CleanOptions, CleanStats, CompactOptions, CompactStats,
     PruneStats,
 };
-pub use events::{EventFilters, EventSummary, FormatConfig, FormatRule};
+pub use coalesce::WriteCoalescer;
+pub use compare::{event_key, InvocationProfile};
+pub use contexts::{ContextEntry, ContextSummary, TimeEntry, TimePeriod, DEFAULT_CONTEXT_GAP_MINUTES};
+pub use contract::{CONTRACT_VERSION, CONTRACT_VIEWS};
+pub use dedup::{DedupStats, DEFAULT_DEDUP_MIN_BYTES};
+pub use events::{EventFilters, EventSummary, ExtractSummary, ExtractionReport, FormatConfig, FormatReason, FormatRule};
+pub use export::{ExportOptions, ExportStats};
+pub use feed::{FeedEntry, FeedOptions};
+pub use fsck::{FsckIssue, FsckReport};
+pub use health::{ClassHealth, CommandClass, DirHealth, DEFAULT_HEALTH_DAYS};
+pub use histfile::{HistImportStats, HISTFILE_SESSION_PREFIX};
+pub use ingest::SpoolFlushStats;
 pub use invocations::InvocationSummary;
+pub use links::Link;
+pub use markers::{Marker, EVENT_TYPE_MARKER};
+pub use mirror::{verify_mirror, MirrorReport, SyncToken};
+pub use corrections::{EventCorrection, EventEdit};
+pub use output_writer::{OutputWriter, StoredOutput};
 pub use outputs::OutputInfo;
 pub use pending::{is_runner_alive, RecoveryStats};
-pub use remote::{parse_since, PullOptions, PullStats, PushOptions, PushStats};
+pub use plan::Plan;
+pub use rebalance::RebalanceStats;
+pub use recent_index::RECENT_INDEX_SIZE;
+pub use running_stats::ExecutableStats;
+pub use short_ids::SHORT_ID_LEN;
+pub use remote::{PullOptions, PullStats, PushOptions, PushStats};
+pub use sessions::{SessionEntry, SessionSummary, SESSION_CLOSED, SESSION_NAMED};
+pub use snapshots::SnapshotInfo;
+pub use tiering::TierStats;
+pub use trash::TrashEntry;
+pub use typos::{Typo, EVENT_TYPE_COMMAND_NOT_FOUND};
+pub use usage::{BlobDedup, BlobUsage, StorageBreakdown, UsageEntry};
 
 // Re-export format detection types (defined below)
 // BuiltinFormat, FormatMatch, FormatSource are defined at the bottom of this file
@@ -134,6 +209,10 @@ pub struct InvocationBatch {
     /// Common streams: "stdout", "stderr", "combined".
     pub outputs: Vec<(String, Vec<u8>)>,
 
+    /// Outputs captured incrementally and already in storage
+    /// (see [`Store::begin_output`]).
+    pub stored_outputs: Vec<StoredOutput>,
+
     /// Session record (optional, created if not already registered).
     pub session: Option<SessionRecord>,
 
@@ -147,6 +226,7 @@ impl InvocationBatch {
         Self {
             invocation: Some(invocation),
             outputs: Vec::new(),
+            stored_outputs: Vec::new(),
             session: None,
             events: None,
         }
@@ -158,6 +238,12 @@ impl InvocationBatch {
         self
     }
 
+    /// Add an output stream captured with [`Store::begin_output`].
+    pub fn with_stored_output(mut self, output: StoredOutput) -> Self {
+        self.stored_outputs.push(output);
+        self
+    }
+
     /// Add a session record.
     pub fn with_session(mut self, session: SessionRecord) -> Self {
         self.session = Some(session);
@@ -171,6 +257,50 @@ impl InvocationBatch {
     }
 }
 
+/// Insert one row into `local.outputs` (DuckDB mode).
+const OUTPUT_INSERT_SQL: &str = "INSERT INTO local.outputs (id, invocation_id, stream, content_hash, byte_length, \
+     storage_type, storage_ref, content_type, date, sequence) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+
+/// The next sequence number for `stream` among a batch's outputs.
+fn next_sequence<'a>(sequences: &mut HashMap<&'a str, i32>, stream: &'a str) -> i32 {
+    let next = sequences.entry(stream).or_insert(0);
+    *next += 1;
+    *next - 1
+}
+
+/// Write records tagged with their batch's index in one group per date
+/// partition with `write`, skipping batches that already failed. A group
+/// that can't be written is written again one batch at a time, and the
+/// batches that still fail are recorded in `failed`.
+fn write_grouped<T: Clone>(
+    records: &[(usize, T)],
+    failed: &mut BTreeMap<usize, Error>,
+    date: impl Fn(&T) -> NaiveDate,
+    write: impl Fn(&[T]) -> Result<()>,
+) {
+    let mut by_date: BTreeMap<NaiveDate, Vec<&(usize, T)>> = BTreeMap::new();
+    for tagged in records.iter().filter(|(i, _)| !failed.contains_key(i)) {
+        by_date.entry(date(&tagged.1)).or_default().push(tagged);
+    }
+
+    for group in by_date.into_values() {
+        let records: Vec<T> = group.iter().map(|(_, record)| record.clone()).collect();
+        match write(&records) {
+            Ok(()) => continue,
+            Err(e) => tracing::debug!(records = records.len(), "grouped write failed, writing one batch at a time: {}", e),
+        }
+        let mut by_batch: BTreeMap<usize, Vec<T>> = BTreeMap::new();
+        for (i, record) in group {
+            by_batch.entry(*i).or_default().push(record.clone());
+        }
+        for (i, records) in by_batch {
+            if let Err(e) = write(&records) {
+                failed.insert(i, e);
+            }
+        }
+    }
+}
+
 /// Options for creating a database connection.
 ///
 /// Controls what gets loaded and attached when opening a connection.
@@ -191,6 +321,10 @@ pub struct ConnectionOptions {
     /// to include the attached data.
     pub attach_remotes: bool,
 
+    /// Defer attaching remotes until a query references them (default: true).
+    /// Only meaningful with `attach_remotes`. See `Store::attach_remotes_for_sql`.
+    pub lazy_remotes: bool,
+
     /// Attach project database if in a project directory (default: true).
     pub attach_project: bool,
 
@@ -208,17 +342,27 @@ impl ConnectionOptions {
     pub fn full() -> Self {
         Self {
             attach_remotes: true,
+            lazy_remotes: true,
             attach_project: true,
             create_ephemeral_views: true,
             run_migration: false,
         }
     }
 
+    /// Create options for a full connection that attaches every remote up front.
+    pub fn eager() -> Self {
+        Self {
+            lazy_remotes: false,
+            ..Self::full()
+        }
+    }
+
     /// Create options for a minimal connection (no attachments).
     /// Useful for write operations that don't need remote data.
     pub fn minimal() -> Self {
         Self {
             attach_remotes: false,
+            lazy_remotes: false,
             attach_project: false,
             create_ephemeral_views: false,
             run_migration: false,
@@ -229,6 +373,7 @@ impl ConnectionOptions {
     pub fn for_migration() -> Self {
         Self {
             attach_remotes: false,
+            lazy_remotes: false,
             attach_project: false,
             create_ephemeral_views: false,
             run_migration: true,
@@ -236,45 +381,210 @@ impl ConnectionOptions {
     }
 }
 
-/// Ensure a DuckDB extension is loaded, installing if necessary.
-///
-/// Attempts in order:
-/// 1. LOAD (extension might already be available)
-/// 2. INSTALL from default repository, then LOAD
-/// 3. INSTALL FROM community, then LOAD
+/// Names of the databases currently attached to the connection.
+fn attached_databases(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT database_name FROM duckdb_databases()")?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(names)
+}
+
+/// Whether this connection already has the `remotes_*()` macros.
+fn remote_macros_exist(conn: &Connection) -> Result<bool> {
+    let exists = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM duckdb_functions() WHERE function_name = 'remotes_invocations'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
+/// Remotes among `remotes` that `sql` refers to.
+fn referenced_remotes<'a>(
+    sql: &str,
+    remotes: &[&'a crate::RemoteConfig],
+) -> Vec<&'a crate::RemoteConfig> {
+    let sql = sql.to_lowercase();
+    if ["remotes.", "remotes_", "unified."].iter().any(|s| sql.contains(s)) {
+        return remotes.to_vec();
+    }
+
+    remotes
+        .iter()
+        .copied()
+        .filter(|r| {
+            let macro_prefix = format!("remote_{}_", r.name.replace(['-', '.'], "_"));
+            sql.contains(&r.schema_name().to_lowercase()) || sql.contains(&macro_prefix.to_lowercase())
+        })
+        .collect()
+}
+
+/// Fragments of (lowercased) DuckDB/httpfs errors caused by bad or missing credentials.
+const AUTH_ERROR_MARKERS: &[&str] = &[
+    "http 401",
+    "http 403",
+    "forbidden",
+    "unauthorized",
+    "access denied",
+    "accessdenied",
+    "invalidaccesskeyid",
+    "signaturedoesnotmatch",
+    "expiredtoken",
+    "credential",
+];
+
+/// Turn credential failures talking to `remote` into [`Error::RemoteAuth`].
+fn remote_error(remote: &str, err: Error) -> Error {
+    let detail = err.to_string();
+    let lower = detail.to_lowercase();
+    if AUTH_ERROR_MARKERS.iter().any(|marker| lower.contains(marker)) {
+        Error::RemoteAuth {
+            remote: remote.to_string(),
+            detail,
+        }
+    } else {
+        err
+    }
+}
+
+/// Run a remote's ATTACH, giving up after its `attach_timeout` (if set).
 ///
-/// Returns Ok(true) if loaded successfully, Ok(false) if extension unavailable.
-fn ensure_extension(conn: &Connection, name: &str) -> Result<bool> {
-    // Try loading directly first (already installed/cached)
-    if conn.execute(&format!("LOAD {}", name), []).is_ok() {
-        return Ok(true);
+/// The ATTACH runs on a cloned connection so it can be interrupted; attached
+/// databases are shared by every connection to the same database instance.
+fn attach_with_timeout(conn: &Connection, remote: &crate::RemoteConfig) -> Result<()> {
+    tracing::debug!(remote = %remote.name, timeout = ?remote.attach_timeout, "attaching remote");
+    let Some(timeout) = remote.attach_timeout else {
+        conn.execute(&remote.attach_sql(), [])
+            .map_err(|e| remote_error(&remote.name, e.into()))?;
+        return Ok(());
+    };
+
+    let worker = conn.try_clone()?;
+    let interrupt = worker.interrupt_handle();
+    let attach_sql = remote.attach_sql();
+    let (tx, rx) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(worker.execute(&attach_sql, []).map(|_| ()));
+    });
+
+    match rx.recv_timeout(Duration::from_secs(timeout)) {
+        Ok(result) => result.map_err(|e| remote_error(&remote.name, e.into())),
+        Err(_) => {
+            interrupt.interrupt();
+            Err(Error::Storage(format!(
+                "attach timed out after {}s",
+                timeout
+            )))
+        }
     }
+}
 
-    // Try installing from default repository
-    if conn.execute(&format!("INSTALL {}", name), []).is_ok()
-        && conn.execute(&format!("LOAD {}", name), []).is_ok()
-    {
-        return Ok(true);
+/// Fail if the database records a schema version other than this build's.
+///
+/// Stores from before `bird_meta` existed have no version and are accepted.
+fn check_schema_version(conn: &Connection) -> Result<()> {
+    let found: Option<String> = conn
+        .query_row("SELECT value FROM bird_meta WHERE key = 'schema_version'", [], |row| row.get(0))
+        .ok();
+    match found {
+        Some(found) if found != crate::schema::BIRD_SCHEMA_VERSION => Err(Error::SchemaVersionMismatch {
+            found,
+            expected: crate::schema::BIRD_SCHEMA_VERSION.to_string(),
+        }),
+        _ => Ok(()),
     }
+}
 
-    // Try installing from community repository
-    if conn.execute(&format!("INSTALL {} FROM community", name), []).is_ok()
-        && conn.execute(&format!("LOAD {}", name), []).is_ok()
-    {
-        return Ok(true);
+/// Record this shq as `bird_meta.shq_version` if it's the newest to use the
+/// store, so an older one running into what it wrote can say so (see
+/// [`crate::version::store_mismatch_hint`]). Best-effort.
+fn record_shq_version(conn: &Connection) {
+    use crate::version::{compare, SHQ_VERSION};
+
+    let found: Option<String> = conn
+        .query_row("SELECT value FROM bird_meta WHERE key = 'shq_version'", [], |row| row.get(0))
+        .ok();
+    if found.is_some_and(|found| compare(&found, SHQ_VERSION) != std::cmp::Ordering::Less) {
+        return;
     }
+    let _ = conn.execute(
+        "INSERT INTO bird_meta (key, value, updated_at) VALUES ('shq_version', ?, now())
+         ON CONFLICT (key) DO UPDATE SET value = excluded.value, updated_at = now()",
+        params![SHQ_VERSION],
+    );
+}
 
-    Ok(false)
+/// `SET VARIABLE blob_roots` for the given roots (read by `resolve_storage_ref`).
+pub(crate) fn blob_roots_sql(roots: &[String]) -> String {
+    // Format as SQL array literal
+    let roots_sql: String = roots
+        .iter()
+        .map(|r| format!("'{}'", r.replace('\'', "''")))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("SET VARIABLE blob_roots = [{}]", roots_sql)
 }
 
+/// Macros that turn a storage ref into paths to read (see `setup_blob_resolution`).
+pub(crate) const BLOB_RESOLUTION_MACROS: &str = r#"
+-- Helper: check if ref is inline data (scalarfs data: protocol)
+CREATE OR REPLACE MACRO is_inline_data(ref) AS (
+    ref[:5] = 'data:' OR ref[:5] = 'data+'
+);
+
+-- Helper: check if ref is a relative file: path
+CREATE OR REPLACE MACRO is_file_ref(ref) AS (
+    ref[:5] = 'file:'
+);
+
+-- Resolve storage ref to list of paths for pathvariable:
+-- - Inline data: pass through as single-element list
+-- - file: refs: expand to glob patterns across all blob_roots
+-- - Other (absolute paths): pass through
+CREATE OR REPLACE MACRO resolve_storage_ref(ref) AS (
+    CASE
+        WHEN is_inline_data(ref) THEN [ref]
+        WHEN is_file_ref(ref) THEN
+            [format('{}/{}*', root, ref[6:]) FOR root IN getvariable('blob_roots')]
+        ELSE [ref]
+    END
+);
+"#;
+
+/// Roots of the in-memory stores open in this process (see
+/// `Store::open_in_memory`).
+static IN_MEMORY_ROOTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
 /// A BIRD store for reading and writing records.
 pub struct Store {
     config: Config,
+    /// Connection reused by `connect()` while inside `with_connection`.
+    shared: Mutex<Option<Connection>>,
+    /// Where long-running operations report progress.
+    progress: Arc<dyn ProgressSink>,
+    /// Checked by long-running operations between units of work.
+    cancel: CancelToken,
+    /// Nesting of operations changing data files (see `mirror`).
+    sync_depth: AtomicUsize,
+}
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        if self.config.in_memory {
+            let mut roots = IN_MEMORY_ROOTS.lock().unwrap_or_else(|e| e.into_inner());
+            roots.retain(|root| *root != self.config.bird_root);
+        }
+    }
 }
 
 impl Store {
-    /// Open an existing BIRD store.
+    /// Open an existing BIRD store, or create an in-memory one for a
+    /// [`Config::in_memory`] config.
     pub fn open(config: Config) -> Result<Self> {
+        if config.in_memory {
+            return Self::open_in_memory(config);
+        }
         if !config.db_path().exists() {
             return Err(Error::NotInitialized(config.bird_root.clone()));
         }
@@ -282,7 +592,109 @@ impl Store {
         // (or files created by shell redirection / DuckDB at a loose umask)
         // are re-chmodded to owner-only on every open.
         crate::perms::repair_permissions(&config);
-        Ok(Self { config })
+        Ok(Self {
+            config,
+            shared: Mutex::new(None),
+            progress: Arc::new(NoProgress),
+            cancel: CancelToken::new(),
+            sync_depth: AtomicUsize::new(0),
+        })
+    }
+
+    /// A store whose database lives in memory until it's dropped.
+    ///
+    /// The database's connection is held as the shared connection for the
+    /// store's lifetime, so every `connect()` gets a handle on it. Opening
+    /// the same config again while the store is open fails, rather than
+    /// handing out a second, empty database.
+    fn open_in_memory(config: Config) -> Result<Self> {
+        {
+            let mut roots = IN_MEMORY_ROOTS.lock().unwrap_or_else(|e| e.into_inner());
+            if roots.contains(&config.bird_root) {
+                return Err(Error::Config(format!(
+                    "in-memory store {} is already open and can't be opened again",
+                    config.bird_root.display()
+                )));
+            }
+            roots.push(config.bird_root.clone());
+        }
+        // Dropping the store from here on releases its root
+        let store = Self {
+            config,
+            shared: Mutex::new(None),
+            progress: Arc::new(NoProgress),
+            cancel: CancelToken::new(),
+            sync_depth: AtomicUsize::new(0),
+        };
+        let conn = crate::init::initialize_in_memory(&store.config)?;
+        store.load_extensions(&conn)?;
+        *store.shared.lock().unwrap_or_else(|e| e.into_inner()) = Some(conn);
+        Ok(store)
+    }
+
+    /// Report progress of long-running operations (compaction, archiving,
+    /// push/pull, event backfill, migration) to `sink`.
+    pub fn with_progress(mut self, sink: Arc<dyn ProgressSink>) -> Self {
+        self.progress = sink;
+        self
+    }
+
+    /// The sink long-running operations report progress to.
+    pub fn progress(&self) -> &dyn ProgressSink {
+        self.progress.as_ref()
+    }
+
+    /// Start a progress phase, finished when the returned guard is dropped.
+    pub(crate) fn progress_phase(&self, phase: &str, total: Option<u64>) -> ProgressPhase<'_> {
+        ProgressPhase::start(self.progress(), phase, total)
+    }
+
+    /// Stop long-running operations (compaction, archiving, push/pull,
+    /// event backfill) at the next safe boundary once `token` is cancelled.
+    pub fn with_cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// The token long-running operations check for cancellation.
+    pub fn cancel_token(&self) -> &CancelToken {
+        &self.cancel
+    }
+
+    /// Fail with [`Error::Cancelled`] if the store's token was cancelled.
+    pub(crate) fn checkpoint(&self) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Run `f` with a connection that every `connect()` inside it reuses.
+    ///
+    /// Store methods called from `f` get clones of the shared connection, so
+    /// the database file is opened and extensions are loaded only once. The
+    /// shared connection (and its file lock) is released when `f` returns.
+    /// Nested calls reuse the outer connection.
+    pub fn with_connection<T>(&self, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+        if self.shared_connection()?.is_some() {
+            let conn = self.connection()?;
+            return f(&conn);
+        }
+
+        let conn = self.connection()?;
+        *self.shared.lock().unwrap_or_else(|e| e.into_inner()) = Some(conn.try_clone()?);
+        let result = f(&conn);
+        self.shared.lock().unwrap_or_else(|e| e.into_inner()).take();
+        result
+    }
+
+    /// A fresh handle on the `with_connection` database, if one is active.
+    fn shared_connection(&self) -> Result<Option<Connection>> {
+        let shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        match shared.as_ref() {
+            Some(conn) => Ok(Some(conn.try_clone()?)),
+            None => Ok(None),
+        }
     }
 
     /// Open a DuckDB connection with retry and exponential backoff.
@@ -326,9 +738,13 @@ impl Store {
         }
 
         // All retries exhausted
-        Err(last_error
-            .map(|e| e.into())
-            .unwrap_or_else(|| Error::Storage("Failed to open database after retries".to_string())))
+        match last_error {
+            Some(e) => {
+                tracing::debug!(path = %db_path.display(), "lock conflict: {}", e);
+                Err(Error::LockConflict { path: db_path })
+            }
+            None => Err(Error::Storage("Failed to open database after retries".to_string())),
+        }
     }
 
     /// Get a DuckDB connection with full features (attachments, ephemeral views).
@@ -356,31 +772,13 @@ impl Store {
     ///
     /// Uses retry with exponential backoff to handle concurrent access.
     pub fn connect(&self, opts: ConnectionOptions) -> Result<Connection> {
-        let conn = self.open_connection_with_retry()?;
-
-        // ===== Load required extensions =====
-        // Uses default extension directory (typically ~/.duckdb/extensions)
-        // Falls back to community repository if not in default
-        conn.execute("SET allow_community_extensions = true", [])?;
-
-        for ext in ["parquet", "icu"] {
-            if !ensure_extension(&conn, ext)? {
-                return Err(Error::Extension(format!(
-                    "Required extension '{}' could not be loaded",
-                    ext
-                )));
-            }
-        }
-
-        // Optional community extensions - warn if missing
-        for (ext, desc) in [
-            ("scalarfs", "data: URL support for inline blobs"),
-            ("duck_hunt", "log/output parsing for event extraction"),
-        ] {
-            if !ensure_extension(&conn, ext)? {
-                eprintln!("Warning: {} extension not available ({})", ext, desc);
-            }
-        }
+        let _profile = crate::profile::during(crate::profile::Phase::Connect);
+        // Inside with_connection, clone the shared connection: same database
+        // instance, so extensions are already loaded
+        let conn = match self.shared_connection()? {
+            Some(conn) => conn,
+            None => self.open_connection_with_extensions()?,
+        };
 
         // Set file search path so views resolve relative paths correctly
         conn.execute(
@@ -396,15 +794,30 @@ impl Store {
             self.migrate_to_new_schema(&conn)?;
         }
 
+        // ===== Always ensure local annotation tables exist =====
+        // Added after v5; created lazily so existing stores pick them up
+        conn.execute_batch(sessions::SESSION_EVENTS_DDL)?;
+        conn.execute_batch(links::LINKS_DDL)?;
+        conn.execute_batch(corrections::EVENT_CORRECTIONS_DDL)?;
+        conn.execute_batch(recent_index::RECENT_INDEX_DDL)?;
+        conn.execute_batch(running_stats::RUNNING_STATS_DDL)?;
+        conn.execute_batch(short_ids::SHORT_IDS_DDL)?;
+        conn.execute_batch(aliases::ALIASES_DDL)?;
+        crate::init::ensure_output_sequence(&conn, &self.config)?;
+        crate::init::ensure_event_origin(&conn, &self.config)?;
+        contract::ensure_contract_views(&conn)?;
+        record_shq_version(&conn);
+
         // ===== Always set up blob resolution =====
         // S3 credentials needed before blob_roots is used
         self.setup_s3_credentials(&conn)?;
         self.setup_blob_resolution(&conn)?;
+        output_text::register_output_text(&conn, &self.config.data_dir())?;
 
         // ===== Optional: Attach remotes and create access macros =====
-        if opts.attach_remotes && !self.config.remotes.is_empty() {
-            self.attach_remotes(&conn)?;
-            self.create_remote_macros(&conn)?;
+        // Lazy connections attach on first reference (attach_remotes_for_sql)
+        if opts.attach_remotes && !opts.lazy_remotes && !self.config.remotes.is_empty() {
+            self.attach_all_remotes(&conn)?;
         }
 
         // ===== Optional: Attach project database =====
@@ -418,9 +831,63 @@ impl Store {
             self.create_cwd_macros(&conn)?;
         }
 
+        // ===== Optional: Create work-context macros =====
+        // contexts() / invocation_contexts() cluster invocations by root+branch
+        if opts.create_ephemeral_views {
+            conn.execute_batch(contexts::CONTEXT_MACROS)?;
+        }
+
         Ok(conn)
     }
 
+    /// Report which optional extensions (and their features) are active.
+    pub fn capabilities(&self) -> Result<crate::Capabilities> {
+        let conn = self.connect(ConnectionOptions::minimal())?;
+        crate::Capabilities::detect(&conn)
+    }
+
+    /// Open the database file and load the required and optional extensions.
+    fn open_connection_with_extensions(&self) -> Result<Connection> {
+        let conn = self.open_connection_with_retry()?;
+        check_schema_version(&conn)?;
+        self.load_extensions(&conn)?;
+        Ok(conn)
+    }
+
+    /// Load the extensions every connection needs and apply display settings.
+    fn load_extensions(&self, conn: &Connection) -> Result<()> {
+        // ===== Load required extensions =====
+        // Uses default extension directory (typically ~/.duckdb/extensions)
+        // Falls back to community repository if not in default
+        conn.execute("SET allow_community_extensions = true", [])?;
+
+        let repository = self.config.extensions.repository.as_deref();
+        for ext in ["parquet", "icu"] {
+            if !ensure_extension(conn, ext, repository)? {
+                return Err(Error::ExtensionMissing { name: ext.to_string() });
+            }
+        }
+
+        // Timestamps are stored in UTC; `timezone('UTC', ts)` renders them in
+        // the session zone, which is the system's unless configured
+        if let Some(tz) = &self.config.display.timezone {
+            conn.execute(&format!("SET TimeZone = '{}'", tz.replace('\'', "''")), [])
+                .map_err(|e| Error::Config(format!("Invalid display.timezone '{}': {}", tz, e)))?;
+        }
+
+        // Optional community extensions - warn if missing
+        for (ext, desc) in [
+            ("scalarfs", "data: URL support for inline blobs"),
+            ("duck_hunt", "log/output parsing for event extraction"),
+        ] {
+            if !ensure_extension(conn, ext, repository)? {
+                tracing::warn!(extension = ext, "{} extension not available ({})", ext, desc);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Attach project-level `.bird/` database if we're in a project directory.
     ///
     /// The project database is attached as read-only under schema "project".
@@ -441,6 +908,11 @@ impl Store {
             return Ok(());
         }
 
+        // Already attached through a connection shared by with_connection
+        if attached_databases(conn)?.iter().any(|name| name == "project") {
+            return Ok(());
+        }
+
         // Attach as read-only
         let attach_sql = format!(
             "ATTACH '{}' AS project (READ_ONLY)",
@@ -449,7 +921,7 @@ impl Store {
 
         if let Err(e) = conn.execute(&attach_sql, []) {
             // Log but don't fail - project DB might be locked or inaccessible
-            eprintln!("Note: Could not attach project database: {}", e);
+            tracing::warn!(path = %project.db_path.display(), "could not attach project database: {}", e);
         }
 
         Ok(())
@@ -478,7 +950,8 @@ impl Store {
         // moving data from old tables/views to new structure.
         // TODO: Implement full data migration if needed
 
-        eprintln!("Note: Migrating to new schema architecture...");
+        tracing::info!("migrating to new schema architecture");
+        let _progress = self.progress_phase("migrate schema", None);
 
         // Create core schemas
         conn.execute_batch(
@@ -671,6 +1144,15 @@ impl Store {
     /// Set up S3 credentials for all remotes that use S3.
     /// This is called early so that blob resolution can access S3 paths.
     fn setup_s3_credentials(&self, conn: &Connection) -> Result<()> {
+        // The cold tier uses the AWS environment credentials it's written with
+        if let Some(cold_url) = self.config.blobs.cold_tier_url().filter(|url| url.starts_with("s3://")) {
+            if let Some(credentials) = s3::S3Credentials::from_env() {
+                if let Err(e) = conn.execute(&credentials.secret_sql("bird_cold_tier", &cold_url), []) {
+                    tracing::warn!("failed to create S3 secret for the cold tier: {}", e);
+                }
+            }
+        }
+
         // Check if any remote uses S3
         let has_s3 = self.config.remotes.iter().any(|r| {
             r.remote_type == crate::config::RemoteType::S3
@@ -689,7 +1171,7 @@ impl Store {
                         remote.name, provider
                     );
                     if let Err(e) = conn.execute(&secret_sql, []) {
-                        eprintln!("Warning: Failed to create S3 secret for {}: {}", remote.name, e);
+                        tracing::warn!(remote = %remote.name, "failed to create S3 secret: {}", e);
                     }
                 }
             }
@@ -705,57 +1187,54 @@ impl Store {
     /// - `file:path` - relative path, resolved against blob_roots
     /// - Absolute paths (`s3://`, `/path/`) - used directly
     fn setup_blob_resolution(&self, conn: &Connection) -> Result<()> {
-        let blob_roots = self.config.blob_roots();
-
-        // Format as SQL array literal
-        let roots_sql: String = blob_roots
-            .iter()
-            .map(|r| format!("'{}'", r.replace('\'', "''")))
-            .collect::<Vec<_>>()
-            .join(", ");
-
-        // Set blob_roots variable
-        conn.execute(&format!("SET VARIABLE blob_roots = [{}]", roots_sql), [])?;
+        conn.execute(&blob_roots_sql(&self.config.blob_roots()), [])?;
+        conn.execute_batch(BLOB_RESOLUTION_MACROS)?;
+        Ok(())
+    }
 
-        // Helper: check if ref is inline data (scalarfs data: protocol)
-        conn.execute(
-            r#"CREATE OR REPLACE MACRO is_inline_data(ref) AS (
-                ref[:5] = 'data:' OR ref[:5] = 'data+'
-            )"#,
-            [],
-        )?;
+    /// Attach every auto-attach remote and rebuild the remote macros and views.
+    ///
+    /// Lazy connections (the default) skip this at open time; call it before
+    /// running SQL that needs all remote data on a raw connection.
+    pub fn attach_all_remotes(&self, conn: &Connection) -> Result<()> {
+        let remotes = self.config.auto_attach_remotes();
+        self.ensure_remotes_attached(conn, &remotes)
+    }
 
-        // Helper: check if ref is a relative file: path
-        conn.execute(
-            r#"CREATE OR REPLACE MACRO is_file_ref(ref) AS (
-                ref[:5] = 'file:'
-            )"#,
-            [],
-        )?;
+    /// Attach the remotes that `sql` references, if not already attached.
+    ///
+    /// `remotes.*`, `unified.*` and `remotes_*()` need every auto-attach
+    /// remote; `remote_<name>` (schema or macro) needs just that one. SQL
+    /// that only touches local data attaches nothing.
+    pub fn attach_remotes_for_sql(&self, conn: &Connection, sql: &str) -> Result<()> {
+        let remotes = referenced_remotes(sql, &self.config.auto_attach_remotes());
+        self.ensure_remotes_attached(conn, &remotes)
+    }
 
-        // Resolve storage ref to list of paths for pathvariable:
-        // - Inline data: pass through as single-element list
-        // - file: refs: expand to glob patterns across all blob_roots
-        // - Other (absolute paths): pass through
-        conn.execute(
-            r#"CREATE OR REPLACE MACRO resolve_storage_ref(ref) AS (
-                CASE
-                    WHEN is_inline_data(ref) THEN [ref]
-                    WHEN is_file_ref(ref) THEN
-                        [format('{}/{}*', root, ref[6:]) FOR root IN getvariable('blob_roots')]
-                    ELSE [ref]
-                END
-            )"#,
-            [],
-        )?;
+    /// Attach whichever of `remotes` are missing, then rebuild macros and views.
+    fn ensure_remotes_attached(&self, conn: &Connection, remotes: &[&crate::RemoteConfig]) -> Result<()> {
+        let attached = attached_databases(conn)?;
+        let missing: Vec<&crate::RemoteConfig> = remotes
+            .iter()
+            .copied()
+            .filter(|r| !attached.contains(&r.schema_name()))
+            .collect();
+        if missing.is_empty() {
+            // Attachments are shared by cloned connections but the TEMPORARY
+            // macros are not, so a clone may still need them
+            if remotes.is_empty() || remote_macros_exist(conn)? {
+                return Ok(());
+            }
+        } else {
+            self.attach_remotes(conn, &missing)?;
+        }
 
-        Ok(())
+        self.create_remote_macros(conn)
     }
 
-    /// Attach configured remotes to the connection.
+    /// Attach remotes to the connection.
     /// Note: S3 credentials are already set up by setup_s3_credentials().
-    fn attach_remotes(&self, conn: &Connection) -> Result<()> {
-        let remotes = self.config.auto_attach_remotes();
+    fn attach_remotes(&self, conn: &Connection, remotes: &[&crate::RemoteConfig]) -> Result<()> {
 
         // Collect all file remote data directories to add to file_search_path
         let remote_data_dirs: Vec<String> = remotes
@@ -784,15 +1263,14 @@ impl Store {
 
             let new_path = paths.join(",");
             if let Err(e) = conn.execute(&format!("SET file_search_path = '{}'", new_path), []) {
-                eprintln!("Warning: Failed to set file_search_path: {}", e);
+                tracing::warn!(path = %new_path, "failed to set file_search_path: {}", e);
             }
         }
 
         // Attach each remote
-        for remote in &remotes {
-            let attach_sql = remote.attach_sql();
-            if let Err(e) = conn.execute(&attach_sql, []) {
-                eprintln!("Warning: Failed to attach remote {}: {}", remote.name, e);
+        for remote in remotes {
+            if let Err(e) = attach_with_timeout(conn, remote) {
+                tracing::warn!(remote = %remote.name, "failed to attach remote: {}", e);
             }
         }
 
@@ -837,7 +1315,15 @@ impl Store {
     ///
     /// Usage: `SELECT * FROM remotes_invocations()` or `SELECT * FROM remote_<name>_invocations()`
     fn create_remote_macros(&self, conn: &Connection) -> Result<()> {
-        let remotes = self.config.auto_attach_remotes();
+        // Only remotes that actually attached; one that failed or timed out
+        // would otherwise break the remotes.* union views
+        let attached = attached_databases(conn)?;
+        let remotes: Vec<&crate::RemoteConfig> = self
+            .config
+            .auto_attach_remotes()
+            .into_iter()
+            .filter(|r| attached.contains(&r.schema_name()))
+            .collect();
         if remotes.is_empty() {
             return Ok(());
         }
@@ -865,7 +1351,7 @@ impl Store {
                     table = table
                 );
                 if let Err(e) = conn.execute(&sql, []) {
-                    eprintln!("Warning: Failed to create macro {}: {}", macro_name, e);
+                    tracing::warn!(remote = %name, "failed to create macro {}: {}", macro_name, e);
                 }
             }
         }
@@ -891,7 +1377,7 @@ impl Store {
                 union = union_parts.join(" UNION ALL BY NAME ")
             );
             if let Err(e) = conn.execute(&sql, []) {
-                eprintln!("Warning: Failed to create remotes_{} macro: {}", table, e);
+                tracing::warn!("failed to create remotes_{} macro: {}", table, e);
             }
         }
 
@@ -921,7 +1407,7 @@ impl Store {
                 union = union_parts.join(" UNION ALL BY NAME ")
             );
             if let Err(e) = conn.execute(&remotes_sql, []) {
-                eprintln!("Warning: Failed to rebuild remotes.{} view: {}", table, e);
+                tracing::warn!("failed to rebuild remotes.{} view: {}", table, e);
             }
         }
 
@@ -937,7 +1423,7 @@ impl Store {
                 SELECT * FROM main.events UNION ALL BY NAME SELECT * FROM remotes.events;
         "#;
         if let Err(e) = conn.execute_batch(unified_views) {
-            eprintln!("Warning: Failed to rebuild unified views: {}", e);
+            tracing::warn!("failed to rebuild unified views: {}", e);
         }
 
         Ok(())
@@ -984,6 +1470,13 @@ impl Store {
 
     /// Manually attach a specific remote.
     pub fn attach_remote(&self, conn: &Connection, remote: &crate::RemoteConfig) -> Result<()> {
+        if !remote.remote_type.is_attachable() {
+            return Err(Error::Config(format!(
+                "Remote '{}' is an {} server, which can only be pushed to",
+                remote.name, remote.remote_type
+            )));
+        }
+
         // Set up credentials
         if let Some(provider) = &remote.credential_provider {
             if remote.remote_type == crate::config::RemoteType::S3 {
@@ -991,7 +1484,8 @@ impl Store {
                     "CREATE SECRET IF NOT EXISTS \"bird_{}\" (TYPE s3, PROVIDER {})",
                     remote.name, provider
                 );
-                conn.execute(&secret_sql, [])?;
+                conn.execute(&secret_sql, [])
+                    .map_err(|e| remote_error(&remote.name, e.into()))?;
             }
         }
 
@@ -1017,8 +1511,7 @@ impl Store {
         }
 
         // Attach
-        conn.execute(&remote.attach_sql(), [])?;
-        Ok(())
+        attach_with_timeout(conn, remote)
     }
 
     /// Detach a remote.
@@ -1051,7 +1544,35 @@ impl Store {
     ///
     /// Returns results as a Vec of rows, where each row is a Vec of string values.
     pub fn query(&self, sql: &str) -> Result<QueryResult> {
+        let mut columns = Vec::new();
+        let mut rows = Vec::new();
+        self.query_rows(
+            sql,
+            |names| {
+                columns = names.to_vec();
+                Ok(())
+            },
+            |row| {
+                rows.push(row.iter().map(SqlValue::to_string).collect());
+                Ok(())
+            },
+        )?;
+        Ok(QueryResult { columns, rows })
+    }
+
+    /// Query the store using SQL, handing rows to `on_row` as they are read.
+    ///
+    /// `on_columns` gets the column names once, before the first row. Unlike
+    /// [`Store::query`] nothing is buffered, so a large result can be written
+    /// out as it arrives. Returns the number of rows.
+    pub fn query_rows(
+        &self,
+        sql: &str,
+        on_columns: impl FnOnce(&[String]) -> Result<()>,
+        mut on_row: impl FnMut(Vec<SqlValue>) -> Result<()>,
+    ) -> Result<usize> {
         let conn = self.connection()?;
+        self.attach_remotes_for_sql(&conn, sql)?;
         let mut stmt = conn.prepare(sql)?;
 
         // Execute the query first to get column info
@@ -1072,28 +1593,29 @@ impl Store {
             Vec::new()
         };
 
-        // Collect all rows
-        let mut result_rows = Vec::new();
+        on_columns(&column_names)?;
+
+        let mut count = 0;
         while let Some(row) = rows_iter.next()? {
             let mut values = Vec::with_capacity(column_count);
             for i in 0..column_count {
-                // Get value as generic ValueRef and convert to string
+                // Get value as generic ValueRef and convert to a SqlValue
                 let value = match row.get_ref(i)? {
-                    ValueRef::Null => "NULL".to_string(),
-                    ValueRef::Boolean(b) => b.to_string(),
-                    ValueRef::TinyInt(n) => n.to_string(),
-                    ValueRef::SmallInt(n) => n.to_string(),
-                    ValueRef::Int(n) => n.to_string(),
-                    ValueRef::BigInt(n) => n.to_string(),
-                    ValueRef::HugeInt(n) => n.to_string(),
-                    ValueRef::UTinyInt(n) => n.to_string(),
-                    ValueRef::USmallInt(n) => n.to_string(),
-                    ValueRef::UInt(n) => n.to_string(),
-                    ValueRef::UBigInt(n) => n.to_string(),
-                    ValueRef::Float(f) => f.to_string(),
-                    ValueRef::Double(f) => f.to_string(),
-                    ValueRef::Decimal(d) => d.to_string(),
-                    ValueRef::Timestamp(unit, val) => {
+                    ValueRef::Null => SqlValue::Null,
+                    ValueRef::Boolean(b) => SqlValue::Bool(b),
+                    ValueRef::TinyInt(n) => SqlValue::Number(n.to_string()),
+                    ValueRef::SmallInt(n) => SqlValue::Number(n.to_string()),
+                    ValueRef::Int(n) => SqlValue::Number(n.to_string()),
+                    ValueRef::BigInt(n) => SqlValue::Number(n.to_string()),
+                    ValueRef::HugeInt(n) => SqlValue::Number(n.to_string()),
+                    ValueRef::UTinyInt(n) => SqlValue::Number(n.to_string()),
+                    ValueRef::USmallInt(n) => SqlValue::Number(n.to_string()),
+                    ValueRef::UInt(n) => SqlValue::Number(n.to_string()),
+                    ValueRef::UBigInt(n) => SqlValue::Number(n.to_string()),
+                    ValueRef::Float(f) => SqlValue::Number(f.to_string()),
+                    ValueRef::Double(f) => SqlValue::Number(f.to_string()),
+                    ValueRef::Decimal(d) => SqlValue::Number(d.to_string()),
+                    ValueRef::Timestamp(unit, val) => SqlValue::Text({
                         // Convert to microseconds then to DateTime
                         let micros = match unit {
                             TimeUnit::Second => val * 1_000_000,
@@ -1104,15 +1626,15 @@ impl Store {
                         DateTime::<Utc>::from_timestamp_micros(micros)
                             .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
                             .unwrap_or_else(|| format!("<invalid timestamp {}>", val))
-                    }
-                    ValueRef::Date32(days) => {
+                    }),
+                    ValueRef::Date32(days) => SqlValue::Text({
                         // Days since 1970-01-01
                         NaiveDate::from_ymd_opt(1970, 1, 1)
                             .and_then(|epoch| epoch.checked_add_signed(TimeDelta::days(days as i64)))
                             .map(|d| d.format("%Y-%m-%d").to_string())
                             .unwrap_or_else(|| format!("<invalid date {}>", days))
-                    }
-                    ValueRef::Time64(unit, val) => {
+                    }),
+                    ValueRef::Time64(unit, val) => SqlValue::Text({
                         // Convert to microseconds then to NaiveTime
                         let micros = match unit {
                             TimeUnit::Second => val * 1_000_000,
@@ -1125,27 +1647,25 @@ impl Store {
                         NaiveTime::from_num_seconds_from_midnight_opt(secs, micro_part * 1000)
                             .map(|t| t.format("%H:%M:%S").to_string())
                             .unwrap_or_else(|| format!("<invalid time {}>", val))
-                    }
+                    }),
                     ValueRef::Interval { months, days, nanos } => {
-                        format!("{} months {} days {} ns", months, days, nanos)
+                        SqlValue::Text(format!("{} months {} days {} ns", months, days, nanos))
                     }
-                    ValueRef::Text(s) => String::from_utf8_lossy(s).to_string(),
-                    ValueRef::Blob(b) => format!("<blob {} bytes>", b.len()),
+                    ValueRef::Text(s) => SqlValue::Text(String::from_utf8_lossy(s).to_string()),
+                    ValueRef::Blob(b) => SqlValue::Text(format!("<blob {} bytes>", b.len())),
                     other => {
                         // Convert to owned Value for complex types (List, Array, Map, Struct)
                         let owned: Value = other.into();
-                        format_value(&owned)
+                        SqlValue::Text(format_value(&owned))
                     }
                 };
                 values.push(value);
             }
-            result_rows.push(values);
+            on_row(values)?;
+            count += 1;
         }
 
-        Ok(QueryResult {
-            columns: column_names,
-            rows: result_rows,
-        })
+        Ok(count)
     }
 
     /// Get the last invocation with its output (if any).
@@ -1166,15 +1686,99 @@ impl Store {
     /// session, and events together. In DuckDB mode, all writes are wrapped
     /// in a transaction. In Parquet mode, files are written atomically.
     pub fn write_batch(&self, batch: &InvocationBatch) -> Result<()> {
+        let invocation = self.batch_invocation(batch)?;
+
+        match self.config.storage_mode {
+            StorageMode::Parquet => self.write_batch_parquet(batch, &invocation)?,
+            StorageMode::DuckDB => self.write_batch_duckdb(batch, &invocation)?,
+        }
+        self.append_to_histfile([invocation.as_ref()]);
+        Ok(())
+    }
+
+    /// Write several batches with one connection (and, in DuckDB mode, one
+    /// transaction), so a burst of invocations takes the database lock once.
+    /// In Parquet mode, their attempts, outcomes and outputs go in one file
+    /// per table and date partition rather than one per invocation.
+    ///
+    /// If the grouped write fails, the batches are written one at a
+    /// time so a single bad record doesn't lose the rest. Returns the
+    /// batches that couldn't be written, by index, with their errors; fails
+    /// outright only if nothing could be written.
+    pub fn write_batches(&self, batches: &[InvocationBatch]) -> Result<Vec<(usize, Error)>> {
+        if batches.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.with_connection(|conn| {
+            if self.config.storage_mode == StorageMode::DuckDB {
+                let invocations = batches
+                    .iter()
+                    .map(|batch| self.batch_invocation(batch))
+                    .collect::<Result<Vec<_>>>();
+                if let Ok(invocations) = invocations {
+                    conn.execute("BEGIN TRANSACTION", [])?;
+                    let result = batches
+                        .iter()
+                        .zip(&invocations)
+                        .try_for_each(|(batch, invocation)| self.write_batch_duckdb_inner(conn, batch, invocation));
+                    match result {
+                        Ok(()) => {
+                            conn.execute("COMMIT", [])?;
+                            self.append_to_histfile(invocations.iter().map(|inv| inv.as_ref()));
+                            return Ok(Vec::new());
+                        }
+                        Err(e) => {
+                            let _ = conn.execute("ROLLBACK", []);
+                            tracing::debug!(batches = batches.len(), "grouped write failed, writing one at a time: {}", e);
+                        }
+                    }
+                }
+            }
+
+            if self.config.storage_mode == StorageMode::Parquet {
+                return Ok(self.write_batches_parquet(batches));
+            }
+
+            Ok(batches
+                .iter()
+                .enumerate()
+                .filter_map(|(i, batch)| self.write_batch(batch).err().map(|e| (i, e)))
+                .collect())
+        })
+    }
+
+    /// The batch's invocation, with its output format recorded up front so
+    /// reparses use the same parser. Detection is best-effort and never
+    /// fails the write.
+    fn batch_invocation<'a>(&self, batch: &'a InvocationBatch) -> Result<Cow<'a, InvocationRecord>> {
         let invocation = batch
             .invocation
             .as_ref()
             .ok_or_else(|| Error::Storage("Batch must contain an invocation".to_string()))?;
 
-        match self.config.storage_mode {
-            StorageMode::Parquet => self.write_batch_parquet(batch, invocation),
-            StorageMode::DuckDB => self.write_batch_duckdb(batch, invocation),
+        if invocation.format_hint.is_some() || (batch.outputs.is_empty() && batch.stored_outputs.is_empty()) {
+            return Ok(Cow::Borrowed(invocation));
         }
+
+        // Streamed outputs are detected from their leading bytes;
+        // attached artifacts aren't command output
+        let samples: Vec<(String, Vec<u8>)> = batch
+            .stored_outputs
+            .iter()
+            .map(|o| (o.stream.clone(), o.sample.clone()))
+            .collect();
+        let outputs: Vec<(String, Vec<u8>)> = [&batch.outputs[..], &samples[..]]
+            .concat()
+            .into_iter()
+            .filter(|(stream, _)| !artifacts::is_artifact_stream(stream))
+            .collect();
+        Ok(Cow::Owned(InvocationRecord {
+            format_hint: self
+                .detect_output_format(&invocation.cmd, &outputs)
+                .unwrap_or(None),
+            ..invocation.clone()
+        }))
     }
 
     /// Write batch using Parquet files (multi-writer safe).
@@ -1186,37 +1790,117 @@ impl Store {
         // For Parquet mode, we write each record type separately.
         // Atomicity is per-file (temp + rename), but not across files.
         // This is acceptable because Parquet mode prioritizes concurrent writes.
+        let outputs = self.prepare_batch_parquet(batch, invocation)?;
 
-        // Write session first (if provided and not already registered)
+        // Write invocation
+        self.write_invocation(invocation)?;
+
+        // Write outputs
+        for output in &outputs {
+            self.write_output(output)?;
+        }
+
+        // Write events (if provided)
+        if let Some(ref events) = batch.events {
+            if !events.is_empty() {
+                self.write_events(events)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write a batch's session (if not already registered) and output
+    /// content for Parquet mode, returning its output records still to be
+    /// written.
+    fn prepare_batch_parquet(&self, batch: &InvocationBatch, invocation: &InvocationRecord) -> Result<Vec<OutputRecord>> {
         if let Some(ref session) = batch.session {
             self.ensure_session(session)?;
         }
 
-        // Write invocation
-        self.write_invocation(invocation)?;
-
         let date = invocation.date();
         let inv_id = invocation.id;
-
-        // Write outputs
+        let mut sequences = HashMap::new();
+        let mut outputs = Vec::new();
         for (stream, content) in &batch.outputs {
-            self.store_output(
+            outputs.push(self.output_record(
                 inv_id,
                 stream,
+                next_sequence(&mut sequences, stream),
                 content,
                 date,
                 invocation.executable.as_deref(),
-            )?;
+            )?);
         }
+        for output in &batch.stored_outputs {
+            let sequence = next_sequence(&mut sequences, &output.stream);
+            outputs.push(OutputRecord {
+                id: uuid::Uuid::now_v7(),
+                invocation_id: inv_id,
+                stream: output.stream.clone(),
+                content_hash: output.content_hash.clone(),
+                byte_length: output.byte_length as usize,
+                storage_type: output.storage_type.clone(),
+                storage_ref: output.storage_ref.clone(),
+                content_type: None,
+                date,
+                sequence,
+            });
+        }
+        Ok(outputs)
+    }
 
-        // Write events (if provided)
-        if let Some(ref events) = batch.events {
-            if !events.is_empty() {
-                self.write_events(events)?;
+    /// Write batches in Parquet mode with one attempts, outcomes and outputs
+    /// file per date partition. Sessions, output content and events are
+    /// written per batch (event files are named after their invocation).
+    /// Returns the batches that couldn't be written, by index.
+    fn write_batches_parquet(&self, batches: &[InvocationBatch]) -> Vec<(usize, Error)> {
+        let mut failed: BTreeMap<usize, Error> = BTreeMap::new();
+        let mut prepared = Vec::new();
+        for (i, batch) in batches.iter().enumerate() {
+            let result = self.batch_invocation(batch).and_then(|invocation| {
+                let outputs = self.prepare_batch_parquet(batch, &invocation)?;
+                Ok((invocation.into_owned(), outputs))
+            });
+            match result {
+                Ok((invocation, outputs)) => prepared.push((i, invocation, outputs)),
+                Err(e) => {
+                    failed.insert(i, e);
+                }
             }
         }
 
-        Ok(())
+        let attempts: Vec<_> = prepared.iter().map(|(i, inv, _)| (*i, inv.to_attempt())).collect();
+        write_grouped(&attempts, &mut failed, |a| a.date(), |group| self.write_attempts_parquet(group));
+        let outcomes: Vec<_> = prepared
+            .iter()
+            .filter_map(|(i, inv, _)| inv.to_outcome().map(|o| (*i, o)))
+            .collect();
+        write_grouped(&outcomes, &mut failed, |o| o.date, |group| self.write_outcomes_parquet(group));
+        let outputs: Vec<_> = prepared
+            .iter()
+            .flat_map(|(i, _, outputs)| outputs.iter().map(move |o| (*i, o.clone())))
+            .collect();
+        write_grouped(&outputs, &mut failed, |o| o.date, |group| self.write_outputs_parquet(group));
+
+        for (i, _, _) in &prepared {
+            if failed.contains_key(i) {
+                continue;
+            }
+            if let Some(events) = batches[*i].events.as_ref().filter(|events| !events.is_empty()) {
+                if let Err(e) = self.write_events(events) {
+                    failed.insert(*i, e);
+                }
+            }
+        }
+
+        self.append_to_histfile(
+            prepared
+                .iter()
+                .filter(|(i, _, _)| !failed.contains_key(i))
+                .map(|(_, invocation, _)| invocation),
+        );
+        failed.into_iter().collect()
     }
 
     /// Write batch using DuckDB tables with transaction.
@@ -1324,6 +2008,10 @@ impl Store {
                 date.to_string(),
             ],
         )?;
+        recent_index::index_attempt(conn, &attempt)?;
+        running_stats::record_attempt(conn, &attempt)?;
+        short_ids::index_attempt(conn, &attempt)?;
+        aliases::assign(conn, &attempt.id)?;
 
         // Write outcome if completed
         if let Some(outcome) = outcome {
@@ -1355,20 +2043,30 @@ impl Store {
                     outcome.date.to_string(),
                 ],
             )?;
+            recent_index::index_outcome(conn, &outcome)?;
+            running_stats::record_outcome(conn, &outcome)?;
         }
 
         // Write outputs
+        let mut sequences = HashMap::new();
         for (stream, content) in &batch.outputs {
+            let sequence = next_sequence(&mut sequences, stream);
+
             // Compute hash
             let hash = blake3::hash(content);
             let hash_hex = hash.to_hex().to_string();
 
             // Route by size
             let (storage_type, storage_ref) = if content.len() < self.config.inline_threshold {
-                // Inline: use data: URL
-                let b64 = base64::engine::general_purpose::STANDARD.encode(content);
-                let data_url = format!("data:application/octet-stream;base64,{}", b64);
-                ("inline".to_string(), data_url)
+                match self.reuse_blob(conn, &hash_hex)? {
+                    Some(storage_path) => ("blob".to_string(), format!("file://{}", storage_path)),
+                    None => {
+                        // Inline: use data: URL
+                        let b64 = base64::engine::general_purpose::STANDARD.encode(content);
+                        let data_url = format!("data:application/octet-stream;base64,{}", b64);
+                        ("inline".to_string(), data_url)
+                    }
+                }
             } else {
                 // Blob: write file and register
                 let cmd_hint = invocation.executable.as_deref().unwrap_or("output");
@@ -1387,8 +2085,12 @@ impl Store {
                 let wrote_new = atomic::write_file(&blob_path, content)?;
 
                 if wrote_new {
+                    // The hash may be registered already, under another name
+                    // or tiered to cold storage; this copy is local again
                     conn.execute(
-                        "INSERT INTO blob_registry (content_hash, byte_length, storage_path) VALUES (?, ?, ?)",
+                        "INSERT INTO blob_registry (content_hash, byte_length, storage_path) VALUES (?, ?, ?) \
+                         ON CONFLICT (content_hash) DO UPDATE SET storage_path = excluded.storage_path, \
+                         ref_count = blob_registry.ref_count + 1, last_accessed = CURRENT_TIMESTAMP",
                         params![&hash_hex, content.len() as i64, &rel_path],
                     )?;
                 } else {
@@ -1403,8 +2105,7 @@ impl Store {
 
             // Write output record
             let output_id = uuid::Uuid::now_v7();
-            conn.execute(
-                r#"INSERT INTO local.outputs VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+            conn.execute(OUTPUT_INSERT_SQL,
                 params![
                     output_id.to_string(),
                     inv_id.to_string(),
@@ -1415,6 +2116,24 @@ impl Store {
                     storage_ref,
                     Option::<String>::None, // content_type
                     date.to_string(),
+                    sequence,
+                ],
+            )?;
+        }
+        for output in &batch.stored_outputs {
+            let sequence = next_sequence(&mut sequences, &output.stream);
+            conn.execute(OUTPUT_INSERT_SQL,
+                params![
+                    uuid::Uuid::now_v7().to_string(),
+                    inv_id.to_string(),
+                    output.stream,
+                    output.content_hash,
+                    output.byte_length as i64,
+                    output.storage_type,
+                    output.storage_ref,
+                    Option::<String>::None, // content_type
+                    date.to_string(),
+                    sequence,
                 ],
             )?;
         }
@@ -1423,7 +2142,7 @@ impl Store {
         if let Some(ref events) = batch.events {
             for event in events {
                 conn.execute(
-                    r#"INSERT INTO local.events VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+                    r#"INSERT INTO local.events VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
                     params![
                         event.id.to_string(),
                         event.invocation_id.to_string(),
@@ -1440,6 +2159,7 @@ impl Store {
                         event.status,
                         event.format_used,
                         event.date.to_string(),
+                        event.origin,
                     ],
                 )?;
             }
@@ -1566,6 +2286,42 @@ pub struct QueryResult {
     pub rows: Vec<Vec<String>>,
 }
 
+/// One value of a SQL result row (see [`Store::query_rows`]).
+///
+/// Numbers keep DuckDB's text form so wide integers and decimals print
+/// exactly; everything that isn't a number or boolean is rendered as text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Null,
+    Bool(bool),
+    Number(String),
+    Text(String),
+}
+
+impl SqlValue {
+    /// The value as JSON: numbers as numbers where JSON can hold them.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            SqlValue::Null => serde_json::Value::Null,
+            SqlValue::Bool(b) => serde_json::Value::Bool(*b),
+            SqlValue::Number(n) => serde_json::from_str::<serde_json::Number>(n)
+                .map(serde_json::Value::Number)
+                .unwrap_or_else(|_| serde_json::Value::String(n.clone())),
+            SqlValue::Text(s) => serde_json::Value::String(s.clone()),
+        }
+    }
+}
+
+impl std::fmt::Display for SqlValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SqlValue::Null => f.write_str("NULL"),
+            SqlValue::Bool(b) => write!(f, "{}", b),
+            SqlValue::Number(n) | SqlValue::Text(n) => f.write_str(n),
+        }
+    }
+}
+
 /// Sanitize a string for use in filenames.
 fn sanitize_filename(s: &str) -> String {
     s.chars()
@@ -1602,6 +2358,90 @@ mod tests {
         (tmp, store)
     }
 
+    #[test]
+    fn test_sql_value_json() {
+        assert_eq!(SqlValue::Number("42".to_string()).to_json(), serde_json::json!(42));
+        assert_eq!(SqlValue::Number("1.5".to_string()).to_json(), serde_json::json!(1.5));
+        assert_eq!(SqlValue::Number("NaN".to_string()).to_json(), serde_json::json!("NaN"));
+        assert_eq!(SqlValue::Null.to_json(), serde_json::Value::Null);
+        assert_eq!(SqlValue::Null.to_string(), "NULL");
+        assert_eq!(SqlValue::Text("a\"b".to_string()).to_json(), serde_json::json!("a\"b"));
+    }
+
+    #[test]
+    fn test_query_rows_streams() {
+        let (_tmp, store) = setup_store();
+
+        let mut columns = Vec::new();
+        let mut rows = Vec::new();
+        let count = store
+            .query_rows(
+                "SELECT range AS n, NULL AS x FROM range(3)",
+                |names| {
+                    columns = names.to_vec();
+                    Ok(())
+                },
+                |row| {
+                    rows.push(row);
+                    Ok(())
+                },
+            )
+            .unwrap();
+
+        assert_eq!(count, 3);
+        assert_eq!(columns, ["n", "x"]);
+        assert_eq!(rows[2], [SqlValue::Number("2".to_string()), SqlValue::Null]);
+    }
+
+    #[test]
+    fn test_remote_error_classifies_auth_failures() {
+        let err = remote_error(
+            "team",
+            Error::Storage("HTTP Error: HTTP GET error on 's3://b/bird.duckdb' (HTTP 403)".to_string()),
+        );
+        assert!(matches!(&err, Error::RemoteAuth { remote, .. } if remote == "team"), "{}", err);
+
+        let err = remote_error("team", Error::Storage("attach timed out after 5s".to_string()));
+        assert!(matches!(err, Error::Storage(_)));
+    }
+
+    #[test]
+    fn test_check_schema_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        // No bird_meta: pre-versioning store
+        check_schema_version(&conn).unwrap();
+
+        conn.execute_batch(
+            "CREATE TABLE bird_meta (key VARCHAR, value VARCHAR); \
+             INSERT INTO bird_meta VALUES ('schema_version', '99');",
+        )
+        .unwrap();
+        let err = check_schema_version(&conn).unwrap_err();
+        assert!(matches!(&err, Error::SchemaVersionMismatch { found, .. } if found == "99"), "{}", err);
+    }
+
+    #[test]
+    fn test_records_newest_shq_version() {
+        use crate::version::{store_mismatch_hint, store_versions, SHQ_VERSION};
+
+        let (_tmp, store) = setup_store();
+        store.invocation_count().unwrap();
+        let config = store.config().clone();
+        drop(store);
+
+        let versions = store_versions(&config).unwrap();
+        assert_eq!(versions.schema.as_deref(), Some(crate::schema::BIRD_SCHEMA_VERSION));
+        assert_eq!(versions.shq.as_deref(), Some(SHQ_VERSION));
+        assert!(store_mismatch_hint(&config).is_none());
+
+        // A newer shq's record is kept, and explains errors
+        let conn = Connection::open(config.db_path()).unwrap();
+        conn.execute("UPDATE bird_meta SET value = '999.0.0' WHERE key = 'shq_version'", []).unwrap();
+        record_shq_version(&conn);
+        drop(conn);
+        assert!(store_mismatch_hint(&config).unwrap().contains("shq 999.0.0"));
+    }
+
     #[test]
     fn test_store_open_uninitialized_fails() {
         let tmp = TempDir::new().unwrap();
@@ -1688,7 +2528,7 @@ mod tests {
 
     #[test]
     fn test_batch_write_duckdb_invocation_only() {
-        let (_tmp, store) = setup_store_duckdb();
+        let store = Store::open(Config::in_memory()).unwrap();
 
         let inv = InvocationRecord::new("test-session", "echo hello", "/home/user", 0, "test@client");
 
@@ -1700,7 +2540,7 @@ mod tests {
 
     #[test]
     fn test_batch_write_duckdb_with_output() {
-        let (_tmp, store) = setup_store_duckdb();
+        let store = Store::open(Config::in_memory()).unwrap();
 
         let inv = InvocationRecord::new("test-session", "echo hello", "/home/user", 0, "test@client");
         let inv_id = inv.id;
@@ -1717,7 +2557,7 @@ mod tests {
 
     #[test]
     fn test_batch_write_duckdb_with_session() {
-        let (_tmp, store) = setup_store_duckdb();
+        let store = Store::open(Config::in_memory()).unwrap();
 
         let inv = InvocationRecord::new("test-session", "echo hello", "/home/user", 0, "test@client");
         let session = SessionRecord::new("test-session", "test@client", "bash", 12345, "shell");
@@ -1730,7 +2570,7 @@ mod tests {
 
     #[test]
     fn test_batch_write_duckdb_full() {
-        let (_tmp, store) = setup_store_duckdb();
+        let store = Store::open(Config::in_memory()).unwrap();
 
         let inv = InvocationRecord::new("test-session", "make test", "/home/user", 1, "test@client");
         let inv_id = inv.id;
@@ -1750,6 +2590,32 @@ mod tests {
         assert_eq!(outputs.len(), 2);
     }
 
+    #[test]
+    fn test_in_memory_store() {
+        let store = Store::open(Config::in_memory()).unwrap();
+
+        let inv = InvocationRecord::new("test-session", "make test", "/home/user", 1, "test@client");
+        let inv_id = inv.id;
+        let big = vec![b'x'; 64 * 1024];
+        let batch = InvocationBatch::new(inv).with_output("stdout", big.clone());
+        store.write_batch(&batch).unwrap();
+
+        assert_eq!(store.invocation_count().unwrap(), 1);
+        let outputs = store.get_outputs(&inv_id.to_string(), None).unwrap();
+        assert_eq!(outputs[0].storage_type, "inline");
+        assert!(!store.config().bird_root.exists());
+
+        // Each store gets its own database
+        let other = Store::open(Config::in_memory()).unwrap();
+        assert_eq!(other.invocation_count().unwrap(), 0);
+
+        // Its config doesn't open a second, empty database while it's open
+        let config = store.config().clone();
+        assert!(matches!(Store::open(config.clone()), Err(Error::Config(_))));
+        drop(store);
+        assert_eq!(Store::open(config).unwrap().invocation_count().unwrap(), 0);
+    }
+
     #[test]
     fn test_batch_requires_invocation() {
         let (_tmp, store) = setup_store();
@@ -1766,7 +2632,7 @@ mod tests {
     fn test_ensure_extension_parquet() {
         // Parquet is an official extension, should always be available
         let conn = duckdb::Connection::open_in_memory().unwrap();
-        let result = ensure_extension(&conn, "parquet").unwrap();
+        let result = ensure_extension(&conn, "parquet", None).unwrap();
         assert!(result, "parquet extension should be loadable");
     }
 
@@ -1774,7 +2640,7 @@ mod tests {
     fn test_ensure_extension_icu() {
         // ICU is an official extension, should always be available
         let conn = duckdb::Connection::open_in_memory().unwrap();
-        let result = ensure_extension(&conn, "icu").unwrap();
+        let result = ensure_extension(&conn, "icu", None).unwrap();
         assert!(result, "icu extension should be loadable");
     }
 
@@ -1785,10 +2651,10 @@ mod tests {
         conn.execute("SET allow_community_extensions = true", []).unwrap();
 
         // scalarfs and duck_hunt are community extensions
-        let result = ensure_extension(&conn, "scalarfs").unwrap();
+        let result = ensure_extension(&conn, "scalarfs", None).unwrap();
         assert!(result, "scalarfs extension should be loadable from community");
 
-        let result = ensure_extension(&conn, "duck_hunt").unwrap();
+        let result = ensure_extension(&conn, "duck_hunt", None).unwrap();
         assert!(result, "duck_hunt extension should be loadable from community");
     }
 
@@ -1798,7 +2664,7 @@ mod tests {
         conn.execute("SET allow_community_extensions = true", []).unwrap();
 
         // A made-up extension should return false (not error)
-        let result = ensure_extension(&conn, "nonexistent_fake_extension_xyz").unwrap();
+        let result = ensure_extension(&conn, "nonexistent_fake_extension_xyz", None).unwrap();
         assert!(!result, "nonexistent extension should return false");
     }
 
@@ -1808,14 +2674,54 @@ mod tests {
         let conn = duckdb::Connection::open_in_memory().unwrap();
 
         // First load might install
-        ensure_extension(&conn, "parquet").unwrap();
+        ensure_extension(&conn, "parquet", None).unwrap();
 
         // Second load should be fast (from cache)
         let start = std::time::Instant::now();
-        ensure_extension(&conn, "parquet").unwrap();
+        ensure_extension(&conn, "parquet", None).unwrap();
         let elapsed = start.elapsed();
 
         // Should be very fast if cached (< 100ms)
         assert!(elapsed.as_millis() < 100, "cached extension load took {:?}", elapsed);
     }
+
+    #[test]
+    fn test_with_connection_reuses_shared_connection() {
+        let (_tmp, store) = setup_store_duckdb();
+
+        let count = store
+            .with_connection(|conn| {
+                // Store methods inside the closure see the shared database
+                store.write_invocation(&InvocationRecord::new("s1", "make", "/tmp", 0, "test@client"))?;
+                assert!(store.shared_connection()?.is_some());
+                let count: i64 =
+                    conn.query_row("SELECT COUNT(*) FROM main.invocations", [], |r| r.get(0))?;
+                Ok(count)
+            })
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // Released afterwards
+        assert!(store.shared_connection().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_referenced_remotes() {
+        let team = crate::RemoteConfig::new("team", crate::RemoteType::File, "/tmp/team.duckdb");
+        let ci = crate::RemoteConfig::new("ci-box", crate::RemoteType::File, "/tmp/ci.duckdb");
+        let remotes = [&team, &ci];
+
+        let names = |sql: &str| -> Vec<String> {
+            referenced_remotes(sql, &remotes)
+                .iter()
+                .map(|r| r.name.clone())
+                .collect()
+        };
+
+        assert!(names("SELECT * FROM main.invocations").is_empty());
+        assert_eq!(names("SELECT * FROM unified.invocations"), vec!["team", "ci-box"]);
+        assert_eq!(names("SELECT * FROM remotes_events()"), vec!["team", "ci-box"]);
+        assert_eq!(names("SELECT * FROM \"remote_team\".local.invocations"), vec!["team"]);
+        assert_eq!(names("SELECT * FROM remote_ci_box_invocations()"), vec!["ci-box"]);
+    }
 }
@@ -9,7 +9,7 @@ use duckdb::params;
 use uuid::Uuid;
 
 use super::atomic;
-use super::Store;
+use super::{recent_index, running_stats, Store};
 use crate::config::StorageMode;
 use crate::schema::OutcomeRecord;
 use crate::Result;
@@ -32,30 +32,29 @@ impl Store {
 
     /// Write outcome to a Parquet file (multi-writer safe).
     fn write_outcome_parquet(&self, record: &OutcomeRecord) -> Result<()> {
+        self.write_outcomes_parquet(std::slice::from_ref(record))
+    }
+
+    /// Write outcomes from one date partition to a single Parquet file
+    /// (multi-writer safe): `{attempt_id}.parquet` for a lone outcome,
+    /// `batch--{attempt_id}.parquet` after its first outcome for a group.
+    pub(super) fn write_outcomes_parquet(&self, records: &[OutcomeRecord]) -> Result<()> {
+        let Some(first) = records.first() else {
+            return Ok(());
+        };
         let conn = self.connection_with_options(false)?;
-        let date = record.date;
+        let date = first.date;
 
         // Ensure the partition directory exists
         let partition_dir = self.config.outcomes_dir(&date);
         fs::create_dir_all(&partition_dir)?;
 
         // Generate filename: {attempt_id}.parquet
-        let filename = format!("{}.parquet", record.attempt_id);
-        let file_path = partition_dir.join(&filename);
-
-        // Convert metadata HashMap to DuckDB MAP format
-        let metadata_map = if record.metadata.is_empty() {
-            "map([],[]::JSON[])".to_string()
-        } else {
-            let entries: Vec<String> = record.metadata.iter()
-                .map(|(k, v)| {
-                    let key = k.replace('\'', "''");
-                    let value = v.to_string().replace('\'', "''");
-                    format!("struct_pack(k := '{}', v := '{}'::JSON)", key, value)
-                })
-                .collect();
-            format!("map_from_entries([{}])", entries.join(", "))
+        let filename = match records {
+            [record] => format!("{}.parquet", record.attempt_id),
+            _ => format!("batch--{}.parquet", first.attempt_id),
         };
+        let file_path = partition_dir.join(&filename);
 
         // Write via DuckDB using COPY
         conn.execute_batch(
@@ -73,26 +72,42 @@ impl Store {
             "#,
         )?;
 
-        // Insert with dynamic SQL for the MAP
-        conn.execute(
-            &format!(
-                r#"
-                INSERT INTO temp_outcome VALUES (
-                    ?, ?, ?, ?, ?, ?, {}, ?
-                )
-                "#,
-                metadata_map
-            ),
-            params![
-                record.attempt_id.to_string(),
-                record.completed_at.to_rfc3339(),
-                record.exit_code,
-                record.duration_ms,
-                record.signal,
-                record.timeout,
-                date.to_string(),
-            ],
-        )?;
+        for record in records {
+            // Convert metadata HashMap to DuckDB MAP format
+            let metadata_map = if record.metadata.is_empty() {
+                "map([],[]::JSON[])".to_string()
+            } else {
+                let entries: Vec<String> = record.metadata.iter()
+                    .map(|(k, v)| {
+                        let key = k.replace('\'', "''");
+                        let value = v.to_string().replace('\'', "''");
+                        format!("struct_pack(k := '{}', v := '{}'::JSON)", key, value)
+                    })
+                    .collect();
+                format!("map_from_entries([{}])", entries.join(", "))
+            };
+
+            // Insert with dynamic SQL for the MAP
+            conn.execute(
+                &format!(
+                    r#"
+                    INSERT INTO temp_outcome VALUES (
+                        ?, ?, ?, ?, ?, ?, {}, ?
+                    )
+                    "#,
+                    metadata_map
+                ),
+                params![
+                    record.attempt_id.to_string(),
+                    record.completed_at.to_rfc3339(),
+                    record.exit_code,
+                    record.duration_ms,
+                    record.signal,
+                    record.timeout,
+                    record.date.to_string(),
+                ],
+            )?;
+        }
 
         // Atomic write: COPY to temp file, then rename
         let temp_path = atomic::temp_path(&file_path);
@@ -108,6 +123,10 @@ impl Store {
         // Rename temp to final (atomic on POSIX)
         atomic::rename_into_place(&temp_path, &file_path)?;
 
+        for record in records {
+            recent_index::index_outcome(&conn, record)?;
+            running_stats::record_outcome(&conn, record)?;
+        }
         Ok(())
     }
 
@@ -150,7 +169,8 @@ impl Store {
             ],
         )?;
 
-        Ok(())
+        recent_index::index_outcome(&conn, record)?;
+        running_stats::record_outcome(&conn, record)
     }
 
     /// Complete an invocation by writing an outcome (v5 schema).
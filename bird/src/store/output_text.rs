@@ -0,0 +1,164 @@
+//! SQL access to output content: `output_text(invocation_id)`.
+//!
+//! Table functions like `read_blob` only take literal paths, so SQL alone
+//! can't read the blob behind each row of `outputs`. Connections get a
+//! scalar function, `output_ref_text(storage_ref)`, that reads one storage
+//! ref (inline `data:` URL or `file://` blob under the data directory) and
+//! returns its content as text, and a macro on top of it that gathers an
+//! invocation's outputs:
+//!
+//! ```sql
+//! SELECT cmd, output_text(id) FROM invocations WHERE exit_code <> 0 LIMIT 5;
+//! SELECT output_text(id, stream_name := 'stderr') FROM invocations LIMIT 1;
+//! ```
+//!
+//! Both are per connection, so they exist in `shq sql` but not in a session
+//! that merely attaches the database file.
+
+use std::path::{Path, PathBuf};
+
+use duckdb::core::{DataChunkHandle, Inserter, LogicalTypeId};
+use duckdb::ffi::duckdb_string_t;
+use duckdb::types::DuckString;
+use duckdb::vscalar::{ScalarFunctionSignature, VScalar};
+use duckdb::vtab::arrow::WritableVector;
+use duckdb::Connection;
+
+use crate::Result;
+
+/// `output_text(inv_id [, stream_name := ...])`: an invocation's output,
+/// stdout before stderr, parts in sequence order.
+const OUTPUT_TEXT_MACRO: &str = r#"
+CREATE OR REPLACE TEMPORARY MACRO output_text(inv_id, stream_name := NULL) AS (
+    SELECT string_agg(output_ref_text(o.storage_ref), '' ORDER BY o.stream DESC, o.sequence)
+    FROM outputs o
+    WHERE o.invocation_id = inv_id::UUID
+      AND (stream_name IS NULL OR o.stream = stream_name)
+);
+"#;
+
+/// Content behind a storage ref, or `None` if it can't be read here (a
+/// missing blob, or a ref to a remote or cold tier).
+fn read_ref(data_dir: &Path, storage_ref: &str) -> Option<Vec<u8>> {
+    use base64::Engine;
+
+    if let Some(rel_path) = storage_ref.strip_prefix("file://") {
+        return std::fs::read(data_dir.join(rel_path)).ok();
+    }
+    if let Some(text) = storage_ref.strip_prefix("data+varchar:") {
+        return Some(text.as_bytes().to_vec());
+    }
+    let (header, data) = storage_ref.strip_prefix("data:")?.split_once(',')?;
+    if header.ends_with(";base64") {
+        base64::engine::general_purpose::STANDARD.decode(data).ok()
+    } else {
+        Some(data.as_bytes().to_vec())
+    }
+}
+
+/// `output_ref_text(storage_ref VARCHAR) -> VARCHAR`, reading blobs
+/// relative to the data directory it was registered with.
+struct OutputRefText;
+
+impl VScalar for OutputRefText {
+    type State = PathBuf;
+
+    unsafe fn invoke(
+        data_dir: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let len = input.len();
+        let refs = input.flat_vector(0);
+        let mut output = output.flat_vector();
+        for (i, raw) in refs.as_slice_with_len::<duckdb_string_t>(len).iter().enumerate() {
+            if refs.row_is_null(i as u64) {
+                output.set_null(i);
+                continue;
+            }
+            let storage_ref = DuckString::new(&mut { *raw }).as_str().to_string();
+            match read_ref(data_dir, &storage_ref) {
+                Some(content) => output.insert(i, String::from_utf8_lossy(&content).as_ref()),
+                None => output.set_null(i),
+            }
+        }
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            vec![LogicalTypeId::Varchar.into()],
+            LogicalTypeId::Varchar.into(),
+        )]
+    }
+}
+
+/// Register `output_ref_text()` and the `output_text()` macro on `conn`.
+///
+/// The function lives in the database instance, so connections cloned from
+/// one that has it (see `Store::with_connection`) skip registering it again.
+pub(crate) fn register_output_text(conn: &Connection, data_dir: &Path) -> Result<()> {
+    let registered: bool = conn.query_row(
+        "SELECT count(*) > 0 FROM duckdb_functions() WHERE function_name = 'output_ref_text'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !registered {
+        conn.register_scalar_function_with_state::<OutputRefText>("output_ref_text", &data_dir.to_path_buf())?;
+    }
+    conn.execute_batch(OUTPUT_TEXT_MACRO)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_output_text_reads_inline_and_blob() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::create_dir_all(tmp.path().join("recent/blobs/content/ab")).unwrap();
+        std::fs::write(tmp.path().join("recent/blobs/content/ab/abc.bin"), "error: boom\n").unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE outputs (invocation_id UUID, stream VARCHAR, sequence INTEGER, storage_ref VARCHAR);
+            INSERT INTO outputs VALUES
+                ('00000000-0000-0000-0000-000000000001', 'stderr', 0, 'file://recent/blobs/content/ab/abc.bin'),
+                ('00000000-0000-0000-0000-000000000001', 'stdout', 1, 'data:application/octet-stream;base64,d29ybGQK'),
+                ('00000000-0000-0000-0000-000000000001', 'stdout', 0, 'data:application/octet-stream;base64,aGVsbG8K'),
+                ('00000000-0000-0000-0000-000000000002', 'stdout', 0, 'file://recent/blobs/content/ff/missing.bin'),
+                ('00000000-0000-0000-0000-000000000003', 'stdout', 0, NULL);
+            "#,
+        )
+        .unwrap();
+        register_output_text(&conn, tmp.path()).unwrap();
+        // Connections cloned from the same database register again
+        register_output_text(&conn.try_clone().unwrap(), tmp.path()).unwrap();
+
+        let text = |sql: &str| -> Option<String> { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(
+            text("SELECT output_text('00000000-0000-0000-0000-000000000001')").as_deref(),
+            Some("hello\nworld\nerror: boom\n")
+        );
+        assert_eq!(
+            text("SELECT output_text('00000000-0000-0000-0000-000000000001', stream_name := 'stderr')").as_deref(),
+            Some("error: boom\n")
+        );
+        assert_eq!(text("SELECT output_text('00000000-0000-0000-0000-000000000002')"), None);
+        assert_eq!(text("SELECT output_text('00000000-0000-0000-0000-000000000003')"), None);
+        assert_eq!(text("SELECT output_ref_text('data+varchar:plain')").as_deref(), Some("plain"));
+
+        // Joins against invocation metadata, one value per row
+        let n: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM (SELECT DISTINCT invocation_id AS id FROM outputs) i WHERE output_text(i.id) IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(n, 1);
+    }
+}
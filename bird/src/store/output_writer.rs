@@ -0,0 +1,283 @@
+//! Incremental output capture.
+//!
+//! [`Store::begin_output`] opens a file in the capture directory, beside the
+//! blob content directory, that a command's output is written to as it
+//! runs, hashing it on the way. [`Store::finish_output`] then inlines it if
+//! small, or renames the file into place as its blob, so finishing a large
+//! capture costs the same as a small one.
+//! The result goes into an [`InvocationBatch`](super::InvocationBatch) with
+//! `with_stored_output`.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::{atomic, Store};
+use crate::Result;
+
+/// Leading bytes kept in memory for output format detection.
+const SAMPLE_SIZE: usize = 64 * 1024;
+
+/// An output stream being written to disk while a command runs.
+pub struct OutputWriter {
+    stream: String,
+    path: PathBuf,
+    /// `running/<id>.out` link to `path`, for `shq show --follow`.
+    link: Option<PathBuf>,
+    file: File,
+    hasher: blake3::Hasher,
+    len: u64,
+    sample: Vec<u8>,
+}
+
+/// Output content already moved into storage by [`Store::finish_output`].
+#[derive(Debug, Clone)]
+pub struct StoredOutput {
+    pub stream: String,
+    pub content_hash: String,
+    pub byte_length: u64,
+    pub storage_type: String,
+    pub storage_ref: String,
+    /// Leading bytes of the content, for format detection.
+    pub sample: Vec<u8>,
+}
+
+impl OutputWriter {
+    /// Path of the file being written (tail it to follow the output).
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Bytes written so far.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Drop the output without storing it.
+    pub fn discard(self) {
+        drop(self.file);
+        let _ = fs::remove_file(&self.path);
+        if let Some(link) = &self.link {
+            let _ = fs::remove_file(link);
+        }
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.file.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.len += n as u64;
+        if self.sample.len() < SAMPLE_SIZE {
+            let take = n.min(SAMPLE_SIZE - self.sample.len());
+            self.sample.extend_from_slice(&buf[..take]);
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Store {
+    /// Start capturing `stream` for an invocation. The invocation's running
+    /// file links to a `combined` capture, so `shq show --follow` can tail it.
+    pub fn begin_output(&self, invocation_id: uuid::Uuid, stream: &str) -> Result<OutputWriter> {
+        let capture_dir = self.config.capture_dir();
+        fs::create_dir_all(&capture_dir)?;
+        crate::perms::harden_dir(&capture_dir);
+        let path = capture_dir.join(format!("{}.{}.out", invocation_id, super::sanitize_filename(stream)));
+        let file = File::create(&path)?;
+        // Live command output is as sensitive as stored output: owner-only.
+        crate::perms::set_mode(&path, crate::perms::FILE_MODE)?;
+
+        let link = if stream == "combined" {
+            let running_dir = self.config.running_dir();
+            fs::create_dir_all(&running_dir)?;
+            crate::perms::harden_dir(&running_dir);
+            let link = self.config.running_path(&invocation_id);
+            let _ = fs::remove_file(&link);
+            std::os::unix::fs::symlink(&path, &link)?;
+            Some(link)
+        } else {
+            None
+        };
+
+        Ok(OutputWriter {
+            stream: stream.to_string(),
+            path,
+            link,
+            file,
+            hasher: blake3::Hasher::new(),
+            len: 0,
+            sample: Vec::new(),
+        })
+    }
+
+    /// Finish a capture: inline it if below `inline_threshold` (unless its
+    /// content is already a shared blob), otherwise move its file into blob
+    /// storage (or drop it on a dedup hit).
+    pub fn finish_output(&self, writer: OutputWriter, cmd_hint: Option<&str>) -> Result<StoredOutput> {
+        use base64::Engine;
+
+        let OutputWriter { stream, path, link, mut file, hasher, len, sample } = writer;
+        file.flush()?;
+        drop(file);
+        // A follower sees the link dangle once the capture is stored
+        if let Some(link) = link {
+            let _ = fs::remove_file(link);
+        }
+        let content_hash = hasher.finalize().to_hex().to_string();
+
+        let conn = self.connection()?;
+        let (storage_type, storage_ref) = if len < self.config.inline_threshold as u64 {
+            // Small content already stored as a blob (see `shq dedup-outputs`)
+            // is referenced rather than inlined again
+            match self.reuse_blob(&conn, &content_hash)? {
+                Some(storage_path) => {
+                    let _ = fs::remove_file(&path);
+                    ("blob".to_string(), format!("file://{}", storage_path))
+                }
+                None => {
+                    let content = fs::read(&path)?;
+                    let _ = fs::remove_file(&path);
+                    let b64 = base64::engine::general_purpose::STANDARD.encode(&content);
+                    ("inline".to_string(), format!("data:application/octet-stream;base64,{}", b64))
+                }
+            }
+        } else {
+            let result = self.register_blob(&conn, &content_hash, len, cmd_hint.unwrap_or("output"), |blob_path| {
+                move_into_place(&path, blob_path)
+            });
+            // Left behind on a dedup hit or a failed move
+            let _ = fs::remove_file(&path);
+            ("blob".to_string(), format!("file://{}", result?))
+        };
+
+        Ok(StoredOutput {
+            stream,
+            content_hash,
+            byte_length: len,
+            storage_type,
+            storage_ref,
+            sample,
+        })
+    }
+}
+
+/// Move a finished capture to `dest`, unless the blob is already there. The
+/// capture directory is beside the blobs, so this is a link unless blob
+/// content is mounted elsewhere.
+fn move_into_place(src: &Path, dest: &Path) -> io::Result<bool> {
+    crate::perms::set_mode(src, crate::perms::FILE_MODE)?;
+    // Linked rather than renamed, so an existing blob is never replaced
+    match fs::hard_link(src, dest) {
+        Ok(()) => return fs::remove_file(src).map(|()| true),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return fs::remove_file(src).map(|()| false),
+        Err(_) => {}
+    }
+    let temp = atomic::temp_path(dest);
+    fs::copy(src, &temp)?;
+    atomic::rename_into_place(&temp, dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::store::InvocationBatch;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_streamed_output_round_trips() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config.clone()).unwrap();
+
+        let inv = InvocationRecord::new("test-session", "seq 10000", "/home/user", 0, "user@laptop");
+        let mut writer = store.begin_output(inv.id, "combined").unwrap();
+        assert!(writer.path().starts_with(config.capture_dir()));
+        assert_eq!(fs::read_link(config.running_path(&inv.id)).unwrap(), writer.path());
+        let mut content = Vec::new();
+        for i in 0..10000 {
+            let line = format!("{}\n", i);
+            writer.write_all(line.as_bytes()).unwrap();
+            content.extend_from_slice(line.as_bytes());
+        }
+
+        let stored = store.finish_output(writer, Some("seq")).unwrap();
+        assert_eq!(stored.storage_type, "blob");
+        assert_eq!(stored.content_hash, blake3::hash(&content).to_hex().to_string());
+        assert!(fs::symlink_metadata(config.running_path(&inv.id)).is_err());
+        assert_eq!(fs::read_dir(config.capture_dir()).unwrap().count(), 0);
+
+        let id = inv.id.to_string();
+        store.write_batch(&InvocationBatch::new(inv).with_stored_output(stored)).unwrap();
+        let output = store.get_output(&id).unwrap().unwrap();
+        assert_eq!(store.read_output_content(&output).unwrap(), content);
+    }
+
+    #[test]
+    fn test_finish_references_shared_small_blob() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config.clone()).unwrap();
+
+        let content = b"Cargo.toml\nREADME.md\nsrc\n".to_vec();
+        let run = || {
+            let inv = InvocationRecord::new("test-session", "ls", "/home/user", 0, "user@laptop");
+            let mut writer = store.begin_output(inv.id, "stdout").unwrap();
+            writer.write_all(&content).unwrap();
+            let stored = store.finish_output(writer, Some("ls")).unwrap();
+            let id = inv.id.to_string();
+            store.write_batch(&InvocationBatch::new(inv).with_stored_output(stored.clone())).unwrap();
+            (id, stored)
+        };
+
+        let (_, first) = run();
+        let (_, second) = run();
+        assert_eq!((first.storage_type.as_str(), second.storage_type.as_str()), ("inline", "inline"));
+        store.dedup_outputs(Some(1), false).unwrap();
+
+        // Once shared as a blob, the same small output references it
+        let (id, third) = run();
+        assert_eq!(third.storage_type, "blob");
+        assert_eq!(fs::read_dir(config.capture_dir()).unwrap().count(), 0);
+        let output = store.get_output(&id).unwrap().unwrap();
+        assert_eq!(store.read_output_content(&output).unwrap(), content);
+        assert_eq!(store.dedup_outputs(Some(1), true).unwrap().outputs, 0);
+    }
+
+    #[test]
+    fn test_finish_never_replaces_existing_blob() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config.clone()).unwrap();
+
+        let content = vec![b'x'; config.inline_threshold * 2];
+        let hash = blake3::hash(&content).to_hex().to_string();
+        // Another process stored this blob but hasn't registered it yet
+        let blob_path = config.blob_path(&hash, "cat");
+        fs::create_dir_all(blob_path.parent().unwrap()).unwrap();
+        fs::write(&blob_path, b"already here").unwrap();
+
+        let inv = InvocationRecord::new("test-session", "cat big", "/home/user", 0, "user@laptop");
+        let mut writer = store.begin_output(inv.id, "stdout").unwrap();
+        writer.write_all(&content).unwrap();
+        let stored = store.finish_output(writer, Some("cat")).unwrap();
+
+        assert_eq!(stored.storage_type, "blob");
+        assert_eq!(fs::read(&blob_path).unwrap(), b"already here");
+        assert_eq!(fs::read_dir(config.capture_dir()).unwrap().count(), 0);
+    }
+}
@@ -2,9 +2,10 @@
 
 use std::fs;
 
-use duckdb::params;
+use duckdb::{params, Connection};
 
 use super::atomic;
+use super::tiering::is_tiered;
 use super::{sanitize_filename, Store};
 use crate::config::StorageMode;
 use crate::schema::OutputRecord;
@@ -18,6 +19,21 @@ pub struct OutputInfo {
     pub stream: String,
     pub byte_length: i64,
     pub content_hash: String,
+    /// Position among the invocation's outputs on this stream, from 0.
+    pub sequence: i32,
+}
+
+/// Each output's sequence, numbering outputs stored before the column
+/// existed by id (UUIDv7, so in the order they were written).
+pub(super) const SEQUENCE_SQL: &str =
+    "COALESCE(sequence, (row_number() OVER (PARTITION BY invocation_id, stream ORDER BY id) - 1)::INTEGER)";
+
+/// Filter on one stream, or on every stream but attached artifacts.
+fn stream_clause(stream_filter: Option<&str>) -> String {
+    match stream_filter {
+        Some(stream) => format!("AND stream = '{}'", stream),
+        None => format!("AND NOT starts_with(stream, '{}')", super::artifacts::ARTIFACT_STREAM_PREFIX),
+    }
 }
 
 impl Store {
@@ -28,6 +44,8 @@ impl Store {
     /// 2. Routes small content to inline (data: URL) or large to blob (file: URL)
     /// 3. Handles deduplication for blobs
     /// 4. Writes the output record to Parquet
+    ///
+    /// The output follows any the invocation already has on `stream`.
     pub fn store_output(
         &self,
         invocation_id: uuid::Uuid,
@@ -36,80 +54,63 @@ impl Store {
         date: chrono::NaiveDate,
         cmd_hint: Option<&str>,
     ) -> Result<()> {
+        let sequence = self.next_output_sequence(invocation_id, stream, date)?;
+        self.store_output_at(invocation_id, stream, sequence, content, date, cmd_hint)
+    }
+
+    /// Store output content as part `sequence` of `stream`.
+    pub(crate) fn store_output_at(
+        &self,
+        invocation_id: uuid::Uuid,
+        stream: &str,
+        sequence: i32,
+        content: &[u8],
+        date: chrono::NaiveDate,
+        cmd_hint: Option<&str>,
+    ) -> Result<()> {
+        let record = self.output_record(invocation_id, stream, sequence, content, date, cmd_hint)?;
+        self.write_output(&record)
+    }
+
+    /// Store output content inline or as a blob, returning the output record
+    /// still to be written.
+    pub(super) fn output_record(
+        &self,
+        invocation_id: uuid::Uuid,
+        stream: &str,
+        sequence: i32,
+        content: &[u8],
+        date: chrono::NaiveDate,
+        cmd_hint: Option<&str>,
+    ) -> Result<OutputRecord> {
         use base64::Engine;
 
         // Compute hash
         let hash = blake3::hash(content);
         let hash_hex = hash.to_hex().to_string();
+        let conn = self.connection()?;
 
         // Route by size
         let (storage_type, storage_ref) = if content.len() < self.config.inline_threshold {
-            // Inline: use data: URL
-            let b64 = base64::engine::general_purpose::STANDARD.encode(content);
-            let data_url = format!("data:application/octet-stream;base64,{}", b64);
-            ("inline".to_string(), data_url)
+            // Small content already stored as a blob (see `shq dedup-outputs`)
+            // is referenced rather than inlined again
+            match self.reuse_blob(&conn, &hash_hex)? {
+                Some(storage_path) => ("blob".to_string(), format!("file://{}", storage_path)),
+                None => {
+                    // Inline: use data: URL
+                    let b64 = base64::engine::general_purpose::STANDARD.encode(content);
+                    let data_url = format!("data:application/octet-stream;base64,{}", b64);
+                    ("inline".to_string(), data_url)
+                }
+            }
         } else {
             // Blob: check for dedup, write file if needed
-            let conn = self.connection()?;
-
-            // Check if blob already exists (dedup check)
-            let existing: std::result::Result<String, _> = conn.query_row(
-                "SELECT storage_path FROM blob_registry WHERE content_hash = ?",
-                params![&hash_hex],
-                |row| row.get(0),
-            );
-
-            let storage_path = match existing {
-                Ok(path) => {
-                    // DEDUP HIT - increment ref count
-                    conn.execute(
-                        "UPDATE blob_registry SET ref_count = ref_count + 1, last_accessed = CURRENT_TIMESTAMP WHERE content_hash = ?",
-                        params![&hash_hex],
-                    )?;
-                    path
-                }
-                Err(_) => {
-                    // DEDUP MISS in registry - write new blob atomically
-                    let cmd_hint = cmd_hint.unwrap_or("output");
-                    let blob_path = self.config.blob_path(&hash_hex, cmd_hint);
-
-                    // Ensure subdirectory exists
-                    if let Some(parent) = blob_path.parent() {
-                        fs::create_dir_all(parent)?;
-                    }
-
-                    // Compute relative path for storage_ref
-                    let rel_path = blob_path
-                        .strip_prefix(self.config.data_dir())
-                        .map(|p| p.to_string_lossy().to_string())
-                        .unwrap_or_else(|_| blob_path.to_string_lossy().to_string());
-
-                    // Atomic write: temp file + rename (handles concurrent writes)
-                    let wrote_new = atomic::write_file(&blob_path, content)?;
-
-                    if wrote_new {
-                        // We wrote the file - register in blob_registry
-                        conn.execute(
-                            "INSERT INTO blob_registry (content_hash, byte_length, storage_path) VALUES (?, ?, ?)",
-                            params![&hash_hex, content.len() as i64, &rel_path],
-                        )?;
-                    } else {
-                        // Another process wrote this blob concurrently - increment ref_count
-                        conn.execute(
-                            "UPDATE blob_registry SET ref_count = ref_count + 1, last_accessed = CURRENT_TIMESTAMP WHERE content_hash = ?",
-                            params![&hash_hex],
-                        )?;
-                    }
-
-                    rel_path
-                }
-            };
+            let storage_path = self.store_blob(&conn, &hash_hex, content, cmd_hint.unwrap_or("output"))?;
 
             ("blob".to_string(), format!("file://{}", storage_path))
         };
 
-        // Create and write the output record
-        let record = OutputRecord {
+        Ok(OutputRecord {
             id: uuid::Uuid::now_v7(),
             invocation_id,
             stream: stream.to_string(),
@@ -119,9 +120,129 @@ impl Store {
             storage_ref,
             content_type: None,
             date,
+            sequence,
+        })
+    }
+
+    /// The sequence number for a new output on an invocation's `stream`.
+    fn next_output_sequence(&self, invocation_id: uuid::Uuid, stream: &str, date: chrono::NaiveDate) -> Result<i32> {
+        let conn = self.connection()?;
+        let next = conn.query_row(
+            "SELECT GREATEST(COUNT(*), COALESCE(MAX(sequence) + 1, 0))::INTEGER FROM local.outputs \
+             WHERE invocation_id = ?::UUID AND stream = ? AND date = ?::DATE",
+            params![invocation_id.to_string(), stream, date.to_string()],
+            |row| row.get(0),
+        )?;
+        Ok(next)
+    }
+
+    /// Store `content` (with BLAKE3 hash `hash_hex`) as a blob file, or reuse
+    /// the registered blob with that hash. Returns the storage path relative
+    /// to the data directory.
+    pub(crate) fn store_blob(
+        &self,
+        conn: &Connection,
+        hash_hex: &str,
+        content: &[u8],
+        cmd_hint: &str,
+    ) -> Result<String> {
+        self.register_blob(conn, hash_hex, content.len() as u64, cmd_hint, |blob_path| {
+            atomic::write_file(blob_path, content)
+        })
+    }
+
+    /// The storage path of the local registered blob with hash `hash_hex`,
+    /// counting a new reference to it, or `None` if there isn't one.
+    pub(crate) fn reuse_blob(&self, conn: &Connection, hash_hex: &str) -> Result<Option<String>> {
+        let existing: Option<String> = conn
+            .query_row(
+                "SELECT storage_path FROM blob_registry WHERE content_hash = ?",
+                params![hash_hex],
+                |row| row.get(0),
+            )
+            .ok();
+        let Some(path) = existing.filter(|p| !is_tiered(p)) else {
+            return Ok(None);
         };
+        if !self.config.data_dir().join(&path).exists() {
+            return Ok(None);
+        }
+        conn.execute(
+            "UPDATE blob_registry SET ref_count = ref_count + 1, last_accessed = CURRENT_TIMESTAMP WHERE content_hash = ?",
+            params![hash_hex],
+        )?;
+        Ok(Some(path))
+    }
 
-        self.write_output(&record)
+    /// Reuse the registered blob with hash `hash_hex`, or create its file
+    /// with `write` (which returns false if the file already existed) and
+    /// register it. Returns the storage path relative to the data directory.
+    pub(crate) fn register_blob(
+        &self,
+        conn: &Connection,
+        hash_hex: &str,
+        byte_length: u64,
+        cmd_hint: &str,
+        write: impl FnOnce(&std::path::Path) -> std::io::Result<bool>,
+    ) -> Result<String> {
+        // Check if blob already exists (dedup check)
+        let existing: std::result::Result<String, _> = conn.query_row(
+            "SELECT storage_path FROM blob_registry WHERE content_hash = ?",
+            params![hash_hex],
+            |row| row.get(0),
+        );
+
+        Ok(match existing {
+            // Tiered blobs live in cold storage; write a local copy again
+            Ok(path) if !is_tiered(&path) => {
+                // DEDUP HIT - increment ref count
+                conn.execute(
+                    "UPDATE blob_registry SET ref_count = ref_count + 1, last_accessed = CURRENT_TIMESTAMP WHERE content_hash = ?",
+                    params![hash_hex],
+                )?;
+                path
+            }
+            existing => {
+                // DEDUP MISS in registry - write new blob atomically
+                let blob_path = self.config.blob_path(hash_hex, cmd_hint);
+
+                // Ensure subdirectory exists
+                if let Some(parent) = blob_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                // Compute relative path for storage_ref
+                let rel_path = blob_path
+                    .strip_prefix(self.config.data_dir())
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_else(|_| blob_path.to_string_lossy().to_string());
+
+                // Atomic write: temp file + rename (handles concurrent writes)
+                let wrote_new = write(&blob_path)?;
+
+                if existing.is_ok() {
+                    // Was tiered - point the registry back at the local copy
+                    conn.execute(
+                        "UPDATE blob_registry SET storage_path = ?, ref_count = ref_count + 1, last_accessed = CURRENT_TIMESTAMP WHERE content_hash = ?",
+                        params![&rel_path, hash_hex],
+                    )?;
+                } else if wrote_new {
+                    // We wrote the file - register in blob_registry
+                    conn.execute(
+                        "INSERT INTO blob_registry (content_hash, byte_length, storage_path) VALUES (?, ?, ?)",
+                        params![hash_hex, byte_length as i64, &rel_path],
+                    )?;
+                } else {
+                    // Another process wrote this blob concurrently - increment ref_count
+                    conn.execute(
+                        "UPDATE blob_registry SET ref_count = ref_count + 1, last_accessed = CURRENT_TIMESTAMP WHERE content_hash = ?",
+                        params![hash_hex],
+                    )?;
+                }
+
+                rel_path
+            }
+        })
     }
 
     /// Write an output record to the store (low-level).
@@ -138,18 +259,33 @@ impl Store {
 
     /// Write output to a Parquet file (multi-writer safe).
     fn write_output_parquet(&self, record: &OutputRecord) -> Result<()> {
+        self.write_outputs_parquet(std::slice::from_ref(record))
+    }
+
+    /// Write outputs from one date partition to a single Parquet file
+    /// (multi-writer safe): `{invocation_id}--{stream}--{id}.parquet` for a
+    /// lone output, `{invocation_id}--batch--{id}.parquet` after its first
+    /// output for a group.
+    pub(super) fn write_outputs_parquet(&self, records: &[OutputRecord]) -> Result<()> {
+        let Some(first) = records.first() else {
+            return Ok(());
+        };
         let conn = self.connection_with_options(false)?;
 
         // Ensure the partition directory exists
-        let partition_dir = self.config.outputs_dir(&record.date);
+        let partition_dir = self.config.outputs_dir(&first.date);
         fs::create_dir_all(&partition_dir)?;
 
         // Generate filename: {invocation_id}--{stream}--{id}.parquet
+        let stream = match records {
+            [record] => record.stream.as_str(),
+            _ => "batch",
+        };
         let filename = format!(
             "{}--{}--{}.parquet",
-            record.invocation_id,
-            sanitize_filename(&record.stream),
-            record.id
+            first.invocation_id,
+            sanitize_filename(stream),
+            first.id
         );
         let file_path = partition_dir.join(&filename);
 
@@ -165,29 +301,33 @@ impl Store {
                 storage_type VARCHAR,
                 storage_ref VARCHAR,
                 content_type VARCHAR,
-                date DATE
+                date DATE,
+                sequence INTEGER
             );
             "#,
         )?;
 
-        conn.execute(
-            r#"
-            INSERT INTO temp_output VALUES (
-                ?, ?, ?, ?, ?, ?, ?, ?, ?
-            )
-            "#,
-            params![
-                record.id.to_string(),
-                record.invocation_id.to_string(),
-                record.stream,
-                record.content_hash,
-                record.byte_length as i64,
-                record.storage_type,
-                record.storage_ref,
-                record.content_type,
-                record.date.to_string(),
-            ],
-        )?;
+        for record in records {
+            conn.execute(
+                r#"
+                INSERT INTO temp_output VALUES (
+                    ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
+                )
+                "#,
+                params![
+                    record.id.to_string(),
+                    record.invocation_id.to_string(),
+                    record.stream,
+                    record.content_hash,
+                    record.byte_length as i64,
+                    record.storage_type,
+                    record.storage_ref,
+                    record.content_type,
+                    record.date.to_string(),
+                    record.sequence,
+                ],
+            )?;
+        }
 
         // Atomic write: COPY to temp file, then rename
         let temp_path = atomic::temp_path(&file_path);
@@ -211,11 +351,7 @@ impl Store {
         let conn = self.connection()?;
 
         conn.execute(
-            r#"
-            INSERT INTO local.outputs VALUES (
-                ?, ?, ?, ?, ?, ?, ?, ?, ?
-            )
-            "#,
+            super::OUTPUT_INSERT_SQL,
             params![
                 record.id.to_string(),
                 record.invocation_id.to_string(),
@@ -226,40 +362,54 @@ impl Store {
                 record.storage_ref,
                 record.content_type,
                 record.date.to_string(),
+                record.sequence,
             ],
         )?;
 
         Ok(())
     }
 
-    /// Get outputs for an invocation by ID, optionally filtered by stream.
+    /// Get outputs for an invocation by ID, optionally filtered by stream,
+    /// ordered by stream and then sequence. Attached artifacts are only
+    /// returned when asked for by stream (see [`Store::get_artifacts`]).
     pub fn get_outputs(
         &self,
         invocation_id: &str,
         stream_filter: Option<&str>,
+    ) -> Result<Vec<OutputInfo>> {
+        self.query_outputs(invocation_id, &stream_clause(stream_filter), "stream, sequence, id")
+    }
+
+    /// Get outputs for an invocation in the order they were captured: by
+    /// sequence, so each stream's parts concatenate in order.
+    pub fn get_outputs_ordered(
+        &self,
+        invocation_id: &str,
+        stream_filter: Option<&str>,
+    ) -> Result<Vec<OutputInfo>> {
+        self.query_outputs(invocation_id, &stream_clause(stream_filter), "sequence, id")
+    }
+
+    pub(super) fn query_outputs(
+        &self,
+        invocation_id: &str,
+        stream_clause: &str,
+        order_by: &str,
     ) -> Result<Vec<OutputInfo>> {
         let conn = self.connection()?;
 
-        let sql = match stream_filter {
-            Some(stream) => format!(
-                r#"
-                SELECT storage_type, storage_ref, stream, byte_length, content_hash
-                FROM outputs
-                WHERE invocation_id = '{}' AND stream = '{}'
-                ORDER BY stream
-                "#,
-                invocation_id, stream
-            ),
-            None => format!(
-                r#"
-                SELECT storage_type, storage_ref, stream, byte_length, content_hash
-                FROM outputs
-                WHERE invocation_id = '{}'
-                ORDER BY stream
-                "#,
-                invocation_id
-            ),
-        };
+        let sql = format!(
+            r#"
+            SELECT storage_type, storage_ref, stream, byte_length, content_hash, {sequence} AS sequence
+            FROM outputs
+            WHERE invocation_id = '{id}' {stream}
+            ORDER BY {order}
+            "#,
+            sequence = SEQUENCE_SQL,
+            id = invocation_id,
+            stream = stream_clause,
+            order = order_by,
+        );
 
         let mut stmt = match conn.prepare(&sql) {
             Ok(stmt) => stmt,
@@ -278,6 +428,7 @@ impl Store {
                 stream: row.get(2)?,
                 byte_length: row.get(3)?,
                 content_hash: row.get(4)?,
+                sequence: row.get(5)?,
             })
         });
 
@@ -306,14 +457,26 @@ impl Store {
     }
 
     /// Read content from storage using DuckDB's read_blob (handles both data: and file:// URLs).
+    ///
+    /// Blobs tiered to cold storage are read from there, and copied back
+    /// locally when `blobs.cache_reads` is set.
+    ///
+    /// With `verify_blob_hashes`, content that doesn't match its hash is
+    /// reported as [`Error::ContentCorrupted`].
     pub fn read_output_content(&self, output: &OutputInfo) -> Result<Vec<u8>> {
         let conn = self.connection()?;
 
         // Resolve the storage_ref to an absolute path for file:// URLs
-        let resolved_ref = if output.storage_ref.starts_with("file://") {
-            let rel_path = output.storage_ref.strip_prefix("file://").unwrap();
+        let mut tiered = None;
+        let resolved_ref = if let Some(rel_path) = output.storage_ref.strip_prefix("file://") {
             let abs_path = self.config.data_dir().join(rel_path);
-            format!("file://{}", abs_path.display())
+            match self.cold_blob_url(&abs_path) {
+                Some(url) if !abs_path.exists() => {
+                    tiered = Some((rel_path, abs_path));
+                    url
+                }
+                _ => format!("file://{}", abs_path.display()),
+            }
         } else {
             output.storage_ref.clone()
         };
@@ -326,11 +489,39 @@ impl Store {
             )
             .map_err(|e| Error::Storage(format!("Failed to read blob: {}", e)))?;
 
+        if self.config.verify_blob_hashes {
+            output.verify(&content)?;
+        }
+
+        if let Some((rel_path, abs_path)) = tiered {
+            if self.config.blobs.cache_reads {
+                if let Err(e) = self.cache_cold_blob(&conn, &output.content_hash, rel_path, &abs_path, &content) {
+                    tracing::warn!(error = %e, hash = %output.content_hash, "could not cache cold blob locally");
+                }
+            }
+        }
+
         Ok(content)
     }
 }
 
 impl OutputInfo {
+    /// Check `content` against this output's BLAKE3 hash (if it has one).
+    pub fn verify(&self, content: &[u8]) -> Result<()> {
+        if self.content_hash.is_empty() {
+            return Ok(());
+        }
+        let actual = blake3::hash(content).to_hex().to_string();
+        if actual != self.content_hash {
+            return Err(Error::ContentCorrupted {
+                storage_ref: self.storage_ref.chars().take(80).collect(),
+                expected: self.content_hash.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
     /// Read the content from storage (inline or blob).
     /// Prefer Store::read_output_content() which uses DuckDB for unified access.
     #[deprecated(note = "Use Store::read_output_content() instead for DuckDB-based reads")]
@@ -491,6 +682,32 @@ mod tests {
         assert!(!outputs[0].content_hash.is_empty());
     }
 
+    #[test]
+    fn test_outputs_ordered_by_sequence() {
+        let (_tmp, store) = setup_store();
+
+        let inv = InvocationRecord::new("test-session", "make watch", "/home/user", 0, "test@client");
+        let inv_id = inv.id;
+        let date = inv.date();
+        store.write_invocation(&inv).unwrap();
+
+        for phase in [&b"build 1\n"[..], b"build 2\n", b"build 3\n"] {
+            store.store_output(inv_id, "stdout", phase, date, None).unwrap();
+        }
+        store.store_output(inv_id, "stderr", b"warning\n", date, None).unwrap();
+
+        let outputs = store.get_outputs_ordered(&inv_id.to_string(), Some("stdout")).unwrap();
+        assert_eq!(outputs.iter().map(|o| o.sequence).collect::<Vec<_>>(), vec![0, 1, 2]);
+        let content: Vec<u8> = outputs
+            .iter()
+            .flat_map(|o| store.read_output_content(o).unwrap())
+            .collect();
+        assert_eq!(content, b"build 1\nbuild 2\nbuild 3\n");
+
+        let stderr = store.get_outputs_ordered(&inv_id.to_string(), Some("stderr")).unwrap();
+        assert_eq!(stderr[0].sequence, 0);
+    }
+
     #[test]
     fn test_output_decode_inline() {
         let content = b"hello world";
@@ -0,0 +1,176 @@
+//! Execution plans for mutating operations.
+//!
+//! A [`Plan`] lists the changes an operation would make, computed without
+//! making them. The CLI's `--dry-run` prints the plan instead of applying it.
+//! Link, format-hint and buffer changes are planned here; archiving,
+//! compaction and push plan as part of their dry runs (the `plan` of
+//! [`ArchiveStats`](super::ArchiveStats), [`CompactStats`](super::CompactStats)
+//! and [`PushStats`](super::PushStats)).
+
+use std::fmt;
+
+use super::Store;
+use crate::{FormatHints, Result};
+
+/// The changes a mutating operation would make, one line per change.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Plan {
+    actions: Vec<String>,
+}
+
+impl Plan {
+    /// An empty plan (nothing to do).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one change, e.g. `remove link from 0193abcd to JIRA-123`.
+    pub fn add(&mut self, action: impl Into<String>) {
+        self.actions.push(action.into());
+    }
+
+    /// Add the changes of `other` after these.
+    pub fn append(&mut self, other: &Plan) {
+        self.actions.extend(other.actions.iter().cloned());
+    }
+
+    /// The recorded changes, in order.
+    pub fn actions(&self) -> &[String] {
+        &self.actions
+    }
+
+    /// Whether the operation would change nothing.
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.actions.is_empty() {
+            return writeln!(f, "Would do nothing.");
+        }
+        writeln!(f, "Would:")?;
+        for action in &self.actions {
+            writeln!(f, "  {}", action)?;
+        }
+        Ok(())
+    }
+}
+
+fn short_id(id: &str) -> &str {
+    &id[..8.min(id.len())]
+}
+
+/// A byte count for plan lines, e.g. `1.5 MB`.
+pub(super) fn size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+impl Store {
+    /// Plan for [`add_link`](Store::add_link).
+    pub fn plan_link(&self, invocation_id: &str, kind: &str, target: &str) -> Result<Plan> {
+        let mut plan = Plan::new();
+        let exists = self
+            .links_for(invocation_id)?
+            .iter()
+            .any(|link| link.kind == kind && link.target == target);
+        if !exists {
+            plan.add(format!("link {} to {} {}", short_id(invocation_id), kind, target));
+        }
+        Ok(plan)
+    }
+
+    /// Plan for [`remove_link`](Store::remove_link).
+    pub fn plan_unlink(&self, invocation_id: &str, target: &str) -> Result<Plan> {
+        let mut plan = Plan::new();
+        for link in self.links_for(invocation_id)?.iter().filter(|link| link.target == target) {
+            plan.add(format!(
+                "remove link from {} to {} {}",
+                short_id(invocation_id),
+                link.kind,
+                link.target
+            ));
+        }
+        Ok(plan)
+    }
+
+    /// Plan for replacing the saved format hints with `after`.
+    pub fn plan_format_hints(&self, after: &FormatHints) -> Result<Plan> {
+        Ok(diff_format_hints(&self.load_format_hints()?, after))
+    }
+}
+
+/// Changes turning `before` into `after`, by pattern.
+fn diff_format_hints(before: &FormatHints, after: &FormatHints) -> Plan {
+    let mut plan = Plan::new();
+    for hint in after.hints() {
+        match before.get(&hint.pattern) {
+            None => plan.add(format!(
+                "add format hint {} -> {} (priority {})",
+                hint.pattern, hint.format, hint.priority
+            )),
+            Some(old) if old != hint => plan.add(format!(
+                "update format hint {}: {} (priority {}) -> {} (priority {})",
+                hint.pattern, old.format, old.priority, hint.format, hint.priority
+            )),
+            Some(_) => {}
+        }
+    }
+    for hint in before.hints() {
+        if after.get(&hint.pattern).is_none() {
+            plan.add(format!("remove format hint {} -> {}", hint.pattern, hint.format));
+        }
+    }
+    if before.default_format() != after.default_format() {
+        plan.add(format!(
+            "set default format {} -> {}",
+            before.default_format(),
+            after.default_format()
+        ));
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FormatHint;
+
+    #[test]
+    fn test_diff_format_hints() {
+        let mut before = FormatHints::new();
+        before.add(FormatHint::new("*make*", "make"));
+        before.add(FormatHint::new("*pytest*", "pytest"));
+
+        let mut after = before.clone();
+        assert!(diff_format_hints(&before, &after).is_empty());
+
+        after.add(FormatHint::with_priority("*make*", "gcc", 700));
+        after.remove("*pytest*");
+        after.add(FormatHint::new("*cargo*", "cargo"));
+        after.set_default_format("gcc");
+
+        let plan = diff_format_hints(&before, &after);
+        assert_eq!(
+            plan.actions(),
+            [
+                "update format hint *make*: make (priority 500) -> gcc (priority 700)",
+                "add format hint *cargo* -> cargo (priority 500)",
+                "remove format hint *pytest* -> pytest",
+                "set default format auto -> gcc",
+            ]
+        );
+        assert_eq!(plan.to_string().lines().next(), Some("Would:"));
+    }
+}
@@ -0,0 +1,302 @@
+//! Re-balancing output storage between inline data and blob files.
+//!
+//! Outputs are stored inline below `inline_threshold` and as blob files at
+//! or above it, but only as of the threshold in effect when they were
+//! captured. [`Store::rebalance_blobs`] moves outputs stored on the wrong
+//! side of the current threshold: small blobs are re-inlined (and their
+//! files trashed once nothing references them) and large inline data is
+//! written out to blob files. Small blobs shared by several outputs (see
+//! [`Store::dedup_outputs`]) stay blobs. In parquet mode the affected output
+//! files are replaced by rewritten copies under new names.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use duckdb::{params, Connection};
+
+use super::compact::is_seed_file;
+use super::tiering::{content_names, is_tiered};
+use super::{atomic, OutputInfo, Store};
+use crate::config::StorageMode;
+use crate::{Error, Result};
+
+/// Results of a rebalance.
+#[derive(Debug, Default)]
+pub struct RebalanceStats {
+    /// Blob outputs now stored inline.
+    pub inlined: usize,
+    /// Inline outputs now stored as blobs.
+    pub externalized: usize,
+    /// Output parquet files rewritten.
+    pub files_rewritten: usize,
+    /// Blob files no longer referenced, moved to the trash.
+    pub blobs_removed: usize,
+}
+
+/// An output row stored on the wrong side of the threshold.
+struct Misplaced {
+    id: String,
+    output: OutputInfo,
+}
+
+impl Store {
+    /// Move outputs stored on the wrong side of `threshold` (default:
+    /// `inline_threshold`) to inline or blob storage.
+    pub fn rebalance_blobs(&self, threshold: Option<usize>, dry_run: bool) -> Result<RebalanceStats> {
+        let _sync = self.sync_guard();
+        let threshold = threshold.unwrap_or(self.config.inline_threshold);
+        self.with_connection(|conn| {
+            let mut stats = RebalanceStats::default();
+            let mut inlined_hashes = BTreeSet::new();
+
+            match self.config.storage_mode {
+                StorageMode::Parquet => {
+                    let files = self.output_files()?;
+                    let progress = self.progress_phase("rebalance outputs", Some(files.len() as u64));
+                    for file in files {
+                        self.checkpoint()?;
+                        progress.advance(1);
+                        let source = format!("read_parquet('{}')", file.display().to_string().replace('\'', "''"));
+                        let rows = misplaced_outputs(conn, &source, threshold)?;
+                        if rows.is_empty() {
+                            continue;
+                        }
+                        count(&rows, threshold, &mut stats, &mut inlined_hashes);
+                        if dry_run {
+                            continue;
+                        }
+                        let changes = self.rebalanced_refs(conn, &rows, threshold)?;
+                        rewrite_output_file(conn, &file, &changes)?;
+                        stats.files_rewritten += 1;
+                    }
+                }
+                StorageMode::DuckDB => {
+                    let rows = misplaced_outputs(conn, "local.outputs", threshold)?;
+                    count(&rows, threshold, &mut stats, &mut inlined_hashes);
+                    if !dry_run {
+                        for (id, storage_type, storage_ref) in self.rebalanced_refs(conn, &rows, threshold)? {
+                            conn.execute(
+                                "UPDATE local.outputs SET storage_type = ?, storage_ref = ? WHERE id::VARCHAR = ?",
+                                params![storage_type, storage_ref, id],
+                            )?;
+                        }
+                    }
+                }
+            }
+
+            if !dry_run {
+                stats.blobs_removed = self.remove_unreferenced_blobs(conn, &inlined_hashes)?;
+            }
+            Ok(stats)
+        })
+    }
+
+    /// Output parquet files in recent and archive, excluding seed files.
+    pub(super) fn output_files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for tier in [self.config.recent_dir(), self.config.archive_dir()] {
+            let outputs_dir = tier.join("outputs");
+            if !outputs_dir.exists() {
+                continue;
+            }
+            for partition in fs::read_dir(&outputs_dir)?.flatten() {
+                if !partition.path().is_dir() {
+                    continue;
+                }
+                for entry in fs::read_dir(partition.path())?.flatten() {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if name.ends_with(".parquet") && !name.starts_with('.') && !is_seed_file(&name) {
+                        files.push(entry.path());
+                    }
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// New `(id, storage_type, storage_ref)` for each misplaced row.
+    fn rebalanced_refs(
+        &self,
+        conn: &Connection,
+        rows: &[Misplaced],
+        threshold: usize,
+    ) -> Result<Vec<(String, String, String)>> {
+        use base64::Engine;
+
+        let mut changes = Vec::new();
+        for row in rows {
+            let change = if (row.output.byte_length as usize) < threshold {
+                let content = self.read_output_content(&row.output)?;
+                let b64 = base64::engine::general_purpose::STANDARD.encode(&content);
+                ("inline".to_string(), format!("data:application/octet-stream;base64,{}", b64))
+            } else {
+                let b64 = row.output.storage_ref.split(',').nth(1).unwrap_or("");
+                let content = base64::engine::general_purpose::STANDARD
+                    .decode(b64)
+                    .map_err(|e| Error::Storage(format!("invalid inline output {}: {}", row.id, e)))?;
+                let storage_path = self.store_blob(conn, &row.output.content_hash, &content, "output")?;
+                ("blob".to_string(), format!("file://{}", storage_path))
+            };
+            changes.push((row.id.clone(), change.0, change.1));
+        }
+        Ok(changes)
+    }
+
+    /// Unregister and trash the blobs for `hashes` that no output references
+    /// as a blob any more. Returns how many blob files were trashed.
+    fn remove_unreferenced_blobs(&self, conn: &Connection, hashes: &BTreeSet<String>) -> Result<usize> {
+        let data_dir = self.config.data_dir();
+        let mut files = Vec::new();
+        for hash in hashes {
+            let referenced: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM main.outputs WHERE content_hash = ? AND storage_type = 'blob'",
+                params![hash],
+                |row| row.get(0),
+            )?;
+            if referenced > 0 {
+                continue;
+            }
+            let storage_path: Option<String> = conn
+                .query_row(
+                    "SELECT storage_path FROM blob_registry WHERE content_hash = ?",
+                    params![hash],
+                    |row| row.get(0),
+                )
+                .ok();
+            conn.execute("DELETE FROM blob_registry WHERE content_hash = ?", params![hash])?;
+            if let Some(path) = storage_path.filter(|p| !is_tiered(p)) {
+                let blob = data_dir.join(path);
+                if blob.exists() {
+                    files.extend(content_names(&blob, hash)?);
+                }
+            }
+        }
+
+        let removed = files.len();
+        self.trash_files("rebalance blobs", &files)?;
+        Ok(removed)
+    }
+}
+
+/// Rows of `source` stored on the wrong side of `threshold`.
+fn misplaced_outputs(conn: &Connection, source: &str, threshold: usize) -> Result<Vec<Misplaced>> {
+    let sql = format!(
+        "SELECT id::VARCHAR, storage_type, storage_ref, stream, byte_length, content_hash FROM {source} \
+         WHERE (storage_type = 'blob' AND byte_length < {threshold} \
+                AND content_hash NOT IN (SELECT content_hash FROM blob_registry WHERE ref_count > 1)) \
+            OR (storage_type = 'inline' AND byte_length >= {threshold})",
+        source = source,
+        threshold = threshold
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Misplaced {
+            id: row.get(0)?,
+            output: OutputInfo {
+                storage_type: row.get(1)?,
+                storage_ref: row.get(2)?,
+                stream: row.get(3)?,
+                byte_length: row.get(4)?,
+                content_hash: row.get(5)?,
+                // Not needed to move content, and older files don't have it
+                sequence: 0,
+            },
+        })
+    })?;
+
+    let mut misplaced = Vec::new();
+    for row in rows {
+        misplaced.push(row?);
+    }
+    Ok(misplaced)
+}
+
+fn count(rows: &[Misplaced], threshold: usize, stats: &mut RebalanceStats, inlined_hashes: &mut BTreeSet<String>) {
+    for row in rows {
+        if (row.output.byte_length as usize) < threshold {
+            stats.inlined += 1;
+            inlined_hashes.insert(row.output.content_hash.clone());
+        } else {
+            stats.externalized += 1;
+        }
+    }
+}
+
+/// Replace `file` with a copy whose rows in `changes` get a new storage type
+/// and ref. Data files are never rewritten in place: the copy is published
+/// under a new name, then the original is removed.
+pub(super) fn rewrite_output_file(conn: &Connection, file: &Path, changes: &[(String, String, String)]) -> Result<()> {
+    conn.execute(
+        &format!(
+            "CREATE OR REPLACE TEMP TABLE rebalance_temp AS SELECT * FROM read_parquet('{}')",
+            file.display().to_string().replace('\'', "''")
+        ),
+        [],
+    )?;
+    for (id, storage_type, storage_ref) in changes {
+        conn.execute(
+            "UPDATE rebalance_temp SET storage_type = ?, storage_ref = ? WHERE id::VARCHAR = ?",
+            params![storage_type, storage_ref, id],
+        )?;
+    }
+
+    let rewritten = rewritten_path(file);
+    let temp_path = atomic::temp_path(&rewritten);
+    conn.execute(
+        &format!(
+            "COPY rebalance_temp TO '{}' (FORMAT PARQUET, COMPRESSION ZSTD)",
+            temp_path.display().to_string().replace('\'', "''")
+        ),
+        [],
+    )?;
+    conn.execute("DROP TABLE rebalance_temp", [])?;
+
+    atomic::rename_into_place(&temp_path, &rewritten)?;
+    fs::remove_file(file)?;
+    Ok(())
+}
+
+/// A new name for a rewrite of `file`, in the same session group: its last
+/// `--` segment (a uuid, for most files) replaced by a fresh uuid.
+fn rewritten_path(file: &Path) -> PathBuf {
+    let stem = file.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let prefix = stem.rsplit_once("--").map_or(stem.as_str(), |(prefix, _)| prefix);
+    file.with_file_name(format!("{}--{}.parquet", prefix, uuid::Uuid::now_v7()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rebalance_reinlines_small_blobs() {
+        let tmp = TempDir::new().unwrap();
+        let mut config = Config::with_root(tmp.path());
+        config.inline_threshold = 16;
+        initialize(&config).unwrap();
+        let store = Store::open(config.clone()).unwrap();
+
+        let inv = InvocationRecord::new("test-session", "echo hello", "/home/user", 0, "user@laptop");
+        store.write_invocation(&inv).unwrap();
+        let content = b"hello, rebalanced world".to_vec();
+        store.store_output(inv.id, "stdout", &content, inv.date(), Some("echo")).unwrap();
+        let before = store.get_output(&inv.id.to_string()).unwrap().unwrap();
+        assert_eq!(before.storage_type, "blob");
+
+        let store = Store::open(Config { inline_threshold: 4096, ..config }).unwrap();
+        let plan = store.rebalance_blobs(None, true).unwrap();
+        assert_eq!((plan.inlined, plan.files_rewritten), (1, 0));
+
+        let stats = store.rebalance_blobs(None, false).unwrap();
+        assert_eq!((stats.inlined, stats.blobs_removed), (1, 1));
+        let after = store.get_output(&inv.id.to_string()).unwrap().unwrap();
+        assert_eq!(after.storage_type, "inline");
+        assert_eq!(store.read_output_content(&after).unwrap(), content);
+    }
+}
@@ -0,0 +1,296 @@
+//! Recent index - a small denormalized table of the newest invocations.
+//!
+//! Listing (`shq i`, `shq history`) only needs the last few dozen
+//! invocations, but `main.invocations` joins attempts and outcomes across
+//! every parquet file and cache. `main.recent_index` keeps the newest
+//! [`RECENT_INDEX_SIZE`] invocations in the main DuckDB database, updated on
+//! every write, so common listings never touch the full history.
+//!
+//! The index is complete above a watermark: every invocation with a
+//! timestamp strictly after `recent_index_state.watermark` is in the index.
+//! A query answered with enough rows from the index is therefore exact;
+//! otherwise it falls back to the full view. Operations that change history
+//! behind the index (pull, archive) invalidate it, and the next listing
+//! rebuilds it.
+
+use duckdb::{params, Connection};
+
+use super::invocations::{
+    invocations_sql, range_limit_offset, read_summaries, InvocationSummary,
+};
+use super::Store;
+use crate::query::{Query, QueryComponent};
+use crate::schema::{AttemptRecord, OutcomeRecord};
+use crate::Result;
+
+/// Number of invocations kept in the recent index.
+pub const RECENT_INDEX_SIZE: usize = 5000;
+
+/// Tables backing the recent index.
+///
+/// Lives in the main DuckDB database in both storage modes (like
+/// `blob_registry`). An empty `recent_index_state` means the index has not
+/// been built yet.
+pub(crate) const RECENT_INDEX_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS main.recent_index (
+    id          UUID PRIMARY KEY,
+    session_id  VARCHAR,
+    timestamp   TIMESTAMP,
+    duration_ms BIGINT,
+    cwd         VARCHAR,
+    cmd         VARCHAR,
+    executable  VARCHAR,
+    exit_code   INTEGER,
+    client_id   VARCHAR,
+    hostname    VARCHAR,
+    date        DATE
+);
+CREATE TABLE IF NOT EXISTS main.recent_index_state (
+    watermark TIMESTAMP
+);
+"#;
+
+/// Query fields answerable from the index's columns.
+const INDEXED_FIELDS: &[&str] = &[
+    "cmd", "command", "exit", "exit_code", "duration", "duration_ms", "cwd", "session",
+    "session_id", "date", "timestamp", "linked",
+];
+
+impl Store {
+    /// Rebuild the recent index from `main.invocations`.
+    ///
+    /// Returns the number of indexed invocations.
+    pub fn rebuild_recent_index(&self) -> Result<usize> {
+        let conn = self.connection()?;
+        rebuild(&conn)
+    }
+
+    /// Mark the recent index stale; the next listing rebuilds it.
+    ///
+    /// Call after changing history other than through `write_attempt` /
+    /// `write_outcome` (pulling caches, archiving, pruning).
+    pub fn invalidate_recent_index(&self) -> Result<()> {
+        let conn = self.connection_with_options(false)?;
+        conn.execute("DELETE FROM main.recent_index_state", [])?;
+        Ok(())
+    }
+}
+
+/// Add a new attempt to the index (no-op if it is below the watermark).
+pub(crate) fn index_attempt(conn: &Connection, record: &AttemptRecord) -> Result<()> {
+    let timestamp = record.timestamp.to_rfc3339();
+    let inserted = conn.execute(
+        r#"
+        INSERT OR REPLACE INTO main.recent_index
+        SELECT ?::UUID, ?, ?::TIMESTAMP, NULL, ?, ?, ?, NULL, ?, ?, ?::DATE
+        WHERE EXISTS (
+            SELECT 1 FROM main.recent_index_state WHERE watermark < ?::TIMESTAMP
+        )
+        "#,
+        params![
+            record.id.to_string(),
+            record.session_id,
+            timestamp,
+            record.cwd,
+            record.cmd,
+            record.executable,
+            record.source_client,
+            record.hostname,
+            record.date().to_string(),
+            timestamp,
+        ],
+    )?;
+
+    if inserted > 0 {
+        let count: i64 =
+            conn.query_row("SELECT COUNT(*) FROM main.recent_index", [], |row| row.get(0))?;
+        // Trim in batches rather than on every write
+        if count as usize > RECENT_INDEX_SIZE + RECENT_INDEX_SIZE / 10 {
+            trim(conn)?;
+        }
+    }
+    Ok(())
+}
+
+/// Record an outcome on its indexed attempt (if still indexed).
+pub(crate) fn index_outcome(conn: &Connection, record: &OutcomeRecord) -> Result<()> {
+    conn.execute(
+        "UPDATE main.recent_index SET exit_code = ?, duration_ms = ? WHERE id = ?::UUID",
+        params![record.exit_code, record.duration_ms, record.attempt_id.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Answer a listing query from the index, or `None` to fall back to the full view.
+pub(crate) fn query(
+    conn: &Connection,
+    query: &Query,
+    default_limit: usize,
+    today: chrono::NaiveDate,
+) -> Result<Option<Vec<InvocationSummary>>> {
+    if !is_indexable(query) {
+        return Ok(None);
+    }
+    let (limit, offset) = range_limit_offset(query, default_limit);
+    if limit + offset > RECENT_INDEX_SIZE {
+        return Ok(None);
+    }
+
+    let complete = match watermark_state(conn)? {
+        Some(complete) => complete,
+        None => {
+            rebuild(conn)?;
+            watermark_state(conn)?.unwrap_or(false)
+        }
+    };
+
+    let results = read_summaries(conn, &invocations_sql("main.recent_index", query, default_limit, today))?;
+    // Fewer rows than asked for may mean matches older than the watermark
+    if results.len() == limit || complete {
+        Ok(Some(results))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Whether every filter in `query` can be evaluated against the index.
+fn is_indexable(query: &Query) -> bool {
+    query.filters.iter().all(|component| match component {
+        QueryComponent::FieldFilter(filter) => INDEXED_FIELDS.contains(&filter.field.as_str()),
+        QueryComponent::CommandRegex(_) | QueryComponent::Tag(_) => true,
+    })
+}
+
+/// `None` if the index is unbuilt, `Some(true)` if it holds all history.
+fn watermark_state(conn: &Connection) -> Result<Option<bool>> {
+    let mut stmt = conn.prepare("SELECT isinf(watermark) FROM main.recent_index_state LIMIT 1")?;
+    let mut rows = stmt.query([])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(row.get::<_, Option<bool>>(0)?.unwrap_or(false))),
+        None => Ok(None),
+    }
+}
+
+/// Refill the index with the newest invocations and reset the watermark.
+fn rebuild(conn: &Connection) -> Result<usize> {
+    conn.execute("DELETE FROM main.recent_index", [])?;
+    conn.execute("DELETE FROM main.recent_index_state", [])?;
+
+    let fill = format!(
+        r#"
+        INSERT INTO main.recent_index
+        SELECT id, session_id, timestamp, duration_ms, cwd, cmd, executable,
+               exit_code, client_id, hostname, date
+        FROM main.invocations
+        QUALIFY row_number() OVER (PARTITION BY id ORDER BY timestamp) = 1
+        ORDER BY timestamp DESC
+        LIMIT {}
+        "#,
+        RECENT_INDEX_SIZE + 1
+    );
+    let count = match conn.execute(&fill, []) {
+        Ok(count) => count,
+        Err(e) if e.to_string().contains("No files found") => 0,
+        Err(e) => return Err(e.into()),
+    };
+
+    if count > RECENT_INDEX_SIZE {
+        // One row past the limit was read so the watermark is a real boundary
+        trim(conn)?;
+    } else {
+        conn.execute("INSERT INTO main.recent_index_state VALUES ('-infinity')", [])?;
+    }
+
+    let indexed: i64 = conn.query_row("SELECT COUNT(*) FROM main.recent_index", [], |row| row.get(0))?;
+    Ok(indexed as usize)
+}
+
+/// Drop all but the newest [`RECENT_INDEX_SIZE`] rows and raise the watermark.
+///
+/// The watermark becomes the timestamp of the first row past the limit and
+/// every row at or below it is removed, so ties never straddle it.
+fn trim(conn: &Connection) -> Result<()> {
+    conn.execute_batch(&format!(
+        r#"
+        DELETE FROM main.recent_index_state;
+        INSERT INTO main.recent_index_state
+        SELECT timestamp FROM main.recent_index
+        ORDER BY timestamp DESC
+        LIMIT 1 OFFSET {};
+        DELETE FROM main.recent_index
+        WHERE timestamp <= (SELECT watermark FROM main.recent_index_state);
+        "#,
+        RECENT_INDEX_SIZE
+    ))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::query::parse_query;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    fn setup_store() -> (TempDir, Store) {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+        (tmp, store)
+    }
+
+    fn index_count(store: &Store) -> i64 {
+        let conn = store.connection().unwrap();
+        conn.query_row("SELECT COUNT(*) FROM main.recent_index", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_listing_builds_index() {
+        let (_tmp, store) = setup_store();
+
+        let record = InvocationRecord::new("s1", "make build", "/home/user", 0, "test@client");
+        store.write_invocation(&record).unwrap();
+        // Not built yet, so the write is not indexed
+        assert_eq!(index_count(&store), 0);
+
+        let recent = store.recent_invocations(10).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(index_count(&store), 1);
+    }
+
+    #[test]
+    fn test_writes_update_index() {
+        let (_tmp, store) = setup_store();
+        assert_eq!(store.rebuild_recent_index().unwrap(), 0);
+
+        let record = InvocationRecord::new("s1", "cargo test", "/home/user", 101, "test@client")
+            .with_duration(250);
+        store.write_invocation(&record).unwrap();
+        assert_eq!(index_count(&store), 1);
+
+        let results = store.query_invocations(&parse_query("%exit=101")).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].cmd, "cargo test");
+        assert_eq!(results[0].exit_code, 101);
+        assert_eq!(results[0].duration_ms, Some(250));
+    }
+
+    #[test]
+    fn test_invalidate_rebuilds_on_next_listing() {
+        let (_tmp, store) = setup_store();
+        store.rebuild_recent_index().unwrap();
+        store.invalidate_recent_index().unwrap();
+
+        // Writes while stale are skipped, then picked up by the rebuild
+        let record = InvocationRecord::new("s1", "ls", "/home/user", 0, "test@client");
+        store.write_invocation(&record).unwrap();
+        assert_eq!(index_count(&store), 0);
+
+        assert_eq!(store.recent_invocations(10).unwrap().len(), 1);
+        assert_eq!(index_count(&store), 1);
+    }
+}
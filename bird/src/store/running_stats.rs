@@ -0,0 +1,428 @@
+//! Running stats - per-day and per-executable aggregates kept up to date
+//! on every write.
+//!
+//! `shq stats`, `shq report` and the heatmap need counts over all of
+//! history, which means scanning every attempt and outcome once the store
+//! holds a few million invocations. `main.daily_stats` and
+//! `main.executable_stats` hold those counts in the main DuckDB database,
+//! bumped as attempts and outcomes are written, so reading them costs a
+//! handful of rows.
+//!
+//! An attempt adds a run and an entry in `main.running_stats_pending`; its
+//! outcome removes the entry and adds the failure and duration. Like the
+//! recent index, the aggregates start unbuilt (an empty
+//! `running_stats_state`): writes are skipped until the first read rebuilds
+//! them from `main.invocations`, and operations that change history behind
+//! them (pull, archive, trash restore) invalidate them.
+
+use chrono::NaiveDate;
+use duckdb::{params, Connection};
+
+use super::Store;
+use crate::schema::{AttemptRecord, OutcomeRecord};
+use crate::{Error, Result};
+
+/// Tables backing the running stats.
+///
+/// Live in the main DuckDB database in both storage modes (like the recent
+/// index).
+pub(crate) const RUNNING_STATS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS main.daily_stats (
+    date    DATE PRIMARY KEY,
+    count   BIGINT NOT NULL,
+    failed  BIGINT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS main.executable_stats (
+    executable        VARCHAR PRIMARY KEY,
+    runs              BIGINT NOT NULL,
+    failed            BIGINT NOT NULL,
+    timed_runs        BIGINT NOT NULL,
+    total_duration_ms BIGINT NOT NULL,
+    last_run          TIMESTAMP
+);
+CREATE TABLE IF NOT EXISTS main.running_stats_pending (
+    id         UUID PRIMARY KEY,
+    date       DATE,
+    executable VARCHAR
+);
+CREATE TABLE IF NOT EXISTS main.running_stats_state (
+    built_at TIMESTAMP
+);
+"#;
+
+/// Invocations counted once each (an attempt may appear in several layers).
+const DISTINCT_INVOCATIONS: &str = r#"
+    (SELECT * FROM main.invocations
+     QUALIFY row_number() OVER (PARTITION BY id ORDER BY timestamp) = 1)
+"#;
+
+/// Run totals for one executable, from [`Store::executable_stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutableStats {
+    pub executable: String,
+    pub runs: i64,
+    /// Runs with a non-zero exit code.
+    pub failed: i64,
+    /// Runs with a recorded duration.
+    pub timed_runs: i64,
+    pub total_duration_ms: i64,
+    /// Start of the most recent run.
+    pub last_run: Option<String>,
+}
+
+impl ExecutableStats {
+    /// Mean duration over the timed runs.
+    pub fn mean_duration_ms(&self) -> Option<i64> {
+        (self.timed_runs > 0).then(|| self.total_duration_ms / self.timed_runs)
+    }
+}
+
+impl Store {
+    /// Rebuild the running stats from `main.invocations`.
+    pub fn rebuild_running_stats(&self) -> Result<()> {
+        let conn = self.connection()?;
+        rebuild(&conn)
+    }
+
+    /// Mark the running stats stale; the next read rebuilds them.
+    ///
+    /// Call after changing history other than through `write_attempt` /
+    /// `write_outcome` (see [`Store::invalidate_recent_index`]).
+    pub fn invalidate_running_stats(&self) -> Result<()> {
+        let conn = self.connection_with_options(false)?;
+        conn.execute("DELETE FROM main.running_stats_state", [])?;
+        Ok(())
+    }
+
+    /// Total invocations and how many of them failed, from the running stats.
+    pub fn invocation_totals(&self) -> Result<(i64, i64)> {
+        let conn = self.connection()?;
+        ensure_built(&conn)?;
+        Ok(conn.query_row(
+            "SELECT COALESCE(SUM(count), 0)::BIGINT, COALESCE(SUM(failed), 0)::BIGINT FROM main.daily_stats",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?)
+    }
+
+    /// The `limit` most-run executables, most runs first.
+    pub fn executable_stats(&self, limit: usize) -> Result<Vec<ExecutableStats>> {
+        let conn = self.connection()?;
+        ensure_built(&conn)?;
+        read_executable_stats(&conn, limit)
+    }
+}
+
+/// Day counts between `start` and `end` (inclusive), oldest first.
+pub(crate) fn daily_counts(conn: &Connection, start: NaiveDate, end: NaiveDate) -> Result<Vec<(NaiveDate, i64, i64)>> {
+    ensure_built(conn)?;
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT date::VARCHAR, count, failed FROM main.daily_stats
+        WHERE date >= ?::DATE AND date <= ?::DATE
+        ORDER BY date
+        "#,
+    )?;
+    let rows = stmt.query_map(params![start.to_string(), end.to_string()], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?))
+    })?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        let (day, count, failed) = row?;
+        let date = NaiveDate::parse_from_str(&day, "%Y-%m-%d")
+            .map_err(|e| Error::Storage(format!("Invalid date '{}': {}", day, e)))?;
+        results.push((date, count, failed));
+    }
+    Ok(results)
+}
+
+/// Count a new attempt (no-op while the stats are unbuilt).
+pub(crate) fn record_attempt(conn: &Connection, record: &AttemptRecord) -> Result<()> {
+    if !is_built(conn)? {
+        return Ok(());
+    }
+    let date = record.date().to_string();
+    conn.execute(
+        "INSERT OR REPLACE INTO main.running_stats_pending VALUES (?::UUID, ?::DATE, ?)",
+        params![record.id.to_string(), date, record.executable],
+    )?;
+    conn.execute(
+        r#"
+        INSERT INTO main.daily_stats VALUES (?::DATE, 1, 0)
+        ON CONFLICT (date) DO UPDATE SET count = count + 1
+        "#,
+        params![date],
+    )?;
+    if let Some(executable) = &record.executable {
+        conn.execute(
+            r#"
+            INSERT INTO main.executable_stats VALUES (?, 1, 0, 0, 0, ?::TIMESTAMP)
+            ON CONFLICT (executable) DO UPDATE SET
+                runs = runs + 1,
+                last_run = greatest(last_run, excluded.last_run)
+            "#,
+            params![executable, record.timestamp.to_rfc3339()],
+        )?;
+    }
+    Ok(())
+}
+
+/// Add an outcome to its attempt's counts.
+///
+/// Outcomes for attempts that aren't pending (written before the stats
+/// were built and already counted, or recorded twice) are ignored.
+pub(crate) fn record_outcome(conn: &Connection, record: &OutcomeRecord) -> Result<()> {
+    let attempt_id = record.attempt_id.to_string();
+    let pending: Option<(String, Option<String>)> = match conn.query_row(
+        "SELECT date::VARCHAR, executable FROM main.running_stats_pending WHERE id = ?::UUID",
+        params![attempt_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ) {
+        Ok(pending) => Some(pending),
+        Err(duckdb::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(e.into()),
+    };
+    let Some((date, executable)) = pending else {
+        return Ok(());
+    };
+    conn.execute("DELETE FROM main.running_stats_pending WHERE id = ?::UUID", params![attempt_id])?;
+
+    let failed = i64::from(record.exit_code.is_some_and(|code| code != 0));
+    conn.execute(
+        "UPDATE main.daily_stats SET failed = failed + ? WHERE date = ?::DATE",
+        params![failed, date],
+    )?;
+    if let Some(executable) = executable {
+        conn.execute(
+            r#"
+            UPDATE main.executable_stats SET
+                failed = failed + ?,
+                timed_runs = timed_runs + ?,
+                total_duration_ms = total_duration_ms + ?
+            WHERE executable = ?
+            "#,
+            params![
+                failed,
+                i64::from(record.duration_ms.is_some()),
+                record.duration_ms.unwrap_or(0),
+                executable
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn is_built(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM main.running_stats_state", [], |row| row.get(0))?;
+    Ok(count > 0)
+}
+
+fn ensure_built(conn: &Connection) -> Result<()> {
+    if !is_built(conn)? {
+        rebuild(conn)?;
+    }
+    Ok(())
+}
+
+/// Recompute every aggregate from `main.invocations`.
+fn rebuild(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        DELETE FROM main.daily_stats;
+        DELETE FROM main.executable_stats;
+        DELETE FROM main.running_stats_pending;
+        DELETE FROM main.running_stats_state;
+        "#,
+    )?;
+
+    let fill = format!(
+        r#"
+        INSERT INTO main.daily_stats
+        SELECT date, COUNT(*), COUNT(*) FILTER (WHERE exit_code IS NOT NULL AND exit_code <> 0)
+        FROM {invocations}
+        GROUP BY date;
+
+        INSERT INTO main.executable_stats
+        SELECT executable,
+               COUNT(*),
+               COUNT(*) FILTER (WHERE exit_code IS NOT NULL AND exit_code <> 0),
+               COUNT(duration_ms),
+               COALESCE(SUM(duration_ms), 0),
+               MAX(timestamp)
+        FROM {invocations}
+        WHERE executable IS NOT NULL
+        GROUP BY executable;
+
+        INSERT INTO main.running_stats_pending
+        SELECT id, date, executable FROM {invocations}
+        WHERE status = 'pending';
+        "#,
+        invocations = DISTINCT_INVOCATIONS
+    );
+    match conn.execute_batch(&fill) {
+        Ok(()) => {}
+        Err(e) if e.to_string().contains("No files found") => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    conn.execute("INSERT INTO main.running_stats_state VALUES (now())", [])?;
+    Ok(())
+}
+
+fn read_executable_stats(conn: &Connection, limit: usize) -> Result<Vec<ExecutableStats>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT executable, runs, failed, timed_runs, total_duration_ms, last_run::VARCHAR
+        FROM main.executable_stats
+        ORDER BY runs DESC, executable
+        LIMIT ?
+        "#,
+    )?;
+    let rows = stmt.query_map(params![limit as i64], |row| {
+        Ok(ExecutableStats {
+            executable: row.get(0)?,
+            runs: row.get(1)?,
+            failed: row.get(2)?,
+            timed_runs: row.get(3)?,
+            total_duration_ms: row.get(4)?,
+            last_run: row.get(5)?,
+        })
+    })?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    /// The tables, marked built, in an in-memory database.
+    fn built_connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(RUNNING_STATS_DDL).unwrap();
+        conn.execute("INSERT INTO main.running_stats_state VALUES (now())", []).unwrap();
+        conn
+    }
+
+    fn attempt(cmd: &str) -> AttemptRecord {
+        AttemptRecord::new("s1", cmd, "/tmp", "test@client")
+    }
+
+    fn outcome(attempt: &AttemptRecord, exit_code: i32, duration_ms: i64) -> OutcomeRecord {
+        OutcomeRecord::completed(attempt.id, exit_code, Some(duration_ms), attempt.date())
+    }
+
+    #[test]
+    fn test_writes_update_running_stats() {
+        let conn = built_connection();
+
+        let make = attempt("make build");
+        let failing = attempt("make test");
+        let ls = attempt("ls");
+        for record in [&make, &failing, &ls] {
+            record_attempt(&conn, record).unwrap();
+        }
+        record_outcome(&conn, &outcome(&make, 0, 300)).unwrap();
+        record_outcome(&conn, &outcome(&failing, 2, 100)).unwrap();
+        // A repeated outcome doesn't count twice
+        record_outcome(&conn, &outcome(&failing, 2, 100)).unwrap();
+        // ls is still running
+
+        let day = make.date();
+        assert_eq!(daily_counts(&conn, day, day).unwrap(), [(day, 3, 1)]);
+
+        let stats = read_executable_stats(&conn, 10).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].executable, "make");
+        assert_eq!((stats[0].runs, stats[0].failed, stats[0].timed_runs), (2, 1, 2));
+        assert_eq!(stats[0].mean_duration_ms(), Some(200));
+        assert_eq!(stats[1].executable, "ls");
+        assert_eq!(stats[1].mean_duration_ms(), None);
+
+        let pending: Vec<String> = conn
+            .prepare("SELECT id::VARCHAR FROM main.running_stats_pending")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(pending, [ls.id.to_string()]);
+    }
+
+    #[test]
+    fn test_rebuild_counts_each_invocation_once() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(RUNNING_STATS_DDL).unwrap();
+        // Stand-in for the view: the same attempt seen in two layers
+        conn.execute_batch(
+            r#"
+            CREATE TABLE main.invocations (
+                id UUID, timestamp TIMESTAMP, date DATE, executable VARCHAR,
+                exit_code INTEGER, duration_ms BIGINT, status VARCHAR
+            );
+            INSERT INTO main.invocations VALUES
+                ('00000000-0000-7000-8000-000000000001', '2026-03-09 10:00', '2026-03-09', 'make', 2, 100, 'completed'),
+                ('00000000-0000-7000-8000-000000000001', '2026-03-09 10:00', '2026-03-09', 'make', 2, 100, 'completed'),
+                ('00000000-0000-7000-8000-000000000002', '2026-03-10 10:00', '2026-03-10', 'make', NULL, NULL, 'pending');
+            "#,
+        )
+        .unwrap();
+
+        ensure_built(&conn).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2026, 3, d).unwrap();
+        assert_eq!(daily_counts(&conn, day(1), day(31)).unwrap(), [(day(9), 1, 1), (day(10), 1, 0)]);
+        let stats = read_executable_stats(&conn, 10).unwrap();
+        assert_eq!((stats[0].runs, stats[0].failed, stats[0].mean_duration_ms()), (2, 1, Some(100)));
+        assert_eq!(stats[0].last_run.as_deref(), Some("2026-03-10 10:00:00"));
+
+        // The pending invocation's outcome still counts
+        let finished = OutcomeRecord::completed(
+            uuid::Uuid::parse_str("00000000-0000-7000-8000-000000000002").unwrap(),
+            1,
+            Some(300),
+            day(10),
+        );
+        record_outcome(&conn, &finished).unwrap();
+        assert_eq!(daily_counts(&conn, day(10), day(10)).unwrap(), [(day(10), 1, 1)]);
+    }
+
+    #[test]
+    fn test_unbuilt_stats_skip_writes() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(RUNNING_STATS_DDL).unwrap();
+
+        let record = attempt("make");
+        record_attempt(&conn, &record).unwrap();
+        record_outcome(&conn, &outcome(&record, 1, 10)).unwrap();
+
+        let rows: i64 = conn.query_row("SELECT COUNT(*) FROM main.daily_stats", [], |row| row.get(0)).unwrap();
+        assert_eq!(rows, 0);
+        assert_eq!(read_executable_stats(&conn, 10).unwrap(), []);
+    }
+
+    #[test]
+    fn test_store_totals_follow_writes() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        // Written before the first read: picked up by the rebuild
+        let record = InvocationRecord::new("s1", "make", "/p", 2, "test@client");
+        store.write_invocation(&record).unwrap();
+        assert_eq!(store.invocation_totals().unwrap(), (1, 1));
+
+        let record = InvocationRecord::new("s1", "make", "/p", 0, "test@client").with_duration(40);
+        store.write_invocation(&record).unwrap();
+        assert_eq!(store.invocation_totals().unwrap(), (2, 1));
+
+        store.invalidate_running_stats().unwrap();
+        assert_eq!(store.invocation_totals().unwrap(), (2, 1));
+        let make = &store.executable_stats(1).unwrap()[0];
+        assert_eq!((make.executable.as_str(), make.runs, make.failed), ("make", 2, 1));
+    }
+}
@@ -2,6 +2,7 @@
 
 use std::fs;
 
+use chrono::{DateTime, TimeDelta, Utc};
 use duckdb::params;
 
 use super::atomic;
@@ -10,6 +11,79 @@ use crate::config::StorageMode;
 use crate::schema::SessionRecord;
 use crate::Result;
 
+/// Summary of a session (for listing).
+#[derive(Debug, Clone)]
+pub struct SessionSummary {
+    pub session_id: String,
+    /// Name given with `shq session name`, if any.
+    pub name: Option<String>,
+    pub client_id: Option<String>,
+    pub invoker: Option<String>,
+    /// Timestamp of the first invocation in the session.
+    pub started_at: DateTime<Utc>,
+    /// Completion (or start) time of the last invocation in the session.
+    pub last_active: DateTime<Utc>,
+    pub invocation_count: i64,
+    pub failed_count: i64,
+}
+
+impl SessionSummary {
+    /// Time between the first and last recorded activity.
+    pub fn duration(&self) -> TimeDelta {
+        self.last_active - self.started_at
+    }
+}
+
+/// One invocation in a session timeline.
+#[derive(Debug, Clone)]
+pub struct SessionEntry {
+    pub id: String,
+    pub session_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub cmd: String,
+    pub cwd: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<i64>,
+    pub status: String,
+}
+
+impl SessionEntry {
+    /// When the invocation finished (start time if the duration is unknown).
+    pub fn ended_at(&self) -> DateTime<Utc> {
+        self.timestamp + TimeDelta::milliseconds(self.duration_ms.unwrap_or(0))
+    }
+}
+
+/// Lifecycle event: a session was given a name (value = name).
+pub const SESSION_NAMED: &str = "session_named";
+
+/// Lifecycle event: the session's shell exited.
+pub const SESSION_CLOSED: &str = "session_closed";
+
+/// DDL for session lifecycle events and the derived name lookup.
+///
+/// Lives in the main DuckDB database in both storage modes (like
+/// `blob_registry`): these are small, local-only annotations. Created
+/// lazily on connect so existing installations pick it up.
+pub(crate) const SESSION_EVENTS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS main.session_events (
+    session_id  VARCHAR NOT NULL,
+    event_type  VARCHAR NOT NULL,
+    value       VARCHAR,
+    timestamp   TIMESTAMP NOT NULL
+);
+CREATE VIEW IF NOT EXISTS main.session_names AS
+SELECT session_id, arg_max(value, timestamp) AS name
+FROM main.session_events
+WHERE event_type = 'session_named'
+GROUP BY session_id;
+"#;
+
+/// Convert epoch milliseconds from DuckDB into a UTC timestamp.
+pub(super) fn from_epoch_ms(ms: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(ms).unwrap_or_default()
+}
+
 impl Store {
     /// Write a session record to the store.
     ///
@@ -149,6 +223,251 @@ impl Store {
         Ok(())
     }
 
+    /// List sessions with activity, most recently active first.
+    ///
+    /// Sessions are derived from invocations so that counts and time spans
+    /// reflect what was actually recorded; the sessions table contributes
+    /// the invoker name.
+    pub fn list_sessions(&self, limit: usize) -> Result<Vec<SessionSummary>> {
+        let conn = self.connection()?;
+
+        let sql = format!(
+            r#"
+            SELECT
+                i.session_id,
+                any_value(n.name),
+                any_value(i.client_id),
+                any_value(s.invoker),
+                epoch_ms(MIN(i.timestamp)),
+                epoch_ms(MAX(COALESCE(i.completed_at, i.timestamp))),
+                COUNT(*),
+                COUNT(*) FILTER (WHERE i.exit_code IS NOT NULL AND i.exit_code <> 0)
+            FROM invocations i
+            LEFT JOIN (
+                SELECT session_id, any_value(invoker) AS invoker
+                FROM sessions
+                GROUP BY session_id
+            ) s ON i.session_id = s.session_id
+            LEFT JOIN main.session_names n ON i.session_id = n.session_id
+            GROUP BY i.session_id
+            ORDER BY 6 DESC
+            LIMIT {}
+            "#,
+            limit
+        );
+
+        let mut stmt = match conn.prepare(&sql) {
+            Ok(stmt) => stmt,
+            Err(e) => {
+                if e.to_string().contains("No files found") {
+                    return Ok(Vec::new());
+                }
+                return Err(e.into());
+            }
+        };
+
+        let rows = stmt.query_map([], |row| {
+            Ok(SessionSummary {
+                session_id: row.get(0)?,
+                name: row.get(1)?,
+                client_id: row.get(2)?,
+                invoker: row.get(3)?,
+                started_at: from_epoch_ms(row.get(4)?),
+                last_active: from_epoch_ms(row.get(5)?),
+                invocation_count: row.get(6)?,
+                failed_count: row.get(7)?,
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+
+    /// Get all invocations in a session, oldest first.
+    pub fn session_invocations(&self, session_id: &str) -> Result<Vec<SessionEntry>> {
+        let conn = self.connection()?;
+
+        let mut stmt = match conn.prepare(
+            r#"
+            SELECT id::VARCHAR, session_id, epoch_ms(timestamp), cmd, cwd, exit_code, duration_ms, status
+            FROM invocations
+            WHERE session_id = ?
+            ORDER BY timestamp
+            "#,
+        ) {
+            Ok(stmt) => stmt,
+            Err(e) => {
+                if e.to_string().contains("No files found") {
+                    return Ok(Vec::new());
+                }
+                return Err(e.into());
+            }
+        };
+
+        let rows = stmt.query_map(params![session_id], |row| {
+            Ok(SessionEntry {
+                id: row.get(0)?,
+                session_id: row.get(1)?,
+                timestamp: from_epoch_ms(row.get(2)?),
+                cmd: row.get(3)?,
+                cwd: row.get(4)?,
+                exit_code: row.get(5)?,
+                duration_ms: row.get(6)?,
+                status: row.get(7)?,
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+
+    /// Find the most recently registered session for an invoker PID.
+    ///
+    /// Used to resolve "the current session" when the shell hook's session
+    /// variable isn't exported.
+    pub fn session_for_pid(&self, pid: u32) -> Result<Option<String>> {
+        let conn = self.connection()?;
+
+        let result: std::result::Result<String, _> = conn.query_row(
+            r#"
+            SELECT session_id FROM sessions
+            WHERE invoker_pid = ?
+            ORDER BY registered_at DESC
+            LIMIT 1
+            "#,
+            params![pid],
+            |row| row.get(0),
+        );
+
+        match result {
+            Ok(id) => Ok(Some(id)),
+            Err(duckdb::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => {
+                if e.to_string().contains("No files found") {
+                    Ok(None)
+                } else {
+                    Err(e.into())
+                }
+            }
+        }
+    }
+
+    /// Record a lifecycle event for a session.
+    fn write_session_event(&self, session_id: &str, event_type: &str, value: Option<&str>) -> Result<()> {
+        let conn = self.connection_with_options(false)?;
+
+        conn.execute(
+            "INSERT INTO main.session_events VALUES (?, ?, ?, ?)",
+            params![session_id, event_type, value, Utc::now().to_rfc3339()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Give a session a human-readable name.
+    ///
+    /// The latest name wins; names need not be unique (the same name can
+    /// label several sessions, e.g. a work block spread over terminals).
+    pub fn name_session(&self, session_id: &str, name: &str) -> Result<()> {
+        self.write_session_event(session_id, SESSION_NAMED, Some(name))
+    }
+
+    /// Record that a session ended (shell exited).
+    pub fn close_session(&self, session_id: &str) -> Result<()> {
+        self.write_session_event(session_id, SESSION_CLOSED, None)
+    }
+
+    /// Get the current name of a session, if it has one.
+    pub fn session_name(&self, session_id: &str) -> Result<Option<String>> {
+        let conn = self.connection()?;
+
+        let result: std::result::Result<Option<String>, _> = conn.query_row(
+            "SELECT name FROM main.session_names WHERE session_id = ?",
+            params![session_id],
+            |row| row.get(0),
+        );
+
+        match result {
+            Ok(name) => Ok(name),
+            Err(duckdb::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Find sessions currently carrying `name`, most recently named first.
+    pub fn sessions_named(&self, name: &str) -> Result<Vec<String>> {
+        let conn = self.connection()?;
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT n.session_id
+            FROM main.session_names n
+            JOIN main.session_events e
+              ON e.session_id = n.session_id AND e.event_type = 'session_named'
+            WHERE n.name = ?
+            GROUP BY n.session_id
+            ORDER BY MAX(e.timestamp) DESC
+            "#,
+        )?;
+
+        let rows = stmt.query_map(params![name], |row| row.get(0))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+
+    /// When a session was closed, if a session_closed event was recorded.
+    pub fn session_closed_at(&self, session_id: &str) -> Result<Option<DateTime<Utc>>> {
+        let conn = self.connection()?;
+
+        let result: std::result::Result<Option<i64>, _> = conn.query_row(
+            "SELECT epoch_ms(MAX(timestamp)) FROM main.session_events WHERE session_id = ? AND event_type = 'session_closed'",
+            params![session_id],
+            |row| row.get(0),
+        );
+
+        match result {
+            Ok(ms) => Ok(ms.map(from_epoch_ms)),
+            Err(duckdb::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Terminal context of a session's latest invocation that recorded one
+    /// (see [`Store::terminal_context`]): where the session's shell runs.
+    pub fn session_terminal(&self, session_id: &str) -> Result<Option<serde_json::Value>> {
+        let conn = self.connection()?;
+
+        let result: std::result::Result<Option<String>, _> = conn.query_row(
+            r#"
+            SELECT arg_max(metadata['terminal']::VARCHAR, timestamp)
+            FROM invocations
+            WHERE session_id = ? AND metadata['terminal'] IS NOT NULL
+            "#,
+            params![session_id],
+            |row| row.get(0),
+        );
+
+        let json = match result {
+            Ok(json) => json,
+            Err(e) if e.to_string().contains("No files found") => None,
+            Err(e) => return Err(e.into()),
+        };
+        json.map(|json| {
+            serde_json::from_str(&json).map_err(|e| crate::Error::Storage(format!("Invalid terminal context: {}", e)))
+        })
+        .transpose()
+    }
+
     /// Count total sessions in the store.
     pub fn session_count(&self) -> Result<i64> {
         let conn = self.connection()?;
@@ -269,4 +588,57 @@ mod tests {
         let count = store.session_count().unwrap();
         assert_eq!(count, 0);
     }
+
+    #[test]
+    fn test_list_sessions_and_timeline() {
+        use crate::schema::InvocationRecord;
+
+        let (_tmp, store) = setup_store();
+
+        store
+            .write_session(&SessionRecord::new("zsh-1", "user@laptop", "zsh", 1, "shell"))
+            .unwrap();
+        for (cmd, exit) in [("make", 0), ("make test", 2)] {
+            let record = InvocationRecord::new("zsh-1", cmd, "/home/user", exit, "user@laptop")
+                .with_duration(10);
+            store.write_invocation(&record).unwrap();
+        }
+        let other = InvocationRecord::new("bash-2", "ls", "/tmp", 0, "user@laptop");
+        store.write_invocation(&other).unwrap();
+
+        let sessions = store.list_sessions(10).unwrap();
+        assert_eq!(sessions.len(), 2);
+        let zsh = sessions.iter().find(|s| s.session_id == "zsh-1").unwrap();
+        assert_eq!(zsh.invocation_count, 2);
+        assert_eq!(zsh.failed_count, 1);
+        assert_eq!(zsh.invoker.as_deref(), Some("zsh"));
+
+        let timeline = store.session_invocations("zsh-1").unwrap();
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(timeline[0].cmd, "make");
+        assert_eq!(timeline[1].exit_code, Some(2));
+
+        assert_eq!(store.session_for_pid(1).unwrap().as_deref(), Some("zsh-1"));
+        assert_eq!(store.session_for_pid(999).unwrap(), None);
+    }
+
+    #[test]
+    fn test_session_naming_and_close() {
+        let (_tmp, store) = setup_store();
+
+        assert_eq!(store.session_name("zsh-1").unwrap(), None);
+
+        store.name_session("zsh-1", "first").unwrap();
+        store.name_session("zsh-1", "refactor-auth").unwrap();
+        store.name_session("bash-2", "refactor-auth").unwrap();
+
+        assert_eq!(store.session_name("zsh-1").unwrap().as_deref(), Some("refactor-auth"));
+        let named = store.sessions_named("refactor-auth").unwrap();
+        assert_eq!(named.len(), 2);
+        assert!(store.sessions_named("first").unwrap().is_empty());
+
+        assert!(store.session_closed_at("zsh-1").unwrap().is_none());
+        store.close_session("zsh-1").unwrap();
+        assert!(store.session_closed_at("zsh-1").unwrap().is_some());
+    }
 }
@@ -0,0 +1,233 @@
+//! Short-id lookup - resolve `shq o 3f2a9c1e` without scanning history.
+//!
+//! Invocations are shown by the last [`SHORT_ID_LEN`] characters of their
+//! UUIDv7 (the random part). `main.short_ids` maps those characters to the
+//! full id, with an index on them, so resolving a short id is an index
+//! probe rather than a `suffix()` scan over every attempt. Ids shorter than
+//! [`SHORT_ID_LEN`] still scan, but only this two-column table.
+//!
+//! Maintained like the recent index: attempts are added as they are
+//! written once the table has been built, the first lookup builds it, and
+//! operations that change history behind it (pull, archive, trash restore)
+//! invalidate it.
+
+use duckdb::{params, Connection};
+
+use super::Store;
+use crate::schema::AttemptRecord;
+use crate::{Error, Result};
+
+/// Characters of an invocation id shown as its short id.
+pub const SHORT_ID_LEN: usize = 8;
+
+/// Candidates listed in an [`Error::AmbiguousId`].
+const AMBIGUOUS_SHOWN: usize = 5;
+
+/// Tables backing the short-id lookup (in the main DuckDB database, in both
+/// storage modes).
+pub(crate) const SHORT_IDS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS main.short_ids (
+    short VARCHAR NOT NULL,
+    id    UUID PRIMARY KEY
+);
+CREATE INDEX IF NOT EXISTS short_ids_short ON main.short_ids (short);
+CREATE TABLE IF NOT EXISTS main.short_ids_state (
+    built_at TIMESTAMP
+);
+"#;
+
+impl Store {
+    /// Resolve a full or short invocation id (any trailing part of at least
+    /// a few characters) to the full id.
+    ///
+    /// Returns `None` if nothing matches, and [`Error::AmbiguousId`] listing
+    /// the candidates if several invocations do.
+    pub fn find_by_short_id(&self, id: &str) -> Result<Option<String>> {
+        let id = id.trim().to_ascii_lowercase();
+        let conn = self.connection()?;
+        ensure_built(&conn)?;
+
+        let mut found = lookup(&conn, &id)?;
+        match found.len() {
+            0 => Ok(None),
+            1 => Ok(found.pop()),
+            count => Err(Error::AmbiguousId {
+                id,
+                count,
+                candidates: describe(&conn, &found[..count.min(AMBIGUOUS_SHOWN)]),
+            }),
+        }
+    }
+
+    /// Rebuild the short-id lookup from `main.attempts`.
+    pub fn rebuild_short_ids(&self) -> Result<usize> {
+        let conn = self.connection()?;
+        rebuild(&conn)
+    }
+
+    /// Mark the short-id lookup stale; the next lookup rebuilds it.
+    pub fn invalidate_short_ids(&self) -> Result<()> {
+        let conn = self.connection_with_options(false)?;
+        conn.execute("DELETE FROM main.short_ids_state", [])?;
+        Ok(())
+    }
+}
+
+/// Add a new attempt (no-op while the table is unbuilt).
+pub(crate) fn index_attempt(conn: &Connection, record: &AttemptRecord) -> Result<()> {
+    let id = record.id.to_string();
+    conn.execute(
+        r#"
+        INSERT OR IGNORE INTO main.short_ids
+        SELECT right(?, 8), ?::UUID
+        WHERE EXISTS (SELECT 1 FROM main.short_ids_state)
+        "#,
+        params![id, id],
+    )?;
+    Ok(())
+}
+
+/// Full ids ending in `id`, newest first.
+fn lookup(conn: &Connection, id: &str) -> Result<Vec<String>> {
+    let short = id.get(id.len().saturating_sub(SHORT_ID_LEN)..).unwrap_or(id);
+    // A full short id probes the index; a shorter one scans the table
+    let sql = if short.len() == SHORT_ID_LEN {
+        "SELECT id::VARCHAR FROM main.short_ids WHERE short = ? AND suffix(id::VARCHAR, ?) ORDER BY id DESC"
+    } else {
+        "SELECT id::VARCHAR FROM main.short_ids WHERE suffix(short, ?) AND suffix(id::VARCHAR, ?) ORDER BY id DESC"
+    };
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map(params![short, id], |row| row.get(0))?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
+/// `ids` with their time and command, for an ambiguity error. Falls back to
+/// the bare ids if the invocations can't be read.
+fn describe(conn: &Connection, ids: &[String]) -> Vec<String> {
+    let list = ids.iter().map(|id| format!("'{}'", id)).collect::<Vec<_>>().join(", ");
+    let sql = format!(
+        r#"
+        SELECT id::VARCHAR, strftime(timezone('UTC', timestamp), '%Y-%m-%d %H:%M'), cmd
+        FROM main.invocations WHERE id IN ({})
+        ORDER BY timestamp DESC
+        "#,
+        list
+    );
+    let described = conn.prepare(&sql).and_then(|mut stmt| {
+        stmt.query_map([], |row| {
+            Ok(format!("{}  {}  {}", row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()
+    });
+    match described {
+        Ok(lines) if !lines.is_empty() => lines,
+        _ => ids.to_vec(),
+    }
+}
+
+fn ensure_built(conn: &Connection) -> Result<()> {
+    let built: i64 = conn.query_row("SELECT COUNT(*) FROM main.short_ids_state", [], |row| row.get(0))?;
+    if built == 0 {
+        rebuild(conn)?;
+    }
+    Ok(())
+}
+
+/// Refill the table from every attempt. Returns the number of ids.
+fn rebuild(conn: &Connection) -> Result<usize> {
+    conn.execute("DELETE FROM main.short_ids", [])?;
+    conn.execute("DELETE FROM main.short_ids_state", [])?;
+
+    let count = match conn.execute(
+        "INSERT INTO main.short_ids SELECT right(id::VARCHAR, 8), id FROM main.attempts GROUP BY id",
+        [],
+    ) {
+        Ok(count) => count,
+        Err(e) if e.to_string().contains("No files found") => 0,
+        Err(e) => return Err(e.into()),
+    };
+    conn.execute("INSERT INTO main.short_ids_state VALUES (now())", [])?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+    use uuid::Uuid;
+
+    /// The table, built and holding `ids`, in an in-memory database.
+    fn built_connection(ids: &[&str]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SHORT_IDS_DDL).unwrap();
+        conn.execute("INSERT INTO main.short_ids_state VALUES (now())", []).unwrap();
+        for id in ids {
+            let mut record = AttemptRecord::new("s1", "true", "/tmp", "test@client");
+            record.id = Uuid::parse_str(id).unwrap();
+            index_attempt(&conn, &record).unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn test_lookup_short_and_full_ids() {
+        let a = "0190a1b2-0000-7000-8000-00003f2a9c1e";
+        let b = "0190a1b2-0000-7000-8000-00117a2a9c1e";
+        let conn = built_connection(&[a, b]);
+
+        assert_eq!(lookup(&conn, "3f2a9c1e").unwrap(), [a]);
+        assert_eq!(lookup(&conn, a).unwrap(), [a]);
+        assert_eq!(lookup(&conn, "17a2a9c1e").unwrap(), [b]);
+        // Shorter than a short id: both end in 2a9c1e
+        assert_eq!(lookup(&conn, "2a9c1e").unwrap(), [b, a]);
+        assert!(lookup(&conn, "deadbeef").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unbuilt_table_skips_writes() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SHORT_IDS_DDL).unwrap();
+        index_attempt(&conn, &AttemptRecord::new("s1", "true", "/tmp", "test@client")).unwrap();
+
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM main.short_ids", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_find_by_short_id() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        let record = InvocationRecord::new("s1", "make", "/p", 0, "test@client");
+        let id = record.id.to_string();
+        store.write_invocation(&record).unwrap();
+
+        let short = &id[id.len() - SHORT_ID_LEN..];
+        assert_eq!(store.find_by_short_id(short).unwrap(), Some(id.clone()));
+        assert_eq!(store.find_by_short_id(&id.to_uppercase()).unwrap(), Some(id.clone()));
+
+        // Written after the first lookup built the table, sharing the short id
+        let mut twin = InvocationRecord::new("s1", "ls", "/p", 0, "test@client");
+        twin.id = Uuid::parse_str(&format!("00000000-0000-7000-8000-0000{}", short)).unwrap();
+        store.write_invocation(&twin).unwrap();
+        assert_eq!(store.find_by_short_id(&twin.id.to_string()).unwrap(), Some(twin.id.to_string()));
+
+        match store.find_by_short_id(short) {
+            Err(Error::AmbiguousId { count, candidates, .. }) => {
+                assert_eq!(count, 2);
+                assert!(candidates.iter().any(|c| c.ends_with("make")), "{:?}", candidates);
+            }
+            other => panic!("expected an ambiguous id, got {:?}", other),
+        }
+
+        // The twins share a word pair, but not an alias
+        let alias = crate::alias::invocation_alias(&record.id);
+        assert_eq!(store.find_by_alias(&alias).unwrap(), Some(id.clone()));
+        assert_eq!(store.find_by_alias(&format!("{}-2", alias)).unwrap(), Some(twin.id.to_string()));
+    }
+}
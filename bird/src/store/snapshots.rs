@@ -0,0 +1,218 @@
+//! Daily snapshots of the store for time-travel queries.
+//!
+//! A snapshot is a read-only BIRD root at `$BIRD_ROOT/snapshots/<date>/`:
+//! every data file hard-linked (parquet and blob files are immutable, so
+//! this costs no space until compaction or pruning removes the originals),
+//! a checkpointed copy of the database and a `manifest.json` listing the
+//! files. [`Store::as_of`] opens the newest snapshot taken on or before a
+//! date, so queries see the store as it was then.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::Store;
+use crate::{Error, Result};
+
+const MANIFEST: &str = "manifest.json";
+
+/// One snapshot of the store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    /// Day the snapshot was taken (local time).
+    pub date: NaiveDate,
+    pub created_at: DateTime<Utc>,
+    /// Data files, relative to the data directory.
+    pub files: Vec<PathBuf>,
+    /// Total size of the data files and database.
+    pub bytes: u64,
+}
+
+impl Store {
+    /// Take today's snapshot, replacing an existing one for today.
+    pub fn snapshot(&self) -> Result<SnapshotInfo> {
+        let date = chrono::Local::now().date_naive();
+        let snapshots_dir = self.config.snapshots_dir();
+        fs::create_dir_all(&snapshots_dir)?;
+        crate::perms::set_mode(&snapshots_dir, crate::perms::DIR_MODE)?;
+
+        // Build next to the final location and rename, so a concurrent
+        // snapshot or query never sees a half-written one
+        let tmp = snapshots_dir.join(format!(".tmp-{}", Uuid::now_v7()));
+        let result = self.write_snapshot(&tmp, date);
+        let info = match result {
+            Ok(info) => info,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&tmp);
+                return Err(e);
+            }
+        };
+
+        let dest = snapshots_dir.join(date.to_string());
+        remove_snapshot(&dest)?;
+        fs::rename(&tmp, &dest)?;
+        Ok(info)
+    }
+
+    /// Take today's snapshot if `snapshots.enabled` and there isn't one yet,
+    /// then drop snapshots older than `snapshots.keep_days`.
+    pub fn snapshot_if_due(&self) -> Result<Option<SnapshotInfo>> {
+        if !self.config.snapshots.enabled {
+            return Ok(None);
+        }
+        let today = chrono::Local::now().date_naive();
+        let taken = if self.config.snapshots_dir().join(today.to_string()).exists() {
+            None
+        } else {
+            Some(self.snapshot()?)
+        };
+        self.prune_snapshots()?;
+        Ok(taken)
+    }
+
+    /// Snapshots, oldest first.
+    pub fn snapshots(&self) -> Result<Vec<SnapshotInfo>> {
+        let snapshots_dir = self.config.snapshots_dir();
+        if !snapshots_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut snapshots = Vec::new();
+        for dir in fs::read_dir(&snapshots_dir)?.flatten() {
+            let name = dir.file_name();
+            if NaiveDate::parse_from_str(&name.to_string_lossy(), "%Y-%m-%d").is_err() {
+                continue;
+            }
+            if let Ok(contents) = fs::read(dir.path().join(MANIFEST)) {
+                snapshots.push(serde_json::from_slice::<SnapshotInfo>(&contents)?);
+            }
+        }
+        snapshots.sort_by_key(|s| s.date);
+        Ok(snapshots)
+    }
+
+    /// Delete snapshots older than `snapshots.keep_days`. Returns how many.
+    pub fn prune_snapshots(&self) -> Result<usize> {
+        let cutoff = chrono::Local::now().date_naive() - chrono::Duration::days(self.config.snapshots.keep_days as i64);
+        let mut pruned = 0;
+        for snapshot in self.snapshots()? {
+            if snapshot.date < cutoff {
+                remove_snapshot(&self.config.snapshots_dir().join(snapshot.date.to_string()))?;
+                pruned += 1;
+            }
+        }
+        Ok(pruned)
+    }
+
+    /// Open the store as it was on `date`, from the newest snapshot taken on
+    /// or before it. The returned store is detached from remotes.
+    pub fn as_of(&self, date: NaiveDate) -> Result<Store> {
+        let snapshot = self
+            .snapshots()?
+            .into_iter()
+            .rev()
+            .find(|s| s.date <= date)
+            .ok_or_else(|| Error::NotFound(format!("snapshot on or before {}", date)))?;
+
+        let mut config = self.config.clone();
+        config.bird_root = self.config.snapshots_dir().join(snapshot.date.to_string());
+        config.remotes.clear();
+        Ok(Store::open(config)?
+            .with_progress(self.progress.clone())
+            .with_cancel(self.cancel.clone()))
+    }
+
+    fn write_snapshot(&self, root: &Path, date: NaiveDate) -> Result<SnapshotInfo> {
+        let data_dir = self.config.data_dir();
+        let snapshot_data = root.join("db").join("data");
+        fs::create_dir_all(&snapshot_data)?;
+        crate::perms::set_mode(root, crate::perms::DIR_MODE)?;
+
+        let mut info = SnapshotInfo {
+            date,
+            created_at: Utc::now(),
+            files: Vec::new(),
+            bytes: 0,
+        };
+        if data_dir.exists() {
+            link_tree(&data_dir, &data_dir, &snapshot_data, &mut info)?;
+        }
+
+        // Flush the WAL so the copied file is self-contained
+        let conn = self.connection_with_options(false)?;
+        conn.execute_batch("CHECKPOINT")?;
+        drop(conn);
+        let db_copy = root.join("db").join("bird.duckdb");
+        info.bytes += fs::copy(self.config.db_path(), &db_copy)?;
+        crate::perms::set_mode(&db_copy, crate::perms::FILE_MODE)?;
+
+        fs::write(root.join(MANIFEST), serde_json::to_vec_pretty(&info)?)?;
+        Ok(info)
+    }
+}
+
+/// Hard-link (or copy, across filesystems) the regular files under `dir`
+/// into `dest`, skipping hidden and temporary files.
+fn link_tree(base: &Path, dir: &Path, dest: &Path, info: &mut SnapshotInfo) -> Result<()> {
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        let target = dest.join(entry.file_name());
+        if file_type.is_dir() {
+            fs::create_dir_all(&target)?;
+            link_tree(base, &path, &target, info)?;
+        } else if file_type.is_file() {
+            if fs::hard_link(&path, &target).is_err() {
+                fs::copy(&path, &target)?;
+            }
+            info.bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
+            if let Ok(relative) = path.strip_prefix(base) {
+                info.files.push(relative.to_path_buf());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn remove_snapshot(dir: &Path) -> Result<()> {
+    match fs::remove_dir_all(dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_as_of_sees_pruned_data() {
+        let tmp = TempDir::new().unwrap();
+        let mut config = Config::with_root(tmp.path());
+        config.snapshots.enabled = true;
+        initialize(&config).unwrap();
+        let store = Store::open(config.clone()).unwrap();
+
+        let file = config.data_dir().join("recent/attempts/date=2024-06-01/a.parquet");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, b"PAR1").unwrap();
+
+        let info = store.snapshot_if_due().unwrap().expect("snapshot taken");
+        assert!(info.files.contains(&PathBuf::from("recent/attempts/date=2024-06-01/a.parquet")));
+        assert!(store.snapshot_if_due().unwrap().is_none());
+
+        fs::remove_file(&file).unwrap();
+        let past = store.as_of(info.date).unwrap();
+        assert!(past.config().data_dir().join("recent/attempts/date=2024-06-01/a.parquet").exists());
+        assert!(store.as_of(info.date.pred_opt().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+//! Blob tiering to cold storage.
+//!
+//! [`Store::tier_blobs`] moves blobs that haven't been accessed for
+//! `blobs.tier_after_days` to `blobs.cold_tier`, keeping their path below
+//! the blobs directory, and points `blob_registry.storage_path` at the cold
+//! copy. Output rows keep their `file://` storage_ref: the cold tier is a
+//! blob root, so refs resolve to it once the local file is gone, and
+//! [`Store::read_output_content`] falls back to it the same way.
+//!
+//! A directory or `file://` tier (which may be a mounted bucket) is written
+//! with file copies; an `s3://` tier is uploaded to (see [`super::s3`]).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use duckdb::{params, Connection};
+
+use super::s3::{self, S3Credentials};
+use super::{atomic, Store};
+use crate::{Error, Result};
+
+/// Results of a tiering run.
+#[derive(Debug, Default)]
+pub struct TierStats {
+    /// Blobs moved (or that would be moved) to the cold tier.
+    pub blobs: usize,
+    /// Their total size.
+    pub bytes: u64,
+    /// Local blob files removed, including other names for the same content.
+    pub files: usize,
+}
+
+/// Whether a registry storage_path points at cold storage rather than a
+/// path relative to the data directory.
+pub(crate) fn is_tiered(storage_path: &str) -> bool {
+    storage_path.contains("://")
+}
+
+impl Store {
+    /// Move blobs not accessed for `days` days to the cold tier.
+    ///
+    /// Directories, `file://` URLs and `s3://` URLs (with credentials in the
+    /// AWS environment variables) can be written; other object stores are
+    /// read-through only.
+    pub fn tier_blobs(&self, days: u32, dry_run: bool) -> Result<TierStats> {
+        let _sync = self.sync_guard();
+        let blobs = &self.config.blobs;
+        let cold_url = blobs
+            .cold_tier_url()
+            .ok_or_else(|| Error::Config("no cold tier configured (set blobs.cold_tier)".to_string()))?;
+        let tier = match blobs.cold_tier_dir() {
+            Some(dir) => ColdTier::Dir(dir),
+            None if cold_url.starts_with("s3://") => ColdTier::S3(S3Credentials::from_env().ok_or_else(|| {
+                Error::Config(format!(
+                    "cannot write to cold tier {}: set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY",
+                    cold_url
+                ))
+            })?),
+            None => {
+                return Err(Error::Config(format!(
+                    "cannot write to cold tier {}: only directories, file:// and s3:// URLs can be written",
+                    cold_url
+                )))
+            }
+        };
+
+        let conn = self.connection()?;
+        let candidates = tiering_candidates(&conn, days)?;
+        let blobs_dir = self.config.blobs_dir();
+        let data_dir = self.config.data_dir();
+        let mut stats = TierStats::default();
+
+        let progress = self.progress_phase("tier blobs", Some(candidates.len() as u64));
+        for (hash, storage_path, bytes) in candidates {
+            self.checkpoint()?;
+            progress.advance(1);
+
+            let src = data_dir.join(&storage_path);
+            let Ok(relative) = src.strip_prefix(&blobs_dir) else {
+                continue;
+            };
+            if !src.exists() {
+                continue;
+            }
+            // Other names for the same content (e.g. pulled from other machines)
+            let names = content_names(&src, &hash)?;
+            stats.blobs += 1;
+            stats.bytes += bytes as u64;
+            stats.files += names.len();
+            if dry_run {
+                continue;
+            }
+
+            for name in &names {
+                let name_relative = name.strip_prefix(&blobs_dir).unwrap_or(relative);
+                match &tier {
+                    ColdTier::Dir(cold_dir) => {
+                        let dest = cold_dir.join(name_relative);
+                        if let Some(parent) = dest.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        let temp = atomic::temp_path(&dest);
+                        fs::copy(name, &temp)?;
+                        atomic::rename_into_place(&temp, &dest)?;
+                    }
+                    ColdTier::S3(credentials) => {
+                        let url = format!("{}/{}", cold_url, name_relative.to_string_lossy());
+                        s3::put_object(credentials, &url, &fs::read(name)?)?;
+                    }
+                }
+            }
+            conn.execute(
+                "UPDATE blob_registry SET storage_path = ? WHERE content_hash = ?",
+                params![format!("{}/{}", cold_url, relative.to_string_lossy()), hash],
+            )?;
+            for name in &names {
+                fs::remove_file(name)?;
+            }
+        }
+        drop(progress);
+
+        Ok(stats)
+    }
+
+    /// The cold tier URL for the local blob path `abs_path`, if a cold tier
+    /// is configured and the path is under the blobs directory.
+    pub(crate) fn cold_blob_url(&self, abs_path: &Path) -> Option<String> {
+        let cold_url = self.config.blobs.cold_tier_url()?;
+        let relative = abs_path.strip_prefix(self.config.blobs_dir()).ok()?;
+        Some(format!("{}/{}", cold_url, relative.to_string_lossy()))
+    }
+
+    /// Keep a local copy of a blob read from the cold tier.
+    pub(crate) fn cache_cold_blob(
+        &self,
+        conn: &Connection,
+        hash: &str,
+        rel_path: &str,
+        abs_path: &Path,
+        content: &[u8],
+    ) -> Result<()> {
+        let _sync = self.sync_guard();
+        if let Some(parent) = abs_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        atomic::write_file(abs_path, content)?;
+        conn.execute(
+            "UPDATE blob_registry SET storage_path = ?, last_accessed = CURRENT_TIMESTAMP \
+             WHERE content_hash = ? AND storage_path LIKE '%://%'",
+            params![rel_path, hash],
+        )?;
+        Ok(())
+    }
+}
+
+/// Where [`Store::tier_blobs`] writes.
+enum ColdTier {
+    Dir(PathBuf),
+    S3(S3Credentials),
+}
+
+/// Local blobs not accessed for `days` days: `(hash, storage_path, bytes)`.
+fn tiering_candidates(conn: &Connection, days: u32) -> Result<Vec<(String, String, i64)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT content_hash, storage_path, byte_length FROM blob_registry \
+         WHERE last_accessed < CURRENT_TIMESTAMP - INTERVAL {} DAY \
+           AND storage_path NOT LIKE '%://%' \
+         ORDER BY last_accessed",
+        days
+    ))?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+
+    let mut candidates = Vec::new();
+    for row in rows {
+        candidates.push(row?);
+    }
+    Ok(candidates)
+}
+
+/// Every file next to `blob` named for content `hash` (`<hash>--<cmd>.bin`).
+pub(super) fn content_names(blob: &Path, hash: &str) -> Result<Vec<PathBuf>> {
+    let Some(dir) = blob.parent() else {
+        return Ok(vec![blob.to_path_buf()]);
+    };
+    let mut names: Vec<PathBuf> = fs::read_dir(dir)?
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(hash))
+        .map(|entry| entry.path())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_tiered_blob_reads_through() {
+        let tmp = TempDir::new().unwrap();
+        let mut config = Config::with_root(tmp.path().join("bird"));
+        config.blobs.cold_tier = Some(tmp.path().join("cold").to_string_lossy().to_string());
+        config.blobs.cache_reads = true;
+        initialize(&config).unwrap();
+        let store = Store::open(config.clone()).unwrap();
+
+        let inv = InvocationRecord::new("test-session", "cat big.log", "/home/user", 0, "user@laptop");
+        store.write_invocation(&inv).unwrap();
+        let content: Vec<u8> = (0..5000).map(|i| (i % 256) as u8).collect();
+        store.store_output(inv.id, "stdout", &content, inv.date(), Some("cat")).unwrap();
+
+        let stats = store.tier_blobs(0, false).unwrap();
+        assert_eq!((stats.blobs, stats.files), (1, 1));
+        let output = store.get_output(&inv.id.to_string()).unwrap().unwrap();
+        let local = config.data_dir().join(output.storage_ref.strip_prefix("file://").unwrap());
+        assert!(!local.exists());
+
+        // Read through from the cold tier, then cached locally
+        assert_eq!(store.read_output_content(&output).unwrap(), content);
+        assert!(local.exists());
+    }
+}
@@ -0,0 +1,160 @@
+//! Trash for data removed by destructive operations.
+//!
+//! Instead of deleting files, destructive operations (currently archive
+//! pruning) move them to `$BIRD_ROOT/trash/<id>/`, keeping their path
+//! relative to the data directory, next to a `manifest.json`. `shq undo`
+//! moves the files of the newest entry back. Entries older than
+//! `trash.grace_days` are deleted for good the next time anything is trashed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::Store;
+use crate::{Error, Result};
+
+const MANIFEST: &str = "manifest.json";
+
+/// One destructive operation's removed files, restorable with `undo`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// Entry id (UUIDv7, so ids sort by creation time).
+    pub id: String,
+    /// What removed the files, e.g. `prune archive older than 90 days`.
+    pub operation: String,
+    pub created_at: DateTime<Utc>,
+    /// Paths relative to the data directory.
+    pub files: Vec<PathBuf>,
+    /// Total size of the files.
+    pub bytes: u64,
+}
+
+impl Store {
+    /// Remove `files` (paths under the data directory) on behalf of `operation`.
+    ///
+    /// Files are moved to a new trash entry, or deleted outright when
+    /// `trash.grace_days` is 0. Expired entries are purged first.
+    pub(crate) fn trash_files(&self, operation: &str, files: &[PathBuf]) -> Result<Option<TrashEntry>> {
+        let _sync = self.sync_guard();
+        if files.is_empty() {
+            return Ok(None);
+        }
+        if self.config.trash.grace_days == 0 {
+            for file in files {
+                let _ = fs::remove_file(file);
+            }
+            return Ok(None);
+        }
+        self.purge_trash()?;
+
+        let data_dir = self.config.data_dir();
+        let id = Uuid::now_v7().to_string();
+        let entry_dir = self.config.trash_dir().join(&id);
+        fs::create_dir_all(&entry_dir)?;
+        crate::perms::set_mode(&self.config.trash_dir(), crate::perms::DIR_MODE)?;
+        crate::perms::set_mode(&entry_dir, crate::perms::DIR_MODE)?;
+        let mut entry = TrashEntry {
+            id,
+            operation: operation.to_string(),
+            created_at: Utc::now(),
+            files: Vec::new(),
+            bytes: 0,
+        };
+
+        for file in files {
+            let relative = file.strip_prefix(&data_dir).map_err(|_| {
+                Error::InvalidPath(format!("{} is not in the data directory", file.display()))
+            })?;
+            let dest = entry_dir.join(relative);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            entry.bytes += fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+            fs::rename(file, &dest)?;
+            entry.files.push(relative.to_path_buf());
+        }
+
+        fs::write(entry_dir.join(MANIFEST), serde_json::to_vec_pretty(&entry)?)?;
+        Ok(Some(entry))
+    }
+
+    /// Trash entries, newest first.
+    pub fn trash_entries(&self) -> Result<Vec<TrashEntry>> {
+        let trash_dir = self.config.trash_dir();
+        if !trash_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+        for dir in fs::read_dir(&trash_dir)?.flatten() {
+            let manifest = dir.path().join(MANIFEST);
+            if let Ok(contents) = fs::read(&manifest) {
+                entries.push(serde_json::from_slice::<TrashEntry>(&contents)?);
+            }
+        }
+        entries.sort_by(|a, b| b.id.cmp(&a.id));
+        Ok(entries)
+    }
+
+    /// Restore the files of the newest trash entry. Returns the entry, if any.
+    ///
+    /// Fails without moving anything if a file has been recreated since.
+    pub fn undo_last(&self) -> Result<Option<TrashEntry>> {
+        let _sync = self.sync_guard();
+        let Some(entry) = self.trash_entries()?.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let data_dir = self.config.data_dir();
+        let entry_dir = self.config.trash_dir().join(&entry.id);
+        if let Some(existing) = entry.files.iter().find(|f| data_dir.join(f).exists()) {
+            return Err(Error::Storage(format!(
+                "cannot undo '{}': {} exists again",
+                entry.operation,
+                existing.display()
+            )));
+        }
+
+        for file in &entry.files {
+            let dest = data_dir.join(file);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+                crate::perms::set_mode(parent, crate::perms::DIR_MODE)?;
+            }
+            fs::rename(entry_dir.join(file), &dest)?;
+        }
+        fs::remove_dir_all(&entry_dir)?;
+
+        // Restored recent rows aren't in the recent index, running stats or
+        // short-id lookup yet
+        if entry.files.iter().any(|f| f.starts_with("recent")) {
+            self.invalidate_recent_index()?;
+            self.invalidate_running_stats()?;
+            self.invalidate_short_ids()?;
+        }
+        Ok(Some(entry))
+    }
+
+    /// Delete trash entries older than `trash.grace_days`. Returns how many.
+    pub fn purge_trash(&self) -> Result<usize> {
+        let cutoff = Utc::now() - chrono::Duration::days(self.config.trash.grace_days as i64);
+        let mut purged = 0;
+        for entry in self.trash_entries()? {
+            if entry.created_at < cutoff {
+                remove_entry(&self.config.trash_dir().join(&entry.id))?;
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+}
+
+fn remove_entry(dir: &Path) -> Result<()> {
+    match fs::remove_dir_all(dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,148 @@
+//! Command-not-found events and typo statistics (`shq typos`).
+//!
+//! With `SHQ_CAPTURE_NOT_FOUND=1`, the shell hook's command-not-found
+//! handler records each missing command as an event with
+//! `event_type = 'command_not_found'` and the command name as its message.
+//! The event isn't tied to an invocation (the handler runs before the
+//! command is saved), so it hangs off the nil invocation id; its time comes
+//! from the UUIDv7 event id.
+//!
+//! [`Store::typos`] counts them and suggests the command probably meant:
+//! the closest by edit distance among commands that have succeeded before,
+//! or failing that, among the names on `PATH`.
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use duckdb::params;
+use uuid::Uuid;
+
+use super::markers::uuid_time;
+use super::Store;
+use crate::schema::{EventRecord, EVENT_ORIGIN_MANUAL};
+use crate::suggest::suggest;
+use crate::{Error, Result};
+
+/// `event_type` of command-not-found events.
+pub const EVENT_TYPE_COMMAND_NOT_FOUND: &str = "command_not_found";
+
+/// Most-used commands from history considered as suggestions.
+const HISTORY_CANDIDATES: usize = 10_000;
+
+/// A command that wasn't found, with how often.
+#[derive(Debug, Clone)]
+pub struct Typo {
+    pub command: String,
+    pub count: i64,
+    pub last_seen: DateTime<Utc>,
+    /// The command probably meant, if one is close enough.
+    pub suggestion: Option<String>,
+}
+
+impl Store {
+    /// Record that `command` (the name typed, without arguments) wasn't found.
+    pub fn record_command_not_found(&self, command: &str) -> Result<()> {
+        let command = command.trim();
+        if command.is_empty() {
+            return Err(Error::Config("No command given".to_string()));
+        }
+
+        let mut record = EventRecord::new(
+            Uuid::nil(),
+            self.config.client_id.clone(),
+            EVENT_ORIGIN_MANUAL,
+            Utc::now().date_naive(),
+        );
+        record.event_type = Some(EVENT_TYPE_COMMAND_NOT_FOUND.to_string());
+        record.message = Some(command.to_string());
+        record.origin = EVENT_ORIGIN_MANUAL.to_string();
+        self.write_events(std::slice::from_ref(&record))
+    }
+
+    /// The `limit` most frequent commands not found in the last `days` days
+    /// (all time if `None`), most frequent first, with suggestions.
+    pub fn typos(&self, days: Option<u32>, limit: usize) -> Result<Vec<Typo>> {
+        let conn = self.connection()?;
+        let since = days.map(|d| format!("AND date >= current_date - INTERVAL {} DAY", d)).unwrap_or_default();
+        let sql = format!(
+            r#"
+            SELECT message, COUNT(*), max(id)::VARCHAR
+            FROM events
+            WHERE event_type = ? AND message IS NOT NULL {}
+            GROUP BY message
+            ORDER BY COUNT(*) DESC, max(id) DESC
+            LIMIT ?
+            "#,
+            since
+        );
+        let mut stmt = match conn.prepare(&sql) {
+            Ok(stmt) => stmt,
+            Err(e) if e.to_string().contains("No files found") => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let rows = stmt.query_map(params![EVENT_TYPE_COMMAND_NOT_FOUND, limit as i64], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?))
+        })?;
+
+        let mut typos = Vec::new();
+        for row in rows {
+            let (command, count, last_id) = row?;
+            let last_id = Uuid::parse_str(&last_id).map_err(|e| Error::Storage(format!("Invalid event id: {}", e)))?;
+            typos.push(Typo { command, count, last_seen: uuid_time(&last_id), suggestion: None });
+        }
+        if typos.is_empty() {
+            return Ok(typos);
+        }
+
+        // Commands that have worked, most used first, then the rest of PATH
+        let known: Vec<String> = self
+            .executable_stats(HISTORY_CANDIDATES)?
+            .into_iter()
+            .filter(|s| s.runs > s.failed)
+            .map(|s| s.executable)
+            .collect();
+        let on_path = path_commands();
+        for typo in &mut typos {
+            typo.suggestion = suggest(&typo.command, known.iter().map(String::as_str))
+                .or_else(|| suggest(&typo.command, on_path.iter().map(String::as_str)));
+        }
+        Ok(typos)
+    }
+}
+
+/// Names of the files in the `PATH` directories, sorted.
+fn path_commands() -> BTreeSet<String> {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::split_paths(&path)
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_typos() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        store.write_invocation(&InvocationRecord::new("s", "gradle build", "/p", 0, "test@client")).unwrap();
+        for command in ["gradel", "gradel", "xyzzy-not-a-command"] {
+            store.record_command_not_found(command).unwrap();
+        }
+        assert!(store.record_command_not_found(" ").is_err());
+
+        let typos = store.typos(None, 10).unwrap();
+        let summary: Vec<_> = typos.iter().map(|t| (t.command.as_str(), t.count, t.suggestion.as_deref())).collect();
+        assert_eq!(summary, [("gradel", 2, Some("gradle")), ("xyzzy-not-a-command", 1, None)]);
+    }
+}
@@ -0,0 +1,487 @@
+//! Disk usage - where BIRD's bytes live and how fast they grow.
+//!
+//! In parquet mode sizes come straight from the partition files. In DuckDB
+//! mode all tables share `bird.duckdb`, so per-table and per-date sizes are
+//! estimated by splitting the database file size by row count.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use chrono::{NaiveDate, Utc};
+
+use super::Store;
+use crate::config::StorageMode;
+use crate::Result;
+
+/// Tables stored under `recent/` and `archive/` (v5 layout).
+const USAGE_TABLES: &[&str] = &["attempts", "outcomes", "outputs", "sessions", "events"];
+
+/// Days of recent partitions used to project growth.
+const GROWTH_WINDOW_DAYS: i64 = 14;
+
+/// Bytes (and file count) attributed to one table, date, or tier.
+#[derive(Debug, Clone, Default)]
+pub struct UsageEntry {
+    pub name: String,
+    pub bytes: u64,
+    pub files: usize,
+}
+
+/// One content-addressed blob and the command that produced it.
+#[derive(Debug, Clone)]
+pub struct BlobUsage {
+    pub content_hash: String,
+    pub bytes: u64,
+    pub ref_count: i64,
+    /// Most recent command whose output is this blob.
+    pub cmd: Option<String>,
+}
+
+/// Storage usage broken down by table, date partition and tier.
+#[derive(Debug, Default)]
+pub struct StorageBreakdown {
+    /// True when table/partition sizes are row-count estimates (DuckDB mode).
+    pub estimated: bool,
+    /// Size of `bird.duckdb` itself.
+    pub database_bytes: u64,
+    /// Bytes per table, largest first.
+    pub tables: Vec<UsageEntry>,
+    /// Bytes per date partition (all tables), newest first.
+    pub partitions: Vec<UsageEntry>,
+    /// Output storage tiers: inline, blob files, running, buffer.
+    pub tiers: Vec<UsageEntry>,
+    /// Largest blobs, largest first.
+    pub largest_blobs: Vec<BlobUsage>,
+    /// Average bytes added per day over the recent window.
+    pub bytes_per_day: u64,
+    /// Days the growth rate was measured over (0 if there is no data).
+    pub growth_window_days: i64,
+}
+
+/// How much blob storage deduplication saves.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlobDedup {
+    /// Blob file names (one per name a producing command or machine used).
+    pub files: usize,
+    /// Size of all blob file names, as if each were a separate file.
+    pub file_bytes: u64,
+    /// Distinct files on disk (names hard-linked together count once).
+    pub unique_files: usize,
+    /// Bytes actually stored.
+    pub stored_bytes: u64,
+    /// Size of the registered blobs, one copy each.
+    pub registered_bytes: u64,
+    /// Outputs referencing registered blobs.
+    pub references: u64,
+    /// Bytes those outputs would take if each had its own copy.
+    pub referenced_bytes: u64,
+}
+
+impl BlobDedup {
+    /// Bytes saved by hard-linking names for the same content (e.g. pulled
+    /// from several machines).
+    pub fn linked_savings(&self) -> u64 {
+        self.file_bytes.saturating_sub(self.stored_bytes)
+    }
+
+    /// Bytes saved by outputs sharing a registered blob.
+    pub fn shared_savings(&self) -> u64 {
+        self.referenced_bytes.saturating_sub(self.registered_bytes)
+    }
+}
+
+impl StorageBreakdown {
+    /// Total bytes on disk (database, table data and blob tiers).
+    pub fn total_bytes(&self) -> u64 {
+        let tables: u64 = self.tables.iter().map(|t| t.bytes).sum();
+        // Inline output lives inside the table files; don't count it twice
+        let tiers: u64 = self.tiers.iter().filter(|t| t.name != "inline").map(|t| t.bytes).sum();
+        let database = if self.estimated { 0 } else { self.database_bytes };
+        tables + tiers + database
+    }
+
+    /// Projected size after `days` more days at the current growth rate.
+    pub fn projected_bytes(&self, days: i64) -> u64 {
+        self.total_bytes() + self.bytes_per_day * days.max(0) as u64
+    }
+}
+
+impl Store {
+    /// Report what blob deduplication saves, locally and across sources.
+    pub fn blob_dedup(&self) -> Result<BlobDedup> {
+        let mut dedup = BlobDedup::default();
+        let mut inodes = HashSet::new();
+        for dir in [self.config.blobs_dir(), self.config.archive_dir().join("blobs/content")] {
+            scan_blob_files(&dir, &mut dedup, &mut inodes)?;
+        }
+
+        let conn = self.connection()?;
+        let (registered_bytes, references, referenced_bytes) = conn.query_row(
+            "SELECT COALESCE(SUM(byte_length), 0), COALESCE(SUM(ref_count), 0), \
+             COALESCE(SUM(byte_length * ref_count), 0) FROM blob_registry",
+            [],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?)),
+        )?;
+        dedup.registered_bytes = registered_bytes as u64;
+        dedup.references = references as u64;
+        dedup.referenced_bytes = referenced_bytes as u64;
+        Ok(dedup)
+    }
+
+    /// Break down storage usage by table, partition, tier and largest blobs.
+    pub fn storage_breakdown(&self, top_blobs: usize) -> Result<StorageBreakdown> {
+        let mut usage = StorageBreakdown {
+            estimated: self.config.storage_mode == StorageMode::DuckDB,
+            database_bytes: fs::metadata(self.config.db_path()).map(|m| m.len()).unwrap_or(0),
+            ..Default::default()
+        };
+
+        let mut tables: BTreeMap<String, UsageEntry> = BTreeMap::new();
+        let mut partitions: BTreeMap<NaiveDate, UsageEntry> = BTreeMap::new();
+
+        if usage.estimated {
+            self.estimate_table_usage(usage.database_bytes, &mut tables, &mut partitions)?;
+        } else {
+            for dir in [self.config.recent_dir(), self.config.archive_dir()] {
+                for table in USAGE_TABLES {
+                    scan_partitions(&dir.join(table), table, &mut tables, &mut partitions)?;
+                }
+            }
+        }
+
+        usage.tables = tables.into_values().collect();
+        usage.tables.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.name.cmp(&b.name)));
+
+        let conn = self.connection()?;
+
+        let (inline_bytes, inline_count) = conn
+            .query_row(
+                "SELECT COALESCE(SUM(byte_length), 0), COUNT(*) FROM main.outputs WHERE storage_type = 'inline'",
+                [],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+            )
+            .unwrap_or((0, 0));
+        usage.tiers.push(UsageEntry {
+            name: "inline".to_string(),
+            bytes: inline_bytes as u64,
+            files: inline_count as usize,
+        });
+        for (name, dirs) in [
+            ("blob", vec![self.config.blobs_dir()]),
+            ("running", vec![self.config.running_dir(), self.config.capture_dir()]),
+            ("buffer", vec![self.config.buffer_dir()]),
+        ] {
+            let mut entry = UsageEntry { name: name.to_string(), bytes: 0, files: 0 };
+            for dir in dirs {
+                let (bytes, files) = dir_usage(&dir)?;
+                entry.bytes += bytes;
+                entry.files += files;
+            }
+            usage.tiers.push(entry);
+        }
+
+        usage.largest_blobs = largest_blobs(&conn, top_blobs)?;
+
+        // Growth: partition bytes plus new blobs over the recent window
+        let today = Utc::now().date_naive();
+        let window_start = today - chrono::Duration::days(GROWTH_WINDOW_DAYS - 1);
+        if let Some(first) = partitions.keys().find(|d| **d >= window_start) {
+            usage.growth_window_days = (today - *first).num_days() + 1;
+            let partition_bytes: u64 = partitions
+                .range(*first..)
+                .map(|(_, entry)| entry.bytes)
+                .sum();
+            let blob_bytes: i64 = conn
+                .query_row(
+                    "SELECT COALESCE(SUM(byte_length), 0) FROM blob_registry WHERE first_seen::DATE >= ?::DATE",
+                    [first.to_string()],
+                    |row| row.get(0),
+                )
+                .unwrap_or(0);
+            usage.bytes_per_day =
+                (partition_bytes + blob_bytes as u64) / usage.growth_window_days.max(1) as u64;
+        }
+
+        usage.partitions = partitions
+            .into_iter()
+            .rev()
+            .map(|(date, mut entry)| {
+                entry.name = date.to_string();
+                entry
+            })
+            .collect();
+
+        Ok(usage)
+    }
+
+    /// Split the DuckDB file size across tables and dates by row count.
+    fn estimate_table_usage(
+        &self,
+        database_bytes: u64,
+        tables: &mut BTreeMap<String, UsageEntry>,
+        partitions: &mut BTreeMap<NaiveDate, UsageEntry>,
+    ) -> Result<()> {
+        let conn = self.connection_with_options(false)?;
+
+        let mut rows: Vec<(&str, NaiveDate, u64)> = Vec::new();
+        for table in USAGE_TABLES {
+            let sql = format!(
+                "SELECT date::VARCHAR, COUNT(*) FROM local.{} WHERE date IS NOT NULL GROUP BY date",
+                table
+            );
+            let Ok(mut stmt) = conn.prepare(&sql) else {
+                continue;
+            };
+            let counts = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?;
+            for count in counts {
+                let (date, count) = count?;
+                if let Ok(date) = NaiveDate::parse_from_str(&date, "%Y-%m-%d") {
+                    rows.push((table, date, count as u64));
+                }
+            }
+        }
+
+        let total_rows: u64 = rows.iter().map(|(_, _, count)| count).sum();
+        if total_rows == 0 {
+            return Ok(());
+        }
+        for (table, date, count) in rows {
+            let bytes = database_bytes * count / total_rows;
+            let entry = tables.entry(table.to_string()).or_insert_with(|| UsageEntry {
+                name: table.to_string(),
+                ..Default::default()
+            });
+            entry.bytes += bytes;
+            partitions.entry(date).or_default().bytes += bytes;
+        }
+        Ok(())
+    }
+}
+
+/// Add the parquet files under `<table_dir>/date=*/` to the table and date totals.
+fn scan_partitions(
+    table_dir: &Path,
+    table: &str,
+    tables: &mut BTreeMap<String, UsageEntry>,
+    partitions: &mut BTreeMap<NaiveDate, UsageEntry>,
+) -> Result<()> {
+    if !table_dir.exists() {
+        return Ok(());
+    }
+    // Seed partitions hold schema-only files; they aren't user data
+    let seed_date = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+
+    for entry in fs::read_dir(table_dir)?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(date) = name
+            .strip_prefix("date=")
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        else {
+            continue;
+        };
+        if date == seed_date {
+            continue;
+        }
+
+        let (bytes, files) = dir_usage(&entry.path())?;
+        let table_entry = tables.entry(table.to_string()).or_insert_with(|| UsageEntry {
+            name: table.to_string(),
+            ..Default::default()
+        });
+        table_entry.bytes += bytes;
+        table_entry.files += files;
+
+        let partition = partitions.entry(date).or_default();
+        partition.bytes += bytes;
+        partition.files += files;
+    }
+    Ok(())
+}
+
+/// Total size and file count of everything under `dir` (0 if missing).
+fn dir_usage(dir: &Path) -> Result<(u64, usize)> {
+    if !dir.exists() {
+        return Ok((0, 0));
+    }
+    let mut bytes = 0;
+    let mut files = 0;
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            let (b, f) = dir_usage(&path)?;
+            bytes += b;
+            files += f;
+        } else if let Ok(meta) = entry.metadata() {
+            bytes += meta.len();
+            files += 1;
+        }
+    }
+    Ok((bytes, files))
+}
+
+/// Count the blob files under `dir`, once per name and once per distinct
+/// file (`inodes` tracks files already seen through another name).
+fn scan_blob_files(dir: &Path, dedup: &mut BlobDedup, inodes: &mut HashSet<(u64, u64)>) -> Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            scan_blob_files(&path, dedup, inodes)?;
+            continue;
+        }
+        let Ok(meta) = entry.metadata() else { continue };
+        dedup.files += 1;
+        dedup.file_bytes += meta.len();
+        if inodes.insert(file_id(&meta, dedup.files)) {
+            dedup.unique_files += 1;
+            dedup.stored_bytes += meta.len();
+        }
+    }
+    Ok(())
+}
+
+/// Identity of the file behind a name: device and inode.
+#[cfg(unix)]
+fn file_id(meta: &fs::Metadata, _seq: usize) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (meta.dev(), meta.ino())
+}
+
+/// Without inodes every name counts as its own file.
+#[cfg(not(unix))]
+fn file_id(_meta: &fs::Metadata, seq: usize) -> (u64, u64) {
+    (u64::MAX, seq as u64)
+}
+
+/// Largest registered blobs with the latest command that produced each.
+fn largest_blobs(conn: &duckdb::Connection, limit: usize) -> Result<Vec<BlobUsage>> {
+    if limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    let with_cmd = format!(
+        r#"
+        WITH top AS (
+            SELECT content_hash, byte_length, ref_count
+            FROM blob_registry
+            ORDER BY byte_length DESC, content_hash
+            LIMIT {limit}
+        )
+        SELECT t.content_hash, t.byte_length, t.ref_count, arg_max(i.cmd, i.timestamp)
+        FROM top t
+        LEFT JOIN main.outputs o ON o.content_hash = t.content_hash
+        LEFT JOIN main.invocations i ON i.id = o.invocation_id
+        GROUP BY ALL
+        ORDER BY t.byte_length DESC, t.content_hash
+        "#,
+        limit = limit
+    );
+    // Outputs may have no files yet; fall back to the registry alone
+    let without_cmd = format!(
+        "SELECT content_hash, byte_length, ref_count, NULL::VARCHAR FROM blob_registry \
+         ORDER BY byte_length DESC, content_hash LIMIT {}",
+        limit
+    );
+
+    let mut stmt = match conn.prepare(&with_cmd) {
+        Ok(stmt) => stmt,
+        Err(e) if e.to_string().contains("No files found") => conn.prepare(&without_cmd)?,
+        Err(e) => return Err(e.into()),
+    };
+    let rows = stmt.query_map([], |row| {
+        Ok(BlobUsage {
+            content_hash: row.get(0)?,
+            bytes: row.get::<_, i64>(1)? as u64,
+            ref_count: row.get::<_, Option<i64>>(2)?.unwrap_or(1),
+            cmd: row.get(3)?,
+        })
+    })?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::initialize;
+    use crate::schema::InvocationRecord;
+    use crate::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_dir_usage_counts_nested_files() {
+        let tmp = TempDir::new().unwrap();
+        fs::create_dir_all(tmp.path().join("a/b")).unwrap();
+        fs::write(tmp.path().join("a/one"), [0u8; 10]).unwrap();
+        fs::write(tmp.path().join("a/b/two"), [0u8; 5]).unwrap();
+
+        assert_eq!(dir_usage(tmp.path()).unwrap(), (15, 2));
+        assert_eq!(dir_usage(&tmp.path().join("missing")).unwrap(), (0, 0));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_blob_files_counts_links_once() {
+        let tmp = TempDir::new().unwrap();
+        fs::create_dir_all(tmp.path().join("ab")).unwrap();
+        fs::write(tmp.path().join("ab/abcd--make.bin"), [0u8; 100]).unwrap();
+        fs::hard_link(tmp.path().join("ab/abcd--make.bin"), tmp.path().join("ab/abcd--cargo.bin")).unwrap();
+        fs::write(tmp.path().join("ab/abef--ls.bin"), [0u8; 30]).unwrap();
+
+        let mut dedup = BlobDedup::default();
+        scan_blob_files(tmp.path(), &mut dedup, &mut HashSet::new()).unwrap();
+        assert_eq!((dedup.files, dedup.file_bytes), (3, 230));
+        assert_eq!((dedup.unique_files, dedup.stored_bytes), (2, 130));
+        assert_eq!(dedup.linked_savings(), 100);
+    }
+
+    #[test]
+    fn test_scan_partitions_skips_seed() {
+        let tmp = TempDir::new().unwrap();
+        let table_dir = tmp.path().join("attempts");
+        fs::create_dir_all(table_dir.join("date=1970-01-01")).unwrap();
+        fs::create_dir_all(table_dir.join("date=2026-10-14")).unwrap();
+        fs::write(table_dir.join("date=1970-01-01/seed.parquet"), [0u8; 100]).unwrap();
+        fs::write(table_dir.join("date=2026-10-14/a.parquet"), [0u8; 40]).unwrap();
+
+        let mut tables = BTreeMap::new();
+        let mut partitions = BTreeMap::new();
+        scan_partitions(&table_dir, "attempts", &mut tables, &mut partitions).unwrap();
+
+        assert_eq!(tables["attempts"].bytes, 40);
+        assert_eq!(partitions.len(), 1);
+        assert_eq!(partitions[&NaiveDate::from_ymd_opt(2026, 10, 14).unwrap()].files, 1);
+    }
+
+    #[test]
+    fn test_storage_breakdown_reports_blobs() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_root(tmp.path());
+        initialize(&config).unwrap();
+        let store = Store::open(config).unwrap();
+
+        let inv = InvocationRecord::new("test-session", "cat big.log", "/home/user", 0, "user@laptop");
+        let big = vec![b'x'; store.config().inline_threshold * 2];
+        store.write_invocation(&inv).unwrap();
+        store
+            .store_output(inv.id, "stdout", &big, inv.date(), Some("cat"))
+            .unwrap();
+
+        let usage = store.storage_breakdown(10).unwrap();
+        assert!(usage.tables.iter().any(|t| t.name == "attempts" && t.bytes > 0));
+        assert_eq!(usage.partitions.len(), 1);
+        let blob_tier = usage.tiers.iter().find(|t| t.name == "blob").unwrap();
+        assert_eq!(blob_tier.files, 1);
+        assert_eq!(usage.largest_blobs.len(), 1);
+        assert_eq!(usage.largest_blobs[0].bytes, big.len() as u64);
+        assert_eq!(usage.largest_blobs[0].cmd.as_deref(), Some("cat big.log"));
+        assert!(usage.bytes_per_day > 0);
+    }
+}
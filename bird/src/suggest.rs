@@ -0,0 +1,70 @@
+//! "Did you mean" suggestions for mistyped names (commands, query fields).
+
+/// Most edits between a typo and a suggestion.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// The candidate closest to `typo`, within [`MAX_SUGGESTION_DISTANCE`]
+/// edits and fewer edits than `typo` has characters. Ties go to the
+/// earliest candidate.
+pub(crate) fn suggest<'a>(typo: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let limit = MAX_SUGGESTION_DISTANCE.min(typo.chars().count().saturating_sub(1));
+    let mut best: Option<(usize, &str)> = None;
+    for candidate in candidates {
+        if candidate == typo {
+            continue;
+        }
+        let distance = edit_distance(typo, candidate);
+        if distance <= limit && best.is_none_or(|(d, _)| distance < d) {
+            best = Some((distance, candidate));
+        }
+    }
+    best.map(|(_, candidate)| candidate.to_string())
+}
+
+/// Edit distance counting insertions, deletions, substitutions and swaps of
+/// adjacent characters (`gti` is one edit from `git`).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut d = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d = d.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = d;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("gti", "git"), 1);
+        assert_eq!(edit_distance("sl", "ls"), 1);
+        assert_eq!(edit_distance("carg", "cargo"), 1);
+        assert_eq!(edit_distance("pyhton3", "python3"), 1);
+        assert_eq!(edit_distance("mkae", "make"), 1);
+        assert_eq!(edit_distance("kubectl", "ls"), 7);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let known = ["git", "grep", "make", "ls"];
+        assert_eq!(suggest("gti", known).as_deref(), Some("git"));
+        assert_eq!(suggest("mkae", known).as_deref(), Some("make"));
+        // Too far, or too short to tell
+        assert_eq!(suggest("kubectl", known), None);
+        assert_eq!(suggest("l", known), None);
+    }
+}
@@ -0,0 +1,349 @@
+//! Fixtures for tests against populated stores (feature `testing`).
+//!
+//! The builders here produce realistic records that are also deterministic:
+//! ids, timestamps, client and hostname come from a sequence number instead
+//! of the clock, the environment or the machine, so assertions (and
+//! snapshots of query output) hold on every run. [`seed_store`] fills a
+//! store with a mix of builds, test runs and failures.
+//!
+//! ```no_run
+//! use bird::testing::{seed_store, EventBuilder, InvocationBuilder};
+//!
+//! let store = bird::Store::open(bird::Config::in_memory())?;
+//! seed_store(&store, 20)?;
+//! InvocationBuilder::new(20, "cargo test")
+//!     .exit_code(101)
+//!     .stdout("test parse::bad ... FAILED\n")
+//!     .event(EventBuilder::test_failure("parse::bad"))
+//!     .write(&store)?;
+//! # Ok::<(), bird::Error>(())
+//! ```
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use uuid::Uuid;
+
+use crate::schema::{EventRecord, InvocationRecord, SessionRecord};
+use crate::store::{InvocationBatch, Store};
+use crate::Result;
+
+/// Client id of fixture records.
+pub const FIXTURE_CLIENT: &str = "tester@fixture-host";
+/// Hostname of fixture records.
+pub const FIXTURE_HOST: &str = "fixture-host";
+/// Session fixture invocations belong to unless set otherwise.
+pub const FIXTURE_SESSION: &str = "fixture-session";
+/// Working directory of fixture invocations unless set otherwise.
+pub const FIXTURE_CWD: &str = "/home/tester/project";
+
+/// Kinds of fixture ids, so an invocation and its events never collide.
+const ID_INVOCATION: u8 = 0;
+const ID_EVENT: u8 = 1;
+
+/// Time of fixture invocation `seq`: one minute apart from 2024-03-01 09:00 UTC.
+pub fn fixture_time(seq: u64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap() + Duration::minutes(seq as i64)
+}
+
+/// Id of fixture invocation `seq`: a UUIDv7 at [`fixture_time`], so ids
+/// sort the same way as timestamps.
+pub fn fixture_id(seq: u64) -> Uuid {
+    derived_id(seq, ID_INVOCATION, 0)
+}
+
+fn derived_id(seq: u64, kind: u8, index: u64) -> Uuid {
+    let mut bytes = [0u8; 10];
+    bytes[0] = kind;
+    bytes[1] = index as u8;
+    bytes[2..].copy_from_slice(&seq.to_be_bytes());
+    uuid::Builder::from_unix_timestamp_millis(fixture_time(seq).timestamp_millis() as u64, &bytes).into_uuid()
+}
+
+/// The session record of fixture session `session_id`.
+pub fn fixture_session(session_id: &str) -> SessionRecord {
+    let registered_at = fixture_time(0);
+    SessionRecord {
+        session_id: session_id.to_string(),
+        client_id: FIXTURE_CLIENT.to_string(),
+        invoker: "zsh".to_string(),
+        invoker_pid: 4242,
+        invoker_type: "shell".to_string(),
+        registered_at,
+        cwd: Some(FIXTURE_CWD.to_string()),
+        date: registered_at.date_naive(),
+    }
+}
+
+/// Builds a fixture invocation, with its outputs and events.
+#[derive(Debug, Clone)]
+pub struct InvocationBuilder {
+    seq: u64,
+    record: InvocationRecord,
+    outputs: Vec<(String, Vec<u8>)>,
+    events: Vec<EventBuilder>,
+}
+
+impl InvocationBuilder {
+    /// Invocation number `seq` (which fixes its id and time) of `cmd`,
+    /// completed with exit code 0 after 1.2s.
+    pub fn new(seq: u64, cmd: &str) -> Self {
+        let mut record =
+            InvocationRecord::with_id(fixture_id(seq), FIXTURE_SESSION, cmd, FIXTURE_CWD, 0, FIXTURE_CLIENT);
+        record.timestamp = fixture_time(seq);
+        record.duration_ms = Some(1_200);
+        record.hostname = Some(FIXTURE_HOST.to_string());
+        record.username = Some("tester".to_string());
+        Self { seq, record, outputs: Vec::new(), events: Vec::new() }
+    }
+
+    pub fn session(mut self, session_id: &str) -> Self {
+        self.record.session_id = session_id.to_string();
+        self
+    }
+
+    pub fn cwd(mut self, cwd: &str) -> Self {
+        self.record.cwd = cwd.to_string();
+        self
+    }
+
+    pub fn exit_code(mut self, exit_code: i32) -> Self {
+        self.record.exit_code = Some(exit_code);
+        self
+    }
+
+    pub fn duration_ms(mut self, duration_ms: i64) -> Self {
+        self.record.duration_ms = Some(duration_ms);
+        self
+    }
+
+    pub fn at(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.record.timestamp = timestamp;
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.record.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn format_hint(mut self, format: &str) -> Self {
+        self.record.format_hint = Some(format.to_string());
+        self
+    }
+
+    pub fn stdout(self, content: impl AsRef<[u8]>) -> Self {
+        self.output("stdout", content)
+    }
+
+    pub fn stderr(self, content: impl AsRef<[u8]>) -> Self {
+        self.output("stderr", content)
+    }
+
+    /// Add captured output on `stream`.
+    pub fn output(mut self, stream: &str, content: impl AsRef<[u8]>) -> Self {
+        self.outputs.push((stream.to_string(), content.as_ref().to_vec()));
+        self
+    }
+
+    /// Add an event, as if extracted from the output.
+    pub fn event(mut self, event: EventBuilder) -> Self {
+        self.events.push(event);
+        self
+    }
+
+    /// The invocation record.
+    pub fn record(&self) -> InvocationRecord {
+        self.record.clone()
+    }
+
+    /// The invocation's events.
+    pub fn event_records(&self) -> Vec<EventRecord> {
+        self.events
+            .iter()
+            .enumerate()
+            .map(|(index, event)| event.build(&self.record, derived_id(self.seq, ID_EVENT, index as u64)))
+            .collect()
+    }
+
+    /// A batch writing the invocation, its session, outputs and events.
+    pub fn batch(&self) -> InvocationBatch {
+        let mut batch = InvocationBatch::new(self.record()).with_session(fixture_session(&self.record.session_id));
+        for (stream, content) in &self.outputs {
+            batch = batch.with_output(stream.clone(), content.clone());
+        }
+        if !self.events.is_empty() {
+            batch = batch.with_events(self.event_records());
+        }
+        batch
+    }
+
+    /// Write the invocation to `store`, returning its record.
+    pub fn write(&self, store: &Store) -> Result<InvocationRecord> {
+        store.write_batch(&self.batch())?;
+        Ok(self.record())
+    }
+}
+
+/// Builds a fixture event for an [`InvocationBuilder`].
+#[derive(Debug, Clone)]
+pub struct EventBuilder {
+    event_type: &'static str,
+    severity: Option<&'static str>,
+    ref_file: Option<String>,
+    ref_line: Option<i32>,
+    message: Option<String>,
+    error_code: Option<String>,
+    test_name: Option<String>,
+    status: Option<&'static str>,
+    format: String,
+}
+
+impl EventBuilder {
+    fn new(event_type: &'static str, format: &str) -> Self {
+        Self {
+            event_type,
+            severity: None,
+            ref_file: None,
+            ref_line: None,
+            message: None,
+            error_code: None,
+            test_name: None,
+            status: None,
+            format: format.to_string(),
+        }
+    }
+
+    /// A compiler error at `file:line`.
+    pub fn error(file: &str, line: i32, message: &str) -> Self {
+        Self {
+            severity: Some("error"),
+            ref_file: Some(file.to_string()),
+            ref_line: Some(line),
+            message: Some(message.to_string()),
+            ..Self::new("diagnostic", "gcc")
+        }
+    }
+
+    /// A compiler warning at `file:line`.
+    pub fn warning(file: &str, line: i32, message: &str) -> Self {
+        Self { severity: Some("warning"), ..Self::error(file, line, message) }
+    }
+
+    /// A failed test.
+    pub fn test_failure(test_name: &str) -> Self {
+        Self {
+            severity: Some("error"),
+            test_name: Some(test_name.to_string()),
+            status: Some("failed"),
+            ..Self::new("test_result", "cargo_test")
+        }
+    }
+
+    /// A passed test.
+    pub fn test_pass(test_name: &str) -> Self {
+        Self { severity: None, status: Some("passed"), ..Self::test_failure(test_name) }
+    }
+
+    pub fn code(mut self, error_code: &str) -> Self {
+        self.error_code = Some(error_code.to_string());
+        self
+    }
+
+    /// Parser the event is attributed to.
+    pub fn format(mut self, format: &str) -> Self {
+        self.format = format.to_string();
+        self
+    }
+
+    fn build(&self, invocation: &InvocationRecord, id: Uuid) -> EventRecord {
+        let mut record = EventRecord::new(
+            invocation.id,
+            invocation.client_id.clone(),
+            self.format.clone(),
+            invocation.timestamp.date_naive(),
+        );
+        record.id = id;
+        record.hostname = invocation.hostname.clone();
+        record.event_type = Some(self.event_type.to_string());
+        record.severity = self.severity.map(str::to_string);
+        record.ref_file = self.ref_file.clone();
+        record.ref_line = self.ref_line;
+        record.message = self.message.clone();
+        record.error_code = self.error_code.clone();
+        record.test_name = self.test_name.clone();
+        record.status = self.status.map(str::to_string);
+        record
+    }
+}
+
+/// Fixture invocation `seq` of [`seed_store`]: one of a fixed rotation of
+/// builds, test runs, VCS and shell commands, about a third of them failing.
+pub fn seed_invocation(seq: u64) -> InvocationBuilder {
+    let cwd = if seq % 4 == 3 { "/home/tester/other" } else { FIXTURE_CWD };
+    let builder = match seq % 6 {
+        0 => InvocationBuilder::new(seq, "cargo build")
+            .duration_ms(8_400)
+            .stderr("   Compiling fixture v0.1.0 (/home/tester/project)\n    Finished `dev` profile in 8.40s\n"),
+        1 => InvocationBuilder::new(seq, "cargo test")
+            .exit_code(101)
+            .duration_ms(12_750)
+            .stdout("running 2 tests\ntest parse::ok ... ok\ntest parse::bad ... FAILED\n")
+            .event(EventBuilder::test_pass("parse::ok"))
+            .event(EventBuilder::test_failure("parse::bad")),
+        2 => InvocationBuilder::new(seq, "git status")
+            .duration_ms(35)
+            .stdout("On branch main\nnothing to commit, working tree clean\n"),
+        3 => InvocationBuilder::new(seq, "make")
+            .exit_code(2)
+            .duration_ms(2_100)
+            .stderr("src/main.c:12:5: error: expected ';' before 'return'\nmake: *** [all] Error 1\n")
+            .event(EventBuilder::error("src/main.c", 12, "expected ';' before 'return'")),
+        4 => InvocationBuilder::new(seq, "ls -la").duration_ms(8).stdout("total 0\n"),
+        _ => InvocationBuilder::new(seq, "pytest tests/")
+            .exit_code(1)
+            .duration_ms(4_300)
+            .stdout("FAILED tests/test_api.py::test_login - AssertionError\n")
+            .event(EventBuilder::test_failure("tests/test_api.py::test_login").format("pytest")),
+    };
+    builder.cwd(cwd)
+}
+
+/// Write `n` fixture invocations ([`seed_invocation`] 0 to n-1) to `store`,
+/// returning their records.
+pub fn seed_store(store: &Store, n: u64) -> Result<Vec<InvocationRecord>> {
+    let builders: Vec<InvocationBuilder> = (0..n).map(seed_invocation).collect();
+    let batches: Vec<InvocationBatch> = builders.iter().map(InvocationBuilder::batch).collect();
+    if let Some((_, err)) = store.write_batches(&batches)?.into_iter().next() {
+        return Err(err);
+    }
+    Ok(builders.iter().map(InvocationBuilder::record).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn test_fixtures_are_deterministic() {
+        assert_eq!(seed_invocation(7).record().id, seed_invocation(7).record().id);
+        assert_ne!(fixture_id(1), fixture_id(2));
+        assert!(fixture_id(1) < fixture_id(2));
+
+        let builder = seed_invocation(1);
+        let events = builder.event_records();
+        assert_eq!(events.len(), 2);
+        assert_ne!(events[0].id, events[1].id);
+        assert_eq!(events[1].id, seed_invocation(1).event_records()[1].id);
+    }
+
+    #[test]
+    fn test_seed_store() {
+        let store = Store::open(Config::in_memory()).unwrap();
+        let records = seed_store(&store, 12).unwrap();
+
+        assert_eq!(records.len(), 12);
+        assert_eq!(store.invocation_count().unwrap(), 12);
+        let outputs = store.get_outputs(&records[0].id.to_string(), None).unwrap();
+        assert_eq!(outputs[0].stream, "stderr");
+    }
+}
@@ -0,0 +1,286 @@
+//! Reading `script(1)` typescripts and `tmux pipe-pane` logs.
+//!
+//! People who already record their terminals with `script` or
+//! `tmux pipe-pane` can ingest those logs (`shq save --from-typescript`)
+//! instead of giving them up for the shell hook. A log is split into
+//! commands by its OSC 133 markers where the shell emitted them (see
+//! [`crate::osc133`]); otherwise it's one capture of the whole session.
+//!
+//! `script` brackets its log with `Script started on ...` and
+//! `Script done on ... [COMMAND_EXIT_CODE="N"]` lines, which give the start
+//! time, the command (`-c`) and its exit code. A timing file (`script -t`,
+//! classic `DELAY BYTES` lines or advanced `O DELAY BYTES` entries) dates
+//! each chunk, so split commands get their own start times and durations.
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use uuid::Uuid;
+
+use crate::{osc133, Error, Result};
+
+const HEADER: &[u8] = b"Script started on ";
+const FOOTER: &[u8] = b"Script done on ";
+
+/// A parsed typescript.
+#[derive(Debug, Clone)]
+pub struct Typescript {
+    /// When recording started, from the header (or the timing file).
+    pub started_at: Option<DateTime<Utc>>,
+    /// The command `script -c` ran, from the header.
+    pub command: Option<String>,
+    /// Exit code from the footer.
+    pub exit_code: Option<i32>,
+    /// The recorded terminal output, without header and footer.
+    pub data: Vec<u8>,
+    /// End offset in `data` of each output chunk, and seconds since start
+    /// when it was written.
+    timing: Vec<(usize, f64)>,
+}
+
+/// One command found in a typescript.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    pub command: Option<String>,
+    pub exit_code: Option<i32>,
+    /// Byte range of its output in [`Typescript::data`].
+    pub output: Range<usize>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub duration_ms: Option<i64>,
+    /// Whether the capture ran to its end (a `D` marker or the footer).
+    pub finished: bool,
+}
+
+impl Typescript {
+    /// Parse `log`, with the contents of its timing file if there is one.
+    pub fn parse(log: &[u8], timing: Option<&str>) -> Result<Self> {
+        let mut typescript = Typescript { started_at: None, command: None, exit_code: None, data: Vec::new(), timing: Vec::new() };
+
+        let mut body = log;
+        if body.starts_with(HEADER) {
+            let end = body.iter().position(|&b| b == b'\n').map_or(body.len(), |i| i + 1);
+            let line = String::from_utf8_lossy(&body[HEADER.len()..end]);
+            let (date, fields) = split_fields(line.trim_end());
+            typescript.started_at = parse_date(date);
+            typescript.command = field(fields, "COMMAND");
+            body = &body[end..];
+        }
+        if let Some(start) = rfind(body, FOOTER).filter(|&i| i == 0 || body[i - 1] == b'\n') {
+            let line = String::from_utf8_lossy(&body[start + FOOTER.len()..]);
+            let (_, fields) = split_fields(line.trim_end());
+            typescript.exit_code = field(fields, "COMMAND_EXIT_CODE").and_then(|code| code.parse().ok());
+            // `script` writes a newline of its own before the footer
+            body = &body[..start];
+            body = body.strip_suffix(b"\n").unwrap_or(body);
+        }
+        typescript.data = body.to_vec();
+
+        if let Some(timing) = timing {
+            typescript.read_timing(timing)?;
+        }
+        Ok(typescript)
+    }
+
+    /// Read a typescript and the timing file next to it, if any.
+    pub fn read(path: &Path, timing: Option<&Path>) -> Result<Self> {
+        let log = std::fs::read(path)?;
+        let timing = match timing.map(Path::to_path_buf).or_else(|| timing_path(path)) {
+            Some(path) => Some(std::fs::read_to_string(path)?),
+            None => None,
+        };
+        Self::parse(&log, timing.as_deref())
+    }
+
+    /// When the byte at `offset` of [`Self::data`] was written.
+    pub fn time_at(&self, offset: usize) -> Option<DateTime<Utc>> {
+        let started_at = self.started_at?;
+        let seconds = match self.timing.iter().find(|(end, _)| *end > offset) {
+            Some((_, seconds)) => *seconds,
+            None => self.timing.last()?.1,
+        };
+        Some(started_at + chrono::Duration::milliseconds((seconds * 1000.0) as i64))
+    }
+
+    /// The commands in the typescript: one per OSC 133 segment, or the whole
+    /// session if the shell didn't mark its commands.
+    pub fn captures(&self) -> Vec<Capture> {
+        let segments = osc133::split(&self.data);
+        if segments.is_empty() {
+            return vec![self.capture(self.command.clone(), self.exit_code, 0..self.data.len(), true)];
+        }
+        segments
+            .into_iter()
+            .map(|segment| self.capture(segment.command, segment.exit_code, segment.output, segment.finished))
+            .collect()
+    }
+
+    /// A stable id for `capture`, the `index`th, dated `at`: derived from
+    /// its own command and output, so ingesting the same log again (or the
+    /// same log after it grew) doesn't record its commands twice.
+    pub fn capture_id(&self, index: usize, capture: &Capture, at: DateTime<Utc>) -> Uuid {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&(index as u64).to_le_bytes());
+        hasher.update(capture.command.as_deref().unwrap_or("").as_bytes());
+        hasher.update(&[0]);
+        hasher.update(&self.data[capture.output.clone()]);
+        let mut random = [0u8; 10];
+        random.copy_from_slice(&hasher.finalize().as_bytes()[..10]);
+        uuid::Builder::from_unix_timestamp_millis(at.timestamp_millis().max(0) as u64, &random).into_uuid()
+    }
+
+    fn capture(&self, command: Option<String>, exit_code: Option<i32>, output: Range<usize>, finished: bool) -> Capture {
+        let started_at = self.time_at(output.start).or(self.started_at);
+        let ended_at = self.time_at(output.end.saturating_sub(1));
+        let duration_ms = match (started_at, ended_at) {
+            (Some(start), Some(end)) if !self.timing.is_empty() => Some((end - start).num_milliseconds().max(0)),
+            _ => None,
+        };
+        Capture { command, exit_code, output, started_at, duration_ms, finished }
+    }
+
+    /// Accumulate timing entries into chunk offsets.
+    fn read_timing(&mut self, timing: &str) -> Result<()> {
+        let invalid = |line: &str| Error::Config(format!("Invalid typescript timing line '{}'", line));
+        let (mut offset, mut seconds) = (0usize, 0f64);
+        for line in timing.lines().filter(|l| !l.trim().is_empty()) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // Advanced format: a type letter, then the delay
+            let (kind, rest) = match fields[0].parse::<f64>() {
+                Ok(_) => ("O", &fields[..]),
+                Err(_) => (fields[0], &fields[1..]),
+            };
+            let delay: f64 = rest.first().and_then(|d| d.parse().ok()).ok_or_else(|| invalid(line))?;
+            seconds += delay;
+            match kind {
+                "O" => {
+                    let bytes: usize = rest.get(1).and_then(|b| b.parse().ok()).ok_or_else(|| invalid(line))?;
+                    offset += bytes;
+                    self.timing.push((offset, seconds));
+                }
+                // The header of an advanced log records the start time
+                "H" if rest.get(1) == Some(&"START_TIME") && self.started_at.is_none() => {
+                    self.started_at = parse_date(&rest[2..].join(" "));
+                }
+                // Input and signals take time but write no output
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The timing file conventionally kept next to `path` (`FILE.timing` or
+/// `FILE.tm`), if it exists.
+pub fn timing_path(path: &Path) -> Option<PathBuf> {
+    ["timing", "tm"]
+        .iter()
+        .map(|ext| {
+            let mut name = path.as_os_str().to_owned();
+            name.push(".");
+            name.push(ext);
+            PathBuf::from(name)
+        })
+        .find(|candidate| candidate.is_file())
+}
+
+/// Split `2024-01-15 10:30:00+00:00 [COMMAND="make" TERM="xterm"]` into its
+/// date and bracketed fields.
+fn split_fields(line: &str) -> (&str, &str) {
+    match line.find(" [") {
+        Some(i) if line.ends_with(']') => (line[..i].trim(), &line[i + 2..line.len() - 1]),
+        _ => (line.trim(), ""),
+    }
+}
+
+/// Value of `NAME="..."` in bracketed header fields.
+fn field(fields: &str, name: &str) -> Option<String> {
+    let start = fields.find(&format!("{}=\"", name)).filter(|&i| i == 0 || fields.as_bytes()[i - 1] == b' ')?;
+    let value = &fields[start + name.len() + 2..];
+    let end = value.find('"')?;
+    Some(value[..end].to_string())
+}
+
+/// Dates as util-linux (ISO 8601) and BSD `script` (ctime, local time)
+/// write them.
+fn parse_date(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    for format in ["%Y-%m-%d %H:%M:%S%:z", "%Y-%m-%d %H:%M:%S%z", "%Y-%m-%dT%H:%M:%S%:z"] {
+        if let Ok(date) = DateTime::parse_from_str(s, format) {
+            return Some(date.with_timezone(&Utc));
+        }
+    }
+    let naive = NaiveDateTime::parse_from_str(s, "%a %b %e %H:%M:%S %Y").ok()?;
+    Local.from_local_datetime(&naive).earliest().map(|date| date.with_timezone(&Utc))
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn osc(body: &str) -> String {
+        format!("\x1b]133;{}\x07", body)
+    }
+
+    #[test]
+    fn test_header_and_footer() {
+        let log = "Script started on 2024-01-15 10:30:00+00:00 [COMMAND=\"make -j4\" TERM=\"xterm\"]\n\
+                   built\n\
+                   \nScript done on 2024-01-15 10:30:05+00:00 [COMMAND_EXIT_CODE=\"2\"]\n";
+        let typescript = Typescript::parse(log.as_bytes(), None).unwrap();
+        assert_eq!(typescript.command.as_deref(), Some("make -j4"));
+        assert_eq!(typescript.exit_code, Some(2));
+        assert_eq!(typescript.data, b"built\n");
+        assert_eq!(typescript.started_at.unwrap().to_rfc3339(), "2024-01-15T10:30:00+00:00");
+
+        let captures = typescript.captures();
+        assert_eq!(captures.len(), 1);
+        assert_eq!((captures[0].command.as_deref(), captures[0].exit_code), (Some("make -j4"), Some(2)));
+        assert_eq!(captures[0].duration_ms, None);
+    }
+
+    #[test]
+    fn test_raw_pipe_pane_log() {
+        let typescript = Typescript::parse(b"$ ls\r\na b\r\n", None).unwrap();
+        assert_eq!(typescript.started_at, None);
+        assert_eq!(typescript.captures()[0].output, 0..typescript.data.len());
+    }
+
+    #[test]
+    fn test_marked_commands_with_timing() {
+        let first = [osc("A"), "$ ".into(), osc("B"), "make\r\n".into(), osc("C")].concat();
+        let second = ["built\r\n".into(), osc("D;0"), osc("A"), "$ ".into(), osc("B"), "ls\r\n".into(), osc("C")].concat();
+        let third = ["a\r\n".into(), osc("D;1")].concat();
+        let body = [first.clone(), second.clone(), third.clone()].concat();
+        let log = format!("Script started on 2024-01-15 10:30:00+00:00 [TERM=\"xterm\"]\n{}", body);
+        // Advanced format; the classic `0.5 20` would mean the same for output
+        let timing = format!("O 0.5 {}\nI 0.1 3\nO 2.4 {}\nO 1.0 {}\n", first.len(), second.len(), third.len());
+
+        let typescript = Typescript::parse(log.as_bytes(), Some(&timing)).unwrap();
+        let captures = typescript.captures();
+        let summary: Vec<_> = captures.iter().map(|c| (c.command.as_deref().unwrap(), c.exit_code)).collect();
+        assert_eq!(summary, [("make", Some(0)), ("ls", Some(1))]);
+        assert_eq!(&typescript.data[captures[0].output.clone()], b"built\r\n");
+        // make's output arrived 3s in; ls's a second later
+        assert_eq!(captures[0].started_at.unwrap().to_rfc3339(), "2024-01-15T10:30:03+00:00");
+        assert_eq!(captures[1].started_at.unwrap().to_rfc3339(), "2024-01-15T10:30:04+00:00");
+        assert_eq!(captures[0].duration_ms, Some(0));
+
+        // The log grows: the commands already in it keep their ids
+        let at = captures[0].started_at.unwrap();
+        let id = typescript.capture_id(0, &captures[0], at);
+        let grown = Typescript::parse(format!("{}{}$ ", log, osc("A")).as_bytes(), Some(&timing)).unwrap();
+        assert_eq!(grown.capture_id(0, &grown.captures()[0], at), id);
+        assert_ne!(typescript.capture_id(1, &captures[1], at), id);
+    }
+
+    #[test]
+    fn test_invalid_timing() {
+        assert!(Typescript::parse(b"x", Some("O 0.5\n")).is_err());
+        assert!(Typescript::parse(b"x", Some("0.5 1\nnope\n")).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+//! Versions of what shq's components hand each other.
+//!
+//! A setup mixes pieces that can come from different releases: the shell
+//! hook (generated by the shq installed when the shell started, or pasted
+//! into an rc file), the binaries (`shq`, `shq-capture`), saves waiting in
+//! the spool, and the store. Each carries a version, so a mismatch can be
+//! reported as the piece that is old instead of an unknown flag or an SQL
+//! error:
+//!
+//! - hook: [`HOOK_VERSION`], exported by the hook as `SHQ_HOOK_VERSION`
+//! - spool files: [`SPOOL_VERSION`], in each file
+//! - store: `schema_version` ([`BIRD_SCHEMA_VERSION`]) and the newest shq
+//!   that used it, `shq_version`, in `bird_meta`
+//!
+//! [`BIRD_SCHEMA_VERSION`]: crate::BIRD_SCHEMA_VERSION
+
+use std::cmp::Ordering;
+
+#[cfg(feature = "store")]
+use crate::{Config, Error, Result};
+
+/// Version of this build of shq and its libraries.
+pub const SHQ_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Version of the shell hook: the script `shq hook init` generates and the
+/// arguments it passes to `shq` and `shq-capture`. Bump it whenever the
+/// generated script changes. Scripts from before versioning count as 1.
+pub const HOOK_VERSION: u32 = 2;
+
+/// Exported by the hook: the [`HOOK_VERSION`] of the script the shell loaded.
+pub const HOOK_VERSION_VAR: &str = "SHQ_HOOK_VERSION";
+
+/// Version of spool files. Files without one are version 1.
+pub const SPOOL_VERSION: u32 = 1;
+
+/// The hook version of the shell shq was started from, if it loaded a hook.
+pub fn shell_hook_version() -> Option<u32> {
+    std::env::var(HOOK_VERSION_VAR).ok()?.trim().parse().ok()
+}
+
+/// Compare dotted release numbers: `0.1.10` is newer than `0.1.9`.
+/// Anything after a `-` (pre-release tags) is ignored.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    fn parts(v: &str) -> Vec<u64> {
+        let v = v.trim().trim_start_matches('v');
+        let v = v.split('-').next().unwrap_or(v);
+        v.split('.').map(|part| part.parse().unwrap_or(0)).collect()
+    }
+    let (a, b) = (parts(a), parts(b));
+    for i in 0..a.len().max(b.len()) {
+        match a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)) {
+            Ordering::Equal => continue,
+            other => return other,
+        }
+    }
+    Ordering::Equal
+}
+
+/// What's wrong between `hook` (a shell's hook version) and this shq, if
+/// anything, naming the side that is old.
+pub fn hook_mismatch(hook: u32) -> Option<String> {
+    match hook.cmp(&HOOK_VERSION) {
+        Ordering::Equal => None,
+        Ordering::Less => Some(format!(
+            "this shell loaded hook version {}, older than this shq's ({}): if the hook is a saved \
+             copy run `shq hook upgrade`, then start a new shell",
+            hook, HOOK_VERSION
+        )),
+        Ordering::Greater => Some(format!(
+            "this shell loaded hook version {} from a newer shq than this one ({}, hook version {}): \
+             an older shq comes first on PATH",
+            hook, SHQ_VERSION, HOOK_VERSION
+        )),
+    }
+}
+
+/// The versions a store records in `bird_meta`.
+#[cfg(feature = "store")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoreVersions {
+    /// `schema_version`; `None` for stores from before `bird_meta`.
+    pub schema: Option<String>,
+    /// The newest shq that used the store; `None` for stores last used
+    /// before it was recorded.
+    pub shq: Option<String>,
+}
+
+/// Read the versions the store at `config` records, without opening it
+/// for writing or checking its schema first (which would fail on the
+/// mismatch this is meant to explain).
+#[cfg(feature = "store")]
+pub fn store_versions(config: &Config) -> Result<StoreVersions> {
+    let db_path = config.db_path();
+    if !db_path.exists() {
+        return Err(Error::NotInitialized(config.bird_root.clone()));
+    }
+    let conn = duckdb::Connection::open_in_memory()?;
+    conn.execute_batch(&format!(
+        "ATTACH '{}' AS store (READ_ONLY)",
+        db_path.display().to_string().replace('\'', "''")
+    ))?;
+
+    let mut versions = StoreVersions::default();
+    let mut stmt = match conn.prepare("SELECT key, value FROM store.bird_meta WHERE key IN ('schema_version', 'shq_version')") {
+        Ok(stmt) => stmt,
+        // No bird_meta: from before schema versioning
+        Err(_) => return Ok(versions),
+    };
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+    for row in rows {
+        match row? {
+            (key, value) if key == "schema_version" => versions.schema = Some(value),
+            (_, value) => versions.shq = Some(value),
+        }
+    }
+    Ok(versions)
+}
+
+/// A hint for an error from the store at `config`, if a newer shq has used
+/// it: what it wrote may be what this one can't read.
+#[cfg(feature = "store")]
+pub fn store_mismatch_hint(config: &Config) -> Option<String> {
+    let newest = store_versions(config).ok()?.shq?;
+    (compare(&newest, SHQ_VERSION) == Ordering::Greater).then(|| {
+        format!(
+            "the store was last used by shq {}, newer than this one ({}); upgrade shq (`shq doctor` shows which parts are old)",
+            newest, SHQ_VERSION
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(compare("0.1.10", "0.1.9"), Ordering::Greater);
+        assert_eq!(compare("0.2", "0.2.0"), Ordering::Equal);
+        assert_eq!(compare("v1.0.0-rc1", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare("0.1.9", "0.1.10"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_hook_mismatch() {
+        assert!(hook_mismatch(HOOK_VERSION).is_none());
+        assert!(hook_mismatch(HOOK_VERSION - 1).unwrap().contains("shq hook upgrade"));
+        assert!(hook_mismatch(HOOK_VERSION + 1).unwrap().contains("first on PATH"));
+    }
+}
@@ -0,0 +1,72 @@
+//! Recognizing task runner shims (`shq wrap`).
+//!
+//! A wrapped tool is captured, with output, by its shim; the shell hook's
+//! save skips the bare command so it isn't recorded twice. Writing and
+//! removing shims is up to shq.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Marker line identifying a shim written by shq.
+pub const MARKER: &str = "# shq-wrap";
+
+/// Whether the file at `path` is a shim written by shq.
+pub fn is_shim(path: &Path) -> bool {
+    fs::read_to_string(path)
+        .map(|s| s.lines().nth(1) == Some(MARKER))
+        .unwrap_or(false)
+}
+
+/// Validate a tool name: shims are plain file names looked up on `$PATH`.
+pub fn validate_tool(tool: &str) -> bool {
+    !tool.is_empty()
+        && tool != "."
+        && tool != ".."
+        && !tool.contains('/')
+        && !tool.chars().any(char::is_whitespace)
+}
+
+/// Whether `command` runs a wrapped tool (its first word has a shim in `dir`
+/// and `dir` is on `path`).
+///
+/// The shim captures such commands itself, so the shell hook skips them.
+pub fn is_wrapped(dir: &Path, path: &OsString, command: &str) -> bool {
+    let Some(tool) = command.split_whitespace().next() else {
+        return false;
+    };
+    validate_tool(tool) && std::env::split_paths(path).any(|p| same_dir(&p, dir)) && is_shim(&dir.join(tool))
+}
+
+/// `path` with `dir` removed, so the real tool is found instead of the shim.
+pub fn path_without(path: &OsString, dir: &Path) -> io::Result<OsString> {
+    let kept: Vec<PathBuf> = std::env::split_paths(path)
+        .filter(|p| !same_dir(p, dir))
+        .collect();
+    std::env::join_paths(kept).map_err(io::Error::other)
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    if a == b {
+        return true;
+    }
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_tool() {
+        assert!(validate_tool("make"));
+        assert!(validate_tool("go-task"));
+        assert!(!validate_tool(""));
+        assert!(!validate_tool("../make"));
+        assert!(!validate_tool("make all"));
+    }
+}
@@ -0,0 +1,277 @@
+//! shq-capture: spool a finished command without loading DuckDB.
+//!
+//! The shell hook saves after every command. `shq save --async` spools the
+//! record too, but each run pays for starting the full shq, DuckDB and all.
+//! shq-capture takes the hook's save arguments, applies the same privacy
+//! checks, writes the record to the spool and starts `shq spool flush` if
+//! no flusher is running. Everything else, queries included, is shq's job.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use bird::spool::{self, SpooledSave};
+use bird::{Config, ContextMetadata, InvocationRecord, ProjectConfig, SessionRecord};
+
+const USAGE: &str = "\
+usage: shq-capture -c CMD [-x EXIT] [-d MS] [-o FILE] [-e FILE] [--session-id ID]
+                   [--invoker-pid PID] [--invoker NAME] [--invoker-type TYPE]
+                   [-t TAG] [--dim KEY=VALUE]... [--no-extract] [--compact] [-q]
+
+Spool a finished command for shq (same options as `shq save --async`).";
+
+/// A hook save: the subset of `shq save`'s options the hooks use.
+#[derive(Debug, PartialEq)]
+struct Args {
+    command: String,
+    exit_code: i32,
+    duration_ms: Option<i64>,
+    stdout_file: Option<PathBuf>,
+    stderr_file: Option<PathBuf>,
+    session_id: Option<String>,
+    invoker_pid: Option<u32>,
+    invoker: Option<String>,
+    invoker_type: String,
+    tag: Option<String>,
+    dims: Vec<String>,
+    extract: bool,
+    compact: bool,
+}
+
+/// Parse the hook's arguments. With `lenient` (a hook newer than this
+/// shq-capture), options it doesn't know are skipped, with their values,
+/// so the command is still captured.
+fn parse_args(args: impl IntoIterator<Item = String>, lenient: bool) -> Result<Args, String> {
+    let mut command = None;
+    let mut parsed = Args {
+        command: String::new(),
+        exit_code: 0,
+        duration_ms: None,
+        stdout_file: None,
+        stderr_file: None,
+        session_id: None,
+        invoker_pid: None,
+        invoker: None,
+        invoker_type: "shell".to_string(),
+        tag: None,
+        dims: Vec::new(),
+        extract: true,
+        compact: false,
+    };
+
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        // --flag=value as well as --flag value
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg, None),
+        };
+        let mut value = || inline.clone().or_else(|| args.next()).ok_or_else(|| format!("{} needs a value", flag));
+        let invalid = |v: String| format!("invalid value '{}' for {}", v, flag);
+
+        match flag.as_str() {
+            "-c" | "--command" => command = Some(value()?),
+            "-x" | "--exit-code" => {
+                let v = value()?;
+                parsed.exit_code = v.parse().map_err(|_| invalid(v))?;
+            }
+            "-d" | "--duration" => {
+                let v = value()?;
+                parsed.duration_ms = Some(v.parse().map_err(|_| invalid(v))?);
+            }
+            "-o" | "--stdout" => parsed.stdout_file = Some(value()?.into()),
+            "-e" | "--stderr" => parsed.stderr_file = Some(value()?.into()),
+            "--session-id" => parsed.session_id = Some(value()?),
+            "--invoker-pid" => {
+                let v = value()?;
+                parsed.invoker_pid = Some(v.parse().map_err(|_| invalid(v))?);
+            }
+            "--invoker" => parsed.invoker = Some(value()?),
+            "--invoker-type" => parsed.invoker_type = value()?,
+            "-t" | "--tag" => parsed.tag = Some(value()?),
+            "--dim" => parsed.dims.push(value()?),
+            "--no-extract" => parsed.extract = false,
+            "--compact" => parsed.compact = true,
+            // Nothing to suppress: shq-capture only ever reports errors
+            "-q" | "--quiet" => {}
+            _ if lenient && flag.starts_with('-') => {
+                if inline.is_none() && args.peek().is_some_and(|next| !next.starts_with('-')) {
+                    args.next();
+                }
+            }
+            _ => return Err(format!("unexpected argument '{}'", flag)),
+        }
+    }
+
+    parsed.command = command.ok_or("-c CMD is required")?;
+    Ok(parsed)
+}
+
+/// Invoker name default: basename of `$SHELL` (as `shq save`).
+fn invoker_name() -> String {
+    std::env::var("SHELL")
+        .map(|s| {
+            Path::new(&s)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or(s)
+        })
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// The shq that runs the flusher: the one installed next to us, else `$PATH`'s.
+fn shq_path() -> PathBuf {
+    std::env::current_exe()
+        .map(|exe| exe.with_file_name("shq"))
+        .ok()
+        .filter(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from("shq"))
+}
+
+/// Spool the save, following `shq save --async`.
+fn capture(args: Args) -> bird::Result<()> {
+    let stdout = args.stdout_file.as_deref().map(std::fs::read).transpose()?;
+    let stderr = args.stderr_file.as_deref().map(std::fs::read).transpose()?;
+
+    // Check for nosave marker - command opted out of recording
+    if [&stdout, &stderr].into_iter().flatten().any(|c| bird::privacy::contains_nosave_marker(c)) {
+        return Ok(());
+    }
+
+    let config = Config::load()?;
+    if bird::privacy::is_paused(&config) || bird::privacy::should_exclude(&config, &args.command) {
+        return Ok(());
+    }
+
+    // Wrapped tools are captured, with output, by their shim
+    if args.invoker_type == "shell" {
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        if bird::wrap::is_wrapped(&config.shims_dir(), &path, &args.command) {
+            return Ok(());
+        }
+    }
+
+    let mut dims: Vec<(String, String)> = ProjectConfig::from_env().dims.into_iter().collect();
+    for dim in &args.dims {
+        dims.push(bird::parse_dim(dim)?);
+    }
+
+    let cwd = std::env::current_dir()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| ".".to_string());
+    let ppid = std::os::unix::process::parent_id();
+    let session_id = args.session_id.unwrap_or_else(|| format!("shell-{}", ppid));
+    let stored_cmd = if config.privacy.redact_commands {
+        bird::privacy::redact_command(&args.command)
+    } else {
+        args.command.clone()
+    };
+
+    let session = SessionRecord::new(
+        &session_id,
+        &config.client_id,
+        args.invoker.unwrap_or_else(invoker_name),
+        args.invoker_pid.unwrap_or(ppid),
+        &args.invoker_type,
+    );
+
+    let mut context = ContextMetadata::collect(Some(Path::new(&cwd)));
+    if config.privacy.record_env {
+        context = context.with_env();
+    }
+    let mut record = InvocationRecord::new(&session_id, &stored_cmd, &cwd, args.exit_code, &config.client_id)
+        .with_metadata(context.into_map())
+        .with_dims(&dims);
+    if let Some(ms) = args.duration_ms {
+        record = record.with_duration(ms);
+    }
+    if let Some(ref tag) = args.tag {
+        record = record.with_tag(tag);
+    }
+
+    let mut save = SpooledSave::new(record).with_session(session);
+    for (stream, content) in [("stdout", stdout), ("stderr", stderr)] {
+        if let Some(content) = content {
+            save = save.with_output(stream, &content);
+        }
+    }
+    save.extract = args.extract;
+    save.compact = args.compact;
+
+    spool::write(&config, &save)?;
+    spool::start_flusher(&config, &shq_path())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("-h" | "--help") => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Some("-V" | "--version") => {
+            println!("shq-capture {}", env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS;
+        }
+        _ => {}
+    }
+
+    let hook = bird::version::shell_hook_version();
+    let newer_hook = hook.is_some_and(|hook| hook > bird::version::HOOK_VERSION);
+    let args = match parse_args(args, newer_hook) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("shq-capture: {}\n\n{}", e, USAGE);
+            if let Some(hint) = hook.and_then(bird::version::hook_mismatch) {
+                eprintln!("hint: {}", hint);
+            }
+            return ExitCode::from(2);
+        }
+    };
+    match capture(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("shq-capture: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|s| s.to_string()), false)
+    }
+
+    #[test]
+    fn test_parse_hook_args() {
+        let args = parse(&[
+            "-c", "make test", "-x", "2", "-d", "1500", "--session-id", "zsh-42",
+            "--invoker-pid=42", "--invoker", "zsh", "--compact", "-q",
+        ])
+        .unwrap();
+        assert_eq!(args.command, "make test");
+        assert_eq!(args.exit_code, 2);
+        assert_eq!(args.duration_ms, Some(1500));
+        assert_eq!(args.session_id.as_deref(), Some("zsh-42"));
+        assert_eq!(args.invoker_pid, Some(42));
+        assert_eq!(args.invoker_type, "shell");
+        assert!(args.extract && args.compact);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_args() {
+        assert!(parse(&["-x", "0"]).unwrap_err().contains("-c CMD"));
+        assert!(parse(&["-c", "ls", "-x", "nope"]).unwrap_err().contains("invalid value 'nope' for -x"));
+        assert!(parse(&["-c", "ls", "--async"]).unwrap_err().contains("unexpected argument '--async'"));
+        assert!(parse(&["-c"]).unwrap_err().contains("needs a value"));
+    }
+
+    #[test]
+    fn test_parse_lenient_skips_unknown_options() {
+        let args = ["-c", "ls", "--new-flag", "value", "--other=1", "--switch", "-x", "3"].map(String::from);
+        let parsed = parse_args(args, true).unwrap();
+        assert_eq!((parsed.command.as_str(), parsed.exit_code), ("ls", 3));
+    }
+}
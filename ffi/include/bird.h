@@ -0,0 +1,78 @@
+/*
+ * bird.h - C ABI for BIRD (Buffer and Invocation Record Database)
+ *
+ * Link against libbird_ffi (cargo build -p magic-bird-ffi --release).
+ *
+ * Functions that can fail return NULL or -1 and leave a message for
+ * bird_last_error() on the calling thread. Strings are UTF-8 and
+ * NUL-terminated. Strings and buffers returned by bird belong to the
+ * caller: free them with bird_string_free() / bird_bytes_free().
+ *
+ * Invocations are written as JSON, in the format of a spool file:
+ *
+ *   {
+ *     "invocation": { "id": "<uuid v7>", "session_id": "...", "cmd": "...",
+ *                     "cwd": "...", "exit_code": 0, "status": "completed",
+ *                     "client_id": "user@host", "timestamp": "<RFC 3339>",
+ *                     "duration_ms": 1200, ... },
+ *     "session": { ... },                              (optional)
+ *     "outputs": [ { "stream": "stdout", "content": "<base64>" } ],
+ *     "extract": true                                  (optional)
+ *   }
+ */
+
+#ifndef BIRD_H
+#define BIRD_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An open store. */
+typedef struct BirdStore BirdStore;
+
+/* Library version, e.g. "0.1.10". Static; don't free. */
+const char *bird_version(void);
+
+/* Message for the last failed call on this thread, or NULL. Valid until
+ * the next failing call on the same thread; don't free. */
+const char *bird_last_error(void);
+
+/* Open the store at bird_root, or the default one ($BIRD_ROOT, else
+ * ~/.local/share/bird) if NULL. Returns NULL on error. */
+BirdStore *bird_store_open(const char *bird_root);
+
+/* Close a store. NULL is ignored. */
+void bird_store_close(BirdStore *store);
+
+/* Write one invocation, with its session and outputs, from a JSON save
+ * document (see above). Returns 0, or -1 on error. */
+int bird_write_batch(const BirdStore *store, const char *save_json);
+
+/* Query invocations with a shq selector ("~20", "%exit<>0~5", ...; NULL
+ * for the most recent), at most `limit` when the selector has no range.
+ * Returns a JSON array of {id, cmd, exit_code, timestamp, duration_ms}
+ * (free with bird_string_free), or NULL on error. */
+char *bird_query_invocations(const BirdStore *store, const char *query, size_t limit);
+
+/* Read an invocation's output: one stream ("stdout", "stderr", ...) or all
+ * of them in capture order if stream is NULL. Sets *data / *len (free with
+ * bird_bytes_free; NULL / 0 when there is no output). Returns 0, or -1 on
+ * error. */
+int bird_read_output(const BirdStore *store, const char *invocation_id, const char *stream,
+                     uint8_t **data, size_t *len);
+
+/* Free a string returned by bird. NULL is ignored. */
+void bird_string_free(char *s);
+
+/* Free a buffer returned by bird_read_output. NULL is ignored. */
+void bird_bytes_free(uint8_t *data, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* BIRD_H */
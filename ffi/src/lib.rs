@@ -0,0 +1,335 @@
+//! C ABI for BIRD.
+//!
+//! Lets non-Rust clients (a Python blq, editor plugins) open a store, write
+//! invocations, query them and read their output without shelling out to
+//! shq. The declarations are in `include/bird.h`.
+//!
+//! Conventions:
+//! - Functions that can fail return NULL or -1 and leave a message for
+//!   [`bird_last_error`] (per thread).
+//! - Strings are UTF-8 and NUL-terminated. Strings and buffers returned by
+//!   bird are owned by the caller and freed with [`bird_string_free`] /
+//!   [`bird_bytes_free`].
+//! - Records cross the boundary as JSON: a write takes the same document a
+//!   spooled save is stored as ([`bird::spool::SpooledSave`]).
+//! - A panic inside bird is reported as an error, never unwound into C.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use bird::spool::SpooledSave;
+use bird::{parse_query, Config, Error, Result, Store};
+
+/// An open store. Opaque to C.
+pub struct BirdStore {
+    store: Store,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Run `f`, turning an error or a panic into `fail` plus a last error.
+fn guard<T>(fail: T, f: impl FnOnce() -> Result<T>) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(e)) => {
+            set_last_error(match e.hint() {
+                Some(hint) => format!("{} ({})", e, hint),
+                None => e.to_string(),
+            });
+            fail
+        }
+        Err(_) => {
+            set_last_error("internal error (panic) in bird".to_string());
+            fail
+        }
+    }
+}
+
+/// Borrow a required string argument.
+///
+/// # Safety
+/// `s` must be NULL or a valid NUL-terminated string.
+unsafe fn arg<'a>(s: *const c_char, name: &str) -> Result<&'a str> {
+    opt_arg(s, name)?.ok_or_else(|| Error::Config(format!("{} must not be NULL", name)))
+}
+
+/// Borrow an optional string argument (NULL for none).
+///
+/// # Safety
+/// `s` must be NULL or a valid NUL-terminated string.
+unsafe fn opt_arg<'a>(s: *const c_char, name: &str) -> Result<Option<&'a str>> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map(Some)
+        .map_err(|_| Error::Config(format!("{} is not valid UTF-8", name)))
+}
+
+/// Borrow the store behind a handle.
+///
+/// # Safety
+/// `store` must be NULL or a handle from [`bird_store_open`] not yet closed.
+unsafe fn handle<'a>(store: *const BirdStore) -> Result<&'a Store> {
+    store
+        .as_ref()
+        .map(|s| &s.store)
+        .ok_or_else(|| Error::Config("store must not be NULL".to_string()))
+}
+
+fn to_c_string(s: String) -> Result<*mut c_char> {
+    CString::new(s)
+        .map(CString::into_raw)
+        .map_err(|_| Error::Storage("result contains a NUL byte".to_string()))
+}
+
+/// Library version, e.g. "0.1.10". Static; don't free.
+#[no_mangle]
+pub extern "C" fn bird_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Message for the last failed call on this thread, or NULL.
+///
+/// Valid until the next failing call on the same thread; don't free.
+#[no_mangle]
+pub extern "C" fn bird_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Open the store at `bird_root`, or the default one (`$BIRD_ROOT`,
+/// else `~/.local/share/bird`) if NULL. Returns NULL on error.
+///
+/// # Safety
+/// `bird_root` must be NULL or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn bird_store_open(bird_root: *const c_char) -> *mut BirdStore {
+    guard(ptr::null_mut(), || {
+        let config = match opt_arg(bird_root, "bird_root")? {
+            Some(root) => Config::load_from(Path::new(root))?,
+            None => Config::load()?,
+        };
+        let store = Store::open(config)?;
+        Ok(Box::into_raw(Box::new(BirdStore { store })))
+    })
+}
+
+/// Close a store. NULL is ignored.
+///
+/// # Safety
+/// `store` must be NULL or a handle from [`bird_store_open`] not yet closed.
+#[no_mangle]
+pub unsafe extern "C" fn bird_store_close(store: *mut BirdStore) {
+    if !store.is_null() {
+        drop(Box::from_raw(store));
+    }
+}
+
+/// Write one invocation, with its session and outputs, from a JSON save
+/// document (the format of a spool file). Extracts events if the
+/// document's `extract` is set or the store extracts automatically.
+///
+/// Returns 0 on success, -1 on error.
+///
+/// # Safety
+/// `store` must be a handle from [`bird_store_open`]; `save_json` a valid
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn bird_write_batch(store: *const BirdStore, save_json: *const c_char) -> c_int {
+    guard(-1, || {
+        let store = handle(store)?;
+        let save: SpooledSave = serde_json::from_str(arg(save_json, "save_json")?)?;
+        store.write_batch(&save.to_batch()?)?;
+        if save.extract || store.config().auto_extract {
+            store.extract_events(&save.invocation.id.to_string(), None)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Query invocations with a shq selector (e.g. "~20", "%exit<>0~5"; NULL
+/// for the most recent), returning at most `limit` when the selector has
+/// no range. Returns a JSON array of `{id, cmd, exit_code, timestamp,
+/// duration_ms}` to free with [`bird_string_free`], or NULL on error.
+///
+/// # Safety
+/// `store` must be a handle from [`bird_store_open`]; `query` NULL or a
+/// valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn bird_query_invocations(
+    store: *const BirdStore,
+    query: *const c_char,
+    limit: usize,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let store = handle(store)?;
+        let query = parse_query(opt_arg(query, "query")?.unwrap_or(""));
+        let rows: Vec<serde_json::Value> = store
+            .query_invocations_with_limit(&query, limit.max(1))?
+            .into_iter()
+            .map(|inv| {
+                serde_json::json!({
+                    "id": inv.id,
+                    "cmd": inv.cmd,
+                    "exit_code": inv.exit_code,
+                    "timestamp": inv.timestamp,
+                    "duration_ms": inv.duration_ms,
+                })
+            })
+            .collect();
+        to_c_string(serde_json::to_string(&rows)?)
+    })
+}
+
+/// Read an invocation's captured output: one stream (e.g. "stdout"), or
+/// every stream in capture order if `stream` is NULL. On success sets
+/// `*data`/`*len` (free with [`bird_bytes_free`]; NULL/0 when there is no
+/// output) and returns 0; returns -1 on error.
+///
+/// # Safety
+/// `store` must be a handle from [`bird_store_open`]; `invocation_id` a
+/// valid NUL-terminated string; `stream` NULL or one; `data` and `len`
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bird_read_output(
+    store: *const BirdStore,
+    invocation_id: *const c_char,
+    stream: *const c_char,
+    data: *mut *mut u8,
+    len: *mut usize,
+) -> c_int {
+    guard(-1, || {
+        let store = handle(store)?;
+        if data.is_null() || len.is_null() {
+            return Err(Error::Config("data and len must not be NULL".to_string()));
+        }
+        // Both end up in SQL; only accept what they can legitimately be
+        let id = arg(invocation_id, "invocation_id")?;
+        let id = uuid::Uuid::parse_str(id)
+            .map_err(|_| Error::Config(format!("invalid invocation id '{}'", id)))?
+            .to_string();
+        let stream = opt_arg(stream, "stream")?;
+        if let Some(stream) = stream.filter(|s| s.contains('\'')) {
+            return Err(Error::Config(format!("invalid stream '{}'", stream)));
+        }
+
+        let mut content = Vec::new();
+        for output in store.get_outputs_ordered(&id, stream)? {
+            content.extend(store.read_output_content(&output)?);
+        }
+
+        if content.is_empty() {
+            *data = ptr::null_mut();
+            *len = 0;
+        } else {
+            let boxed = content.into_boxed_slice();
+            *len = boxed.len();
+            *data = Box::into_raw(boxed).cast();
+        }
+        Ok(0)
+    })
+}
+
+/// Free a string returned by bird. NULL is ignored.
+///
+/// # Safety
+/// `s` must be NULL or a string returned by bird, not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn bird_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Free a buffer returned by [`bird_read_output`]. NULL is ignored.
+///
+/// # Safety
+/// `data`/`len` must be NULL or exactly as returned by bird, not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn bird_bytes_free(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bird::init::initialize;
+    use bird::InvocationRecord;
+    use tempfile::TempDir;
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(bird_last_error()) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_open_uninitialized_reports_error() {
+        let tmp = TempDir::new().unwrap();
+        let root = c(&tmp.path().display().to_string());
+        let store = unsafe { bird_store_open(root.as_ptr()) };
+        assert!(store.is_null());
+        assert!(last_error().contains("not initialized"), "{}", last_error());
+
+        assert_eq!(unsafe { bird_write_batch(ptr::null(), c("{}").as_ptr()) }, -1);
+        assert!(last_error().contains("store must not be NULL"));
+
+        // Freeing NULL is a no-op
+        unsafe {
+            bird_store_close(ptr::null_mut());
+            bird_string_free(ptr::null_mut());
+            bird_bytes_free(ptr::null_mut(), 0);
+        }
+        assert!(!unsafe { CStr::from_ptr(bird_version()) }.to_bytes().is_empty());
+    }
+
+    #[test]
+    fn test_write_query_read_roundtrip() {
+        let tmp = TempDir::new().unwrap();
+        let config = Config::with_duckdb_mode(tmp.path());
+        initialize(&config).unwrap();
+
+        let root = c(&tmp.path().display().to_string());
+        let store = unsafe { bird_store_open(root.as_ptr()) };
+        assert!(!store.is_null(), "{}", last_error());
+
+        let inv = InvocationRecord::new("ffi", "make test", "/src", 2, "me@host");
+        let id = inv.id.to_string();
+        let save = SpooledSave::new(inv).with_output("stdout", b"FAILED: test_x\n");
+        let json = c(&serde_json::to_string(&save).unwrap());
+        assert_eq!(unsafe { bird_write_batch(store, json.as_ptr()) }, 0, "{}", last_error());
+
+        let rows = unsafe { bird_query_invocations(store, c("%exit<>0").as_ptr(), 10) };
+        assert!(!rows.is_null(), "{}", last_error());
+        let parsed: serde_json::Value =
+            serde_json::from_str(&unsafe { CStr::from_ptr(rows) }.to_string_lossy()).unwrap();
+        assert_eq!(parsed[0]["id"], id);
+        assert_eq!(parsed[0]["exit_code"], 2);
+        unsafe { bird_string_free(rows) };
+
+        let mut data = ptr::null_mut();
+        let mut len = 0;
+        let rc = unsafe { bird_read_output(store, c(&id).as_ptr(), ptr::null(), &mut data, &mut len) };
+        assert_eq!(rc, 0, "{}", last_error());
+        assert_eq!(unsafe { std::slice::from_raw_parts(data, len) }, b"FAILED: test_x\n");
+        unsafe {
+            bird_bytes_free(data, len);
+            bird_store_close(store);
+        }
+    }
+}
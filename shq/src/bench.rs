@@ -0,0 +1,298 @@
+//! Benchmarks: hook latency (`shq hook bench`) and command timings
+//! (`shq bench`, `shq ab`).
+//!
+//! For hooks, the shell side is timed by running the real hook functions
+//! in the shell, with `shq` (and `shq-capture`) stubbed out so only the
+//! shell's own work (and the fork of the background save) is counted. The save itself is timed
+//! separately as a `shq save` process, the way the hook runs it.
+
+use std::time::Duration;
+
+use crate::hooks::Shell;
+
+/// Timings of one stage, one sample per synthetic command.
+#[derive(Debug, Clone)]
+pub struct Samples {
+    pub name: String,
+    samples: Vec<Duration>,
+}
+
+impl Samples {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, d: Duration) {
+        self.samples.push(d);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Per-sample sum with `other` (same commands, in the same order).
+    pub fn plus(&self, name: impl Into<String>, other: &Samples) -> Samples {
+        Samples {
+            name: name.into(),
+            samples: self.samples.iter().zip(&other.samples).map(|(a, b)| *a + *b).collect(),
+        }
+    }
+
+    /// Nearest-rank percentile (`p` in 0..=100).
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+        sorted[rank.clamp(1, sorted.len()) - 1]
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples.iter().max().copied().unwrap_or_default()
+    }
+}
+
+/// Summary of a `shq bench` group's run durations, in milliseconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub runs: usize,
+    pub mean: f64,
+    /// Sample standard deviation (0 for a single run)
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    pub fn from_ms(durations: &[i64]) -> Option<Stats> {
+        if durations.is_empty() {
+            return None;
+        }
+        let n = durations.len() as f64;
+        let mean = durations.iter().sum::<i64>() as f64 / n;
+        let variance = if durations.len() > 1 {
+            durations.iter().map(|d| (*d as f64 - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        Some(Stats {
+            runs: durations.len(),
+            mean,
+            stddev: variance.sqrt(),
+            min: *durations.iter().min()? as f64,
+            max: *durations.iter().max()? as f64,
+        })
+    }
+
+    /// How many times slower `self` is than `baseline`, with its
+    /// uncertainty (propagated from both standard deviations).
+    pub fn ratio_to(&self, baseline: &Stats) -> Option<(f64, f64)> {
+        if self.mean <= 0.0 || baseline.mean <= 0.0 {
+            return None;
+        }
+        let ratio = self.mean / baseline.mean;
+        let error = ratio * ((self.stddev / self.mean).powi(2) + (baseline.stddev / baseline.mean).powi(2)).sqrt();
+        Some((ratio, error))
+    }
+
+    /// Welch's t-test of the means of `self` and `other`: the t statistic
+    /// and its degrees of freedom. `None` with fewer than two runs on either
+    /// side, or no variance at all.
+    pub fn welch_t(&self, other: &Stats) -> Option<(f64, f64)> {
+        if self.runs < 2 || other.runs < 2 {
+            return None;
+        }
+        let a = self.stddev.powi(2) / self.runs as f64;
+        let b = other.stddev.powi(2) / other.runs as f64;
+        if a + b == 0.0 {
+            return None;
+        }
+        let t = (self.mean - other.mean) / (a + b).sqrt();
+        let df = (a + b).powi(2) / (a.powi(2) / (self.runs - 1) as f64 + b.powi(2) / (other.runs - 1) as f64);
+        Some((t, df))
+    }
+}
+
+/// Whether a t statistic with `df` degrees of freedom is significant at
+/// the 5% level (two-sided).
+pub fn significant(t: f64, df: f64) -> bool {
+    // Critical values for df = 1..=30; beyond that the normal 1.96 is close
+    const CRITICAL: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160, 2.145, 2.131,
+        2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+    ];
+    // Round df down, which errs on the side of "not significant"
+    let critical = match df.floor() as usize {
+        0 => return false,
+        n if n <= CRITICAL.len() => CRITICAL[n - 1],
+        _ => 1.96,
+    };
+    t.abs() > critical
+}
+
+/// Shell script that sources the hook at `hook_path` and runs its
+/// pre- and post-command functions `count` times, printing
+/// `<start> <after preexec> <after postexec>` (`$EPOCHREALTIME`) per command.
+pub fn driver_script(shell: Shell, hook_path: &str, count: usize) -> String {
+    let hook_path = crate::wrap::sh_quote(hook_path);
+    match shell {
+        Shell::Zsh => format!(
+            r#"zmodload zsh/datetime
+shq() {{ :; }}
+shq-capture() {{ :; }}
+source {hook_path}
+__shq_project_pwd="$PWD"
+for i in {{1..{count}}}; do
+    t0=$EPOCHREALTIME
+    __shq_preexec "echo bench $i"
+    t1=$EPOCHREALTIME
+    true
+    __shq_precmd
+    t2=$EPOCHREALTIME
+    print -r -- "$t0 $t1 $t2"
+done
+"#
+        ),
+        Shell::Bash => format!(
+            r#"HISTFILE=/dev/null
+set -o history
+shq() {{ :; }}
+shq-capture() {{ :; }}
+source {hook_path}
+__shq_project_pwd="$PWD"
+for ((i = 1; i <= {count}; i++)); do
+    history -s "echo bench $i"
+    t0=$EPOCHREALTIME
+    __shq_ps0_hook
+    t1=$EPOCHREALTIME
+    true
+    __shq_prompt_command
+    t2=$EPOCHREALTIME
+    echo "$t0 $t1 $t2"
+done
+"#
+        ),
+    }
+}
+
+/// Parse the driver's output into (preexec, postexec) timings.
+///
+/// Returns `None` if the shell has no `$EPOCHREALTIME` (bash < 5).
+pub fn parse_driver_output(output: &str) -> Option<(Samples, Samples)> {
+    let mut preexec = Samples::new("preexec");
+    let mut postexec = Samples::new("postexec");
+    for line in output.lines().filter(|l| !l.trim().is_empty()) {
+        let times: Vec<f64> = line
+            .split_whitespace()
+            .map(|t| t.replace(',', ".").parse::<f64>())
+            .collect::<Result<_, _>>()
+            .ok()?;
+        let [t0, t1, t2] = times[..] else { return None };
+        preexec.push(Duration::from_secs_f64((t1 - t0).max(0.0)));
+        postexec.push(Duration::from_secs_f64((t2 - t1).max(0.0)));
+    }
+    (!preexec.is_empty()).then_some((preexec, postexec))
+}
+
+/// First `name` on `$PATH`.
+pub fn find_on_path(name: &str) -> Option<std::path::PathBuf> {
+    std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default())
+        .map(|dir| dir.join(name))
+        .find(|p| p.is_file())
+}
+
+/// Format a duration in milliseconds with sub-millisecond precision.
+pub fn format_ms(d: Duration) -> String {
+    format!("{:.2}ms", d.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let mut s = Samples::new("save");
+        for n in 1..=100 {
+            s.push(ms(n));
+        }
+        assert_eq!(s.percentile(50.0), ms(50));
+        assert_eq!(s.percentile(99.0), ms(99));
+        assert_eq!(s.percentile(100.0), ms(100));
+        assert_eq!(s.max(), ms(100));
+
+        let one = s.plus("total", &s);
+        assert_eq!(one.percentile(50.0), ms(100));
+        assert_eq!(Samples::new("empty").percentile(99.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = Stats::from_ms(&[100, 110, 120, 130]).unwrap();
+        assert_eq!(stats.runs, 4);
+        assert_eq!(stats.mean, 115.0);
+        assert!((stats.stddev - 12.91).abs() < 0.01);
+        assert_eq!((stats.min, stats.max), (100.0, 130.0));
+        assert_eq!(Stats::from_ms(&[50]).unwrap().stddev, 0.0);
+        assert!(Stats::from_ms(&[]).is_none());
+
+        let baseline = Stats::from_ms(&[200, 200]).unwrap();
+        let (ratio, error) = Stats::from_ms(&[100, 100]).unwrap().ratio_to(&baseline).unwrap();
+        assert_eq!((ratio, error), (0.5, 0.0));
+    }
+
+    #[test]
+    fn test_welch_t() {
+        let a = Stats::from_ms(&[100, 102, 98, 101, 99]).unwrap();
+        let b = Stats::from_ms(&[120, 118, 122, 119, 121]).unwrap();
+        let (t, df) = a.welch_t(&b).unwrap();
+        assert!((t + 20.0).abs() < 0.01, "t = {}", t);
+        assert!((df - 8.0).abs() < 0.01, "df = {}", df);
+        assert!(significant(t, df));
+
+        let noisy = Stats::from_ms(&[80, 130, 95, 125, 110]).unwrap();
+        let (t, df) = a.welch_t(&noisy).unwrap();
+        assert!(!significant(t, df));
+
+        assert!(a.welch_t(&Stats::from_ms(&[100]).unwrap()).is_none());
+        assert!(Stats::from_ms(&[5, 5]).unwrap().welch_t(&Stats::from_ms(&[5, 5]).unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_parse_driver_output() {
+        let (pre, post) = parse_driver_output("100.5 101.0 104.0\n200,25 200,5 201,0\n").unwrap();
+        assert_eq!(pre.samples.len(), 2);
+        assert_eq!(pre.max(), ms(500));
+        assert_eq!(post.max(), ms(3000));
+        // bash < 5: $EPOCHREALTIME expands to nothing
+        assert!(parse_driver_output("  \n").is_none());
+        assert!(parse_driver_output("1.0 2.0\n").is_none());
+    }
+
+    #[test]
+    fn test_bash_driver_runs_hook() {
+        let Some(bash) = find_on_path("bash") else { return };
+        let tmp = tempfile::TempDir::new().unwrap();
+        let hook = tmp.path().join("hook.sh");
+        std::fs::write(&hook, crate::hooks::generate(Shell::Bash, crate::hooks::Mode::Active, false)).unwrap();
+
+        let out = std::process::Command::new(bash)
+            .args(["-c", &driver_script(Shell::Bash, &hook.display().to_string(), 3)])
+            .env("BIRD_ROOT", tmp.path())
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        if let Some((pre, _)) = parse_driver_output(&stdout) {
+            assert_eq!(pre.samples.len(), 3);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! Ctrl-C handling for long-running store operations.
+//!
+//! The first Ctrl-C cancels the store's [`CancelToken`], so compaction,
+//! sync and extraction stop at the next safe boundary instead of being
+//! killed mid-write. The handler then restores the default action, so a
+//! second Ctrl-C exits immediately.
+
+use std::sync::OnceLock;
+
+use bird::CancelToken;
+
+static TOKEN: OnceLock<CancelToken> = OnceLock::new();
+
+const NOTICE: &[u8] = b"\nCancelling after the current step (Ctrl-C again to force)...\n";
+
+extern "C" fn on_sigint(_signal: libc::c_int) {
+    if let Some(token) = TOKEN.get() {
+        token.cancel();
+    }
+    // Only async-signal-safe calls from here on
+    unsafe {
+        libc::write(libc::STDERR_FILENO, NOTICE.as_ptr() as *const libc::c_void, NOTICE.len());
+        libc::signal(libc::SIGINT, libc::SIG_DFL);
+    }
+}
+
+/// Token cancelled by Ctrl-C, installing the SIGINT handler on first use.
+pub fn token() -> CancelToken {
+    let mut installed = false;
+    let token = TOKEN.get_or_init(|| {
+        installed = true;
+        CancelToken::new()
+    });
+    if installed {
+        unsafe {
+            libc::signal(libc::SIGINT, on_sigint as *const () as libc::sighandler_t);
+        }
+    }
+    token.clone()
+}
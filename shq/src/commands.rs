@@ -3,21 +3,29 @@
 use std::io::{self, Read, Write};
 use std::os::fd::AsRawFd;
 use std::process::Command;
-use std::time::Instant;
+use std::time::{Instant, SystemTime};
 
 use std::fs::File;
 
+use bird::profile::Phase;
 use bird::{
-    init, parse_query, Buffer, CompactOptions, Config, ContextMetadata, EventFilters, InvocationBatch,
-    InvocationRecord, Query, SessionRecord, StorageMode, Store, BIRD_INVOCATION_UUID_VAR,
+    init, parse_query, parse_query_strict, Buffer, CompactOptions, Config, ContextMetadata, EventFilters, GroupField,
+    InvocationBatch,
+    InvocationRecord, Query, SessionRecord, StorageMode, Store, WriteCoalescer, BIRD_INVOCATION_UUID_VAR,
     BIRD_PARENT_CLIENT_VAR,
 };
 use pty_process::blocking::{Command as PtyCommand, open as pty_open};
 
-/// Streaming output writer that writes to both terminal and a temp file.
+use crate::expect::Expectations;
+use crate::sinks::Sinks;
+use crate::theme::{BOLD, DIM, GREEN, MAGENTA, RED, YELLOW};
+
+/// Streaming output file for `shq show --follow` when outputs are captured
+/// per stream (`--no-pty`).
 ///
-/// This enables `shq show --follow` to tail output while a command is running.
-/// The temp file lives at `~/.bird/running/<invocation_id>.out` during execution.
+/// The file lives at `~/.bird/running/<invocation_id>.out` during execution.
+/// With a PTY the captured `combined` output is written there directly (see
+/// [`Store::begin_output`]).
 struct StreamingOutput {
     file: File,
     path: std::path::PathBuf,
@@ -43,18 +51,10 @@ impl StreamingOutput {
         Ok(())
     }
 
-    /// Read all content from the streaming file and delete it.
-    fn finish(self) -> io::Result<Vec<u8>> {
+    /// Delete the streaming file.
+    fn finish(self) {
         drop(self.file); // Close the file handle
-        let content = std::fs::read(&self.path)?;
-        let _ = std::fs::remove_file(&self.path); // Clean up
-        Ok(content)
-    }
-
-    /// Get the path for external access (e.g., for --follow).
-    #[allow(dead_code)]
-    fn path(&self) -> &std::path::Path {
-        &self.path
+        let _ = std::fs::remove_file(&self.path);
     }
 }
 
@@ -82,16 +82,27 @@ fn invoker_pid() -> u32 {
     std::os::unix::process::parent_id()
 }
 
-/// OSC escape sequence that commands can emit to opt out of recording.
-/// Format: ESC ] shq;nosave BEL  (or ESC ] shq;nosave ESC \)
-const NOSAVE_OSC: &[u8] = b"\x1b]shq;nosave\x07";
-const NOSAVE_OSC_ST: &[u8] = b"\x1b]shq;nosave\x1b\\";
+/// Looks for the nosave marker in output read in chunks, including markers
+/// split across chunks.
+#[derive(Default)]
+struct NosaveScanner {
+    carry: Vec<u8>,
+    found: bool,
+}
 
-/// Check if output contains the nosave marker.
-/// Commands can emit this OSC escape sequence to opt out of being recorded.
-fn contains_nosave_marker(data: &[u8]) -> bool {
-    data.windows(NOSAVE_OSC.len()).any(|w| w == NOSAVE_OSC)
-        || data.windows(NOSAVE_OSC_ST.len()).any(|w| w == NOSAVE_OSC_ST)
+impl NosaveScanner {
+    fn scan(&mut self, chunk: &[u8]) {
+        if self.found {
+            return;
+        }
+        self.carry.extend_from_slice(chunk);
+        self.found = bird::privacy::contains_nosave_marker(&self.carry);
+        // Keep just enough to complete a marker started in this chunk
+        let keep = bird::privacy::NOSAVE_OSC_ST.len() - 1;
+        if self.carry.len() > keep {
+            self.carry.drain(..self.carry.len() - keep);
+        }
+    }
 }
 
 /// Run a command and capture it to BIRD.
@@ -112,7 +123,15 @@ fn contains_nosave_marker(data: &[u8]) -> bool {
 /// `format_override`: Override format detection for event extraction.
 /// `auto_compact`: If true, spawn background compaction after saving.
 /// `no_pty`: If true, use pipes instead of PTY for separate stdout/stderr capture.
-pub fn run(shell_cmd: Option<&str>, cmd_args: &[String], tag: Option<&str>, extract_override: Option<bool>, format_override: Option<&str>, auto_compact: bool, no_pty: bool, force_capture: bool) -> bird::Result<()> {
+/// `sinks`: Extra destinations (`--tee`) that also receive the output.
+#[allow(clippy::too_many_arguments)]
+pub fn run(shell_cmd: Option<&str>, cmd_args: &[String], tag: Option<&str>, dims: &[String], attach: &[String], extract_override: Option<bool>, format_override: Option<&str>, auto_compact: bool, no_pty: bool, force_capture: bool, expect: &Expectations, mut sinks: Sinks) -> bird::Result<()> {
+    // Defaults for the current project, exported by the shell hook (`shq project env`)
+    let project = bird::ProjectConfig::from_env();
+    let dims = parse_dims(&project, dims)?;
+    let extract_override = extract_override.or(project.extract);
+    let format_override = format_override.or(project.extract_format.as_deref());
+
     // Determine command string and build PTY command
     let (cmd_str, shell, args): (String, String, Vec<String>) = match shell_cmd {
         Some(cmd) => {
@@ -144,12 +163,20 @@ pub fn run(shell_cmd: Option<&str>, cmd_args: &[String], tag: Option<&str>, extr
 
     let invocation_id = uuid::Uuid::now_v7();
 
+    // The code state the command starts from (its VCS context is collected
+    // once it exits)
+    let snapshot = if config.snapshot_worktree {
+        bird::worktree_snapshot(std::path::Path::new(&cwd))
+    } else {
+        None
+    };
+
     // Branch based on PTY mode
     if no_pty {
         return run_no_pty(
             &cmd_str, &shell, &args, &cwd, invocation_id,
-            tag, extract_override, format_override, auto_compact,
-            config, store, force_capture,
+            tag, &dims, attach, snapshot, extract_override, format_override, auto_compact,
+            config, store, force_capture, expect, sinks,
         );
     }
 
@@ -174,6 +201,7 @@ pub fn run(shell_cmd: Option<&str>, cmd_args: &[String], tag: Option<&str>, extr
         .env(BIRD_PARENT_CLIENT_VAR, "shq");
 
     // Spawn process in PTY - it becomes session leader with PTY as controlling terminal
+    let started = SystemTime::now();
     let start = Instant::now();
     let mut child = cmd.spawn(pts)
         .map_err(|e| bird::Error::Io(io::Error::other(e)))?;
@@ -225,11 +253,11 @@ pub fn run(shell_cmd: Option<&str>, cmd_args: &[String], tag: Option<&str>, extr
         }
     });
 
-    // Create streaming output file for real-time tailing via `shq show --follow`
-    let mut streaming = StreamingOutput::new(&config, invocation_id)
-        .map_err(|e| bird::Error::Io(e))?;
+    // Capture straight to the running file, which `shq show --follow` tails
+    let mut output = store.begin_output(invocation_id, "combined")?;
+    let mut nosave = NosaveScanner::default();
 
-    // Read output from PTY and pass through to our stdout while streaming to file
+    // Read output from PTY and pass through to our stdout while capturing it
     let mut buf = [0u8; 4096];
 
     // Set PTY to non-blocking for reading
@@ -243,7 +271,9 @@ pub fn run(shell_cmd: Option<&str>, cmd_args: &[String], tag: Option<&str>, extr
                 set_nonblocking(pty.as_raw_fd(), false);
                 while let Ok(n) = pty.read(&mut buf) {
                     if n == 0 { break; }
-                    let _ = streaming.write(&buf[..n]);
+                    nosave.scan(&buf[..n]);
+                    let _ = output.write_all(&buf[..n]);
+                    sinks.write(&buf[..n]);
                     let _ = io::stdout().write_all(&buf[..n]);
                     let _ = io::stdout().flush();
                 }
@@ -257,7 +287,9 @@ pub fn run(shell_cmd: Option<&str>, cmd_args: &[String], tag: Option<&str>, extr
                         break;
                     }
                     Ok(n) => {
-                        let _ = streaming.write(&buf[..n]);
+                        nosave.scan(&buf[..n]);
+                        let _ = output.write_all(&buf[..n]);
+                        sinks.write(&buf[..n]);
                         let _ = io::stdout().write_all(&buf[..n]);
                         let _ = io::stdout().flush();
                     }
@@ -289,15 +321,10 @@ pub fn run(shell_cmd: Option<&str>, cmd_args: &[String], tag: Option<&str>, extr
     let duration_ms = start.elapsed().as_millis() as i64;
     let exit_code = status.code().unwrap_or(-1);
 
-    // Finalize streaming output - read content and clean up temp file
-    let output_buffer = streaming.finish().unwrap_or_default();
-
     // Check for nosave marker
-    if contains_nosave_marker(&output_buffer) {
-        if !status.success() {
-            std::process::exit(exit_code);
-        }
-        return Ok(());
+    if nosave.found {
+        output.discard();
+        return finish_run(&store, invocation_id, exit_code, duration_ms, expect);
     }
 
     // Create and save records
@@ -311,7 +338,11 @@ pub fn run(shell_cmd: Option<&str>, cmd_args: &[String], tag: Option<&str>, extr
     );
 
     // Collect context metadata (VCS, CI)
-    let context = ContextMetadata::collect(Some(std::path::Path::new(&cwd)));
+    let mut context = ContextMetadata::collect(Some(std::path::Path::new(&cwd)))
+        .with_worktree_snapshot(snapshot);
+    if config.privacy.record_env {
+        context = context.with_env();
+    }
 
     // Privacy: redact recognizable secret values from the stored command
     // line (the command itself already ran with its real arguments).
@@ -330,19 +361,24 @@ pub fn run(shell_cmd: Option<&str>, cmd_args: &[String], tag: Option<&str>, extr
         &config.client_id,
     )
     .with_duration(duration_ms)
-    .with_metadata(context.into_map());
+    .with_metadata(context.into_map())
+    .with_dims(&dims);
 
     if let Some(t) = tag {
         record = record.with_tag(t);
     }
 
     let inv_id = record.id;
+    let cmd_hint = record.executable.clone();
     let mut batch = InvocationBatch::new(record).with_session(session);
 
     // PTY merges stdout/stderr into a single stream - store as "combined"
-    if !output_buffer.is_empty() {
-        batch = batch.with_output("combined", output_buffer);
+    if output.is_empty() {
+        output.discard();
+    } else {
+        batch = batch.with_stored_output(store.finish_output(output, cmd_hint.as_deref())?);
     }
+    let batch = attach_artifacts(&store, &config, batch, inv_id, &cwd, attach, started, cmd_hint.as_deref());
 
     store.write_batch(&batch)?;
 
@@ -366,16 +402,89 @@ pub fn run(shell_cmd: Option<&str>, cmd_args: &[String], tag: Option<&str>, extr
             .spawn();
     }
 
-    if !status.success() {
-        std::process::exit(exit_code);
+    finish_run(&store, inv_id, exit_code, duration_ms, expect)
+}
+
+/// Exit as the command did, or, with `--expect`, by whether the
+/// expectations hold for the captured run (reporting those that don't).
+fn finish_run(
+    store: &Store,
+    invocation_id: uuid::Uuid,
+    exit_code: i32,
+    duration_ms: i64,
+    expect: &Expectations,
+) -> bird::Result<()> {
+    if expect.is_empty() {
+        if exit_code != 0 {
+            std::process::exit(exit_code);
+        }
+        return Ok(());
     }
 
-    Ok(())
+    let event_count = |severity: Option<&str>| {
+        store.event_count(&EventFilters {
+            invocation_id: Some(invocation_id.to_string()),
+            severity: severity.map(str::to_string),
+            ..Default::default()
+        })
+    };
+    let outcome = crate::expect::Outcome { exit_code, duration_ms, event_count: &event_count };
+    let failures = expect.check(&outcome)?;
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    for (expectation, actual) in &failures {
+        eprintln!("shq: expected {}, but {}", expectation, actual);
+    }
+    if expect.notify {
+        let failed: Vec<String> = failures.iter().map(|(e, _)| e.to_string()).collect();
+        crate::expect::notify("shq: expectation failed", &failed.join(", "));
+    }
+    std::process::exit(1);
+}
+
+/// Snapshot the files given with `--attach` (paths or globs), and those
+/// matching `[artifacts] capture` written during the run, into the batch.
+/// Files that can't be captured are warned about, not fatal.
+#[allow(clippy::too_many_arguments)]
+fn attach_artifacts(
+    store: &Store,
+    config: &Config,
+    mut batch: InvocationBatch,
+    invocation_id: uuid::Uuid,
+    cwd: &str,
+    attach: &[String],
+    started: SystemTime,
+    cmd_hint: Option<&str>,
+) -> InvocationBatch {
+    let cwd = std::path::Path::new(cwd);
+    let mut paths = Vec::new();
+    for pattern in attach {
+        let found = bird::find_artifacts(cwd, std::slice::from_ref(pattern), None);
+        if found.is_empty() {
+            eprintln!("shq: --attach {}: no matching files", pattern);
+        }
+        paths.extend(found);
+    }
+    paths.extend(bird::find_artifacts(cwd, &config.artifacts.capture, Some(started)));
+    paths.sort();
+    paths.dedup();
+
+    for path in paths {
+        let name = bird::artifact_name(cwd, &path);
+        match store.attach_artifact(invocation_id, &name, &path, cmd_hint) {
+            Ok(output) => batch = batch.with_stored_output(output),
+            Err(e) => eprintln!("shq: failed to attach {}: {}", name, e),
+        }
+    }
+    batch
 }
 
 /// Run a command without PTY, capturing stdout/stderr separately via pipes.
 ///
 /// This mode loses colors and interactivity but gains separate stream capture.
+#[allow(clippy::too_many_arguments)]
 fn run_no_pty(
     cmd_str: &str,
     shell: &str,
@@ -383,23 +492,37 @@ fn run_no_pty(
     cwd: &str,
     invocation_id: uuid::Uuid,
     tag: Option<&str>,
+    dims: &[(String, String)],
+    attach: &[String],
+    snapshot: Option<String>,
     extract_override: Option<bool>,
     format_override: Option<&str>,
     auto_compact: bool,
     config: Config,
     store: Store,
     force_capture: bool,
+    expect: &Expectations,
+    sinks: Sinks,
 ) -> bird::Result<()> {
     use std::io::{BufRead, BufReader};
-    use std::sync::{mpsc, Arc, Mutex};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
     use std::thread;
 
     // Create streaming output file for real-time tailing via `shq show --follow`
     let streaming = StreamingOutput::new(&config, invocation_id)
-        .map_err(|e| bird::Error::Io(e))?;
+        .map_err(bird::Error::Io)?;
     let streaming = Arc::new(Mutex::new(streaming));
+    // Both streams go to the same sinks, interleaved by line
+    let sinks = Arc::new(Mutex::new(sinks));
+
+    // Each stream is captured to its own file as it arrives
+    let stdout_output = Arc::new(Mutex::new(store.begin_output(invocation_id, "stdout")?));
+    let stderr_output = Arc::new(Mutex::new(store.begin_output(invocation_id, "stderr")?));
+    let nosave = Arc::new(AtomicBool::new(false));
 
     // Build command with piped stdout/stderr
+    let started = SystemTime::now();
     let mut child = Command::new(shell)
         .args(args)
         .env(BIRD_INVOCATION_UUID_VAR, invocation_id.to_string())
@@ -407,7 +530,7 @@ fn run_no_pty(
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .spawn()
-        .map_err(|e| bird::Error::Io(e))?;
+        .map_err(bird::Error::Io)?;
 
     let start = Instant::now();
 
@@ -415,29 +538,37 @@ fn run_no_pty(
     let stdout = child.stdout.take().expect("stdout was piped");
     let stderr = child.stderr.take().expect("stderr was piped");
 
-    // Buffers to collect output
-    let (tx_stdout, rx) = mpsc::channel::<(bool, Vec<u8>)>(); // (is_stderr, data)
-    let tx_stderr = tx_stdout.clone();
-
-    // Clone streaming for threads
+    // Clone shared state for threads
     let streaming_stdout = Arc::clone(&streaming);
     let streaming_stderr = Arc::clone(&streaming);
+    let capture_stdout = Arc::clone(&stdout_output);
+    let capture_stderr = Arc::clone(&stderr_output);
+    let nosave_stdout = Arc::clone(&nosave);
+    let nosave_stderr = Arc::clone(&nosave);
+    let sinks_stdout = Arc::clone(&sinks);
+    let sinks_stderr = Arc::clone(&sinks);
 
     // Spawn thread to read stdout
     let stdout_handle = thread::spawn(move || {
         let reader = BufReader::new(stdout);
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                // Echo to real stdout
-                println!("{}", line);
-                // Send to collector (with newline)
-                let mut data = line.into_bytes();
-                data.push(b'\n');
-                // Write to streaming file for --follow
-                if let Ok(mut s) = streaming_stdout.lock() {
-                    let _ = s.write(&data);
-                }
-                let _ = tx_stdout.send((false, data));
+        for line in reader.lines().map_while(Result::ok) {
+            // Echo to real stdout
+            println!("{}", line);
+            // Capture (with newline)
+            let mut data = line.into_bytes();
+            data.push(b'\n');
+            if bird::privacy::contains_nosave_marker(&data) {
+                nosave_stdout.store(true, Ordering::Relaxed);
+            }
+            // Write to streaming file for --follow
+            if let Ok(mut s) = streaming_stdout.lock() {
+                let _ = s.write(&data);
+            }
+            if let Ok(mut sinks) = sinks_stdout.lock() {
+                sinks.write(&data);
+            }
+            if let Ok(mut output) = capture_stdout.lock() {
+                let _ = output.write_all(&data);
             }
         }
     });
@@ -445,24 +576,30 @@ fn run_no_pty(
     // Spawn thread to read stderr
     let stderr_handle = thread::spawn(move || {
         let reader = BufReader::new(stderr);
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                // Echo to real stderr
-                eprintln!("{}", line);
-                // Send to collector (with newline)
-                let mut data = line.into_bytes();
-                data.push(b'\n');
-                // Write to streaming file for --follow
-                if let Ok(mut s) = streaming_stderr.lock() {
-                    let _ = s.write(&data);
-                }
-                let _ = tx_stderr.send((true, data));
+        for line in reader.lines().map_while(Result::ok) {
+            // Echo to real stderr
+            eprintln!("{}", line);
+            // Capture (with newline)
+            let mut data = line.into_bytes();
+            data.push(b'\n');
+            if bird::privacy::contains_nosave_marker(&data) {
+                nosave_stderr.store(true, Ordering::Relaxed);
+            }
+            // Write to streaming file for --follow
+            if let Ok(mut s) = streaming_stderr.lock() {
+                let _ = s.write(&data);
+            }
+            if let Ok(mut sinks) = sinks_stderr.lock() {
+                sinks.write(&data);
+            }
+            if let Ok(mut output) = capture_stderr.lock() {
+                let _ = output.write_all(&data);
             }
         }
     });
 
     // Wait for child to exit
-    let status = child.wait().map_err(|e| bird::Error::Io(e))?;
+    let status = child.wait().map_err(bird::Error::Io)?;
     let duration_ms = start.elapsed().as_millis() as i64;
     let exit_code = status.code().unwrap_or(-1);
 
@@ -470,29 +607,27 @@ fn run_no_pty(
     let _ = stdout_handle.join();
     let _ = stderr_handle.join();
 
-    // Clean up streaming file (we keep separate stdout/stderr in buffers)
+    // Clean up streaming file (the streams were captured separately)
     if let Ok(streaming) = Arc::try_unwrap(streaming) {
-        let _ = streaming.into_inner().map(|s| s.finish());
-    }
-
-    // Collect all output
-    let mut stdout_buffer = Vec::new();
-    let mut stderr_buffer = Vec::new();
-    while let Ok((is_stderr, data)) = rx.try_recv() {
-        if is_stderr {
-            stderr_buffer.extend(data);
-        } else {
-            stdout_buffer.extend(data);
+        if let Ok(streaming) = streaming.into_inner() {
+            streaming.finish();
         }
     }
 
-    // Check for nosave marker in combined output
-    let combined = [&stdout_buffer[..], &stderr_buffer[..]].concat();
-    if contains_nosave_marker(&combined) {
-        if !status.success() {
-            std::process::exit(exit_code);
+    // The reader threads are done, so these are the only references left
+    let outputs = [stdout_output, stderr_output].map(|output| {
+        Arc::try_unwrap(output)
+            .ok()
+            .and_then(|output| output.into_inner().ok())
+            .expect("reader threads have exited")
+    });
+
+    // Check for nosave marker in either stream
+    if nosave.load(Ordering::Relaxed) {
+        for output in outputs {
+            output.discard();
         }
-        return Ok(());
+        return finish_run(&store, invocation_id, exit_code, duration_ms, expect);
     }
 
     // Create and save records
@@ -506,7 +641,11 @@ fn run_no_pty(
     );
 
     // Collect context metadata (VCS, CI)
-    let context = ContextMetadata::collect(Some(std::path::Path::new(cwd)));
+    let mut context = ContextMetadata::collect(Some(std::path::Path::new(cwd)))
+        .with_worktree_snapshot(snapshot);
+    if config.privacy.record_env {
+        context = context.with_env();
+    }
 
     // Privacy: redact recognizable secret values from the stored command line.
     let stored_cmd = if !force_capture && config.privacy.redact_commands {
@@ -524,22 +663,26 @@ fn run_no_pty(
         &config.client_id,
     )
     .with_duration(duration_ms)
-    .with_metadata(context.into_map());
+    .with_metadata(context.into_map())
+    .with_dims(dims);
 
     if let Some(t) = tag {
         record = record.with_tag(t);
     }
 
     let inv_id = record.id;
+    let cmd_hint = record.executable.clone();
     let mut batch = InvocationBatch::new(record).with_session(session);
 
     // Store stdout and stderr as separate streams
-    if !stdout_buffer.is_empty() {
-        batch = batch.with_output("stdout", stdout_buffer);
-    }
-    if !stderr_buffer.is_empty() {
-        batch = batch.with_output("stderr", stderr_buffer);
+    for output in outputs {
+        if output.is_empty() {
+            output.discard();
+        } else {
+            batch = batch.with_stored_output(store.finish_output(output, cmd_hint.as_deref())?);
+        }
     }
+    let batch = attach_artifacts(&store, &config, batch, inv_id, cwd, attach, started, cmd_hint.as_deref());
 
     store.write_batch(&batch)?;
 
@@ -563,11 +706,7 @@ fn run_no_pty(
             .spawn();
     }
 
-    if !status.success() {
-        std::process::exit(exit_code);
-    }
-
-    Ok(())
+    finish_run(&store, inv_id, exit_code, duration_ms, expect)
 }
 
 /// Get terminal size (rows, cols)
@@ -644,6 +783,17 @@ fn disable_pty_echo(fd: i32) {
     }
 }
 
+/// Parse `--dim KEY=VALUE` arguments.
+/// Parse `--dim` values, after the project's default dimensions so explicit
+/// values win.
+fn parse_dims(project: &bird::ProjectConfig, dims: &[String]) -> bird::Result<Vec<(String, String)>> {
+    let mut parsed: Vec<(String, String)> = project.dims.clone().into_iter().collect();
+    for dim in dims {
+        parsed.push(bird::parse_dim(dim)?);
+    }
+    Ok(parsed)
+}
+
 /// Save output from stdin or file with an explicit command.
 #[allow(clippy::too_many_arguments)]
 pub fn save(
@@ -661,12 +811,16 @@ pub fn save(
     extract: bool,
     compact: bool,
     tag: Option<&str>,
+    dims: &[String],
     quiet: bool,
     to_buffer: bool,
+    async_save: bool,
     force_capture: bool,
 ) -> bird::Result<()> {
     use std::process::Command;
 
+    let dims = parse_dims(&bird::ProjectConfig::from_env(), dims)?;
+
     // Read content first so we can check for nosave marker
     let (mut stdout_content, mut stderr_content, mut single_content) = if stdout_file.is_some() || stderr_file.is_some() {
         let stdout = stdout_file.map(std::fs::read).transpose()?;
@@ -685,9 +839,9 @@ pub fn save(
     };
 
     // Check for nosave marker - command opted out of recording
-    let has_nosave = stdout_content.as_ref().is_some_and(|c| contains_nosave_marker(c))
-        || stderr_content.as_ref().is_some_and(|c| contains_nosave_marker(c))
-        || single_content.as_ref().is_some_and(|c| contains_nosave_marker(c));
+    let has_nosave = stdout_content.as_ref().is_some_and(|c| bird::privacy::contains_nosave_marker(c))
+        || stderr_content.as_ref().is_some_and(|c| bird::privacy::contains_nosave_marker(c))
+        || single_content.as_ref().is_some_and(|c| bird::privacy::contains_nosave_marker(c));
 
     if has_nosave {
         return Ok(());
@@ -695,13 +849,26 @@ pub fn save(
 
     let config = Config::load()?;
 
+    // Privacy: `shq off` pauses capture everywhere. Hooks check this too,
+    // but a save already in flight (or from another tool) must respect it.
+    if !force_capture && bird::privacy::is_paused(&config) {
+        return Ok(());
+    }
+
     // Privacy: exclusion patterns apply to EVERY capture path, not just the
     // opt-in buffer. `shq -X` (force-capture) bypasses this deliberately.
     if !force_capture && bird::privacy::should_exclude(&config, command) {
         return Ok(());
     }
 
-    let store = Store::open(config.clone())?;
+    // Wrapped tools (`shq wrap`) are captured, with output, by their shim;
+    // don't also record the bare command from the shell hook.
+    if explicit_invoker_type == "shell" {
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        if crate::wrap::is_wrapped(&config.shims_dir(), &path, command) {
+            return Ok(());
+        }
+    }
 
     // Get current working directory
     let cwd = std::env::current_dir()
@@ -790,7 +957,10 @@ pub fn save(
     );
 
     // Collect context metadata (VCS, CI)
-    let context = ContextMetadata::collect(Some(std::path::Path::new(&cwd)));
+    let mut context = ContextMetadata::collect(Some(std::path::Path::new(&cwd)));
+    if config.privacy.record_env {
+        context = context.with_env();
+    }
 
     let mut inv_record = InvocationRecord::new(
         &sid,
@@ -799,7 +969,8 @@ pub fn save(
         exit_code,
         &config.client_id,
     )
-    .with_metadata(context.into_map());
+    .with_metadata(context.into_map())
+    .with_dims(&dims);
 
     if let Some(ms) = duration_ms {
         inv_record = inv_record.with_duration(ms);
@@ -808,21 +979,33 @@ pub fn save(
         inv_record = inv_record.with_tag(t);
     }
     let inv_id = inv_record.id;
+    let outputs = [("stdout", stdout_content), ("stderr", stderr_content), (stream, single_content)];
+
+    // Fire-and-forget: leave the record for the spool flusher
+    if async_save {
+        let mut save = bird::spool::SpooledSave::new(inv_record).with_session(session);
+        for (name, content) in outputs {
+            if let Some(content) = content {
+                save = save.with_output(name, &content);
+            }
+        }
+        save.extract = extract;
+        save.compact = compact;
+        bird::spool::write(&config, &save)?;
+        bird::spool::start_flusher(&config, &std::env::current_exe().unwrap_or_else(|_| "shq".into()))?;
+        return Ok(());
+    }
 
     // Build batch with all related records
     let mut batch = InvocationBatch::new(inv_record).with_session(session);
-
-    if let Some(content) = stdout_content {
-        batch = batch.with_output("stdout", content);
-    }
-    if let Some(content) = stderr_content {
-        batch = batch.with_output("stderr", content);
-    }
-    if let Some(content) = single_content {
-        batch = batch.with_output(stream, content);
+    for (name, content) in outputs {
+        if let Some(content) = content {
+            batch = batch.with_output(name, content);
+        }
     }
 
     // Write everything atomically
+    let store = Store::open(config.clone())?;
     store.write_batch(&batch)?;
 
     // Extract events if requested (uses config default or explicit flag)
@@ -848,6 +1031,114 @@ pub fn save(
     Ok(())
 }
 
+/// Ingest a `script` typescript or `tmux pipe-pane` log: one invocation per
+/// command its OSC 133 markers delimit, or one for the whole log.
+///
+/// Commands are dated from the log's header and timing file where it has
+/// them (otherwise the file's modification time), and get stable ids, so
+/// ingesting a log again only adds what's new. `command` names a capture
+/// whose command isn't known (an unmarked log not made with `script -c`);
+/// `tag` goes on the last command.
+#[allow(clippy::too_many_arguments)]
+pub fn save_typescript(
+    path: &std::path::Path,
+    timing: Option<&std::path::Path>,
+    command: Option<&str>,
+    explicit_session_id: Option<&str>,
+    extract: bool,
+    tag: Option<&str>,
+    dims: &[String],
+    quiet: bool,
+    force_capture: bool,
+) -> bird::Result<()> {
+    let dims = parse_dims(&bird::ProjectConfig::from_env(), dims)?;
+    let typescript = bird::typescript::Typescript::read(path, timing)?;
+
+    let config = Config::load()?;
+    if !force_capture && bird::privacy::is_paused(&config) {
+        return Ok(());
+    }
+    let store = Store::open(config.clone())?;
+
+    let modified: chrono::DateTime<chrono::Utc> = std::fs::metadata(path)?.modified()?.into();
+    let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| "typescript".to_string());
+    let sid = explicit_session_id.map(str::to_string).unwrap_or_else(|| format!("typescript-{}", name));
+    let cwd = std::env::current_dir()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| ".".to_string());
+
+    let captures = typescript.captures();
+    let (mut saved, mut skipped) = (0, 0);
+    let mut ids = Vec::new();
+    // A log can hold hundreds of commands; write them in groups
+    let mut coalescer = WriteCoalescer::new(&store);
+    for (index, capture) in captures.iter().enumerate() {
+        let Some(cmd) = capture.command.as_deref().or(command) else {
+            return Err(bird::Error::Config(format!(
+                "{} doesn't say what command it recorded; name it with -c",
+                path.display()
+            )));
+        };
+        let output = &typescript.data[capture.output.clone()];
+        if bird::privacy::contains_nosave_marker(output)
+            || (!force_capture && bird::privacy::should_exclude(&config, cmd))
+        {
+            skipped += 1;
+            continue;
+        }
+
+        let started_at = capture.started_at.unwrap_or(modified);
+        let id = typescript.capture_id(index, capture, started_at);
+        if store.find_by_short_id(&id.to_string())?.is_some() {
+            skipped += 1;
+            continue;
+        }
+
+        let stored_cmd = if !force_capture && config.privacy.redact_commands {
+            bird::privacy::redact_command(cmd)
+        } else {
+            cmd.to_string()
+        };
+        // -1 like an interrupted run when the log doesn't say
+        let exit_code = capture.exit_code.unwrap_or(-1);
+        let mut record = InvocationRecord::with_id(id, &sid, &stored_cmd, &cwd, exit_code, &config.client_id)
+            .with_dims(&dims);
+        record.timestamp = started_at;
+        if let Some(ms) = capture.duration_ms {
+            record = record.with_duration(ms);
+        }
+        if index + 1 == captures.len() {
+            if let Some(t) = tag {
+                record = record.with_tag(t);
+            }
+        }
+
+        let session = SessionRecord::new(&sid, &config.client_id, "script", invoker_pid(), "typescript");
+        let mut batch = InvocationBatch::new(record).with_session(session);
+        if !output.is_empty() {
+            batch = batch.with_output("combined", output.to_vec());
+        }
+        coalescer.push(batch)?;
+        ids.push(id);
+        saved += 1;
+    }
+    coalescer.flush()?;
+    if let Some((_, e)) = coalescer.take_failed().into_iter().next() {
+        return Err(e);
+    }
+
+    if extract || config.auto_extract {
+        for id in &ids {
+            store.extract_events(&id.to_string(), None)?;
+        }
+    }
+    if !quiet {
+        let note = if skipped > 0 { format!(" ({} already saved or excluded)", skipped) } else { String::new() };
+        eprintln!("shq: saved {} of {} commands from {}{}", saved, captures.len(), path.display(), note);
+    }
+    Ok(())
+}
+
 /// Follow output from a running command in real-time (like tail -f).
 ///
 /// Looks for the streaming output file at `~/.bird/running/<invocation_id>.out`
@@ -913,6 +1204,8 @@ pub struct OutputOptions {
     pub head: Option<usize>,
     pub tail: Option<usize>,
     pub follow: bool,
+    /// Only this part (sequence number) of each stream.
+    pub part: Option<i32>,
 }
 
 /// Show captured output from invocation(s).
@@ -924,7 +1217,7 @@ pub fn output(query_str: &str, stream_filter: Option<&str>, opts: &OutputOptions
     let store = Store::open(config.clone())?;
 
     // Parse query
-    let query = parse_query(query_str);
+    let query = parse_query_strict(query_str)?;
 
     // Normalize stream filter aliases
     let (db_filter, combine_to_stdout) = match stream_filter {
@@ -954,8 +1247,12 @@ pub fn output(query_str: &str, stream_filter: Option<&str>, opts: &OutputOptions
         return follow_running_output(&config, &invocation_id);
     }
 
-    // Get outputs for the invocation (optionally filtered by stream)
-    let outputs = store.get_outputs(&invocation_id, db_filter)?;
+    // Get outputs for the invocation (optionally filtered by stream), with
+    // each stream's parts in order
+    let mut outputs = store.get_outputs_ordered(&invocation_id, db_filter)?;
+    if let Some(part) = opts.part {
+        outputs.retain(|o| o.sequence == part);
+    }
 
     if outputs.is_empty() {
         eprintln!("No output found for invocation {}", invocation_id);
@@ -1013,7 +1310,7 @@ pub fn output(query_str: &str, stream_filter: Option<&str>, opts: &OutputOptions
         all_content.extend_from_slice(&stderr_content);
         let final_content = process_content(all_content);
 
-        let pager_cmd = std::env::var("PAGER").unwrap_or_else(|_| "less -R".to_string());
+        let pager_cmd = crate::pager::command();
         let parts: Vec<&str> = pager_cmd.split_whitespace().collect();
         if let Some((cmd, args)) = parts.split_first() {
             let mut child = Command::new(cmd)
@@ -1048,6 +1345,107 @@ pub fn output(query_str: &str, stream_filter: Option<&str>, opts: &OutputOptions
     Ok(())
 }
 
+/// List the files attached to an invocation, or write them under `extract`
+/// by their attached paths.
+pub fn artifacts(query_str: &str, extract: Option<&str>) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let query = parse_query_strict(query_str)?;
+    let invocation_id = if let Some(id) = try_find_by_id(&store, query_str)? {
+        id
+    } else {
+        match resolve_query_to_invocation(&store, &query) {
+            Ok(id) => id,
+            Err(bird::Error::NotFound(_)) => {
+                eprintln!("No matching invocation found");
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+    };
+
+    let artifacts = store.get_artifacts(&invocation_id)?;
+    if artifacts.is_empty() {
+        eprintln!("No artifacts for invocation {}", invocation_id);
+        return Ok(());
+    }
+
+    let Some(dir) = extract else {
+        for artifact in &artifacts {
+            println!("{:>10}  {}", format_bytes(artifact.output.byte_length as u64), artifact.path);
+        }
+        return Ok(());
+    };
+
+    for artifact in &artifacts {
+        let dest = std::path::Path::new(dir).join(artifact.relative_path());
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&dest, store.read_output_content(&artifact.output)?)?;
+        println!("{}", dest.display());
+    }
+    Ok(())
+}
+
+/// List the commands run inside a captured shell session (`shq run bash`,
+/// ssh, ...) from the OSC 133 markers in its output, or with `show`, print
+/// the output of one of them.
+pub fn segments(query_str: &str, show: Option<usize>, strip: bool) -> bird::Result<()> {
+    use std::io::Write;
+
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let invocation_id = match try_find_by_id(&store, query_str)? {
+        Some(id) => id,
+        None => resolve_query_to_invocation(&store, &parse_query_strict(query_str)?)?,
+    };
+
+    // PTY captures are one combined stream; join pipe captures the same way
+    let mut content = Vec::new();
+    for output in store.get_outputs_ordered(&invocation_id, None)? {
+        content.extend_from_slice(&store.read_output_content(&output)?);
+    }
+    let segments = bird::osc133::split(&content);
+
+    if let Some(n) = show {
+        let segment = n.checked_sub(1).and_then(|i| segments.get(i)).ok_or_else(|| {
+            bird::Error::NotFound(format!("Segment {} not found ({} segments)", n, segments.len()))
+        })?;
+        let bytes = &content[segment.output.clone()];
+        let bytes = if strip { strip_ansi_escapes(bytes) } else { bytes.to_vec() };
+        io::stdout().write_all(&bytes)?;
+        return Ok(());
+    }
+
+    if segments.is_empty() {
+        eprintln!("No OSC 133 markers in the output of {} (see SHQ_OSC133)", invocation_id);
+        return Ok(());
+    }
+
+    println!("{:>4}  {:>5}  {:>4}  {:>9}  COMMAND", "#", "DEPTH", "EXIT", "OUTPUT");
+    for (i, segment) in segments.iter().enumerate() {
+        let exit = match (segment.finished, segment.exit_code) {
+            (true, Some(code)) => code.to_string(),
+            (true, None) => "-".to_string(),
+            (false, _) => "?".to_string(),
+        };
+        let command = segment.command.as_deref().unwrap_or("?");
+        println!(
+            "{:>4}  {:>5}  {:>4}  {:>9}  {}{}",
+            i + 1,
+            segment.depth,
+            exit,
+            format_bytes(segment.output.len() as u64),
+            "  ".repeat(segment.depth),
+            command
+        );
+    }
+    Ok(())
+}
+
 /// Strip ANSI escape codes from bytes.
 fn strip_ansi_escapes(input: &[u8]) -> Vec<u8> {
     let mut output = Vec::with_capacity(input.len());
@@ -1159,84 +1557,265 @@ pub fn update_extensions(dry_run: bool) -> bird::Result<()> {
     Ok(())
 }
 
-/// List invocation history.
-pub fn invocations(query_str: &str, format: &str, limit: Option<usize>) -> bird::Result<()> {
-    let config = Config::load()?;
-    let store = Store::open(config)?;
-
-    // Parse query and apply filters
-    let mut query = parse_query(query_str);
+/// Copy installed extensions into a repository directory for offline use.
+pub fn extensions_bundle(dir: &str) -> bird::Result<()> {
+    let written = bird::extensions::bundle(std::path::Path::new(dir))?;
 
-    // Override range if -n/--last is provided (last N items)
-    if let Some(n) = limit {
-        query.range = Some(bird::RangeSelector { start: n, end: Some(0) });
+    if written.is_empty() {
+        println!("No installed extensions to bundle. Run any shq command online first.");
+        return Ok(());
     }
 
-    let invocations = store.query_invocations(&query)?;
-
-    if invocations.is_empty() {
-        println!("No invocations recorded yet.");
-        return Ok(());
+    for path in &written {
+        println!("  {}", path.display());
     }
+    println!("\nBundled {} extensions into {}", written.len(), dir);
+    println!("On the offline machine: shq extensions install --from <dir>");
 
-    // Get output info for all invocations (which streams have data)
-    let inv_ids: Vec<&str> = invocations.iter().map(|i| i.id.as_str()).collect();
-    let output_info = get_output_info_batch(&store, &inv_ids)?;
+    Ok(())
+}
 
-    match format {
-        "json" => {
-            // JSON output
-            println!("[");
-            for (i, inv) in invocations.iter().enumerate() {
-                let comma = if i < invocations.len() - 1 { "," } else { "" };
-                let out_state = output_info.get(inv.id.as_str()).copied().unwrap_or_default();
-                println!(
-                    r#"  {{"id": "{}", "timestamp": "{}", "cmd": "{}", "exit_code": {}, "duration_ms": {}, "has_stdout": {}, "has_stderr": {}, "has_combined": {}}}{}"#,
-                    inv.id,
-                    inv.timestamp,
-                    inv.cmd.replace('\\', "\\\\").replace('"', "\\\""),
-                    inv.exit_code,
-                    inv.duration_ms.unwrap_or(0),
-                    out_state.has_stdout,
-                    out_state.has_stderr,
-                    out_state.has_combined,
-                    comma
-                );
-            }
-            println!("]");
+/// Install extensions from a bundled repository.
+pub fn extensions_install(from: &str) -> bird::Result<()> {
+    let results = bird::extensions::install_from(from)?;
+
+    for result in &results {
+        match &result.error {
+            None => println!("  {} installed", result.name),
+            Some(e) => println!("  {} failed: {}", result.name, e),
         }
-        "table" => {
-            // Detailed table output
-            println!("{:<20} {:<6} {:<10} {:<4} COMMAND", "TIMESTAMP", "EXIT", "DURATION", "OUT");
-            println!("{}", "-".repeat(80));
+    }
 
-            for inv in invocations {
-                let duration = inv
-                    .duration_ms
-                    .map(|d| format!("{}ms", d))
-                    .unwrap_or_else(|| "-".to_string());
+    let installed = results.iter().filter(|r| r.error.is_none()).count();
+    println!("\nInstalled {} of {} extensions from {}", installed, results.len(), from);
+    println!("Set extensions.repository in config.toml to install from here automatically.");
 
-                // Truncate timestamp to just time portion if today
-                let timestamp = if inv.timestamp.len() > 19 {
-                    &inv.timestamp[11..19]
-                } else {
-                    &inv.timestamp
-                };
+    Ok(())
+}
 
-                // Output indicator
-                let out_state = output_info.get(inv.id.as_str()).copied().unwrap_or_default();
-                let out_indicator = out_state.glyph();
+/// Write this machine's setup to a bundle.
+pub fn setup_export(path: &std::path::Path) -> bird::Result<()> {
+    let config = Config::load()?;
+    let written = bird::setup::export(&config, path)?;
 
-                // Truncate command if too long
-                let cmd_display = if inv.cmd.len() > 50 {
-                    format!("{}...", &inv.cmd[..47])
-                } else {
-                    inv.cmd.clone()
-                };
+    for name in &written {
+        println!("  {}", name);
+    }
+    println!("\nBundled {} files into {}", written.len(), path.display());
+    println!("On the other machine: shq setup apply {}", path.display());
 
-                println!(
-                    "{:<20} {:<6} {:<10} {:<4} {}",
-                    timestamp, inv.exit_code, duration, out_indicator, cmd_display
+    Ok(())
+}
+
+/// Apply a setup bundle to this machine.
+pub fn setup_apply(path: &std::path::Path, dry_run: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    if !config.db_path().exists() {
+        return Err(bird::Error::NotInitialized(config.bird_root.clone()));
+    }
+    let changes = bird::setup::apply(&config, path, dry_run)?;
+
+    for (name, change) in &changes {
+        println!("  {:<20} {}", name, change);
+    }
+    let changed = changes.iter().filter(|(_, c)| *c != bird::setup::Change::Unchanged).count();
+    if dry_run {
+        println!("\nWould change {} of {} files (dry run)", changed, changes.len());
+    } else {
+        println!("\nChanged {} of {} files (previous versions kept as .bak)", changed, changes.len());
+    }
+
+    Ok(())
+}
+
+/// Show which optional extensions are active.
+pub fn extensions_status() -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+    let caps = store.capabilities()?;
+
+    for cap in bird::Capability::ALL {
+        let status = if caps.has(cap) { "active" } else { "unavailable" };
+        println!("{:10} {:12} {}", cap.extension(), status, cap.description());
+    }
+
+    Ok(())
+}
+
+/// Open the store, or the snapshot of it for `--as-of DATE`.
+fn open_store_as_of(as_of: Option<&str>) -> bird::Result<Store> {
+    let store = Store::open(Config::load()?)?;
+    match as_of {
+        Some(date) => store.as_of(bird::parse_since(date)?),
+        None => Ok(store),
+    }
+}
+
+/// A timestamp as the store renders it (in the display zone) for display:
+/// relative to now ("3m ago", "yesterday 14:02"), or date and time with
+/// `absolute`.
+fn display_time(timestamp: &str, absolute: bool) -> String {
+    match bird::reltime::parse_timestamp(timestamp) {
+        Some(time) if absolute => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        Some(time) => bird::format_relative(time, chrono::Utc::now()),
+        None => timestamp.to_string(),
+    }
+}
+
+/// Limit `shq i` to `query.default_scope` when run inside a project and
+/// the query names no directory or source of its own. Returns whether it did.
+fn apply_default_scope(query: &mut Query, config: &Config, global: bool) -> bool {
+    if global || query.path.is_some() || query.source.is_some() {
+        return false;
+    }
+    let Some(project) = bird::project::find_current_project() else {
+        return false;
+    };
+    query.path = match config.query.default_scope {
+        bird::QueryScope::Cwd => Some(bird::PathFilter::Current),
+        bird::QueryScope::Project => Some(bird::PathFilter::Absolute(project.root)),
+        bird::QueryScope::Global => None,
+    };
+    query.path.is_some()
+}
+
+/// Message for an empty `shq i` listing.
+fn no_invocations_message(scoped: bool, default: &str) -> String {
+    if scoped {
+        "No invocations here (use -g for all history).".to_string()
+    } else {
+        default.to_string()
+    }
+}
+
+/// An invocation as a `--columns`/template row.
+struct InvocationRow<'a> {
+    inv: &'a bird::InvocationSummary,
+    absolute: bool,
+}
+
+impl crate::table::Row for InvocationRow<'_> {
+    const FIELDS: &'static [&'static str] = &["id", "time", "exit", "duration", "cwd", "cmd"];
+
+    fn field(&self, field: &str) -> String {
+        match field {
+            "id" => self.inv.id.clone(),
+            "time" => display_time(&self.inv.timestamp, self.absolute),
+            "exit" => self.inv.exit_code.to_string(),
+            "duration" => self.inv.duration_ms.map(format_duration_ms).unwrap_or_else(|| "-".to_string()),
+            "cwd" => self.inv.cwd.clone(),
+            _ => self.inv.cmd.clone(),
+        }
+    }
+}
+
+/// List invocation history.
+///
+/// `columns` picks the table columns; a `template=...` format prints one
+/// line per invocation instead.
+#[allow(clippy::too_many_arguments)]
+pub fn invocations(
+    query_str: &str,
+    format: &str,
+    columns: Option<&str>,
+    limit: Option<usize>,
+    as_of: Option<&str>,
+    global: bool,
+    absolute: bool,
+) -> bird::Result<()> {
+    let template = format
+        .strip_prefix("template=")
+        .map(crate::table::Template::parse::<InvocationRow>)
+        .transpose()?;
+    let columns = columns.map(crate::table::parse_columns::<InvocationRow>).transpose()?;
+    let store = open_store_as_of(as_of)?;
+
+    // Parse query and apply filters
+    let mut query = parse_query_strict(query_str)?;
+    let scoped = apply_default_scope(&mut query, store.config(), global);
+
+    // Override range if -n/--last is provided (last N items)
+    if let Some(n) = limit {
+        query.range = Some(bird::RangeSelector { start: n, end: Some(0) });
+    }
+
+    let invocations = store.query_invocations(&query)?;
+    let _render = bird::profile::during(Phase::Render);
+
+    if let Some(template) = template {
+        for inv in &invocations {
+            println!("{}", template.render(&InvocationRow { inv, absolute }));
+        }
+        return Ok(());
+    }
+
+    if invocations.is_empty() {
+        println!("{}", no_invocations_message(scoped, "No invocations recorded yet."));
+        return Ok(());
+    }
+
+    if let Some(columns) = columns.filter(|_| format != "json") {
+        let rows: Vec<InvocationRow> = invocations.iter().map(|inv| InvocationRow { inv, absolute }).collect();
+        crate::table::print_table(&rows, &columns);
+        return Ok(());
+    }
+
+    // Get output info for all invocations (which streams have data)
+    let inv_ids: Vec<&str> = invocations.iter().map(|i| i.id.as_str()).collect();
+    let output_info = {
+        let _query = bird::profile::during(Phase::Query);
+        get_output_info_batch(&store, &inv_ids)?
+    };
+
+    match format {
+        "json" => {
+            // JSON output
+            println!("[");
+            for (i, inv) in invocations.iter().enumerate() {
+                let comma = if i < invocations.len() - 1 { "," } else { "" };
+                let out_state = output_info.get(inv.id.as_str()).copied().unwrap_or_default();
+                println!(
+                    r#"  {{"id": "{}", "timestamp": "{}", "cmd": "{}", "exit_code": {}, "duration_ms": {}, "has_stdout": {}, "has_stderr": {}, "has_combined": {}}}{}"#,
+                    inv.id,
+                    inv.timestamp,
+                    inv.cmd.replace('\\', "\\\\").replace('"', "\\\""),
+                    inv.exit_code,
+                    inv.duration_ms.unwrap_or(0),
+                    out_state.has_stdout,
+                    out_state.has_stderr,
+                    out_state.has_combined,
+                    comma
+                );
+            }
+            println!("]");
+        }
+        "table" => {
+            // Detailed table output
+            println!("{:<20} {:<6} {:<10} {:<4} COMMAND", "TIMESTAMP", "EXIT", "DURATION", "OUT");
+            println!("{}", "-".repeat(80));
+
+            for inv in invocations {
+                let duration = inv
+                    .duration_ms
+                    .map(|d| format!("{}ms", d))
+                    .unwrap_or_else(|| "-".to_string());
+
+                let timestamp = display_time(&inv.timestamp, absolute);
+
+                // Output indicator
+                let out_state = output_info.get(inv.id.as_str()).copied().unwrap_or_default();
+                let out_indicator = out_state.glyph(&crate::theme::current().glyphs);
+
+                // Truncate command if too long
+                let cmd_display = if inv.cmd.len() > 50 {
+                    format!("{}...", &inv.cmd[..47])
+                } else {
+                    inv.cmd.clone()
+                };
+
+                println!(
+                    "{:<20} {:<6} {:<10} {:<4} {}",
+                    timestamp, inv.exit_code, duration, out_indicator, cmd_display
                 );
             }
         }
@@ -1248,16 +1827,9 @@ pub fn invocations(query_str: &str, format: &str, limit: Option<usize>) -> bird:
         }
         _ => {
             // Compact color output (default)
-            // Format: ✓ abcd1234 command... ●
+            // Format: ✓ abcd1234 ● command... 3m ago
+            let theme = crate::theme::current();
             for inv in invocations {
-                // Status glyph with color
-                let (status_glyph, color_code) = if inv.exit_code == 0 {
-                    ("✓", "\x1b[32m") // Green
-                } else {
-                    ("✗", "\x1b[31m") // Red
-                };
-                let reset = "\x1b[0m";
-                let dim = "\x1b[2m";
 
                 // Short ID (last 8 chars - more unique for UUIDv7)
                 let id_len = inv.id.len();
@@ -1269,7 +1841,7 @@ pub fn invocations(query_str: &str, format: &str, limit: Option<usize>) -> bird:
 
                 // Output indicator
                 let out_state = output_info.get(inv.id.as_str()).copied().unwrap_or_default();
-                let out_glyph = out_state.glyph();
+                let out_glyph = out_state.glyph(&theme.glyphs);
 
                 // Truncate command for terminal width (leave room for prefix)
                 let max_cmd_len = 65;
@@ -1280,13 +1852,196 @@ pub fn invocations(query_str: &str, format: &str, limit: Option<usize>) -> bird:
                 };
 
                 println!(
-                    "{}{}{} {}{}{} {} {}",
-                    color_code, status_glyph, reset,
-                    dim, short_id, reset,
+                    "{} {} {} {}  {}",
+                    theme.status(Some(inv.exit_code)),
+                    theme.paint(DIM, short_id),
                     out_glyph,
-                    cmd_display
+                    cmd_display,
+                    theme.paint(DIM, display_time(&inv.timestamp, absolute))
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// List distinct commands with how often and how well they ran
+/// (`shq i --dedup`). The range counts distinct commands.
+pub fn invocations_dedup(
+    query_str: &str,
+    format: &str,
+    limit: Option<usize>,
+    as_of: Option<&str>,
+    global: bool,
+    absolute: bool,
+) -> bird::Result<()> {
+    let store = open_store_as_of(as_of)?;
+
+    let mut query = parse_query_strict(query_str)?;
+    let scoped = apply_default_scope(&mut query, store.config(), global);
+    if let Some(n) = limit {
+        query.range = Some(bird::RangeSelector { start: n, end: Some(0) });
+    }
+
+    let commands = store.deduped_history(&query)?;
+
+    if commands.is_empty() {
+        println!("{}", no_invocations_message(scoped, "No invocations recorded yet."));
+        return Ok(());
+    }
+
+    let ratio = |c: &bird::DedupedCommand| {
+        c.success_ratio().map(|r| format!("{:.0}%", r * 100.0)).unwrap_or_else(|| "-".to_string())
+    };
+
+    match format {
+        "json" => {
+            let json: Vec<serde_json::Value> = commands
+                .iter()
+                .map(|c| {
+                    serde_json::json!({
+                        "cmd": c.cmd,
+                        "count": c.count,
+                        "succeeded": c.succeeded,
+                        "completed": c.completed,
+                        "success_ratio": c.success_ratio(),
+                        "first_run": c.first_run,
+                        "last_run": c.last_run,
+                        "last_id": c.last_id,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+        }
+        "table" => {
+            println!("{:>6} {:>5}  {:<19}  {:<19}  COMMAND", "COUNT", "OK", "FIRST", "LAST");
+            println!("{}", "-".repeat(80));
+            for c in &commands {
+                println!(
+                    "{:>6} {:>5}  {:<19}  {:<19}  {}",
+                    c.count,
+                    ratio(c),
+                    display_time(&c.first_run, absolute),
+                    display_time(&c.last_run, absolute),
+                    truncate_string(&c.cmd, 50)
+                );
+            }
+        }
+        "commands" => {
+            for c in &commands {
+                println!("{}", c.cmd);
+            }
+        }
+        _ => {
+            // Compact: glyph by outcome, short ID of the latest run, count, ratio
+            let theme = crate::theme::current();
+            for c in &commands {
+                let glyph = if c.completed == 0 {
+                    theme.status(None)
+                } else if c.succeeded == c.completed {
+                    theme.status(Some(0))
+                } else if c.succeeded == 0 {
+                    theme.status(Some(1))
+                } else {
+                    theme.paint(YELLOW, &theme.glyphs.mixed)
+                };
+                let short_id = &c.last_id[c.last_id.len().saturating_sub(8)..];
+                println!(
+                    "{} {} {:>5}x {:>4}  {}  {}",
+                    glyph,
+                    theme.paint(DIM, short_id),
+                    c.count,
+                    ratio(c),
+                    truncate_string(&c.cmd, 60),
+                    theme.paint(DIM, display_time(&c.last_run, absolute))
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Show invocation counts grouped by a field (`shq i <query> --by <field>`).
+pub fn invocations_by(
+    query_str: &str,
+    by: &str,
+    format: &str,
+    limit: Option<usize>,
+    as_of: Option<&str>,
+    global: bool,
+) -> bird::Result<()> {
+    let store = open_store_as_of(as_of)?;
+
+    let group_field: GroupField = by.parse()?;
+
+    let mut query = parse_query_strict(query_str)?;
+    let scoped = apply_default_scope(&mut query, store.config(), global);
+    if let Some(n) = limit {
+        query.range = Some(bird::RangeSelector { start: n, end: Some(0) });
+    }
+
+    let groups = store.aggregate(&query, group_field)?;
+
+    if groups.is_empty() {
+        println!("{}", no_invocations_message(scoped, "No invocations matched."));
+        return Ok(());
+    }
+
+    match format {
+        "json" => {
+            let json: Vec<serde_json::Value> = groups
+                .iter()
+                .map(|g| {
+                    serde_json::json!({
+                        group_field.as_str(): g.key,
+                        "count": g.count,
+                        "failed": g.failed,
+                        "total_duration_ms": g.total_duration_ms,
+                        "avg_duration_ms": g.avg_duration_ms,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+        }
+        _ => {
+            let total: i64 = groups.iter().map(|g| g.count).sum();
+            let max = groups.iter().map(|g| g.count).max().unwrap_or(1).max(1);
+            let key_width = groups
+                .iter()
+                .map(|g| g.key.chars().count())
+                .max()
+                .unwrap_or(0)
+                .clamp(group_field.as_str().len(), 40);
+            let bar_width = 30;
+
+            println!(
+                "{:<kw$} {:>7} {:>7} {:>10}  ",
+                group_field.as_str().to_uppercase(),
+                "COUNT",
+                "FAILED",
+                "AVG",
+                kw = key_width
+            );
+            for g in &groups {
+                let bar_len = ((g.count as f64 / max as f64) * bar_width as f64).ceil() as usize;
+                let avg = g
+                    .avg_duration_ms
+                    .map(|d| format_duration_ms(d as i64))
+                    .unwrap_or_else(|| "-".to_string());
+                println!(
+                    "{:<kw$} {:>7} {:>7} {:>10}  {}",
+                    truncate_string(&g.key, key_width),
+                    g.count,
+                    g.failed,
+                    avg,
+                    "█".repeat(bar_len),
+                    kw = key_width
                 );
             }
+            println!();
+            println!("{} invocations in {} groups", total, groups.len());
         }
     }
 
@@ -1304,19 +2059,19 @@ struct OutputState {
 
 impl OutputState {
     /// Get display glyph for output state
-    fn glyph(&self) -> &'static str {
+    fn glyph<'a>(&self, glyphs: &'a crate::theme::Glyphs) -> &'a str {
         if self.has_combined {
-            "◉"  // Combined (merged, can't separate)
+            &glyphs.out_combined  // Combined (merged, can't separate)
         } else if self.has_stdout && self.has_stderr {
-            "●"  // Both separate streams
+            &glyphs.out_both  // Both separate streams
         } else if self.has_stdout {
-            "◐"  // Stdout only
+            &glyphs.out_stdout  // Stdout only
         } else if self.has_stderr {
-            "◑"  // Stderr only
+            &glyphs.out_stderr  // Stderr only
         } else if self.has_empty {
-            "○"  // Captured but empty
+            &glyphs.out_empty  // Captured but empty
         } else {
-            "·"  // Not captured
+            &glyphs.out_none  // Not captured
         }
     }
 }
@@ -1362,65 +2117,320 @@ fn get_output_info_batch(store: &Store, inv_ids: &[&str]) -> bird::Result<std::c
     Ok(info)
 }
 
-/// Show quick reference for commands and query syntax.
-pub fn quick_help() -> bird::Result<()> {
-    print!("{}", QUICK_HELP);
-    Ok(())
-}
+/// List schemas, tables, views and macros with descriptions (`shq schema`).
+pub fn schema(pattern: Option<&str>, remotes: bool, format: &str) -> bird::Result<()> {
+    use bird::CatalogKind;
 
-pub fn sql(query: &str) -> bird::Result<()> {
     let config = Config::load()?;
     let store = Store::open(config)?;
 
-    let result = store.query(query)?;
+    let pattern = pattern.map(str::to_lowercase);
+    let entries: Vec<bird::CatalogEntry> = store
+        .catalog(remotes)?
+        .into_iter()
+        .filter(|e| match &pattern {
+            Some(p) => {
+                e.sql_name().to_lowercase().contains(p)
+                    || e.description.as_deref().is_some_and(|d| d.to_lowercase().contains(p))
+            }
+            None => true,
+        })
+        .collect();
 
-    if result.rows.is_empty() {
-        println!("No results.");
+    if format == "json" {
+        let json: Vec<serde_json::Value> = entries
+            .iter()
+            .map(|e| {
+                serde_json::json!({
+                    "kind": e.kind.to_string(),
+                    "schema": e.schema,
+                    "name": e.name,
+                    "sql_name": e.sql_name(),
+                    "temporary": e.temporary,
+                    "parameters": e.parameters,
+                    "description": e.description,
+                    "example": e.example,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
         return Ok(());
     }
 
-    // Calculate column widths
-    let mut widths: Vec<usize> = result.columns.iter().map(|c| c.len()).collect();
-    for row in &result.rows {
-        for (i, val) in row.iter().enumerate() {
-            widths[i] = widths[i].max(val.len().min(50));
+    if entries.is_empty() {
+        println!("Nothing matches.");
+        return Ok(());
+    }
+
+    let width = entries.iter().map(|e| e.sql_name().len()).max().unwrap_or(0).min(40);
+    let print_entry = |e: &bird::CatalogEntry, label: &str| {
+        println!(
+            "  {:<6} {:<width$}  {}",
+            label,
+            e.sql_name(),
+            e.description.as_deref().unwrap_or("-"),
+            width = width
+        );
+        if let Some(example) = &e.example {
+            println!("  {:<6} {:<width$}    {}", "", "", example, width = width);
+        }
+    };
+
+    let mut section = None;
+    for e in entries.iter().filter(|e| e.kind != CatalogKind::Macro) {
+        let heading = if e.kind == CatalogKind::Schema { "Schemas".to_string() } else { format!("{}.*", e.schema) };
+        if section.as_ref() != Some(&heading) {
+            println!("{}{}", if section.is_some() { "\n" } else { "" }, heading);
+            section = Some(heading);
         }
+        let label = if e.kind == CatalogKind::Schema { String::new() } else { e.kind.to_string() };
+        print_entry(e, &label);
     }
 
-    // Print header
-    for (i, col) in result.columns.iter().enumerate() {
-        print!("{:width$} ", col, width = widths[i]);
+    let macros: Vec<&bird::CatalogEntry> = entries.iter().filter(|e| e.kind == CatalogKind::Macro).collect();
+    if !macros.is_empty() {
+        println!("{}Macros (temp: created per connection, only visible through shq)", if section.is_some() { "\n" } else { "" });
+        for e in macros {
+            print_entry(e, if e.temporary { "temp" } else { "" });
+        }
     }
-    println!();
+    Ok(())
+}
 
-    // Print separator
-    for width in &widths {
-        print!("{} ", "-".repeat(*width));
+/// Print the public view contract version (`shq schema --contract-version`).
+pub fn schema_contract_version(format: &str) -> bird::Result<()> {
+    if format == "json" {
+        let json = serde_json::json!({
+            "contract_version": bird::CONTRACT_VERSION,
+            "views": bird::CONTRACT_VIEWS
+                .iter()
+                .map(|(name, _)| format!("v{}.{}", bird::CONTRACT_VERSION, name))
+                .collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+    } else {
+        println!("{}", bird::CONTRACT_VERSION);
     }
-    println!();
+    Ok(())
+}
 
-    // Print rows
-    for row in &result.rows {
-        for (i, val) in row.iter().enumerate() {
-            let display = if val.len() > 50 {
-                format!("{}...", &val[..47])
-            } else {
-                val.clone()
-            };
-            print!("{:width$} ", display, width = widths[i]);
+/// Print (or write to `output`) SQL that attaches the store read-only.
+pub fn attach_snippet(alias: &str, output: Option<&std::path::Path>) -> bird::Result<()> {
+    let config = Config::load()?;
+    let sql = bird::attach_sql(&config, alias)?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, &sql)?;
+            eprintln!("Wrote {}", path.display());
+            eprintln!("  duckdb -init {}", path.display());
         }
-        println!();
+        None => print!("{}", sql),
     }
+    Ok(())
+}
 
-    println!("\n({} rows)", result.rows.len());
-
+/// Show quick reference for commands and query syntax.
+pub fn quick_help() -> bird::Result<()> {
+    print!("{}", QUICK_HELP);
     Ok(())
 }
 
-/// Statistics about the BIRD store.
-#[derive(serde::Serialize)]
-pub struct BirdStats {
-    pub root: String,
+/// Run a SQL query (`shq sql`).
+///
+/// Formats: table (default; values cut to `max_width` characters unless
+/// `None`), json, ndjson, csv. The machine formats are written row by row as
+/// DuckDB produces them, so large results can be piped without buffering.
+pub fn sql(query: &str, format: &str, max_width: Option<usize>) -> bird::Result<()> {
+    use std::io::Write;
+
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let format = match format {
+        "json" => RowFormat::Json,
+        "ndjson" => RowFormat::Ndjson,
+        "csv" => RowFormat::Csv,
+        _ => return sql_table(&store, query, max_width),
+    };
+
+    let stdout = std::io::stdout();
+    let writer = std::cell::RefCell::new(RowWriter::new(std::io::BufWriter::new(stdout.lock()), format));
+    let result = store
+        .query_rows(
+            query,
+            |columns| {
+                let _render = bird::profile::during(Phase::Render);
+                Ok(writer.borrow_mut().header(columns)?)
+            },
+            |row| {
+                let _render = bird::profile::during(Phase::Render);
+                Ok(writer.borrow_mut().row(&row)?)
+            },
+        )
+        .and_then(|_| {
+            let _render = bird::profile::during(Phase::Render);
+            let mut writer = writer.borrow_mut();
+            writer.finish()?;
+            writer.out.flush()?;
+            Ok(())
+        });
+
+    match result {
+        // The reader (e.g. `head`) went away; that's not our error
+        Err(bird::Error::Io(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        other => other,
+    }
+}
+
+/// Machine-readable `shq sql` output formats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RowFormat {
+    /// One JSON array of objects.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+    /// Header line, then one record per line (RFC 4180 quoting, NULL empty).
+    Csv,
+}
+
+/// Writes query rows in a [`RowFormat`] as they arrive.
+struct RowWriter<W: std::io::Write> {
+    out: W,
+    format: RowFormat,
+    columns: Vec<String>,
+    rows: usize,
+}
+
+impl<W: std::io::Write> RowWriter<W> {
+    fn new(out: W, format: RowFormat) -> Self {
+        Self { out, format, columns: Vec::new(), rows: 0 }
+    }
+
+    fn header(&mut self, columns: &[String]) -> std::io::Result<()> {
+        self.columns = columns.to_vec();
+        match self.format {
+            RowFormat::Json => write!(self.out, "["),
+            RowFormat::Ndjson => Ok(()),
+            RowFormat::Csv => {
+                let fields: Vec<String> = columns.iter().map(|c| csv_field(c)).collect();
+                writeln!(self.out, "{}", fields.join(","))
+            }
+        }
+    }
+
+    fn row(&mut self, row: &[bird::SqlValue]) -> std::io::Result<()> {
+        match self.format {
+            RowFormat::Json => {
+                let sep = if self.rows == 0 { "" } else { "," };
+                write!(self.out, "{}\n  {}", sep, self.json_object(row))?;
+            }
+            RowFormat::Ndjson => writeln!(self.out, "{}", self.json_object(row))?,
+            RowFormat::Csv => {
+                let fields: Vec<String> = row
+                    .iter()
+                    .map(|v| match v {
+                        bird::SqlValue::Null => String::new(),
+                        v => csv_field(&v.to_string()),
+                    })
+                    .collect();
+                writeln!(self.out, "{}", fields.join(","))?;
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        match self.format {
+            RowFormat::Json if self.rows > 0 => writeln!(self.out, "\n]"),
+            RowFormat::Json => writeln!(self.out, "]"),
+            _ => Ok(()),
+        }
+    }
+
+    /// The row as a JSON object, keys in column order.
+    fn json_object(&self, row: &[bird::SqlValue]) -> String {
+        let mut object = String::from("{");
+        for (i, value) in row.iter().enumerate() {
+            if i > 0 {
+                object.push(',');
+            }
+            let name = self.columns.get(i).map(String::as_str).unwrap_or_default();
+            object.push_str(&serde_json::Value::from(name).to_string());
+            object.push(':');
+            object.push_str(&value.to_json().to_string());
+        }
+        object.push('}');
+        object
+    }
+}
+
+/// `shq sql` as an aligned table, values cut to `max_width` characters.
+fn sql_table(store: &Store, query: &str, max_width: Option<usize>) -> bird::Result<()> {
+    let result = store.query(query)?;
+    let _render = bird::profile::during(Phase::Render);
+
+    if result.rows.is_empty() {
+        println!("No results.");
+        return Ok(());
+    }
+
+    let truncate = |val: &str| -> String {
+        match max_width {
+            Some(max) if val.chars().count() > max => {
+                let keep = max.saturating_sub(3);
+                format!("{}...", val.chars().take(keep).collect::<String>())
+            }
+            _ => val.to_string(),
+        }
+    };
+
+    // Calculate column widths
+    let mut widths: Vec<usize> = result.columns.iter().map(|c| c.chars().count()).collect();
+    for row in &result.rows {
+        for (i, val) in row.iter().enumerate() {
+            widths[i] = widths[i].max(truncate(val).chars().count());
+        }
+    }
+
+    // Print header
+    for (i, col) in result.columns.iter().enumerate() {
+        print!("{:width$} ", col, width = widths[i]);
+    }
+    println!();
+
+    // Print separator
+    for width in &widths {
+        print!("{} ", "-".repeat(*width));
+    }
+    println!();
+
+    // Print rows
+    for row in &result.rows {
+        for (i, val) in row.iter().enumerate() {
+            print!("{:width$} ", truncate(val), width = widths[i]);
+        }
+        println!();
+    }
+
+    println!("\n({} rows)", result.rows.len());
+
+    Ok(())
+}
+
+/// Quote a CSV field if it needs it (RFC 4180).
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Statistics about the BIRD store.
+#[derive(serde::Serialize)]
+pub struct BirdStats {
+    pub root: String,
     pub client_id: String,
     pub storage_mode: String,
     pub current_session: CurrentSession,
@@ -1463,8 +2473,20 @@ pub struct SchemaCounts {
 #[derive(serde::Serialize)]
 pub struct InvocationStats {
     pub total: i64,
+    pub failed: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last: Option<LastInvocation>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub top_executables: Vec<ExecutableInfo>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ExecutableInfo {
+    pub executable: String,
+    pub runs: i64,
+    pub failed: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_duration_ms: Option<i64>,
 }
 
 #[derive(serde::Serialize)]
@@ -1513,8 +2535,16 @@ pub fn stats(format: &str, details: bool, field: Option<&str>) -> bird::Result<(
     let config = Config::load()?;
     let store = Store::open(config.clone())?;
 
+    // Totals come from the running stats, which stay cheap on large stores
+    let (inv_count, failed_count) = store.invocation_totals().unwrap_or((0, 0));
+    let top_executables = store.executable_stats(5).unwrap_or_default();
+
     // Use a single connection for all queries to avoid multiple connection issues
     let conn = store.connection()?;
+    // Per-remote and unified counts below need every remote attached
+    if !config.remotes.is_empty() {
+        store.attach_all_remotes(&conn)?;
+    }
 
     // Get current session info from client_id (username@hostname) and environment
     let (username, hostname) = config.client_id.split_once('@')
@@ -1524,9 +2554,6 @@ pub fn stats(format: &str, details: bool, field: Option<&str>) -> bird::Result<(
     let session_id = std::env::var("__shq_session_id").ok();
 
     // Gather basic stats using the single connection
-    let inv_count: i64 = conn
-        .query_row("SELECT COUNT(*) FROM main.invocations", [], |r| r.get(0))
-        .unwrap_or(0);
     let session_count: i64 = conn
         .query_row("SELECT COUNT(*) FROM main.sessions", [], |r| r.get(0))
         .unwrap_or(0);
@@ -1534,7 +2561,8 @@ pub fn stats(format: &str, details: bool, field: Option<&str>) -> bird::Result<(
     // Get last invocation
     let last_inv: Option<bird::InvocationSummary> = conn
         .query_row(
-            "SELECT id, cmd, exit_code, timestamp FROM main.invocations ORDER BY timestamp DESC LIMIT 1",
+            "SELECT id, cmd, exit_code, timezone('UTC', timestamp)::VARCHAR, cwd FROM main.invocations \
+             ORDER BY timestamp DESC LIMIT 1",
             [],
             |row| {
                 Ok(bird::InvocationSummary {
@@ -1543,6 +2571,7 @@ pub fn stats(format: &str, details: bool, field: Option<&str>) -> bird::Result<(
                     exit_code: row.get::<_, i32>(2)?,
                     timestamp: row.get::<_, String>(3)?,
                     duration_ms: None,
+                    cwd: row.get::<_, String>(4)?,
                 })
             },
         )
@@ -1577,7 +2606,7 @@ pub fn stats(format: &str, details: bool, field: Option<&str>) -> bird::Result<(
                 .ok();
             RemoteInfo {
                 name: r.name.clone(),
-                remote_type: format!("{:?}", r.remote_type).to_lowercase(),
+                remote_type: r.remote_type.to_string(),
                 uri: r.uri.clone(),
                 auto_attach: r.auto_attach,
                 invocations: inv_count,
@@ -1647,6 +2676,16 @@ pub fn stats(format: &str, details: bool, field: Option<&str>) -> bird::Result<(
         },
         invocations: InvocationStats {
             total: inv_count,
+            failed: failed_count,
+            top_executables: top_executables
+                .iter()
+                .map(|e| ExecutableInfo {
+                    executable: e.executable.clone(),
+                    runs: e.runs,
+                    failed: e.failed,
+                    mean_duration_ms: e.mean_duration_ms(),
+                })
+                .collect(),
             last: last_inv.map(|inv| LastInvocation {
                 id: inv.id.clone(),
                 cmd: inv.cmd.clone(),
@@ -1678,13 +2717,14 @@ pub fn stats(format: &str, details: bool, field: Option<&str>) -> bird::Result<(
             "shell" => stats.current_session.shell.clone(),
             "session_id" => stats.current_session.session_id.clone().unwrap_or_default(),
             "invocations" | "invocations.total" => stats.invocations.total.to_string(),
+            "failed" | "invocations.failed" => stats.invocations.failed.to_string(),
             "sessions" | "sessions.total" => stats.sessions.total.to_string(),
             "events" | "events.total" => stats.events.total.to_string(),
             "errors" | "events.errors" => stats.events.errors.to_string(),
             "warnings" | "events.warnings" => stats.events.warnings.to_string(),
             _ => {
                 eprintln!("Unknown field: {}", field_name);
-                eprintln!("Available fields: root, client_id, storage_mode, hostname, username, shell, session_id, invocations, sessions, events, errors, warnings");
+                eprintln!("Available fields: root, client_id, storage_mode, hostname, username, shell, session_id, invocations, failed, sessions, events, errors, warnings");
                 return Ok(());
             }
         };
@@ -1710,10 +2750,25 @@ pub fn stats(format: &str, details: bool, field: Option<&str>) -> bird::Result<(
             }
             println!();
             println!("Total invocations: {}", stats.invocations.total);
+            println!("  Failed:          {}", stats.invocations.failed);
             println!("Total sessions:    {}", stats.sessions.total);
             if let Some(ref inv) = stats.invocations.last {
                 println!("Last command:      {} (exit {})", inv.cmd, inv.exit_code);
             }
+            if !stats.invocations.top_executables.is_empty() {
+                println!();
+                println!("Top executables:");
+                for e in &stats.invocations.top_executables {
+                    let mean = e.mean_duration_ms.map(format_duration_ms).unwrap_or_else(|| "-".to_string());
+                    println!(
+                        "  {:20} {:>8} runs {:>6} failed   avg {}",
+                        truncate_string(&e.executable, 20),
+                        e.runs,
+                        e.failed,
+                        mean
+                    );
+                }
+            }
             println!();
             println!("Total events:      {}", stats.events.total);
             println!("  Errors:          {}", stats.events.errors);
@@ -1756,1382 +2811,4619 @@ pub fn stats(format: &str, details: bool, field: Option<&str>) -> bird::Result<(
     Ok(())
 }
 
-/// Move old data from recent to archive.
-pub fn archive(days: u32, dry_run: bool, extract_first: bool) -> bird::Result<()> {
+/// Show a leaderboard of frequent, slow and failing commands (`shq top`).
+///
+/// Refreshes every `interval` seconds until interrupted when stdout is a
+/// terminal; prints a single snapshot otherwise (or with `once`).
+pub fn top(query_str: &str, since: &str, by: &str, limit: usize, interval: u64, once: bool) -> bird::Result<()> {
+    use std::io::IsTerminal;
+
     let config = Config::load()?;
     let store = Store::open(config)?;
 
-    if dry_run {
-        println!("Dry run - no changes will be made\n");
-    }
+    let group_field: GroupField = by.parse()?;
+    let since_date = bird::parse_since(since)?;
 
-    // Optionally extract events from invocations before archiving
-    if extract_first && !dry_run {
-        println!("Extracting events from invocations to be archived...");
-        let cutoff_date = chrono::Utc::now().date_naive() - chrono::Duration::days(days as i64);
-        let invocations = store.invocations_without_events(Some(cutoff_date), None)?;
+    // The window is a date filter, so it combines with any query filters
+    let mut query = parse_query_strict(query_str)?;
+    query.filters.push(bird::QueryComponent::FieldFilter(bird::FieldFilter {
+        field: "date".to_string(),
+        op: bird::CompareOp::Gte,
+        value: since_date.to_string(),
+    }));
 
-        if !invocations.is_empty() {
-            let mut total_events = 0;
-            for inv in &invocations {
-                let count = store.extract_events(&inv.id, None)?;
-                total_events += count;
-            }
-            println!(
-                "  Extracted {} events from {} invocations",
-                total_events,
-                invocations.len()
-            );
+    let refresh = !once && io::stdout().is_terminal();
+    let mut stdout = io::stdout();
+
+    loop {
+        let groups = store.aggregate(&query, group_field)?;
+
+        if refresh {
+            use crossterm::{cursor::MoveTo, terminal::{Clear, ClearType}, ExecutableCommand};
+            stdout.execute(Clear(ClearType::All))?;
+            stdout.execute(MoveTo(0, 0))?;
+        }
+
+        let total: i64 = groups.iter().map(|g| g.count).sum();
+        let failed: i64 = groups.iter().map(|g| g.failed).sum();
+        println!(
+            "shq top — since {} by {} — {} invocations, {} failed",
+            since_date, group_field, total, failed
+        );
+        if !query_str.is_empty() {
+            println!("filter: {}", query_str);
+        }
+
+        // Most frequent (aggregate already orders by count)
+        print_top_section("MOST FREQUENT", group_field, groups.iter().take(limit));
+
+        // Slowest by average duration
+        let mut slowest: Vec<_> = groups.iter().filter(|g| g.avg_duration_ms.is_some()).collect();
+        slowest.sort_by(|a, b| b.avg_duration_ms.partial_cmp(&a.avg_duration_ms).unwrap_or(std::cmp::Ordering::Equal));
+        print_top_section("SLOWEST", group_field, slowest.into_iter().take(limit));
+
+        // Most failures
+        let mut failing: Vec<_> = groups.iter().filter(|g| g.failed > 0).collect();
+        failing.sort_by(|a, b| b.failed.cmp(&a.failed).then(b.count.cmp(&a.count)));
+        print_top_section("MOST FAILING", group_field, failing.into_iter().take(limit));
+
+        if !refresh {
+            break;
         }
+
+        println!();
+        println!("{}", crate::theme::current().paint(DIM, format!("Refreshing every {}s — Ctrl-C to exit", interval)));
+        stdout.flush()?;
+        std::thread::sleep(std::time::Duration::from_secs(interval.max(1)));
     }
 
-    let stats = store.archive_old_data(days, dry_run)?;
+    Ok(())
+}
+
+/// Print one leaderboard section for `shq top`.
+fn print_top_section<'a>(
+    title: &str,
+    group_field: GroupField,
+    rows: impl Iterator<Item = &'a bird::AggregateRow>,
+) {
+    println!();
+    println!(
+        "{:<44} {:>7} {:>7} {:>6} {:>10}",
+        format!("{} ({})", title, group_field),
+        "COUNT",
+        "FAILED",
+        "FAIL%",
+        "AVG"
+    );
 
-    if stats.partitions_archived > 0 {
+    let mut any = false;
+    for g in rows {
+        any = true;
+        let fail_pct = if g.count > 0 { g.failed as f64 * 100.0 / g.count as f64 } else { 0.0 };
+        let avg = g
+            .avg_duration_ms
+            .map(|d| format_duration_ms(d as i64))
+            .unwrap_or_else(|| "-".to_string());
         println!(
-            "Archived {} partitions ({} files, {})",
-            stats.partitions_archived,
-            stats.files_moved,
-            format_bytes(stats.bytes_moved)
+            "{:<44} {:>7} {:>7} {:>5.0}% {:>10}",
+            truncate_string(&g.key, 44),
+            g.count,
+            g.failed,
+            fail_pct,
+            avg
         );
-    } else {
-        println!("Nothing to archive.");
     }
+    if !any {
+        println!("  (none)");
+    }
+}
 
-    Ok(())
+/// Output formats of `shq heatmap`.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum HeatmapFormat {
+    /// Colored terminal grid.
+    Grid,
+    /// Per-day counts.
+    Json,
+    /// Standalone SVG image.
+    Svg,
 }
 
-/// Compact parquet files to reduce storage and improve performance.
-#[allow(clippy::too_many_arguments)]
-pub fn compact(
-    file_threshold: usize,
-    recompact_threshold: usize,
-    consolidate: bool,
-    extract_first: bool,
-    session: Option<&str>,
-    today_only: bool,
-    quiet: bool,
-    recent_only: bool,
-    archive_only: bool,
-    dry_run: bool,
-) -> bird::Result<()> {
+/// Render a calendar heatmap of invocations per day (`shq heatmap`).
+///
+/// Covers the given calendar year, or the last 53 weeks when no year is
+/// given. Formats: terminal grid (default), json, svg.
+pub fn heatmap(year: Option<i32>, format: HeatmapFormat) -> bird::Result<()> {
+    use chrono::{Datelike, NaiveDate, TimeDelta};
+
     let config = Config::load()?;
     let store = Store::open(config)?;
 
-    if dry_run && !quiet {
-        println!("Dry run - no changes will be made\n");
-    }
+    // Days are the store's `date` partitions, which are UTC dates
+    let today = chrono::Utc::now().date_naive();
+    let (start, end) = match year {
+        Some(y) => {
+            let start = NaiveDate::from_ymd_opt(y, 1, 1)
+                .ok_or_else(|| bird::Error::Config(format!("Invalid year: {}", y)))?;
+            let end = NaiveDate::from_ymd_opt(y, 12, 31)
+                .ok_or_else(|| bird::Error::Config(format!("Invalid year: {}", y)))?;
+            (start, end)
+        }
+        None => (today - TimeDelta::weeks(52), today),
+    };
 
-    // Extract events first if requested
-    if extract_first && !dry_run {
-        if !quiet {
-            println!("Extracting events from invocations before compacting...");
+    let days = store.daily_counts(start, end)?;
+    let counts: std::collections::HashMap<NaiveDate, i64> =
+        days.iter().map(|d| (d.date, d.count)).collect();
+    let max = days.iter().map(|d| d.count).max().unwrap_or(0);
+    let total: i64 = days.iter().map(|d| d.count).sum();
+
+    // Grid columns are weeks starting on Sunday, like GitHub
+    let grid_start = start - TimeDelta::days(start.weekday().num_days_from_sunday() as i64);
+    let weeks = ((end - grid_start).num_days() / 7 + 1) as usize;
+    let level = |count: i64| -> usize {
+        if count <= 0 || max == 0 {
+            0
+        } else {
+            ((count * 4 + max - 1) / max).clamp(1, 4) as usize
         }
-        let invocations = store.invocations_without_events(None, None)?;
-        let mut extracted = 0;
-        for inv in &invocations {
-            let count = store.extract_events(&inv.id, None)?;
-            extracted += count;
+    };
+    let cell = |week: usize, weekday: usize| -> Option<NaiveDate> {
+        let date = grid_start + TimeDelta::days((week * 7 + weekday) as i64);
+        (date >= start && date <= end).then_some(date)
+    };
+
+    match format {
+        HeatmapFormat::Json => {
+            let json = serde_json::json!({
+                "start": start.to_string(),
+                "end": end.to_string(),
+                "total": total,
+                "max": max,
+                "days": days.iter().map(|d| serde_json::json!({
+                    "date": d.date.to_string(),
+                    "count": d.count,
+                    "failed": d.failed,
+                })).collect::<Vec<_>>(),
+            });
+            println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
         }
-        if !quiet && extracted > 0 {
-            println!("  Extracted {} events from {} invocations\n", extracted, invocations.len());
+        HeatmapFormat::Svg => println!("{}", crate::report::heatmap_svg(start, end, &days)),
+        HeatmapFormat::Grid => {
+            const SHADES: [&str; 5] = [
+                "\x1b[38;5;238m",
+                "\x1b[38;5;22m",
+                "\x1b[38;5;28m",
+                "\x1b[38;5;34m",
+                "\x1b[38;5;40m",
+            ];
+            // Without color, density shows the level instead
+            let theme = crate::theme::current();
+            let shades: [&str; 5] = if theme.unicode() { ["·", "░", "▒", "▓", "█"] } else { [".", "-", "+", "*", "#"] };
+            let square = |level: usize| {
+                if theme.color() {
+                    theme.paint(SHADES[level], if theme.unicode() { "■" } else { "#" })
+                } else {
+                    shades[level].to_string()
+                }
+            };
+
+            // Month labels, placed above the first week of each month
+            let mut header = String::from("    ");
+            let mut last_month = None;
+            let mut week = 0;
+            while week < weeks {
+                let month = (0..7).find_map(|d| cell(week, d)).map(|d| d.month());
+                if month.is_some() && month != last_month {
+                    last_month = month;
+                    let date = (0..7).find_map(|d| cell(week, d)).unwrap_or(start);
+                    header.push_str(&format!("{:<4}", date.format("%b")));
+                    week += 2;
+                } else {
+                    header.push_str("  ");
+                    week += 1;
+                }
+            }
+            println!("{}", header.trim_end());
+
+            for weekday in 0..7 {
+                let label = match weekday {
+                    1 => "Mon ",
+                    3 => "Wed ",
+                    5 => "Fri ",
+                    _ => "    ",
+                };
+                let mut line = String::from(label);
+                for week in 0..weeks {
+                    match cell(week, weekday) {
+                        Some(date) => {
+                            let count = counts.get(&date).copied().unwrap_or(0);
+                            line.push_str(&format!("{} ", square(level(count))));
+                        }
+                        None => line.push_str("  "),
+                    }
+                }
+                println!("{}", line.trim_end());
+            }
+
+            println!();
+            let legend: String = (0..SHADES.len()).map(|l| format!("{} ", square(l))).collect();
+            println!("    Less {}More", legend);
+            match days.iter().max_by_key(|d| d.count) {
+                Some(busiest) => println!(
+                    "    {} invocations on {} days from {} to {} (busiest: {} with {})",
+                    total,
+                    days.len(),
+                    start,
+                    end,
+                    busiest.date,
+                    busiest.count
+                ),
+                None => println!("    No invocations from {} to {}", start, end),
+            }
         }
     }
 
-    let opts = CompactOptions {
-        file_threshold,
-        recompact_threshold,
-        consolidate,
-        dry_run,
-        session_filter: session.map(|s| s.to_string()),
+    Ok(())
+}
+
+/// Write a static HTML report to `dir/index.html` (`shq report --html`).
+///
+/// Covers `since` (default: the last 30 days) up to today: activity heatmap,
+/// failures, slowest commands and a searchable table of up to `limit`
+/// invocations.
+pub fn report(dir: &std::path::Path, since: Option<&str>, limit: usize) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    // The heatmap and ranges are over `date` partitions, which are UTC dates
+    let end = chrono::Utc::now().date_naive();
+    let start = match since {
+        Some(since) => bird::parse_since(since)?,
+        None => end - chrono::TimeDelta::days(30),
+    };
+    if start > end {
+        return Err(bird::Error::Config(format!("--since {} is in the future", start)));
+    }
+
+    let days = store.daily_counts(start, end)?;
+    let invocations = store.invocations_between(start, end, limit.max(1))?;
+    let in_window: i64 = days.iter().map(|d| d.count).sum();
+    let report = crate::report::Report {
+        start,
+        end,
+        generated: chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+        truncated: (invocations.len() as i64) < in_window,
+        days,
+        invocations,
     };
 
-    // Session-specific compaction (lightweight, used by shell hooks)
-    if let Some(session_id) = session {
-        let stats = if today_only {
-            // today_only uses legacy API (no recompact support for shell hooks)
-            store.compact_session_today(session_id, file_threshold, dry_run)?
-        } else {
-            store.compact_for_session_with_opts(session_id, &opts)?
-        };
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join("index.html");
+    std::fs::write(&path, crate::report::render(&report))?;
 
-        if stats.sessions_compacted > 0 {
-            let action = if consolidate { "Consolidated" } else { "Compacted" };
-            println!("{} session '{}':", action, session_id);
-            println!("  {} files -> {} files", stats.files_before, stats.files_after);
-            println!(
-                "  {} -> {} ({})",
-                format_bytes(stats.bytes_before),
-                format_bytes(stats.bytes_after),
-                format_reduction(stats.bytes_before, stats.bytes_after)
-            );
-        } else if !quiet {
-            println!("Nothing to compact for session '{}'.", session_id);
-        }
-        return Ok(());
-    }
+    println!(
+        "Wrote {} ({} invocations from {} to {}{})",
+        path.display(),
+        report.invocations.len(),
+        start,
+        end,
+        if report.truncated { ", truncated" } else { "" }
+    );
+    Ok(())
+}
 
-    // Global compaction
-    let mut total_stats = bird::CompactStats::default();
+/// Summarize a week of history (`shq digest`).
+pub fn digest(
+    markdown: bool,
+    template: Option<&std::path::Path>,
+    ago: u32,
+    output: Option<&std::path::Path>,
+) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
 
-    if !archive_only {
-        let stats = store.compact_recent_with_opts(&opts)?;
-        total_stats.add(&stats);
+    // Read the template first so a bad path fails before any querying
+    let template = match template {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| bird::Error::Config(format!("Cannot read template {}: {}", path.display(), e)))?,
+        None => crate::digest::DEFAULT_TEMPLATE.to_string(),
+    };
+
+    let period = bird::TimePeriod::Week;
+    let (mut start, mut end) = period.range(chrono::Local::now().date_naive());
+    for _ in 0..ago {
+        end = start;
+        start = period.range(start - chrono::Duration::days(1)).0;
     }
+    let end = end - chrono::Duration::days(1);
+    let previous = start - chrono::Duration::days(7);
 
-    if !recent_only {
-        let stats = store.compact_archive_with_opts(&opts)?;
-        total_stats.add(&stats);
+    let invocations = store.invocations_between(previous, end, 100_000)?;
+    let digest = crate::digest::build(start, end, &invocations);
+    let text = if markdown {
+        crate::digest::render_markdown(&digest, &template)
+    } else {
+        crate::digest::render_text(&digest)
+    };
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, &text)?;
+            println!("Wrote {}", path.display());
+        }
+        None => print!("{}", text),
     }
+    Ok(())
+}
 
-    if total_stats.sessions_compacted > 0 {
-        let action = if consolidate { "Consolidated" } else { "Compacted" };
-        println!(
-            "{} {} sessions across {} partitions",
-            action, total_stats.sessions_compacted, total_stats.partitions_compacted
-        );
-        println!(
-            "  {} files -> {} files",
-            total_stats.files_before, total_stats.files_after
-        );
-        println!(
-            "  {} -> {} ({})",
-            format_bytes(total_stats.bytes_before),
-            format_bytes(total_stats.bytes_after),
-            format_reduction(total_stats.bytes_before, total_stats.bytes_after)
-        );
-    } else if !quiet {
-        println!("Nothing to compact.");
+/// Show what blob deduplication saves (`shq du --dedup`).
+pub fn du_dedup(format: &str) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+    let dedup = store.blob_dedup()?;
+
+    if format == "json" {
+        let json = serde_json::json!({
+            "files": dedup.files,
+            "file_bytes": dedup.file_bytes,
+            "unique_files": dedup.unique_files,
+            "stored_bytes": dedup.stored_bytes,
+            "registered_bytes": dedup.registered_bytes,
+            "references": dedup.references,
+            "referenced_bytes": dedup.referenced_bytes,
+            "linked_savings": dedup.linked_savings(),
+            "shared_savings": dedup.shared_savings(),
+        });
+        println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+        return Ok(());
     }
 
+    println!(
+        "Blob files:   {} names, {} distinct files, {} on disk",
+        dedup.files,
+        dedup.unique_files,
+        format_bytes(dedup.stored_bytes)
+    );
+    println!(
+        "Linked names: {} saved ({} names share another's file)",
+        format_bytes(dedup.linked_savings()),
+        dedup.files - dedup.unique_files
+    );
+    println!(
+        "Shared blobs: {} saved ({} outputs reference {} of blobs)",
+        format_bytes(dedup.shared_savings()),
+        dedup.references,
+        format_bytes(dedup.registered_bytes)
+    );
+    println!("Total saved:  {}", format_bytes(dedup.linked_savings() + dedup.shared_savings()));
     Ok(())
 }
 
-/// Format bytes for display.
-fn format_bytes(bytes: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = KB * 1024;
-    const GB: u64 = MB * 1024;
+/// Show disk usage by table, date partition, storage tier and largest blobs.
+pub fn du(top: usize, days: usize, format: &str) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+    let usage = store.storage_breakdown(top)?;
 
-    if bytes >= GB {
-        format!("{:.1} GB", bytes as f64 / GB as f64)
-    } else if bytes >= MB {
-        format!("{:.1} MB", bytes as f64 / MB as f64)
-    } else if bytes >= KB {
-        format!("{:.1} KB", bytes as f64 / KB as f64)
-    } else {
-        format!("{} bytes", bytes)
+    let entry_json = |e: &bird::UsageEntry| {
+        serde_json::json!({ "name": e.name, "bytes": e.bytes, "files": e.files })
+    };
+    let shown_partitions = if days == 0 { usage.partitions.len() } else { days };
+
+    if format == "json" {
+        let json = serde_json::json!({
+            "total_bytes": usage.total_bytes(),
+            "database_bytes": usage.database_bytes,
+            "estimated": usage.estimated,
+            "tables": usage.tables.iter().map(entry_json).collect::<Vec<_>>(),
+            "partitions": usage.partitions.iter().take(shown_partitions).map(entry_json).collect::<Vec<_>>(),
+            "tiers": usage.tiers.iter().map(entry_json).collect::<Vec<_>>(),
+            "largest_blobs": usage.largest_blobs.iter().map(|b| serde_json::json!({
+                "content_hash": b.content_hash,
+                "bytes": b.bytes,
+                "ref_count": b.ref_count,
+                "cmd": b.cmd,
+            })).collect::<Vec<_>>(),
+            "growth": {
+                "bytes_per_day": usage.bytes_per_day,
+                "window_days": usage.growth_window_days,
+                "projected_30d": usage.projected_bytes(30),
+                "projected_365d": usage.projected_bytes(365),
+            },
+        });
+        println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+        return Ok(());
     }
-}
 
-/// Format byte reduction as percentage.
-fn format_reduction(before: u64, after: u64) -> String {
-    if before == 0 {
-        return "0%".to_string();
+    println!("Total: {} (database file {})", format_bytes(usage.total_bytes()), format_bytes(usage.database_bytes));
+    if usage.estimated {
+        println!("(DuckDB mode: table and date sizes are estimated from row counts)");
     }
-    if after >= before {
-        let increase = ((after - before) as f64 / before as f64) * 100.0;
-        format!("+{:.1}%", increase)
-    } else {
-        let reduction = ((before - after) as f64 / before as f64) * 100.0;
-        format!("-{:.1}%", reduction)
+
+    println!("\nBy table:");
+    for t in &usage.tables {
+        println!("  {:<12} {:>10}  {} files", t.name, format_bytes(t.bytes), t.files);
     }
-}
 
-/// Output ignore patterns for shell hooks (colon-separated).
-pub fn hook_ignore_patterns() -> bird::Result<()> {
-    let config = Config::load()?;
-    let patterns = config.hooks.ignore_patterns.join(":");
-    println!("{}", patterns);
-    Ok(())
-}
+    println!("\nBy date:");
+    for p in usage.partitions.iter().take(shown_partitions) {
+        println!("  {:<12} {:>10}", p.name, format_bytes(p.bytes));
+    }
+    if usage.partitions.len() > shown_partitions {
+        println!("  ... {} older partitions", usage.partitions.len() - shown_partitions);
+    }
 
-/// Output shell integration code.
-pub fn hook_init(shell: Option<&str>, inactive: bool, prompt_indicator: bool, quiet: bool) -> bird::Result<()> {
-    use crate::hooks::{self, Shell, Mode};
+    println!("\nBy tier:");
+    for t in &usage.tiers {
+        let unit = if t.name == "inline" { "outputs" } else { "files" };
+        println!("  {:<12} {:>10}  {} {}", t.name, format_bytes(t.bytes), t.files, unit);
+    }
 
-    // Auto-detect shell from $SHELL if not specified
-    let shell_str = shell
-        .map(|s| s.to_string())
-        .or_else(|| std::env::var("SHELL").ok())
-        .unwrap_or_default();
+    if !usage.largest_blobs.is_empty() {
+        println!("\nLargest blobs:");
+        for b in &usage.largest_blobs {
+            let cmd = b.cmd.as_deref().unwrap_or("(unknown)");
+            let cmd = if cmd.chars().count() > 60 {
+                format!("{}...", cmd.chars().take(57).collect::<String>())
+            } else {
+                cmd.to_string()
+            };
+            println!("  {:>10}  x{:<3} {}", format_bytes(b.bytes), b.ref_count, cmd);
+        }
+    }
 
-    let shell_type = if shell_str.contains("zsh") {
-        Shell::Zsh
-    } else if shell_str.contains("bash") {
-        Shell::Bash
+    println!("\nGrowth:");
+    if usage.growth_window_days > 0 {
+        println!(
+            "  {}/day over the last {} days; projected {} in 30 days, {} in a year",
+            format_bytes(usage.bytes_per_day),
+            usage.growth_window_days,
+            format_bytes(usage.projected_bytes(30)),
+            format_bytes(usage.projected_bytes(365))
+        );
     } else {
-        eprintln!("Unknown shell type. Use --shell zsh or --shell bash");
-        std::process::exit(1);
-    };
-
-    let mode = if inactive { Mode::Inactive } else { Mode::Active };
-
-    // Output quiet mode variable if requested
-    if quiet {
-        println!("__shq_quiet=1");
+        println!("  No recent data.");
     }
 
-    // Generate and output the hook
-    print!("{}", hooks::generate(shell_type, mode, prompt_indicator));
-
     Ok(())
 }
 
-/// Order for limiting results.
-#[derive(Clone, Copy, Debug)]
-pub enum LimitOrder {
-    Any,   // Just limit, no specific order
-    First, // First N (head)
-    Last,  // Last N (tail)
-}
+/// Export history to parquet files, optionally anonymized.
+pub fn export(dir: &std::path::Path, anonymize: bool, since: Option<&str>) -> bird::Result<()> {
+    use bird::{parse_since, ExportOptions};
 
-/// Query parsed events from invocation outputs.
-#[allow(clippy::too_many_arguments)]
-pub fn events(
-    query_str: &str,
-    severity: Option<&str>,
-    count_only: bool,
-    limit: usize,
-    order: LimitOrder,
-    reparse: bool,
-    extract: bool,
-    format: Option<&str>,
-) -> bird::Result<()> {
     let config = Config::load()?;
     let store = Store::open(config)?;
 
-    // Parse query (filters and range applied by query_invocations)
-    let query = parse_query(query_str);
-
-    // Handle reparse mode: re-extract events from outputs
-    if reparse {
-        let invocations = store.query_invocations(&query)?;
-        let mut total_events = 0;
+    let opts = ExportOptions {
+        anonymize,
+        since: since.map(parse_since).transpose()?,
+    };
+    let stats = store.export(dir, &opts)?;
 
-        for inv in &invocations {
-            // Delete existing events for this invocation
-            store.delete_events_for_invocation(&inv.id)?;
-            // Re-extract
-            let count = store.extract_events(&inv.id, format)?;
-            total_events += count;
-        }
+    println!(
+        "Exported{} to {}: {}",
+        if anonymize { " (anonymized)" } else { "" },
+        dir.display(),
+        stats
+    );
+    Ok(())
+}
 
-        println!(
-            "Re-extracted {} events from {} invocations",
-            total_events,
-            invocations.len()
-        );
-        return Ok(());
+/// Take the maintenance lock for `intent`, unless this is a dry run.
+fn maintenance_lease(config: &Config, intent: &str, dry_run: bool) -> bird::Result<Option<bird::locks::Lease>> {
+    if dry_run {
+        return Ok(None);
     }
+    bird::locks::Lease::acquire(config, bird::locks::MAINTENANCE_LOCK, intent).map(Some)
+}
 
-    // Get invocations matching query (with filters applied)
-    let invocations = store.query_invocations(&query)?;
-    if invocations.is_empty() {
-        println!("No invocations found.");
-        return Ok(());
-    }
+/// Move old data from recent to archive.
+pub fn archive(days: u32, dry_run: bool, extract_first: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let _lease = maintenance_lease(&config, "archiving", dry_run)?;
+    let store = Store::open(config)?
+        .with_progress(crate::progress::sink(false))
+        .with_cancel(crate::cancel::token());
 
-    // Extract events if requested and not already extracted
-    if extract {
-        for inv in &invocations {
-            let existing = store.event_count(&EventFilters {
-                invocation_id: Some(inv.id.clone()),
-                ..Default::default()
-            })?;
-            if existing == 0 {
-                let _ = store.extract_events(&inv.id, format);
+    // Optionally extract events from invocations before archiving
+    if extract_first && !dry_run {
+        println!("Extracting events from invocations to be archived...");
+        let cutoff_date = chrono::Utc::now().date_naive() - chrono::Duration::days(days as i64);
+        let invocations = store.invocations_without_events(Some(cutoff_date), None)?;
+
+        if !invocations.is_empty() {
+            let mut total_events = 0;
+            for inv in &invocations {
+                let count = store.extract_events(&inv.id, None)?;
+                total_events += count;
             }
+            println!(
+                "  Extracted {} events from {} invocations",
+                total_events,
+                invocations.len()
+            );
         }
     }
 
-    // Build filters
-    let inv_ids: Vec<String> = invocations.iter().map(|inv| inv.id.clone()).collect();
-    let filters = EventFilters {
-        severity: severity.map(|s| s.to_string()),
-        invocation_ids: Some(inv_ids),
-        limit: Some(limit),
-        // TODO: Add order support to EventFilters when needed
-        ..Default::default()
-    };
-    let _ = order; // Will be used when EventFilters supports ordering
+    let stats = store.archive_old_data(days, dry_run)?;
 
-    // Count only mode
-    if count_only {
-        let count = store.event_count(&filters)?;
-        println!("{}", count);
-        return Ok(());
+    if dry_run {
+        print!("{}", stats.plan);
+    } else if stats.partitions_archived > 0 {
+        println!(
+            "Archived {} partitions ({} files, {})",
+            stats.partitions_archived,
+            stats.files_moved,
+            format_bytes(stats.bytes_moved)
+        );
+    } else {
+        println!("Nothing to archive.");
     }
 
-    // Query events
-    let events = store.query_events(&filters)?;
+    Ok(())
+}
 
-    if events.is_empty() {
-        println!("No events found.");
-        return Ok(());
+/// Write a year of archived data to a bundle and detach it from the store.
+pub fn archive_bundle(path: &std::path::Path, year: Option<i32>, keep: bool, dry_run: bool) -> bird::Result<()> {
+    let year = match year {
+        Some(year) => year,
+        None => path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<i32>().ok())
+            .ok_or_else(|| {
+                bird::Error::Config(format!("Can't tell the year from {}; pass --year", path.display()))
+            })?,
+    };
+    if path.exists() && !dry_run {
+        return Err(bird::Error::Config(format!("{} already exists", path.display())));
     }
 
-    // Display events
+    let config = Config::load()?;
+    let _lease = maintenance_lease(&config, "bundling", dry_run)?;
+    let store = Store::open(config)?.with_cancel(crate::cancel::token());
+    let stats = store.bundle_year(year, path, keep, dry_run)?;
+
+    let verb = if dry_run { "Would bundle" } else { "Bundled" };
     println!(
-        "{:<8} {:<40} {:<30} MESSAGE",
-        "SEVERITY", "FILE:LINE", "CODE"
+        "{} {}: {} partitions, {} files, {} blobs ({})",
+        verb,
+        year,
+        stats.partitions,
+        stats.files,
+        stats.blobs,
+        format_bytes(stats.bytes)
     );
-    println!("{}", "-".repeat(100));
-
-    for event in &events {
-        let sev = event.severity.as_deref().unwrap_or("-");
-        let location = match (&event.ref_file, event.ref_line) {
-            (Some(f), Some(l)) => format!("{}:{}", truncate_path(f, 35), l),
-            (Some(f), None) => truncate_path(f, 40).to_string(),
-            _ => "-".to_string(),
-        };
-        let code = event
-            .error_code
-            .as_deref()
-            .or(event.test_name.as_deref())
-            .unwrap_or("-");
-        let message = event
-            .message
-            .as_deref()
-            .map(|m| truncate_string(m, 50))
-            .unwrap_or_else(|| "-".to_string());
-
-        // Color based on severity
-        let severity_display = match sev {
-            "error" => format!("\x1b[31m{:<8}\x1b[0m", sev),
-            "warning" => format!("\x1b[33m{:<8}\x1b[0m", sev),
-            _ => format!("{:<8}", sev),
-        };
+    if stats.blobs_skipped > 0 {
+        println!("  {} referenced blobs aren't stored locally and were left out", stats.blobs_skipped);
+    }
+    if dry_run {
+        return Ok(());
+    }
+    println!("  Wrote {} ({})", path.display(), format_bytes(stats.bundle_bytes));
+    if !keep {
+        println!("  Detached {} files from the store (shq undo restores them)", stats.files_detached);
+    }
+    println!("  Query it again with: shq mount {}", path.display());
+    Ok(())
+}
 
+/// Move blobs not accessed recently to the cold tier.
+pub fn tier(days: Option<u32>, dry_run: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let _lease = maintenance_lease(&config, "blob tiering", dry_run)?;
+    let days = days.unwrap_or(config.blobs.tier_after_days);
+    let store = Store::open(config)?
+        .with_progress(crate::progress::sink(false))
+        .with_cancel(crate::cancel::token());
+
+    let stats = store.tier_blobs(days, dry_run)?;
+    if stats.blobs == 0 {
+        println!("No blobs unused for {} days.", days);
+    } else {
         println!(
-            "{} {:<40} {:<30} {}",
-            severity_display, location, code, message
+            "{} {} blobs ({} files, {}) to the cold tier",
+            if dry_run { "Would move" } else { "Moved" },
+            stats.blobs,
+            stats.files,
+            format_bytes(stats.bytes)
         );
     }
-
-    println!("\n({} events)", events.len());
-
     Ok(())
 }
 
-/// Extract events from an invocation's output.
-#[allow(clippy::too_many_arguments)]
-pub fn extract_events(
-    selector: &str,
-    format: Option<&str>,
-    quiet: bool,
-    force: bool,
-    all: bool,
-    since: Option<&str>,
-    limit: Option<usize>,
-    dry_run: bool,
-) -> bird::Result<()> {
+/// Move outputs stored on the wrong side of the inline threshold.
+pub fn rebalance_blobs(threshold: Option<usize>, dry_run: bool) -> bird::Result<()> {
     let config = Config::load()?;
-    let store = Store::open(config)?;
+    let _lease = maintenance_lease(&config, "blob rebalancing", dry_run)?;
+    let threshold = threshold.unwrap_or(config.inline_threshold);
+    let store = Store::open(config)?
+        .with_progress(crate::progress::sink(false))
+        .with_cancel(crate::cancel::token());
+
+    let stats = store.rebalance_blobs(Some(threshold), dry_run)?;
+    if stats.inlined == 0 && stats.externalized == 0 {
+        println!("All outputs match the {} inline threshold.", format_bytes(threshold as u64));
+        return Ok(());
+    }
+    let verb = if dry_run { "Would move" } else { "Moved" };
+    println!("{} {} blob outputs inline and {} inline outputs to blobs", verb, stats.inlined, stats.externalized);
+    if !dry_run {
+        println!(
+            "  {} output files rewritten, {} unused blob files trashed (shq undo restores them)",
+            stats.files_rewritten, stats.blobs_removed
+        );
+    }
+    Ok(())
+}
 
-    // Backfill mode: extract from all invocations without events
-    if all {
-        return extract_events_backfill(&store, format, quiet, since, limit, dry_run);
+/// Move repeated inline outputs to shared blob files.
+pub fn dedup_outputs(min_bytes: Option<usize>, dry_run: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let _lease = maintenance_lease(&config, "output dedup", dry_run)?;
+    let store = Store::open(config)?
+        .with_progress(crate::progress::sink(false))
+        .with_cancel(crate::cancel::token());
+
+    let stats = store.dedup_outputs(min_bytes, dry_run)?;
+    if stats.outputs == 0 {
+        println!("No repeated inline outputs to deduplicate.");
+        return Ok(());
     }
+    let verb = if dry_run { "Would move" } else { "Moved" };
+    println!(
+        "{} {} inline outputs ({} distinct) to shared blobs, saving {}",
+        verb,
+        stats.outputs,
+        stats.contents,
+        format_bytes(stats.bytes_saved)
+    );
+    if !dry_run {
+        println!("  {} output files rewritten", stats.files_rewritten);
+    }
+    Ok(())
+}
 
-    // Single invocation mode
-    let invocation_id = resolve_invocation_id(&store, selector)?;
+/// Check stored output content; fails if any output has a problem.
+pub fn fsck(verify_content: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?
+        .with_progress(crate::progress::sink(false))
+        .with_cancel(crate::cancel::token());
 
-    // Check if events already exist
-    let existing_count = store.event_count(&EventFilters {
-        invocation_id: Some(invocation_id.clone()),
-        ..Default::default()
-    })?;
+    let report = store.fsck(verify_content)?;
+    for issue in &report.issues {
+        println!(
+            "{}  {}  {}: {}",
+            &issue.invocation_id[..8.min(issue.invocation_id.len())],
+            issue.stream,
+            issue.storage_ref.chars().take(60).collect::<String>(),
+            issue.problem
+        );
+    }
 
-    if existing_count > 0 && !force {
-        if !quiet {
-            println!(
-                "Events already exist for invocation {} ({} events). Use --force to re-extract.",
-                invocation_id, existing_count
-            );
-        }
-        return Ok(());
+    let what = if verify_content { "verified" } else { "checked" };
+    if report.issues.is_empty() {
+        println!("{} stored outputs {}, no problems found.", report.checked, what);
+        Ok(())
+    } else {
+        Err(bird::Error::Storage(format!(
+            "{} of {} stored outputs {} have problems",
+            report.issues.len(),
+            report.checked,
+            what
+        )))
     }
+}
 
-    // Delete existing events if forcing
-    if force && existing_count > 0 {
-        store.delete_events_for_invocation(&invocation_id)?;
+/// Check that the hook, binaries, spool and store are versions that work
+/// together, and explain which one is old if they aren't.
+pub fn doctor() -> bird::Result<()> {
+    use bird::version::{self, HOOK_VERSION, SHQ_VERSION, SPOOL_VERSION};
+    use std::cmp::Ordering;
+
+    let config = Config::load()?;
+    let mut problems: Vec<String> = Vec::new();
+    let exe = std::env::current_exe().ok();
+
+    println!(
+        "{:<14} {} (hook {}, spool {}, schema {})",
+        "shq",
+        SHQ_VERSION,
+        HOOK_VERSION,
+        SPOOL_VERSION,
+        bird::BIRD_SCHEMA_VERSION
+    );
+
+    // What the hook runs: the first of each on PATH
+    for name in ["shq", "shq-capture"] {
+        let Some(path) = crate::bench::find_on_path(name) else {
+            if name == "shq" {
+                problems.push("shq is not on PATH; the hook can't run it".to_string());
+            }
+            println!("{:<14} not on PATH", name);
+            continue;
+        };
+        let this = exe.as_ref().is_some_and(|exe| same_file(exe, &path));
+        let found = if this { Some(SHQ_VERSION.to_string()) } else { binary_version(&path) };
+        let Some(found) = found else {
+            println!("{:<14} unknown version ({})", name, path.display());
+            continue;
+        };
+        let note = match version::compare(&found, SHQ_VERSION) {
+            Ordering::Equal => "",
+            Ordering::Less => {
+                problems.push(format!(
+                    "{} on PATH ({}) is {}, older than this shq ({}): the hook runs it; reinstall it",
+                    name,
+                    path.display(),
+                    found,
+                    SHQ_VERSION
+                ));
+                ", older"
+            }
+            Ordering::Greater => {
+                problems.push(format!(
+                    "{} on PATH ({}) is {}, newer than this shq ({}): this one is old",
+                    name,
+                    path.display(),
+                    found,
+                    SHQ_VERSION
+                ));
+                ", newer"
+            }
+        };
+        println!("{:<14} {} ({}{})", name, found, path.display(), note);
     }
 
-    // Extract events
-    let count = store.extract_events(&invocation_id, format)?;
+    // The hook this shell loaded, and the ones rc files load
+    match version::shell_hook_version() {
+        Some(loaded) => {
+            println!("{:<14} {}", "hook (shell)", loaded);
+            problems.extend(version::hook_mismatch(loaded));
+        }
+        None => println!("{:<14} not loaded in this shell", "hook (shell)"),
+    }
+    if let Ok((files, home)) = hook_rc_files() {
+        for found in crate::hook_install::scan(&files, &home) {
+            let location = format!("{}:{}", found.path.display(), found.line);
+            match found.install {
+                crate::hook_install::Install::Eval => println!("{:<14} current ({}, eval)", "hook (rc)", location),
+                crate::hook_install::Install::Static { version, .. } => {
+                    println!("{:<14} {} ({}, saved copy)", "hook (rc)", version, location);
+                    if found.is_stale() {
+                        problems.push(format!(
+                            "the hook saved in {} is version {}, older than this shq's ({}): run `shq hook upgrade`",
+                            location, version, HOOK_VERSION
+                        ));
+                    }
+                }
+            }
+        }
+    }
 
-    if !quiet {
-        if count > 0 {
-            println!("Extracted {} events from invocation {}", count, invocation_id);
-        } else {
-            println!("No events found in invocation {}", invocation_id);
+    // Saves waiting in the spool
+    let pending = bird::spool::pending(&config)?;
+    let newer = pending
+        .iter()
+        .filter(|path| matches!(bird::spool::read(path), Err(bird::Error::NewerFormat { .. })))
+        .count();
+    println!("{:<14} {} pending", "spool", pending.len());
+    if newer > 0 {
+        problems.push(format!(
+            "{} spooled save(s) were written by a newer shq (shq-capture?) and wait for it: upgrade shq",
+            newer
+        ));
+    }
+
+    // The store
+    match bird::version::store_versions(&config) {
+        Ok(versions) => {
+            let schema = versions.schema.as_deref().unwrap_or("none");
+            println!("{:<14} {}", "store schema", schema);
+            if let Some(found) = versions.schema.filter(|found| found != bird::BIRD_SCHEMA_VERSION) {
+                let mismatch = bird::Error::SchemaVersionMismatch { found, expected: bird::BIRD_SCHEMA_VERSION.to_string() };
+                problems.push(format!("{}: {}", mismatch, mismatch.hint().unwrap_or_default()));
+            }
+            match &versions.shq {
+                Some(newest) => {
+                    println!("{:<14} {}", "store used by", newest);
+                    if version::compare(newest, SHQ_VERSION) == Ordering::Greater {
+                        problems.push(format!(
+                            "the store was last used by shq {}, newer than this one ({}): what it wrote may not be readable here; upgrade shq",
+                            newest, SHQ_VERSION
+                        ));
+                    }
+                }
+                None => println!("{:<14} unknown", "store used by"),
+            }
+        }
+        Err(e) => {
+            println!("{:<14} unreadable", "store");
+            problems.push(format!("couldn't read the store's versions: {}", e));
         }
     }
 
-    Ok(())
+    println!();
+    if problems.is_empty() {
+        println!("No problems found.");
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("- {}", problem);
+    }
+    Err(bird::Error::Config(format!("{} problem(s) found", problems.len())))
 }
 
-/// Backfill events from all invocations that don't have events yet.
-fn extract_events_backfill(
-    store: &Store,
-    format: Option<&str>,
-    quiet: bool,
-    since: Option<&str>,
-    limit: Option<usize>,
-    dry_run: bool,
-) -> bird::Result<()> {
-    use chrono::NaiveDate;
-
-    // Parse since date if provided
-    let since_date = if let Some(date_str) = since {
-        Some(
-            NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
-                .map_err(|e| bird::Error::Config(format!("Invalid date '{}': {}", date_str, e)))?,
-        )
-    } else {
-        None
-    };
-
-    // Get invocations without events
-    let invocations = store.invocations_without_events(since_date, limit)?;
+/// Turn self-profiling on or off, clear it, or report the slowest phases
+/// of the commands recorded.
+pub fn self_profile(on: bool, off: bool, clear: bool, command: Option<&str>, format: &str) -> bird::Result<()> {
+    use bird::profile::{self, ProfileRecord};
+    use crate::bench::{format_ms, Samples};
+    use std::collections::BTreeMap;
+
+    fn samples(name: &str, of: &[&ProfileRecord], ms: impl Fn(&ProfileRecord) -> f64) -> Samples {
+        let mut samples = Samples::new(name);
+        for r in of {
+            samples.push(std::time::Duration::from_secs_f64(ms(r).max(0.0) / 1000.0));
+        }
+        samples
+    }
 
-    if invocations.is_empty() {
-        if !quiet {
-            println!("No invocations found without events.");
+    /// Percentage of the time of `of` spent in `phase`.
+    fn share(phase: Phase, of: &[&ProfileRecord]) -> f64 {
+        let total: f64 = of.iter().map(|r| r.total_ms).sum();
+        if total > 0.0 {
+            of.iter().map(|r| r.phase_ms(phase)).sum::<f64>() / total * 100.0
+        } else {
+            0.0
         }
-        return Ok(());
     }
 
-    if dry_run {
-        println!("Would extract events from {} invocations:", invocations.len());
-        for inv in &invocations {
-            let cmd_preview: String = inv.cmd.chars().take(60).collect();
-            let suffix = if inv.cmd.len() > 60 { "..." } else { "" };
-            println!("  {} {}{}", &inv.id[..8], cmd_preview, suffix);
+    let mut config = Config::load()?;
+    if on || off {
+        config.self_profile = on;
+        config.save()?;
+        if on {
+            println!("Recording the phase timings of every shq command in {}", config.self_profile_path().display());
+            println!("Nothing is sent anywhere. Report with `shq self-profile`; stop with `shq self-profile --off`.");
+        } else {
+            println!("Self-profiling stopped (recorded timings are kept; --clear deletes them)");
         }
         return Ok(());
     }
+    if clear {
+        profile::clear(&config)?;
+        println!("Recorded timings deleted");
+        return Ok(());
+    }
 
-    let mut total_events = 0;
-    let mut processed = 0;
+    let records: Vec<_> = profile::read(&config)?
+        .into_iter()
+        .filter(|r| command.is_none_or(|c| r.command == c))
+        .collect();
+    let all: Vec<&ProfileRecord> = records.iter().collect();
+    let total_ms: f64 = records.iter().map(|r| r.total_ms).sum();
 
-    for inv in &invocations {
-        let count = store.extract_events(&inv.id, format)?;
-        total_events += count;
-        processed += 1;
+    // Commands, slowest (P95) first
+    let mut by_command: BTreeMap<&str, Vec<&ProfileRecord>> = BTreeMap::new();
+    for r in &records {
+        by_command.entry(r.command.as_str()).or_default().push(r);
+    }
+    let mut commands: Vec<(&str, Samples, Phase, f64)> = by_command
+        .iter()
+        .map(|(name, runs)| {
+            let slowest = Phase::ALL
+                .into_iter()
+                .max_by(|a, b| share(*a, runs).total_cmp(&share(*b, runs)))
+                .unwrap_or(Phase::Query);
+            (*name, samples(name, runs, |r| r.total_ms), slowest, share(slowest, runs))
+        })
+        .collect();
+    commands.sort_by(|a, b| b.1.percentile(95.0).cmp(&a.1.percentile(95.0)));
+
+    if format == "json" {
+        let phases: Vec<_> = Phase::ALL
+            .into_iter()
+            .map(|phase| {
+                let s = samples(phase.name(), &all, |r| r.phase_ms(phase));
+                serde_json::json!({
+                    "phase": phase.name(),
+                    "share_pct": share(phase, &all),
+                    "p50_ms": s.percentile(50.0).as_secs_f64() * 1000.0,
+                    "p95_ms": s.percentile(95.0).as_secs_f64() * 1000.0,
+                    "max_ms": s.max().as_secs_f64() * 1000.0,
+                })
+            })
+            .collect();
+        let commands: Vec<_> = commands
+            .iter()
+            .map(|(name, s, slowest, pct)| {
+                serde_json::json!({
+                    "command": name,
+                    "runs": by_command[name].len(),
+                    "p50_ms": s.percentile(50.0).as_secs_f64() * 1000.0,
+                    "p95_ms": s.percentile(95.0).as_secs_f64() * 1000.0,
+                    "slowest_phase": slowest.name(),
+                    "slowest_phase_pct": pct,
+                })
+            })
+            .collect();
+        let json = serde_json::json!({
+            "version": bird::version::SHQ_VERSION,
+            "recording": config.self_profile,
+            "commands_recorded": records.len(),
+            "total_ms": total_ms,
+            "phases": phases,
+            "by_command": commands,
+        });
+        println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+        return Ok(());
+    }
 
-        if !quiet && count > 0 {
-            println!("  {} events from: {}", count, truncate_cmd(&inv.cmd, 50));
+    if records.is_empty() {
+        println!("No timings recorded{}.", if config.self_profile { " yet" } else { "" });
+        if !config.self_profile {
+            println!("Start recording with: shq self-profile --on");
         }
+        return Ok(());
     }
 
-    if !quiet {
+    let (first, last) = (&records[0].at, &records[records.len() - 1].at);
+    println!(
+        "{} commands from {} to {} (shq {}; recording {})",
+        records.len(),
+        first.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+        last.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+        bird::version::SHQ_VERSION,
+        if config.self_profile { "on" } else { "off" }
+    );
+    println!();
+    println!("{:<10} {:>6} {:>10} {:>10} {:>10}", "PHASE", "SHARE", "P50", "P95", "MAX");
+    for phase in Phase::ALL {
+        let s = samples(phase.name(), &all, |r| r.phase_ms(phase));
         println!(
-            "Extracted {} events from {} invocations.",
-            total_events, processed
+            "{:<10} {:>5.0}% {:>10} {:>10} {:>10}",
+            phase.name(),
+            share(phase, &all),
+            format_ms(s.percentile(50.0)),
+            format_ms(s.percentile(95.0)),
+            format_ms(s.max())
         );
     }
+    let totals = samples("total", &all, |r| r.total_ms);
+    println!(
+        "{:<10} {:>6} {:>10} {:>10} {:>10}",
+        "total",
+        "",
+        format_ms(totals.percentile(50.0)),
+        format_ms(totals.percentile(95.0)),
+        format_ms(totals.max())
+    );
 
+    println!();
+    println!("{:<20} {:>5} {:>10} {:>10}  SLOWEST PHASE", "COMMAND", "RUNS", "P50", "P95");
+    for (name, s, slowest, pct) in commands.iter().take(10) {
+        println!(
+            "{:<20} {:>5} {:>10} {:>10}  {} ({:.0}%)",
+            name,
+            by_command[name].len(),
+            format_ms(s.percentile(50.0)),
+            format_ms(s.percentile(95.0)),
+            slowest.name(),
+            pct
+        );
+    }
     Ok(())
 }
 
-/// Truncate a command string for display.
-fn truncate_cmd(cmd: &str, max_len: usize) -> String {
-    if cmd.len() <= max_len {
-        cmd.to_string()
-    } else {
-        format!("{}...", &cmd[..max_len])
-    }
+/// The version `path --version` reports (`shq 0.1.10`).
+fn binary_version(path: &std::path::Path) -> Option<String> {
+    let output = Command::new(path).arg("--version").stdin(std::process::Stdio::null()).output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.split_whitespace().nth(1).map(str::to_string)
 }
 
-/// Resolve a selector (negative offset, ~N syntax, or UUID) to an invocation ID.
-fn resolve_invocation_id(store: &Store, selector: &str) -> bird::Result<String> {
-    // Handle ~N syntax (e.g., ~1 for most recent, ~2 for second-to-last)
-    if let Some(stripped) = selector.strip_prefix('~') {
-        if let Ok(n) = stripped.parse::<usize>() {
-            if n > 0 {
-                let invocations = store.recent_invocations(n)?;
-                if let Some(inv) = invocations.last() {
-                    return Ok(inv.id.clone());
-                } else {
-                    return Err(bird::Error::NotFound(format!(
-                        "No invocation found at offset ~{}",
-                        n
-                    )));
-                }
-            }
-        }
-    }
-
-    // Handle negative offset (e.g., -1 for last, -2 for second-to-last)
-    if let Ok(offset) = selector.parse::<i64>() {
-        if offset < 0 {
-            let n = (-offset) as usize;
-            let invocations = store.recent_invocations(n)?;
-            if let Some(inv) = invocations.last() {
-                return Ok(inv.id.clone());
-            } else {
-                return Err(bird::Error::NotFound(format!(
-                    "No invocation found at offset {}",
-                    offset
-                )));
-            }
-        }
-    }
-
-    // Try short ID lookup
-    if let Some(id) = try_find_by_id(store, selector)? {
-        return Ok(id);
+fn same_file(a: &std::path::Path, b: &std::path::Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
     }
-
-    // Assume it's a full UUID
-    Ok(selector.to_string())
 }
 
-/// Truncate a path for display, keeping the filename visible.
-fn truncate_path(path: &str, max_len: usize) -> &str {
-    if path.len() <= max_len {
-        return path;
+/// Show (or rewrite) the sync token.
+pub fn sync_token(refresh: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    if config.storage_mode != bird::StorageMode::Parquet {
+        return Err(bird::Error::Config(
+            "The sync token covers parquet-mode data files; this store is in duckdb mode".to_string(),
+        ));
     }
-    // Try to keep at least the filename
-    if let Some(pos) = path.rfind('/') {
-        let filename = &path[pos + 1..];
-        if filename.len() < max_len {
-            return &path[path.len() - max_len..];
+    let token = if refresh {
+        Store::open(config.clone())?.write_sync_token()?
+    } else {
+        match bird::SyncToken::read(&config.bird_root) {
+            Some(token) => token,
+            None => Store::open(config.clone())?.write_sync_token()?,
         }
-    }
-    &path[path.len() - max_len..]
+    };
+    println!("Token:   {}", config.sync_token_path().display());
+    println!("Written: {}", token.written_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S"));
+    println!("Files:   {} ({})", token.files, format_bytes(token.bytes));
+    println!("Digest:  {}", token.digest);
+    Ok(())
 }
 
-/// Truncate a string for display.
-fn truncate_string(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        s.to_string()
+/// Check that a copy of a store holds a state the store was in.
+pub fn verify_mirror(path: &std::path::Path) -> bird::Result<()> {
+    let report = bird::verify_mirror(path)?;
+    println!("Files:   {} ({})", report.actual.files, format_bytes(report.actual.bytes));
+    match &report.token {
+        Some(token) if token.same_files(&report.actual) => println!(
+            "Token:   matches (state of {})",
+            token.written_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S")
+        ),
+        Some(token) => println!(
+            "Token:   does not match: the source had {} files ({}) when it was written; it changed during the copy",
+            token.files,
+            format_bytes(token.bytes)
+        ),
+        None => println!("Token:   missing (copied from a duckdb-mode store, or one that never wrote a token)"),
+    }
+    for file in &report.temp_files {
+        println!("Temp file: {} (exclude '.tmp.*' from the copy)", file);
+    }
+    if let Some(e) = &report.database_error {
+        println!("Database: unreadable: {}", e);
+    }
+
+    if report.is_consistent() {
+        println!("{} is a consistent copy.", path.display());
+        Ok(())
     } else {
-        format!("{}...", &s[..max_len - 3])
+        Err(bird::Error::Storage(format!(
+            "{} is not a consistent copy; copy it again (only new files are transferred)",
+            path.display()
+        )))
     }
 }
 
-/// Check if a string looks like a hex ID (short or full UUID).
-fn looks_like_hex_id(s: &str) -> bool {
-    // Must be at least 4 chars and only contain hex digits and dashes
-    s.len() >= 4 && s.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
-}
-
-/// Try to find an invocation by tag, short ID, or full ID.
-/// Returns Some(full_id) if found, None if not found or query doesn't look like an ID/tag.
-fn try_find_by_id(store: &Store, query_str: &str) -> bird::Result<Option<String>> {
-    let trimmed = query_str.trim();
+/// Restore the data removed by the last destructive operation.
+pub fn undo(list: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let grace_days = config.trash.grace_days;
+    let _lease = maintenance_lease(&config, "undo", list)?;
+    let store = Store::open(config)?;
 
-    // Check for tag lookup (:tagname)
-    if let Some(tag) = trimmed.strip_prefix(':') {
-        if let Some(id) = store.find_by_tag(tag)? {
-            return Ok(Some(id));
+    if list {
+        let entries = store.trash_entries()?;
+        if entries.is_empty() {
+            println!("Nothing to undo.");
         }
-        // Tag not found - this is an explicit error, not a fallback to query
-        return Err(bird::Error::NotFound(format!("Tag '{}' not found", tag)));
+        for entry in entries {
+            println!(
+                "{}  {} ({} files, {})",
+                entry.created_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                entry.operation,
+                entry.files.len(),
+                format_bytes(entry.bytes)
+            );
+        }
+        return Ok(());
     }
 
-    if !looks_like_hex_id(trimmed) {
-        return Ok(None);
+    match store.undo_last()? {
+        Some(entry) => println!(
+            "Restored {} files ({}) removed by: {}",
+            entry.files.len(),
+            format_bytes(entry.bytes),
+            entry.operation
+        ),
+        None => println!("Nothing to undo (removed data is kept for {} days).", grace_days),
     }
+    Ok(())
+}
 
-    // Try exact match first (full UUID)
-    let result = store.query(&format!(
-        "SELECT id::VARCHAR FROM invocations WHERE id::VARCHAR = '{}' LIMIT 1",
-        trimmed
-    ))?;
+/// Take a store snapshot now, or list snapshots.
+pub fn snapshot(list: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
 
-    if !result.rows.is_empty() {
-        return Ok(Some(result.rows[0][0].clone()));
+    if list {
+        let snapshots = store.snapshots()?;
+        if snapshots.is_empty() {
+            println!("No snapshots.");
+        }
+        for snapshot in snapshots {
+            println!("{}  {} files, {}", snapshot.date, snapshot.files.len(), format_bytes(snapshot.bytes));
+        }
+        return Ok(());
     }
 
-    // Try suffix match (short ID - we show last 8 chars of UUIDv7)
-    let result = store.query(&format!(
-        "SELECT id::VARCHAR FROM invocations WHERE suffix(id::VARCHAR, '{}') ORDER BY timestamp DESC LIMIT 1",
-        trimmed
-    ))?;
-
-    if !result.rows.is_empty() {
-        return Ok(Some(result.rows[0][0].clone()));
+    let snapshot = store.snapshot()?;
+    println!(
+        "Snapshot {}: {} files, {}",
+        snapshot.date,
+        snapshot.files.len(),
+        format_bytes(snapshot.bytes)
+    );
+    Ok(())
+}
+
+/// Compact parquet files to reduce storage and improve performance.
+#[allow(clippy::too_many_arguments)]
+pub fn compact(
+    file_threshold: usize,
+    recompact_threshold: usize,
+    consolidate: bool,
+    extract_first: bool,
+    session: Option<&str>,
+    today_only: bool,
+    quiet: bool,
+    recent_only: bool,
+    archive_only: bool,
+    dry_run: bool,
+) -> bird::Result<()> {
+    let config = Config::load()?;
+    // Hook-triggered runs skip rather than queue up behind another one
+    let _lease = match maintenance_lease(&config, "compaction", dry_run) {
+        Err(bird::Error::Busy { .. }) if quiet => return Ok(()),
+        lease => lease?,
+    };
+    let store = Store::open(config)?
+        .with_progress(crate::progress::sink(quiet))
+        .with_cancel(crate::cancel::token());
+
+    // Shell hooks compact after commands, which makes this the daily trigger
+    if !dry_run {
+        if let Err(e) = store.snapshot_if_due() {
+            tracing::warn!(error = %e, "daily snapshot failed");
+        }
+    }
+
+    // Extract events first if requested
+    if extract_first && !dry_run {
+        if !quiet {
+            println!("Extracting events from invocations before compacting...");
+        }
+        let invocations = store.invocations_without_events(None, None)?;
+        let mut extracted = 0;
+        for inv in &invocations {
+            let count = store.extract_events(&inv.id, None)?;
+            extracted += count;
+        }
+        if !quiet && extracted > 0 {
+            println!("  Extracted {} events from {} invocations\n", extracted, invocations.len());
+        }
+    }
+
+    let opts = CompactOptions {
+        file_threshold,
+        recompact_threshold,
+        consolidate,
+        dry_run,
+        session_filter: session.map(|s| s.to_string()),
+    };
+
+    // Session-specific compaction (lightweight, used by shell hooks)
+    if let Some(session_id) = session {
+        let stats = if today_only {
+            // today_only uses legacy API (no recompact support for shell hooks)
+            store.compact_session_today(session_id, file_threshold, dry_run)?
+        } else {
+            store.compact_for_session_with_opts(session_id, &opts)?
+        };
+
+        if dry_run {
+            if !quiet {
+                print!("{}", stats.plan);
+            }
+        } else if stats.sessions_compacted > 0 {
+            let action = if consolidate { "Consolidated" } else { "Compacted" };
+            println!("{} session '{}':", action, session_id);
+            println!("  {} files -> {} files", stats.files_before, stats.files_after);
+            println!(
+                "  {} -> {} ({})",
+                format_bytes(stats.bytes_before),
+                format_bytes(stats.bytes_after),
+                format_reduction(stats.bytes_before, stats.bytes_after)
+            );
+        } else if !quiet {
+            println!("Nothing to compact for session '{}'.", session_id);
+        }
+        return Ok(());
+    }
+
+    // Global compaction
+    let mut total_stats = bird::CompactStats::default();
+
+    if !archive_only {
+        let stats = store.compact_recent_with_opts(&opts)?;
+        total_stats.add(&stats);
+    }
+
+    if !recent_only {
+        let stats = store.compact_archive_with_opts(&opts)?;
+        total_stats.add(&stats);
+    }
+
+    if dry_run {
+        if !quiet {
+            print!("{}", total_stats.plan);
+        }
+    } else if total_stats.sessions_compacted > 0 {
+        let action = if consolidate { "Consolidated" } else { "Compacted" };
+        println!(
+            "{} {} sessions across {} partitions",
+            action, total_stats.sessions_compacted, total_stats.partitions_compacted
+        );
+        println!(
+            "  {} files -> {} files",
+            total_stats.files_before, total_stats.files_after
+        );
+        println!(
+            "  {} -> {} ({})",
+            format_bytes(total_stats.bytes_before),
+            format_bytes(total_stats.bytes_after),
+            format_reduction(total_stats.bytes_before, total_stats.bytes_after)
+        );
+    } else if !quiet {
+        println!("Nothing to compact.");
+    }
+
+    Ok(())
+}
+
+/// Format bytes for display.
+fn format_bytes(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = KB * 1024;
+    const GB: u64 = MB * 1024;
+
+    if bytes >= GB {
+        format!("{:.1} GB", bytes as f64 / GB as f64)
+    } else if bytes >= MB {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    } else if bytes >= KB {
+        format!("{:.1} KB", bytes as f64 / KB as f64)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+/// Format a duration in milliseconds for display.
+fn format_duration_ms(ms: i64) -> String {
+    if ms >= 3_600_000 {
+        format!("{:.1}h", ms as f64 / 3_600_000.0)
+    } else if ms >= 60_000 {
+        format!("{:.1}m", ms as f64 / 60_000.0)
+    } else if ms >= 1_000 {
+        format!("{:.1}s", ms as f64 / 1_000.0)
+    } else {
+        format!("{}ms", ms)
+    }
+}
+
+/// Format byte reduction as percentage.
+fn format_reduction(before: u64, after: u64) -> String {
+    if before == 0 {
+        return "0%".to_string();
+    }
+    if after >= before {
+        let increase = ((after - before) as f64 / before as f64) * 100.0;
+        format!("+{:.1}%", increase)
+    } else {
+        let reduction = ((before - after) as f64 / before as f64) * 100.0;
+        format!("-{:.1}%", reduction)
+    }
+}
+
+/// Pause capture in every shell, optionally for a limited time.
+pub fn capture_off(duration: Option<&str>, quiet: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+
+    let until = duration
+        .map(bird::privacy::parse_pause_duration)
+        .transpose()?
+        .map(|d| chrono::Utc::now() + d);
+    bird::privacy::pause(&config, until)?;
+
+    if !quiet {
+        match until {
+            Some(t) => println!(
+                "Capture paused until {} (resume early with: shq on)",
+                t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S")
+            ),
+            None => println!("Capture paused (resume with: shq on)"),
+        }
+    }
+    Ok(())
+}
+
+/// Resume capture after `shq off`.
+pub fn capture_on(quiet: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let was_paused = bird::privacy::resume(&config)?;
+
+    if !quiet {
+        if was_paused {
+            println!("Capture resumed");
+        } else {
+            println!("Capture was not paused");
+        }
+    }
+    Ok(())
+}
+
+/// Output ignore patterns for shell hooks (colon-separated).
+pub fn hook_ignore_patterns() -> bird::Result<()> {
+    let config = Config::load()?;
+    let patterns = config.hooks.ignore_patterns.join(":");
+    println!("{}", patterns);
+    Ok(())
+}
+
+/// Output shell integration code.
+pub fn hook_init(shell: Option<&str>, inactive: bool, prompt_indicator: bool, quiet: bool) -> bird::Result<()> {
+    use crate::hooks::{self, Mode};
+
+    let Some(shell_type) = detect_shell(shell) else {
+        eprintln!("Unknown shell type. Use --shell zsh or --shell bash");
+        std::process::exit(1);
+    };
+
+    let mode = if inactive { Mode::Inactive } else { Mode::Active };
+
+    // Output quiet mode variable if requested
+    if quiet {
+        println!("__shq_quiet=1");
+    }
+
+    // Generate and output the hook
+    print!("{}", hooks::generate(shell_type, mode, prompt_indicator));
+
+    Ok(())
+}
+
+/// Where to look for installed hooks: the rc files in `$HOME` (and
+/// `$ZDOTDIR`), and `$HOME` to expand sourced paths with.
+fn hook_rc_files() -> bird::Result<(Vec<std::path::PathBuf>, std::path::PathBuf)> {
+    let home = std::env::var_os("HOME")
+        .map(std::path::PathBuf::from)
+        .ok_or_else(|| bird::Error::Config("HOME is not set".to_string()))?;
+    let zdotdir = std::env::var_os("ZDOTDIR").map(std::path::PathBuf::from);
+    Ok((crate::hook_install::rc_files(&home, zdotdir.as_deref()), home))
+}
+
+/// Show the hooks installed in rc files, and whether they're current.
+pub fn hook_status() -> bird::Result<()> {
+    use crate::hook_install::{self, Install};
+    use crate::hooks::HOOK_VERSION;
+
+    let (files, home) = hook_rc_files()?;
+    let found = hook_install::scan(&files, &home);
+    println!("Hook version: {} (shq {})", HOOK_VERSION, env!("CARGO_PKG_VERSION"));
+
+    if found.is_empty() {
+        println!("No shq hook found in your shell's rc files.");
+        println!("Add to ~/.zshrc or ~/.bashrc: eval \"$(shq hook init)\"");
+    }
+    for f in &found {
+        let location = format!("{}:{}", f.path.display(), f.line);
+        match &f.install {
+            Install::Eval => println!("  {}  eval \"$(shq hook init)\" (always current)", location),
+            Install::Static { version, end, .. } => {
+                let state = if f.is_stale() {
+                    "stale: run `shq hook upgrade`"
+                } else {
+                    "current"
+                };
+                let copy = if end.is_none() { ", end not found" } else { "" };
+                println!("  {}  copy of the script, version {}{} ({})", location, version, copy, state);
+            }
+        }
+    }
+
+    // What this shell loaded, which an upgraded rc file doesn't change
+    match bird::version::shell_hook_version() {
+        Some(loaded) if loaded < HOOK_VERSION => {
+            println!("This shell loaded hook version {}; start a new shell (exec $SHELL) to load the current one.", loaded)
+        }
+        Some(loaded) => println!("This shell loaded hook version {}.", loaded),
+        None => {}
+    }
+
+    let stale = found.iter().filter(|f| f.is_stale()).count();
+    if stale > 0 {
+        return Err(bird::Error::Config(format!(
+            "{} installed hook(s) older than this shq; run `shq hook upgrade`",
+            stale
+        )));
+    }
+    Ok(())
+}
+
+/// Replace stale copies of the hook script in rc files with the current
+/// one, backing each file up first.
+pub fn hook_upgrade(dry_run: bool) -> bird::Result<()> {
+    use crate::hook_install;
+
+    let (files, home) = hook_rc_files()?;
+    let stale: Vec<_> = hook_install::scan(&files, &home).into_iter().filter(|f| f.is_stale()).collect();
+    if stale.is_empty() {
+        println!("Installed hooks are current.");
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for f in &stale {
+        if dry_run {
+            println!("Would upgrade {}:{}", f.path.display(), f.line);
+            continue;
+        }
+        match hook_install::upgrade(f) {
+            Ok(backup) => println!("Upgraded {}:{} (backup: {})", f.path.display(), f.line, backup.display()),
+            Err(e) => {
+                eprintln!("Couldn't upgrade {}:{}: {}", f.path.display(), f.line, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(bird::Error::Config(format!(
+            "{} hook(s) not upgraded; replace them with: eval \"$(shq hook init)\"",
+            failed
+        )));
+    }
+    if !dry_run {
+        println!("Start a new shell (exec $SHELL) to load the upgraded hook.");
+    }
+    Ok(())
+}
+
+/// Shell to generate hooks for: `shell` if given, else `$SHELL`.
+fn detect_shell(shell: Option<&str>) -> Option<crate::hooks::Shell> {
+    use crate::hooks::Shell;
+
+    let shell_str = shell
+        .map(|s| s.to_string())
+        .or_else(|| std::env::var("SHELL").ok())
+        .unwrap_or_default();
+
+    if shell_str.contains("zsh") {
+        Some(Shell::Zsh)
+    } else if shell_str.contains("bash") {
+        Some(Shell::Bash)
+    } else {
+        None
+    }
+}
+
+/// Measure hook overhead over `count` synthetic commands.
+///
+/// Runs against a scratch BIRD_ROOT with the current settings (minus
+/// remotes), so the synthetic commands never reach the real store.
+pub fn hook_bench(count: usize, shell: Option<&str>, sync: bool, format: &str) -> bird::Result<()> {
+    let shell = detect_shell(shell)
+        .ok_or_else(|| bird::Error::Config("Unknown shell type. Use --shell zsh or --shell bash".to_string()))?;
+
+    let mut config = Config::load()?;
+    config.bird_root = std::env::temp_dir().join(format!("shq-bench-{}", uuid::Uuid::now_v7()));
+    config.remotes.clear();
+    init::initialize(&config)?;
+
+    let result = run_hook_bench(&config, count.max(1), shell, sync);
+
+    // Let the spool drain so no flusher is left writing into the scratch root
+    let deadline = Instant::now() + std::time::Duration::from_secs(30);
+    while Instant::now() < deadline {
+        let idle = matches!(bird::spool::FlushLock::try_acquire(&config), Ok(Some(_)));
+        if idle && bird::spool::pending(&config).map(|p| p.is_empty()).unwrap_or(true) {
+            break;
+        }
+        if idle {
+            if let Err(e) = Store::open(config.clone()).and_then(|store| store.flush_spool()) {
+                tracing::debug!("bench spool flush: {}", e);
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    let _ = std::fs::remove_dir_all(&config.bird_root);
+
+    let (stages, breakdown) = result?;
+    let save_mode = if sync { "sync" } else { "async" };
+    let shell_name = match shell {
+        crate::hooks::Shell::Zsh => "zsh",
+        crate::hooks::Shell::Bash => "bash",
+    };
+
+    match format {
+        "json" => {
+            let row = |s: &crate::bench::Samples| {
+                serde_json::json!({
+                    "stage": s.name,
+                    "p50_ms": s.percentile(50.0).as_secs_f64() * 1000.0,
+                    "p99_ms": s.percentile(99.0).as_secs_f64() * 1000.0,
+                    "max_ms": s.max().as_secs_f64() * 1000.0,
+                })
+            };
+            let json = serde_json::json!({
+                "commands": count.max(1),
+                "shell": shell_name,
+                "save": save_mode,
+                "stages": stages.iter().map(row).collect::<Vec<_>>(),
+                "save_breakdown": breakdown.iter().map(row).collect::<Vec<_>>(),
+            });
+            println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+        }
+        _ => {
+            use crate::bench::format_ms;
+
+            println!("Hook overhead over {} commands ({}, {} save)", count.max(1), shell_name, save_mode);
+            println!();
+            println!("{:<20} {:>10} {:>10} {:>10}", "STAGE", "P50", "P99", "MAX");
+            for s in &stages {
+                println!(
+                    "{:<20} {:>10} {:>10} {:>10}",
+                    s.name,
+                    format_ms(s.percentile(50.0)),
+                    format_ms(s.percentile(99.0)),
+                    format_ms(s.max())
+                );
+            }
+            println!();
+            println!("Save breakdown (in-process):");
+            for s in &breakdown {
+                println!(
+                    "  {:<18} {:>10} {:>10} {:>10}",
+                    s.name,
+                    format_ms(s.percentile(50.0)),
+                    format_ms(s.percentile(99.0)),
+                    format_ms(s.max())
+                );
+            }
+            println!();
+            println!("Only preexec and postexec delay the prompt; the save runs in the background.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Time each hook stage, then the phases of a save. Returns
+/// (stages, save breakdown).
+fn run_hook_bench(
+    config: &Config,
+    count: usize,
+    shell: crate::hooks::Shell,
+    sync: bool,
+) -> bird::Result<(Vec<crate::bench::Samples>, Vec<crate::bench::Samples>)> {
+    use crate::bench::{self, Samples};
+    use crate::hooks::{self, Mode, Shell};
+    use std::process::Stdio;
+
+    let exe = std::env::current_exe()?;
+    let root = &config.bird_root;
+    let (shell_name, invoker) = match shell {
+        Shell::Zsh => ("zsh", "zsh"),
+        Shell::Bash => ("bash", "bash"),
+    };
+    let session_id = format!("bench-{}", std::process::id());
+    let cwd = std::env::current_dir()?;
+    let mut stages = Vec::new();
+
+    // Process startup alone: the floor for anything the hook execs
+    let mut startup = Samples::new("shq startup");
+    for _ in 0..count {
+        let start = Instant::now();
+        Command::new(&exe).arg("--version").stdout(Stdio::null()).status()?;
+        startup.push(start.elapsed());
+    }
+    stages.push(startup);
+
+    // Shell side: the hook's own functions, with `shq` stubbed out
+    let hook_path = root.join("bench-hook.sh");
+    std::fs::write(&hook_path, hooks::generate(shell, Mode::Active, false))?;
+    let driver = bench::driver_script(shell, &hook_path.display().to_string(), count);
+    let shell_timings = Command::new(shell_name)
+        .args(["-c", &driver])
+        .env("BIRD_ROOT", root)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .and_then(|out| bench::parse_driver_output(&String::from_utf8_lossy(&out.stdout)));
+    if shell_timings.is_none() {
+        eprintln!("shq: couldn't time the {} hook functions (needs {} with $EPOCHREALTIME); timing the save only", shell_name, shell_name);
+    }
+
+    // The save, as the hook runs it: spooled through shq-capture if it's
+    // installed, else with shq itself
+    let capture = if sync { None } else { bench::find_on_path("shq-capture") };
+    let (save_exe, save_args): (&std::path::Path, &[&str]) = match (&capture, sync) {
+        (Some(capture), _) => (capture, &[]),
+        (None, true) => (&exe, &["save"]),
+        (None, false) => (&exe, &["save", "--async"]),
+    };
+    let mut save = Samples::new(if capture.is_some() { "save (shq-capture)" } else { "save" });
+    for i in 0..count {
+        let start = Instant::now();
+        let status = Command::new(save_exe)
+            .args(save_args)
+            .args(["-c", &format!("echo bench {}", i), "-x", "0", "-d", "5"])
+            .args(["--session-id", &session_id, "--invoker-pid", &std::process::id().to_string()])
+            .args(["--invoker", invoker, "--compact", "-q"])
+            .env("BIRD_ROOT", root)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .status()?;
+        save.push(start.elapsed());
+        if !status.success() {
+            return Err(bird::Error::Storage(format!("{} exited with {}", save_exe.display(), status)));
+        }
+    }
+
+    match shell_timings {
+        Some((preexec, postexec)) => {
+            let prompt = preexec.plus("prompt (pre+post)", &postexec);
+            let total = prompt.plus("total", &save);
+            stages.extend([preexec, postexec, save, prompt, total]);
+        }
+        None => stages.push(save),
+    }
+
+    // Where a save's time goes, phase by phase
+    let mut load = Samples::new("config");
+    let mut privacy = Samples::new("privacy");
+    let mut context = Samples::new("context");
+    let mut write = Samples::new(if sync { "store write" } else { "spool write" });
+    for i in 0..count {
+        let cmd = format!("echo bench {}", i);
+
+        let start = Instant::now();
+        let loaded = Config::load_from(root)?;
+        load.push(start.elapsed());
+
+        let start = Instant::now();
+        let _ = bird::privacy::is_paused(&loaded) || bird::privacy::should_exclude(&loaded, &cmd);
+        let stored_cmd = bird::privacy::redact_command(&cmd);
+        privacy.push(start.elapsed());
+
+        let start = Instant::now();
+        let metadata = ContextMetadata::collect(Some(&cwd)).into_map();
+        context.push(start.elapsed());
+
+        let record = InvocationRecord::new(&session_id, &stored_cmd, cwd.display().to_string(), 0, &loaded.client_id)
+            .with_metadata(metadata)
+            .with_duration(5);
+        let start = Instant::now();
+        if sync {
+            Store::open(loaded)?.write_batch(&InvocationBatch::new(record))?;
+        } else {
+            bird::spool::write(&loaded, &bird::spool::SpooledSave::new(record))?;
+        }
+        write.push(start.elapsed());
+    }
+
+    Ok((stages, vec![load, privacy, context, write]))
+}
+
+/// Output shell code applying the current project's settings.
+///
+/// Evaluated by the shell hook whenever the directory changes: exports the
+/// `SHQ_PROJECT*` variables for the project containing the current directory
+/// and unsets any left over from a project that was left. Deliberately
+/// doesn't load the global config or open the store.
+pub fn project_env() -> bird::Result<()> {
+    let vars = match bird::find_current_project() {
+        Some(project) => project.load_config()?.env_vars(&project.root),
+        None => Vec::new(),
+    };
+
+    for name in bird::project::PROJECT_ENV_VARS {
+        match vars.iter().find(|(var, _)| var == name) {
+            Some((_, value)) => println!("export {}={}", name, crate::wrap::sh_quote(value)),
+            None => println!("unset {}", name),
+        }
+    }
+    Ok(())
+}
+
+/// Show the current project and its settings.
+pub fn project_show() -> bird::Result<()> {
+    let Some(project) = bird::find_current_project() else {
+        println!("Not in a project (no .bird/ directory found)");
+        return Ok(());
+    };
+    let config = project.load_config()?;
+
+    println!("Project: {}", project.root.display());
+    let settings = if project.config_path().exists() {
+        project.config_path().display().to_string()
+    } else {
+        "(none)".to_string()
+    };
+    println!("Settings: {}", settings);
+    println!("Capture: {}", if config.capture_enabled() { "on" } else { "off" });
+    if let Some(extract) = config.extract {
+        println!("Extract: {}", if extract { "on" } else { "off" });
+    }
+    if let Some(format) = &config.extract_format {
+        println!("Extract format: {}", format);
+    }
+    for (key, value) in &config.dims {
+        println!("Dim: {}={}", key, value);
+    }
+    Ok(())
+}
+
+/// Create or remove a task runner shim, or list wrapped tools.
+pub fn wrap(tool: Option<&str>, remove: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let dir = config.shims_dir();
+
+    let Some(tool) = tool else {
+        let tools = crate::wrap::list(&dir)?;
+        if tools.is_empty() {
+            println!("No wrapped tools. Use `shq wrap <tool>` to add one.");
+        } else {
+            for tool in tools {
+                println!("{}", tool);
+            }
+        }
+        return Ok(());
+    };
+
+    if !crate::wrap::validate_tool(tool) {
+        return Err(bird::Error::Config(format!("Invalid tool name: {}", tool)));
+    }
+
+    if remove {
+        if crate::wrap::remove(&dir, tool)? {
+            println!("Removed {} shim", tool);
+        } else {
+            println!("{} is not wrapped", tool);
+        }
+        return Ok(());
+    }
+
+    let shq_path = std::env::current_exe()?;
+    if crate::wrap::install(&dir, tool, &shq_path)? {
+        println!("Wrapped {} ({})", tool, dir.join(tool).display());
+    } else {
+        println!("{} already wrapped", tool);
+    }
+
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    if !std::env::split_paths(&path).any(|p| p == dir) {
+        println!();
+        println!("Add the shims directory to the front of your PATH:");
+        println!("  export PATH=\"{}:$PATH\"", dir.display());
+    }
+    Ok(())
+}
+
+/// Run a wrapped tool under capture. Invoked by the shims from `shq wrap`.
+///
+/// The shims directory is dropped from `PATH` first so the real tool (and
+/// anything it spawns) is found instead of the shim. Runs already inside a
+/// capture (`shq run make`, recursive make), while capture is paused, or
+/// before `shq init` execute the tool directly.
+pub fn wrap_exec(tool: &str, args: &[String], force_capture: bool) -> bird::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let config = Config::load()?;
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::set_var("PATH", crate::wrap::path_without(&path, &config.shims_dir())?);
+
+    let nested = std::env::var_os(BIRD_INVOCATION_UUID_VAR).is_some();
+    let paused = !force_capture
+        && (bird::privacy::is_paused(&config) || !bird::ProjectConfig::from_env().capture_enabled());
+    if nested || paused || !config.db_path().exists() {
+        let err = Command::new(tool).args(args).exec();
+        return Err(err.into());
+    }
+
+    let settings = config.wrap.tool(tool);
+    let mut cmd_args = Vec::with_capacity(args.len() + 1);
+    cmd_args.push(tool.to_string());
+    cmd_args.extend_from_slice(args);
+
+    run(
+        None,
+        &cmd_args,
+        settings.tag.as_deref(),
+        &[],
+        &[],
+        Some(settings.extract),
+        settings.extract_format.as_deref(),
+        false,
+        settings.no_pty,
+        force_capture,
+        &Expectations::default(),
+        Sinks::default(),
+    )
+}
+
+/// Hooks directory of the git repository containing the current directory.
+///
+/// Uses `git rev-parse --git-path hooks` so `core.hooksPath` and worktrees
+/// are respected.
+fn git_hooks_dir() -> bird::Result<std::path::PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .output()?;
+    if !output.status.success() {
+        return Err(bird::Error::Config("Not inside a git repository".to_string()));
+    }
+    Ok(std::path::PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+fn selected_git_hooks(hooks: &[String]) -> Vec<&str> {
+    if hooks.is_empty() {
+        crate::git_hooks::DEFAULT_HOOKS.to_vec()
+    } else {
+        hooks.iter().map(String::as_str).collect()
+    }
+}
+
+/// Wrap git hooks in the current repository so their runs are captured.
+pub fn git_hooks_install(hooks: &[String]) -> bird::Result<()> {
+    use crate::git_hooks::{self, Status};
+
+    let dir = git_hooks_dir()?;
+    for hook in selected_git_hooks(hooks) {
+        match git_hooks::install(&dir, hook)? {
+            Status::Installed => println!("Wrapped {} (original: {}.shq-orig)", hook, hook),
+            Status::AlreadyInstalled => println!("{} already wrapped", hook),
+            _ => println!("{} not found in {}, skipped", hook, dir.display()),
+        }
+    }
+    Ok(())
+}
+
+/// Remove shq git hook wrappers, restoring the original hooks.
+pub fn git_hooks_uninstall(hooks: &[String]) -> bird::Result<()> {
+    use crate::git_hooks::{self, Status};
+
+    let dir = git_hooks_dir()?;
+    for hook in selected_git_hooks(hooks) {
+        match git_hooks::uninstall(&dir, hook)? {
+            Status::Removed => println!("Restored {}", hook),
+            _ => println!("{} not wrapped, skipped", hook),
+        }
+    }
+    Ok(())
+}
+
+/// Order for limiting results.
+#[derive(Clone, Copy, Debug)]
+pub enum LimitOrder {
+    Any,   // Just limit, no specific order
+    First, // First N (head)
+    Last,  // Last N (tail)
+}
+
+/// Query parsed events from invocation outputs.
+///
+/// Returns whether the invocations have events at or above the `fail_on`
+/// severity, for `--fail-on` to turn into the exit status.
+#[allow(clippy::too_many_arguments)]
+pub fn events(
+    query_str: &str,
+    severity: Option<&str>,
+    count_only: bool,
+    limit: usize,
+    order: LimitOrder,
+    reparse: bool,
+    extract: bool,
+    format: Option<&str>,
+    show_ids: bool,
+    fail_on: Option<&str>,
+) -> bird::Result<bool> {
+    let fail_on = fail_on.map(severities_at_least).transpose()?;
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    // Parse query (filters and range applied by query_invocations)
+    let query = parse_query_strict(query_str)?;
+
+    // One connection for the whole command (query, extract, query again)
+    store.with_connection(|_| {
+        events_with_store(&store, &query, severity, count_only, limit, order, reparse, extract, format, show_ids)?;
+        match fail_on {
+            Some(severities) => has_events(&store, &query, severities),
+            None => Ok(false),
+        }
+    })
+}
+
+/// Severities at least as severe as `severity`, most severe first.
+fn severities_at_least(severity: &str) -> bird::Result<&'static [&'static str]> {
+    const SEVERITIES: [&str; 4] = ["error", "warning", "info", "note"];
+    match SEVERITIES.iter().position(|s| s.eq_ignore_ascii_case(severity)) {
+        Some(i) => Ok(&SEVERITIES[..=i]),
+        None => Err(bird::Error::Config(format!(
+            "Invalid severity '{}': expected error, warning, info or note",
+            severity
+        ))),
+    }
+}
+
+/// Whether the invocations matching `query` have events of any of `severities`.
+fn has_events(store: &Store, query: &bird::Query, severities: &[&str]) -> bird::Result<bool> {
+    let invocation_ids: Vec<String> = store.query_invocations(query)?.into_iter().map(|inv| inv.id).collect();
+    if invocation_ids.is_empty() {
+        return Ok(false);
+    }
+    for severity in severities {
+        let count = store.event_count(&EventFilters {
+            severity: Some(severity.to_string()),
+            invocation_ids: Some(invocation_ids.clone()),
+            ..Default::default()
+        })?;
+        if count > 0 {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn events_with_store(
+    store: &Store,
+    query: &bird::Query,
+    severity: Option<&str>,
+    count_only: bool,
+    limit: usize,
+    order: LimitOrder,
+    reparse: bool,
+    extract: bool,
+    format: Option<&str>,
+    show_ids: bool,
+) -> bird::Result<()> {
+    // Handle reparse mode: re-extract events from outputs
+    if reparse {
+        let invocations = store.query_invocations(query)?;
+        let mut total_events = 0;
+
+        for inv in &invocations {
+            // Delete existing events for this invocation
+            store.delete_events_for_invocation(&inv.id)?;
+            // Re-extract
+            let count = store.extract_events(&inv.id, format)?;
+            total_events += count;
+        }
+
+        println!(
+            "Re-extracted {} events from {} invocations",
+            total_events,
+            invocations.len()
+        );
+        return Ok(());
+    }
+
+    // Get invocations matching query (with filters applied)
+    let invocations = store.query_invocations(query)?;
+    if invocations.is_empty() {
+        println!("No invocations found.");
+        return Ok(());
+    }
+
+    // Extract events if requested and not already extracted
+    if extract {
+        for inv in &invocations {
+            let existing = store.event_count(&EventFilters {
+                invocation_id: Some(inv.id.clone()),
+                include_corrected: true,
+                ..Default::default()
+            })?;
+            if existing == 0 {
+                let _ = store.extract_events(&inv.id, format);
+            }
+        }
+    }
+
+    // Build filters
+    let inv_ids: Vec<String> = invocations.iter().map(|inv| inv.id.clone()).collect();
+    let filters = EventFilters {
+        severity: severity.map(|s| s.to_string()),
+        invocation_ids: Some(inv_ids),
+        limit: Some(limit),
+        // TODO: Add order support to EventFilters when needed
+        ..Default::default()
+    };
+    let _ = order; // Will be used when EventFilters supports ordering
+
+    // Count only mode
+    if count_only {
+        let count = store.event_count(&filters)?;
+        println!("{}", count);
+        return Ok(());
+    }
+
+    // Query events
+    let events = store.query_events(&filters)?;
+
+    if events.is_empty() {
+        println!("No events found.");
+        return Ok(());
+    }
+
+    // Display events
+    if show_ids {
+        print!("{:<36} ", "ID");
+    }
+    println!(
+        "{:<8} {:<40} {:<30} MESSAGE",
+        "SEVERITY", "FILE:LINE", "CODE"
+    );
+    println!("{}", "-".repeat(if show_ids { 137 } else { 100 }));
+
+    let theme = crate::theme::current();
+    for event in &events {
+        if show_ids {
+            print!("{:<36} ", event.id);
+        }
+        let sev = event.severity.as_deref().unwrap_or("-");
+        let location = match (&event.ref_file, event.ref_line) {
+            (Some(f), Some(l)) => format!("{}:{}", truncate_path(f, 35), l),
+            (Some(f), None) => truncate_path(f, 40).to_string(),
+            _ => "-".to_string(),
+        };
+        let code = event
+            .error_code
+            .as_deref()
+            .or(event.test_name.as_deref())
+            .unwrap_or("-");
+        let message = event
+            .message
+            .as_deref()
+            .map(|m| truncate_string(m, 50))
+            .unwrap_or_else(|| "-".to_string());
+        let message = if event.origin == bird::EVENT_ORIGIN_MANUAL {
+            format!("{} (manual)", message)
+        } else {
+            message
+        };
+
+        // Color based on severity
+        let severity_display = match sev {
+            "error" => theme.paint(RED, format!("{:<8}", sev)),
+            "warning" => theme.paint(YELLOW, format!("{:<8}", sev)),
+            _ => format!("{:<8}", sev),
+        };
+
+        println!(
+            "{} {:<40} {:<30} {}",
+            severity_display, location, code, message
+        );
+    }
+
+    println!("\n({} events)", events.len());
+
+    Ok(())
+}
+
+/// Hide a wrongly extracted event from `shq events` and `v1.events`.
+pub fn mark_event_wrong(event_id: &str, note: Option<&str>) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    if store.mark_event_wrong(event_id, note)? {
+        println!("Marked event {} as wrong", event_id);
+    } else {
+        println!("Event {} was already corrected", event_id);
+    }
+    Ok(())
+}
+
+/// List recorded event corrections, newest first.
+pub fn event_corrections(limit: usize) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let corrections = store.event_corrections(Some(limit))?;
+    if corrections.is_empty() {
+        println!("No corrections recorded.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<16} {:<8} {:<10} {:<30} MESSAGE",
+        "WHEN", "ACTION", "FORMAT", "FILE:LINE"
+    );
+    println!("{}", "-".repeat(100));
+
+    for c in &corrections {
+        let location = match (&c.ref_file, c.ref_line) {
+            (Some(f), Some(l)) => format!("{}:{}", truncate_path(f, 25), l),
+            (Some(f), None) => truncate_path(f, 30).to_string(),
+            _ => "-".to_string(),
+        };
+        let message = c
+            .message
+            .as_deref()
+            .map(|m| truncate_string(m, 40))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<16} {:<8} {:<10} {:<30} {}",
+            c.created_at.format("%Y-%m-%d %H:%M"),
+            c.action,
+            c.format_used.as_deref().unwrap_or("-"),
+            location,
+            message
+        );
+        if let Some(note) = &c.note {
+            println!("{:<16} note: {}", "", note);
+        }
+    }
+
+    println!("\n({} corrections)", corrections.len());
+    Ok(())
+}
+
+/// Extract events from an invocation's output.
+#[allow(clippy::too_many_arguments)]
+pub fn extract_events(
+    selector: &str,
+    format: Option<&str>,
+    quiet: bool,
+    force: bool,
+    all: bool,
+    since: Option<&str>,
+    limit: Option<usize>,
+    dry_run: bool,
+) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?
+        .with_progress(crate::progress::sink(quiet))
+        .with_cancel(crate::cancel::token());
+
+    // Backfill mode: extract from all invocations without events
+    if all {
+        return extract_events_backfill(&store, format, quiet, since, limit, dry_run);
+    }
+
+    // Single invocation mode
+    let invocation_id = resolve_invocation_id(&store, selector)?;
+
+    // Check if events already exist
+    let existing_count = store.event_count(&EventFilters {
+        invocation_id: Some(invocation_id.clone()),
+        include_corrected: true,
+        ..Default::default()
+    })?;
+
+    if existing_count > 0 && !force {
+        if !quiet {
+            println!(
+                "Events already exist for invocation {} ({} events). Use --force to re-extract.",
+                invocation_id, existing_count
+            );
+        }
+        return Ok(());
+    }
+
+    // Delete existing events if forcing
+    if force && existing_count > 0 {
+        store.delete_events_for_invocation(&invocation_id)?;
+    }
+
+    // Extract events
+    let count = store.extract_events(&invocation_id, format)?;
+
+    if !quiet {
+        if count > 0 {
+            println!("Extracted {} events from invocation {}", count, invocation_id);
+        } else {
+            println!("No events found in invocation {}", invocation_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Explain how events are extracted from one invocation
+/// (`shq extract-events --explain`), without extracting them.
+pub fn explain_extraction(selector: &str, format: Option<&str>) -> bird::Result<()> {
+    use bird::FormatReason;
+
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+    let invocation_id = resolve_invocation_id(&store, selector)?;
+    let report = store.explain_extraction(&invocation_id, format)?;
+
+    println!("Invocation: {}", report.invocation_id);
+    println!("Command:    {}", truncate_string(&report.cmd, 70));
+    let reason = match &report.reason {
+        FormatReason::Override => "given with --format".to_string(),
+        FormatReason::Captured => "recorded when the command was captured".to_string(),
+        FormatReason::Hint { pattern, priority } => {
+            format!("format hint '{}' (priority {})", pattern, priority)
+        }
+        FormatReason::Default => "default format, no hint matched".to_string(),
+    };
+    println!("Format:     {} ({})", report.format, reason);
+    match (&report.parser, report.format.as_str()) {
+        (Some(parser), "auto") => println!("Parser:     {} (content detection)", parser),
+        (Some(parser), _) => println!("Parser:     {}", parser),
+        (None, _) if report.engine == "duck_hunt" => println!("Parser:     duck_hunt"),
+        (None, _) => println!("Parser:     none (no built-in parser recognised the output)"),
+    }
+    println!(
+        "Lines:      {} matched of {} in {} output(s), {} events",
+        report.matched_lines, report.lines, report.outputs, report.events
+    );
+
+    if !report.unmatched_samples.is_empty() {
+        println!("\nError-looking lines without an event:");
+        for line in &report.unmatched_samples {
+            println!("  {}", line);
+        }
+        if report.events == 0 {
+            println!(
+                "\nTry a format hint: shq format-hints add '<pattern>' <format> (see shq format-hints list)"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse an optional `--since` date (YYYY-MM-DD or a duration like 7d).
+fn parse_since_date(since: Option<&str>) -> bird::Result<Option<chrono::NaiveDate>> {
+    since.map(bird::parse_since).transpose()
+}
+
+/// Re-extract events for invocations whose command matches `pattern`
+/// (`shq extract-events --matching PATTERN --reparse`).
+///
+/// Run after adding a format hint: old invocations were parsed with the
+/// format known at capture time, so their events are replaced using the
+/// current hints.
+pub fn reparse_events(
+    pattern: &str,
+    format: Option<&str>,
+    quiet: bool,
+    since: Option<&str>,
+    limit: Option<usize>,
+    dry_run: bool,
+) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?
+        .with_progress(crate::progress::sink(quiet))
+        .with_cancel(crate::cancel::token());
+
+    let invocations = store.invocations_matching(pattern, parse_since_date(since)?, limit)?;
+    if invocations.is_empty() {
+        if !quiet {
+            println!("No invocations with output match '{}'.", pattern);
+        }
+        return Ok(());
+    }
+
+    if dry_run {
+        println!("Would reparse events for {} invocations:", invocations.len());
+        for inv in &invocations {
+            let format = match format {
+                Some(f) => f.to_string(),
+                None => store.detect_format_for_command(&inv.cmd)?,
+            };
+            let cmd_preview: String = inv.cmd.chars().take(60).collect();
+            let suffix = if inv.cmd.chars().count() > 60 { "..." } else { "" };
+            println!("  {} [{}] {}{}", &inv.id[..8], format, cmd_preview, suffix);
+        }
+        return Ok(());
+    }
+
+    let ids: Vec<String> = invocations.iter().map(|inv| inv.id.clone()).collect();
+    let summary = store.reparse_events(&ids, format)?;
+
+    if !quiet {
+        println!(
+            "Reparsed {} invocations: {} events ({} invocations with events).",
+            summary.processed, summary.events, summary.with_events
+        );
+    }
+
+    Ok(())
+}
+
+/// Backfill events from all invocations that don't have events yet.
+fn extract_events_backfill(
+    store: &Store,
+    format: Option<&str>,
+    quiet: bool,
+    since: Option<&str>,
+    limit: Option<usize>,
+    dry_run: bool,
+) -> bird::Result<()> {
+    let since_date = parse_since_date(since)?;
+
+    // Get invocations without events
+    let invocations = store.invocations_without_events(since_date, limit)?;
+
+    if invocations.is_empty() {
+        if !quiet {
+            println!("No invocations found without events.");
+        }
+        return Ok(());
+    }
+
+    if dry_run {
+        println!("Would extract events from {} invocations:", invocations.len());
+        for inv in &invocations {
+            let cmd_preview: String = inv.cmd.chars().take(60).collect();
+            let suffix = if inv.cmd.len() > 60 { "..." } else { "" };
+            println!("  {} {}{}", &inv.id[..8], cmd_preview, suffix);
+        }
+        return Ok(());
+    }
+
+    let ids: Vec<String> = invocations.iter().map(|inv| inv.id.clone()).collect();
+    let summary = store.extract_events_batch(&ids, format)?;
+
+    if !quiet {
+        println!(
+            "Extracted {} events from {} invocations ({} with events).",
+            summary.events, summary.processed, summary.with_events
+        );
+    }
+
+    Ok(())
+}
+
+/// Truncate a command string for display.
+fn truncate_cmd(cmd: &str, max_len: usize) -> String {
+    if cmd.len() <= max_len {
+        cmd.to_string()
+    } else {
+        format!("{}...", &cmd[..max_len])
+    }
+}
+
+/// Resolve a selector (negative offset, ~N syntax, or UUID) to an invocation ID.
+fn resolve_invocation_id(store: &Store, selector: &str) -> bird::Result<String> {
+    // Handle ~N syntax (e.g., ~1 for most recent, ~2 for second-to-last)
+    if let Some(stripped) = selector.strip_prefix('~') {
+        if let Ok(n) = stripped.parse::<usize>() {
+            if n > 0 {
+                let invocations = store.recent_invocations(n)?;
+                if let Some(inv) = invocations.last() {
+                    return Ok(inv.id.clone());
+                } else {
+                    return Err(bird::Error::NotFound(format!(
+                        "No invocation found at offset ~{}",
+                        n
+                    )));
+                }
+            }
+        }
+    }
+
+    // Handle negative offset (e.g., -1 for last, -2 for second-to-last)
+    if let Ok(offset) = selector.parse::<i64>() {
+        if offset < 0 {
+            let n = (-offset) as usize;
+            let invocations = store.recent_invocations(n)?;
+            if let Some(inv) = invocations.last() {
+                return Ok(inv.id.clone());
+            } else {
+                return Err(bird::Error::NotFound(format!(
+                    "No invocation found at offset {}",
+                    offset
+                )));
+            }
+        }
+    }
+
+    // Try short ID lookup
+    if let Some(id) = try_find_by_id(store, selector)? {
+        return Ok(id);
+    }
+
+    // Assume it's a full UUID
+    Ok(selector.to_string())
+}
+
+/// Truncate a path for display, keeping the filename visible.
+fn truncate_path(path: &str, max_len: usize) -> &str {
+    if path.len() <= max_len {
+        return path;
+    }
+    // Try to keep at least the filename
+    if let Some(pos) = path.rfind('/') {
+        let filename = &path[pos + 1..];
+        if filename.len() < max_len {
+            return &path[path.len() - max_len..];
+        }
+    }
+    &path[path.len() - max_len..]
+}
+
+/// Truncate a string for display.
+fn truncate_string(s: &str, max_len: usize) -> String {
+    if s.len() <= max_len {
+        s.to_string()
+    } else {
+        format!("{}...", &s[..max_len - 3])
+    }
+}
+
+/// Check if a string looks like a hex ID (short or full UUID).
+fn looks_like_hex_id(s: &str) -> bool {
+    // Must be at least 4 chars and only contain hex digits and dashes
+    s.len() >= 4 && s.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+/// Try to find an invocation by tag, alias, short ID, or full ID.
+/// Returns Some(full_id) if found, None if not found or query doesn't look like an ID/tag.
+fn try_find_by_id(store: &Store, query_str: &str) -> bird::Result<Option<String>> {
+    let trimmed = query_str.trim();
+
+    // Check for tag lookup (:tagname)
+    if let Some(tag) = trimmed.strip_prefix(':') {
+        if let Some(id) = store.find_by_tag(tag)? {
+            return Ok(Some(id));
+        }
+        // Tag not found - this is an explicit error, not a fallback to query
+        return Err(bird::Error::NotFound(format!("Tag '{}' not found", tag)));
+    }
+
+    // Word-pair alias (brave-otter); like a tag, never a query
+    if bird::is_alias(trimmed) {
+        return match store.find_by_alias(trimmed)? {
+            Some(id) => Ok(Some(id)),
+            None => Err(bird::Error::NotFound(format!("Alias '{}' not found", trimmed))),
+        };
+    }
+
+    if !looks_like_hex_id(trimmed) {
+        return Ok(None);
+    }
+
+    // Full UUID or short ID (we show the last 8 chars of UUIDv7); errors
+    // with the candidates if several invocations match
+    store.find_by_short_id(trimmed)
+}
+
+/// Resolve a query to a single invocation ID.
+fn resolve_query_to_invocation(store: &Store, query: &Query) -> bird::Result<String> {
+    // For single-item commands, the range selector determines which item:
+    // - ~N = single item at position N (1 = most recent)
+    // - ~N: = last N items, we take the most recent (position 1)
+    // - ~N:~M = range from N to M, we take position M (most recent in range)
+    // - No range = default to position 1 (most recent)
+
+    // query_invocations_with_limit handles the range semantics, returning
+    // the correct subset. For single-item commands, we just take the first result.
+    let invocations = store.query_invocations_with_limit(query, 1)?;
+
+    if let Some(inv) = invocations.first() {
+        Ok(inv.id.clone())
+    } else {
+        Err(bird::Error::NotFound("No matching invocation found".to_string()))
+    }
+}
+
+/// Show detailed info about an invocation.
+/// Link an invocation to an issue, PR or other external reference.
+pub fn link(kind: &str, query_str: &str, target: &str, delete: bool, dry_run: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let invocation_id = match try_find_by_id(&store, query_str)? {
+        Some(id) => id,
+        None => resolve_query_to_invocation(&store, &parse_query_strict(query_str)?)?,
+    };
+    let short_id = &invocation_id[..8.min(invocation_id.len())];
+
+    if dry_run {
+        let plan = if delete {
+            store.plan_unlink(&invocation_id, target)?
+        } else {
+            store.plan_link(&invocation_id, kind, target)?
+        };
+        print!("{}", plan);
+        return Ok(());
+    }
+
+    if delete {
+        match store.remove_link(&invocation_id, target)? {
+            0 => println!("No link to {} on {}", target, short_id),
+            _ => println!("Unlinked {} from {}", target, short_id),
+        }
+    } else if store.add_link(&invocation_id, kind, target)? {
+        println!("Linked {} to {} {}", short_id, kind, target);
+    } else {
+        println!("{} is already linked to {} {}", short_id, kind, target);
+    }
+
+    Ok(())
+}
+
+/// Compare the environment snapshots recorded with two invocations.
+pub fn env_diff(old_query: &str, new_query: &str, all: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let resolve = |query_str: &str| -> bird::Result<String> {
+        match try_find_by_id(&store, query_str)? {
+            Some(id) => Ok(id),
+            None => resolve_query_to_invocation(&store, &parse_query_strict(query_str)?),
+        }
+    };
+    let old_id = resolve(old_query)?;
+    let new_id = resolve(new_query)?;
+
+    let snapshot = |id: &str, query_str: &str| -> bird::Result<_> {
+        store.env_snapshot(id)?.ok_or_else(|| {
+            bird::Error::NotFound(format!(
+                "No environment recorded for {} (set privacy.record_env = true to record it)",
+                query_str
+            ))
+        })
+    };
+    let old_env = snapshot(&old_id, old_query)?;
+    let new_env = snapshot(&new_id, new_query)?;
+
+    let changes = bird::diff_env(&old_env, &new_env);
+    let theme = crate::theme::current();
+
+    println!("--- {} ({})", old_query, &old_id[..8.min(old_id.len())]);
+    println!("+++ {} ({})", new_query, &new_id[..8.min(new_id.len())]);
+
+    for change in &changes {
+        match change {
+            bird::EnvChange::Removed { name, value } => {
+                println!("{}", theme.paint(RED, format!("- {}={}", name, value)));
+            }
+            bird::EnvChange::Added { name, value } => {
+                println!("{}", theme.paint(GREEN, format!("+ {}={}", name, value)));
+            }
+            bird::EnvChange::Changed { name, old, new } => {
+                println!("{}", theme.paint(RED, format!("- {}={}", name, old)));
+                println!("{}", theme.paint(GREEN, format!("+ {}={}", name, new)));
+            }
+        }
+    }
+
+    if all {
+        for (name, value) in new_env.iter().filter(|(name, value)| old_env.get(*name) == Some(value)) {
+            println!("{}", theme.paint(DIM, format!("  {}={}", name, value)));
+        }
+    }
+
+    if changes.is_empty() {
+        println!("Environments are identical ({} variables)", new_env.len());
+    } else {
+        println!("{} variable(s) differ", changes.len());
+    }
+
+    Ok(())
+}
+
+/// Line up two invocations of the same command, e.g. here and on a remote
+/// (`remote:NAME:SELECTOR`), showing how exit code, duration, code state,
+/// events and environment differ.
+pub fn compare(left_query: &str, right_query: &str) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    // remote:NAME:SELECTOR, with an empty or ~N selector meaning the Nth most
+    // recent run of the other side's command there
+    let load = |query_str: &str, cmd: Option<&str>| -> bird::Result<bird::InvocationProfile> {
+        if let Some(rest) = query_str.strip_prefix("remote:") {
+            let (remote, selector) = rest.split_once(':').unwrap_or((rest, ""));
+            let id = store.find_remote_invocation(remote, selector, cmd)?;
+            return store.invocation_profile(&id, Some(remote));
+        }
+        let id = match try_find_by_id(&store, query_str)? {
+            Some(id) => id,
+            None => resolve_query_to_invocation(&store, &parse_query_strict(query_str)?)?,
+        };
+        store.invocation_profile(&id, None)
+    };
+    let left = load(left_query, None)?;
+    let right = load(right_query, Some(&left.cmd))?;
+
+    let theme = crate::theme::current();
+    let short = |id: &str| id[id.len().saturating_sub(8)..].to_string();
+    let place = |p: &bird::InvocationProfile| match &p.hostname {
+        Some(host) => format!("{} on {}", p.source, host),
+        None => p.source.clone(),
+    };
+    let row = |label: &str, old: String, new: String| {
+        if old == new {
+            println!("{:<12} {}", label, old);
+        } else {
+            println!("{:<12} {} -> {}", label, theme.paint(RED, old), theme.paint(GREEN, new));
+        }
+    };
+    let or_dash = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+    let vcs_field = |p: &bird::InvocationProfile, key: &str| {
+        p.vcs.as_ref().and_then(|v| v.get(key)).and_then(|v| v.as_str()).map(str::to_string)
+    };
+
+    println!("--- {} ({}, {}, {})", left_query, short(&left.id), place(&left), left.timestamp);
+    println!("+++ {} ({}, {}, {})", right_query, short(&right.id), place(&right), right.timestamp);
+    row("Command:", left.cmd.clone(), right.cmd.clone());
+    row("Exit code:", or_dash(left.exit_code.map(|c| c.to_string())), or_dash(right.exit_code.map(|c| c.to_string())));
+    row(
+        "Duration:",
+        or_dash(left.duration_ms.map(format_duration_ms)),
+        or_dash(right.duration_ms.map(format_duration_ms)),
+    );
+    row("Commit:", or_dash(vcs_field(&left, "commit")), or_dash(vcs_field(&right, "commit")));
+    if left.vcs.is_some() || right.vcs.is_some() {
+        row("Branch:", or_dash(vcs_field(&left, "branch")), or_dash(vcs_field(&right, "branch")));
+    }
+
+    // Events: what only one side reported
+    let only_left: Vec<_> = left.events.difference(&right.events).collect();
+    let only_right: Vec<_> = right.events.difference(&left.events).collect();
+    let shared = left.events.intersection(&right.events).count();
+    println!(
+        "{:<12} {} shared, {} only in {}, {} only in {}",
+        "Events:",
+        shared,
+        only_left.len(),
+        left_query,
+        only_right.len(),
+        right_query
+    );
+    for event in only_left {
+        println!("  {}", theme.paint(RED, format!("- {}", event)));
+    }
+    for event in only_right {
+        println!("  {}", theme.paint(GREEN, format!("+ {}", event)));
+    }
+
+    match (&left.env, &right.env) {
+        (Some(old_env), Some(new_env)) => {
+            let changes = bird::diff_env(old_env, new_env);
+            println!("{:<12} {} variable(s) differ", "Environment:", changes.len());
+            for change in &changes {
+                match change {
+                    bird::EnvChange::Removed { name, value } => {
+                        println!("  {}", theme.paint(RED, format!("- {}={}", name, value)));
+                    }
+                    bird::EnvChange::Added { name, value } => {
+                        println!("  {}", theme.paint(GREEN, format!("+ {}={}", name, value)));
+                    }
+                    bird::EnvChange::Changed { name, old, new } => {
+                        println!("  {}", theme.paint(RED, format!("- {}={}", name, old)));
+                        println!("  {}", theme.paint(GREEN, format!("+ {}={}", name, new)));
+                    }
+                }
+            }
+        }
+        _ => println!("{:<12} not recorded for both (privacy.record_env)", "Environment:"),
+    }
+
+    Ok(())
+}
+
+/// Print an invocation's word-pair alias (`shq alias`).
+///
+/// With `new`, reserve an alias for a fresh invocation id and print both
+/// instead: the shell hook shows the alias and saves the command under that
+/// id (`SHQ_SHOW_ALIAS`).
+pub fn alias(query_str: &str, new: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+    if new {
+        let id = uuid::Uuid::now_v7();
+        println!("{} {}", id, store.alias_of(&id)?);
+        return Ok(());
+    }
+
+    let invocation_id = match try_find_by_id(&store, query_str)? {
+        Some(id) => id,
+        None => resolve_query_to_invocation(&store, &parse_query_strict(query_str)?)?,
+    };
+    let id = uuid::Uuid::parse_str(&invocation_id)
+        .map_err(|e| bird::Error::Storage(format!("Invalid invocation id: {}", e)))?;
+    println!("{}", store.alias_of(&id)?);
+    Ok(())
+}
+
+pub fn info(query_str: &str, format: &str, field: Option<&str>) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    // First try to find by ID (short or full)
+    let invocation_id = if let Some(id) = try_find_by_id(&store, query_str)? {
+        id
+    } else {
+        // Fall back to query system
+        let query = parse_query_strict(query_str)?;
+        resolve_query_to_invocation(&store, &query)?
+    };
+
+    // Get full invocation details via SQL
+    let result = store.query(&format!(
+        "SELECT id, cmd, cwd, exit_code, timezone('UTC', timestamp)::VARCHAR, duration_ms, session_id, tag
+         FROM invocations
+         WHERE id = '{}'",
+        invocation_id
+    ))?;
+
+    if result.rows.is_empty() {
+        return Err(bird::Error::NotFound(format!("Invocation {} not found", invocation_id)));
+    }
+
+    let row = &result.rows[0];
+    let id = &row[0];
+    let cmd = &row[1];
+    let cwd = &row[2];
+    let exit_code = &row[3];
+    let timestamp = &row[4];
+    let duration_ms = &row[5];
+    let session_id = &row[6];
+    let tag = &row[7];
+
+    // Get output info
+    let outputs = store.get_outputs(&invocation_id, None)?;
+    let stdout_size: i64 = outputs.iter().filter(|o| o.stream == "stdout").map(|o| o.byte_length).sum();
+    let stderr_size: i64 = outputs.iter().filter(|o| o.stream == "stderr").map(|o| o.byte_length).sum();
+
+    // Get event count
+    let event_count = store.event_count(&EventFilters {
+        invocation_id: Some(invocation_id.clone()),
+        ..Default::default()
+    })?;
+
+    let links = store.links_for(&invocation_id)?;
+
+    // Code state: HEAD, and the working-tree snapshot with `snapshot_worktree`
+    let vcs = store.vcs_context(&invocation_id)?;
+    let vcs_field = |key: &str| {
+        vcs.as_ref()
+            .and_then(|v| v.get(key))
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+    };
+    let commit = vcs_field("commit");
+    let snapshot = vcs_field("snapshot");
+    let terminal = store.terminal_context(&invocation_id)?;
+    let terminal_field = |key: &str| {
+        terminal.as_ref().and_then(|t| t.get(key)).and_then(|v| v.as_str()).map(str::to_string)
+    };
+    let alias = match uuid::Uuid::parse_str(id) {
+        Ok(uuid) => store.alias_of(&uuid)?,
+        Err(_) => String::new(),
+    };
+
+    // If a specific field is requested, just print that value (for scripting)
+    if let Some(f) = field {
+        let value = match f.to_lowercase().as_str() {
+            "id" => id.to_string(),
+            "alias" => alias,
+            "cmd" | "command" => cmd.to_string(),
+            "cwd" | "dir" | "working_dir" => cwd.to_string(),
+            "exit" | "exit_code" => exit_code.to_string(),
+            "timestamp" | "time" => timestamp.to_string(),
+            "duration" | "duration_ms" => duration_ms.to_string(),
+            "session" | "session_id" => session_id.to_string(),
+            "tag" => tag.to_string(),
+            "stdout" | "stdout_bytes" => stdout_size.to_string(),
+            "stderr" | "stderr_bytes" => stderr_size.to_string(),
+            "events" | "event_count" => event_count.to_string(),
+            "links" => links.iter().map(|l| l.target.as_str()).collect::<Vec<_>>().join("\n"),
+            "commit" => commit.unwrap_or_default(),
+            "snapshot" => snapshot.unwrap_or_default(),
+            "terminal" => terminal.as_ref().map(describe_terminal).unwrap_or_default(),
+            "window" => terminal_field("window").unwrap_or_default(),
+            "title" => terminal_field("title").unwrap_or_default(),
+            _ => return Err(bird::Error::Config(format!("Unknown field: {}", f))),
+        };
+        println!("{}", value);
+        return Ok(());
+    }
+
+    match format {
+        "json" => {
+            println!(r#"{{"#);
+            println!(r#"  "id": "{}","#, id);
+            println!(r#"  "alias": "{}","#, alias);
+            println!(r#"  "timestamp": "{}","#, timestamp);
+            println!(r#"  "cmd": "{}","#, cmd.replace('\\', "\\\\").replace('"', "\\\""));
+            println!(r#"  "cwd": "{}","#, cwd.replace('\\', "\\\\").replace('"', "\\\""));
+            println!(r#"  "exit_code": {},"#, exit_code);
+            println!(r#"  "duration_ms": {},"#, duration_ms);
+            println!(r#"  "session_id": "{}","#, session_id);
+            if tag != "NULL" && !tag.is_empty() {
+                println!(r#"  "tag": "{}","#, tag);
+            }
+            println!(r#"  "stdout_bytes": {},"#, stdout_size);
+            println!(r#"  "stderr_bytes": {},"#, stderr_size);
+            println!(r#"  "event_count": {},"#, event_count);
+            if let Some(commit) = &commit {
+                println!(r#"  "commit": "{}","#, commit);
+            }
+            if let Some(snapshot) = &snapshot {
+                println!(r#"  "snapshot": "{}","#, snapshot);
+            }
+            if let Some(terminal) = &terminal {
+                println!(r#"  "terminal": {},"#, terminal);
+            }
+            let links_json: Vec<_> = links
+                .iter()
+                .map(|l| serde_json::json!({"kind": l.kind, "target": l.target}))
+                .collect();
+            println!(r#"  "links": {}"#, serde_json::Value::Array(links_json));
+            println!(r#"}}"#);
+        }
+        _ => {
+            // Table format
+            println!("ID:          {}", id);
+            println!("Alias:       {}", alias);
+            println!(
+                "Timestamp:   {} ({})",
+                display_time(timestamp, true),
+                display_time(timestamp, false)
+            );
+            println!("Command:     {}", cmd);
+            println!("Working Dir: {}", cwd);
+            println!("Exit Code:   {}", exit_code);
+            println!("Duration:    {}ms", duration_ms);
+            println!("Session:     {}", session_id);
+            if tag != "NULL" && !tag.is_empty() {
+                println!("Tag:         {}", tag);
+            }
+            println!("Stdout:      {} bytes", stdout_size);
+            println!("Stderr:      {} bytes", stderr_size);
+            println!("Events:      {}", event_count);
+            if let Some(commit) = &commit {
+                println!("Commit:      {}", commit);
+            }
+            if let Some(snapshot) = &snapshot {
+                println!("Snapshot:    {}", snapshot);
+            }
+            if let Some(terminal) = &terminal {
+                println!("Terminal:    {}", describe_terminal(terminal));
+            }
+            for link in &links {
+                println!("Link:        {} {}", link.kind, link.target);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// One-line description of recorded terminal context, e.g.
+/// `WezTerm, tmux work:2, "vim main.rs"`.
+fn describe_terminal(terminal: &serde_json::Value) -> String {
+    let field = |key: &str| terminal.get(key).and_then(|v| v.as_str());
+    let mut parts = Vec::new();
+    if let Some(program) = field("program") {
+        parts.push(program.to_string());
+    }
+    match (field("mux"), field("window").or(field("pane"))) {
+        (Some(mux), Some(window)) => parts.push(format!("{} {}", mux, window)),
+        (Some(mux), None) => parts.push(mux.to_string()),
+        (None, Some(window)) => parts.push(format!("window {}", window)),
+        (None, None) => {}
+    }
+    if let Some(title) = field("title") {
+        parts.push(format!("\"{}\"", title));
+    }
+    parts.join(", ")
+}
+
+/// Re-run a previous command.
+///
+/// With `at_snapshot`, it runs in a temporary git worktree checked out at the
+/// working-tree snapshot recorded with the invocation (`snapshot_worktree`),
+/// leaving the current checkout alone.
+pub fn rerun(query_str: &str, dry_run: bool, no_capture: bool, at_snapshot: bool) -> bird::Result<()> {
+    use std::io::Write;
+
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    // First try to find by ID (short or full)
+    let invocation_id = if let Some(id) = try_find_by_id(&store, query_str)? {
+        id
+    } else {
+        // Fall back to query system
+        let query = parse_query_strict(query_str)?;
+        resolve_query_to_invocation(&store, &query)?
+    };
+
+    // Get full invocation details via SQL (need cmd and cwd)
+    let result = store.query(&format!(
+        "SELECT cmd, cwd FROM invocations WHERE id = '{}'",
+        invocation_id
+    ))?;
+
+    if result.rows.is_empty() {
+        return Err(bird::Error::NotFound(format!("Invocation {} not found", invocation_id)));
+    }
+
+    let cmd = &result.rows[0][0];
+    let cwd = &result.rows[0][1];
+
+    let worktree = if at_snapshot {
+        Some(SnapshotWorktree::for_invocation(&store, &invocation_id, cwd)?)
+    } else {
+        None
+    };
+    let run_dir = worktree.as_ref().map_or(cwd.as_str(), |w| w.cwd.as_str()).to_string();
+
+    if dry_run {
+        println!("Would run: {}", cmd);
+        println!("In directory: {}", run_dir);
+        if let Some(worktree) = worktree {
+            println!("At snapshot: {}", worktree.snapshot);
+            worktree.remove();
+        }
+        return Ok(());
+    }
+
+    // Print the command being re-run
+    eprintln!("{}", crate::theme::current().paint(DIM, format!("$ {}", cmd)));
+
+    if no_capture {
+        // Just execute without capturing
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+        let status = Command::new(&shell)
+            .arg("-c")
+            .arg(cmd)
+            .current_dir(&run_dir)
+            .status();
+        if let Some(worktree) = worktree {
+            worktree.remove();
+        }
+        let status = status?;
+
+        if !status.success() {
+            std::process::exit(status.code().unwrap_or(1));
+        }
+    } else {
+        // Use shq run to capture the command
+        let start = std::time::Instant::now();
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+        let output = Command::new(&shell)
+            .arg("-c")
+            .arg(cmd)
+            .current_dir(&run_dir)
+            .output();
+        let duration_ms = start.elapsed().as_millis() as i64;
+        // Context is collected from the snapshot's checkout, so keep it until then
+        let context = ContextMetadata::collect(Some(std::path::Path::new(&run_dir)));
+        if let Some(worktree) = worktree {
+            worktree.remove();
+        }
+        let output = output?;
+
+        // Display output
+        if !output.stdout.is_empty() {
+            io::stdout().write_all(&output.stdout)?;
+        }
+        if !output.stderr.is_empty() {
+            io::stderr().write_all(&output.stderr)?;
+        }
+
+        let exit_code = output.status.code().unwrap_or(-1);
+
+        // Save to BIRD
+        let config = Config::load()?;
+        let store = Store::open(config.clone())?;
+        let sid = session_id();
+
+        let session = SessionRecord::new(
+            &sid,
+            &config.client_id,
+            invoker_name(),
+            invoker_pid(),
+            "shell",
+        );
+
+        let record = InvocationRecord::new(
+            &sid,
+            cmd,
+            cwd,
+            exit_code,
+            &config.client_id,
+        )
+        .with_duration(duration_ms)
+        .with_metadata(context.into_map());
+
+        // Build batch with all related records
+        let mut batch = InvocationBatch::new(record).with_session(session);
+
+        if !output.stdout.is_empty() {
+            batch = batch.with_output("stdout", output.stdout.clone());
+        }
+        if !output.stderr.is_empty() {
+            batch = batch.with_output("stderr", output.stderr.clone());
+        }
+
+        // Write everything atomically
+        store.write_batch(&batch)?;
+
+        if !output.status.success() {
+            std::process::exit(exit_code);
+        }
+    }
+
+    Ok(())
+}
+
+/// A temporary git worktree checked out at an invocation's working-tree
+/// snapshot, for `shq rerun --at-snapshot`.
+struct SnapshotWorktree {
+    /// Repository the worktree belongs to.
+    root: String,
+    path: std::path::PathBuf,
+    /// The invocation's cwd, within the worktree.
+    cwd: String,
+    snapshot: String,
+}
+
+impl SnapshotWorktree {
+    fn for_invocation(store: &Store, invocation_id: &str, cwd: &str) -> bird::Result<Self> {
+        let vcs = store.vcs_context(invocation_id)?.unwrap_or_default();
+        let field = |key: &str| vcs.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        let (Some(snapshot), Some(root)) = (field("snapshot"), field("root")) else {
+            return Err(bird::Error::NotFound(format!(
+                "No working-tree snapshot recorded for {} (set snapshot_worktree = true to record them)",
+                invocation_id
+            )));
+        };
+
+        let path = std::env::temp_dir().join(format!("shq-rerun-{}", invocation_id));
+        let status = Command::new("git")
+            .args(["worktree", "add", "--detach", "--quiet"])
+            .arg(&path)
+            .arg(&snapshot)
+            .current_dir(&root)
+            .status()?;
+        if !status.success() {
+            return Err(bird::Error::Config(format!(
+                "Could not check out snapshot {} in {} (it may have been pruned by git gc)",
+                snapshot, root
+            )));
+        }
+
+        let relative = std::path::Path::new(cwd).strip_prefix(&root).unwrap_or(std::path::Path::new(""));
+        Ok(Self {
+            cwd: path.join(relative).display().to_string(),
+            root,
+            path,
+            snapshot,
+        })
+    }
+
+    fn remove(self) {
+        let _ = Command::new("git")
+            .args(["worktree", "remove", "--force"])
+            .arg(&self.path)
+            .current_dir(&self.root)
+            .status();
+    }
+}
+
+const QUICK_HELP: &str = r#"
+SHQ QUICK REFERENCE
+===================
+
+COMMANDS                                    EXAMPLES
+────────────────────────────────────────────────────────────────────────────────
+output (o, show)   Show captured output     shq o ~1          shq o %/make/~1
+invocations (i)    List command history     shq i -n 20       shq i %exit<>0~10:
+events (e)         Show parsed events       shq e -n 10       shq e -s error~5:
+info (I)           Invocation details       shq I ~1          shq I %/test/~1
+rerun (R, !!)      Re-run a command         shq R ~1          shq R %/make/~1
+run (r)            Run and capture          shq r cargo test  shq r -c "make all"
+sql (q)            Execute SQL query        shq q "SELECT * FROM invocations LIMIT 5"
+
+QUERY SYNTAX: [source][path][filters][range]
+────────────────────────────────────────────────────────────────────────────────
+RANGE         ~1          Most recent command (position 1)
+              ~5          5th most recent command (position 5)
+              ~10:        Last 10 commands (positions 1-10)
+              ~10:~5      Range from position 10 to 5
+              -n 10       CLI flag: last 10 (same as ~10:)
+
+SOURCE        Format: host:type:client:session:
+              shell:           Shell commands on this host
+              shell:bash:      Bash shells only
+              shell:zsh:       Zsh shells only
+              myhost:shell::   All shells on myhost
+              *:*:*:*:         Everything everywhere (all hosts, all types)
+              *:shell:*:*:     All shell commands on all hosts
+
+PATH          .           Current directory
+              ~/Projects/ Home-relative
+              /tmp/       Absolute path
+
+FILTERS       %failed     Non-zero exit code (alias for %exit<>0)
+              %success    Successful commands (alias for %exit=0)
+              %ok         Same as %success
+              %exit<>0    Non-zero exit code
+              %exit=0     Successful commands only
+              %duration>5000   Took > 5 seconds
+              %cmd~=test  Command matches regex
+              %cwd~=/src/ Working dir matches
+
+CMD REGEX     %/make/     Commands containing "make"
+              %/^cargo/   Commands starting with "cargo"
+              %/test$/    Commands ending with "test"
+
+OPERATORS     =  equals      <>  not equals     ~=  regex match
+              >  greater     <   less           >=  gte    <=  lte
+
+EXAMPLES
+────────────────────────────────────────────────────────────────────────────────
+shq o                        Show output of last command (default: 1)
+shq o 1                      Same as above
+shq o -E 1                   Show only stderr of last command
+shq o %/make/~1              Output of last make command
+shq o %exit<>0~1             Output of last failed command
+
+shq i                        Last 20 commands (default)
+shq i 50                     Last 50 commands
+shq i %failed~20             Last 20 failed commands
+shq i %failed                All failed commands (up to default limit)
+shq i %duration>10000~10     Last 10 commands that took >10s
+shq i %/cargo/~10            Last 10 cargo commands
+
+shq e                        Events from last 10 commands (default)
+shq e 5                      Events from last 5 commands
+shq e -s error 10            Only errors from last 10 commands
+shq e %/cargo build/~1       Events from last cargo build
+
+shq R                        Re-run last command
+shq R 3                      Re-run 3rd-last command
+shq R %/make test/~1         Re-run last "make test"
+shq R -n %/deploy/~1         Dry-run: show what would run
+
+shq I                        Details about last command
+shq I -f json 1              Details as JSON
+
+.~5                          Last 5 commands in current directory
+~/Projects/foo/~10           Last 10 in ~/Projects/foo/
+shell:%exit<>0~5             Last 5 failed shell commands
+
+"#;
+
+
+
+// ZSH hook without prompt indicator - same as ZSH_HOOK but without PS1 modification
+
+// Format hints management
+
+/// List format hints (user-defined and optionally built-in).
+pub fn format_hints_list(show_builtin: bool, show_user: bool, filter: Option<&str>) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let hints = store.load_format_hints()?;
+
+    // Helper to check if a hint matches the filter
+    let matches_filter = |pattern: &str, format: &str| -> bool {
+        match filter {
+            None => true,
+            Some(f) => {
+                let f_lower = f.to_lowercase();
+                pattern.to_lowercase().contains(&f_lower) || format.to_lowercase().contains(&f_lower)
+            }
+        }
+    };
+
+    // Show user-defined hints
+    if show_user {
+        let user_hints: Vec<_> = hints.hints()
+            .iter()
+            .filter(|h| matches_filter(&h.pattern, &h.format))
+            .collect();
+
+        if user_hints.is_empty() {
+            if filter.is_some() {
+                println!("No user-defined format hints matching filter.");
+            } else {
+                println!("No user-defined format hints.");
+            }
+        } else {
+            println!("User-defined format hints:");
+            println!("{:<6} {:<30} FORMAT", "PRI", "PATTERN");
+            println!("{}", "-".repeat(60));
+            for hint in user_hints {
+                println!("{:<6} {:<30} {}", hint.priority, hint.pattern, hint.format);
+            }
+        }
+        println!();
+    }
+
+    // Show built-in formats from duck_hunt
+    if show_builtin {
+        match store.list_builtin_formats() {
+            Ok(formats) => {
+                let filtered: Vec<_> = formats.iter()
+                    .filter(|f| matches_filter(&f.pattern, &f.format))
+                    .collect();
+
+                if filtered.is_empty() {
+                    if filter.is_some() {
+                        println!("No built-in formats matching filter.");
+                    } else {
+                        println!("No built-in formats available.");
+                    }
+                } else {
+                    println!("Available formats (from duck_hunt):");
+                    println!("{:<6} {:<20} DESCRIPTION", "PRI", "FORMAT");
+                    println!("{}", "-".repeat(70));
+                    for fmt in filtered {
+                        // pattern field contains description for builtin formats
+                        let desc = if fmt.pattern.len() > 45 {
+                            format!("{}...", &fmt.pattern[..42])
+                        } else {
+                            fmt.pattern.clone()
+                        };
+                        println!("{:<6} {:<20} {}", fmt.priority, fmt.format, desc);
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("Warning: Could not list built-in formats: {}", e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Add a format hint.
+pub fn format_hints_add(pattern: &str, format: &str, priority: Option<i32>, dry_run: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let mut hints = store.load_format_hints()?;
+
+    let priority = priority.unwrap_or(bird::format_hints::DEFAULT_PRIORITY);
+    let hint = bird::FormatHint::with_priority(pattern, format, priority);
+
+    // Check if pattern already exists
+    if hints.get(pattern).is_some() && !dry_run {
+        println!("Updating existing pattern: {}", pattern);
+    }
+
+    hints.add(hint);
+    if dry_run {
+        print!("{}", store.plan_format_hints(&hints)?);
+        return Ok(());
+    }
+    store.save_format_hints(&hints)?;
+
+    println!("Added: {} -> {} (priority {})", pattern, format, priority);
+    Ok(())
+}
+
+/// Remove a format hint by pattern.
+pub fn format_hints_remove(pattern: &str, dry_run: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let mut hints = store.load_format_hints()?;
+
+    if dry_run {
+        hints.remove(pattern);
+        print!("{}", store.plan_format_hints(&hints)?);
+    } else if hints.remove(pattern) {
+        store.save_format_hints(&hints)?;
+        println!("Removed: {}", pattern);
+    } else {
+        println!("Pattern not found: {}", pattern);
+    }
+
+    Ok(())
+}
+
+/// Check which format would be detected for a command.
+pub fn format_hints_check(cmd: &str) -> bird::Result<()> {
+    use bird::FormatSource;
+
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let result = store.check_format(cmd)?;
+
+    println!("Command: {}", cmd);
+    println!("Format:  {}", result.format);
+
+    match result.source {
+        FormatSource::UserDefined { pattern, priority } => {
+            println!("Source:  user-defined (pattern: {}, priority: {})", pattern, priority);
+        }
+        FormatSource::Builtin { pattern, priority } => {
+            println!("Source:  built-in (pattern: {}, priority: {})", pattern, priority);
+        }
+        FormatSource::Default => {
+            println!("Source:  default (no pattern matched)");
+        }
+    }
+
+    Ok(())
+}
+
+/// Set the default format.
+pub fn format_hints_set_default(format: &str, dry_run: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let mut hints = store.load_format_hints()?;
+    hints.set_default_format(format);
+    if dry_run {
+        print!("{}", store.plan_format_hints(&hints)?);
+        return Ok(());
+    }
+    store.save_format_hints(&hints)?;
+
+    println!("Default format set to: {}", format);
+    Ok(())
+}
+
+/// Export user-defined format hints as TOML to stdout.
+pub fn format_hints_export() -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    print!("{}", store.load_format_hints()?.to_toml());
+    Ok(())
+}
+
+/// Import format hints from a TOML file (or stdin with "-").
+pub fn format_hints_import(file: &str, strategy: &str, dry_run: bool) -> bird::Result<()> {
+    let strategy: bird::MergeStrategy = strategy.parse()?;
+    let contents = if file == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        std::fs::read_to_string(file)?
+    };
+    let incoming = bird::FormatHints::parse(&contents)?;
+
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let mut hints = store.load_format_hints()?;
+    let summary = hints.merge(&incoming, strategy);
+    if dry_run {
+        print!("{}", store.plan_format_hints(&hints)?);
+        return Ok(());
+    }
+    store.save_format_hints(&hints)?;
+
+    println!(
+        "Imported {}: {} added, {} updated, {} skipped",
+        file, summary.added, summary.updated, summary.skipped
+    );
+    Ok(())
+}
+
+/// Install a built-in hint pack, or list the available packs.
+pub fn format_hints_install(pack: Option<&str>, strategy: &str, dry_run: bool) -> bird::Result<()> {
+    let Some(name) = pack else {
+        println!("Available hint packs:");
+        for pack in bird::format_hints::PACKS {
+            println!("  {:<10} {}", pack.name, pack.description);
+        }
+        return Ok(());
+    };
+
+    let strategy: bird::MergeStrategy = strategy.parse()?;
+    let pack = bird::format_hints::pack(name).ok_or_else(|| {
+        let names: Vec<_> = bird::format_hints::PACKS.iter().map(|p| p.name).collect();
+        bird::Error::Config(format!(
+            "Unknown hint pack '{}' (available: {})",
+            name,
+            names.join(", ")
+        ))
+    })?;
+
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let mut hints = store.load_format_hints()?;
+    let summary = hints.merge(&pack.hints()?, strategy);
+    if dry_run {
+        print!("{}", store.plan_format_hints(&hints)?);
+        return Ok(());
     }
+    store.save_format_hints(&hints)?;
+
+    println!(
+        "Installed pack '{}': {} added, {} updated, {} skipped",
+        pack.name, summary.added, summary.updated, summary.skipped
+    );
+    Ok(())
+}
+
+// Remote management commands
+
+/// Add a remote storage connection.
+pub fn remote_add(
+    name: &str,
+    remote_type: &str,
+    uri: &str,
+    read_only: bool,
+    credential_provider: Option<&str>,
+    auto_attach: bool,
+    signing: bird::SigningPolicy,
+) -> bird::Result<()> {
+    use bird::{RemoteConfig, RemoteMode, RemoteType};
+    use std::str::FromStr;
 
-    Ok(None)
-}
+    let mut config = Config::load()?;
 
-/// Resolve a query to a single invocation ID.
-fn resolve_query_to_invocation(store: &Store, query: &Query) -> bird::Result<String> {
-    // For single-item commands, the range selector determines which item:
-    // - ~N = single item at position N (1 = most recent)
-    // - ~N: = last N items, we take the most recent (position 1)
-    // - ~N:~M = range from N to M, we take position M (most recent in range)
-    // - No range = default to position 1 (most recent)
+    let rtype = RemoteType::from_str(remote_type)?;
+    let mut remote = RemoteConfig::new(name, rtype, uri);
 
-    // query_invocations_with_limit handles the range semantics, returning
-    // the correct subset. For single-item commands, we just take the first result.
-    let invocations = store.query_invocations_with_limit(query, 1)?;
+    if read_only {
+        remote.mode = RemoteMode::ReadOnly;
+    }
+    if let Some(provider) = credential_provider {
+        remote.credential_provider = Some(provider.to_string());
+    }
+    remote.auto_attach = auto_attach;
+    remote.signing = signing.clone();
 
-    if let Some(inv) = invocations.first() {
-        Ok(inv.id.clone())
+    // Check if updating existing
+    let updating = config.get_remote(name).is_some();
+
+    config.add_remote(remote);
+    config.save()?;
+
+    if updating {
+        println!("Updated remote: {}", name);
     } else {
-        Err(bird::Error::NotFound("No matching invocation found".to_string()))
+        println!("Added remote: {}", name);
+    }
+    println!("  Type: {}", remote_type);
+    println!("  URI:  {}", uri);
+    println!("  Mode: {}", if read_only { "read-only" } else { "read-write" });
+    if let Some(provider) = credential_provider {
+        println!("  Credentials: {}", provider);
+    }
+    println!("  Auto-attach: {}", auto_attach);
+    let signing_modes: Vec<&str> = [(signing.sign, "sign"), (signing.verify, "verify")]
+        .into_iter()
+        .filter_map(|(on, mode)| on.then_some(mode))
+        .collect();
+    if !signing_modes.is_empty() {
+        println!("  Signing: {}", signing_modes.join(", "));
     }
+
+    Ok(())
 }
 
-/// Show detailed info about an invocation.
-pub fn info(query_str: &str, format: &str, field: Option<&str>) -> bird::Result<()> {
+/// List configured remotes.
+pub fn remote_list() -> bird::Result<()> {
     let config = Config::load()?;
-    let store = Store::open(config)?;
 
-    // First try to find by ID (short or full)
-    let invocation_id = if let Some(id) = try_find_by_id(&store, query_str)? {
-        id
+    if config.remotes.is_empty() {
+        println!("No remotes configured.");
+        println!();
+        println!("Add a remote with:");
+        println!("  shq remote add <name> --type s3 --uri s3://bucket/path/bird.duckdb");
+        return Ok(());
+    }
+
+    println!("{:<12} {:<12} {:<10} {:<8} URI", "NAME", "TYPE", "MODE", "ATTACH");
+    println!("{}", "-".repeat(70));
+
+    for remote in &config.remotes {
+        println!(
+            "{:<12} {:<12} {:<10} {:<8} {}",
+            remote.name,
+            remote.remote_type,
+            remote.mode,
+            if remote.auto_attach { "auto" } else { "manual" },
+            remote.uri
+        );
+    }
+
+    Ok(())
+}
+
+/// Mount the BIRD root at `path` read-only under `name`.
+pub fn mount(path: &std::path::Path, name: Option<&str>) -> bird::Result<()> {
+    let mut config = Config::load()?;
+
+    // Accept the root, its database file or a bundle (unpacked under mounts/)
+    let path = std::fs::canonicalize(path)
+        .map_err(|e| bird::Error::InvalidPath(format!("{}: {}", path.display(), e)))?;
+    let root = if path.extension().is_some_and(|ext| ext == bird::BUNDLE_EXTENSION) {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let dest = config.bird_root.join("mounts").join(&stem);
+        if !Config::with_root(&dest).db_path().exists() {
+            let manifest = bird::unpack_bundle(&path, &dest)?;
+            println!(
+                "Unpacked {} ({} to {}) to {}",
+                path.display(),
+                manifest.first_date,
+                manifest.last_date,
+                dest.display()
+            );
+        }
+        dest
+    } else if path.is_file() {
+        path.parent().and_then(|db| db.parent()).map(std::path::Path::to_path_buf).unwrap_or_default()
     } else {
-        // Fall back to query system
-        let query = parse_query(query_str);
-        resolve_query_to_invocation(&store, &query)?
+        path.clone()
     };
+    let mounted = Config::load_from(&root)?;
+    if !mounted.db_path().exists() {
+        return Err(bird::Error::NotFound(format!("BIRD store at {} (no db/bird.duckdb)", root.display())));
+    }
+    if std::fs::canonicalize(&config.bird_root).ok().as_deref() == Some(root.as_path()) {
+        return Err(bird::Error::Config("That is the current BIRD root".to_string()));
+    }
 
-    // Get full invocation details via SQL
-    let result = store.query(&format!(
-        "SELECT id, cmd, cwd, exit_code, timestamp, duration_ms, session_id, tag
-         FROM invocations
-         WHERE id = '{}'",
-        invocation_id
-    ))?;
-
-    if result.rows.is_empty() {
-        return Err(bird::Error::NotFound(format!("Invocation {} not found", invocation_id)));
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => root
+            .file_name()
+            .map(|n| n.to_string_lossy().replace(|c: char| !c.is_ascii_alphanumeric(), "_"))
+            .unwrap_or_default(),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(bird::Error::Config(format!(
+            "Invalid mount name '{}': use letters, digits and underscores",
+            name
+        )));
+    }
+    if let Some(existing) = config.get_remote(&name) {
+        if existing.mounted_root().is_none() {
+            return Err(bird::Error::Config(format!(
+                "'{}' is already a remote; choose another name with --as",
+                name
+            )));
+        }
     }
 
-    let row = &result.rows[0];
-    let id = &row[0];
-    let cmd = &row[1];
-    let cwd = &row[2];
-    let exit_code = &row[3];
-    let timestamp = &row[4];
-    let duration_ms = &row[5];
-    let session_id = &row[6];
-    let tag = &row[7];
+    let remote = bird::RemoteConfig::mount(name.as_str(), &root);
+    let schema = remote.schema_name();
+    config.add_remote(remote);
+    config.save()?;
 
-    // Get output info
-    let outputs = store.get_outputs(&invocation_id, None)?;
-    let stdout_size: i64 = outputs.iter().filter(|o| o.stream == "stdout").map(|o| o.byte_length).sum();
-    let stderr_size: i64 = outputs.iter().filter(|o| o.stream == "stderr").map(|o| o.byte_length).sum();
+    println!("Mounted {} as '{}' (read-only, {} storage)", root.display(), name, mounted.storage_mode);
+    println!("  Query: shq sql \"SELECT cmd, timestamp FROM {}.invocations LIMIT 10\"", schema);
+    println!("  With current history: shq sql \"SELECT * FROM unified.invocations\"");
+    Ok(())
+}
 
-    // Get event count
-    let event_count = store.event_count(&EventFilters {
-        invocation_id: Some(invocation_id.clone()),
-        ..Default::default()
-    })?;
+/// List mounted BIRD roots.
+pub fn mount_list() -> bird::Result<()> {
+    let config = Config::load()?;
+    let mounts: Vec<_> = config
+        .remotes
+        .iter()
+        .filter_map(|r| r.mounted_root().map(|root| (r, root)))
+        .collect();
 
-    // If a specific field is requested, just print that value (for scripting)
-    if let Some(f) = field {
-        let value = match f.to_lowercase().as_str() {
-            "id" => id.to_string(),
-            "cmd" | "command" => cmd.to_string(),
-            "cwd" | "dir" | "working_dir" => cwd.to_string(),
-            "exit" | "exit_code" => exit_code.to_string(),
-            "timestamp" | "time" => timestamp.to_string(),
-            "duration" | "duration_ms" => duration_ms.to_string(),
-            "session" | "session_id" => session_id.to_string(),
-            "tag" => tag.to_string(),
-            "stdout" | "stdout_bytes" => stdout_size.to_string(),
-            "stderr" | "stderr_bytes" => stderr_size.to_string(),
-            "events" | "event_count" => event_count.to_string(),
-            _ => return Err(bird::Error::Config(format!("Unknown field: {}", f))),
-        };
-        println!("{}", value);
+    if mounts.is_empty() {
+        println!("No BIRD roots mounted. Mount one with: shq mount PATH --as NAME");
         return Ok(());
     }
 
-    match format {
-        "json" => {
-            println!(r#"{{"#);
-            println!(r#"  "id": "{}","#, id);
-            println!(r#"  "timestamp": "{}","#, timestamp);
-            println!(r#"  "cmd": "{}","#, cmd.replace('\\', "\\\\").replace('"', "\\\""));
-            println!(r#"  "cwd": "{}","#, cwd.replace('\\', "\\\\").replace('"', "\\\""));
-            println!(r#"  "exit_code": {},"#, exit_code);
-            println!(r#"  "duration_ms": {},"#, duration_ms);
-            println!(r#"  "session_id": "{}","#, session_id);
-            if tag != "NULL" && !tag.is_empty() {
-                println!(r#"  "tag": "{}","#, tag);
-            }
-            println!(r#"  "stdout_bytes": {},"#, stdout_size);
-            println!(r#"  "stderr_bytes": {},"#, stderr_size);
-            println!(r#"  "event_count": {}"#, event_count);
-            println!(r#"}}"#);
+    println!("{:<12} {:<16} {:<10} ROOT", "NAME", "SCHEMA", "STATUS");
+    for (remote, root) in mounts {
+        let status = if Config::with_root(&root).db_path().exists() { "ok" } else { "missing" };
+        println!("{:<12} {:<16} {:<10} {}", remote.name, remote.schema_name(), status, root.display());
+    }
+    Ok(())
+}
+
+/// Unmount a BIRD root mounted with `shq mount`.
+pub fn unmount(name: &str) -> bird::Result<()> {
+    let mut config = Config::load()?;
+    match config.get_remote(name) {
+        None => println!("Mount not found: {}", name),
+        Some(remote) if remote.mounted_root().is_none() => {
+            return Err(bird::Error::Config(format!(
+                "'{}' is a remote, not a mount (use: shq remote remove {})",
+                name, name
+            )));
         }
-        _ => {
-            // Table format
-            println!("ID:          {}", id);
-            println!("Timestamp:   {}", timestamp);
-            println!("Command:     {}", cmd);
-            println!("Working Dir: {}", cwd);
-            println!("Exit Code:   {}", exit_code);
-            println!("Duration:    {}ms", duration_ms);
-            println!("Session:     {}", session_id);
-            if tag != "NULL" && !tag.is_empty() {
-                println!("Tag:         {}", tag);
+        Some(remote) => {
+            // Unpacked bundles are only a cache of the bundle file
+            let unpacked = remote.mounted_root().filter(|root| root.starts_with(config.bird_root.join("mounts")));
+            config.remove_remote(name);
+            config.save()?;
+            if let Some(root) = unpacked {
+                std::fs::remove_dir_all(&root)?;
             }
-            println!("Stdout:      {} bytes", stdout_size);
-            println!("Stderr:      {} bytes", stderr_size);
-            println!("Events:      {}", event_count);
+            println!("Unmounted: {}", name);
         }
     }
-
     Ok(())
 }
 
-/// Re-run a previous command.
-pub fn rerun(query_str: &str, dry_run: bool, no_capture: bool) -> bird::Result<()> {
-    use std::io::Write;
-
-    let config = Config::load()?;
-    let store = Store::open(config)?;
+/// Remove a remote configuration.
+pub fn remote_remove(name: &str) -> bird::Result<()> {
+    let mut config = Config::load()?;
 
-    // First try to find by ID (short or full)
-    let invocation_id = if let Some(id) = try_find_by_id(&store, query_str)? {
-        id
+    if config.remove_remote(name) {
+        config.save()?;
+        println!("Removed remote: {}", name);
     } else {
-        // Fall back to query system
-        let query = parse_query(query_str);
-        resolve_query_to_invocation(&store, &query)?
-    };
+        println!("Remote not found: {}", name);
+    }
 
-    // Get full invocation details via SQL (need cmd and cwd)
-    let result = store.query(&format!(
-        "SELECT cmd, cwd FROM invocations WHERE id = '{}'",
-        invocation_id
-    ))?;
+    Ok(())
+}
 
-    if result.rows.is_empty() {
-        return Err(bird::Error::NotFound(format!("Invocation {} not found", invocation_id)));
-    }
+/// Test connection to a remote.
+pub fn remote_test(name: Option<&str>) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config.clone())?;
 
-    let cmd = &result.rows[0][0];
-    let cwd = &result.rows[0][1];
+    let remotes_to_test: Vec<_> = if let Some(n) = name {
+        match config.get_remote(n) {
+            Some(r) => vec![r],
+            None => {
+                println!("Remote not found: {}", n);
+                return Ok(());
+            }
+        }
+    } else {
+        config.remotes.iter().collect()
+    };
 
-    if dry_run {
-        println!("Would run: {}", cmd);
-        println!("In directory: {}", cwd);
+    if remotes_to_test.is_empty() {
+        println!("No remotes configured.");
         return Ok(());
     }
 
-    // Print the command being re-run
-    eprintln!("\x1b[2m$ {}\x1b[0m", cmd);
-
-    if no_capture {
-        // Just execute without capturing
-        let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
-        let status = Command::new(&shell)
-            .arg("-c")
-            .arg(cmd)
-            .current_dir(cwd)
-            .status()?;
-
-        if !status.success() {
-            std::process::exit(status.code().unwrap_or(1));
+    for remote in remotes_to_test {
+        print!("Testing {}... ", remote.name);
+        match store.test_remote(remote) {
+            Ok(()) => println!("OK"),
+            Err(e) => println!("FAILED: {}", e),
         }
-    } else {
-        // Use shq run to capture the command
-        let start = std::time::Instant::now();
-        let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
-        let output = Command::new(&shell)
-            .arg("-c")
-            .arg(cmd)
-            .current_dir(cwd)
-            .output()?;
-        let duration_ms = start.elapsed().as_millis() as i64;
+    }
+
+    Ok(())
+}
 
-        // Display output
-        if !output.stdout.is_empty() {
-            io::stdout().write_all(&output.stdout)?;
+/// Manually attach a remote (shows SQL to run).
+pub fn remote_attach(name: &str) -> bird::Result<()> {
+    let config = Config::load()?;
+
+    match config.get_remote(name) {
+        Some(remote) => {
+            println!("To attach this remote in SQL:");
+            println!();
+            if remote.credential_provider.is_some() {
+                println!("LOAD httpfs;");
+                println!(
+                    "CREATE SECRET IF NOT EXISTS \"bird_{}\" (TYPE s3, PROVIDER credential_chain);",
+                    remote.name
+                );
+            }
+            println!("{};", remote.attach_sql());
+            println!();
+            println!("Then query with: SELECT * FROM {}.invocations LIMIT 10;", remote.quoted_schema_name());
         }
-        if !output.stderr.is_empty() {
-            io::stderr().write_all(&output.stderr)?;
+        None => {
+            println!("Remote not found: {}", name);
         }
+    }
 
-        let exit_code = output.status.code().unwrap_or(-1);
+    Ok(())
+}
 
-        // Save to BIRD
-        let config = Config::load()?;
-        let store = Store::open(config.clone())?;
-        let sid = session_id();
+/// Show remote sync status.
+pub fn remote_status() -> bird::Result<()> {
+    use bird::PushOptions;
 
-        let session = SessionRecord::new(
-            &sid,
-            &config.client_id,
-            invoker_name(),
-            invoker_pid(),
-            "shell",
-        );
+    let config = Config::load()?;
+    let store = Store::open(config.clone())?;
 
-        // Collect context metadata (VCS, CI)
-        let context = ContextMetadata::collect(Some(std::path::Path::new(cwd)));
+    println!("Sync Configuration:");
+    println!("  Default remote:    {}", config.sync.default_remote.as_deref().unwrap_or("(none)"));
+    println!("  Push on compact:   {}", config.sync.push_on_compact);
+    println!("  Push on archive:   {}", config.sync.push_on_archive);
+    println!("  Sync invocations:  {}", config.sync.sync_invocations);
+    println!("  Sync outputs:      {}", config.sync.sync_outputs);
+    println!("  Sync events:       {}", config.sync.sync_events);
+    println!("  Sync blobs:        {}", config.sync.sync_blobs);
+    if config.sync.sync_blobs {
+        println!("  Blob min size:     {} bytes", config.sync.blob_sync_min_bytes);
+    }
+    println!();
 
-        let record = InvocationRecord::new(
-            &sid,
-            cmd,
-            cwd,
-            exit_code,
-            &config.client_id,
-        )
-        .with_duration(duration_ms)
-        .with_metadata(context.into_map());
+    println!("Blob Roots (search order):");
+    for (i, root) in config.blob_roots().iter().enumerate() {
+        println!("  {}. {}", i + 1, root);
+    }
+    println!();
 
-        // Build batch with all related records
-        let mut batch = InvocationBatch::new(record).with_session(session);
+    if config.remotes.is_empty() {
+        println!("No remotes configured.");
+    } else {
+        println!("Configured Remotes:");
+        for remote in &config.remotes {
+            println!("  {} ({}, {})", remote.name, remote.remote_type, remote.mode);
 
-        if !output.stdout.is_empty() {
-            batch = batch.with_output("stdout", output.stdout.clone());
-        }
-        if !output.stderr.is_empty() {
-            batch = batch.with_output("stderr", output.stderr.clone());
+            // Show pending sync stats (dry-run)
+            let opts = PushOptions {
+                since: None,
+                dry_run: true,
+                sync_blobs: true,
+            };
+            match store.push(remote, opts) {
+                Ok(stats) => {
+                    let total = stats.sessions + stats.invocations + stats.outputs + stats.events;
+                    if total > 0 || stats.blobs.count > 0 {
+                        println!("    Pending push: {}", stats);
+                    } else {
+                        println!("    Pending push: (up to date)");
+                    }
+                }
+                Err(e) => {
+                    println!("    Status: error - {}", e);
+                }
+            }
         }
+    }
 
-        // Write everything atomically
-        store.write_batch(&batch)?;
+    Ok(())
+}
 
-        if !output.status.success() {
-            std::process::exit(exit_code);
+/// Show this client's public signing key (creating it if needed) and the
+/// keys pinned for other clients.
+pub fn remote_key() -> bird::Result<()> {
+    use bird::signing::{ClientKey, KnownClients};
+
+    let config = Config::load()?;
+    let key = ClientKey::load_or_create(&config)?;
+
+    println!("Client:     {}", config.client_id);
+    println!("Public key: {}", key.public_key());
+    println!();
+    let path = KnownClients::path(&config);
+    match std::fs::read_to_string(&path) {
+        Ok(content) if !content.trim().is_empty() => {
+            println!("Pinned keys ({}):", path.display());
+            for line in content.lines().filter(|l| !l.trim().is_empty()) {
+                println!("  {}", line);
+            }
         }
+        _ => println!("No pinned keys yet ({}).", path.display()),
     }
 
     Ok(())
 }
 
-const QUICK_HELP: &str = r#"
-SHQ QUICK REFERENCE
-===================
+// Push/Pull commands
 
-COMMANDS                                    EXAMPLES
-────────────────────────────────────────────────────────────────────────────────
-output (o, show)   Show captured output     shq o ~1          shq o %/make/~1
-invocations (i)    List command history     shq i -n 20       shq i %exit<>0~10:
-events (e)         Show parsed events       shq e -n 10       shq e -s error~5:
-info (I)           Invocation details       shq I ~1          shq I %/test/~1
-rerun (R, !!)      Re-run a command         shq R ~1          shq R %/make/~1
-run (r)            Run and capture          shq r cargo test  shq r -c "make all"
-sql (q)            Execute SQL query        shq q "SELECT * FROM invocations LIMIT 5"
+/// Push local data to a remote.
+pub fn push(remote: Option<&str>, since: Option<&str>, dry_run: bool, sync_blobs: bool) -> bird::Result<()> {
+    use bird::{parse_since, PushOptions};
 
-QUERY SYNTAX: [source][path][filters][range]
-────────────────────────────────────────────────────────────────────────────────
-RANGE         ~1          Most recent command (position 1)
-              ~5          5th most recent command (position 5)
-              ~10:        Last 10 commands (positions 1-10)
-              ~10:~5      Range from position 10 to 5
-              -n 10       CLI flag: last 10 (same as ~10:)
+    let config = Config::load()?;
+    let store = Store::open(config.clone())?
+        .with_progress(crate::progress::sink(false))
+        .with_cancel(crate::cancel::token());
 
-SOURCE        Format: host:type:client:session:
-              shell:           Shell commands on this host
-              shell:bash:      Bash shells only
-              shell:zsh:       Zsh shells only
-              myhost:shell::   All shells on myhost
-              *:*:*:*:         Everything everywhere (all hosts, all types)
-              *:shell:*:*:     All shell commands on all hosts
+    // Resolve remote
+    let remote_name = remote
+        .map(String::from)
+        .or_else(|| config.sync.default_remote.clone())
+        .ok_or_else(|| bird::Error::Config(
+            "No remote specified and no default remote configured. Use --remote <name> or set sync.default_remote in config.".to_string()
+        ))?;
 
-PATH          .           Current directory
-              ~/Projects/ Home-relative
-              /tmp/       Absolute path
+    let remote_config = config.get_remote(&remote_name)
+        .ok_or_else(|| bird::Error::Config(format!("Remote '{}' not found", remote_name)))?;
 
-FILTERS       %failed     Non-zero exit code (alias for %exit<>0)
-              %success    Successful commands (alias for %exit=0)
-              %ok         Same as %success
-              %exit<>0    Non-zero exit code
-              %exit=0     Successful commands only
-              %duration>5000   Took > 5 seconds
-              %cmd~=test  Command matches regex
-              %cwd~=/src/ Working dir matches
+    if remote_config.remote_type == bird::RemoteType::S3 {
+        store.capabilities()?.require(bird::Capability::Httpfs)?;
+    }
 
-CMD REGEX     %/make/     Commands containing "make"
-              %/^cargo/   Commands starting with "cargo"
-              %/test$/    Commands ending with "test"
+    // Parse since date
+    let since_date = since.map(parse_since).transpose()?;
 
-OPERATORS     =  equals      <>  not equals     ~=  regex match
-              >  greater     <   less           >=  gte    <=  lte
+    let opts = PushOptions {
+        since: since_date,
+        dry_run,
+        sync_blobs,
+    };
 
-EXAMPLES
-────────────────────────────────────────────────────────────────────────────────
-shq o                        Show output of last command (default: 1)
-shq o 1                      Same as above
-shq o -E 1                   Show only stderr of last command
-shq o %/make/~1              Output of last make command
-shq o %exit<>0~1             Output of last failed command
+    let _lease = maintenance_lease(&config, &format!("push to '{}'", remote_name), dry_run)?;
 
-shq i                        Last 20 commands (default)
-shq i 50                     Last 50 commands
-shq i %failed~20             Last 20 failed commands
-shq i %failed                All failed commands (up to default limit)
-shq i %duration>10000~10     Last 10 commands that took >10s
-shq i %/cargo/~10            Last 10 cargo commands
+    let stats = store.push(remote_config, opts)?;
 
-shq e                        Events from last 10 commands (default)
-shq e 5                      Events from last 5 commands
-shq e -s error 10            Only errors from last 10 commands
-shq e %/cargo build/~1       Events from last cargo build
+    if dry_run {
+        print!("{}", stats.plan);
+    } else {
+        println!("Pushed to '{}': {}", remote_name, stats);
+    }
 
-shq R                        Re-run last command
-shq R 3                      Re-run 3rd-last command
-shq R %/make test/~1         Re-run last "make test"
-shq R -n %/deploy/~1         Dry-run: show what would run
+    Ok(())
+}
 
-shq I                        Details about last command
-shq I -f json 1              Details as JSON
+/// Pull data from a remote to local.
+pub fn pull(remote: Option<&str>, client: Option<&str>, since: Option<&str>, sync_blobs: bool) -> bird::Result<()> {
+    use bird::{parse_since, PullOptions};
 
-.~5                          Last 5 commands in current directory
-~/Projects/foo/~10           Last 10 in ~/Projects/foo/
-shell:%exit<>0~5             Last 5 failed shell commands
+    let config = Config::load()?;
+    let store = Store::open(config.clone())?
+        .with_progress(crate::progress::sink(false))
+        .with_cancel(crate::cancel::token());
 
-"#;
+    // Resolve remote
+    let remote_name = remote
+        .map(String::from)
+        .or_else(|| config.sync.default_remote.clone())
+        .ok_or_else(|| bird::Error::Config(
+            "No remote specified and no default remote configured. Use --remote <name> or set sync.default_remote in config.".to_string()
+        ))?;
 
+    let remote_config = config.get_remote(&remote_name)
+        .ok_or_else(|| bird::Error::Config(format!("Remote '{}' not found", remote_name)))?;
 
+    if remote_config.remote_type == bird::RemoteType::S3 {
+        store.capabilities()?.require(bird::Capability::Httpfs)?;
+    }
 
-// ZSH hook without prompt indicator - same as ZSH_HOOK but without PS1 modification
+    // Parse since date
+    let since_date = since.map(parse_since).transpose()?;
 
-// Format hints management
+    let opts = PullOptions {
+        since: since_date,
+        client_id: client.map(String::from),
+        sync_blobs,
+    };
 
-/// List format hints (user-defined and optionally built-in).
-pub fn format_hints_list(show_builtin: bool, show_user: bool, filter: Option<&str>) -> bird::Result<()> {
-    let config = Config::load()?;
-    let store = Store::open(config)?;
+    let _lease = maintenance_lease(&config, &format!("pull from '{}'", remote_name), false)?;
 
-    let hints = store.load_format_hints()?;
+    let stats = store.pull(remote_config, opts)?;
 
-    // Helper to check if a hint matches the filter
-    let matches_filter = |pattern: &str, format: &str| -> bool {
-        match filter {
-            None => true,
-            Some(f) => {
-                let f_lower = f.to_lowercase();
-                pattern.to_lowercase().contains(&f_lower) || format.to_lowercase().contains(&f_lower)
-            }
+    println!("Pulled from '{}': {}", remote_name, stats);
+
+    Ok(())
+}
+
+/// Summarize recent build, test and lint results for a project.
+pub fn health(path: Option<&std::path::Path>, days: u32) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+
+    let dir = match path {
+        Some(path) => std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+        None => {
+            let cwd = std::env::current_dir()?;
+            let vcs_root = ContextMetadata::collect(Some(&cwd))
+                .into_map()
+                .remove("vcs")
+                .and_then(|v| v.get("root").and_then(|r| r.as_str()).map(std::path::PathBuf::from));
+            bird::find_current_project().map(|p| p.root).or(vcs_root).unwrap_or(cwd)
         }
     };
 
-    // Show user-defined hints
-    if show_user {
-        let user_hints: Vec<_> = hints.hints()
-            .iter()
-            .filter(|h| matches_filter(&h.pattern, &h.format))
-            .collect();
+    let health = store.dir_health(&dir.display().to_string(), days)?;
+    println!("{} (since {})", health.dir, health.since);
+    if health.classes.is_empty() {
+        println!("No builds, tests or lints in the last {} days.", days);
+        return Ok(());
+    }
 
-        if user_hints.is_empty() {
-            if filter.is_some() {
-                println!("No user-defined format hints matching filter.");
-            } else {
-                println!("No user-defined format hints.");
-            }
+    println!();
+    println!(
+        "{:<6} {:>5} {:>7} {:<8} {:<18} {:>8}",
+        "CLASS", "RUNS", "FAILED", "NOW", "LAST GREEN", "MTTF"
+    );
+    let theme = crate::theme::current();
+    for class in &health.classes {
+        let now = if class.failing {
+            theme.paint(RED, format!("{:<8}", "failing"))
         } else {
-            println!("User-defined format hints:");
-            println!("{:<6} {:<30} FORMAT", "PRI", "PATTERN");
-            println!("{}", "-".repeat(60));
-            for hint in user_hints {
-                println!("{:<6} {:<30} {}", hint.priority, hint.pattern, hint.format);
-            }
-        }
-        println!();
+            theme.paint(GREEN, format!("{:<8}", "ok"))
+        };
+        let last_green = class
+            .last_green
+            .as_ref()
+            .map(|(at, _)| at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let mttf = class
+            .mean_time_to_fix
+            .map(|d| format_duration_ms(d.num_milliseconds()))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<6} {:>5} {:>6.0}% {} {:<18} {:>8}",
+            class.class,
+            class.runs,
+            class.failure_rate() * 100.0,
+            now,
+            last_green,
+            mttf
+        );
     }
 
-    // Show built-in formats from duck_hunt
-    if show_builtin {
-        match store.list_builtin_formats() {
-            Ok(formats) => {
-                let filtered: Vec<_> = formats.iter()
-                    .filter(|f| matches_filter(&f.pattern, &f.format))
-                    .collect();
-
-                if filtered.is_empty() {
-                    if filter.is_some() {
-                        println!("No built-in formats matching filter.");
-                    } else {
-                        println!("No built-in formats available.");
-                    }
-                } else {
-                    println!("Available formats (from duck_hunt):");
-                    println!("{:<6} {:<20} DESCRIPTION", "PRI", "FORMAT");
-                    println!("{}", "-".repeat(70));
-                    for fmt in filtered {
-                        // pattern field contains description for builtin formats
-                        let desc = if fmt.pattern.len() > 45 {
-                            format!("{}...", &fmt.pattern[..42])
-                        } else {
-                            fmt.pattern.clone()
-                        };
-                        println!("{:<6} {:<20} {}", fmt.priority, fmt.format, desc);
-                    }
-                }
-            }
-            Err(e) => {
-                eprintln!("Warning: Could not list built-in formats: {}", e);
-            }
-        }
+    if let Some((_, cmd)) = health
+        .classes
+        .iter()
+        .find(|c| c.class == bird::CommandClass::Build)
+        .and_then(|c| c.last_green.as_ref())
+    {
+        println!("\nLast green build: {}", truncate_string(cmd, 70));
+    }
+    if health.other_runs > 0 {
+        println!("({} other commands not counted)", health.other_runs);
     }
-
     Ok(())
 }
 
-/// Add a format hint.
-pub fn format_hints_add(pattern: &str, format: &str, priority: Option<i32>) -> bird::Result<()> {
+/// Show teammates' recent commands from shared remotes, by default only
+/// those run in the current project.
+pub fn feed(remote: Option<&str>, since: &str, all_projects: bool, include_self: bool, limit: usize) -> bird::Result<()> {
     let config = Config::load()?;
     let store = Store::open(config)?;
 
-    let mut hints = store.load_format_hints()?;
+    let cwd = std::env::current_dir()?;
+    let (project_path, vcs_remote) = if all_projects {
+        (None, None)
+    } else {
+        // A checkout shows up under the same path relative to $HOME (with
+        // relative_home), or under the same git origin anywhere
+        let vcs = ContextMetadata::collect(Some(&cwd)).into_map().remove("vcs");
+        let vcs_field = |key: &str| {
+            vcs.as_ref().and_then(|v| v.get(key)).and_then(|v| v.as_str()).map(str::to_string)
+        };
+        let root = bird::find_current_project()
+            .map(|p| p.root.display().to_string())
+            .or_else(|| vcs_field("root"))
+            .unwrap_or_else(|| cwd.display().to_string());
+        (Some(root), vcs_field("remote"))
+    };
 
-    let priority = priority.unwrap_or(bird::format_hints::DEFAULT_PRIORITY);
-    let hint = bird::FormatHint::with_priority(pattern, format, priority);
+    let entries = store.feed(&bird::FeedOptions {
+        remote: remote.map(String::from),
+        project_path: project_path.clone(),
+        vcs_remote,
+        since: Some(bird::parse_since(since)?),
+        include_self,
+        limit,
+    })?;
 
-    // Check if pattern already exists
-    if hints.get(pattern).is_some() {
-        println!("Updating existing pattern: {}", pattern);
+    if entries.is_empty() {
+        match &project_path {
+            Some(path) => println!("No teammate activity in {} since {}.", path, since),
+            None => println!("No teammate activity since {}.", since),
+        }
+        return Ok(());
     }
 
-    hints.add(hint);
-    store.save_format_hints(&hints)?;
+    // Hashed clients (hash_fields = ["client"]) are just long hex strings
+    let who = |e: &bird::FeedEntry| {
+        if e.client_id.len() == 32 && e.client_id.chars().all(|c| c.is_ascii_hexdigit()) {
+            format!("#{}", &e.client_id[..8])
+        } else {
+            e.client_id.clone()
+        }
+    };
+    let width = entries.iter().map(|e| who(e).len()).max().unwrap_or(0);
+    let show_remote = entries.iter().any(|e| e.remote != entries[0].remote);
+
+    for entry in entries.iter().rev() {
+        let time = entry.timestamp.get(..16).unwrap_or(&entry.timestamp);
+        let status = match entry.exit_code {
+            Some(0) => "  ok".to_string(),
+            Some(code) => format!("{:>4}", code),
+            None => "   …".to_string(),
+        };
+        let duration = entry.duration_ms.map(format_duration_ms).unwrap_or_default();
+        let remote = if show_remote { format!("{}: ", entry.remote) } else { String::new() };
+        println!(
+            "{}  {}{:<width$}  {} {:>6}  {}",
+            time,
+            remote,
+            who(entry),
+            status,
+            duration,
+            entry.cmd,
+            width = width
+        );
+    }
 
-    println!("Added: {} -> {} (priority {})", pattern, format, priority);
     Ok(())
 }
 
-/// Remove a format hint by pattern.
-pub fn format_hints_remove(pattern: &str) -> bird::Result<()> {
-    let config = Config::load()?;
-    let store = Store::open(config)?;
+// =============================================================================
+// Session Commands
+// =============================================================================
 
-    let mut hints = store.load_format_hints()?;
+/// Gap between commands worth calling out in a session timeline.
+const SESSION_GAP_SECS: i64 = 5 * 60;
 
-    if hints.remove(pattern) {
-        store.save_format_hints(&hints)?;
-        println!("Removed: {}", pattern);
-    } else {
-        println!("Pattern not found: {}", pattern);
+/// Resolve a session selector ("current", a session name, or an explicit ID).
+fn resolve_session_id(store: &Store, selector: &str) -> bird::Result<String> {
+    if selector != "current" {
+        // Names win over IDs; the most recently named session is first
+        if let Some(id) = store.sessions_named(selector)?.into_iter().next() {
+            return Ok(id);
+        }
+        return Ok(selector.to_string());
     }
 
-    Ok(())
+    // The hook's session variable, if the shell exported it
+    if let Ok(id) = std::env::var("__shq_session_id") {
+        if !id.is_empty() {
+            return Ok(id);
+        }
+    }
+
+    // Otherwise the session registered by our parent shell
+    let ppid = std::os::unix::process::parent_id();
+    if let Some(id) = store.session_for_pid(ppid)? {
+        return Ok(id);
+    }
+
+    Ok(session_id())
 }
 
-/// Check which format would be detected for a command.
-pub fn format_hints_check(cmd: &str) -> bird::Result<()> {
-    use bird::FormatSource;
+/// Format a time span as a compact human-readable duration.
+fn format_span(span: chrono::TimeDelta) -> String {
+    let secs = span.num_seconds().max(0);
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
 
+/// List sessions with duration and command counts.
+pub fn session_list(last: usize, format: &str) -> bird::Result<()> {
     let config = Config::load()?;
     let store = Store::open(config)?;
 
-    let result = store.check_format(cmd)?;
+    let sessions = store.list_sessions(last)?;
 
-    println!("Command: {}", cmd);
-    println!("Format:  {}", result.format);
+    if sessions.is_empty() {
+        println!("No sessions recorded yet.");
+        return Ok(());
+    }
 
-    match result.source {
-        FormatSource::UserDefined { pattern, priority } => {
-            println!("Source:  user-defined (pattern: {}, priority: {})", pattern, priority);
-        }
-        FormatSource::Builtin { pattern, priority } => {
-            println!("Source:  built-in (pattern: {}, priority: {})", pattern, priority);
+    match format {
+        "json" => {
+            let json: Vec<serde_json::Value> = sessions
+                .iter()
+                .map(|s| {
+                    serde_json::json!({
+                        "session_id": s.session_id,
+                        "name": s.name,
+                        "client_id": s.client_id,
+                        "invoker": s.invoker,
+                        "started_at": s.started_at.to_rfc3339(),
+                        "last_active": s.last_active.to_rfc3339(),
+                        "duration_secs": s.duration().num_seconds(),
+                        "invocations": s.invocation_count,
+                        "failed": s.failed_count,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
         }
-        FormatSource::Default => {
-            println!("Source:  default (no pattern matched)");
+        _ => {
+            println!(
+                "{:<24} {:<16} {:<8} {:<20} {:>9} {:>6} {:>6}",
+                "SESSION", "NAME", "INVOKER", "STARTED", "DURATION", "CMDS", "FAILED"
+            );
+            println!("{}", "-".repeat(95));
+            for s in &sessions {
+                println!(
+                    "{:<24} {:<16} {:<8} {:<20} {:>9} {:>6} {:>6}",
+                    truncate_string(&s.session_id, 24),
+                    truncate_string(s.name.as_deref().unwrap_or("-"), 16),
+                    s.invoker.as_deref().unwrap_or("-"),
+                    s.started_at.format("%Y-%m-%d %H:%M:%S"),
+                    format_span(s.duration()),
+                    s.invocation_count,
+                    s.failed_count
+                );
+            }
         }
     }
 
     Ok(())
 }
 
-/// Set the default format.
-pub fn format_hints_set_default(format: &str) -> bird::Result<()> {
+/// Show a timeline of a session's invocations.
+///
+/// Marks failures, long gaps between commands, and working directory changes.
+pub fn session_show(selector: &str, format: &str) -> bird::Result<()> {
     let config = Config::load()?;
     let store = Store::open(config)?;
 
-    let mut hints = store.load_format_hints()?;
-    hints.set_default_format(format);
-    store.save_format_hints(&hints)?;
+    let session_id = resolve_session_id(&store, selector)?;
+    let entries = store.session_invocations(&session_id)?;
+
+    if entries.is_empty() {
+        println!("No invocations recorded for session '{}'.", session_id);
+        return Ok(());
+    }
+
+    if format == "json" {
+        let json: Vec<serde_json::Value> = entries
+            .iter()
+            .map(|e| {
+                serde_json::json!({
+                    "id": e.id,
+                    "timestamp": e.timestamp.to_rfc3339(),
+                    "cmd": e.cmd,
+                    "cwd": e.cwd,
+                    "exit_code": e.exit_code,
+                    "duration_ms": e.duration_ms,
+                    "status": e.status,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+        return Ok(());
+    }
+
+    let theme = crate::theme::current();
+    let failed = entries.iter().filter(|e| e.exit_code.is_some_and(|c| c != 0)).count();
+    let first = &entries[0];
+    let last = &entries[entries.len() - 1];
+
+    match store.session_name(&session_id)? {
+        Some(name) => println!("Session {} ({})", session_id, name),
+        None => println!("Session {}", session_id),
+    }
+    println!(
+        "{}",
+        theme.paint(
+            DIM,
+            format!(
+                "{} → {} ({}), {} commands, {} failed",
+                first.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                last.ended_at().format("%H:%M:%S"),
+                format_span(last.ended_at() - first.timestamp),
+                entries.len(),
+                failed
+            )
+        )
+    );
+    if let Some(terminal) = store.session_terminal(&session_id)? {
+        println!("{}", theme.paint(DIM, format!("in {}", describe_terminal(&terminal))));
+    }
+    if let Some(closed) = store.session_closed_at(&session_id)? {
+        println!("{}", theme.paint(DIM, format!("closed {}", closed.format("%Y-%m-%d %H:%M:%S"))));
+    }
+    println!();
+
+    print_timeline(&entries, &store.session_markers(&session_id)?);
 
-    println!("Default format set to: {}", format);
     Ok(())
 }
 
-// Remote management commands
+/// Print invocations as a timeline, with `markers` (oldest first) in place.
+///
+/// Marks failures, long gaps between commands, working directory changes,
+/// and switches between shell sessions.
+fn print_timeline<'a>(entries: impl IntoIterator<Item = &'a bird::SessionEntry>, markers: &[bird::Marker]) {
+    let theme = crate::theme::current();
+    let print_marker = |m: &bird::Marker| {
+        println!(
+            "{} {}          {}",
+            m.timestamp.format("%H:%M:%S"),
+            theme.paint(MAGENTA, &theme.glyphs.marker),
+            theme.paint(BOLD, &m.text)
+        );
+    };
 
-/// Add a remote storage connection.
-pub fn remote_add(
-    name: &str,
-    remote_type: &str,
-    uri: &str,
-    read_only: bool,
-    credential_provider: Option<&str>,
-    auto_attach: bool,
-) -> bird::Result<()> {
-    use bird::{RemoteConfig, RemoteMode, RemoteType};
-    use std::str::FromStr;
+    let mut markers = markers.iter().peekable();
+    let mut prev: Option<&bird::SessionEntry> = None;
+    for entry in entries {
+        while let Some(marker) = markers.next_if(|m| m.timestamp < entry.timestamp) {
+            print_marker(marker);
+        }
+        match prev {
+            Some(p) => {
+                let gap = entry.timestamp - p.ended_at();
+                if gap.num_seconds() >= SESSION_GAP_SECS {
+                    println!("{}", theme.paint(DIM, format!("          ⋯ {} idle", format_span(gap))));
+                }
+                if entry.session_id != p.session_id {
+                    println!("{}", theme.paint(DIM, format!("          ⇄ {}", entry.session_id)));
+                }
+                if entry.cwd != p.cwd {
+                    println!("{}", theme.paint(DIM, format!("          → {}", entry.cwd)));
+                }
+            }
+            None => println!("{}", theme.paint(DIM, format!("          → {}", entry.cwd))),
+        }
 
-    let mut config = Config::load()?;
+        let duration = entry
+            .duration_ms
+            .map(format_duration_ms)
+            .unwrap_or_else(|| "-".to_string());
+        let exit = match entry.exit_code {
+            Some(code) if code != 0 => format!(" {}", theme.paint(RED, format!("[exit {}]", code))),
+            Some(_) => String::new(),
+            None => format!(" {}", theme.paint(YELLOW, format!("[{}]", entry.status))),
+        };
 
-    let rtype = RemoteType::from_str(remote_type)?;
-    let mut remote = RemoteConfig::new(name, rtype, uri);
+        println!(
+            "{} {} {:>7}  {}{}",
+            entry.timestamp.format("%H:%M:%S"),
+            theme.status(entry.exit_code),
+            duration,
+            truncate_string(&entry.cmd, 60),
+            exit
+        );
 
-    if read_only {
-        remote.mode = RemoteMode::ReadOnly;
-    }
-    if let Some(provider) = credential_provider {
-        remote.credential_provider = Some(provider.to_string());
+        prev = Some(entry);
     }
-    remote.auto_attach = auto_attach;
-
-    // Check if updating existing
-    let updating = config.get_remote(name).is_some();
+    markers.for_each(print_marker);
+}
 
-    config.add_remote(remote);
-    config.save()?;
+/// Give a session a name, usable as `%session=<name>` in queries.
+pub fn session_name(name: &str, selector: &str) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
 
-    if updating {
-        println!("Updated remote: {}", name);
-    } else {
-        println!("Added remote: {}", name);
-    }
-    println!("  Type: {}", remote_type);
-    println!("  URI:  {}", uri);
-    println!("  Mode: {}", if read_only { "read-only" } else { "read-write" });
-    if let Some(provider) = credential_provider {
-        println!("  Credentials: {}", provider);
-    }
-    println!("  Auto-attach: {}", auto_attach);
+    let session_id = resolve_session_id(&store, selector)?;
+    store.name_session(&session_id, name)?;
 
+    println!("Named session {} '{}'", session_id, name);
     Ok(())
 }
 
-/// List configured remotes.
-pub fn remote_list() -> bird::Result<()> {
+/// List the most frequent commands not found (`shq typos`).
+pub fn typos(days: Option<u32>, limit: usize, format: &str) -> bird::Result<()> {
     let config = Config::load()?;
+    let store = Store::open(config)?;
+    let typos = store.typos(days, limit)?;
 
-    if config.remotes.is_empty() {
-        println!("No remotes configured.");
-        println!();
-        println!("Add a remote with:");
-        println!("  shq remote add <name> --type s3 --uri s3://bucket/path/bird.duckdb");
+    if format == "json" {
+        let json: Vec<serde_json::Value> = typos
+            .iter()
+            .map(|t| {
+                serde_json::json!({
+                    "command": t.command,
+                    "count": t.count,
+                    "last_seen": t.last_seen.to_rfc3339(),
+                    "suggestion": t.suggestion,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
         return Ok(());
     }
 
-    println!("{:<12} {:<12} {:<10} {:<8} URI", "NAME", "TYPE", "MODE", "ATTACH");
-    println!("{}", "-".repeat(70));
-
-    for remote in &config.remotes {
+    if typos.is_empty() {
+        println!("No commands not found recorded (enable with SHQ_CAPTURE_NOT_FOUND=1).");
+        return Ok(());
+    }
+    println!("{:>5}  {:<20} {:<20} LAST SEEN", "COUNT", "TYPED", "DID YOU MEAN");
+    for typo in &typos {
         println!(
-            "{:<12} {:<12} {:<10} {:<8} {}",
-            remote.name,
-            remote.remote_type,
-            remote.mode,
-            if remote.auto_attach { "auto" } else { "manual" },
-            remote.uri
+            "{:>5}  {:<20} {:<20} {}",
+            typo.count,
+            truncate_string(&typo.command, 20),
+            typo.suggestion.as_deref().unwrap_or("-"),
+            typo.last_seen.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
         );
     }
-
     Ok(())
 }
 
-/// Remove a remote configuration.
-pub fn remote_remove(name: &str) -> bird::Result<()> {
-    let mut config = Config::load()?;
-
-    if config.remove_remote(name) {
-        config.save()?;
-        println!("Removed remote: {}", name);
-    } else {
-        println!("Remote not found: {}", name);
+/// Record a command the shell couldn't find, from the hook's
+/// command-not-found handler.
+///
+/// Only names that look like commands are kept: a password pasted at the
+/// prompt is "not found" too. Skipped while capture is paused or before
+/// `shq init`.
+pub fn not_found(command: &str) -> bird::Result<()> {
+    let Ok(config) = Config::load() else {
+        return Ok(());
+    };
+    let command_like = !command.is_empty()
+        && command.len() <= 40
+        && command.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+'));
+    let paused = bird::privacy::is_paused(&config) || !bird::ProjectConfig::from_env().capture_enabled();
+    if !command_like || paused || !config.db_path().exists() {
+        return Ok(());
     }
 
-    Ok(())
+    let store = Store::open(config)?;
+    store.record_command_not_found(command)
 }
 
-/// Test connection to a remote.
-pub fn remote_test(name: Option<&str>) -> bird::Result<()> {
+/// Write a marker into a session's timeline (`shq mark`).
+pub fn mark(text: &str, selector: &str, quiet: bool) -> bird::Result<()> {
     let config = Config::load()?;
-    let store = Store::open(config.clone())?;
-
-    let remotes_to_test: Vec<_> = if let Some(n) = name {
-        match config.get_remote(n) {
-            Some(r) => vec![r],
-            None => {
-                println!("Remote not found: {}", n);
-                return Ok(());
-            }
-        }
-    } else {
-        config.remotes.iter().collect()
-    };
+    let store = Store::open(config)?;
 
-    if remotes_to_test.is_empty() {
-        println!("No remotes configured.");
-        return Ok(());
-    }
+    let session_id = resolve_session_id(&store, selector)?;
+    let marker = store.add_marker(&session_id, text)?;
 
-    for remote in remotes_to_test {
-        print!("Testing {}... ", remote.name);
-        match store.test_remote(remote) {
-            Ok(()) => println!("OK"),
-            Err(e) => println!("FAILED: {}", e),
+    if !quiet {
+        match marker.invocation_id {
+            Some(_) => println!("Marked session {}: {}", session_id, marker.text),
+            None => println!(
+                "Marked: {} (session {} has no commands yet, so it won't show in its timeline)",
+                marker.text, session_id
+            ),
         }
     }
-
     Ok(())
 }
 
-/// Manually attach a remote (shows SQL to run).
-pub fn remote_attach(name: &str) -> bird::Result<()> {
+/// Record that a session has ended.
+pub fn session_close(selector: &str, quiet: bool) -> bird::Result<()> {
     let config = Config::load()?;
+    let store = Store::open(config)?;
 
-    match config.get_remote(name) {
-        Some(remote) => {
-            println!("To attach this remote in SQL:");
-            println!();
-            if remote.credential_provider.is_some() {
-                println!("LOAD httpfs;");
-                println!(
-                    "CREATE SECRET IF NOT EXISTS \"bird_{}\" (TYPE s3, PROVIDER credential_chain);",
-                    remote.name
-                );
-            }
-            println!("{};", remote.attach_sql());
-            println!();
-            println!("Then query with: SELECT * FROM {}.invocations LIMIT 10;", remote.quoted_schema_name());
-        }
-        None => {
-            println!("Remote not found: {}", name);
-        }
-    }
+    let session_id = resolve_session_id(&store, selector)?;
+    store.close_session(&session_id)?;
 
+    if !quiet {
+        println!("Closed session {}", session_id);
+    }
     Ok(())
 }
 
-/// Show remote sync status.
-pub fn remote_status() -> bird::Result<()> {
-    use bird::PushOptions;
+// =============================================================================
+// Context Commands
+// =============================================================================
 
+/// Find the most recent context for the current directory and branch.
+fn current_context(store: &Store, gap_minutes: i64) -> bird::Result<Option<bird::ContextSummary>> {
+    let cwd = std::env::current_dir()?;
+    let vcs = ContextMetadata::collect(Some(&cwd)).into_map().remove("vcs");
+
+    let root = vcs
+        .as_ref()
+        .and_then(|v| v.get("root"))
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| cwd.display().to_string());
+    let branch = vcs
+        .as_ref()
+        .and_then(|v| v.get("branch"))
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+
+    Ok(store
+        .find_contexts(&root, gap_minutes)?
+        .into_iter()
+        .find(|c| c.root == root && c.branch == branch))
+}
+
+/// Estimate active working time per project and branch (`shq time`).
+///
+/// Time is the summed duration of work contexts, so idle gaps longer than
+/// `gap_minutes` don't count.
+pub fn time(period: &str, ago: u32, project: bool, gap_minutes: i64, format: &str) -> bird::Result<()> {
     let config = Config::load()?;
-    let store = Store::open(config.clone())?;
+    let store = Store::open(config)?;
 
-    println!("Sync Configuration:");
-    println!("  Default remote:    {}", config.sync.default_remote.as_deref().unwrap_or("(none)"));
-    println!("  Push on compact:   {}", config.sync.push_on_compact);
-    println!("  Push on archive:   {}", config.sync.push_on_archive);
-    println!("  Sync invocations:  {}", config.sync.sync_invocations);
-    println!("  Sync outputs:      {}", config.sync.sync_outputs);
-    println!("  Sync events:       {}", config.sync.sync_events);
-    println!("  Sync blobs:        {}", config.sync.sync_blobs);
-    if config.sync.sync_blobs {
-        println!("  Blob min size:     {} bytes", config.sync.blob_sync_min_bytes);
+    let period: bird::TimePeriod = period.parse()?;
+    let (mut start, mut end) = period.range(chrono::Local::now().date_naive());
+    for _ in 0..ago {
+        end = start;
+        start = period.range(start - chrono::Duration::days(1)).0;
     }
-    println!();
 
-    println!("Blob Roots (search order):");
-    for (i, root) in config.blob_roots().iter().enumerate() {
-        println!("  {}. {}", i + 1, root);
+    let root = if project {
+        let cwd = std::env::current_dir()?;
+        let vcs = ContextMetadata::collect(Some(&cwd)).into_map().remove("vcs");
+        Some(
+            vcs.as_ref()
+                .and_then(|v| v.get("root"))
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| cwd.display().to_string()),
+        )
+    } else {
+        None
+    };
+
+    let entries = store.time_spent(start, end, gap_minutes, root.as_deref())?;
+
+    if format == "csv" {
+        println!("day,root,branch,active_seconds,active_hours,contexts,invocations");
+        for e in &entries {
+            let secs = e.active.num_seconds();
+            println!(
+                "{},{},{},{},{:.2},{},{}",
+                e.day,
+                csv_field(&e.root),
+                csv_field(e.branch.as_deref().unwrap_or("")),
+                secs,
+                secs as f64 / 3600.0,
+                e.contexts,
+                e.invocations
+            );
+        }
+        return Ok(());
     }
-    println!();
 
-    if config.remotes.is_empty() {
-        println!("No remotes configured.");
-    } else {
-        println!("Configured Remotes:");
-        for remote in &config.remotes {
-            println!("  {} ({}, {})", remote.name, remote.remote_type, remote.mode);
+    let last_day = end - chrono::Duration::days(1);
+    if entries.is_empty() {
+        println!("No activity from {} to {}.", start, last_day);
+        return Ok(());
+    }
 
-            // Show pending sync stats (dry-run)
-            let opts = PushOptions {
-                since: None,
-                dry_run: true,
-                sync_blobs: true,
-            };
-            match store.push(remote, opts) {
-                Ok(stats) => {
-                    let total = stats.sessions + stats.invocations + stats.outputs + stats.events;
-                    if total > 0 || stats.blobs.count > 0 {
-                        println!("    Pending push: {}", stats);
-                    } else {
-                        println!("    Pending push: (up to date)");
-                    }
-                }
-                Err(e) => {
-                    println!("    Status: error - {}", e);
-                }
+    // Total per root and branch, most time first
+    let mut totals: Vec<(String, Option<String>, chrono::TimeDelta, i64, i64)> = Vec::new();
+    for e in &entries {
+        match totals.iter_mut().find(|t| t.0 == e.root && t.1 == e.branch) {
+            Some(t) => {
+                t.2 += e.active;
+                t.3 += e.contexts;
+                t.4 += e.invocations;
             }
+            None => totals.push((e.root.clone(), e.branch.clone(), e.active, e.contexts, e.invocations)),
         }
     }
+    totals.sort_by_key(|t| std::cmp::Reverse(t.2));
+
+    println!("{} to {} ({} gap)", start, last_day, format_span(chrono::TimeDelta::minutes(gap_minutes)));
+    println!();
+    println!("{:<40} {:<24} {:>8} {:>9} {:>6}", "PROJECT", "BRANCH", "TIME", "STRETCHES", "CMDS");
+    for (root, branch, active, contexts, invocations) in &totals {
+        println!(
+            "{:<40} {:<24} {:>8} {:>9} {:>6}",
+            truncate_path(root, 40),
+            truncate_string(branch.as_deref().unwrap_or("-"), 24),
+            format_span(*active),
+            contexts,
+            invocations
+        );
+    }
+    let total: chrono::TimeDelta = totals.iter().map(|t| t.2).sum();
+    println!("{:<40} {:<24} {:>8}", "", "total", format_span(total));
 
     Ok(())
 }
 
-// Push/Pull commands
-
-/// Push local data to a remote.
-pub fn push(remote: Option<&str>, since: Option<&str>, dry_run: bool, sync_blobs: bool) -> bird::Result<()> {
-    use bird::{parse_since, PushOptions};
-
+/// List work contexts (invocations clustered by root, branch and time gap).
+pub fn context_list(last: usize, gap_minutes: i64, format: &str) -> bird::Result<()> {
     let config = Config::load()?;
-    let store = Store::open(config.clone())?;
-
-    // Resolve remote
-    let remote_name = remote
-        .map(String::from)
-        .or_else(|| config.sync.default_remote.clone())
-        .ok_or_else(|| bird::Error::Config(
-            "No remote specified and no default remote configured. Use --remote <name> or set sync.default_remote in config.".to_string()
-        ))?;
-
-    let remote_config = config.get_remote(&remote_name)
-        .ok_or_else(|| bird::Error::Config(format!("Remote '{}' not found", remote_name)))?;
-
-    // Parse since date
-    let since_date = since.map(parse_since).transpose()?;
+    let store = Store::open(config)?;
 
-    let opts = PushOptions {
-        since: since_date,
-        dry_run,
-        sync_blobs,
-    };
+    let contexts = store.list_contexts(gap_minutes, last)?;
 
-    let stats = store.push(remote_config, opts)?;
+    if contexts.is_empty() {
+        println!("No contexts recorded yet.");
+        return Ok(());
+    }
 
-    if dry_run {
-        println!("Would push to '{}': {}", remote_name, stats);
-    } else {
-        println!("Pushed to '{}': {}", remote_name, stats);
+    match format {
+        "json" => {
+            let json: Vec<serde_json::Value> = contexts
+                .iter()
+                .map(|c| {
+                    serde_json::json!({
+                        "context_id": c.context_id,
+                        "root": c.root,
+                        "branch": c.branch,
+                        "started_at": c.started_at.to_rfc3339(),
+                        "last_active": c.last_active.to_rfc3339(),
+                        "duration_secs": c.duration().num_seconds(),
+                        "invocations": c.invocation_count,
+                        "failed": c.failed_count,
+                        "sessions": c.session_count,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+        }
+        _ => {
+            println!(
+                "{:<36} {:<20} {:>9} {:>6} {:>6} {:>8}",
+                "CONTEXT", "STARTED", "DURATION", "CMDS", "FAILED", "SESSIONS"
+            );
+            println!("{}", "-".repeat(90));
+            for c in &contexts {
+                println!(
+                    "{:<36} {:<20} {:>9} {:>6} {:>6} {:>8}",
+                    truncate_string(&c.context_id, 36),
+                    c.started_at.format("%Y-%m-%d %H:%M:%S"),
+                    format_span(c.duration()),
+                    c.invocation_count,
+                    c.failed_count,
+                    c.session_count
+                );
+            }
+        }
     }
 
     Ok(())
 }
 
-/// Pull data from a remote to local.
-pub fn pull(remote: Option<&str>, client: Option<&str>, since: Option<&str>, sync_blobs: bool) -> bird::Result<()> {
-    use bird::{parse_since, PullOptions};
-
+/// Show everything done in a work context, across terminals.
+///
+/// `selector` is a context ID, a branch or repo name (every matching
+/// context), or "current" for the current directory and branch.
+pub fn context_show(selector: &str, gap_minutes: i64, format: &str) -> bird::Result<()> {
     let config = Config::load()?;
-    let store = Store::open(config.clone())?;
+    let store = Store::open(config)?;
 
-    // Resolve remote
-    let remote_name = remote
-        .map(String::from)
-        .or_else(|| config.sync.default_remote.clone())
-        .ok_or_else(|| bird::Error::Config(
-            "No remote specified and no default remote configured. Use --remote <name> or set sync.default_remote in config.".to_string()
-        ))?;
+    let mut contexts = if selector == "current" {
+        current_context(&store, gap_minutes)?.into_iter().collect()
+    } else {
+        store.find_contexts(selector, gap_minutes)?
+    };
 
-    let remote_config = config.get_remote(&remote_name)
-        .ok_or_else(|| bird::Error::Config(format!("Remote '{}' not found", remote_name)))?;
+    if contexts.is_empty() {
+        println!("No context matches '{}'.", selector);
+        return Ok(());
+    }
 
-    // Parse since date
-    let since_date = since.map(parse_since).transpose()?;
+    // Oldest first, to read as one story
+    contexts.reverse();
+    let ids: Vec<String> = contexts.iter().map(|c| c.context_id.clone()).collect();
+    let entries = store.context_invocations(&ids, gap_minutes)?;
 
-    let opts = PullOptions {
-        since: since_date,
-        client_id: client.map(String::from),
-        sync_blobs,
-    };
+    if format == "json" {
+        let json: Vec<serde_json::Value> = entries
+            .iter()
+            .map(|e| {
+                serde_json::json!({
+                    "context_id": e.context_id,
+                    "id": e.entry.id,
+                    "session_id": e.entry.session_id,
+                    "timestamp": e.entry.timestamp.to_rfc3339(),
+                    "cmd": e.entry.cmd,
+                    "cwd": e.entry.cwd,
+                    "exit_code": e.entry.exit_code,
+                    "duration_ms": e.entry.duration_ms,
+                    "status": e.entry.status,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+        return Ok(());
+    }
 
-    let stats = store.pull(remote_config, opts)?;
+    let theme = crate::theme::current();
 
-    println!("Pulled from '{}': {}", remote_name, stats);
+    for (i, context) in contexts.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("Context {}", context.context_id);
+        println!(
+            "{}",
+            theme.paint(
+                DIM,
+                format!(
+                    "{} → {} ({}), {} commands, {} failed, {} sessions",
+                    context.started_at.format("%Y-%m-%d %H:%M:%S"),
+                    context.last_active.format("%H:%M:%S"),
+                    format_span(context.duration()),
+                    context.invocation_count,
+                    context.failed_count,
+                    context.session_count
+                )
+            )
+        );
+        println!();
+
+        print_timeline(
+            entries
+                .iter()
+                .filter(|e| e.context_id == context.context_id)
+                .map(|e| &e.entry),
+            &[],
+        );
+    }
 
     Ok(())
 }
@@ -3229,10 +7521,19 @@ pub fn buffer_show(selector: &str) -> bird::Result<()> {
 }
 
 /// Clear all buffered entries.
-pub fn buffer_clear(force: bool) -> bird::Result<()> {
+pub fn buffer_clear(force: bool, dry_run: bool) -> bird::Result<()> {
     let config = Config::load()?;
     let buffer = Buffer::new(config);
 
+    if dry_run {
+        let mut plan = bird::Plan::new();
+        for entry in buffer.list_entries()? {
+            plan.add(format!("delete buffer entry {} ({})", entry.meta.id, entry.meta.cmd));
+        }
+        print!("{}", plan);
+        return Ok(());
+    }
+
     if !force {
         eprint!("Clear all buffer entries? [y/N] ");
         io::stderr().flush()?;
@@ -3298,15 +7599,118 @@ pub fn buffer_status() -> bird::Result<()> {
         println!("Current size: {}", format_size(total_size));
     }
 
-    println!();
-    println!("Exclude patterns ({}):", config.buffer.exclude_patterns.len());
-    for pattern in config.buffer.exclude_patterns.iter().take(10) {
-        println!("  {}", pattern);
+    println!();
+    println!("Exclude patterns ({}):", config.buffer.exclude_patterns.len());
+    for pattern in config.buffer.exclude_patterns.iter().take(10) {
+        println!("  {}", pattern);
+    }
+    if config.buffer.exclude_patterns.len() > 10 {
+        println!("  ... and {} more", config.buffer.exclude_patterns.len() - 10);
+    }
+
+    Ok(())
+}
+
+/// Show which maintenance locks are held, and by whom.
+pub fn locks() -> bird::Result<()> {
+    let config = Config::load()?;
+    let held = bird::locks::holders(&config)?;
+    if held.is_empty() {
+        println!("No maintenance running.");
+        return Ok(());
+    }
+
+    println!("{:<12} {:<8} {:<17} INTENT", "LOCK", "PID", "SINCE");
+    for info in held {
+        println!(
+            "{:<12} {:<8} {:<17} {}",
+            info.lock,
+            info.pid,
+            info.started_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+            info.intent
+        );
+    }
+    Ok(())
+}
+
+/// Write saves spooled by `shq save --async` to the store.
+pub fn spool_flush(quiet: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let store = Store::open(config)?;
+    let stats = store.flush_spool()?;
+
+    for session_id in &stats.compact_sessions {
+        let _ = Command::new(std::env::current_exe().unwrap_or_else(|_| "shq".into()))
+            .args(["compact", "-s", session_id, "--today", "-q"])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+    }
+
+    if !quiet {
+        println!("Wrote {} spooled saves", stats.written);
+        if stats.events > 0 {
+            println!("  Extracted {} events", stats.events);
+        }
+        if stats.failed > 0 {
+            println!("  {} could not be written (moved to {})", stats.failed, store.config().spool_dir().join("failed").display());
+        }
+        if stats.deferred > 0 {
+            println!("  {} were spooled by a newer shq and left for it (see `shq doctor`)", stats.deferred);
+        }
+    }
+    Ok(())
+}
+
+/// Show saves waiting in the spool.
+pub fn spool_status() -> bird::Result<()> {
+    let config = Config::load()?;
+    let pending = bird::spool::pending(&config)?;
+    let flushing = bird::spool::FlushLock::try_acquire(&config)?.is_none();
+
+    println!("Spool:    {}", config.spool_dir().display());
+    println!("Pending:  {}", pending.len());
+    println!("Flusher:  {}", if flushing { "running" } else { "idle" });
+    let failed = bird::spool::failed_count(&config);
+    if failed > 0 {
+        println!("Failed:   {} (in {})", failed, config.spool_dir().join("failed").display());
+    }
+    if !pending.is_empty() && !flushing {
+        println!();
+        println!("Write them with: shq spool flush");
+    }
+    Ok(())
+}
+
+/// Import new entries from shell history files: `paths`, or else those in
+/// `[histfile] ingest`, or else $HISTFILE.
+pub fn histfile_import(paths: &[std::path::PathBuf], format: Option<&str>, quiet: bool) -> bird::Result<()> {
+    let config = Config::load()?;
+    let format: Option<bird::histfile::HistFormat> = format.map(str::parse).transpose()?;
+
+    let mut paths = paths.to_vec();
+    if paths.is_empty() {
+        paths = config.histfile.ingest.iter().map(|p| bird::histfile::expand_home(p)).collect();
     }
-    if config.buffer.exclude_patterns.len() > 10 {
-        println!("  ... and {} more", config.buffer.exclude_patterns.len() - 10);
+    if paths.is_empty() {
+        paths.extend(std::env::var_os("HISTFILE").map(std::path::PathBuf::from));
+    }
+    if paths.is_empty() {
+        return Err(bird::Error::Config(
+            "No history file to import: name one, or set [histfile] ingest or $HISTFILE".to_string(),
+        ));
     }
 
+    let store = Store::open(config.clone())?;
+    for path in &paths {
+        let format = format.unwrap_or_else(|| config.histfile.format_for(path));
+        let stats = store.import_histfile(path, format)?;
+        if !quiet {
+            let skipped = if stats.skipped > 0 { format!(" ({} already imported)", stats.skipped) } else { String::new() };
+            println!("{}: imported {} commands{}", path.display(), stats.imported, skipped);
+        }
+    }
     Ok(())
 }
 
@@ -3440,3 +7844,756 @@ fn format_size(bytes: u64) -> String {
     }
 }
 
+
+// =============================================================================
+// Cron Commands
+// =============================================================================
+
+/// Dimension that marks the runs of a cron job.
+const CRON_DIM: &str = "cron";
+
+/// How far back the daemon catches up on minutes it missed (a late wakeup);
+/// after a longer gap (a suspended machine) missed runs are skipped, as cron
+/// skips them.
+const CRON_CATCH_UP_MINUTES: i64 = 5;
+
+/// Add (or replace) a scheduled command.
+pub fn cron_add(name: &str, schedule: &str, cwd: Option<&std::path::Path>, command: &[String]) -> bird::Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+        return Err(bird::Error::Config(format!(
+            "Invalid job name '{}': use letters, digits, '-', '_' and '.'",
+            name
+        )));
+    }
+    if command.is_empty() {
+        return Err(bird::Error::Config("No command given (shq cron add NAME SCHEDULE -- CMD...)".to_string()));
+    }
+    let parsed = bird::cron::Schedule::parse(schedule)?;
+
+    let cwd = match cwd {
+        Some(dir) => dir.to_path_buf(),
+        None => std::env::current_dir()?,
+    };
+    let mut config = Config::load()?;
+    let replacing = config.cron_jobs.iter().any(|j| j.name == name);
+    config.add_cron_job(bird::CronJob {
+        name: name.to_string(),
+        schedule: schedule.to_string(),
+        command: command.join(" "),
+        cwd: Some(cwd.display().to_string()),
+    });
+    config.save()?;
+
+    println!("{} cron job '{}' ({})", if replacing { "Updated" } else { "Added" }, name, schedule);
+    if let Some(next) = parsed.next_after(chrono::Local::now().naive_local()) {
+        println!("  next run: {}", next.format("%Y-%m-%d %H:%M"));
+    }
+    println!("  runs while `shq cron daemon` is running");
+    Ok(())
+}
+
+/// Remove a scheduled command.
+pub fn cron_remove(name: &str) -> bird::Result<()> {
+    let mut config = Config::load()?;
+    if config.remove_cron_job(name) {
+        config.save()?;
+        println!("Removed cron job: {}", name);
+    } else {
+        println!("Cron job not found: {}", name);
+    }
+    Ok(())
+}
+
+/// Show each job's schedule, next run, last run and recent failures.
+pub fn cron_status(format: &str, recent: usize) -> bird::Result<()> {
+    let config = Config::load()?;
+    if config.cron_jobs.is_empty() {
+        println!("No cron jobs. Add one with: shq cron add NAME SCHEDULE -- CMD...");
+        return Ok(());
+    }
+    let store = Store::open(config.clone())?;
+    let now = chrono::Local::now().naive_local();
+
+    let mut rows = Vec::new();
+    for job in &config.cron_jobs {
+        let next = bird::cron::Schedule::parse(&job.schedule).ok().and_then(|s| s.next_after(now));
+        let query = parse_query(&format!("%dim:{}={}~{}", CRON_DIM, job.name, recent.max(1)));
+        let runs = store.query_invocations(&query)?;
+        let failed = runs.iter().filter(|r| r.exit_code != 0).count();
+        rows.push((job, next, runs, failed));
+    }
+
+    if format == "json" {
+        let json: Vec<serde_json::Value> = rows
+            .iter()
+            .map(|(job, next, runs, failed)| {
+                serde_json::json!({
+                    "name": job.name,
+                    "schedule": job.schedule,
+                    "command": job.command,
+                    "cwd": job.cwd,
+                    "next_run": next.map(|n| n.format("%Y-%m-%d %H:%M").to_string()),
+                    "last_run": runs.first().map(|r| &r.timestamp),
+                    "last_exit": runs.first().map(|r| r.exit_code),
+                    "last_id": runs.first().map(|r| &r.id),
+                    "recent_runs": runs.len(),
+                    "recent_failed": failed,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+        return Ok(());
+    }
+
+    let theme = crate::theme::current();
+    println!(
+        "{:<16} {:<16} {:<17} {:<16} {:>6}  COMMAND",
+        "NAME", "SCHEDULE", "NEXT", "LAST RUN", "FAILED"
+    );
+    for (job, next, runs, failed) in &rows {
+        let next = next
+            .map(|n| n.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "invalid".to_string());
+        let last = match runs.first() {
+            Some(run) => format!("{} {}", theme.status(Some(run.exit_code)), display_time(&run.timestamp, false)),
+            None => "never".to_string(),
+        };
+        // Pad before painting, so the escape codes don't count
+        let last = format!("{}{}", last, " ".repeat(16usize.saturating_sub(visible_width(&last))));
+        let failed = format!("{}/{}", failed, runs.len());
+        println!(
+            "{:<16} {:<16} {:<17} {} {:>6}  {}",
+            truncate_string(&job.name, 16),
+            job.schedule,
+            next,
+            last,
+            failed,
+            truncate_string(&job.command, 50)
+        );
+    }
+    println!();
+    println!("Runs: shq i -g %dim:{}=NAME", CRON_DIM);
+    Ok(())
+}
+
+/// Width of `s` on screen, without ANSI escapes.
+fn visible_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+/// Run a job now, in the foreground, captured like its scheduled runs.
+pub fn cron_run(name: &str) -> bird::Result<()> {
+    let config = Config::load()?;
+    let job = config
+        .cron_jobs
+        .iter()
+        .find(|j| j.name == name)
+        .ok_or_else(|| bird::Error::NotFound(format!("Cron job '{}'", name)))?;
+    std::env::set_current_dir(cron_dir(job))?;
+    let dims = [format!("{}={}", CRON_DIM, job.name)];
+    run(Some(&job.command), &[], None, &dims, &[], None, None, false, false, false, &Expectations::default(), Sinks::default())
+}
+
+/// Directory a job runs in.
+fn cron_dir(job: &bird::CronJob) -> std::path::PathBuf {
+    job.cwd
+        .as_ref()
+        .map(std::path::PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(std::path::PathBuf::from))
+        .unwrap_or_else(|| std::path::PathBuf::from("/"))
+}
+
+/// Run jobs as they come due, until interrupted. Each run is a
+/// `shq run --dim cron=<name>` of its own, so it's captured like any other.
+/// The config is re-read every minute, so added and removed jobs apply
+/// without a restart.
+pub fn cron_daemon() -> bird::Result<()> {
+    use chrono::{Local, TimeDelta, Timelike};
+
+    let exe = std::env::current_exe().unwrap_or_else(|_| "shq".into());
+    let minute_of = |t: chrono::NaiveDateTime| t.with_second(0).and_then(|t| t.with_nanosecond(0)).unwrap_or(t);
+    let mut last = minute_of(Local::now().naive_local());
+    let mut running: Vec<std::process::Child> = Vec::new();
+    let mut warned = std::collections::HashSet::new();
+
+    eprintln!("shq cron: waiting for scheduled jobs (Ctrl-C to stop)");
+    loop {
+        // Wake just after the next minute starts
+        let now = Local::now().naive_local();
+        let wait = (last + TimeDelta::minutes(1) - now).to_std().unwrap_or_default();
+        std::thread::sleep(wait + std::time::Duration::from_millis(200));
+
+        let now = minute_of(Local::now().naive_local());
+        if now <= last {
+            continue;
+        }
+        let first = if now - last > TimeDelta::minutes(CRON_CATCH_UP_MINUTES) { now } else { last + TimeDelta::minutes(1) };
+        last = now;
+
+        let config = Config::load()?;
+        for job in &config.cron_jobs {
+            let schedule = match bird::cron::Schedule::parse(&job.schedule) {
+                Ok(schedule) => schedule,
+                Err(e) => {
+                    if warned.insert(job.name.clone()) {
+                        eprintln!("shq cron: skipping '{}': {}", job.name, e);
+                    }
+                    continue;
+                }
+            };
+            let mut minute = first;
+            while minute <= now {
+                if schedule.matches(minute) {
+                    eprintln!("shq cron: {} running '{}'", minute.format("%Y-%m-%d %H:%M"), job.name);
+                    match Command::new(&exe)
+                        .args(["run", "--dim", &format!("{}={}", CRON_DIM, job.name), "-c", &job.command])
+                        .current_dir(cron_dir(job))
+                        .stdin(std::process::Stdio::null())
+                        .stdout(std::process::Stdio::null())
+                        .stderr(std::process::Stdio::null())
+                        .spawn()
+                    {
+                        Ok(child) => running.push(child),
+                        Err(e) => eprintln!("shq cron: failed to start '{}': {}", job.name, e),
+                    }
+                    // At most one run per job per wakeup
+                    break;
+                }
+                minute += TimeDelta::minutes(1);
+            }
+        }
+
+        // Reap finished runs
+        running.retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+    }
+}
+
+// =============================================================================
+// Watch Commands
+// =============================================================================
+
+/// Dimension that links the runs of one `shq watch`.
+const WATCH_DIM: &str = "watch";
+
+/// Directories `shq watch` doesn't descend into, besides hidden ones: build
+/// output would retrigger the command that wrote it.
+const WATCH_IGNORED_DIRS: &[&str] = &["target", "node_modules", "__pycache__"];
+
+/// How long files must stay unchanged before a rerun, so a save that
+/// touches several files starts one run.
+const WATCH_SETTLE: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Query for the latest run of the watch `watch_id`, or of any watch.
+pub fn latest_of_watch_query(watch_id: &str) -> String {
+    if watch_id.is_empty() {
+        format!("%dim:{}~=.~1", WATCH_DIM)
+    } else {
+        format!("%dim:{}={}~1", WATCH_DIM, watch_id)
+    }
+}
+
+/// Modification time and size of every file under `paths`.
+type WatchSnapshot = std::collections::HashMap<std::path::PathBuf, (Option<SystemTime>, u64)>;
+
+fn watch_snapshot(paths: &[std::path::PathBuf]) -> WatchSnapshot {
+    fn visit(path: &std::path::Path, snapshot: &mut WatchSnapshot) {
+        let Ok(meta) = std::fs::metadata(path) else {
+            return;
+        };
+        if !meta.is_dir() {
+            snapshot.insert(path.to_path_buf(), (meta.modified().ok(), meta.len()));
+            return;
+        }
+        let Ok(entries) = std::fs::read_dir(path) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') {
+                continue;
+            }
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            if is_dir && WATCH_IGNORED_DIRS.contains(&name.as_ref()) {
+                continue;
+            }
+            visit(&entry.path(), snapshot);
+        }
+    }
+
+    let mut snapshot = WatchSnapshot::new();
+    for path in paths {
+        visit(path, &mut snapshot);
+    }
+    snapshot
+}
+
+/// Rerun `command` whenever a file under `paths` changes, capturing each
+/// run with `shq run` and linking the runs with a `watch` dimension.
+pub fn watch(
+    command: &str,
+    paths: &[std::path::PathBuf],
+    interval_ms: u64,
+    clear: bool,
+    dims: &[String],
+) -> bird::Result<()> {
+    // Fail here rather than on every run
+    Config::load()?;
+    let paths = if paths.is_empty() { vec![std::path::PathBuf::from(".")] } else { paths.to_vec() };
+    if let Some(missing) = paths.iter().find(|p| !p.exists()) {
+        return Err(bird::Error::NotFound(format!("Path to watch '{}'", missing.display())));
+    }
+
+    let watch_id = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let exe = std::env::current_exe().unwrap_or_else(|_| "shq".into());
+    let interval = std::time::Duration::from_millis(interval_ms.max(50));
+    let theme = crate::theme::current();
+
+    eprintln!(
+        "shq watch: {} (runs: shq i %dim:{}={}, events: shq events --latest-of-watch)",
+        watch_id, WATCH_DIM, watch_id
+    );
+
+    let mut snapshot = watch_snapshot(&paths);
+    for run in 1.. {
+        if clear {
+            print!("\x1b[2J\x1b[H");
+            let _ = io::stdout().flush();
+        }
+
+        let mut args = vec![
+            "run".to_string(),
+            "--dim".to_string(),
+            format!("{}={}", WATCH_DIM, watch_id),
+        ];
+        for dim in dims {
+            args.push("--dim".to_string());
+            args.push(dim.clone());
+        }
+        args.extend(["-c".to_string(), command.to_string()]);
+
+        let start = Instant::now();
+        let status = Command::new(&exe).args(&args).status()?;
+        let exit_code = status.code().unwrap_or(-1);
+        eprintln!(
+            "{} {}",
+            theme.status(Some(exit_code)),
+            theme.paint(
+                DIM,
+                format!(
+                    "shq watch: run {} exited {} after {}, waiting for changes (Ctrl-C to stop)",
+                    run,
+                    exit_code,
+                    format_duration_ms(start.elapsed().as_millis() as i64)
+                )
+            )
+        );
+
+        // Files changed while the command ran count too: compare against
+        // the snapshot from before it started
+        loop {
+            std::thread::sleep(interval);
+            let current = watch_snapshot(&paths);
+            if current != snapshot {
+                break;
+            }
+        }
+        let mut settled = watch_snapshot(&paths);
+        loop {
+            std::thread::sleep(WATCH_SETTLE);
+            let current = watch_snapshot(&paths);
+            if current == settled {
+                break;
+            }
+            settled = current;
+        }
+        snapshot = settled;
+    }
+    Ok(())
+}
+
+// =============================================================================
+// Bench Commands
+// =============================================================================
+
+/// Dimension that groups the runs of one `shq bench`.
+const BENCH_DIM: &str = "bench";
+
+/// Every captured run matching `filters` (e.g. `%dim:bench=NAME`).
+fn all_runs(store: &Store, filters: &str) -> bird::Result<Vec<bird::InvocationSummary>> {
+    store.query_invocations_with_limit(&parse_query(filters), usize::MAX)
+}
+
+/// Durations of runs, in milliseconds.
+fn run_durations(runs: &[bird::InvocationSummary]) -> Vec<i64> {
+    runs.iter().filter_map(|inv| inv.duration_ms).collect()
+}
+
+/// Durations of a benchmark group's runs, in milliseconds.
+fn bench_durations(store: &Store, group: &str) -> bird::Result<Vec<i64>> {
+    Ok(run_durations(&all_runs(store, &format!("%dim:{}={}", BENCH_DIM, group))?))
+}
+
+/// Run `command` with `$SHELL -c`, its output discarded (a warmup run).
+fn quiet_run(command: &str) -> io::Result<std::process::ExitStatus> {
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+    Command::new(shell)
+        .args(["-c", command])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+}
+
+/// Run `command` as a `shq run` with `dims` (`KEY=VALUE`), its output
+/// discarded from the screen but captured.
+fn captured_run(command: &str, dims: &[String]) -> io::Result<std::process::ExitStatus> {
+    let exe = std::env::current_exe().unwrap_or_else(|_| "shq".into());
+    let mut args = vec!["run".to_string()];
+    for dim in dims {
+        args.push("--dim".to_string());
+        args.push(dim.clone());
+    }
+    args.extend(["-c".to_string(), command.to_string()]);
+    Command::new(exe)
+        .args(&args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+}
+
+/// Name for a benchmark or A/B group: `name`, or the current time.
+fn group_name(name: Option<&str>) -> String {
+    name.map(str::to_string)
+        .unwrap_or_else(|| chrono::Local::now().format("%Y%m%d-%H%M%S").to_string())
+}
+
+/// The most recent benchmark group of `command` other than `current`.
+fn previous_bench_group(store: &Store, command: &str, current: &str) -> bird::Result<Option<String>> {
+    let group = format!(
+        "json_extract_string(metadata['{}'], '$.\"{}\"')",
+        bird::DIMS_METADATA_KEY,
+        BENCH_DIM
+    );
+    let result = store.query(&format!(
+        "SELECT {group} AS bench_group FROM invocations \
+         WHERE cmd = '{}' AND {group} IS NOT NULL AND {group} <> '{}' \
+         GROUP BY bench_group ORDER BY max(timestamp) DESC LIMIT 1",
+        command.replace('\'', "''"),
+        current.replace('\'', "''"),
+    ))?;
+    Ok(result.rows.into_iter().next().and_then(|row| row.into_iter().next()))
+}
+
+/// Run `command` `runs` times (after `warmup` unmeasured runs), capturing
+/// each run into a benchmark group, and report its timings.
+pub fn bench(
+    command: &str,
+    runs: usize,
+    warmup: usize,
+    name: Option<&str>,
+    compare: Option<&str>,
+    ignore_failure: bool,
+    format: &str,
+) -> bird::Result<()> {
+    use crate::bench::Stats;
+    use std::io::IsTerminal;
+
+    let config = Config::load()?;
+    let group = group_name(name);
+    let dims = [format!("{}={}", BENCH_DIM, group)];
+    let runs = runs.max(1);
+    let progress = io::stderr().is_terminal();
+
+    for i in 1..=warmup {
+        if progress {
+            eprint!("\rWarmup {}/{}", i, warmup);
+        }
+        let status = quiet_run(command)?;
+        if !status.success() && !ignore_failure {
+            if progress {
+                eprintln!();
+            }
+            eprintln!("shq bench: warmup run {} exited {} (use -i to ignore failures)", i, status.code().unwrap_or(-1));
+            std::process::exit(1);
+        }
+    }
+
+    let mut failed = 0;
+    for i in 1..=runs {
+        if progress {
+            eprint!("\rRun {}/{}     ", i, runs);
+        }
+        let status = captured_run(command, &dims)?;
+        if !status.success() {
+            failed += 1;
+            if !ignore_failure {
+                if progress {
+                    eprintln!();
+                }
+                eprintln!(
+                    "shq bench: run {} exited {} (use -i to ignore failures; see: shq i -g %dim:{}={})",
+                    i,
+                    status.code().unwrap_or(-1),
+                    BENCH_DIM,
+                    group
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+    if progress {
+        eprint!("\r{}\r", " ".repeat(20));
+    }
+
+    let store = Store::open(config)?;
+    let stats = Stats::from_ms(&bench_durations(&store, &group)?)
+        .ok_or_else(|| bird::Error::NotFound(format!("Captured runs of benchmark group '{}'", group)))?;
+    let baseline_group = match compare {
+        Some(g) => Some(g.to_string()),
+        None => previous_bench_group(&store, command, &group)?,
+    };
+    let baseline = match &baseline_group {
+        Some(g) => Stats::from_ms(&bench_durations(&store, g)?).map(|stats| (g.clone(), stats)),
+        None => None,
+    };
+    if let (Some(g), None) = (compare, &baseline) {
+        return Err(bird::Error::NotFound(format!("Benchmark group '{}'", g)));
+    }
+
+    if format == "json" {
+        let stats_json = |s: &Stats| {
+            serde_json::json!({
+                "runs": s.runs,
+                "mean_ms": s.mean,
+                "stddev_ms": s.stddev,
+                "min_ms": s.min,
+                "max_ms": s.max,
+            })
+        };
+        let mut json = serde_json::json!({
+            "command": command,
+            "group": group,
+            "failed": failed,
+            "stats": stats_json(&stats),
+        });
+        if let Some((g, base)) = &baseline {
+            json["baseline"] = serde_json::json!({ "group": g, "stats": stats_json(base) });
+            if let Some((ratio, error)) = stats.ratio_to(base) {
+                json["ratio"] = serde_json::json!(ratio);
+                json["ratio_error"] = serde_json::json!(error);
+            }
+        }
+        println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+        return Ok(());
+    }
+
+    let ms = |v: f64| format_duration_ms(v.round() as i64);
+    let theme = crate::theme::current();
+    println!("Benchmark {}: {}", group, command);
+    println!(
+        "  Time (mean ± σ):  {} ± {}",
+        theme.paint(BOLD, ms(stats.mean)),
+        ms(stats.stddev)
+    );
+    println!("  Range (min … max): {} … {}  ({} runs)", ms(stats.min), ms(stats.max), stats.runs);
+    if failed > 0 {
+        println!("  {}", theme.paint(YELLOW, format!("{} of {} runs exited non-zero", failed, runs)));
+    }
+
+    if let Some((g, base)) = &baseline {
+        println!();
+        println!("  Previous {}: {} ± {}", g, ms(base.mean), ms(base.stddev));
+        if let Some((ratio, error)) = stats.ratio_to(base) {
+            // Within the uncertainty of either group, call it unchanged
+            let (text, style) = if (ratio - 1.0).abs() <= error {
+                (format!("{:.2} ± {:.2} times as long: no significant change", ratio, error), DIM)
+            } else if ratio < 1.0 {
+                (format!("{:.2} ± {:.2} times faster", 1.0 / ratio, error / (ratio * ratio)), GREEN)
+            } else {
+                (format!("{:.2} ± {:.2} times slower", ratio, error), RED)
+            };
+            println!("  {}", theme.paint(style, text));
+        }
+    }
+    println!();
+    println!("Runs: shq i -g %dim:{}={}", BENCH_DIM, group);
+    Ok(())
+}
+
+// =============================================================================
+// A/B Commands
+// =============================================================================
+
+/// Dimensions that group the runs of one `shq ab`, and say which variant
+/// each run was.
+const AB_DIM: &str = "ab";
+const AB_VARIANT_DIM: &str = "ab_variant";
+
+/// Run variants `a` and `b` `runs` times each, interleaved, capturing every
+/// run, then compare them. With `show`, compare a stored A/B run instead.
+pub fn ab(
+    a: Option<&str>,
+    b: Option<&str>,
+    runs: usize,
+    warmup: usize,
+    name: Option<&str>,
+    show: Option<&str>,
+    format: &str,
+) -> bird::Result<()> {
+    use std::io::IsTerminal;
+
+    let config = Config::load()?;
+    let group = match show {
+        Some(group) => group.to_string(),
+        None => {
+            let (Some(a), Some(b)) = (a, b) else {
+                return Err(bird::Error::Config("Both --a and --b are required (or --show GROUP)".to_string()));
+            };
+            let group = group_name(name);
+            let runs = runs.max(1);
+            let progress = io::stderr().is_terminal();
+
+            for i in 1..=warmup {
+                if progress {
+                    eprint!("\rWarmup {}/{}", i, warmup);
+                }
+                quiet_run(a)?;
+                quiet_run(b)?;
+            }
+            for i in 1..=runs {
+                if progress {
+                    eprint!("\rRun {}/{}     ", i, runs);
+                }
+                // Alternate which goes first, so drift (a warming cache, a
+                // throttling CPU) doesn't favor one variant
+                let order = if i % 2 == 1 { [("a", a), ("b", b)] } else { [("b", b), ("a", a)] };
+                for (variant, command) in order {
+                    let dims = [format!("{}={}", AB_DIM, group), format!("{}={}", AB_VARIANT_DIM, variant)];
+                    captured_run(command, &dims)?;
+                }
+            }
+            if progress {
+                eprint!("\r{}\r", " ".repeat(20));
+            }
+            group
+        }
+    };
+
+    let store = Store::open(config)?;
+    let variant_runs = |variant: &str| {
+        all_runs(&store, &format!("%dim:{}={} %dim:{}={}", AB_DIM, group, AB_VARIANT_DIM, variant))
+    };
+    let (runs_a, runs_b) = (variant_runs("a")?, variant_runs("b")?);
+    if runs_a.is_empty() && runs_b.is_empty() {
+        return Err(bird::Error::NotFound(format!("A/B group '{}'", group)));
+    }
+    report_ab(&group, &runs_a, &runs_b, format);
+    Ok(())
+}
+
+fn report_ab(group: &str, runs_a: &[bird::InvocationSummary], runs_b: &[bird::InvocationSummary], format: &str) {
+    use crate::bench::{significant, Stats};
+
+    let stats_a = Stats::from_ms(&run_durations(runs_a));
+    let stats_b = Stats::from_ms(&run_durations(runs_b));
+    let ok = |runs: &[bird::InvocationSummary]| runs.iter().filter(|r| r.exit_code == 0).count();
+    let command = |runs: &[bird::InvocationSummary]| runs.first().map(|r| r.cmd.clone()).unwrap_or_default();
+    let ratio = match (&stats_a, &stats_b) {
+        (Some(a), Some(b)) => b.ratio_to(a),
+        _ => None,
+    };
+    let welch = match (&stats_a, &stats_b) {
+        (Some(a), Some(b)) => b.welch_t(a),
+        _ => None,
+    };
+
+    if format == "json" {
+        let variant = |runs: &[bird::InvocationSummary], stats: &Option<Stats>| {
+            serde_json::json!({
+                "command": command(runs),
+                "runs": runs.len(),
+                "succeeded": ok(runs),
+                "mean_ms": stats.as_ref().map(|s| s.mean),
+                "stddev_ms": stats.as_ref().map(|s| s.stddev),
+                "min_ms": stats.as_ref().map(|s| s.min),
+                "max_ms": stats.as_ref().map(|s| s.max),
+            })
+        };
+        let json = serde_json::json!({
+            "group": group,
+            "a": variant(runs_a, &stats_a),
+            "b": variant(runs_b, &stats_b),
+            "ratio_b_to_a": ratio.map(|(r, _)| r),
+            "ratio_error": ratio.map(|(_, e)| e),
+            "welch_t": welch.map(|(t, _)| t),
+            "welch_df": welch.map(|(_, df)| df),
+            "significant": welch.map(|(t, df)| significant(t, df)),
+        });
+        println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+        return;
+    }
+
+    let theme = crate::theme::current();
+    let ms = |v: f64| format_duration_ms(v.round() as i64);
+    println!("A/B {}", group);
+    println!();
+    println!("{:<3} {:>10} {:>10} {:>10} {:>10} {:>8}  COMMAND", "", "MEAN", "STDDEV", "MIN", "MAX", "OK");
+    for (label, runs, stats) in [("A", runs_a, &stats_a), ("B", runs_b, &stats_b)] {
+        let cell = |f: fn(&Stats) -> f64| stats.as_ref().map(|s| ms(f(s))).unwrap_or_else(|| "-".to_string());
+        let succeeded = format!("{}/{}", ok(runs), runs.len());
+        // Pad before painting, so the escape codes don't count
+        let succeeded = if ok(runs) == runs.len() {
+            format!("{:>8}", succeeded)
+        } else {
+            theme.paint(YELLOW, format!("{:>8}", succeeded))
+        };
+        println!(
+            "{:<3} {:>10} {:>10} {:>10} {:>10} {}  {}",
+            label,
+            cell(|s| s.mean),
+            cell(|s| s.stddev),
+            cell(|s| s.min),
+            cell(|s| s.max),
+            succeeded,
+            truncate_string(&command(runs), 50)
+        );
+    }
+    println!();
+
+    match (ratio, welch) {
+        (Some((ratio, error)), Some((t, df))) => {
+            let change = if ratio < 1.0 {
+                format!("B is {:.2} ± {:.2} times faster than A", 1.0 / ratio, error / (ratio * ratio))
+            } else {
+                format!("B is {:.2} ± {:.2} times slower than A", ratio, error)
+            };
+            if significant(t, df) {
+                println!("{}", theme.paint(if ratio < 1.0 { GREEN } else { RED }, change));
+                println!("{}", theme.paint(DIM, format!("significant at 5% (Welch's t = {:.2}, df = {:.1})", t, df)));
+            } else {
+                println!("{}", change);
+                println!(
+                    "{}",
+                    theme.paint(DIM, format!("not significant at 5% (Welch's t = {:.2}, df = {:.1}); try more runs", t, df))
+                );
+            }
+        }
+        (Some((ratio, error)), None) => {
+            println!("B takes {:.2} ± {:.2} times as long as A (too few runs to test significance)", ratio, error);
+        }
+        _ => println!("Not enough captured runs to compare"),
+    }
+    println!();
+    println!("Runs: shq i -g %dim:{}={}   Review: shq ab --show {}", AB_DIM, group, group);
+}
@@ -0,0 +1,263 @@
+//! Weekly digest (`shq digest`).
+//!
+//! Summarizes one week for a standup or status mail: what got done, what
+//! started failing, and what got slower, each compared with the week
+//! before. The Markdown form is rendered from a template so teams can
+//! reorder, rename or drop sections.
+
+use std::collections::HashMap;
+
+use bird::InvocationSummary;
+use chrono::NaiveDate;
+
+use crate::report::{failure_groups, format_duration};
+
+/// How many items each section lists.
+const SECTION_ITEMS: usize = 10;
+
+/// Successful runs at least this long (ms) count as noteworthy.
+const NOTEWORTHY_MS: i64 = 60_000;
+
+/// A command is a slowdown when its median run got this much slower.
+const REGRESSION_RATIO: f64 = 1.5;
+
+/// Template used without `--template`. Placeholders are replaced with the
+/// week's dates and each section's bullet list.
+pub const DEFAULT_TEMPLATE: &str = "\
+# Week of {{start}}
+
+{{summary}}
+
+## Done
+
+{{noteworthy}}
+
+## New recurring failures
+
+{{new_failures}}
+
+## Slower than last week
+
+{{regressions}}
+";
+
+/// One week's digest.
+pub struct Digest {
+    pub start: NaiveDate,
+    /// Last day of the week.
+    pub end: NaiveDate,
+    pub summary: String,
+    /// Long successful runs and commands fixed during the week.
+    pub noteworthy: Vec<String>,
+    /// Commands failing repeatedly this week that didn't fail last week.
+    pub new_failures: Vec<String>,
+    /// Commands whose median duration went up.
+    pub regressions: Vec<String>,
+}
+
+/// Whether `inv` ran on or after `day` (timestamps sort as text).
+fn on_or_after(inv: &InvocationSummary, day: NaiveDate) -> bool {
+    inv.timestamp.as_str() >= day.to_string().as_str()
+}
+
+fn median(durations: &mut [i64]) -> Option<i64> {
+    durations.sort_unstable();
+    durations.get(durations.len() / 2).copied()
+}
+
+/// Median duration of each command's successful runs.
+fn medians<'a>(invocations: impl Iterator<Item = &'a InvocationSummary>) -> HashMap<&'a str, i64> {
+    let mut durations: HashMap<&str, Vec<i64>> = HashMap::new();
+    for inv in invocations.filter(|i| i.exit_code == 0) {
+        if let Some(ms) = inv.duration_ms {
+            durations.entry(inv.cmd.as_str()).or_default().push(ms);
+        }
+    }
+    durations
+        .into_iter()
+        .filter_map(|(cmd, mut ms)| median(&mut ms).map(|m| (cmd, m)))
+        .collect()
+}
+
+/// Build the digest for the week `start..=end` from `invocations` (most
+/// recent first) covering that week and the one before it.
+pub fn build(start: NaiveDate, end: NaiveDate, invocations: &[InvocationSummary]) -> Digest {
+    let split = invocations.iter().position(|inv| !on_or_after(inv, start)).unwrap_or(invocations.len());
+    let (week, previous) = invocations.split_at(split);
+
+    let failed = week.iter().filter(|i| i.exit_code != 0).count();
+    let days = {
+        let mut days: Vec<&str> = week.iter().filter_map(|i| i.timestamp.get(..10)).collect();
+        days.dedup();
+        days.len()
+    };
+    let summary = if week.is_empty() {
+        "No commands recorded.".to_string()
+    } else {
+        format!(
+            "{} commands on {} days, {} failed ({:.0}%).",
+            week.len(),
+            days,
+            failed,
+            failed as f64 * 100.0 / week.len() as f64
+        )
+    };
+
+    // Done: commands that went from failing to passing, then long runs
+    let mut noteworthy = Vec::new();
+    let mut seen: Vec<&str> = Vec::new();
+    for group in failure_groups(week) {
+        let fixed = week
+            .iter()
+            .find(|i| i.cmd == group.cmd && i.exit_code == 0 && i.timestamp > group.last_seen);
+        if let Some(fix) = fixed {
+            noteworthy.push(format!(
+                "`{}` fixed {} after {} failure{}",
+                fix.cmd,
+                fix.timestamp.get(..10).unwrap_or(&fix.timestamp),
+                group.count,
+                if group.count == 1 { "" } else { "s" }
+            ));
+            seen.push(&fix.cmd);
+        }
+    }
+    let mut long_runs: Vec<&InvocationSummary> = week
+        .iter()
+        .filter(|i| i.exit_code == 0 && i.duration_ms.is_some_and(|ms| ms >= NOTEWORTHY_MS))
+        .collect();
+    long_runs.sort_by_key(|i| std::cmp::Reverse(i.duration_ms));
+    for inv in long_runs {
+        if !seen.contains(&inv.cmd.as_str()) {
+            seen.push(&inv.cmd);
+            noteworthy.push(format!("`{}` ({})", inv.cmd, format_duration(inv.duration_ms)));
+        }
+    }
+    noteworthy.truncate(SECTION_ITEMS);
+
+    let failed_before: Vec<&str> =
+        previous.iter().filter(|i| i.exit_code != 0).map(|i| i.cmd.as_str()).collect();
+    let new_failures: Vec<String> = failure_groups(week)
+        .into_iter()
+        .filter(|g| g.count >= 2 && !failed_before.contains(&g.cmd.as_str()))
+        .take(SECTION_ITEMS)
+        .map(|g| format!("`{}` failed {} times (last exit {})", g.cmd, g.count, g.last_exit_code))
+        .collect();
+
+    let before = medians(previous.iter());
+    let mut slower: Vec<(&str, i64, i64)> = medians(week.iter())
+        .into_iter()
+        .filter_map(|(cmd, now)| {
+            let was = *before.get(cmd)?;
+            (now >= 1_000 && now as f64 >= was as f64 * REGRESSION_RATIO).then_some((cmd, was, now))
+        })
+        .collect();
+    slower.sort_by(|a, b| (b.2 * a.1.max(1)).cmp(&(a.2 * b.1.max(1))).then(a.0.cmp(b.0)));
+    let regressions = slower
+        .into_iter()
+        .take(SECTION_ITEMS)
+        .map(|(cmd, was, now)| {
+            format!("`{}` {} → {} (median)", cmd, format_duration(Some(was)), format_duration(Some(now)))
+        })
+        .collect();
+
+    Digest { start, end, summary, noteworthy, new_failures, regressions }
+}
+
+fn bullets(items: &[String]) -> String {
+    if items.is_empty() {
+        "_Nothing._".to_string()
+    } else {
+        items.iter().map(|i| format!("- {}", i)).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// Render `digest` as Markdown through `template`.
+///
+/// Placeholders: `{{start}}`, `{{end}}`, `{{summary}}`, `{{noteworthy}}`,
+/// `{{new_failures}}` and `{{regressions}}`.
+pub fn render_markdown(digest: &Digest, template: &str) -> String {
+    template
+        .replace("{{start}}", &digest.start.to_string())
+        .replace("{{end}}", &digest.end.to_string())
+        .replace("{{summary}}", &digest.summary)
+        .replace("{{noteworthy}}", &bullets(&digest.noteworthy))
+        .replace("{{new_failures}}", &bullets(&digest.new_failures))
+        .replace("{{regressions}}", &bullets(&digest.regressions))
+}
+
+/// Render `digest` for the terminal.
+pub fn render_text(digest: &Digest) -> String {
+    let mut out = format!("Week of {} to {}\n{}\n", digest.start, digest.end, digest.summary);
+    for (title, items) in [
+        ("DONE", &digest.noteworthy),
+        ("NEW RECURRING FAILURES", &digest.new_failures),
+        ("SLOWER THAN LAST WEEK", &digest.regressions),
+    ] {
+        out.push_str(&format!("\n{}\n", title));
+        if items.is_empty() {
+            out.push_str("  -\n");
+        }
+        for item in items {
+            out.push_str(&format!("  {}\n", item.replace('`', "")));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inv(cmd: &str, exit_code: i32, timestamp: &str, duration_ms: Option<i64>) -> InvocationSummary {
+        InvocationSummary {
+            id: format!("id-{}", timestamp),
+            cmd: cmd.to_string(),
+            exit_code,
+            timestamp: timestamp.to_string(),
+            duration_ms,
+            cwd: "/tmp".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_build_digest() {
+        let start = NaiveDate::from_ymd_opt(2026, 3, 9).unwrap();
+        let end = NaiveDate::from_ymd_opt(2026, 3, 15).unwrap();
+        // Most recent first, like Store::invocations_between
+        let invs = vec![
+            inv("make test", 0, "2026-03-12 10:00:00", Some(5_000)),
+            inv("make test", 2, "2026-03-11 10:00:00", Some(4_000)),
+            inv("make test", 2, "2026-03-10 10:00:00", Some(4_000)),
+            inv("./deploy.sh", 0, "2026-03-10 09:00:00", Some(300_000)),
+            inv("cargo build", 0, "2026-03-09 09:00:00", Some(9_000)),
+            inv("lint", 1, "2026-03-09 08:00:00", None),
+            inv("lint", 1, "2026-03-09 07:00:00", None),
+            // The week before
+            inv("cargo build", 0, "2026-03-05 09:00:00", Some(3_000)),
+            inv("lint", 1, "2026-03-04 09:00:00", None),
+        ];
+
+        let digest = build(start, end, &invs);
+        assert_eq!(digest.summary, "7 commands on 4 days, 4 failed (57%).");
+        assert_eq!(
+            digest.noteworthy,
+            ["`make test` fixed 2026-03-12 after 2 failures", "`./deploy.sh` (5m00s)"]
+        );
+        // lint was already failing last week
+        assert_eq!(digest.new_failures, ["`make test` failed 2 times (last exit 2)"]);
+        assert_eq!(digest.regressions, ["`cargo build` 3.0s → 9.0s (median)"]);
+    }
+
+    #[test]
+    fn test_render_markdown_template() {
+        let start = NaiveDate::from_ymd_opt(2026, 3, 9).unwrap();
+        let digest = build(start, start, &[]);
+
+        let md = render_markdown(&digest, DEFAULT_TEMPLATE);
+        assert!(md.starts_with("# Week of 2026-03-09\n\nNo commands recorded."));
+        assert!(md.contains("## Done\n\n_Nothing._"));
+
+        let md = render_markdown(&digest, "Fixed this week:\n{{noteworthy}}\n");
+        assert_eq!(md, "Fixed this week:\n_Nothing._\n");
+    }
+}
@@ -0,0 +1,180 @@
+//! Assertions on a captured run (`shq run --expect ...`).
+//!
+//! - `exit=N`, `exit!=N`: the exit code
+//! - `duration<D`, `duration<=D`: how long it ran (`D` like `500ms`, `30s`, `2m`)
+//! - `no-events`, `no-events:severity=S`: no (matching) events were extracted
+//!
+//! With any `--expect`, shq exits 0 when all of them hold and 1 otherwise,
+//! whatever the command's own exit code.
+
+use std::process::{Command, Stdio};
+
+/// One `--expect` assertion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expectation {
+    Exit { code: i32, equal: bool },
+    Duration { max_ms: i64, inclusive: bool },
+    NoEvents { severity: Option<String> },
+}
+
+impl std::fmt::Display for Expectation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expectation::Exit { code, equal } => write!(f, "exit{}{}", if *equal { "=" } else { "!=" }, code),
+            Expectation::Duration { max_ms, inclusive } => {
+                write!(f, "duration{}{}ms", if *inclusive { "<=" } else { "<" }, max_ms)
+            }
+            Expectation::NoEvents { severity: None } => write!(f, "no-events"),
+            Expectation::NoEvents { severity: Some(s) } => write!(f, "no-events:severity={}", s),
+        }
+    }
+}
+
+impl std::str::FromStr for Expectation {
+    type Err = bird::Error;
+
+    fn from_str(s: &str) -> bird::Result<Self> {
+        let s = s.trim();
+        let invalid = || {
+            bird::Error::Config(format!(
+                "Invalid expectation '{}' (expected exit=N, exit!=N, duration<D or no-events[:severity=S])",
+                s
+            ))
+        };
+
+        if let Some(code) = s.strip_prefix("exit!=") {
+            return Ok(Expectation::Exit { code: code.parse().map_err(|_| invalid())?, equal: false });
+        }
+        if let Some(code) = s.strip_prefix("exit=") {
+            return Ok(Expectation::Exit { code: code.parse().map_err(|_| invalid())?, equal: true });
+        }
+        if let Some(rest) = s.strip_prefix("duration<") {
+            let (rest, inclusive) = match rest.strip_prefix('=') {
+                Some(rest) => (rest, true),
+                None => (rest, false),
+            };
+            return Ok(Expectation::Duration { max_ms: parse_ms(rest).ok_or_else(invalid)?, inclusive });
+        }
+        if s == "no-events" {
+            return Ok(Expectation::NoEvents { severity: None });
+        }
+        if let Some(severity) = s.strip_prefix("no-events:severity=") {
+            return Ok(Expectation::NoEvents { severity: Some(severity.to_lowercase()) });
+        }
+        Err(invalid())
+    }
+}
+
+/// Milliseconds in `500ms`, or any duration [`bird::reltime::parse_duration`] takes.
+fn parse_ms(s: &str) -> Option<i64> {
+    match s.trim().strip_suffix("ms") {
+        Some(ms) => ms.parse().ok(),
+        None => bird::reltime::parse_duration(s).map(|d| d.num_milliseconds()),
+    }
+}
+
+/// What a run produced, for checking expectations against.
+pub struct Outcome<'a> {
+    pub exit_code: i32,
+    pub duration_ms: i64,
+    /// Number of the run's events, of a severity or of any
+    pub event_count: &'a dyn Fn(Option<&str>) -> bird::Result<i64>,
+}
+
+/// The `--expect`s of a run, and whether to notify when they fail.
+#[derive(Debug, Default)]
+pub struct Expectations {
+    pub expectations: Vec<Expectation>,
+    pub notify: bool,
+}
+
+impl Expectations {
+    pub fn parse(specs: &[String], notify: bool) -> bird::Result<Self> {
+        let expectations = specs.iter().map(|s| s.parse()).collect::<bird::Result<_>>()?;
+        Ok(Expectations { expectations, notify })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.expectations.is_empty()
+    }
+
+    /// The expectations that don't hold, each with what was found instead.
+    pub fn check(&self, outcome: &Outcome) -> bird::Result<Vec<(&Expectation, String)>> {
+        let mut failures = Vec::new();
+        for expectation in &self.expectations {
+            let actual = match expectation {
+                Expectation::Exit { code, equal } => {
+                    ((outcome.exit_code == *code) != *equal).then(|| format!("exit code was {}", outcome.exit_code))
+                }
+                Expectation::Duration { max_ms, inclusive } => {
+                    let ok = if *inclusive { outcome.duration_ms <= *max_ms } else { outcome.duration_ms < *max_ms };
+                    (!ok).then(|| format!("took {}ms", outcome.duration_ms))
+                }
+                Expectation::NoEvents { severity } => {
+                    let count = (outcome.event_count)(severity.as_deref())?;
+                    (count > 0).then(|| format!("{} events", count))
+                }
+            };
+            if let Some(actual) = actual {
+                failures.push((expectation, actual));
+            }
+        }
+        Ok(failures)
+    }
+}
+
+/// Show a desktop notification, if the platform has a way to. Best effort.
+pub fn notify(title: &str, body: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification {:?} with title {:?}",
+            body.replace('"', "'"),
+            title.replace('"', "'")
+        );
+        let mut command = Command::new("osascript");
+        command.args(["-e", &script]);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args([title, body]);
+        command
+    };
+    let _ = command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_expectation() {
+        assert_eq!("exit=0".parse::<Expectation>().unwrap(), Expectation::Exit { code: 0, equal: true });
+        assert_eq!("exit!=1".parse::<Expectation>().unwrap(), Expectation::Exit { code: 1, equal: false });
+        assert_eq!(
+            "duration<30s".parse::<Expectation>().unwrap(),
+            Expectation::Duration { max_ms: 30_000, inclusive: false }
+        );
+        assert_eq!(
+            "duration<=500ms".parse::<Expectation>().unwrap(),
+            Expectation::Duration { max_ms: 500, inclusive: true }
+        );
+        assert_eq!(
+            "no-events:severity=error".parse::<Expectation>().unwrap(),
+            Expectation::NoEvents { severity: Some("error".to_string()) }
+        );
+        assert!("exit=ok".parse::<Expectation>().is_err());
+        assert!("events".parse::<Expectation>().is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let specs = ["exit=0", "duration<1s", "no-events:severity=error", "no-events"].map(String::from);
+        let expectations = Expectations::parse(&specs, false).unwrap();
+        let event_count = |severity: Option<&str>| Ok(if severity == Some("error") { 0 } else { 2 });
+        let outcome = Outcome { exit_code: 2, duration_ms: 400, event_count: &event_count };
+
+        let failures = expectations.check(&outcome).unwrap();
+        let failed: Vec<String> = failures.iter().map(|(e, actual)| format!("{}: {}", e, actual)).collect();
+        assert_eq!(failed, vec!["exit=0: exit code was 2", "no-events: 2 events"]);
+    }
+}
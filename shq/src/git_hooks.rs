@@ -0,0 +1,151 @@
+//! Git hook wrappers for shq.
+//!
+//! `shq git-hooks install` moves an existing hook aside (`<hook>.shq-orig`)
+//! and installs a small POSIX sh wrapper in its place. The wrapper runs the
+//! original hook, tees its output to the terminal, and saves the run with
+//! `--invoker-type git-hook` so failed pre-commit/pre-push checks can be
+//! inspected from history.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Hooks wrapped when none are named explicitly.
+pub const DEFAULT_HOOKS: &[&str] = &["pre-commit", "pre-push"];
+
+/// Marker line identifying a wrapper installed by shq.
+const MARKER: &str = "# shq-git-hook";
+
+/// Suffix for the original hook moved aside by the wrapper.
+const ORIG_SUFFIX: &str = ".shq-orig";
+
+/// Outcome of installing or removing a single hook wrapper.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Installed,
+    AlreadyInstalled,
+    Removed,
+    NotInstalled,
+    /// No hook to wrap (nothing at `<hooks_dir>/<hook>`).
+    Missing,
+}
+
+/// Path of the original hook for `hook` in `dir`.
+fn orig_path(dir: &Path, hook: &str) -> PathBuf {
+    dir.join(format!("{hook}{ORIG_SUFFIX}"))
+}
+
+/// Whether the file at `path` is a wrapper installed by shq.
+fn is_wrapper(path: &Path) -> bool {
+    fs::read_to_string(path)
+        .map(|s| s.lines().nth(1) == Some(MARKER))
+        .unwrap_or(false)
+}
+
+/// Generate the wrapper script for `hook`.
+pub fn wrapper_script(hook: &str) -> String {
+    format!(
+        r#"#!/bin/sh
+{MARKER}
+# Installed by `shq git-hooks install`; remove with `shq git-hooks uninstall`.
+# Runs the original hook under shq capture (invoker type: git-hook).
+hook="$(dirname "$0")/{hook}{ORIG_SUFFIX}"
+[ -x "$hook" ] || exit 0
+command -v shq >/dev/null 2>&1 || exec "$hook" "$@"
+out=$(mktemp "${{TMPDIR:-/tmp}}/shq-hook.XXXXXX") || exec "$hook" "$@"
+rc_file="$out.rc"
+start=$(date +%s)
+{{ "$hook" "$@" 2>&1; echo $? > "$rc_file"; }} | tee "$out"
+rc=$(cat "$rc_file" 2>/dev/null || echo 1)
+end=$(date +%s)
+shq save "$out" -c "git hook {hook}${{*:+ $*}}" -x "$rc" -d $(( (end - start) * 1000 )) \
+    --invoker git --invoker-type git-hook -q >/dev/null 2>&1 || true
+rm -f "$out" "$rc_file"
+exit "$rc"
+"#
+    )
+}
+
+/// Install the wrapper for `hook` in `dir`, moving the existing hook aside.
+pub fn install(dir: &Path, hook: &str) -> io::Result<Status> {
+    let path = dir.join(hook);
+    if is_wrapper(&path) {
+        return Ok(Status::AlreadyInstalled);
+    }
+    if !path.exists() {
+        return Ok(Status::Missing);
+    }
+
+    fs::rename(&path, orig_path(dir, hook))?;
+    fs::write(&path, wrapper_script(hook))?;
+    set_executable(&path)?;
+    Ok(Status::Installed)
+}
+
+/// Remove the wrapper for `hook` in `dir`, restoring the original hook.
+pub fn uninstall(dir: &Path, hook: &str) -> io::Result<Status> {
+    let path = dir.join(hook);
+    if !is_wrapper(&path) {
+        return Ok(Status::NotInstalled);
+    }
+
+    let orig = orig_path(dir, hook);
+    if orig.exists() {
+        fs::rename(orig, &path)?;
+    } else {
+        fs::remove_file(&path)?;
+    }
+    Ok(Status::Removed)
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_wrapper_script() {
+        let script = wrapper_script("pre-push");
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("pre-push.shq-orig"));
+        assert!(script.contains("--invoker-type git-hook"));
+        assert!(script.contains("-c \"git hook pre-push"));
+    }
+
+    #[test]
+    fn test_install_and_uninstall() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        fs::write(dir.join("pre-commit"), "#!/bin/sh\ncargo clippy\n").unwrap();
+
+        assert_eq!(install(dir, "pre-commit").unwrap(), Status::Installed);
+        assert_eq!(install(dir, "pre-commit").unwrap(), Status::AlreadyInstalled);
+        assert!(is_wrapper(&dir.join("pre-commit")));
+        assert_eq!(
+            fs::read_to_string(dir.join("pre-commit.shq-orig")).unwrap(),
+            "#!/bin/sh\ncargo clippy\n"
+        );
+
+        // Nothing to wrap
+        assert_eq!(install(dir, "pre-push").unwrap(), Status::Missing);
+        assert_eq!(uninstall(dir, "pre-push").unwrap(), Status::NotInstalled);
+
+        assert_eq!(uninstall(dir, "pre-commit").unwrap(), Status::Removed);
+        assert_eq!(
+            fs::read_to_string(dir.join("pre-commit")).unwrap(),
+            "#!/bin/sh\ncargo clippy\n"
+        );
+        assert!(!dir.join("pre-commit.shq-orig").exists());
+    }
+}
@@ -0,0 +1,257 @@
+//! Finding the shell hooks installed in rc files, and upgrading stale ones.
+//!
+//! A hook loaded with `eval "$(shq hook init)"` is generated at startup by
+//! the installed binary and can't go stale. A generated script pasted into
+//! an rc file, or saved to a file the rc file sources, keeps whatever the
+//! shq that generated it did; after an upgrade it may call commands or
+//! flags that changed, and capture silently stops. Scripts carry a
+//! [`HOOK_VERSION`] line to tell.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::hooks::{self, Mode, Shell, END_MARKER, HEADER_PREFIX, HOOK_VERSION, VERSION_PREFIX};
+
+/// Suffix of the backup written before an rc file is patched.
+const BACKUP_SUFFIX: &str = ".shq-backup";
+
+/// How a hook is installed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Install {
+    /// `eval "$(shq hook init ...)"`: always the binary's current script.
+    Eval,
+    /// A copy of a generated script, lines `start..=end` (0-based).
+    Static {
+        /// Version of the copy; 1 for scripts from before versioning.
+        version: u32,
+        shell: Shell,
+        mode: Mode,
+        prompt_indicator: bool,
+        start: usize,
+        /// `None` when the end of the copy couldn't be found.
+        end: Option<usize>,
+    },
+}
+
+/// A hook found in a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Found {
+    pub path: PathBuf,
+    /// 1-based line where it starts.
+    pub line: usize,
+    pub install: Install,
+}
+
+impl Found {
+    /// Whether the hook is older than this binary's.
+    pub fn is_stale(&self) -> bool {
+        matches!(self.install, Install::Static { version, .. } if version < HOOK_VERSION)
+    }
+}
+
+/// The rc files shells read at startup, in `home` (and `zdotdir` for zsh).
+pub fn rc_files(home: &Path, zdotdir: Option<&Path>) -> Vec<PathBuf> {
+    let zdotdir = zdotdir.unwrap_or(home);
+    let mut files: Vec<PathBuf> = [".zshrc", ".zprofile", ".zshenv"].iter().map(|name| zdotdir.join(name)).collect();
+    files.extend([".bashrc", ".bash_profile", ".profile"].iter().map(|name| home.join(name)));
+    files.dedup();
+    files
+}
+
+/// The hooks in `files` and in the files they source.
+pub fn scan(files: &[PathBuf], home: &Path) -> Vec<Found> {
+    let mut found = Vec::new();
+    let mut seen: Vec<PathBuf> = Vec::new();
+    let mut pending: Vec<PathBuf> = files.to_vec();
+    while let Some(path) = pending.pop() {
+        if seen.contains(&path) {
+            continue;
+        }
+        let Ok(text) = fs::read_to_string(&path) else {
+            seen.push(path);
+            continue;
+        };
+        pending.extend(sourced_files(&text, home).into_iter().filter(|p| p.is_file()));
+        found.extend(scan_text(&text).into_iter().map(|(line, install)| Found { path: path.clone(), line, install }));
+        seen.push(path);
+    }
+    found.sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+    found
+}
+
+/// The hooks in `text`, with their 1-based lines.
+pub fn scan_text(text: &str) -> Vec<(usize, Install)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim();
+        if let Some(rest) = line.strip_prefix(HEADER_PREFIX) {
+            let install = parse_static(&lines, i, rest);
+            // The script's own `shq hook init` calls (in shq-on) aren't
+            // installs; without an end, the rest of the file is the script's
+            let next = match install {
+                Install::Static { end: Some(end), .. } => end + 1,
+                _ => lines.len(),
+            };
+            found.push((i + 1, install));
+            i = next;
+            continue;
+        }
+        if !line.starts_with('#') && line.contains("shq hook init") {
+            found.push((i + 1, Install::Eval));
+        }
+        i += 1;
+    }
+    found
+}
+
+/// The copy of a script whose header is `lines[start]`, `rest` being the
+/// header after [`HEADER_PREFIX`].
+fn parse_static(lines: &[&str], start: usize, rest: &str) -> Install {
+    let shell = if rest.starts_with("zsh") { Shell::Zsh } else { Shell::Bash };
+    let (mode, prompt_indicator) = if rest.contains("(inactive mode)") {
+        (Mode::Inactive, true)
+    } else if rest.contains("(no prompt indicator)") {
+        (Mode::Active, false)
+    } else {
+        (Mode::Active, true)
+    };
+    let version = lines
+        .get(start + 1)
+        .and_then(|line| line.trim().strip_prefix(VERSION_PREFIX))
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(1);
+
+    let body = &lines[start + 1..];
+    let end = match body.iter().position(|line| line.trim() == END_MARKER) {
+        Some(i) => Some(i),
+        // Scripts from before the end marker ended with the aliases, or the
+        // startup message in inactive mode
+        None if mode == Mode::Inactive => body.iter().position(|line| line.contains("shq loaded (inactive)")),
+        None => body.iter().position(|line| line.trim() == "alias %S='shq stats'"),
+    };
+    Install::Static { version, shell, mode, prompt_indicator, start, end: end.map(|i| start + 1 + i) }
+}
+
+/// Files `text` sources (`source FILE`, `. FILE`), with `~` and `$HOME`
+/// expanded.
+fn sourced_files(text: &str, home: &Path) -> Vec<PathBuf> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let rest = line.strip_prefix("source ").or_else(|| line.strip_prefix(". "))?;
+            let arg = rest.split_whitespace().next()?.trim_matches(|c| c == '"' || c == '\'');
+            let arg = arg.replace("${HOME}", "~").replace("$HOME", "~");
+            Some(match arg.strip_prefix("~/") {
+                Some(rest) => home.join(rest),
+                None => PathBuf::from(arg),
+            })
+        })
+        .collect()
+}
+
+/// Replace the stale script `found` with the current one, after backing
+/// the file up. Returns the backup's path.
+pub fn upgrade(found: &Found) -> io::Result<PathBuf> {
+    let Install::Static { shell, mode, prompt_indicator, start, end, .. } = found.install else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a copy of a generated script"));
+    };
+    let Some(end) = end else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("couldn't find where the shq script starting at line {} ends", found.line),
+        ));
+    };
+
+    let text = fs::read_to_string(&found.path)?;
+    let lines: Vec<&str> = text.lines().collect();
+    if lines.get(start).is_none_or(|line| !line.trim().starts_with(HEADER_PREFIX)) || end >= lines.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "the file changed since it was scanned"));
+    }
+
+    let mut patched = String::with_capacity(text.len());
+    for line in &lines[..start] {
+        patched.push_str(line);
+        patched.push('\n');
+    }
+    patched.push_str(&hooks::generate(shell, mode, prompt_indicator));
+    for line in &lines[end + 1..] {
+        patched.push_str(line);
+        patched.push('\n');
+    }
+
+    let backup = backup_path(&found.path);
+    fs::copy(&found.path, &backup)?;
+    // Written in place, keeping the file's permissions and any symlink to it
+    fs::write(&found.path, patched)?;
+    Ok(backup)
+}
+
+/// A backup path for `path` that doesn't exist yet.
+fn backup_path(path: &Path) -> PathBuf {
+    let base = format!("{}{}", path.display(), BACKUP_SUFFIX);
+    let mut backup = PathBuf::from(&base);
+    let mut n = 1;
+    while backup.exists() {
+        backup = PathBuf::from(format!("{}.{}", base, n));
+        n += 1;
+    }
+    backup
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A script as shq generated it before versioning.
+    fn legacy_script() -> String {
+        hooks::generate(Shell::Zsh, Mode::Active, false)
+            .lines()
+            .filter(|line| !line.starts_with(VERSION_PREFIX) && !line.contains("SHQ_HOOK_VERSION") && *line != END_MARKER)
+            .map(|line| format!("{line}\n"))
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_text() {
+        let text = format!(
+            "export PATH=~/bin:$PATH\n# eval \"$(shq hook init)\"\neval \"$(shq hook init --quiet)\"\n{}alias ll='ls -l'\n",
+            hooks::generate(Shell::Bash, Mode::Inactive, true)
+        );
+        let found = scan_text(&text);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0], (3, Install::Eval));
+        match &found[1] {
+            (4, Install::Static { version, shell: Shell::Bash, mode: Mode::Inactive, end: Some(end), .. }) => {
+                assert_eq!(*version, HOOK_VERSION);
+                assert_eq!(text.lines().nth(end + 1), Some("alias ll='ls -l'"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_upgrade_legacy_script() {
+        let tmp = TempDir::new().unwrap();
+        let home = tmp.path();
+        let snippet = home.join(".shq.zsh");
+        fs::write(home.join(".zshrc"), "setopt extendedglob\nsource ~/.shq.zsh\n").unwrap();
+        fs::write(&snippet, format!("# before\n{}# after\n", legacy_script())).unwrap();
+
+        let found = scan(&rc_files(home, None), home);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, snippet);
+        assert!(found[0].is_stale());
+        assert!(matches!(found[0].install, Install::Static { version: 1, mode: Mode::Active, prompt_indicator: false, .. }));
+
+        let backup = upgrade(&found[0]).unwrap();
+        assert!(fs::read_to_string(&backup).unwrap().contains("alias %S='shq stats'\n# after\n"));
+        let upgraded = fs::read_to_string(&snippet).unwrap();
+        assert!(upgraded.starts_with("# before\n# shq shell integration for zsh (no prompt indicator)\n"));
+        assert!(upgraded.ends_with(&format!("{END_MARKER}\n# after\n")));
+        assert!(scan(&rc_files(home, None), home).iter().all(|f| !f.is_stale()));
+    }
+}
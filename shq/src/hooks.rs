@@ -5,6 +5,18 @@
 //! - Inactive: Only aliases, no automatic tracking
 //! - With/without prompt indicator
 
+pub use bird::version::HOOK_VERSION;
+use bird::version::HOOK_VERSION_VAR;
+
+/// First line of every generated script, followed by the shell name.
+pub const HEADER_PREFIX: &str = "# shq shell integration for ";
+
+/// Line carrying the script's [`HOOK_VERSION`].
+pub const VERSION_PREFIX: &str = "# shq-hook-version: ";
+
+/// Last line of every generated script.
+pub const END_MARKER: &str = "# end shq shell integration";
+
 /// Shell type for hook generation
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Shell {
@@ -33,6 +45,11 @@ pub fn generate(shell: Shell, mode: Mode, prompt_indicator: bool) -> String {
     if mode == Mode::Active {
         out.push_str(&ignore_patterns(shell));
         out.push_str(&should_ignore_fn(shell));
+        out.push_str(&paused_fn());
+        out.push_str(&project_sync_fn());
+        out.push_str(&save_fn());
+        out.push_str(&semantic_prompt_fn(shell));
+        out.push_str(&not_found_fn(shell));
         out.push_str(&hook_functions(shell));
         out.push_str(&shqr_function(shell));
         out.push_str(&on_off_functions(shell, prompt_indicator));
@@ -55,6 +72,7 @@ pub fn generate(shell: Shell, mode: Mode, prompt_indicator: bool) -> String {
         out.push_str(&inactive_message());
     }
 
+    out.push_str(&footer());
     out
 }
 
@@ -76,7 +94,8 @@ fn header(shell: Shell, mode: Mode, prompt_indicator: bool) -> String {
     };
 
     format!(
-        r#"# shq shell integration for {shell_name}{mode_desc}
+        r#"{HEADER_PREFIX}{shell_name}{mode_desc}
+{VERSION_PREFIX}{HOOK_VERSION}
 {setup_hint}#
 # Privacy escapes (command not recorded):
 #   - Start command with a space: " ls -la"
@@ -84,8 +103,13 @@ fn header(shell: Shell, mode: Mode, prompt_indicator: bool) -> String {
 #     history itself, e.g. export HISTCONTROL=ignorespace)
 #   - Start command with backslash: "\ls -la"
 #
-# Temporary disable: export SHQ_DISABLED=1
+# Temporary disable: export SHQ_DISABLED=1 (this shell)
+#   or: shq off [--for 1h] / shq on (all shells)
 # Exclude patterns: export SHQ_EXCLUDE="*password*:*secret*"
+# Print each command's alias (for `shq o brave-otter`): export SHQ_SHOW_ALIAS=1
+# Emit OSC 133 semantic prompt markers: export SHQ_OSC133=1 (before loading)
+# Record commands not found (for `shq typos`): export SHQ_CAPTURE_NOT_FOUND=1
+#   (before loading)
 # (sensitive commands are also excluded/redacted server-side by shq save;
 #  see privacy.exclude_patterns in config.toml)
 
@@ -93,6 +117,11 @@ fn header(shell: Shell, mode: Mode, prompt_indicator: bool) -> String {
     )
 }
 
+fn footer() -> String {
+    // Exported so `shq hook status` can tell what this shell loaded
+    format!("\nexport {HOOK_VERSION_VAR}={HOOK_VERSION}\n{END_MARKER}\n")
+}
+
 fn session_id(shell: Shell) -> String {
     match shell {
         Shell::Bash => "__shq_session_id=\"bash-$$\"\n\n".to_string(),
@@ -136,6 +165,138 @@ __shq_should_ignore() {{
     )
 }
 
+fn paused_fn() -> String {
+    r#"# Check if capture is paused (`shq off`). The state file holds
+# paused_until=<epoch seconds>, where 0 means until `shq on`.
+__shq_state_file="${BIRD_ROOT:-$HOME/.local/share/bird}/state"
+__shq_paused() {
+    [[ -f "$__shq_state_file" ]] || return 1
+    local line expires now
+    read -r line < "$__shq_state_file"
+    expires="${line#paused_until=}"
+    [[ "$expires" == 0 ]] && return 0
+    now="${EPOCHREALTIME%.*}"
+    [[ -z "$now" ]] && now=$(date +%s)
+    (( now < expires ))
+}
+
+"#
+    .to_string()
+}
+
+fn project_sync_fn() -> String {
+    r#"# Apply per-project settings (.bird/config.toml) when the directory
+# changes: `shq project env` exports SHQ_PROJECT* for the project containing
+# $PWD and unsets them after leaving it.
+__shq_project_sync() {
+    [[ "$PWD" == "$__shq_project_pwd" ]] && return
+    __shq_project_pwd="$PWD"
+    eval "$(shq project env 2>/dev/null)"
+}
+
+"#
+    .to_string()
+}
+
+fn save_fn() -> String {
+    r#"# Hand a finished command to shq: the buffer if it's enabled, otherwise
+# the spool - through shq-capture, which doesn't load DuckDB, if installed.
+__shq_save() {
+    if [[ -n "$__shq_buffer_enabled" ]]; then
+        shq save --to-buffer "$@"
+    elif command -v shq-capture >/dev/null 2>&1; then
+        shq-capture "$@"
+    else
+        shq save --async "$@"
+    fi
+}
+
+# With SHQ_SHOW_ALIAS set, pick the id the command is saved under up front
+# and print its alias (e.g. "shq: brave-otter"), usable as `shq o brave-otter`.
+__shq_show_alias() {
+    __shq_next_id=""
+    [[ -n "$SHQ_SHOW_ALIAS" ]] || return
+    local new
+    new=$(shq alias --new 2>/dev/null) || return
+    __shq_next_id="${new%% *}"
+    printf 'shq: %s\n' "${new#* }" >&2
+}
+
+"#
+    .to_string()
+}
+
+fn semantic_prompt_fn(shell: Shell) -> String {
+    // Zero-width in the prompt, so line editing isn't thrown off
+    let end_of_prompt = match shell {
+        Shell::Zsh => "%{$(__shq_mark B)%}",
+        Shell::Bash => "\\[$(__shq_mark B)\\]",
+    };
+    format!(
+        r#"# OSC 133 semantic prompt markers (SHQ_OSC133=1), so terminals with shell
+# integration can delimit commands and `shq segments` can split a captured
+# shell session into its commands. aid tells nested shells apart.
+__shq_mark() {{
+    [[ "$SHQ_OSC133" == 1 ]] || return 0
+    printf '\e]133;%s;aid=%s\a' "$1" "$$"
+}}
+if [[ "$SHQ_OSC133" == 1 && "$PS1" != *"133;B"* ]]; then
+    PS1="${{PS1}}{end_of_prompt}"
+fi
+
+"#
+    )
+}
+
+fn not_found_fn(shell: Shell) -> String {
+    match shell {
+        Shell::Zsh => r#"# Record commands that aren't found (SHQ_CAPTURE_NOT_FOUND=1) for `shq typos`,
+# then defer to any handler already defined.
+if [[ "$SHQ_CAPTURE_NOT_FOUND" == 1 ]]; then
+    if (( $+functions[command_not_found_handler] )) && [[ "$functions[command_not_found_handler]" != *"shq not-found"* ]]; then
+        functions[__shq_orig_not_found]=$functions[command_not_found_handler]
+    fi
+    command_not_found_handler() {
+        if [[ -z "$SHQ_DISABLED" ]] && ! __shq_should_ignore "$*"; then
+            shq not-found "$1" </dev/null >/dev/null \
+                2>> "${BIRD_ROOT:-$HOME/.local/share/bird}/errors.log" &!
+        fi
+        if (( $+functions[__shq_orig_not_found] )); then
+            __shq_orig_not_found "$@"
+            return
+        fi
+        print -u2 "zsh: command not found: $1"
+        return 127
+    }
+fi
+
+"#
+        .to_string(),
+        Shell::Bash => r#"# Record commands that aren't found (SHQ_CAPTURE_NOT_FOUND=1) for `shq typos`,
+# then defer to any handler already defined.
+if [[ "$SHQ_CAPTURE_NOT_FOUND" == 1 ]]; then
+    if declare -F command_not_found_handle >/dev/null && [[ "$(declare -f command_not_found_handle)" != *"shq not-found"* ]]; then
+        eval "__shq_orig_not_found() $(declare -f command_not_found_handle | tail -n +2)"
+    fi
+    command_not_found_handle() {
+        if [[ -z "$SHQ_DISABLED" ]] && ! __shq_should_ignore "$*"; then
+            ( shq not-found "$1" </dev/null >/dev/null \
+                2>> "${BIRD_ROOT:-$HOME/.local/share/bird}/errors.log" & )
+        fi
+        if declare -F __shq_orig_not_found >/dev/null; then
+            __shq_orig_not_found "$@"
+            return
+        fi
+        printf 'bash: %s: command not found\n' "$1" >&2
+        return 127
+    }
+fi
+
+"#
+        .to_string(),
+    }
+}
+
 fn hook_functions(shell: Shell) -> String {
     match shell {
         Shell::Zsh => zsh_hook_functions(),
@@ -152,6 +313,7 @@ fi
 
 # Capture command before execution
 __shq_preexec() {
+    __shq_mark C
     __shq_last_cmd="$1"
     __shq_start_time=$EPOCHREALTIME
 }
@@ -159,11 +321,16 @@ __shq_preexec() {
 # Capture result after execution (metadata only - no output capture)
 __shq_precmd() {
     local exit_code=$?
+    __shq_mark "D;$exit_code"
+    __shq_mark A
     local cmd="$__shq_last_cmd"
     __shq_last_cmd=""
+    __shq_project_sync
 
-    # Skip if disabled, empty, or privacy escape
+    # Skip if disabled, paused, empty, or privacy escape
     [[ -n "$SHQ_DISABLED" ]] && return
+    __shq_paused && return
+    [[ "$SHQ_PROJECT_CAPTURE" == 0 ]] && return
     [[ -z "$cmd" ]] && return
     [[ "$cmd" =~ ^[[:space:]] ]] && return
     [[ "$cmd" =~ ^\\ ]] && return
@@ -177,16 +344,14 @@ __shq_precmd() {
     fi
     __shq_start_time=""
 
-    # Build save command - use buffer if enabled
-    local buffer_flag=""
-    [[ -n "$__shq_buffer_enabled" ]] && buffer_flag="--to-buffer"
-
     # Save to BIRD (async, non-blocking)
+    __shq_show_alias
     (
-        shq save -c "$cmd" -x "$exit_code" -d "$duration" \
+        [[ -n "$__shq_next_id" ]] && export BIRD_INVOCATION_UUID="$__shq_next_id"
+        __shq_save -c "$cmd" -x "$exit_code" -d "$duration" \
             --session-id "$__shq_session_id" \
             --invoker-pid $$ --invoker zsh \
-            --compact -q $buffer_flag </dev/null \
+            --compact -q </dev/null \
             2>> "${BIRD_ROOT:-$HOME/.local/share/bird}/errors.log"
     ) &!
 }
@@ -217,7 +382,7 @@ __shq_now_ms() {
 __shq_ps0_hook() {
     __shq_start_ms=$(__shq_now_ms)
 }
-PS0='${__shq_cmd:+$(__shq_ps0_hook)}'
+PS0='${__shq_cmd:+$(__shq_ps0_hook)}${SHQ_OSC133:+$(__shq_mark C)}'
 
 # Track the last-saved history number so empty-Enter (and commands kept out
 # of history, e.g. via HISTCONTROL=ignorespace) don't re-save the previous
@@ -227,7 +392,10 @@ __shq_last_histnum=$(HISTTIMEFORMAT='' history 1 | sed 's/^[[:space:]]*\([0-9]\{
 # PROMPT_COMMAND hook: fires after command completes
 __shq_prompt_command() {
     local exit_code=$?
+    __shq_mark "D;$exit_code"
+    __shq_mark A
     local histline histnum cmd
+    __shq_project_sync
     histline=$(HISTTIMEFORMAT='' history 1)
 
     # Parse "  <num><*| > <cmd>" precisely, keeping the command's own leading
@@ -245,8 +413,10 @@ __shq_prompt_command() {
     [[ "$histnum" == "$__shq_last_histnum" ]] && { __shq_cmd=""; return; }
     __shq_last_histnum="$histnum"
 
-    # Skip if disabled, empty, or privacy escape
+    # Skip if disabled, paused, empty, or privacy escape
     [[ -n "$SHQ_DISABLED" ]] && { __shq_cmd=""; return; }
+    __shq_paused && { __shq_cmd=""; return; }
+    [[ "$SHQ_PROJECT_CAPTURE" == 0 ]] && { __shq_cmd=""; return; }
     [[ -z "$cmd" ]] && return
     [[ "$cmd" =~ ^[[:space:]] ]] && { __shq_cmd=""; return; }
     [[ "$cmd" =~ ^\\ ]] && { __shq_cmd=""; return; }
@@ -263,16 +433,14 @@ __shq_prompt_command() {
     __shq_cmd=1
     __shq_start_ms=""
 
-    # Build save command - use buffer if enabled
-    local buffer_flag=""
-    [[ -n "$__shq_buffer_enabled" ]] && buffer_flag="--to-buffer"
-
     # Save to BIRD (background, non-blocking)
+    __shq_show_alias
     (
-        shq save -c "$cmd" -x "$exit_code" -d "$duration" \
+        [[ -n "$__shq_next_id" ]] && export BIRD_INVOCATION_UUID="$__shq_next_id"
+        __shq_save -c "$cmd" -x "$exit_code" -d "$duration" \
             --session-id "$__shq_session_id" \
             --invoker-pid $$ --invoker bash \
-            --compact -q $buffer_flag </dev/null \
+            --compact -q </dev/null \
             2>> "${BIRD_ROOT:-$HOME/.local/share/bird}/errors.log"
     ) & disown
 }
@@ -294,7 +462,7 @@ fn shqr_function(shell: Shell) -> String {
     format!(
         r#"# Run command with full output capture
 shqr() {{
-    if [[ -n "$SHQ_DISABLED" ]]; then
+    if [[ -n "$SHQ_DISABLED" || "$SHQ_PROJECT_CAPTURE" == 0 ]] || __shq_paused; then
         "$@"
         return $?
     fi
@@ -314,17 +482,13 @@ shqr() {{
     local end_ms=$(__shq_now_ms 2>/dev/null || echo 0)
     local duration=$(( end_ms - start_ms ))
 
-    # Use buffer if enabled
-    local buffer_flag=""
-    [[ -n "$__shq_buffer_enabled" ]] && buffer_flag="--to-buffer"
-
     # Save with captured output (background)
     (
-        shq save -c "$cmd" -x "$exit_code" -d "$duration" \
+        __shq_save -c "$cmd" -x "$exit_code" -d "$duration" \
             --stdout "$stdout_file" --stderr "$stderr_file" \
             --session-id "$__shq_session_id" \
             --invoker-pid $$ --invoker {invoker} \
-            --compact -q $buffer_flag \
+            --compact -q \
             2>> "${{BIRD_ROOT:-$HOME/.local/share/bird}}/errors.log"
         rm -f "$stdout_file" "$stderr_file"
     {bg_syntax}
@@ -353,7 +517,8 @@ fn on_off_functions(shell: Shell, prompt_indicator: bool) -> String {
                 r#"shq-off() {{
     add-zsh-hook -d preexec __shq_preexec
     add-zsh-hook -d precmd __shq_precmd
-    unset __shq_last_cmd __shq_start_time __shq_session_id
+    add-zsh-hook -d zshexit __shq_zshexit
+    unset __shq_last_cmd __shq_start_time __shq_session_id __shq_project_pwd
     unalias {unalias_list} 2>/dev/null
 {restore_ps1}    [[ -z "$__shq_quiet" ]] && echo "shq disabled (use shq-on to re-enable)"
     unset __shq_quiet
@@ -382,7 +547,8 @@ shq-on() {{
     PROMPT_COMMAND="${{PROMPT_COMMAND//__shq_prompt_command;/}}"
     PROMPT_COMMAND="${{PROMPT_COMMAND//__shq_prompt_command/}}"
     PROMPT_COMMAND="${{PROMPT_COMMAND#; }}"; PROMPT_COMMAND="${{PROMPT_COMMAND#;}}"
-    unset __shq_cmd __shq_start_ms __shq_session_id PS0
+    [[ "$(trap -p EXIT)" == *__shq_exit* ]] && trap - EXIT
+    unset __shq_cmd __shq_start_ms __shq_session_id __shq_project_pwd PS0
     unalias {unalias_list} 2>/dev/null
 {restore_ps1}    [[ -z "$__shq_quiet" ]] && echo "shq disabled (use shq-on to re-enable)"
     unset __shq_quiet
@@ -427,7 +593,7 @@ shq-on() {{
     PROMPT_COMMAND="${{PROMPT_COMMAND//__shq_prompt_command;/}}"
     PROMPT_COMMAND="${{PROMPT_COMMAND//__shq_prompt_command/}}"
     PROMPT_COMMAND="${{PROMPT_COMMAND#; }}"; PROMPT_COMMAND="${{PROMPT_COMMAND#;}}"
-    unset __shq_cmd __shq_start_ms __shq_session_id PS0
+    unset __shq_cmd __shq_start_ms __shq_session_id __shq_project_pwd PS0
     unalias {unalias_list} 2>/dev/null
     [[ -n "$__shq_orig_ps1" ]] && PS1="$__shq_orig_ps1"
     unset __shq_orig_ps1 SHQ_INDICATOR
@@ -448,20 +614,42 @@ shq-on() {{
 
 fn register_hooks(shell: Shell) -> String {
     match shell {
-        Shell::Zsh => r#"# Register hooks
+        Shell::Zsh => r#"# Record session end when the shell exits
+__shq_zshexit() {
+    [[ -n "$SHQ_DISABLED" ]] && return
+    __shq_paused && return
+    [[ -z "$__shq_session_id" ]] && return
+    shq session close --session-id "$__shq_session_id" </dev/null \
+        2>> "${BIRD_ROOT:-$HOME/.local/share/bird}/errors.log" &!
+}
+
+# Register hooks
 autoload -Uz add-zsh-hook
 add-zsh-hook preexec __shq_preexec
 add-zsh-hook precmd __shq_precmd
+add-zsh-hook zshexit __shq_zshexit
 
 "#
         .to_string(),
-        Shell::Bash => r#"# Register PROMPT_COMMAND
+        Shell::Bash => r#"# Record session end when the shell exits
+__shq_exit() {
+    [[ -n "$SHQ_DISABLED" ]] && return
+    __shq_paused && return
+    [[ -z "$__shq_session_id" ]] && return
+    ( shq session close --session-id "$__shq_session_id" </dev/null \
+        2>> "${BIRD_ROOT:-$HOME/.local/share/bird}/errors.log" & )
+}
+
+# Register PROMPT_COMMAND
 if [[ -z "$PROMPT_COMMAND" ]]; then
     PROMPT_COMMAND="__shq_prompt_command"
 else
     PROMPT_COMMAND="__shq_prompt_command; $PROMPT_COMMAND"
 fi
 
+# Register EXIT trap (leave any existing user trap alone)
+[[ -z "$(trap -p EXIT)" ]] && trap '__shq_exit' EXIT
+
 "#
         .to_string(),
     }
@@ -547,6 +735,86 @@ mod tests {
         assert!(hook.contains("shq-on"));
     }
 
+    #[test]
+    fn test_generate_syncs_project_settings() {
+        for shell in [Shell::Zsh, Shell::Bash] {
+            let hook = generate(shell, Mode::Active, true);
+            assert!(hook.contains("eval \"$(shq project env 2>/dev/null)\""));
+            assert!(hook.contains("[[ \"$SHQ_PROJECT_CAPTURE\" == 0 ]]"));
+        }
+        let hook = generate(Shell::Bash, Mode::Inactive, true);
+        assert!(!hook.contains("shq project env"));
+    }
+
+    #[test]
+    fn test_generate_checks_pause_state() {
+        for shell in [Shell::Zsh, Shell::Bash] {
+            let hook = generate(shell, Mode::Active, true);
+            assert!(hook.contains("__shq_paused() {"));
+            assert!(hook.contains("__shq_paused && "));
+            assert!(hook.contains("|| __shq_paused; then"));
+        }
+    }
+
+    #[test]
+    fn test_generate_records_session_close() {
+        let zsh = generate(Shell::Zsh, Mode::Active, true);
+        assert!(zsh.contains("add-zsh-hook zshexit __shq_zshexit"));
+        assert!(zsh.contains("shq session close"));
+
+        let bash = generate(Shell::Bash, Mode::Active, true);
+        assert!(bash.contains("trap '__shq_exit' EXIT"));
+        assert!(bash.contains("shq session close"));
+    }
+
+    #[test]
+    fn test_generate_spools_saves() {
+        for shell in [Shell::Zsh, Shell::Bash] {
+            let hook = generate(shell, Mode::Active, true);
+            assert!(hook.contains("shq save --async \"$@\""));
+            assert!(hook.contains("shq save --to-buffer \"$@\""));
+            assert!(hook.contains("shq-capture \"$@\""));
+            assert!(!hook.contains("shq save -c"));
+        }
+    }
+
+    #[test]
+    fn test_generate_shows_aliases() {
+        for shell in [Shell::Zsh, Shell::Bash] {
+            let hook = generate(shell, Mode::Active, true);
+            assert!(hook.contains("[[ -n \"$SHQ_SHOW_ALIAS\" ]] || return"));
+            assert!(hook.contains("shq alias --new"));
+            assert!(hook.contains("export BIRD_INVOCATION_UUID=\"$__shq_next_id\""));
+        }
+    }
+
+    #[test]
+    fn test_generate_semantic_prompt_markers() {
+        for shell in [Shell::Zsh, Shell::Bash] {
+            let hook = generate(shell, Mode::Active, true);
+            assert!(hook.contains(r#"printf '\e]133;%s;aid=%s\a' "$1" "$$""#));
+            assert!(hook.contains("__shq_mark \"D;$exit_code\"\n    __shq_mark A"));
+            assert!(hook.contains("__shq_mark C"));
+        }
+        assert!(generate(Shell::Zsh, Mode::Active, true).contains(r#"PS1="${PS1}%{$(__shq_mark B)%}""#));
+        assert!(generate(Shell::Bash, Mode::Active, true).contains(r#"PS1="${PS1}\[$(__shq_mark B)\]""#));
+        assert!(!generate(Shell::Bash, Mode::Inactive, true).contains("133;"));
+    }
+
+    #[test]
+    fn test_generate_records_commands_not_found() {
+        let zsh = generate(Shell::Zsh, Mode::Active, true);
+        assert!(zsh.contains("command_not_found_handler() {"));
+        let bash = generate(Shell::Bash, Mode::Active, true);
+        assert!(bash.contains("command_not_found_handle() {"));
+        for hook in [zsh, bash] {
+            assert!(hook.contains(r#"if [[ "$SHQ_CAPTURE_NOT_FOUND" == 1 ]]; then"#));
+            assert!(hook.contains(r#"shq not-found "$1""#));
+            assert!(hook.contains(r#"__shq_orig_not_found "$@""#));
+        }
+        assert!(!generate(Shell::Bash, Mode::Inactive, true).contains("not-found"));
+    }
+
     #[test]
     fn test_generate_zsh_active() {
         let hook = generate(Shell::Zsh, Mode::Active, true);
@@ -564,6 +832,18 @@ mod tests {
         // But may reference it in cleanup code
     }
 
+    #[test]
+    fn test_generate_is_versioned() {
+        for (shell, mode) in [(Shell::Zsh, Mode::Active), (Shell::Bash, Mode::Inactive)] {
+            let hook = generate(shell, mode, true);
+            let lines: Vec<&str> = hook.lines().collect();
+            assert!(lines[0].starts_with(HEADER_PREFIX));
+            assert_eq!(lines[1], format!("{VERSION_PREFIX}{HOOK_VERSION}"));
+            assert_eq!(lines.last(), Some(&END_MARKER));
+            assert!(hook.contains(&format!("export SHQ_HOOK_VERSION={HOOK_VERSION}")));
+        }
+    }
+
     #[test]
     fn test_aliases_present() {
         let hook = generate(Shell::Bash, Mode::Inactive, true);
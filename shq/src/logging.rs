@@ -0,0 +1,208 @@
+//! Diagnostic logging for shq and the BIRD store.
+//!
+//! bird and shq report warnings and diagnostics through `tracing`. This
+//! module installs a small subscriber that prints them to stderr (or a log
+//! file with `--log-file`). Warnings are shown by default; `-v` adds info,
+//! `-vv` debug and `-vvv` trace. `SHQ_LOG` takes the same levels, globally
+//! or per module: `SHQ_LOG=debug` or `SHQ_LOG=warn,magic_bird::store=trace`.
+
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+/// Environment variable with log level directives.
+pub const LOG_ENV_VAR: &str = "SHQ_LOG";
+
+/// Level filter: a default plus per-target overrides.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    default: LevelFilter,
+    /// `(target prefix, level)`, longest prefix first.
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    /// Filter for `-v` given `verbosity` times, refined by `SHQ_LOG`
+    /// directives (if any). The more verbose default wins.
+    pub fn new(verbosity: u8, directives: Option<&str>) -> Result<Self, String> {
+        let mut filter = Filter {
+            default: match verbosity {
+                0 => LevelFilter::WARN,
+                1 => LevelFilter::INFO,
+                2 => LevelFilter::DEBUG,
+                _ => LevelFilter::TRACE,
+            },
+            targets: Vec::new(),
+        };
+
+        for directive in directives.unwrap_or("").split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => filter.targets.push((target.to_string(), parse_level(level)?)),
+                None => filter.default = filter.default.max(parse_level(directive)?),
+            }
+        }
+        filter.targets.sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        Ok(filter)
+    }
+
+    /// Most verbose level any target is enabled at.
+    fn max_level(&self) -> LevelFilter {
+        self.targets.iter().map(|(_, level)| *level).fold(self.default, LevelFilter::max)
+    }
+
+    fn enabled(&self, target: &str, level: &Level) -> bool {
+        let filter = self
+            .targets
+            .iter()
+            .find(|(prefix, _)| {
+                target == prefix || target.strip_prefix(prefix.as_str()).is_some_and(|rest| rest.starts_with("::"))
+            })
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default);
+        *level <= filter
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid log level '{}' (expected off, error, warn, info, debug or trace)", level))
+}
+
+/// Writes formatted events to stderr or a log file.
+struct Logger {
+    filter: Filter,
+    /// Log file; stderr when unset. Lines in the file are timestamped.
+    file: Option<Mutex<File>>,
+    next_span: AtomicU64,
+}
+
+impl Subscriber for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.filter.enabled(metadata.target(), metadata.level())
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.filter.max_level())
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(self.next_span.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let line = format_event(event);
+        match &self.file {
+            Some(file) => {
+                let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+                let _ = writeln!(file, "{} {}", chrono::Local::now().to_rfc3339(), line);
+            }
+            None => {
+                let _ = writeln!(io::stderr().lock(), "{}", line);
+            }
+        }
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+/// Collects an event's message and `key=value` fields.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    fields: String,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// `LEVEL target: message key=value ...`
+fn format_event(event: &Event<'_>) -> String {
+    let mut fields = Fields::default();
+    event.record(&mut fields);
+    let metadata = event.metadata();
+    format!("{} {}: {}{}", metadata.level(), metadata.target(), fields.message, fields.fields)
+}
+
+/// Install the global subscriber for this process.
+pub fn init(verbosity: u8, log_file: Option<&Path>) -> Result<(), String> {
+    let directives = std::env::var(LOG_ENV_VAR).ok();
+    let filter = Filter::new(verbosity, directives.as_deref()).map_err(|e| format!("{}: {}", LOG_ENV_VAR, e))?;
+    let file = log_file
+        .map(|path| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("cannot open log file {}: {}", path.display(), e))
+        })
+        .transpose()?;
+
+    let logger = Logger {
+        filter,
+        file: file.map(Mutex::new),
+        next_span: AtomicU64::new(1),
+    };
+    tracing::subscriber::set_global_default(logger).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_verbosity() {
+        let filter = Filter::new(0, None).unwrap();
+        assert!(filter.enabled("magic_bird::store", &Level::WARN));
+        assert!(!filter.enabled("magic_bird::store", &Level::INFO));
+
+        let filter = Filter::new(2, None).unwrap();
+        assert!(filter.enabled("magic_bird::store", &Level::DEBUG));
+        assert!(!filter.enabled("magic_bird::store", &Level::TRACE));
+    }
+
+    #[test]
+    fn test_filter_directives() {
+        let filter = Filter::new(0, Some("info, magic_bird::store=trace, magic_bird::store::remote=off")).unwrap();
+        assert!(filter.enabled("shq", &Level::INFO));
+        assert!(filter.enabled("magic_bird::store::events", &Level::TRACE));
+        assert!(!filter.enabled("magic_bird::store::remote", &Level::ERROR));
+        // Prefixes match whole path segments only
+        assert!(!filter.enabled("magic_bird::storefront", &Level::DEBUG));
+        assert_eq!(filter.max_level(), LevelFilter::TRACE);
+
+        // A quieter SHQ_LOG doesn't override -v
+        assert!(Filter::new(1, Some("error")).unwrap().enabled("shq", &Level::INFO));
+        assert!(Filter::new(0, Some("loud")).is_err());
+    }
+}
@@ -1,10 +1,25 @@
 //! shq: Shell Query - CLI for capturing and querying shell command history.
 
-use clap::{Parser, Subcommand};
+use bird::profile::Phase;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 
+mod bench;
+mod cancel;
 mod commands;
+mod digest;
+mod expect;
+mod git_hooks;
+mod hook_install;
 mod hooks;
+mod logging;
+mod pager;
+mod progress;
+mod report;
+mod sinks;
+mod table;
+mod theme;
 mod tutorial;
+mod wrap;
 
 #[derive(Parser)]
 #[command(name = "shq")]
@@ -15,6 +30,18 @@ struct Cli {
     #[arg(short = 'X', long = "force-capture", global = true)]
     force_capture: bool,
 
+    /// Show more diagnostics (-v info, -vv debug, -vvv trace); see also SHQ_LOG
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Write diagnostics to this file instead of stderr
+    #[arg(long = "log-file", value_name = "PATH", global = true)]
+    log_file: Option<std::path::PathBuf>,
+
+    /// Don't page long listings (`i`, `events`, `sql`) through $PAGER
+    #[arg(long = "no-pager", global = true)]
+    no_pager: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -59,15 +86,139 @@ enum Commands {
         #[arg(short = 'C', long = "compact")]
         compact: bool,
 
+        /// Record a dimension as KEY=VALUE (repeatable; query with %dim:KEY=VALUE)
+        #[arg(long = "dim", value_name = "KEY=VALUE")]
+        dims: Vec<String>,
+
         /// Disable PTY (pseudo-terminal) - captures stdout/stderr separately but loses colors/interactivity
         #[arg(long = "no-pty")]
         no_pty: bool,
 
+        /// Snapshot a file (or glob) the command produces (repeatable; see `shq artifacts`)
+        #[arg(long = "attach", value_name = "PATH")]
+        attach: Vec<String>,
+
+        /// Assert on the captured run, exiting 1 unless all hold (repeatable):
+        /// exit=N, exit!=N, duration<30s, no-events, no-events:severity=error
+        #[arg(long = "expect", value_name = "ASSERTION")]
+        expect: Vec<String>,
+
+        /// Show a desktop notification when an --expect fails
+        #[arg(long = "notify", requires = "expect")]
+        notify: bool,
+
+        /// Also append the output to FILE, keeping the PTY (repeatable; use instead of `| tee`)
+        #[arg(long = "tee", value_name = "FILE")]
+        tee: Vec<std::path::PathBuf>,
+
+        /// Also write the output to an already-open file descriptor, as in `3>build.log` (repeatable)
+        #[arg(long = "tee-fd", value_name = "FD")]
+        tee_fd: Vec<i32>,
+
+        /// Also stream the output to a listening Unix socket (repeatable)
+        #[arg(long = "tee-socket", value_name = "PATH")]
+        tee_socket: Vec<std::path::PathBuf>,
+
         /// The command to run (alternative to -c)
         #[arg(trailing_var_arg = true)]
         cmd: Vec<String>,
     },
 
+    /// Rerun a command whenever files change, capturing each run
+    ///
+    /// The runs of one watch share a `watch` dimension, so
+    /// `shq i %dim:watch=ID` lists them and `shq events --latest-of-watch`
+    /// shows the events of the latest.
+    #[command(visible_alias = "w")]
+    Watch {
+        /// Shell command string (passed to $SHELL -c)
+        command: String,
+
+        /// Files or directories to watch (repeatable; default: the current directory)
+        #[arg(short = 'p', long = "paths", value_name = "PATH", num_args = 1..)]
+        paths: Vec<std::path::PathBuf>,
+
+        /// How often to check for changes, in milliseconds
+        #[arg(long = "interval", default_value = "500")]
+        interval_ms: u64,
+
+        /// Clear the screen before each run
+        #[arg(long = "clear")]
+        clear: bool,
+
+        /// Record a dimension as KEY=VALUE on every run (repeatable)
+        #[arg(long = "dim", value_name = "KEY=VALUE")]
+        dims: Vec<String>,
+    },
+
+    /// Time a command over repeated runs, capturing each as a benchmark group
+    ///
+    /// Reports mean, standard deviation, min and max of the captured
+    /// durations, compared with the command's previous benchmark group.
+    Bench {
+        /// Shell command string (passed to $SHELL -c)
+        command: String,
+
+        /// Number of measured runs
+        #[arg(short = 'n', long = "runs", default_value = "10")]
+        runs: usize,
+
+        /// Runs to do first, unmeasured and uncaptured (e.g. to warm caches)
+        #[arg(short = 'w', long = "warmup", default_value = "0")]
+        warmup: usize,
+
+        /// Name of the benchmark group (default: the start time)
+        #[arg(long = "name")]
+        name: Option<String>,
+
+        /// Group to compare with (default: the command's previous group)
+        #[arg(long = "compare", value_name = "GROUP")]
+        compare: Option<String>,
+
+        /// Keep going when a run exits non-zero
+        #[arg(short = 'i', long = "ignore-failure")]
+        ignore_failure: bool,
+
+        /// Output format: table (default), json
+        #[arg(short = 'f', long = "format", default_value = "table")]
+        format: String,
+    },
+
+    /// Compare two commands over interleaved, captured runs
+    ///
+    /// Runs A and B alternately, then reports each one's timings and
+    /// successes and whether B's difference from A is significant. The runs
+    /// are stored, so `shq ab --show GROUP` reports on them again later.
+    Ab {
+        /// Variant A (passed to $SHELL -c)
+        #[arg(long = "a", value_name = "COMMAND", required_unless_present = "show")]
+        a: Option<String>,
+
+        /// Variant B (passed to $SHELL -c)
+        #[arg(long = "b", value_name = "COMMAND", required_unless_present = "show")]
+        b: Option<String>,
+
+        /// Number of measured runs of each variant
+        #[arg(short = 'n', long = "runs", default_value = "5")]
+        runs: usize,
+
+        /// Runs of each to do first, unmeasured and uncaptured
+        #[arg(short = 'w', long = "warmup", default_value = "0")]
+        warmup: usize,
+
+        /// Name of the A/B group (default: the start time)
+        #[arg(long = "name", conflicts_with = "show")]
+        name: Option<String>,
+
+        /// Report on a stored A/B group instead of running one
+        #[arg(long = "show", value_name = "GROUP", conflicts_with_all = ["a", "b"])]
+        show: Option<String>,
+
+        /// Output format: table (default), json
+        #[arg(short = 'f', long = "format", default_value = "table")]
+        format: String,
+    },
+
     /// Save output from stdin or file to BIRD
     #[command(visible_alias = "S")]
     Save {
@@ -126,6 +277,10 @@ enum Commands {
         #[arg(short = 't', long = "tag")]
         tag: Option<String>,
 
+        /// Record a dimension as KEY=VALUE (repeatable; query with %dim:KEY=VALUE)
+        #[arg(long = "dim", value_name = "KEY=VALUE")]
+        dims: Vec<String>,
+
         /// Suppress informational output
         #[arg(short = 'q', long = "quiet")]
         quiet: bool,
@@ -133,6 +288,19 @@ enum Commands {
         /// Save to buffer instead of permanent storage (for retrospective saving)
         #[arg(long = "to-buffer")]
         to_buffer: bool,
+
+        /// Spool the record and return at once; a background flusher writes it
+        #[arg(long = "async", conflicts_with = "to_buffer")]
+        async_save: bool,
+
+        /// Ingest a `script` typescript or `tmux pipe-pane` log, one invocation
+        /// per command its OSC 133 markers delimit (or one for the whole log)
+        #[arg(long = "from-typescript", value_name = "FILE", conflicts_with_all = ["file", "stdout_file", "stderr_file", "to_buffer", "async_save"])]
+        from_typescript: Option<std::path::PathBuf>,
+
+        /// Timing file for --from-typescript (`script -t`; default: FILE.timing if present)
+        #[arg(long = "timing", value_name = "FILE", requires = "from_typescript")]
+        timing: Option<std::path::PathBuf>,
     },
 
     /// Show captured output from invocation(s)
@@ -186,16 +354,47 @@ enum Commands {
         /// Follow output in real-time (like tail -f) for running commands
         #[arg(short = 'f', long = "follow")]
         follow: bool,
+
+        /// Only part N of each stream, for commands captured in several parts
+        /// (from 0; default: all parts in order)
+        #[arg(long = "part", value_name = "N")]
+        part: Option<i32>,
+    },
+
+    /// List or extract files attached to an invocation (`shq run --attach`)
+    Artifacts {
+        /// Query selector (e.g., ~1, %/make/~1)
+        #[arg(default_value = "~1")]
+        query: String,
+
+        /// Write the artifacts under DIR, at their attached paths
+        #[arg(long = "extract", value_name = "DIR")]
+        extract: Option<String>,
+    },
+
+    /// Split a captured shell session into its commands, by their OSC 133 markers
+    Segments {
+        /// Query selector (e.g., ~1, %/ssh/~1)
+        #[arg(default_value = "~1")]
+        query: String,
+
+        /// Show the output of segment N instead of listing them
+        #[arg(short = 'n', long = "show", value_name = "N")]
+        show: Option<usize>,
+
+        /// Strip ANSI escape codes from the shown output
+        #[arg(long = "strip", requires = "show")]
+        strip: bool,
     },
 
     /// List invocation history
     #[command(visible_aliases = ["i", "history", "list"])]
     Invocations {
-        /// Query selector (e.g., ~20:, shell:~10:, %failed~5:)
-        #[arg(default_value = "~20:")]
-        query: String,
+        /// Query selector (e.g., ~20:, shell:~10:, %failed~5:) [default: ~20:]
+        query: Option<String>,
 
-        /// Output format: compact (default), table, json
+        /// Output format: compact (default), table, json, or
+        /// template='{id} {cmd}' for one line per invocation
         #[arg(short = 'f', long = "format", default_value = "compact")]
         format: String,
 
@@ -203,9 +402,37 @@ enum Commands {
         #[arg(short = 'd', long = "details")]
         details: bool,
 
+        /// Table columns, in order: id, time, exit, duration, cwd, cmd
+        #[arg(long = "columns", value_name = "COLS", conflicts_with_all = ["by", "dedup"])]
+        columns: Option<String>,
+
         /// Show last N invocations (equivalent to ~N: in query)
         #[arg(short = 'n', long = "last")]
         last: Option<usize>,
+
+        /// Count matches per group instead of listing them
+        /// (executable, cmd, cwd, exit, day, hour, session, host, client, status)
+        #[arg(short = 'b', long = "by")]
+        by: Option<String>,
+
+        /// Collapse repeats of the same command into one row, with its run
+        /// count, first/last run and success ratio
+        #[arg(long = "dedup", conflicts_with = "by")]
+        dedup: bool,
+
+        /// Search all history, not just this directory or project
+        /// (see `query.default_scope`)
+        #[arg(short = 'g', long = "global")]
+        global: bool,
+
+        /// Show dates and times instead of relative times ("3m ago")
+        #[arg(long = "absolute")]
+        absolute: bool,
+
+        /// Query the store as of a date (YYYY-MM-DD, or a duration back
+        /// like 3d), from the newest snapshot taken on or before it
+        #[arg(long = "as-of", value_name = "DATE")]
+        as_of: Option<String>,
     },
 
     /// Show detailed info about an invocation
@@ -219,11 +446,71 @@ enum Commands {
         #[arg(short = 'f', long = "format", default_value = "table")]
         format: String,
 
-        /// Return only this field (id, cmd, cwd, exit, timestamp, duration, session)
+        /// Return only this field (id, alias, cmd, cwd, exit, timestamp, duration, session, links,
+        /// terminal, window, title)
         #[arg(long = "field")]
         field: Option<String>,
     },
 
+    /// Show an invocation's word-pair alias (e.g. brave-otter)
+    Alias {
+        /// Query selector (e.g., ~1, %/make/~1) or short ID
+        #[arg(default_value = "~1")]
+        query: String,
+
+        /// Reserve an alias for a new invocation ID and print both (used by the shell hook)
+        #[arg(long = "new", conflicts_with = "query")]
+        new: bool,
+    },
+
+    /// Link an invocation to an issue, PR or other reference
+    Link {
+        /// Link kind (e.g., issue, pr, doc)
+        kind: String,
+
+        /// Query selector (e.g., ~1, %/make/~1) or short ID
+        query: String,
+
+        /// URL or identifier (e.g., https://github.com/org/repo/issues/42, JIRA-123)
+        target: String,
+
+        /// Remove the link instead of adding it
+        #[arg(short = 'd', long = "delete")]
+        delete: bool,
+
+        /// Show what would change without changing anything
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+    },
+
+    /// Compare the recorded environments of two invocations
+    EnvDiff {
+        /// Earlier invocation (e.g., ~5, %/make/~2) or short ID
+        #[arg(default_value = "~2")]
+        old: String,
+
+        /// Later invocation (default: most recent)
+        #[arg(default_value = "~1")]
+        new: String,
+
+        /// Also list variables that are the same in both
+        #[arg(short = 'a', long)]
+        all: bool,
+    },
+
+    /// Compare two runs of a command (e.g. here and on a remote): exit code,
+    /// duration, commit, events and environment
+    Compare {
+        /// First invocation (e.g., ~1, %/make/~1) or short ID
+        #[arg(default_value = "~2")]
+        left: String,
+
+        /// Second invocation; `remote:NAME:` for the latest run of the same
+        /// command on a remote (or `remote:NAME:~N`, `remote:NAME:ID`)
+        #[arg(default_value = "~1")]
+        right: String,
+    },
+
     /// Re-run a previous command
     #[command(visible_aliases = ["R", "!!"])]
     Rerun {
@@ -238,6 +525,11 @@ enum Commands {
         /// Don't capture the re-run (just execute)
         #[arg(short = 'N', long = "no-capture")]
         no_capture: bool,
+
+        /// Run against the working-tree snapshot recorded with the command
+        /// (needs `snapshot_worktree = true`), in a temporary git worktree
+        #[arg(long = "at-snapshot")]
+        at_snapshot: bool,
     },
 
     /// Execute SQL query
@@ -245,6 +537,48 @@ enum Commands {
     Sql {
         /// SQL query to execute
         query: String,
+
+        /// Output format: table (default), json, ndjson, csv
+        #[arg(short = 'f', long = "format", default_value = "table")]
+        format: String,
+
+        /// Cut table values longer than this many characters
+        #[arg(long = "max-width", default_value = "50", conflicts_with = "no_truncate")]
+        max_width: usize,
+
+        /// Show table values in full
+        #[arg(long = "no-truncate")]
+        no_truncate: bool,
+    },
+
+    /// List the schemas, views and macros available to `shq sql`
+    Schema {
+        /// Only show objects whose name or description contains this
+        pattern: Option<String>,
+
+        /// Attach remotes first, to list their remote_<name>_*() macros
+        #[arg(long = "remotes")]
+        remotes: bool,
+
+        /// Print the version of the stable public views (v1.*) and exit
+        #[arg(long = "contract-version")]
+        contract_version: bool,
+
+        /// Output format: table, json
+        #[arg(short = 'f', long = "format", default_value = "table")]
+        format: String,
+    },
+
+    /// Print SQL that attaches the store read-only in your own DuckDB session
+    #[command(name = "attach-snippet")]
+    AttachSnippet {
+        /// Database alias to attach the store as
+        #[arg(long = "as", default_value = "bird")]
+        alias: String,
+
+        /// Write the SQL to this file (e.g. for `duckdb -init FILE`) instead of stdout
+        #[arg(short = 'o', long = "output")]
+        output: Option<std::path::PathBuf>,
     },
 
     /// Quick reference for commands and query syntax
@@ -267,6 +601,111 @@ enum Commands {
         field: Option<String>,
     },
 
+    /// Show disk usage by table, date, storage tier and largest blobs
+    Du {
+        /// Number of largest blobs to list
+        #[arg(short = 'n', long = "top", default_value = "10")]
+        top: usize,
+
+        /// Number of date partitions to list (newest first, 0 = all)
+        #[arg(short = 'd', long = "days", default_value = "14")]
+        days: usize,
+
+        /// Output format: table, json
+        #[arg(short = 'f', long = "format", default_value = "table")]
+        format: String,
+
+        /// Report what blob deduplication saves instead
+        #[arg(long = "dedup")]
+        dedup: bool,
+    },
+
+    /// Leaderboard of frequent, slow and failing commands
+    Top {
+        /// Query filter to drill down (e.g., %/cargo/, %failed)
+        #[arg(default_value = "")]
+        query: String,
+
+        /// Time window: duration (2h, 7d, 2w, 1mo) or date (YYYY-MM-DD), in whole days
+        #[arg(short = 's', long = "since", default_value = "7d")]
+        since: String,
+
+        /// Group by field (executable, cmd, cwd, exit, session, host, ...)
+        #[arg(short = 'b', long = "by", default_value = "executable")]
+        by: String,
+
+        /// Rows per section
+        #[arg(short = 'n', long = "limit", default_value = "10")]
+        limit: usize,
+
+        /// Refresh interval in seconds
+        #[arg(short = 'i', long = "interval", default_value = "2")]
+        interval: u64,
+
+        /// Print once and exit (default when stdout is not a terminal)
+        #[arg(long = "once")]
+        once: bool,
+    },
+
+    /// Calendar heatmap of invocations per day
+    Heatmap {
+        /// Calendar year to show (default: the last 53 weeks)
+        #[arg(short = 'y', long = "year")]
+        year: Option<i32>,
+
+        /// Output format
+        #[arg(short = 'f', long = "format", value_enum, default_value_t = commands::HeatmapFormat::Grid)]
+        format: commands::HeatmapFormat,
+    },
+
+    /// Write a static, self-contained HTML report of recent activity
+    Report {
+        /// Directory to write index.html into
+        #[arg(long = "html", value_name = "DIR")]
+        html: std::path::PathBuf,
+
+        /// Start of the report, in whole days (e.g. 14d, 2w, 2024-01-01; default: 30d)
+        #[arg(long = "since")]
+        since: Option<String>,
+
+        /// Maximum invocations to include in the table
+        #[arg(short = 'n', long = "limit", default_value = "10000")]
+        limit: usize,
+    },
+
+    /// Summarize a week of history for a standup
+    Digest {
+        /// Markdown output, rendered through the template
+        #[arg(long = "markdown")]
+        markdown: bool,
+
+        /// Markdown template file ({{summary}}, {{noteworthy}}, {{new_failures}}, {{regressions}}, {{start}}, {{end}})
+        #[arg(long = "template", value_name = "FILE", requires = "markdown")]
+        template: Option<std::path::PathBuf>,
+
+        /// Weeks back (0 = this week, 1 = last week)
+        #[arg(long = "ago", default_value = "0")]
+        ago: u32,
+
+        /// Write to a file instead of stdout
+        #[arg(short = 'o', long = "output", value_name = "FILE")]
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Export history to parquet files (e.g. to share a bug reproduction)
+    Export {
+        /// Directory to write <table>.parquet files into
+        dir: std::path::PathBuf,
+
+        /// Replace users, hosts, paths and arguments with consistent pseudonyms
+        #[arg(short = 'a', long = "anonymize")]
+        anonymize: bool,
+
+        /// Only export data since this date or duration, in whole days (e.g. 7d, 2024-01-15)
+        #[arg(short = 's', long = "since")]
+        since: Option<String>,
+    },
+
     /// Move old data from recent to archive
     Archive {
         /// Archive data older than this many days
@@ -280,6 +719,118 @@ enum Commands {
         /// Extract events from invocations before archiving (backfill)
         #[arg(short = 'x', long = "extract-first")]
         extract_first: bool,
+
+        /// Then write a year of archived data to this single-file bundle
+        /// (e.g. 2023.birdpack) and detach it from the store
+        #[arg(long = "bundle", value_name = "FILE")]
+        bundle: Option<std::path::PathBuf>,
+
+        /// Year to bundle [default: from the bundle's file name]
+        #[arg(long = "year", requires = "bundle")]
+        year: Option<i32>,
+
+        /// Keep the bundled data in the store too
+        #[arg(long = "keep", requires = "bundle")]
+        keep: bool,
+    },
+
+    /// Move blobs not accessed recently to the cold tier (blobs.cold_tier)
+    Tier {
+        /// Move blobs not accessed for this many days [default: blobs.tier_after_days]
+        #[arg(short = 'd', long = "days")]
+        days: Option<u32>,
+
+        /// Show what would be done without making changes
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+    },
+
+    /// Re-inline small blobs and move large inline output to blob files,
+    /// per the current inline_threshold
+    RebalanceBlobs {
+        /// Threshold in bytes to rebalance to [default: inline_threshold]
+        #[arg(short = 't', long = "threshold")]
+        threshold: Option<usize>,
+
+        /// Show what would be done without making changes
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+    },
+
+    /// Move inline output stored more than once (e.g. `ls` in the same
+    /// directory) to shared blob files
+    DedupOutputs {
+        /// Smallest output in bytes to deduplicate [default: 256]
+        #[arg(short = 'm', long = "min-bytes")]
+        min_bytes: Option<usize>,
+
+        /// Show what would be done without making changes
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+    },
+
+    /// Check that stored output content is present (and intact)
+    Fsck {
+        /// Read every output and check it against its content hash
+        #[arg(long = "verify-content")]
+        verify_content: bool,
+    },
+
+    /// Check that the hook, binaries, spool and store versions work together
+    Doctor,
+
+    /// Report shq's own latency by phase (parse, connect, query, render); recorded locally only
+    #[command(name = "self-profile")]
+    SelfProfile {
+        /// Start recording the phase timings of every shq command
+        #[arg(long, conflicts_with_all = ["off", "clear"])]
+        on: bool,
+
+        /// Stop recording
+        #[arg(long, conflicts_with = "clear")]
+        off: bool,
+
+        /// Delete the recorded timings
+        #[arg(long)]
+        clear: bool,
+
+        /// Only commands with this name (e.g. invocations, sql, "hook status")
+        #[arg(long)]
+        command: Option<String>,
+
+        /// Output format: table (default), json
+        #[arg(short = 'f', long = "format", default_value = "table")]
+        format: String,
+    },
+
+    /// Show the sync token: the data files of the store's last consistent state
+    SyncToken {
+        /// Rewrite it from the data files as they are now
+        #[arg(long)]
+        refresh: bool,
+    },
+
+    /// Check that a copy of the store (rsync, backup) holds a consistent state
+    VerifyMirror {
+        /// BIRD root of the copy
+        path: std::path::PathBuf,
+    },
+
+    /// Restore the data removed by the last destructive operation
+    Undo {
+        /// List operations that can be undone instead
+        #[arg(short = 'l', long = "list")]
+        list: bool,
+    },
+
+    /// Show maintenance in progress (compaction, archiving, sync) and its pid
+    Locks,
+
+    /// Snapshot the store for `shq invocations --as-of`
+    Snapshot {
+        /// List snapshots instead of taking one
+        #[arg(short = 'l', long = "list")]
+        list: bool,
     },
 
     /// Compact parquet files to reduce storage and improve query performance
@@ -331,6 +882,38 @@ enum Commands {
         action: HookAction,
     },
 
+    /// Per-project settings from .bird/config.toml
+    Project {
+        #[command(subcommand)]
+        action: ProjectAction,
+    },
+
+    /// Capture a task runner (make, just, ...) via a shim earlier in PATH
+    Wrap {
+        /// Tool to wrap (lists wrapped tools if omitted)
+        tool: Option<String>,
+
+        /// Remove the shim instead of creating it
+        #[arg(short = 'r', long = "remove", requires = "tool")]
+        remove: bool,
+    },
+
+    /// Run a wrapped tool under capture (invoked by `shq wrap` shims)
+    #[command(name = "wrap-exec", hide = true)]
+    WrapExec {
+        tool: String,
+
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+
+    /// Capture commands run by git hooks (pre-commit, pre-push, ...)
+    #[command(name = "git-hooks")]
+    GitHooks {
+        #[command(subcommand)]
+        action: GitHooksAction,
+    },
+
     /// Manage format detection hints
     #[command(name = "format-hints", visible_alias = "fh")]
     FormatHints {
@@ -338,25 +921,113 @@ enum Commands {
         action: FormatHintsAction,
     },
 
+    /// Pause capture in all shells (privacy mode)
+    Off {
+        /// Resume automatically after this long (e.g. 30m, 1h, 2d)
+        #[arg(long = "for", value_name = "DURATION")]
+        duration: Option<String>,
+
+        /// Suppress output
+        #[arg(short = 'q', long = "quiet")]
+        quiet: bool,
+    },
+
+    /// Resume capture after `shq off`
+    On {
+        /// Suppress output
+        #[arg(short = 'q', long = "quiet")]
+        quiet: bool,
+    },
+
+    /// Write a marker ("meeting start") into the session timeline
+    Mark {
+        /// Marker text
+        text: String,
+
+        /// Session to mark (ID, name, or "current")
+        #[arg(short = 's', long = "session", default_value = "current")]
+        session: String,
+
+        /// Suppress output
+        #[arg(short = 'q', long = "quiet")]
+        quiet: bool,
+    },
+
+    /// Browse shell sessions
+    Session {
+        #[command(subcommand)]
+        action: SessionAction,
+    },
+
+    /// Browse work contexts (activity grouped by repo, branch and time)
+    Context {
+        #[command(subcommand)]
+        action: ContextAction,
+    },
+
     /// Retrospective buffer for unsaved commands
     Buffer {
         #[command(subcommand)]
         action: BufferAction,
     },
 
+    /// Saves spooled by `shq save --async`
+    Spool {
+        #[command(subcommand)]
+        action: SpoolAction,
+    },
+
+    /// Shell history files (~/.zsh_history, ~/.bash_history) alongside BIRD
+    Histfile {
+        #[command(subcommand)]
+        action: HistfileAction,
+    },
+
     /// Manage remote storage connections
     Remote {
         #[command(subcommand)]
         action: RemoteAction,
     },
 
+    /// Mount another BIRD root (e.g. an archive drive) read-only, or list mounts
+    ///
+    /// Its history is queryable as `remote_NAME.*` in `shq sql` and is
+    /// included in the `unified.*` views, next to the current history.
+    Mount {
+        /// BIRD root directory to mount (or its db/bird.duckdb, or a .birdpack
+        /// bundle from `shq archive --bundle`); omit to list mounts
+        path: Option<std::path::PathBuf>,
+
+        /// Name to mount it under (default: the directory name)
+        #[arg(long = "as", value_name = "NAME", requires = "path")]
+        name: Option<String>,
+    },
+
+    /// Unmount a BIRD root mounted with `shq mount`
+    Unmount {
+        /// Mount name
+        name: String,
+    },
+
+    /// Copy a BIRD setup (config, format hints) between machines, without data
+    Setup {
+        #[command(subcommand)]
+        action: SetupAction,
+    },
+
+    /// Run commands on a schedule, capturing every run
+    Cron {
+        #[command(subcommand)]
+        action: CronAction,
+    },
+
     /// Push local data to a remote
     Push {
         /// Remote to push to (uses default if not specified)
         #[arg(short, long)]
         remote: Option<String>,
 
-        /// Only push data since this date or duration (e.g., "7d", "2024-01-15")
+        /// Only push data since this date or duration, in whole days (e.g., "7d", "2024-01-15")
         #[arg(short, long)]
         since: Option<String>,
 
@@ -379,7 +1050,7 @@ enum Commands {
         #[arg(short, long)]
         client: Option<String>,
 
-        /// Only pull data since this date or duration (e.g., "7d", "2024-01-15")
+        /// Only pull data since this date or duration, in whole days (e.g., "7d", "2024-01-15")
         #[arg(short, long)]
         since: Option<String>,
 
@@ -388,6 +1059,83 @@ enum Commands {
         blobs: bool,
     },
 
+    /// Teammates' recent commands from shared remotes, in this project
+    Feed {
+        /// Only this remote (default: all auto-attached remotes)
+        #[arg(short, long)]
+        remote: Option<String>,
+
+        /// Only commands since this date or duration, in whole days (e.g., "1d", "2024-01-15")
+        #[arg(short, long, default_value = "1d")]
+        since: String,
+
+        /// Show every project, not just the current one
+        #[arg(short, long)]
+        all: bool,
+
+        /// Include your own pushed commands
+        #[arg(long)]
+        mine: bool,
+
+        /// Show at most N commands
+        #[arg(short = 'n', long = "limit", default_value = "50")]
+        limit: usize,
+    },
+
+    /// Estimate active working time per project and branch
+    Time {
+        /// Period: day, week, month
+        #[arg(short = 'p', long = "period", default_value = "week")]
+        period: String,
+
+        /// Periods back (0 = this one, 1 = the previous one)
+        #[arg(long = "ago", default_value = "0")]
+        ago: u32,
+
+        /// Only the current project (git root, or the current directory)
+        #[arg(long = "project")]
+        project: bool,
+
+        /// Idle minutes that end a stretch of work
+        #[arg(short = 'g', long = "gap", default_value = "30")]
+        gap: i64,
+
+        /// Output format: table (default), csv (one row per day, project and branch)
+        #[arg(short = 'f', long = "format", default_value = "table")]
+        format: String,
+    },
+
+    /// Recent build, test and lint health of a project, from history
+    Health {
+        /// Project directory (default: the current project, or the current directory)
+        path: Option<std::path::PathBuf>,
+
+        /// Days of history to summarize
+        #[arg(short = 'd', long = "days", default_value_t = bird::DEFAULT_HEALTH_DAYS)]
+        days: u32,
+    },
+
+    /// Most frequent commands not found (typos), with what was probably meant
+    Typos {
+        /// Only the last N days (default: all time)
+        #[arg(short = 'd', long = "days")]
+        days: Option<u32>,
+
+        /// Number of typos to show
+        #[arg(short = 'n', long = "limit", default_value = "20")]
+        limit: usize,
+
+        /// Output format: table, json
+        #[arg(short = 'f', long = "format", default_value = "table")]
+        format: String,
+    },
+
+    /// Record a command that wasn't found (invoked by the shell hook)
+    #[command(name = "not-found", hide = true)]
+    NotFound {
+        command: String,
+    },
+
     /// Query parsed events (errors, warnings, test results) from invocation outputs
     #[command(visible_alias = "e")]
     Events {
@@ -418,6 +1166,38 @@ enum Commands {
         /// Override format detection (e.g., gcc, pytest, cargo)
         #[arg(short = 'f', long = "format")]
         format: Option<String>,
+
+        /// Show full event IDs (for --mark-wrong)
+        #[arg(long = "ids")]
+        ids: bool,
+
+        /// Hide a wrongly extracted event (ID or unique prefix)
+        #[arg(long = "mark-wrong", value_name = "EVENT_ID", conflicts_with_all = ["reparse", "extract", "corrections"])]
+        mark_wrong: Option<String>,
+
+        /// Why the event is wrong (with --mark-wrong)
+        #[arg(long = "note", requires = "mark_wrong")]
+        note: Option<String>,
+
+        /// List recorded corrections (events marked wrong or edited)
+        #[arg(long = "corrections", conflicts_with_all = ["reparse", "extract"])]
+        corrections: bool,
+
+        /// Exit with status 1 if the invocations have events of this
+        /// severity or worse (error, warning, info, note)
+        #[arg(long = "fail-on", value_name = "SEVERITY", conflicts_with_all = ["mark_wrong", "corrections"])]
+        fail_on: Option<String>,
+
+        /// Show the latest run of a `shq watch` (the most recent watch, or
+        /// the one with this ID) instead of QUERY
+        #[arg(
+            long = "latest-of-watch",
+            value_name = "WATCH_ID",
+            num_args = 0..=1,
+            default_missing_value = "",
+            conflicts_with_all = ["mark_wrong", "corrections"]
+        )]
+        latest_of_watch: Option<String>,
     },
 
     /// Update DuckDB extensions to latest versions
@@ -427,6 +1207,12 @@ enum Commands {
         dry_run: bool,
     },
 
+    /// Bundle or install DuckDB extensions for offline machines
+    Extensions {
+        #[command(subcommand)]
+        action: ExtensionsAction,
+    },
+
     /// Extract events from an invocation's output
     ExtractEvents {
         /// Invocation ID (default: last invocation, ignored if --all)
@@ -449,17 +1235,30 @@ enum Commands {
         #[arg(short = 'a', long = "all")]
         all: bool,
 
-        /// Only process invocations since this date (YYYY-MM-DD, default: 30 days ago)
+        /// Invocations whose command matches this SQL LIKE pattern (e.g. '%/mytool/%')
+        #[arg(long = "matching", requires = "reparse", conflicts_with = "all")]
+        matching: Option<String>,
+
+        /// Replace existing events, detecting formats from the current format hints
+        #[arg(long = "reparse", requires = "matching")]
+        reparse: bool,
+
+        /// Only process invocations since this date or duration (YYYY-MM-DD, 7d; default: 30 days ago;
+        /// with --matching: all history)
         #[arg(long = "since")]
         since: Option<String>,
 
-        /// Maximum number of invocations to process (default: 1000)
+        /// Maximum number of invocations to process (default: 1000; with --matching: no limit)
         #[arg(short = 'n', long = "limit")]
         limit: Option<usize>,
 
         /// Show what would be extracted without actually extracting
         #[arg(long = "dry-run")]
         dry_run: bool,
+
+        /// Report the format chosen and why, lines matched, and error-looking lines missed
+        #[arg(long = "explain", conflicts_with_all = ["all", "matching", "force", "dry_run"])]
+        explain: bool,
     },
 
     /// Interactive tutorial to learn shq features
@@ -497,6 +1296,61 @@ enum HookAction {
 
     /// Output ignore patterns for shell hooks (colon-separated)
     IgnorePatterns,
+
+    /// Show the hooks installed in rc files and whether they're out of date
+    Status,
+
+    /// Replace out-of-date copies of the hook in rc files (backups are kept)
+    Upgrade {
+        /// Show what would be upgraded without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Measure hook overhead (preexec + postexec + save) per command
+    Bench {
+        /// Number of synthetic commands
+        #[arg(short = 'n', long = "count", default_value = "50")]
+        count: usize,
+
+        /// Shell whose hook to time (zsh, bash). Auto-detected from $SHELL if not specified.
+        #[arg(short, long)]
+        shell: Option<String>,
+
+        /// Time synchronous saves instead of spooled (--async) ones
+        #[arg(long)]
+        sync: bool,
+
+        /// Output format: table (default), json
+        #[arg(short = 'f', long = "format", default_value = "table")]
+        format: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProjectAction {
+    /// Show the current project and its settings
+    Show,
+
+    /// Output shell code exporting the current project's settings (used by the hook)
+    Env,
+}
+
+#[derive(Subcommand)]
+enum GitHooksAction {
+    /// Wrap existing hooks in the current repository so their runs are captured
+    Install {
+        /// Hook to wrap (repeatable; default: pre-commit, pre-push)
+        #[arg(long = "hook", value_name = "NAME")]
+        hooks: Vec<String>,
+    },
+
+    /// Remove shq wrappers and restore the original hooks
+    Uninstall {
+        /// Hook to unwrap (repeatable; default: pre-commit, pre-push)
+        #[arg(long = "hook", value_name = "NAME")]
+        hooks: Vec<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -526,12 +1380,20 @@ enum FormatHintsAction {
         /// Priority (higher wins, default: 500)
         #[arg(short = 'p', long)]
         priority: Option<i32>,
+
+        /// Show what would change without changing anything
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
     },
 
     /// Remove a format hint by pattern
     Remove {
         /// Pattern to remove
         pattern: String,
+
+        /// Show what would change without changing anything
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
     },
 
     /// Check which format would be detected for a command
@@ -544,6 +1406,122 @@ enum FormatHintsAction {
     SetDefault {
         /// Default format (e.g., auto, text)
         format: String,
+
+        /// Show what would change without changing anything
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+    },
+
+    /// Export user-defined hints as TOML (to stdout)
+    Export,
+
+    /// Import hints from a TOML file ("-" for stdin)
+    Import {
+        /// File produced by `shq format-hints export`
+        file: String,
+
+        /// How to combine with existing hints: overwrite, keep, replace
+        #[arg(short = 's', long, default_value = "overwrite")]
+        strategy: String,
+
+        /// Show what would change without changing anything
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+    },
+
+    /// Install a built-in hint pack (lists packs if none given)
+    Install {
+        /// Pack name (e.g., python, js, rust)
+        pack: Option<String>,
+
+        /// How to combine with existing hints: overwrite, keep, replace
+        #[arg(short = 's', long, default_value = "keep")]
+        strategy: String,
+
+        /// Show what would change without changing anything
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionAction {
+    /// List sessions with duration and command counts
+    #[command(visible_alias = "ls")]
+    List {
+        /// Number of sessions to show
+        #[arg(short = 'n', long = "last", default_value = "20")]
+        last: usize,
+
+        /// Output format: table (default), json
+        #[arg(short = 'f', long = "format", default_value = "table")]
+        format: String,
+    },
+
+    /// Show a timeline of a session's invocations
+    Show {
+        /// Session ID, or "current" for this shell's session
+        #[arg(default_value = "current")]
+        session: String,
+
+        /// Output format: timeline (default), json
+        #[arg(short = 'f', long = "format", default_value = "timeline")]
+        format: String,
+    },
+
+    /// Name a session so it can be found with %session=<name>
+    Name {
+        /// Name to give the session
+        name: String,
+
+        /// Session to name (ID, existing name, or "current")
+        #[arg(short = 's', long = "session", default_value = "current")]
+        session: String,
+    },
+
+    /// Record that a session has ended (called by the shell exit hook)
+    Close {
+        /// Session to close (ID, name, or "current")
+        #[arg(long = "session-id", default_value = "current")]
+        session_id: String,
+
+        /// Suppress output
+        #[arg(short = 'q', long = "quiet")]
+        quiet: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ContextAction {
+    /// List work contexts with duration and command counts
+    #[command(visible_alias = "ls")]
+    List {
+        /// Number of contexts to show
+        #[arg(short = 'n', long = "last", default_value = "20")]
+        last: usize,
+
+        /// Idle minutes that start a new context
+        #[arg(short = 'g', long = "gap", default_value = "30")]
+        gap: i64,
+
+        /// Output format: table (default), json
+        #[arg(short = 'f', long = "format", default_value = "table")]
+        format: String,
+    },
+
+    /// Show a timeline of a work context across terminals
+    Show {
+        /// Context ID, branch or repo name, or "current" for this directory
+        #[arg(default_value = "current")]
+        context: String,
+
+        /// Idle minutes that start a new context
+        #[arg(short = 'g', long = "gap", default_value = "30")]
+        gap: i64,
+
+        /// Output format: timeline (default), json
+        #[arg(short = 'f', long = "format", default_value = "timeline")]
+        format: String,
     },
 }
 
@@ -572,6 +1550,10 @@ enum BufferAction {
         /// Skip confirmation prompt
         #[arg(short = 'f', long = "force")]
         force: bool,
+
+        /// Show what would change without changing anything
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
     },
 
     /// Enable/disable retrospective buffering
@@ -589,6 +1571,124 @@ enum BufferAction {
     Status,
 }
 
+#[derive(Subcommand)]
+enum SpoolAction {
+    /// Write spooled saves to the store (started in the background by `save --async`)
+    Flush {
+        /// Suppress informational output
+        #[arg(short = 'q', long = "quiet")]
+        quiet: bool,
+    },
+
+    /// Show how many saves are waiting
+    Status,
+}
+
+#[derive(Subcommand)]
+enum HistfileAction {
+    /// Record new entries of history files as invocations (without output)
+    ///
+    /// Reads the files given, or those in `[histfile] ingest`, or $HISTFILE,
+    /// from where the last import stopped.
+    Import {
+        /// History files to read
+        paths: Vec<std::path::PathBuf>,
+
+        /// File format: zsh, bash or plain (default: from the file name)
+        #[arg(long = "format")]
+        format: Option<String>,
+
+        /// Suppress informational output
+        #[arg(short = 'q', long = "quiet")]
+        quiet: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ExtensionsAction {
+    /// Copy installed extensions into a repository directory
+    Bundle {
+        /// Directory to write the repository to
+        dir: String,
+    },
+
+    /// Install extensions from a bundled repository
+    Install {
+        /// Repository directory (from `shq extensions bundle`) or URL
+        #[arg(long = "from")]
+        from: String,
+    },
+
+    /// Show which optional extensions are active
+    Status,
+}
+
+#[derive(Subcommand)]
+enum SetupAction {
+    /// Write config.toml and format hints to a bundle (a tar file)
+    Export {
+        /// Bundle file to write
+        path: std::path::PathBuf,
+    },
+
+    /// Apply a bundle from `shq setup export`, keeping .bak copies of
+    /// replaced files
+    Apply {
+        /// Bundle file to read
+        path: std::path::PathBuf,
+
+        /// Show what would change without writing anything
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CronAction {
+    /// Add or replace a scheduled command
+    Add {
+        /// Job name (runs are recorded with %dim:cron=NAME)
+        name: String,
+
+        /// Cron schedule in local time, e.g. '0 3 * * *' or @daily
+        schedule: String,
+
+        /// Directory to run in (default: the current directory)
+        #[arg(long = "cwd", value_name = "DIR")]
+        cwd: Option<std::path::PathBuf>,
+
+        /// The command to run
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// Remove a scheduled command
+    Remove {
+        /// Job name
+        name: String,
+    },
+
+    /// Show jobs with their next run, last run and recent failures
+    Status {
+        /// Output format: table, json
+        #[arg(short = 'f', long = "format", default_value = "table")]
+        format: String,
+
+        /// Count failures over this many recent runs
+        #[arg(short = 'n', long = "recent", default_value = "10")]
+        recent: usize,
+    },
+
+    /// Run a job now, in the foreground
+    Run {
+        /// Job name
+        name: String,
+    },
+
+    /// Run jobs as they come due (keep running, e.g. as a user service)
+    Daemon,
+}
+
 #[derive(Subcommand)]
 enum RemoteAction {
     /// Add a remote storage connection
@@ -596,7 +1696,7 @@ enum RemoteAction {
         /// Name for this remote (e.g., team, backup, ci)
         name: String,
 
-        /// Remote type: s3, motherduck, postgres, or file
+        /// Remote type: s3, motherduck, postgres, file, or atuin (push-only)
         #[arg(short = 't', long = "type")]
         remote_type: String,
 
@@ -608,13 +1708,22 @@ enum RemoteAction {
         #[arg(long)]
         read_only: bool,
 
-        /// Credential provider for S3 (e.g., credential_chain)
+        /// Credential provider for S3 (e.g., credential_chain), or the Atuin
+        /// data directory for atuin remotes
         #[arg(long)]
         credential_provider: Option<String>,
 
         /// Don't auto-attach on connection open
         #[arg(long)]
         no_auto_attach: bool,
+
+        /// Sign pushed invocations with this client's key
+        #[arg(long)]
+        sign: bool,
+
+        /// Drop pulled invocations not signed by their client's trusted key
+        #[arg(long)]
+        verify: bool,
     },
 
     /// List configured remotes
@@ -640,6 +1749,9 @@ enum RemoteAction {
 
     /// Show sync status
     Status,
+
+    /// Show this client's signing key and pinned client keys
+    Key,
 }
 
 /// Parse lines argument: N (any), +N (first N), -N (last N).
@@ -656,12 +1768,46 @@ fn parse_lines_arg(s: &str) -> (usize, commands::LimitOrder) {
 }
 
 fn main() {
-    let cli = Cli::parse();
+    bird::profile::start(Phase::Parse, std::time::Instant::now());
+    let matches = match Cli::command().try_get_matches() {
+        Ok(matches) => matches,
+        Err(e) => {
+            let _ = e.print();
+            // An argument this shq doesn't know, from a hook of another version
+            if e.use_stderr() {
+                if let Some(hint) = bird::version::shell_hook_version().and_then(bird::version::hook_mismatch) {
+                    eprintln!("hint: {}", hint);
+                }
+            }
+            std::process::exit(e.exit_code());
+        }
+    };
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let command_name = subcommand_path(&matches);
+    let query_phase = bird::profile::during(Phase::Query);
     let force_capture = cli.force_capture;
 
+    if let Err(e) = logging::init(cli.verbose, cli.log_file.as_deref()) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+
+    // Listings page like git's; the pager is closed before any error shows
+    let pager = if !cli.no_pager
+        && matches!(cli.command, Commands::Invocations { .. } | Commands::Events { .. } | Commands::Sql { .. })
+    {
+        let _render = bird::profile::during(Phase::Render);
+        pager::start()
+    } else {
+        None
+    };
+
+    // Set by checks like `events --fail-on` that succeed but should exit 1
+    let mut failed_check = false;
+
     let result = match cli.command {
         Commands::Init { mode, force } => commands::init(&mode, force),
-        Commands::Run { shell_cmd, tag, extract, no_extract, format, compact, no_pty, cmd } => {
+        Commands::Run { shell_cmd, tag, extract, no_extract, format, compact, dims, no_pty, attach, expect, notify, tee, tee_fd, tee_socket, cmd } => {
             // Resolve extract behavior: --extract forces on, --no-extract forces off, otherwise use config
             let extract_override = if extract {
                 Some(true)
@@ -670,9 +1816,21 @@ fn main() {
             } else {
                 None
             };
-            commands::run(shell_cmd.as_deref(), &cmd, tag.as_deref(), extract_override, format.as_deref(), compact, no_pty, force_capture)
+            expect::Expectations::parse(&expect, notify).and_then(|expect| {
+                let sinks = sinks::Sinks::open(&tee, &tee_fd, &tee_socket)?;
+                commands::run(shell_cmd.as_deref(), &cmd, tag.as_deref(), &dims, &attach, extract_override, format.as_deref(), compact, no_pty, force_capture, &expect, sinks)
+            })
+        }
+        Commands::Bench { command, runs, warmup, name, compare, ignore_failure, format } => {
+            commands::bench(&command, runs, warmup, name.as_deref(), compare.as_deref(), ignore_failure, &format)
         }
-        Commands::Save { file, command, exit_code, duration_ms, stream, stdout_file, stderr_file, session_id, invoker_pid, invoker, invoker_type, no_extract, compact, tag, quiet, to_buffer } => {
+        Commands::Ab { a, b, runs, warmup, name, show, format } => {
+            commands::ab(a.as_deref(), b.as_deref(), runs, warmup, name.as_deref(), show.as_deref(), &format)
+        }
+        Commands::Watch { command, paths, interval_ms, clear, dims } => {
+            commands::watch(&command, &paths, interval_ms, clear, &dims)
+        }
+        Commands::Save { file, command, exit_code, duration_ms, stream, stdout_file, stderr_file, session_id, invoker_pid, invoker, invoker_type, no_extract, compact, tag, dims, quiet, to_buffer, async_save, from_typescript, timing } => {
             // Check if this is a buffer reference (~N or just a number)
             let is_buffer_ref = file.as_ref().map(|f| {
                 f.starts_with('~') || f.chars().all(|c| c.is_ascii_digit())
@@ -681,7 +1839,9 @@ fn main() {
             // Extract is enabled by default, --no-extract disables it
             let extract = !no_extract;
 
-            if is_buffer_ref && command.is_none() {
+            if let Some(path) = from_typescript {
+                commands::save_typescript(&path, timing.as_deref(), command.as_deref(), session_id.as_deref(), extract, tag.as_deref(), &dims, quiet, force_capture)
+            } else if is_buffer_ref && command.is_none() {
                 // Promote buffer entry to permanent storage
                 let selector = file.as_deref().unwrap_or("~1");
                 commands::save_from_buffer(selector, tag.as_deref(), extract, compact, quiet)
@@ -702,8 +1862,10 @@ fn main() {
                     extract,
                     compact,
                     tag.as_deref(),
+                    &dims,
                     quiet,
                     to_buffer,
+                    async_save,
                     force_capture,
                 )
             } else {
@@ -712,7 +1874,7 @@ fn main() {
                 std::process::exit(1);
             }
         }
-        Commands::Output { query, stream, stdout_only, stderr_only, all_combined, pager, raw: _, strip, head, tail, lines, follow } => {
+        Commands::Output { query, stream, stdout_only, stderr_only, all_combined, pager, raw: _, strip, head, tail, lines, follow, part } => {
             // Resolve stream from flags or -s value
             let resolved_stream = if stdout_only {
                 Some("stdout")
@@ -729,25 +1891,107 @@ fn main() {
                 head: head.or(lines),
                 tail,
                 follow,
+                part,
             };
             commands::output(&query, resolved_stream, &opts)
         }
-        Commands::Invocations { query, format, details, last } => {
+        Commands::Artifacts { query, extract } => commands::artifacts(&query, extract.as_deref()),
+        Commands::Segments { query, show, strip } => commands::segments(&query, show, strip),
+        Commands::Invocations { query, format, details, columns, last, by, dedup, global, absolute, as_of } => {
             let fmt = if details { "table" } else { &format };
-            commands::invocations(&query, fmt, last)
+            let as_of = as_of.as_deref();
+            match by {
+                // Aggregates cover every match unless a range is given
+                Some(by) => commands::invocations_by(query.as_deref().unwrap_or(""), &by, fmt, last, as_of, global),
+                None if dedup => {
+                    commands::invocations_dedup(query.as_deref().unwrap_or("~20:"), fmt, last, as_of, global, absolute)
+                }
+                None => commands::invocations(
+                    query.as_deref().unwrap_or("~20:"),
+                    fmt,
+                    columns.as_deref(),
+                    last,
+                    as_of,
+                    global,
+                    absolute,
+                ),
+            }
         }
         Commands::Info { query, format, field } => commands::info(&query, &format, field.as_deref()),
-        Commands::Rerun { query, dry_run, no_capture } => commands::rerun(&query, dry_run, no_capture),
-        Commands::Sql { query } => commands::sql(&query),
+        Commands::Alias { query, new } => commands::alias(&query, new),
+        Commands::EnvDiff { old, new, all } => commands::env_diff(&old, &new, all),
+        Commands::Link { kind, query, target, delete, dry_run } => {
+            commands::link(&kind, &query, &target, delete, dry_run)
+        }
+        Commands::Compare { left, right } => commands::compare(&left, &right),
+        Commands::Rerun { query, dry_run, no_capture, at_snapshot } => commands::rerun(&query, dry_run, no_capture, at_snapshot),
+        Commands::Sql { query, format, max_width, no_truncate } => {
+            commands::sql(&query, &format, (!no_truncate).then_some(max_width))
+        }
+        Commands::Schema { pattern, remotes, contract_version, format } => {
+            if contract_version {
+                commands::schema_contract_version(&format)
+            } else {
+                commands::schema(pattern.as_deref(), remotes, &format)
+            }
+        }
+        Commands::AttachSnippet { alias, output } => commands::attach_snippet(&alias, output.as_deref()),
         Commands::QuickHelp => commands::quick_help(),
         Commands::Stats { format, details, field } => commands::stats(&format, details, field.as_deref()),
-        Commands::Archive { days, dry_run, extract_first } => commands::archive(days, dry_run, extract_first),
+        Commands::Du { top, days, format, dedup } => {
+            if dedup {
+                commands::du_dedup(&format)
+            } else {
+                commands::du(top, days, &format)
+            }
+        }
+        Commands::Top { query, since, by, limit, interval, once } => {
+            commands::top(&query, &since, &by, limit, interval, once)
+        }
+        Commands::Heatmap { year, format } => commands::heatmap(year, format),
+        Commands::Report { html, since, limit } => commands::report(&html, since.as_deref(), limit),
+        Commands::Digest { markdown, template, ago, output } => {
+            commands::digest(markdown, template.as_deref(), ago, output.as_deref())
+        }
+        Commands::Export { dir, anonymize, since } => {
+            commands::export(&dir, anonymize, since.as_deref())
+        }
+        Commands::Archive { days, dry_run, extract_first, bundle, year, keep } => {
+            commands::archive(days, dry_run, extract_first).and_then(|()| match bundle {
+                Some(path) => commands::archive_bundle(&path, year, keep, dry_run),
+                None => Ok(()),
+            })
+        }
+        Commands::Tier { days, dry_run } => commands::tier(days, dry_run),
+        Commands::RebalanceBlobs { threshold, dry_run } => commands::rebalance_blobs(threshold, dry_run),
+        Commands::DedupOutputs { min_bytes, dry_run } => commands::dedup_outputs(min_bytes, dry_run),
+        Commands::Fsck { verify_content } => commands::fsck(verify_content),
+        Commands::Doctor => commands::doctor(),
+        Commands::SelfProfile { on, off, clear, command, format } => commands::self_profile(on, off, clear, command.as_deref(), &format),
+        Commands::SyncToken { refresh } => commands::sync_token(refresh),
+        Commands::VerifyMirror { path } => commands::verify_mirror(&path),
+        Commands::Undo { list } => commands::undo(list),
+        Commands::Locks => commands::locks(),
+        Commands::Snapshot { list } => commands::snapshot(list),
         Commands::Compact { file_threshold, recompact_threshold, consolidate, extract_first, session, today_only, quiet, recent_only, archive_only, dry_run } => {
             commands::compact(file_threshold, recompact_threshold, consolidate, extract_first, session.as_deref(), today_only, quiet, recent_only, archive_only, dry_run)
         }
         Commands::Hook { action } => match action {
             HookAction::Init { shell, inactive, no_prompt_indicator, quiet } => commands::hook_init(shell.as_deref(), inactive, !no_prompt_indicator, quiet),
             HookAction::IgnorePatterns => commands::hook_ignore_patterns(),
+            HookAction::Status => commands::hook_status(),
+            HookAction::Upgrade { dry_run } => commands::hook_upgrade(dry_run),
+            HookAction::Bench { count, shell, sync, format } => commands::hook_bench(count, shell.as_deref(), sync, &format),
+        },
+        Commands::Project { action } => match action {
+            ProjectAction::Show => commands::project_show(),
+            ProjectAction::Env => commands::project_env(),
+        },
+        Commands::Wrap { tool, remove } => commands::wrap(tool.as_deref(), remove),
+        Commands::WrapExec { tool, args } => commands::wrap_exec(&tool, &args, force_capture),
+        Commands::GitHooks { action } => match action {
+            GitHooksAction::Install { hooks } => commands::git_hooks_install(&hooks),
+            GitHooksAction::Uninstall { hooks } => commands::git_hooks_uninstall(&hooks),
         },
         Commands::FormatHints { action } => match action {
             FormatHintsAction::List { filter, user_only, builtin_only } => {
@@ -755,29 +1999,78 @@ fn main() {
                 let show_user = !builtin_only;
                 commands::format_hints_list(show_builtin, show_user, filter.as_deref())
             },
-            FormatHintsAction::Add { pattern, format, priority } => {
-                commands::format_hints_add(&pattern, &format, priority)
+            FormatHintsAction::Add { pattern, format, priority, dry_run } => {
+                commands::format_hints_add(&pattern, &format, priority, dry_run)
             },
-            FormatHintsAction::Remove { pattern } => commands::format_hints_remove(&pattern),
+            FormatHintsAction::Remove { pattern, dry_run } => commands::format_hints_remove(&pattern, dry_run),
             FormatHintsAction::Check { command } => commands::format_hints_check(&command),
-            FormatHintsAction::SetDefault { format } => commands::format_hints_set_default(&format),
+            FormatHintsAction::SetDefault { format, dry_run } => {
+                commands::format_hints_set_default(&format, dry_run)
+            }
+            FormatHintsAction::Export => commands::format_hints_export(),
+            FormatHintsAction::Import { file, strategy, dry_run } => {
+                commands::format_hints_import(&file, &strategy, dry_run)
+            }
+            FormatHintsAction::Install { pack, strategy, dry_run } => {
+                commands::format_hints_install(pack.as_deref(), &strategy, dry_run)
+            }
+        },
+        Commands::Off { duration, quiet } => commands::capture_off(duration.as_deref(), quiet),
+        Commands::On { quiet } => commands::capture_on(quiet),
+        Commands::Mark { text, session, quiet } => commands::mark(&text, &session, quiet),
+        Commands::Session { action } => match action {
+            SessionAction::List { last, format } => commands::session_list(last, &format),
+            SessionAction::Show { session, format } => commands::session_show(&session, &format),
+            SessionAction::Name { name, session } => commands::session_name(&name, &session),
+            SessionAction::Close { session_id, quiet } => commands::session_close(&session_id, quiet),
+        },
+        Commands::Context { action } => match action {
+            ContextAction::List { last, gap, format } => commands::context_list(last, gap, &format),
+            ContextAction::Show { context, gap, format } => {
+                commands::context_show(&context, gap, &format)
+            }
         },
         Commands::Buffer { action } => match action {
             BufferAction::List { format, last } => commands::buffer_list(&format, last),
             BufferAction::Show { selector } => commands::buffer_show(&selector),
-            BufferAction::Clear { force } => commands::buffer_clear(force),
+            BufferAction::Clear { force, dry_run } => commands::buffer_clear(force, dry_run),
             BufferAction::Enable { on, off } => commands::buffer_enable(on, off),
             BufferAction::Status => commands::buffer_status(),
         },
+        Commands::Spool { action } => match action {
+            SpoolAction::Flush { quiet } => commands::spool_flush(quiet),
+            SpoolAction::Status => commands::spool_status(),
+        },
+        Commands::Histfile { action } => match action {
+            HistfileAction::Import { paths, format, quiet } => commands::histfile_import(&paths, format.as_deref(), quiet),
+        },
         Commands::Remote { action } => match action {
-            RemoteAction::Add { name, remote_type, uri, read_only, credential_provider, no_auto_attach } => {
-                commands::remote_add(&name, &remote_type, &uri, read_only, credential_provider.as_deref(), !no_auto_attach)
+            RemoteAction::Add { name, remote_type, uri, read_only, credential_provider, no_auto_attach, sign, verify } => {
+                let signing = bird::SigningPolicy { sign, verify };
+                commands::remote_add(&name, &remote_type, &uri, read_only, credential_provider.as_deref(), !no_auto_attach, signing)
             },
             RemoteAction::List => commands::remote_list(),
             RemoteAction::Remove { name } => commands::remote_remove(&name),
             RemoteAction::Test { name } => commands::remote_test(name.as_deref()),
             RemoteAction::Attach { name } => commands::remote_attach(&name),
             RemoteAction::Status => commands::remote_status(),
+            RemoteAction::Key => commands::remote_key(),
+        },
+        Commands::Mount { path: Some(path), name } => commands::mount(&path, name.as_deref()),
+        Commands::Mount { path: None, .. } => commands::mount_list(),
+        Commands::Unmount { name } => commands::unmount(&name),
+        Commands::Setup { action } => match action {
+            SetupAction::Export { path } => commands::setup_export(&path),
+            SetupAction::Apply { path, dry_run } => commands::setup_apply(&path, dry_run),
+        },
+        Commands::Cron { action } => match action {
+            CronAction::Add { name, schedule, cwd, command } => {
+                commands::cron_add(&name, &schedule, cwd.as_deref(), &command)
+            }
+            CronAction::Remove { name } => commands::cron_remove(&name),
+            CronAction::Status { format, recent } => commands::cron_status(&format, recent),
+            CronAction::Run { name } => commands::cron_run(&name),
+            CronAction::Daemon => commands::cron_daemon(),
         },
         Commands::Push { remote, since, dry_run, blobs } => {
             commands::push(remote.as_deref(), since.as_deref(), dry_run, blobs)
@@ -785,14 +2078,91 @@ fn main() {
         Commands::Pull { remote, client, since, blobs } => {
             commands::pull(remote.as_deref(), client.as_deref(), since.as_deref(), blobs)
         },
-        Commands::Events { query, severity, count_only, lines, reparse, extract, format } => {
+        Commands::Feed { remote, since, all, mine, limit } => {
+            commands::feed(remote.as_deref(), &since, all, mine, limit)
+        }
+        Commands::Time { period, ago, project, gap, format } => {
+            commands::time(&period, ago, project, gap, &format)
+        }
+        Commands::Health { path, days } => commands::health(path.as_deref(), days),
+        Commands::Typos { days, limit, format } => commands::typos(days, limit, &format),
+        Commands::NotFound { command } => commands::not_found(&command),
+        Commands::Events {
+            query,
+            severity,
+            count_only,
+            lines,
+            reparse,
+            extract,
+            format,
+            ids,
+            mark_wrong,
+            note,
+            corrections,
+            fail_on,
+            latest_of_watch,
+        } => {
             // Parse lines: N (any), +N (first N), -N (last N)
             let (limit, order) = parse_lines_arg(&lines);
-            commands::events(&query, severity.as_deref(), count_only, limit, order, reparse, extract, format.as_deref())
+            let query = match latest_of_watch {
+                Some(watch_id) => commands::latest_of_watch_query(&watch_id),
+                None => query,
+            };
+            if let Some(event_id) = mark_wrong {
+                commands::mark_event_wrong(&event_id, note.as_deref())
+            } else if corrections {
+                commands::event_corrections(limit)
+            } else {
+                commands::events(
+                    &query,
+                    severity.as_deref(),
+                    count_only,
+                    limit,
+                    order,
+                    reparse,
+                    extract,
+                    format.as_deref(),
+                    ids,
+                    fail_on.as_deref(),
+                )
+                .map(|failed| failed_check = failed)
+            }
         }
         Commands::UpdateExtensions { dry_run } => commands::update_extensions(dry_run),
-        Commands::ExtractEvents { selector, format, quiet, force, all, since, limit, dry_run } => {
-            commands::extract_events(&selector, format.as_deref(), quiet, force, all, since.as_deref(), limit, dry_run)
+        Commands::Extensions { action } => match action {
+            ExtensionsAction::Bundle { dir } => commands::extensions_bundle(&dir),
+            ExtensionsAction::Install { from } => commands::extensions_install(&from),
+            ExtensionsAction::Status => commands::extensions_status(),
+        },
+        Commands::ExtractEvents {
+            selector,
+            format,
+            quiet,
+            force,
+            all,
+            matching,
+            reparse: _,
+            since,
+            limit,
+            dry_run,
+            explain,
+        } => {
+            match matching {
+                _ if explain => commands::explain_extraction(&selector, format.as_deref()),
+                Some(pattern) => {
+                    commands::reparse_events(&pattern, format.as_deref(), quiet, since.as_deref(), limit, dry_run)
+                }
+                None => commands::extract_events(
+                    &selector,
+                    format.as_deref(),
+                    quiet,
+                    force,
+                    all,
+                    since.as_deref(),
+                    limit,
+                    dry_run,
+                ),
+            }
         }
         Commands::Tutorial { lesson, list } => {
             if list {
@@ -803,9 +2173,60 @@ fn main() {
             }
         }
     };
+    drop(query_phase);
+    // Before the pager closes: waiting for the reader isn't shq's time
+    record_self_profile(&command_name, result.is_ok());
+    drop(pager);
 
     if let Err(e) = result {
         eprintln!("Error: {}", e);
+        if let bird::Error::InvalidQuery { query, diagnostics } = &e {
+            for diagnostic in diagnostics {
+                eprint!("{}", diagnostic.render(query));
+            }
+        }
+        if let Some(hint) = e.hint() {
+            eprintln!("hint: {}", hint);
+        }
+        // SQL errors can come from what a newer shq wrote to the store
+        if matches!(e, bird::Error::DuckDb(_)) {
+            if let Some(hint) = bird::Config::load().ok().and_then(|config| bird::version::store_mismatch_hint(&config)) {
+                eprintln!("hint: {}", hint);
+            }
+        }
+        // Conventional status for an interrupted command
+        let code = if matches!(e, bird::Error::Cancelled) { 130 } else { 1 };
+        std::process::exit(code);
+    }
+    if failed_check {
         std::process::exit(1);
     }
 }
+
+/// The subcommand `matches` ran, e.g. `invocations` or `hook status`.
+fn subcommand_path(matches: &clap::ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut matches = matches;
+    while let Some((name, sub)) = matches.subcommand() {
+        names.push(name);
+        matches = sub;
+    }
+    names.join(" ")
+}
+
+/// Append the command's phase timings to the self-profile log if
+/// `self_profile` is on. Never fails the command.
+fn record_self_profile(command: &str, ok: bool) {
+    let Some(times) = bird::profile::finish() else {
+        return;
+    };
+    let Ok(config) = bird::Config::load() else {
+        return;
+    };
+    if !config.self_profile || config.in_memory {
+        return;
+    }
+    if let Err(e) = bird::profile::record(&config, &bird::profile::ProfileRecord::new(command, &times, ok)) {
+        tracing::debug!("couldn't record the self-profile: {}", e);
+    }
+}
@@ -0,0 +1,79 @@
+//! Paging long listings, like git.
+//!
+//! When stdout is a terminal, `shq i`, `shq events` and `shq sql` send it
+//! through `$PAGER` (default `less -R`). Unless `LESS` is set, less gets `FRX`:
+//! it exits at once when the output fits on the screen, keeps colors and
+//! leaves the output on the screen. `--no-pager`, `PAGER=cat` or an empty
+//! `PAGER` turn paging off.
+
+use std::io::{IsTerminal, Write};
+use std::os::fd::AsRawFd;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// The pager command line: `$PAGER`, or `less -R`.
+pub fn command() -> String {
+    std::env::var("PAGER").unwrap_or_else(|_| "less -R".to_string())
+}
+
+/// Whether stdout goes to a pager (and so, in the end, to a terminal).
+pub fn active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// A running pager reading our stdout. Dropping it closes stdout and waits
+/// for the pager to exit.
+pub struct Pager {
+    child: Child,
+}
+
+/// Start the pager and point stdout at it, if stdout is a terminal and a
+/// pager is configured.
+pub fn start() -> Option<Pager> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let pager = command();
+    let pager = pager.trim();
+    if pager.is_empty() || pager == "cat" {
+        return None;
+    }
+
+    let mut child = Command::new("sh");
+    child.arg("-c").arg(pager).stdin(Stdio::piped());
+    if std::env::var_os("LESS").is_none() {
+        child.env("LESS", "FRX");
+    }
+    let mut child = child.spawn().ok()?;
+    let stdin = child.stdin.take()?;
+    // SAFETY: dup2 onto stdout from a pipe fd we own, which stays open for the
+    // call; resetting SIGPIPE to its default handler has no other preconditions
+    unsafe {
+        if libc::dup2(stdin.as_raw_fd(), libc::STDOUT_FILENO) < 0 {
+            drop(stdin);
+            let _ = child.wait();
+            return None;
+        }
+        // Quitting the pager early ends us quietly, as it would git
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+    drop(stdin);
+    ACTIVE.store(true, Ordering::Relaxed);
+    Some(Pager { child })
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        let _ = std::io::stdout().flush();
+        // The pager sees EOF once our last handle on the pipe is closed
+        // SAFETY: stdout is the dup'd pipe fd from `start`, flushed above;
+        // nothing writes to it after the pager has been dropped
+        unsafe {
+            libc::close(libc::STDOUT_FILENO);
+        }
+        let _ = self.child.wait();
+        ACTIVE.store(false, Ordering::Relaxed);
+    }
+}
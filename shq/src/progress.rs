@@ -0,0 +1,134 @@
+//! Terminal progress bars for long-running store operations.
+
+use std::io::{IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bird::{NoProgress, ProgressSink};
+
+/// Width of the bar itself, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// Sink for a store: a progress bar on stderr when it's a terminal and
+/// output isn't suppressed, otherwise nothing.
+pub fn sink(quiet: bool) -> Arc<dyn ProgressSink> {
+    if !quiet && std::io::stderr().is_terminal() {
+        Arc::new(TerminalProgress::default())
+    } else {
+        Arc::new(NoProgress)
+    }
+}
+
+#[derive(Default)]
+struct Phase {
+    name: String,
+    total: Option<u64>,
+    done: u64,
+    message: String,
+    started: Option<Instant>,
+}
+
+/// Renders each phase as a single line on stderr, redrawn in place.
+#[derive(Default)]
+pub struct TerminalProgress {
+    phase: Mutex<Phase>,
+}
+
+impl TerminalProgress {
+    fn draw(phase: &Phase) {
+        let elapsed = phase.started.map(|s| s.elapsed()).unwrap_or_default();
+        let line = render(&phase.name, phase.done, phase.total, elapsed, &phase.message);
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K{}", line);
+        let _ = stderr.flush();
+    }
+}
+
+impl ProgressSink for TerminalProgress {
+    fn start(&self, name: &str, total: Option<u64>) {
+        let mut phase = self.phase.lock().unwrap_or_else(|e| e.into_inner());
+        *phase = Phase {
+            name: name.to_string(),
+            total,
+            started: Some(Instant::now()),
+            ..Default::default()
+        };
+        Self::draw(&phase);
+    }
+
+    fn advance(&self, steps: u64) {
+        let mut phase = self.phase.lock().unwrap_or_else(|e| e.into_inner());
+        phase.done += steps;
+        Self::draw(&phase);
+    }
+
+    fn message(&self, message: &str) {
+        let mut phase = self.phase.lock().unwrap_or_else(|e| e.into_inner());
+        phase.message = message.to_string();
+        Self::draw(&phase);
+    }
+
+    fn finish(&self) {
+        let phase = self.phase.lock().unwrap_or_else(|e| e.into_inner());
+        // Empty phases (nothing to do) are cleared rather than left behind
+        if phase.total == Some(0) {
+            eprint!("\r\x1b[2K");
+        } else {
+            eprintln!();
+        }
+    }
+}
+
+/// One progress line: `phase [####------] done/total, message, ~ETA`.
+fn render(name: &str, done: u64, total: Option<u64>, elapsed: Duration, message: &str) -> String {
+    let mut line = match total {
+        Some(total) => {
+            let filled = if total == 0 {
+                BAR_WIDTH
+            } else {
+                BAR_WIDTH * done.min(total) as usize / total as usize
+            };
+            format!(
+                "{} [{}{}] {}/{}",
+                name,
+                "#".repeat(filled),
+                "-".repeat(BAR_WIDTH - filled),
+                done,
+                total
+            )
+        }
+        None => format!("{} ({} done)", name, done),
+    };
+
+    if !message.is_empty() {
+        line.push_str(", ");
+        line.push_str(message);
+    }
+
+    if let Some(total) = total {
+        if done > 0 && done < total {
+            let remaining = elapsed.mul_f64((total - done) as f64 / done as f64);
+            line.push_str(&format!(", ~{}s left", remaining.as_secs()));
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let line = render("compact recent/outputs", 5, Some(10), Duration::from_secs(4), "");
+        assert_eq!(
+            line,
+            format!("compact recent/outputs [{}{}] 5/10, ~4s left", "#".repeat(15), "-".repeat(15))
+        );
+
+        let line = render("extract events", 10, Some(10), Duration::from_secs(4), "42 events");
+        assert!(line.ends_with("10/10, 42 events"));
+
+        assert_eq!(render("migrate schema", 0, None, Duration::ZERO, ""), "migrate schema (0 done)");
+    }
+}
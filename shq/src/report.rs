@@ -0,0 +1,386 @@
+//! Static HTML report (`shq report --html`).
+//!
+//! Renders one self-contained `index.html`: no external scripts, styles or
+//! fonts, so the file can be mailed around or archived with a retrospective.
+//! Summaries are rendered as HTML; the full invocation table is embedded as
+//! JSON and filtered by a few lines of inline script.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use bird::{DailyCount, InvocationSummary};
+use chrono::{Datelike, NaiveDate, TimeDelta};
+
+/// How many rows the failure and slowest-command summaries show.
+const SUMMARY_ROWS: usize = 15;
+
+/// Everything a report shows.
+pub struct Report {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub generated: String,
+    pub days: Vec<DailyCount>,
+    /// Invocations in the window, most recent first.
+    pub invocations: Vec<InvocationSummary>,
+    /// True if `invocations` stopped at the row limit.
+    pub truncated: bool,
+}
+
+/// Failing commands grouped by command line.
+#[derive(Debug, PartialEq)]
+pub struct FailureGroup {
+    pub cmd: String,
+    pub count: usize,
+    pub last_exit_code: i32,
+    pub last_seen: String,
+}
+
+/// Group failed invocations by command, most frequent first.
+pub fn failure_groups(invocations: &[InvocationSummary]) -> Vec<FailureGroup> {
+    let mut groups: Vec<FailureGroup> = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    // Most recent first, so the first failure seen is the last one run
+    for inv in invocations.iter().filter(|i| i.exit_code != 0) {
+        match index.get(inv.cmd.as_str()) {
+            Some(&i) => groups[i].count += 1,
+            None => {
+                index.insert(&inv.cmd, groups.len());
+                groups.push(FailureGroup {
+                    cmd: inv.cmd.clone(),
+                    count: 1,
+                    last_exit_code: inv.exit_code,
+                    last_seen: inv.timestamp.clone(),
+                });
+            }
+        }
+    }
+    groups.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| b.last_seen.cmp(&a.last_seen)));
+    groups
+}
+
+/// The longest-running invocations, slowest first.
+pub fn slowest(invocations: &[InvocationSummary]) -> Vec<&InvocationSummary> {
+    let mut timed: Vec<&InvocationSummary> = invocations.iter().filter(|i| i.duration_ms.is_some()).collect();
+    timed.sort_by_key(|i| std::cmp::Reverse(i.duration_ms));
+    timed.truncate(SUMMARY_ROWS);
+    timed
+}
+
+/// GitHub-style calendar heatmap of `days` as an SVG document.
+pub fn heatmap_svg(start: NaiveDate, end: NaiveDate, days: &[DailyCount]) -> String {
+    const COLORS: [&str; 5] = ["#ebedf0", "#9be9a8", "#40c463", "#30a14e", "#216e39"];
+    const STEP: usize = 13;
+    const LEFT: usize = 30;
+    const TOP: usize = 20;
+
+    let counts: HashMap<NaiveDate, i64> = days.iter().map(|d| (d.date, d.count)).collect();
+    let max = days.iter().map(|d| d.count).max().unwrap_or(0);
+    let level = |count: i64| -> usize {
+        if count <= 0 || max == 0 {
+            0
+        } else {
+            ((count * 4 + max - 1) / max).clamp(1, 4) as usize
+        }
+    };
+
+    // Columns are weeks starting on Sunday
+    let grid_start = start - TimeDelta::days(start.weekday().num_days_from_sunday() as i64);
+    let weeks = ((end - grid_start).num_days() / 7 + 1) as usize;
+    let cell = |week: usize, weekday: usize| -> Option<NaiveDate> {
+        let date = grid_start + TimeDelta::days((week * 7 + weekday) as i64);
+        (date >= start && date <= end).then_some(date)
+    };
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="9">"#,
+        LEFT + weeks * STEP + 10,
+        TOP + 7 * STEP + 10
+    );
+    for (weekday, label) in [(1, "Mon"), (3, "Wed"), (5, "Fri")] {
+        let _ = writeln!(svg, r#"  <text x="0" y="{}" fill="gray">{}</text>"#, TOP + weekday * STEP + 9, label);
+    }
+    let mut last_month = None;
+    for week in 0..weeks {
+        if let Some(date) = (0..7).find_map(|d| cell(week, d)) {
+            if last_month != Some(date.month()) {
+                last_month = Some(date.month());
+                let _ = writeln!(
+                    svg,
+                    r#"  <text x="{}" y="{}" fill="gray">{}</text>"#,
+                    LEFT + week * STEP,
+                    TOP - 6,
+                    date.format("%b")
+                );
+            }
+        }
+        for weekday in 0..7 {
+            if let Some(date) = cell(week, weekday) {
+                let count = counts.get(&date).copied().unwrap_or(0);
+                let _ = writeln!(
+                    svg,
+                    r#"  <rect x="{}" y="{}" width="11" height="11" rx="2" fill="{}"><title>{}: {} invocations</title></rect>"#,
+                    LEFT + week * STEP,
+                    TOP + weekday * STEP,
+                    COLORS[level(count)],
+                    date,
+                    count
+                );
+            }
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Escape text for HTML element content and attribute values.
+pub fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Serialize `value` for a `<script type="application/json">` block.
+///
+/// `<` is escaped so a command containing `</script>` can't end the block.
+fn embed_json(value: &serde_json::Value) -> String {
+    serde_json::to_string(value).unwrap_or_default().replace('<', "\\u003c")
+}
+
+pub fn format_duration(ms: Option<i64>) -> String {
+    match ms {
+        None => "-".to_string(),
+        Some(ms) if ms < 1000 => format!("{}ms", ms),
+        Some(ms) if ms < 60_000 => format!("{:.1}s", ms as f64 / 1000.0),
+        Some(ms) => format!("{}m{:02}s", ms / 60_000, (ms % 60_000) / 1000),
+    }
+}
+
+/// Timestamps as stored, trimmed to the second.
+fn short_time(ts: &str) -> &str {
+    ts.get(..19).unwrap_or(ts)
+}
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 2em auto; max-width: 72em; padding: 0 1em; color: #24292f; }
+h1 { font-size: 1.6em; margin-bottom: 0.2em; }
+h2 { font-size: 1.2em; margin-top: 2em; border-bottom: 1px solid #d0d7de; padding-bottom: 0.3em; }
+.meta { color: #57606a; }
+.stats { display: flex; gap: 2em; margin: 1.5em 0; }
+.stat b { display: block; font-size: 1.6em; }
+table { border-collapse: collapse; width: 100%; font-size: 0.9em; }
+th, td { text-align: left; padding: 0.3em 0.6em; border-bottom: 1px solid #eaeef2; vertical-align: top; }
+td.num, th.num { text-align: right; white-space: nowrap; }
+td.cmd { font-family: ui-monospace, Menlo, Consolas, monospace; word-break: break-all; }
+tr.fail td.exit { color: #cf222e; font-weight: bold; }
+input[type=search] { width: 100%; padding: 0.4em; font-size: 1em; margin-bottom: 0.5em; box-sizing: border-box; }
+.heatmap { overflow-x: auto; }
+"#;
+
+const SCRIPT: &str = r#"
+(function () {
+  var rows = JSON.parse(document.getElementById('invocations').textContent);
+  var body = document.querySelector('#table tbody');
+  var count = document.getElementById('count');
+  var search = document.getElementById('search');
+  var failedOnly = document.getElementById('failed');
+  function cell(tr, text, cls) {
+    var td = document.createElement('td');
+    td.textContent = text;
+    if (cls) td.className = cls;
+    tr.appendChild(td);
+  }
+  function render() {
+    var terms = search.value.toLowerCase().split(/\s+/).filter(Boolean);
+    var shown = 0;
+    body.textContent = '';
+    rows.forEach(function (r) {
+      if (failedOnly.checked && r.exit_code === 0) return;
+      var hay = (r.cmd + ' ' + r.timestamp + ' ' + r.exit_code).toLowerCase();
+      if (!terms.every(function (t) { return hay.indexOf(t) >= 0; })) return;
+      shown++;
+      if (shown > 1000) return;
+      var tr = document.createElement('tr');
+      if (r.exit_code !== 0) tr.className = 'fail';
+      cell(tr, r.timestamp.slice(0, 19));
+      cell(tr, r.cmd, 'cmd');
+      cell(tr, String(r.exit_code), 'num exit');
+      cell(tr, r.duration, 'num');
+      body.appendChild(tr);
+    });
+    count.textContent = shown + ' of ' + rows.length + ' invocations' + (shown > 1000 ? ' (first 1000 shown)' : '');
+  }
+  search.addEventListener('input', render);
+  failedOnly.addEventListener('change', render);
+  render();
+})();
+"#;
+
+/// Render the report as a self-contained HTML page.
+pub fn render(report: &Report) -> String {
+    let invocations = &report.invocations;
+    let total = invocations.len();
+    let failed = invocations.iter().filter(|i| i.exit_code != 0).count();
+    let total_ms: i64 = invocations.iter().filter_map(|i| i.duration_ms).sum();
+    let title = format!("Shell activity {} to {}", report.start, report.end);
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<p class=\"meta\">Generated by shq on {generated}{truncated}</p>\n",
+        title = escape_html(&title),
+        generated = escape_html(&report.generated),
+        truncated = if report.truncated {
+            format!(" &middot; only the {} most recent invocations are included", total)
+        } else {
+            String::new()
+        },
+    );
+
+    let _ = write!(
+        html,
+        "<div class=\"stats\">\n<div class=\"stat\"><b>{}</b>invocations</div>\n<div class=\"stat\"><b>{}</b>failed</div>\n<div class=\"stat\"><b>{}</b>active days</div>\n<div class=\"stat\"><b>{}</b>total run time</div>\n</div>\n",
+        total,
+        failed,
+        report.days.len(),
+        format_duration(Some(total_ms)),
+    );
+
+    html.push_str("<h2>Activity</h2>\n<div class=\"heatmap\">\n");
+    html.push_str(&heatmap_svg(report.start, report.end, &report.days));
+    html.push_str("\n</div>\n");
+
+    html.push_str("<h2>Failures</h2>\n");
+    let failures = failure_groups(invocations);
+    if failures.is_empty() {
+        html.push_str("<p>No failed commands.</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>Command</th><th class=\"num\">Failures</th><th class=\"num\">Last exit</th><th>Last failed</th></tr>\n");
+        for group in failures.iter().take(SUMMARY_ROWS) {
+            let _ = writeln!(
+                html,
+                "<tr class=\"fail\"><td class=\"cmd\">{}</td><td class=\"num\">{}</td><td class=\"num exit\">{}</td><td>{}</td></tr>",
+                escape_html(&group.cmd),
+                group.count,
+                group.last_exit_code,
+                escape_html(short_time(&group.last_seen))
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Slowest commands</h2>\n");
+    let slow = slowest(invocations);
+    if slow.is_empty() {
+        html.push_str("<p>No timed commands.</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>Command</th><th class=\"num\">Duration</th><th class=\"num\">Exit</th><th>Started</th></tr>\n");
+        for inv in slow {
+            let _ = writeln!(
+                html,
+                "<tr{}><td class=\"cmd\">{}</td><td class=\"num\">{}</td><td class=\"num exit\">{}</td><td>{}</td></tr>",
+                if inv.exit_code != 0 { " class=\"fail\"" } else { "" },
+                escape_html(&inv.cmd),
+                format_duration(inv.duration_ms),
+                inv.exit_code,
+                escape_html(short_time(&inv.timestamp))
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    let rows: Vec<serde_json::Value> = invocations
+        .iter()
+        .map(|inv| {
+            serde_json::json!({
+                "id": inv.id,
+                "cmd": inv.cmd,
+                "exit_code": inv.exit_code,
+                "timestamp": inv.timestamp,
+                "duration_ms": inv.duration_ms,
+                "duration": format_duration(inv.duration_ms),
+            })
+        })
+        .collect();
+    let _ = write!(
+        html,
+        "<h2>All invocations</h2>\n<input type=\"search\" id=\"search\" placeholder=\"Filter commands...\">\n<label><input type=\"checkbox\" id=\"failed\"> Failed only</label> <span id=\"count\" class=\"meta\"></span>\n<table id=\"table\">\n<thead><tr><th>Started</th><th>Command</th><th class=\"num\">Exit</th><th class=\"num\">Duration</th></tr></thead>\n<tbody></tbody>\n</table>\n<script type=\"application/json\" id=\"invocations\">{}</script>\n<script>{}</script>\n</body>\n</html>\n",
+        embed_json(&serde_json::Value::Array(rows)),
+        SCRIPT
+    );
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inv(cmd: &str, exit_code: i32, timestamp: &str, duration_ms: Option<i64>) -> InvocationSummary {
+        InvocationSummary {
+            id: format!("id-{}", timestamp),
+            cmd: cmd.to_string(),
+            exit_code,
+            timestamp: timestamp.to_string(),
+            duration_ms,
+            cwd: "/tmp".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_failure_groups_and_slowest() {
+        let invs = vec![
+            inv("make test", 2, "2026-03-03 10:00:00", Some(40_000)),
+            inv("ls", 0, "2026-03-02 10:00:00", Some(3)),
+            inv("cargo build", 101, "2026-03-02 09:00:00", None),
+            inv("make test", 1, "2026-03-01 10:00:00", Some(35_000)),
+        ];
+
+        let groups = failure_groups(&invs);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(
+            groups[0],
+            FailureGroup {
+                cmd: "make test".to_string(),
+                count: 2,
+                last_exit_code: 2,
+                last_seen: "2026-03-03 10:00:00".to_string(),
+            }
+        );
+
+        let slow: Vec<&str> = slowest(&invs).iter().map(|i| i.timestamp.as_str()).collect();
+        assert_eq!(slow, ["2026-03-03 10:00:00", "2026-03-01 10:00:00", "2026-03-02 10:00:00"]);
+    }
+
+    #[test]
+    fn test_render_escapes_commands() {
+        let day = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        let report = Report {
+            start: day - TimeDelta::days(13),
+            end: day,
+            generated: "2026-03-02 12:00".to_string(),
+            days: vec![DailyCount { date: day, count: 1, failed: 1 }],
+            invocations: vec![inv("echo '</script><b>x</b>'", 1, "2026-03-02 10:00:00.123", Some(1200))],
+            truncated: false,
+        };
+        let html = render(&report);
+
+        assert!(html.contains("<svg"));
+        assert!(html.contains("2026-03-02: 1 invocations"));
+        assert!(html.contains("echo &#39;&lt;/script&gt;&lt;b&gt;x&lt;/b&gt;&#39;"));
+        // Only the page's own script blocks are closed
+        assert_eq!(html.matches("</script>").count(), 2);
+        assert!(html.contains(r#""duration":"1.2s""#));
+        // Self-contained: nothing loaded from elsewhere
+        assert!(!html.contains("src=") && !html.contains("href="));
+    }
+}
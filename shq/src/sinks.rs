@@ -0,0 +1,194 @@
+//! Extra destinations for a captured run's output (`shq run --tee FILE`,
+//! `--tee-fd N`, `--tee-socket PATH`).
+//!
+//! Piping a run through `| tee build.log` takes the terminal away from it,
+//! so the command loses its colors and shq captures nothing useful. Sinks
+//! instead receive every chunk the PTY (or the pipes, with `--no-pty`)
+//! produces, alongside the terminal and the store.
+//!
+//! A sink that fails (a full disk, a reader that went away) is dropped with
+//! a warning; it never interrupts the command or its capture.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// A destination for captured output.
+pub trait Sink: Send {
+    /// Where the output goes, for warnings.
+    fn describe(&self) -> String;
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()>;
+}
+
+/// A file or inherited file descriptor.
+struct FileSink {
+    name: String,
+    file: File,
+}
+
+impl Sink for FileSink {
+    fn describe(&self) -> String {
+        self.name.clone()
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.file.write_all(data)?;
+        self.file.flush()
+    }
+}
+
+/// A listening Unix socket.
+#[cfg(unix)]
+struct SocketSink {
+    name: String,
+    stream: std::os::unix::net::UnixStream,
+}
+
+#[cfg(unix)]
+impl Sink for SocketSink {
+    fn describe(&self) -> String {
+        self.name.clone()
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.stream.write_all(data)
+    }
+}
+
+/// The sinks of one run.
+#[derive(Default)]
+pub struct Sinks {
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+impl Sinks {
+    /// Open the sinks given on the command line. Files are appended to
+    /// (created owner-only); descriptors must already be open for writing,
+    /// as with `3>build.log`.
+    pub fn open(files: &[std::path::PathBuf], fds: &[i32], sockets: &[std::path::PathBuf]) -> bird::Result<Self> {
+        let mut sinks = Self::default();
+        for path in files {
+            sinks.push(open_file(path)?);
+        }
+        for &fd in fds {
+            sinks.push(open_fd(fd)?);
+        }
+        for path in sockets {
+            sinks.push(open_socket(path)?);
+        }
+        Ok(sinks)
+    }
+
+    fn push(&mut self, sink: Box<dyn Sink>) {
+        self.sinks.push(sink);
+    }
+
+    /// Send `data` to every sink, dropping those that fail.
+    pub fn write(&mut self, data: &[u8]) {
+        self.sinks.retain_mut(|sink| match sink.write(data) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("shq: stopped writing to {}: {}", sink.describe(), e);
+                false
+            }
+        });
+    }
+}
+
+fn open_file(path: &Path) -> bird::Result<Box<dyn Sink>> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(bird::perms::FILE_MODE);
+    }
+    let file = options
+        .open(path)
+        .map_err(|e| bird::Error::Config(format!("--tee {}: {}", path.display(), e)))?;
+    Ok(Box::new(FileSink { name: path.display().to_string(), file }))
+}
+
+#[cfg(unix)]
+fn open_fd(fd: i32) -> bird::Result<Box<dyn Sink>> {
+    use std::os::unix::io::FromRawFd;
+
+    if fd <= libc::STDERR_FILENO {
+        return Err(bird::Error::Config(format!(
+            "--tee-fd {}: stdin, stdout and stderr already get the output; redirect another descriptor (3>FILE)",
+            fd
+        )));
+    }
+    // Our own copy, so closing the sink leaves the caller's descriptor alone
+    // SAFETY: dup only reads the descriptor table
+    let copy = unsafe { libc::dup(fd) };
+    if copy < 0 {
+        return Err(bird::Error::Config(format!(
+            "--tee-fd {}: not an open file descriptor (redirect one with {}>FILE)",
+            fd, fd
+        )));
+    }
+    // SAFETY: `copy` is a fresh descriptor nothing else owns
+    let file = unsafe { File::from_raw_fd(copy) };
+    Ok(Box::new(FileSink { name: format!("fd {}", fd), file }))
+}
+
+#[cfg(not(unix))]
+fn open_fd(fd: i32) -> bird::Result<Box<dyn Sink>> {
+    Err(bird::Error::Config(format!("--tee-fd {}: not supported on this platform", fd)))
+}
+
+#[cfg(unix)]
+fn open_socket(path: &Path) -> bird::Result<Box<dyn Sink>> {
+    let stream = std::os::unix::net::UnixStream::connect(path)
+        .map_err(|e| bird::Error::Config(format!("--tee-socket {}: {}", path.display(), e)))?;
+    Ok(Box::new(SocketSink { name: path.display().to_string(), stream }))
+}
+
+#[cfg(not(unix))]
+fn open_socket(path: &Path) -> bird::Result<Box<dyn Sink>> {
+    Err(bird::Error::Config(format!("--tee-socket {}: not supported on this platform", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_sinks_append() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("build.log");
+        std::fs::write(&path, "before\n").unwrap();
+
+        let mut sinks = Sinks::open(std::slice::from_ref(&path), &[], &[]).unwrap();
+        sinks.write(b"compiling\n");
+        sinks.write(b"done\n");
+        drop(sinks);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "before\ncompiling\ndone\n");
+    }
+
+    #[test]
+    fn test_failing_sink_is_dropped() {
+        struct Broken;
+        impl Sink for Broken {
+            fn describe(&self) -> String {
+                "broken".to_string()
+            }
+            fn write(&mut self, _: &[u8]) -> std::io::Result<()> {
+                Err(std::io::Error::other("gone"))
+            }
+        }
+
+        let mut sinks = Sinks::default();
+        sinks.push(Box::new(Broken));
+        sinks.write(b"x");
+        assert!(sinks.sinks.is_empty());
+    }
+
+    #[test]
+    fn test_bad_fd_is_rejected() {
+        assert!(Sinks::open(&[], &[1], &[]).is_err());
+        assert!(Sinks::open(&[], &[987_654], &[]).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+//! Shaping listings for scripts: `--columns id,time,cmd` picks and orders
+//! table columns, `-f template='{id}\t{cmd}'` prints one formatted line per
+//! row.
+
+/// A listing row whose fields can be picked by name.
+pub trait Row {
+    /// Names of the fields, in their default order.
+    const FIELDS: &'static [&'static str];
+
+    /// The value of `field` (one of [`Row::FIELDS`]).
+    fn field(&self, field: &str) -> String;
+}
+
+/// Parse a comma-separated list of field names.
+pub fn parse_columns<R: Row>(spec: &str) -> bird::Result<Vec<&'static str>> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(field_name::<R>)
+        .collect::<bird::Result<Vec<_>>>()
+        .and_then(|columns| {
+            if columns.is_empty() {
+                Err(bird::Error::Config("No columns given".to_string()))
+            } else {
+                Ok(columns)
+            }
+        })
+}
+
+fn field_name<R: Row>(name: &str) -> bird::Result<&'static str> {
+    let name = name.to_lowercase();
+    R::FIELDS.iter().copied().find(|f| *f == name).ok_or_else(|| {
+        bird::Error::Config(format!("Unknown column '{}' (expected one of: {})", name, R::FIELDS.join(", ")))
+    })
+}
+
+/// Print `rows` as a table of `columns`, with a header. Every column but
+/// the last is padded to its widest value.
+pub fn print_table<R: Row>(rows: &[R], columns: &[&str]) {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| columns.iter().map(|c| row.field(c)).collect())
+        .collect();
+    let header: Vec<String> = columns.iter().map(|c| c.to_uppercase()).collect();
+
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            std::iter::once(&header)
+                .chain(&cells)
+                .map(|line| line[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    for line in std::iter::once(&header).chain(&cells) {
+        let last = line.len() - 1;
+        let text: Vec<String> = line
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| if i == last { cell.clone() } else { format!("{:<1$}", cell, widths[i]) })
+            .collect();
+        println!("{}", text.join("  "));
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field(&'static str),
+}
+
+/// A line template: `{field}` placeholders between literal text, with
+/// `{{`/`}}` for braces and `\t`/`\n` for tab and newline.
+#[derive(Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse<R: Row>(template: &str) -> bird::Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.next_if_eq(&'{').is_some() => text.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => text.push('}'),
+                '\\' if chars.next_if_eq(&'t').is_some() => text.push('\t'),
+                '\\' if chars.next_if_eq(&'n').is_some() => text.push('\n'),
+                '{' => {
+                    let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(field_name::<R>(name.trim())?));
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template { parts })
+    }
+
+    pub fn render<R: Row>(&self, row: &R) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Field(field) => row.field(field),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Pair(&'static str, i32);
+
+    impl Row for Pair {
+        const FIELDS: &'static [&'static str] = &["name", "count"];
+
+        fn field(&self, field: &str) -> String {
+            match field {
+                "name" => self.0.to_string(),
+                _ => self.1.to_string(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_columns() {
+        assert_eq!(parse_columns::<Pair>("count, NAME").unwrap(), vec!["count", "name"]);
+        assert!(parse_columns::<Pair>("name,size").is_err());
+        assert!(parse_columns::<Pair>(" , ").is_err());
+    }
+
+    #[test]
+    fn test_template() {
+        let template = Template::parse::<Pair>(r"{name}\t{count} {{x}}").unwrap();
+        assert_eq!(template.render(&Pair("make", 3)), "make\t3 {x}");
+        assert!(Template::parse::<Pair>("{size}").is_err());
+    }
+}
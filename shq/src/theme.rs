@@ -0,0 +1,236 @@
+//! Colors and glyphs for terminal output.
+//!
+//! Set by `[display]` in config.toml: `theme` turns color on or off
+//! (`auto` colors a terminal unless `NO_COLOR` is set or `TERM=dumb`),
+//! `charset` picks Unicode or ASCII glyphs (`auto` follows the locale) and
+//! `[display.glyphs]` replaces single glyphs.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+use bird::{Charset, Config, DisplayConfig, DisplayTheme};
+
+pub const RED: &str = "\x1b[31m";
+pub const GREEN: &str = "\x1b[32m";
+pub const YELLOW: &str = "\x1b[33m";
+pub const MAGENTA: &str = "\x1b[35m";
+pub const BOLD: &str = "\x1b[1m";
+pub const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Theme for stdout, from the config (or the defaults without one).
+pub fn current() -> &'static Theme {
+    THEME.get_or_init(|| {
+        let display = Config::load().map(|c| c.display).unwrap_or_default();
+        let terminal = std::io::stdout().is_terminal() || crate::pager::active();
+        Theme::new(&display, terminal, &Env::from_process())
+    })
+}
+
+/// Glyphs for command status, output capture and markers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glyphs {
+    pub ok: String,
+    pub fail: String,
+    /// Some runs succeeded, some failed
+    pub mixed: String,
+    /// Still running, or no exit code
+    pub pending: String,
+    pub marker: String,
+    /// Output captured merged, streams can't be separated
+    pub out_combined: String,
+    pub out_both: String,
+    pub out_stdout: String,
+    pub out_stderr: String,
+    /// Captured, but empty
+    pub out_empty: String,
+    /// Not captured
+    pub out_none: String,
+}
+
+impl Glyphs {
+    fn unicode() -> Self {
+        Self::from_strs(["✓", "✗", "~", "…", "◆", "◉", "●", "◐", "◑", "○", "·"])
+    }
+
+    fn ascii() -> Self {
+        Self::from_strs(["+", "x", "~", "?", "*", "C", "B", "O", "E", "-", "."])
+    }
+
+    fn from_strs(glyphs: [&str; 11]) -> Self {
+        let [ok, fail, mixed, pending, marker, out_combined, out_both, out_stdout, out_stderr, out_empty, out_none] =
+            glyphs.map(String::from);
+        Glyphs {
+            ok,
+            fail,
+            mixed,
+            pending,
+            marker,
+            out_combined,
+            out_both,
+            out_stdout,
+            out_stderr,
+            out_empty,
+            out_none,
+        }
+    }
+
+    /// Apply `[display.glyphs]` overrides; unknown names are ignored.
+    fn with_overrides(mut self, overrides: &BTreeMap<String, String>) -> Self {
+        for (name, glyph) in overrides {
+            let slot = match name.as_str() {
+                "ok" => &mut self.ok,
+                "fail" => &mut self.fail,
+                "mixed" => &mut self.mixed,
+                "pending" => &mut self.pending,
+                "marker" => &mut self.marker,
+                "out_combined" => &mut self.out_combined,
+                "out_both" => &mut self.out_both,
+                "out_stdout" => &mut self.out_stdout,
+                "out_stderr" => &mut self.out_stderr,
+                "out_empty" => &mut self.out_empty,
+                "out_none" => &mut self.out_none,
+                _ => continue,
+            };
+            *slot = glyph.clone();
+        }
+        self
+    }
+}
+
+/// The environment variables the theme depends on.
+#[derive(Debug, Default)]
+struct Env {
+    no_color: bool,
+    dumb_terminal: bool,
+    /// First of `LC_ALL`, `LC_CTYPE` and `LANG` that is set
+    locale: Option<String>,
+}
+
+impl Env {
+    fn from_process() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Env {
+            no_color: var("NO_COLOR").is_some(),
+            dumb_terminal: var("TERM").as_deref() == Some("dumb"),
+            locale: ["LC_ALL", "LC_CTYPE", "LANG"].into_iter().find_map(var),
+        }
+    }
+
+    /// Without a locale, assume a UTF-8 terminal rather than the C locale.
+    fn utf8(&self) -> bool {
+        self.locale.as_deref().is_none_or(|locale| {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+    }
+}
+
+/// Resolved display settings.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    color: bool,
+    unicode: bool,
+    pub glyphs: Glyphs,
+}
+
+impl Theme {
+    fn new(display: &DisplayConfig, terminal: bool, env: &Env) -> Self {
+        let color = match display.theme {
+            DisplayTheme::Color => true,
+            DisplayTheme::Plain => false,
+            DisplayTheme::Auto => terminal && !env.no_color && !env.dumb_terminal,
+        };
+        let unicode = match display.charset {
+            Charset::Unicode => true,
+            Charset::Ascii => false,
+            Charset::Auto => env.utf8(),
+        };
+        let glyphs = if unicode { Glyphs::unicode() } else { Glyphs::ascii() };
+        Theme { color, unicode, glyphs: glyphs.with_overrides(&display.glyphs) }
+    }
+
+    /// Whether output is colored.
+    pub fn color(&self) -> bool {
+        self.color
+    }
+
+    /// Whether the charset is Unicode (glyph overrides aside).
+    pub fn unicode(&self) -> bool {
+        self.unicode
+    }
+
+    /// `text` in `style` (one of the color constants), or as is without color.
+    pub fn paint(&self, style: &str, text: impl Display) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Glyph for an exit code: ok, fail, or pending without one.
+    pub fn status(&self, exit_code: Option<i32>) -> String {
+        match exit_code {
+            Some(0) => self.paint(GREEN, &self.glyphs.ok),
+            Some(_) => self.paint(RED, &self.glyphs.fail),
+            None => self.paint(YELLOW, &self.glyphs.pending),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(locale: Option<&str>) -> Env {
+        Env { locale: locale.map(String::from), ..Env::default() }
+    }
+
+    #[test]
+    fn test_color_follows_terminal_and_no_color() {
+        let display = DisplayConfig::default();
+        assert!(Theme::new(&display, true, &env(None)).color());
+        assert!(!Theme::new(&display, false, &env(None)).color());
+        let no_color = Env { no_color: true, ..Env::default() };
+        assert!(!Theme::new(&display, true, &no_color).color());
+
+        // An explicit theme wins
+        let display = DisplayConfig { theme: DisplayTheme::Color, ..DisplayConfig::default() };
+        assert!(Theme::new(&display, false, &no_color).color());
+        let display = DisplayConfig { theme: DisplayTheme::Plain, ..DisplayConfig::default() };
+        let theme = Theme::new(&display, true, &env(None));
+        assert_eq!(theme.paint(RED, "x"), "x");
+        assert_eq!(theme.status(Some(1)), "✗");
+    }
+
+    #[test]
+    fn test_glyphs_follow_locale() {
+        let display = DisplayConfig::default();
+        assert_eq!(Theme::new(&display, false, &env(Some("en_US.UTF-8"))).glyphs.ok, "✓");
+        assert_eq!(Theme::new(&display, false, &env(Some("C"))).glyphs.ok, "+");
+        assert_eq!(Theme::new(&display, false, &env(None)).glyphs.out_both, "●");
+
+        let display = DisplayConfig { charset: Charset::Ascii, ..DisplayConfig::default() };
+        let glyphs = Theme::new(&display, false, &env(Some("en_US.UTF-8"))).glyphs;
+        assert_eq!(glyphs.out_stdout, "O");
+        assert!([glyphs.ok, glyphs.fail, glyphs.out_combined, glyphs.out_none].iter().all(|g| g.is_ascii()));
+    }
+
+    #[test]
+    fn test_glyph_overrides() {
+        let glyphs = [("ok", "OK"), ("unknown", "?")];
+        let display = DisplayConfig {
+            charset: Charset::Ascii,
+            glyphs: glyphs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            ..DisplayConfig::default()
+        };
+        let glyphs = Theme::new(&display, false, &env(None)).glyphs;
+        assert_eq!(glyphs.ok, "OK");
+        assert_eq!(glyphs.fail, "x");
+    }
+}
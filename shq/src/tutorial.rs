@@ -260,7 +260,7 @@ pub fn run_tutorial(lesson_selector: Option<&str>) -> bird::Result<()> {
     };
 
     // Enable raw mode for keypress detection
-    terminal::enable_raw_mode().map_err(|e| bird::Error::Io(io::Error::new(io::ErrorKind::Other, e)))?;
+    terminal::enable_raw_mode().map_err(|e| bird::Error::Io(io::Error::other(e)))?;
 
     let result = run_lessons(&lessons_to_run);
 
@@ -300,16 +300,16 @@ fn execute_step(stdout: &mut io::Stdout, step: &Step) -> bird::Result<bool> {
         }
         Step::Explain(text) => {
             // Temporarily disable raw mode for proper text display
-            terminal::disable_raw_mode().map_err(|e| bird::Error::Io(io::Error::new(io::ErrorKind::Other, e)))?;
+            terminal::disable_raw_mode().map_err(|e| bird::Error::Io(io::Error::other(e)))?;
             stdout.execute(SetForegroundColor(Color::White))?;
             println!("\n{}", text);
             stdout.execute(ResetColor)?;
             stdout.flush()?;
-            terminal::enable_raw_mode().map_err(|e| bird::Error::Io(io::Error::new(io::ErrorKind::Other, e)))?;
+            terminal::enable_raw_mode().map_err(|e| bird::Error::Io(io::Error::other(e)))?;
         }
         Step::RunCommand(cmd) => {
             // Show the command being run
-            terminal::disable_raw_mode().map_err(|e| bird::Error::Io(io::Error::new(io::ErrorKind::Other, e)))?;
+            terminal::disable_raw_mode().map_err(|e| bird::Error::Io(io::Error::other(e)))?;
             stdout.execute(SetForegroundColor(Color::Yellow))?;
             println!("\n$ {}", cmd);
             stdout.execute(ResetColor)?;
@@ -334,34 +334,34 @@ fn execute_step(stdout: &mut io::Stdout, step: &Step) -> bird::Result<bool> {
             stdout.execute(ResetColor)?;
             stdout.flush()?;
 
-            terminal::enable_raw_mode().map_err(|e| bird::Error::Io(io::Error::new(io::ErrorKind::Other, e)))?;
+            terminal::enable_raw_mode().map_err(|e| bird::Error::Io(io::Error::other(e)))?;
         }
         Step::Prompt(text) => {
             // Show prompt and wait for keypress
-            terminal::disable_raw_mode().map_err(|e| bird::Error::Io(io::Error::new(io::ErrorKind::Other, e)))?;
+            terminal::disable_raw_mode().map_err(|e| bird::Error::Io(io::Error::other(e)))?;
             stdout.execute(SetForegroundColor(Color::Green))?;
             print!("\n{}", text);
             stdout.execute(ResetColor)?;
             stdout.flush()?;
-            terminal::enable_raw_mode().map_err(|e| bird::Error::Io(io::Error::new(io::ErrorKind::Other, e)))?;
+            terminal::enable_raw_mode().map_err(|e| bird::Error::Io(io::Error::other(e)))?;
 
             // Wait for Enter or Ctrl+C
             loop {
                 if event::poll(std::time::Duration::from_millis(100))
-                    .map_err(|e| bird::Error::Io(io::Error::new(io::ErrorKind::Other, e)))?
+                    .map_err(|e| bird::Error::Io(io::Error::other(e)))?
                 {
                     if let Event::Key(key_event) = event::read()
-                        .map_err(|e| bird::Error::Io(io::Error::new(io::ErrorKind::Other, e)))?
+                        .map_err(|e| bird::Error::Io(io::Error::other(e)))?
                     {
                         match key_event.code {
                             KeyCode::Enter => break,
                             KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                                terminal::disable_raw_mode().map_err(|e| bird::Error::Io(io::Error::new(io::ErrorKind::Other, e)))?;
+                                terminal::disable_raw_mode().map_err(|e| bird::Error::Io(io::Error::other(e)))?;
                                 println!("\n\nTutorial interrupted. Run 'shq tutorial' to continue later.");
                                 return Ok(false);
                             }
                             KeyCode::Char('q') => {
-                                terminal::disable_raw_mode().map_err(|e| bird::Error::Io(io::Error::new(io::ErrorKind::Other, e)))?;
+                                terminal::disable_raw_mode().map_err(|e| bird::Error::Io(io::Error::other(e)))?;
                                 println!("\n\nTutorial ended. Run 'shq tutorial' to start again.");
                                 return Ok(false);
                             }
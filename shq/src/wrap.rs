@@ -0,0 +1,132 @@
+//! Task runner shims for shq.
+//!
+//! `shq wrap make` writes a `make` shim into the shims directory
+//! (`$BIRD_ROOT/shims`). With that directory early in `$PATH`, running
+//! `make` goes through `shq wrap-exec make ...`, which captures the real
+//! tool like `shq run` would, using per-tool settings from `[wrap.tools]`.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use bird::wrap::{is_shim, MARKER};
+pub use bird::wrap::{is_wrapped, path_without, validate_tool};
+
+/// Quote `s` for a POSIX shell.
+pub fn sh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Generate the shim script for `tool`, invoking `shq` at `shq_path`.
+pub fn shim_script(tool: &str, shq_path: &Path) -> String {
+    format!(
+        "#!/bin/sh\n{MARKER}\n# Installed by `shq wrap {tool}`; remove with `shq wrap --remove {tool}`.\nexec {} wrap-exec {} \"$@\"\n",
+        sh_quote(&shq_path.display().to_string()),
+        sh_quote(tool),
+    )
+}
+
+/// Write the shim for `tool` into `dir`. Returns false if it already exists.
+///
+/// Refuses to overwrite a file in `dir` that isn't a shq shim.
+pub fn install(dir: &Path, tool: &str, shq_path: &Path) -> io::Result<bool> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(tool);
+    let script = shim_script(tool, shq_path);
+
+    if path.exists() {
+        if !is_shim(&path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a shq shim", path.display()),
+            ));
+        }
+        if fs::read_to_string(&path)? == script {
+            return Ok(false);
+        }
+    }
+
+    fs::write(&path, script)?;
+    set_executable(&path)?;
+    Ok(true)
+}
+
+/// Remove the shim for `tool` from `dir`. Returns false if there was none.
+pub fn remove(dir: &Path, tool: &str) -> io::Result<bool> {
+    let path = dir.join(tool);
+    if !is_shim(&path) {
+        return Ok(false);
+    }
+    fs::remove_file(path)?;
+    Ok(true)
+}
+
+/// Names of the tools with shims in `dir`, sorted.
+pub fn list(dir: &Path) -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut tools = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if is_shim(&entry.path()) {
+            tools.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    tools.sort();
+    Ok(tools)
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_shim_script() {
+        let script = shim_script("make", Path::new("/opt/it's/shq"));
+        assert!(script.starts_with("#!/bin/sh\n# shq-wrap\n"));
+        assert!(script.contains(r#"exec '/opt/it'\''s/shq' wrap-exec 'make' "$@""#));
+    }
+
+    #[test]
+    fn test_install_list_remove() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().join("shims");
+        let shq = Path::new("/usr/bin/shq");
+
+        assert!(install(&dir, "make", shq).unwrap());
+        assert!(!install(&dir, "make", shq).unwrap());
+        assert!(install(&dir, "just", shq).unwrap());
+        fs::write(dir.join("other"), "#!/bin/sh\n").unwrap();
+        assert!(install(&dir, "other", shq).is_err());
+
+        assert_eq!(list(&dir).unwrap(), vec!["just", "make"]);
+
+        let path = std::env::join_paths([dir.as_path(), Path::new("/usr/bin")]).unwrap();
+        assert!(is_wrapped(&dir, &path, "make -j8 all"));
+        assert!(!is_wrapped(&dir, &path, "cargo build"));
+        assert!(!is_wrapped(&dir, &OsString::from("/usr/bin"), "make"));
+        assert_eq!(path_without(&path, &dir).unwrap(), OsString::from("/usr/bin"));
+
+        assert!(remove(&dir, "make").unwrap());
+        assert!(!remove(&dir, "make").unwrap());
+        assert!(!remove(&dir, "other").unwrap());
+        assert_eq!(list(&dir).unwrap(), vec!["just"]);
+    }
+}
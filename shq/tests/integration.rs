@@ -72,6 +72,180 @@ fn test_run_captures_exit_code() {
     assert_eq!(output.status.code(), Some(42));
 }
 
+#[test]
+fn test_run_expect() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let run = |cmd: &str, expect: &str| {
+        shq_cmd(tmp.path())
+            .args(["run", "--expect", expect, "-c", cmd])
+            .output()
+            .expect("failed to run command")
+    };
+
+    // Expectations decide the exit status, not the command
+    assert_eq!(run("exit 3", "exit!=0").status.code(), Some(0));
+    let output = run("true", "exit=1");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected exit=1, but exit code was 0"));
+
+    assert_eq!(run("true", "no-events:severity=error").status.code(), Some(0));
+    assert_eq!(run("true", "exit=zero").status.code(), Some(1));
+}
+
+#[test]
+fn test_cron_jobs() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let add = shq_cmd(tmp.path())
+        .args(["cron", "add", "backup-db", "0 3 * * *", "--", "echo", "backup"])
+        .output()
+        .expect("failed to add cron job");
+    assert!(add.status.success());
+
+    let bad = shq_cmd(tmp.path())
+        .args(["cron", "add", "broken", "0 25 * * *", "--", "true"])
+        .output()
+        .expect("failed to run shq");
+    assert!(!bad.status.success());
+
+    // A run now is captured under the job's dimension
+    let run = shq_cmd(tmp.path())
+        .args(["cron", "run", "backup-db"])
+        .output()
+        .expect("failed to run cron job");
+    assert!(run.status.success());
+
+    let status = shq_cmd(tmp.path())
+        .args(["cron", "status"])
+        .output()
+        .expect("failed to show cron status");
+    let stdout = String::from_utf8_lossy(&status.stdout);
+    assert!(stdout.contains("backup-db"), "missing job: {}", stdout);
+    assert!(stdout.contains("0/1"), "missing run: {}", stdout);
+
+    let remove = shq_cmd(tmp.path())
+        .args(["cron", "remove", "backup-db"])
+        .output()
+        .expect("failed to remove cron job");
+    assert!(String::from_utf8_lossy(&remove.stdout).contains("Removed"));
+}
+
+#[test]
+fn test_watch_links_runs() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+    let src = tmp.path().join("src");
+    std::fs::create_dir(&src).unwrap();
+    std::fs::write(src.join("lib.rs"), "fn a() {}\n").unwrap();
+
+    let mut watch = shq_cmd(tmp.path())
+        .args(["watch", "echo 'error: build failed'", "--paths"])
+        .arg(&src)
+        .args(["--interval", "100"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("failed to start watch");
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    std::fs::write(src.join("lib.rs"), "fn b() {}\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    watch.kill().unwrap();
+    watch.wait().unwrap();
+
+    // Both runs share the watch dimension
+    let runs = shq_cmd(tmp.path())
+        .args(["i", "-g", "%dim:watch~=.", "-f", "template={id}"])
+        .output()
+        .expect("failed to list runs");
+    assert_eq!(String::from_utf8_lossy(&runs.stdout).lines().count(), 2);
+
+    let events = shq_cmd(tmp.path())
+        .args(["events", "--latest-of-watch", "--count"])
+        .output()
+        .expect("failed to query events");
+    assert!(events.status.success());
+}
+
+#[test]
+fn test_bench_compares_groups() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let bench = |name: &str| {
+        shq_cmd(tmp.path())
+            .args(["bench", "true", "-n", "3", "--warmup", "1", "--name", name, "-f", "json"])
+            .output()
+            .expect("failed to run bench")
+    };
+    let first = bench("before");
+    assert!(first.status.success());
+    let first: serde_json::Value = serde_json::from_slice(&first.stdout).unwrap();
+    assert_eq!(first["stats"]["runs"], 3);
+    assert!(first.get("baseline").is_none());
+
+    let second: serde_json::Value = serde_json::from_slice(&bench("after").stdout).unwrap();
+    assert_eq!(second["baseline"]["group"], "before");
+}
+
+#[test]
+fn test_ab_stores_runs() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let output = shq_cmd(tmp.path())
+        .args(["ab", "--a", "true", "--b", "false", "-n", "2", "--name", "tf", "-f", "json"])
+        .output()
+        .expect("failed to run ab");
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["a"]["runs"], 2);
+    assert_eq!(json["a"]["succeeded"], 2);
+    assert_eq!(json["b"]["succeeded"], 0);
+
+    // Reviewable later from the stored runs
+    let shown = shq_cmd(tmp.path())
+        .args(["ab", "--show", "tf", "-f", "json"])
+        .output()
+        .expect("failed to show ab");
+    let shown: serde_json::Value = serde_json::from_slice(&shown.stdout).unwrap();
+    assert_eq!(shown["b"]["runs"], 2);
+}
+
+#[test]
+fn test_mount_and_unmount() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+    let archive = TempDir::new().unwrap();
+    init_bird(archive.path());
+
+    let mount = shq_cmd(tmp.path())
+        .args(["mount"])
+        .arg(archive.path())
+        .args(["--as", "old"])
+        .output()
+        .expect("failed to mount");
+    assert!(mount.status.success());
+
+    let list = shq_cmd(tmp.path()).args(["mount"]).output().expect("failed to list mounts");
+    let stdout = String::from_utf8_lossy(&list.stdout);
+    assert!(stdout.contains("remote_old"), "missing mount: {}", stdout);
+
+    let query = shq_cmd(tmp.path())
+        .args(["sql", "SELECT count(*) FROM remote_old.invocations"])
+        .output()
+        .expect("failed to query mount");
+    assert!(query.status.success(), "{}", String::from_utf8_lossy(&query.stderr));
+
+    // Only mounts can be unmounted
+    let own = shq_cmd(tmp.path()).arg("mount").arg(tmp.path()).output().unwrap();
+    assert!(!own.status.success());
+    let unmount = shq_cmd(tmp.path()).args(["unmount", "old"]).output().expect("failed to unmount");
+    assert!(String::from_utf8_lossy(&unmount.stdout).contains("Unmounted"));
+}
+
 #[test]
 fn test_save_from_stdin() {
     let tmp = TempDir::new().unwrap();
@@ -231,6 +405,316 @@ fn test_sql_query() {
     assert!(String::from_utf8_lossy(&output.stdout).contains("sql test"));
 }
 
+#[test]
+fn test_sql_formats() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let sql = "SELECT range AS n, 'a,\"b' AS s, NULL AS x FROM range(2)";
+    let run = |format: &str| {
+        let output = shq_cmd(tmp.path())
+            .args(["sql", sql, "--format", format])
+            .output()
+            .expect("failed to query");
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let json: serde_json::Value = serde_json::from_str(&run("json")).unwrap();
+    assert_eq!(json, serde_json::json!([{"n": 0, "s": "a,\"b", "x": null}, {"n": 1, "s": "a,\"b", "x": null}]));
+
+    let ndjson = run("ndjson");
+    assert_eq!(ndjson.lines().next().unwrap(), r#"{"n":0,"s":"a,\"b","x":null}"#);
+    assert_eq!(ndjson.lines().count(), 2);
+
+    assert_eq!(run("csv"), "n,s,x\n0,\"a,\"\"b\",\n1,\"a,\"\"b\",\n");
+
+    let long = shq_cmd(tmp.path())
+        .args(["sql", "SELECT repeat('x', 80) AS v", "--max-width", "10"])
+        .output()
+        .expect("failed to query");
+    assert!(String::from_utf8_lossy(&long.stdout).contains("xxxxxxx..."));
+}
+
+#[test]
+fn test_schema_catalog() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let output = shq_cmd(tmp.path())
+        .args(["schema", "invocations", "--format", "json"])
+        .output()
+        .expect("failed to run schema");
+
+    assert!(output.status.success(), "{:?}", output);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let names: Vec<&str> = json.as_array().unwrap().iter().map(|e| e["sql_name"].as_str().unwrap()).collect();
+    assert!(names.contains(&"unified.qualified_invocations"), "{:?}", names);
+    assert!(names.contains(&"cwd_invocations()"), "{:?}", names);
+    assert!(names.contains(&"v1.invocations"), "{:?}", names);
+}
+
+#[test]
+fn test_attach_snippet() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let output = shq_cmd(tmp.path())
+        .args(["attach-snippet", "--as", "history"])
+        .output()
+        .expect("failed to run attach-snippet");
+    assert!(output.status.success(), "{:?}", output);
+    let sql = String::from_utf8_lossy(&output.stdout);
+    assert!(sql.contains("AS history (READ_ONLY)"), "{}", sql);
+    assert!(sql.contains("CREATE OR REPLACE TEMPORARY MACRO resolve_storage_ref"), "{}", sql);
+
+    let file = tmp.path().join("attach.sql");
+    let output = shq_cmd(tmp.path())
+        .args(["attach-snippet", "-o", file.to_str().unwrap()])
+        .output()
+        .expect("failed to run attach-snippet");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(std::fs::read_to_string(&file).unwrap().contains("AS bird (READ_ONLY)"));
+
+    let output = shq_cmd(tmp.path()).args(["attach-snippet", "--as", "a-b"]).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_events_mark_wrong() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let output = shq_cmd(tmp.path())
+        .args(["events", "--mark-wrong", "00000000-0000-0000-0000-000000000000"])
+        .output()
+        .expect("failed to run events");
+    assert!(!output.status.success(), "unknown event should fail: {:?}", output);
+
+    let output = shq_cmd(tmp.path()).args(["events", "--corrections"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No corrections recorded."));
+
+    // --note only makes sense with --mark-wrong
+    let output = shq_cmd(tmp.path()).args(["events", "--note", "x"]).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_health() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+    let project = tmp.path().join("project");
+    std::fs::create_dir_all(&project).unwrap();
+
+    let output = shq_cmd(tmp.path()).args(["health", project.to_str().unwrap()]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No builds, tests or lints"));
+
+    // `test` commands count as test runs: one failure, then fixed
+    for cmd in ["test -e missing", "test -d ."] {
+        shq_cmd(tmp.path())
+            .current_dir(&project)
+            .args(["run", "--no-extract", "-c", cmd])
+            .output()
+            .unwrap();
+    }
+
+    let output = shq_cmd(tmp.path()).args(["health", project.to_str().unwrap()]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("test"), "{}", stdout);
+    assert!(stdout.contains("50%"), "{}", stdout);
+}
+
+#[test]
+fn test_time() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    shq_cmd(tmp.path()).args(["run", "--no-extract", "echo", "hi"]).output().unwrap();
+
+    let output = shq_cmd(tmp.path()).args(["time", "--period", "day", "-f", "csv"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("day,root,branch,active_seconds,active_hours,contexts,invocations"));
+    assert_eq!(lines.count(), 1, "{}", stdout);
+
+    let output = shq_cmd(tmp.path()).args(["time", "--period", "fortnight"]).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_digest_markdown_template() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    shq_cmd(tmp.path()).args(["run", "--no-extract", "echo", "hi"]).output().unwrap();
+
+    let output = shq_cmd(tmp.path()).args(["digest", "--markdown"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("# Week of "), "{}", stdout);
+    assert!(stdout.contains("1 commands on 1 days, 0 failed"), "{}", stdout);
+
+    let template = tmp.path().join("standup.md");
+    std::fs::write(&template, "Standup {{start}}..{{end}}\n{{new_failures}}\n").unwrap();
+    let output = shq_cmd(tmp.path())
+        .args(["digest", "--markdown", "--template"])
+        .arg(&template)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("Standup "), "{}", stdout);
+    assert!(stdout.ends_with("\n_Nothing._\n"), "{}", stdout);
+}
+
+#[test]
+fn test_alias_selects_invocation() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    // What the hook does with SHQ_SHOW_ALIAS: pick an id, then save under it
+    let output = shq_cmd(tmp.path()).args(["alias", "--new"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (id, alias) = stdout.trim().split_once(' ').unwrap();
+    assert!(alias.contains('-'), "{}", stdout);
+
+    shq_cmd(tmp.path())
+        .env("BIRD_INVOCATION_UUID", id)
+        .args(["save", "-c", "make test", "-x", "0", "-q"])
+        .output()
+        .unwrap();
+
+    let output = shq_cmd(tmp.path()).args(["I", alias, "--field", "id"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), id);
+
+    let output = shq_cmd(tmp.path()).args(["alias"]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), alias);
+}
+
+#[test]
+fn test_segments_split_shell_session() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    // A shell session with a nested shell, as the hook marks it
+    let mark = |body: &str| format!("\x1b]133;{}\x07", body);
+    let session = [
+        mark("A;aid=1"), "$ ".into(), mark("B;aid=1"), "bash\r\n".into(), mark("C;aid=1"),
+        mark("A;aid=2"), "$ ".into(), mark("B;aid=2"), "make\r\n".into(), mark("C;aid=2"), "built\r\n".into(),
+        mark("D;2;aid=2"), mark("D;0;aid=1"),
+    ]
+    .concat();
+    let file = tmp.path().join("session.txt");
+    std::fs::write(&file, session).unwrap();
+    shq_cmd(tmp.path())
+        .args(["save", "-c", "ssh host", "-x", "0", "-q"])
+        .arg(&file)
+        .output()
+        .unwrap();
+
+    let output = shq_cmd(tmp.path()).args(["segments"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("0     0"), "{}", stdout);
+    assert!(stdout.contains("  bash\n"), "{}", stdout);
+    assert!(stdout.contains("1     2"), "{}", stdout);
+    assert!(stdout.contains("    make\n"), "{}", stdout);
+
+    let output = shq_cmd(tmp.path()).args(["segments", "-n", "2"]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "built\r\n");
+}
+
+#[test]
+fn test_invocations_dedup() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    for cmd in ["echo  dup", "echo other", "echo dup"] {
+        shq_cmd(tmp.path()).args(["save", "-c", cmd, "-x", "0", "-q"]).output().unwrap();
+    }
+
+    let output = shq_cmd(tmp.path()).args(["i", "--dedup", "-f", "table"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let rows: Vec<&str> = stdout.lines().skip(2).collect();
+    assert_eq!(rows.len(), 2, "{}", stdout);
+    assert!(rows[0].starts_with("     2  100%") && rows[0].ends_with("echo dup"), "{}", stdout);
+    assert!(rows[1].ends_with("echo other"), "{}", stdout);
+}
+
+#[test]
+fn test_invocations_columns_and_template() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    for (cmd, exit) in [("make", "2"), ("ls", "0")] {
+        shq_cmd(tmp.path()).args(["save", "-c", cmd, "-x", exit, "-q"]).output().unwrap();
+    }
+
+    let output = shq_cmd(tmp.path()).args(["i", "-g", "--columns", "exit,cmd"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "EXIT  CMD\n0     ls\n2     make\n");
+
+    let output = shq_cmd(tmp.path()).args(["i", "-g", "-f", "template={exit}:{cmd}"]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0:ls\n2:make\n");
+
+    let output = shq_cmd(tmp.path()).args(["i", "--columns", "exit,size"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown column 'size'"));
+}
+
+#[test]
+fn test_invocations_scoped_to_cwd_in_project() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("bird");
+    init_bird(&root);
+    let src = tmp.path().join("proj/src");
+    let other = tmp.path().join("other");
+    std::fs::create_dir_all(tmp.path().join("proj/.bird")).unwrap();
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::create_dir_all(&other).unwrap();
+
+    for (dir, cmd) in [(&src, "in-src"), (&src.join(".."), "in-root"), (&other, "outside")] {
+        shq_cmd(&root).current_dir(dir).args(["save", "-c", cmd, "-x", "0", "-q"]).output().unwrap();
+    }
+    let list = |dir: &std::path::Path, args: &[&str]| {
+        let output = shq_cmd(&root).current_dir(dir).args(["i", "-f", "commands"]).args(args).output().unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout).lines().map(String::from).collect::<Vec<_>>()
+    };
+
+    assert_eq!(list(&src, &[]), ["in-src"]);
+    assert_eq!(list(&src, &["-g"]), ["outside", "in-root", "in-src"]);
+    assert_eq!(list(&src, &["../"]), ["in-root", "in-src"]);
+    // Outside a project history is global
+    assert_eq!(list(&other, &[]).len(), 3);
+}
+
+#[test]
+fn test_typos_from_not_found() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    shq_cmd(tmp.path()).args(["run", "true"]).output().unwrap();
+    for command in ["ture", "ture", "echo secret words"] {
+        shq_cmd(tmp.path()).args(["not-found", command]).output().unwrap();
+    }
+
+    let output = shq_cmd(tmp.path()).args(["typos"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("    2  ture"), "{}", stdout);
+    assert!(stdout.contains("true"), "{}", stdout);
+    // Not a command name, so never recorded
+    assert!(!stdout.contains("secret"), "{}", stdout);
+}
+
 #[test]
 fn test_stats() {
     let tmp = TempDir::new().unwrap();
@@ -250,6 +734,323 @@ fn test_stats() {
     assert!(output.status.success());
     let stats = String::from_utf8_lossy(&output.stdout);
     assert!(stats.contains("Total invocations: 1"));
+    assert!(stats.contains("Top executables:\n  echo "), "{}", stats);
+}
+
+#[test]
+fn test_top_once() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    for cmd in ["echo top-a", "echo top-b", "false"] {
+        shq_cmd(tmp.path())
+            .args(["run", "-c", cmd])
+            .output()
+            .expect("failed to run");
+    }
+
+    let output = shq_cmd(tmp.path())
+        .args(["top", "--once"])
+        .output()
+        .expect("failed to run top");
+
+    assert!(output.status.success());
+    let top = String::from_utf8_lossy(&output.stdout);
+    assert!(top.contains("MOST FREQUENT"));
+    assert!(top.contains("SLOWEST"));
+    assert!(top.contains("MOST FAILING"));
+    assert!(top.contains("echo"));
+    assert!(top.contains("false"));
+}
+
+#[test]
+fn test_heatmap_json() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    shq_cmd(tmp.path())
+        .args(["run", "echo", "heatmap test"])
+        .output()
+        .expect("failed to run");
+
+    let output = shq_cmd(tmp.path())
+        .args(["heatmap", "--format", "json"])
+        .output()
+        .expect("failed to run heatmap");
+
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["total"], 1);
+    assert_eq!(json["days"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_report_html() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    shq_cmd(tmp.path())
+        .args(["run", "sh", "-c", "exit 3"])
+        .output()
+        .expect("failed to run");
+
+    let out_dir = tmp.path().join("report");
+    let output = shq_cmd(tmp.path())
+        .args(["report", "--html", out_dir.to_str().unwrap()])
+        .output()
+        .expect("failed to run report");
+
+    assert!(output.status.success(), "{:?}", output);
+    let html = std::fs::read_to_string(out_dir.join("index.html")).unwrap();
+    assert!(html.contains("<svg"));
+    assert!(html.contains("exit 3"));
+}
+
+#[test]
+fn test_session_list_and_show() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let out_file = tmp.path().join("out.txt");
+    std::fs::write(&out_file, "output\n").unwrap();
+
+    for (cmd, exit) in [("make", "0"), ("make test", "2")] {
+        let output = shq_cmd(tmp.path())
+            .args(["save", out_file.to_str().unwrap(), "-c", cmd, "-x", exit, "--session-id", "zsh-4242"])
+            .output()
+            .expect("failed to save");
+        assert!(output.status.success());
+    }
+
+    let output = shq_cmd(tmp.path())
+        .args(["session", "list"])
+        .output()
+        .expect("failed to list sessions");
+    assert!(output.status.success());
+    let list = String::from_utf8_lossy(&output.stdout);
+    assert!(list.contains("zsh-4242"), "Session not listed: {}", list);
+
+    let output = shq_cmd(tmp.path())
+        .args(["session", "show", "zsh-4242"])
+        .output()
+        .expect("failed to show session");
+    assert!(output.status.success());
+    let timeline = String::from_utf8_lossy(&output.stdout);
+    assert!(timeline.contains("make test"));
+    assert!(timeline.contains("2 commands, 1 failed"));
+    assert!(timeline.contains("[exit 2]"));
+}
+
+#[test]
+fn test_mark_shows_in_session_timeline() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let out_file = tmp.path().join("out.txt");
+    std::fs::write(&out_file, "output\n").unwrap();
+    let save = |cmd: &str| {
+        let output = shq_cmd(tmp.path())
+            .args(["save", out_file.to_str().unwrap(), "-c", cmd, "--session-id", "zsh-77"])
+            .output()
+            .expect("failed to save");
+        assert!(output.status.success());
+    };
+
+    save("make");
+    let output = shq_cmd(tmp.path())
+        .args(["mark", "meeting start", "--session", "zsh-77"])
+        .output()
+        .expect("failed to mark");
+    assert!(output.status.success(), "{:?}", output);
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    save("make test");
+
+    let output = shq_cmd(tmp.path()).args(["session", "show", "zsh-77"]).output().unwrap();
+    let timeline = String::from_utf8_lossy(&output.stdout);
+    let marker = timeline.find("meeting start").expect("marker missing from timeline");
+    assert!(timeline.find("make test").unwrap() > marker, "{}", timeline);
+
+    let output = shq_cmd(tmp.path()).args(["mark", ""]).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_session_name_filter() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let out_file = tmp.path().join("out.txt");
+    std::fs::write(&out_file, "output\n").unwrap();
+
+    for (cmd, session) in [("make auth", "zsh-1"), ("make other", "zsh-2")] {
+        let output = shq_cmd(tmp.path())
+            .args(["save", out_file.to_str().unwrap(), "-c", cmd, "--session-id", session])
+            .output()
+            .expect("failed to save");
+        assert!(output.status.success());
+    }
+
+    let output = shq_cmd(tmp.path())
+        .args(["session", "name", "refactor-auth", "--session", "zsh-1"])
+        .output()
+        .expect("failed to name session");
+    assert!(output.status.success());
+
+    let output = shq_cmd(tmp.path())
+        .args(["invocations", "%session=refactor-auth"])
+        .output()
+        .expect("failed to query invocations");
+    assert!(output.status.success());
+    let list = String::from_utf8_lossy(&output.stdout);
+    assert!(list.contains("make auth"), "Named session missing: {}", list);
+    assert!(!list.contains("make other"), "Other session leaked: {}", list);
+
+    let output = shq_cmd(tmp.path())
+        .args(["session", "close", "--session-id", "refactor-auth", "-q"])
+        .output()
+        .expect("failed to close session");
+    assert!(output.status.success());
+
+    let output = shq_cmd(tmp.path())
+        .args(["session", "show", "refactor-auth"])
+        .output()
+        .expect("failed to show session");
+    let timeline = String::from_utf8_lossy(&output.stdout);
+    assert!(timeline.contains("Session zsh-1 (refactor-auth)"));
+    assert!(timeline.contains("closed "));
+}
+
+#[test]
+fn test_context_list_and_show() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let work = tmp.path().join("work");
+    std::fs::create_dir(&work).unwrap();
+    let out_file = tmp.path().join("out.txt");
+    std::fs::write(&out_file, "output\n").unwrap();
+
+    for (cmd, session) in [("make", "zsh-1"), ("make test", "zsh-2")] {
+        let output = shq_cmd(tmp.path())
+            .current_dir(&work)
+            .args(["save", out_file.to_str().unwrap(), "-c", cmd, "--session-id", session])
+            .output()
+            .expect("failed to save");
+        assert!(output.status.success());
+    }
+
+    let output = shq_cmd(tmp.path())
+        .args(["context", "list", "-f", "json"])
+        .output()
+        .expect("failed to list contexts");
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let contexts = json.as_array().unwrap();
+    assert_eq!(contexts.len(), 1, "Expected one context: {}", json);
+    assert_eq!(contexts[0]["sessions"], 2);
+
+    let output = shq_cmd(tmp.path())
+        .args(["context", "show", "work"])
+        .output()
+        .expect("failed to show context");
+    assert!(output.status.success());
+    let timeline = String::from_utf8_lossy(&output.stdout);
+    assert!(timeline.contains("make test"));
+    assert!(timeline.contains("⇄ zsh-2"), "Session switch not marked: {}", timeline);
+}
+
+#[test]
+fn test_off_pauses_capture() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let out_file = tmp.path().join("out.txt");
+    std::fs::write(&out_file, "output\n").unwrap();
+    let save = |cmd: &str| {
+        let output = shq_cmd(tmp.path())
+            .args(["save", out_file.to_str().unwrap(), "-c", cmd, "-q"])
+            .output()
+            .expect("failed to save");
+        assert!(output.status.success());
+    };
+
+    let output = shq_cmd(tmp.path())
+        .args(["off", "--for", "1h", "-q"])
+        .output()
+        .expect("failed to pause");
+    assert!(output.status.success());
+    assert!(tmp.path().join("state").exists());
+    save("echo while-paused");
+
+    let output = shq_cmd(tmp.path())
+        .args(["on", "-q"])
+        .output()
+        .expect("failed to resume");
+    assert!(output.status.success());
+    save("echo after-resume");
+
+    let output = shq_cmd(tmp.path())
+        .args(["invocations"])
+        .output()
+        .expect("failed to list invocations");
+    let list = String::from_utf8_lossy(&output.stdout);
+    assert!(list.contains("after-resume"), "Capture not resumed: {}", list);
+    assert!(!list.contains("while-paused"), "Captured while paused: {}", list);
+}
+
+#[test]
+fn test_du_reports_usage() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let out_file = tmp.path().join("out.txt");
+    std::fs::write(&out_file, "output\n").unwrap();
+    let output = shq_cmd(tmp.path())
+        .args(["save", out_file.to_str().unwrap(), "-c", "echo hi", "-q"])
+        .output()
+        .expect("failed to save");
+    assert!(output.status.success());
+
+    let output = shq_cmd(tmp.path())
+        .args(["du", "-f", "json"])
+        .output()
+        .expect("failed to run du");
+    assert!(output.status.success(), "du failed: {}", String::from_utf8_lossy(&output.stderr));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(json["total_bytes"].as_u64().unwrap() > 0);
+    assert_eq!(json["partitions"].as_array().unwrap().len(), 1);
+    assert!(json["growth"]["bytes_per_day"].as_u64().unwrap() > 0);
+}
+
+#[test]
+fn test_export_anonymize() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let out_file = tmp.path().join("out.txt");
+    std::fs::write(&out_file, "output\n").unwrap();
+    let output = shq_cmd(tmp.path())
+        .args(["save", out_file.to_str().unwrap(), "-c", "grep hunter2 secrets.txt", "-q"])
+        .output()
+        .expect("failed to save");
+    assert!(output.status.success());
+
+    let export_dir = tmp.path().join("export");
+    let output = shq_cmd(tmp.path())
+        .args(["export", export_dir.to_str().unwrap(), "--anonymize"])
+        .output()
+        .expect("failed to export");
+    assert!(output.status.success(), "export failed: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("1 invocations"));
+
+    let parquet = export_dir.join("invocations.parquet");
+    let output = shq_cmd(tmp.path())
+        .args(["sql", &format!("SELECT cmd FROM '{}'", parquet.display())])
+        .output()
+        .expect("failed to query export");
+    let cmds = String::from_utf8_lossy(&output.stdout);
+    assert!(cmds.contains("grep arg1 arg2"), "Unexpected export: {}", cmds);
+    assert!(!cmds.contains("hunter2"), "Export leaked an argument: {}", cmds);
 }
 
 #[test]
@@ -379,6 +1180,32 @@ fn test_save_default_extraction() {
     assert!(events.status.success());
 }
 
+#[test]
+fn test_events_fail_on() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    let output_file = tmp.path().join("build.txt");
+    std::fs::write(&output_file, "src/main.c:3:5: warning: unused variable 'x'\n").unwrap();
+    shq_cmd(tmp.path())
+        .args(["save", "-c", "gcc -c src/main.c", "-x", "0", "-q"])
+        .arg(&output_file)
+        .output()
+        .expect("failed to save");
+
+    let status = |severity: &str| {
+        shq_cmd(tmp.path())
+            .args(["events", "~1", "--fail-on", severity])
+            .output()
+            .expect("failed to query events")
+            .status
+            .code()
+    };
+    assert_eq!(status("error"), Some(0));
+    assert_eq!(status("warning"), Some(1));
+    assert_eq!(status("note"), Some(1));
+}
+
 #[test]
 fn test_save_with_no_extract_flag() {
     let tmp = TempDir::new().unwrap();
@@ -1218,6 +2045,30 @@ fn test_extract_events_backfill_dry_run() {
     );
 }
 
+#[test]
+fn test_extract_events_explain() {
+    let tmp = TempDir::new().unwrap();
+    init_bird(tmp.path());
+
+    shq_cmd(tmp.path())
+        .args(["run", "--no-extract", "-c", "echo 'main.c:3:5: error: expected x'; echo 'link FAILED'"])
+        .output()
+        .expect("failed to run");
+
+    let output = shq_cmd(tmp.path())
+        .args(["extract-events", "~1", "--explain", "-f", "gcc"])
+        .output()
+        .expect("failed to explain");
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("gcc (given with --format)"), "{}", stdout);
+    assert!(stdout.contains("link FAILED"), "{}", stdout);
+
+    // Explaining extracts nothing
+    let output = shq_cmd(tmp.path()).args(["events", "--count", "~1"]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "0");
+}
+
 #[test]
 fn test_extract_events_backfill_limit() {
     let tmp = TempDir::new().unwrap();